@@ -0,0 +1,94 @@
+//! Microbenchmarks for what runs on every message: parsing IRC lines and
+//! EventSub chat notifications, copying events for each frontend, the emote
+//! picker's grid math and laying out chat lines, so each one's cost can be
+//! followed as the parsers grow.
+//! Run with: cargo bench --bench hot_paths [-- <name filter>]
+//!
+//! Criterion keeps the previous run's results in target/criterion and says
+//! whether each benchmark got faster or slower since.
+
+use choui_the_no_gui_chatbot::irc::{Command, Line};
+use choui_the_no_gui_chatbot::state::{AppEvent, ChatLine, Role};
+use choui_the_no_gui_chatbot::ui::{self, EmoteGrid};
+use choui_the_no_gui_chatbot::ws;
+use criterion::{criterion_group, criterion_main, Criterion};
+use ratatui::layout::Rect;
+use std::hint::black_box;
+use std::sync::Arc;
+
+const PRIVMSG: &str = "@badge-info=subscriber/14;badges=moderator/1,subscriber/12;color=#FF0000;display-name=Viewer;emotes=25:8-12;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=1;room-id=1000;subscriber=1;tmi-sent-ts=1714594445123;turbo=0;user-id=3000;user-type=mod :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #channel :hi @bot Kappa how's the stream going?";
+const CLEARCHAT: &str = r"@ban-duration=600;room-id=1000;target-user-id=4000;tmi-sent-ts=1714594445123 :tmi.twitch.tv CLEARCHAT #channel :troll";
+
+const CHAT_NOTIFICATION: &str = r##"{"metadata":{"message_id":"2","message_type":"notification","message_timestamp":"2024-05-01T20:14:05.123456789Z","subscription_type":"channel.chat.message","subscription_version":"1"},"payload":{"subscription":{"id":"sub-1","type":"channel.chat.message"},"event":{"broadcaster_user_id":"1000","chatter_user_id":"3000","chatter_user_login":"viewer","chatter_user_name":"Viewer","message_id":"msg-1","message":{"text":"hi @bot Kappa cheer100","fragments":[{"type":"text","text":"hi ","cheermote":null,"emote":null,"mention":null},{"type":"mention","text":"@bot","cheermote":null,"emote":null,"mention":{"user_id":"2000","user_login":"bot","user_name":"Bot"}},{"type":"text","text":" ","cheermote":null,"emote":null,"mention":null},{"type":"emote","text":"Kappa","cheermote":null,"emote":{"id":"25","emote_set_id":"0"},"mention":null},{"type":"text","text":" ","cheermote":null,"emote":null,"mention":null},{"type":"cheermote","text":"cheer100","cheermote":{"prefix":"cheer","bits":100,"tier":100},"emote":null,"mention":null}]},"color":"#FF0000","badges":[{"set_id":"moderator","id":"1","info":""},{"set_id":"subscriber","id":"12","info":"14"}],"message_type":"text"}}}"##;
+
+fn irc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("irc");
+    group.bench_function("parse_privmsg", |b| {
+        b.iter(|| Line::parse(black_box(PRIVMSG)))
+    });
+    group.bench_function("parse_and_read_clearchat", |b| {
+        b.iter(|| Line::parse(black_box(CLEARCHAT)).map(|line| Command::from_line(&line)))
+    });
+    group.finish();
+}
+
+fn eventsub(c: &mut Criterion) {
+    c.bench_function("eventsub/chat_notification", |b| {
+        b.iter(|| ws::parse_chat_notification(black_box(CHAT_NOTIFICATION)).unwrap())
+    });
+}
+
+// What the event loop does with every event it hands the overlay
+fn events(c: &mut Criterion) {
+    let message = ws::parse_chat_notification(CHAT_NOTIFICATION).unwrap();
+    let event = AppEvent::ChatMessage(Arc::new(message));
+    c.bench_function("events/clone_chat_message", |b| {
+        b.iter(|| black_box(&event).clone())
+    });
+}
+
+fn emote_grid(c: &mut Criterion) {
+    let grid = EmoteGrid::new(Rect::new(0, 30, 120, 8), 400);
+    let mut group = c.benchmark_group("emote_grid");
+    group.bench_function("visible_cells", |b| {
+        b.iter(|| {
+            let scroll = black_box(5);
+            let first = scroll * grid.items_per_row();
+            (first..)
+                .map_while(|index| grid.cell(index, scroll))
+                .count()
+        })
+    });
+    group.bench_function("click", |b| {
+        b.iter(|| grid.index_at(black_box(57), black_box(33), black_box(5)))
+    });
+    group.finish();
+}
+
+fn format(c: &mut Criterion) {
+    let plain = ChatLine::chat(
+        "viewer".to_string(),
+        "hi everyone, how's the stream going today? first time catching it live".to_string(),
+        Some("#FF0000".to_string()),
+        Role::Viewer,
+        Vec::new(),
+    );
+    let emotes = ChatLine::chat(
+        "moderator".to_string(),
+        "Kappa Kappa PogChamp that was amazing LUL LUL LUL what a play Kappa".to_string(),
+        None,
+        Role::Moderator,
+        ["Kappa", "PogChamp", "LUL"].map(String::from).to_vec(),
+    );
+    let mut group = c.benchmark_group("format");
+    group.bench_function("wrap_plain_line", |b| {
+        b.iter(|| ui::wrapped_height(black_box(&plain), 40))
+    });
+    group.bench_function("wrap_line_with_emotes", |b| {
+        b.iter(|| ui::wrapped_height(black_box(&emotes), 40))
+    });
+    group.finish();
+}
+
+criterion_group!(hot_paths, irc, eventsub, events, emote_grid, format);
+criterion_main!(hot_paths);
@@ -1,13 +1,22 @@
-use crate::config::{Config, LlmProvider};
+use crate::config::{AiParams, Config, LlmProvider, ReplyStyle};
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
 // --- Common ---
 
 pub async fn ask_ai(prompt: &str, config: &Config) -> Result<String> {
+    ask(prompt, &system_prompt(&config.ai_params), config).await
+}
+
+/// Ask with a system prompt of the caller's, for answers that aren't chat
+/// replies in the bot's persona.
+pub async fn ask(prompt: &str, system: &str, config: &Config) -> Result<String> {
+    if config.demo {
+        return Ok(crate::demo::reply().await);
+    }
     match config.llm_provider {
-        LlmProvider::Gemini => ask_gemini(prompt, config).await,
-        LlmProvider::Ollama => ask_ollama(prompt, config).await,
+        LlmProvider::Gemini => ask_gemini(prompt, system, config).await,
+        LlmProvider::Ollama => ask_ollama(prompt, system, config).await,
     }
 }
 
@@ -16,6 +25,17 @@ struct GenerateContentRequest {
     contents: Vec<Content>,
     #[serde(skip_serializing_if = "Option::is_none")]
     system_instruction: Option<Content>,
+    #[serde(rename = "generationConfig")]
+    generation_config: GenerationConfig,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
 }
 
 #[derive(Serialize)]
@@ -49,9 +69,12 @@ struct CandidatePart {
     text: Option<String>,
 }
 
+// Who the bot is unless AI_PERSONA picks someone else
+const DEFAULT_PERSONA: &str =
+    "You are CHOUIBOT, a cheerful, funny, and helpful weasel bot!\nYou love everyone who chats!";
+
 const SYSTEM_PROMPT: &str = r#"
-You are CHOUIBOT, a cheerful, funny, and helpful weasel bot!
-You love everyone who chats!
+{persona}
 
 Context:
 - The input will be in the format: "User <username>: <message>".
@@ -59,8 +82,7 @@ Context:
 Rules:
 1. Be super cheerful and funny! Use emojis!
 2. Answer questions if asked, but keep it light.
-3. Use short sentences. Be punchy.
-4. Keep responses strictly under 400 characters.
+{length}
 5. NEVER reveal personal info about yourself or the streamer (me).
 6. NEVER use quotes around your response.
 7. Don't repeat the user's name at the start. Just talk to them!
@@ -69,31 +91,120 @@ Rules:
 10. NEVER say "Champion" or "Champions".
 "#;
 
+// The persona, and rules 3 and 4 of the system prompt per AI_REPLY_STYLE
+fn system_prompt(params: &AiParams) -> String {
+    let length = match params.style {
+        ReplyStyle::Short => {
+            "3. Use short sentences. Be punchy.\n4. Keep responses strictly under 400 characters."
+        }
+        ReplyStyle::Long => {
+            "3. Give a fuller answer of a few sentences when there's something to explain.\n\
+             4. Keep responses strictly under 480 characters."
+        }
+    };
+    SYSTEM_PROMPT
+        .replace(
+            "{persona}",
+            params.persona_prompt().unwrap_or(DEFAULT_PERSONA),
+        )
+        .replace("{length}", length)
+}
+
+/// Checks the provider is reachable and knows the configured model, without
+/// spending a generation. Returns a short description for `choui check`.
+pub async fn ping(config: &Config) -> Result<String> {
+    let client = reqwest::Client::new();
+    match config.llm_provider {
+        LlmProvider::Ollama => {
+            let url = format!("{}/api/tags", config.ollama_host);
+            let resp = client.get(&url).send().await?;
+            if !resp.status().is_success() {
+                bail!("Ollama API error ({})", resp.status());
+            }
+            let tags: OllamaTags = resp.json().await?;
+            // "llama3.2:1b" is listed as is, "llama3" as "llama3:latest"
+            let wanted = &config.ollama_model;
+            let found = tags
+                .models
+                .iter()
+                .any(|model| model.name == *wanted || model.name == format!("{}:latest", wanted));
+            if !found {
+                bail!(
+                    "Ollama at {} doesn't have {} (ollama pull {})",
+                    config.ollama_host,
+                    wanted,
+                    wanted
+                );
+            }
+            Ok(format!("Ollama at {} has {}", config.ollama_host, wanted))
+        }
+        LlmProvider::Gemini => {
+            let api_key = config
+                .gemini_api_key
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("GEMINI_API_KEY not set"))?;
+            let url = format!(
+                "{}/models/{}?key={}",
+                config.endpoints.gemini, config.gemini_model, api_key
+            );
+            let resp = client.get(&url).send().await?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let text = resp.text().await?;
+                bail!("Gemini API error ({}): {}", status, text);
+            }
+            Ok(format!("Gemini model {} is available", config.gemini_model))
+        }
+    }
+}
+
 // --- Ollama ---
 
+#[derive(Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
 #[derive(Serialize)]
 struct OllamaRequest {
     model: String,
     prompt: String,
     system: String,
     stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
 }
 
 #[derive(Deserialize)]
 struct OllamaResponse {
     response: String,
-    done: bool,
 }
 
-async fn ask_ollama(prompt: &str, config: &Config) -> Result<String> {
+async fn ask_ollama(prompt: &str, system: &str, config: &Config) -> Result<String> {
     let client = reqwest::Client::new();
     let url = format!("{}/api/generate", config.ollama_host);
 
     let request_body = OllamaRequest {
         model: config.ollama_model.clone(),
         prompt: prompt.to_string(),
-        system: SYSTEM_PROMPT.to_string(),
+        system: system.to_string(),
         stream: false,
+        options: OllamaOptions {
+            temperature: config.ai_params.temperature,
+            num_predict: config.ai_params.max_tokens,
+        },
     };
 
     let resp = client.post(&url).json(&request_body).send().await?;
@@ -115,7 +226,7 @@ async fn ask_ollama(prompt: &str, config: &Config) -> Result<String> {
 
 // --- Gemini ---
 
-async fn ask_gemini(prompt: &str, config: &Config) -> Result<String> {
+async fn ask_gemini(prompt: &str, system: &str, config: &Config) -> Result<String> {
     let api_key = config
         .gemini_api_key
         .as_ref()
@@ -123,8 +234,8 @@ async fn ask_gemini(prompt: &str, config: &Config) -> Result<String> {
 
     let client = reqwest::Client::new();
     let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-        config.gemini_model, api_key
+        "{}/models/{}:generateContent?key={}",
+        config.endpoints.gemini, config.gemini_model, api_key
     );
 
     let request_body = GenerateContentRequest {
@@ -137,9 +248,13 @@ async fn ask_gemini(prompt: &str, config: &Config) -> Result<String> {
         system_instruction: Some(Content {
             role: "user".to_string(),
             parts: vec![Part {
-                text: SYSTEM_PROMPT.to_string(),
+                text: system.to_string(),
             }],
         }),
+        generation_config: GenerationConfig {
+            temperature: config.ai_params.temperature,
+            max_output_tokens: config.ai_params.max_tokens,
+        },
     };
 
     let resp = client.post(&url).json(&request_body).send().await?;
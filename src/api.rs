@@ -0,0 +1,427 @@
+use crate::commands::{SlashCommand, TtsControl};
+use crate::config::{env_flag, var};
+use crate::state::StreamAlert;
+use anyhow::{bail, Context, Result};
+use axum::body::Bytes;
+use axum::extract::ws::rejection::WebSocketUpgradeRejection;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{any, get};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+
+// A small HTTP API for driving the bot from elsewhere: Stream Deck buttons,
+// scripts, phone shortcuts. Off unless API_ENABLED is set, and every request
+// needs the API_TOKEN as "Authorization: Bearer <token>". It listens on
+// API_ADDRESS, this machine only by default.
+//
+//   GET  /api/status   connections, stream, TTS, AI queue, persona
+//   POST /api/chat     {"message": "..."}         say something in chat
+//   POST /api/alert    {"kind": "follow", "user": "..."}
+//                      show an alert on the overlay (follow, sub, gift,
+//                      raid, cheer; "count", "viewers" or "bits" for the last
+//                      three)
+//   POST /api/tts      {"action": "on"}           on, off, toggle, skip, clear
+//   POST /api/persona  {"name": "pirate"}         null for the built-in one
+//   POST /api/message  {"name": "brb"}            say one of API_MESSAGES
+//   POST /api/sound    {"name": "airhorn"}        play one of API_SOUNDS
+//   GET  /api/feed     the latest chat, moderation and AI requests
+//   POST /api/moderate {"action": "timeout", "user": "...", "seconds": 600,
+//                      "reason": "..."}           or "ban"
+//   POST /api/ai       {"id": 3, "action": "approve"}  reject, retry
+//   POST /api/settings {"timers": true, "ai_approval": false}  either or both
+//
+// GET / is the web dashboard, a page that asks for the token and then uses
+// the endpoints above.
+//
+// GET /ws is the same as a WebSocket, for Stream Deck plugins and Touch
+// Portal that keep one connection open. The token goes in the Authorization
+// header or, since browsers can't set one, as ?token=. Each text frame is the
+// endpoint's body plus its name, {"command": "tts", "action": "toggle"}, and
+// is answered with {"ok": true, "result": ...} or {"ok": false, "error": ...},
+// with the frame's "id" copied in if it had one.
+
+const DASHBOARD: &str = include_str!("../assets/dashboard.html");
+
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub address: String,
+    pub token: String,
+    // Canned chat messages and sound files for buttons, by name
+    pub messages: Vec<(String, String)>,
+    pub sounds: Vec<(String, String)>,
+}
+
+impl ApiSettings {
+    // API_ENABLED, API_ADDRESS, API_TOKEN, API_MESSAGES, API_SOUNDS
+    pub fn from_env() -> Result<Self> {
+        let enabled = env_flag("API_ENABLED", false)?;
+        let token = var("API_TOKEN").unwrap_or_default().trim().to_string();
+        if enabled && token.is_empty() {
+            bail!("API_ENABLED needs an API_TOKEN for clients to send");
+        }
+        Ok(Self {
+            enabled,
+            address: var("API_ADDRESS")
+                .map(|address| address.trim().to_string())
+                .ok()
+                .filter(|address| !address.is_empty())
+                .unwrap_or_else(|| "127.0.0.1:8765".to_string()),
+            token,
+            messages: named("API_MESSAGES", "brb => Be right back!")?,
+            sounds: named("API_SOUNDS", "airhorn => sounds/airhorn.mp3")?,
+        })
+    }
+}
+
+// "<name> => <value>" entries, separated by |
+fn named(name: &str, example: &str) -> Result<Vec<(String, String)>> {
+    var(name)
+        .unwrap_or_default()
+        .split('|')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| match entry.split_once("=>") {
+            Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+                Ok((key.trim().to_lowercase(), value.trim().to_string()))
+            }
+            _ => bail!(
+                "{} entries look like \"{}\", got '{}'",
+                name,
+                example,
+                entry
+            ),
+        })
+        .collect()
+}
+
+/// What a client asked for, answered by the event loop.
+#[derive(Debug, Clone)]
+pub enum ApiRequest {
+    Status,
+    Say(String),
+    Alert(StreamAlert),
+    Tts(TtsControl),
+    // None for the built-in persona
+    Persona(Option<String>),
+    // One of API_MESSAGES or API_SOUNDS, by name
+    Message(String),
+    Sound(String),
+    Feed,
+    // A /timeout or /ban
+    Moderate(SlashCommand),
+    Ai {
+        id: u64,
+        action: AiAction,
+    },
+    // None leaves a setting as it is
+    Settings {
+        timers: Option<bool>,
+        ai_approval: Option<bool>,
+    },
+}
+
+/// What to do with a request on the AI tab, like its keys do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiAction {
+    Approve,
+    // Rejects a reply awaiting approval, or cancels one still being written
+    Reject,
+    Retry,
+}
+
+/// A request and where its answer goes: JSON for the client, or why it
+/// couldn't be done.
+#[derive(Debug)]
+pub struct ApiCall {
+    pub request: ApiRequest,
+    pub reply: oneshot::Sender<Result<Value, String>>,
+}
+
+/// Accept clients until the listener fails.
+pub async fn serve(settings: ApiSettings, calls: mpsc::UnboundedSender<ApiCall>) -> Result<()> {
+    let listener = TcpListener::bind(&settings.address)
+        .await
+        .with_context(|| format!("Failed to listen on {}", settings.address))?;
+    let api = Api {
+        token: settings.token,
+        calls,
+    };
+    let router = Router::new()
+        .route("/", get(Html(DASHBOARD)))
+        .route("/ws", get(websocket))
+        .route("/api/{endpoint}", any(endpoint))
+        .fallback(|| async { error(StatusCode::NOT_FOUND, "No such endpoint") })
+        .layer(DefaultBodyLimit::max(MAX_BODY))
+        .with_state(api);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+#[derive(Clone)]
+struct Api {
+    token: String,
+    calls: mpsc::UnboundedSender<ApiCall>,
+}
+
+// ?token=, for WebSocket clients that can't send headers
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+impl Api {
+    fn authorized(&self, headers: &HeaderMap, query: &TokenQuery) -> bool {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or(query.token.as_deref())
+            .is_some_and(|given| same_token(given.trim(), &self.token))
+    }
+}
+
+fn error(status: StatusCode, error: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": error.into() }))).into_response()
+}
+
+// Compares every byte, so how long it takes doesn't give away the token
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn endpoint(
+    State(api): State<Api>,
+    Path(endpoint): Path<String>,
+    Query(query): Query<TokenQuery>,
+    method: Method,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !api.authorized(&headers, &query) {
+        return error(StatusCode::UNAUTHORIZED, "Missing or wrong API token");
+    }
+    let expected = match endpoint.as_str() {
+        "status" | "feed" => Method::GET,
+        "chat" | "alert" | "tts" | "persona" | "message" | "sound" | "moderate" | "ai"
+        | "settings" => Method::POST,
+        _ => return error(StatusCode::NOT_FOUND, "No such endpoint"),
+    };
+    if method != expected {
+        let message = format!("Use {} for /api/{}", expected, endpoint);
+        return error(StatusCode::METHOD_NOT_ALLOWED, message);
+    }
+    let body = match expected {
+        Method::GET => Value::Null,
+        _ => match serde_json::from_slice(&body) {
+            Ok(body) => body,
+            Err(_) => return error(StatusCode::BAD_REQUEST, "Body must be JSON"),
+        },
+    };
+    let request = match parse(&endpoint, &body) {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("{:#}", e)),
+    };
+    match call(&api.calls, request).await {
+        Ok(body) => Json(body).into_response(),
+        Err((status, e)) => error(status, e),
+    }
+}
+
+// Hand the request to the event loop and wait for its answer, or the status
+// and reason it couldn't be done
+async fn call(
+    calls: &mpsc::UnboundedSender<ApiCall>,
+    request: ApiRequest,
+) -> Result<Value, (StatusCode, String)> {
+    let shutting_down = || {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The bot is shutting down".to_string(),
+        )
+    };
+    let (reply, answer) = oneshot::channel();
+    if calls.send(ApiCall { request, reply }).is_err() {
+        return Err(shutting_down());
+    }
+    match answer.await {
+        Ok(Ok(body)) => Ok(body),
+        Ok(Err(e)) => Err((StatusCode::BAD_REQUEST, e)),
+        Err(_) => Err(shutting_down()),
+    }
+}
+
+async fn websocket(
+    State(api): State<Api>,
+    Query(query): Query<TokenQuery>,
+    headers: HeaderMap,
+    upgrade: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Response {
+    let Ok(upgrade) = upgrade else {
+        return error(StatusCode::BAD_REQUEST, "/ws only speaks WebSocket");
+    };
+    if !api.authorized(&headers, &query) {
+        return error(StatusCode::UNAUTHORIZED, "Missing or wrong API token");
+    }
+    upgrade.on_upgrade(move |socket| async move {
+        // The client is gone when this fails; nothing to tell it
+        let _ = control(socket, &api.calls).await;
+    })
+}
+
+// The WebSocket at /ws: every frame is a request, answered in turn until the
+// client hangs up
+async fn control(mut socket: WebSocket, calls: &mpsc::UnboundedSender<ApiCall>) -> Result<()> {
+    while let Some(frame) = socket.recv().await {
+        let text = match frame? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            // Pings are answered by the socket itself
+            _ => continue,
+        };
+        let answer = answer(&text, calls).await;
+        socket
+            .send(Message::Text(answer.to_string().into()))
+            .await?;
+    }
+    Ok(())
+}
+
+async fn answer(text: &str, calls: &mpsc::UnboundedSender<ApiCall>) -> Value {
+    let frame: Value = serde_json::from_str(text).unwrap_or_default();
+    let result = match frame.get("command").and_then(Value::as_str) {
+        Some(command) => match parse(command, &frame) {
+            Ok(request) => call(calls, request).await.map_err(|(_, e)| e),
+            Err(e) => Err(format!("{:#}", e)),
+        },
+        None => Err("Frames are JSON objects with a \"command\"".to_string()),
+    };
+    let mut answer = match result {
+        Ok(result) => json!({ "ok": true, "result": result }),
+        Err(error) => json!({ "ok": false, "error": error }),
+    };
+    if let Some(id) = frame.get("id") {
+        answer["id"] = id.clone();
+    }
+    answer
+}
+
+// A request to one of the endpoints (the part after /api/) with its body
+fn parse(endpoint: &str, body: &Value) -> Result<ApiRequest> {
+    let text = |key: &str| body.get(key).and_then(Value::as_str).map(str::trim);
+    let number = |key: &str, default: u64| {
+        body.get(key)
+            .and_then(Value::as_u64)
+            .unwrap_or(default)
+            .min(u32::MAX as u64) as u32
+    };
+    Ok(match endpoint {
+        "status" => ApiRequest::Status,
+        "feed" => ApiRequest::Feed,
+        "chat" => match text("message") {
+            Some(message) if !message.is_empty() => ApiRequest::Say(message.to_string()),
+            _ => bail!("Give the \"message\" to send"),
+        },
+        "alert" => {
+            let user = text("user").unwrap_or("someone").to_string();
+            let tier = "1000".to_string();
+            ApiRequest::Alert(match text("kind").unwrap_or_default() {
+                "follow" => StreamAlert::Follow { user },
+                "sub" => StreamAlert::Subscribe { user, tier },
+                "gift" => StreamAlert::GiftSub {
+                    user: Some(user),
+                    count: number("count", 1),
+                    tier,
+                },
+                "raid" => StreamAlert::Raid {
+                    from: user,
+                    from_id: String::new(),
+                    viewers: number("viewers", 10),
+                },
+                "cheer" => StreamAlert::Cheer {
+                    user: Some(user),
+                    bits: number("bits", 100),
+                    message: String::new(),
+                },
+                other => bail!(
+                    "\"kind\" must be follow, sub, gift, raid or cheer, got '{}'",
+                    other
+                ),
+            })
+        }
+        "tts" => ApiRequest::Tts(match text("action").unwrap_or_default() {
+            "on" => TtsControl::On,
+            "off" => TtsControl::Off,
+            "toggle" => TtsControl::Toggle,
+            "skip" => TtsControl::Skip,
+            "clear" => TtsControl::Clear,
+            other => bail!(
+                "\"action\" must be on, off, toggle, skip or clear, got '{}'",
+                other
+            ),
+        }),
+        "persona" => ApiRequest::Persona(
+            text("name")
+                .filter(|name| !name.is_empty())
+                .map(String::from),
+        ),
+        "moderate" => {
+            let user = match text("user") {
+                Some(user) if !user.is_empty() => user.trim_start_matches('@').to_string(),
+                _ => bail!("Give the \"user\""),
+            };
+            let reason = text("reason").unwrap_or_default().to_string();
+            ApiRequest::Moderate(match text("action").unwrap_or_default() {
+                "timeout" => SlashCommand::Timeout {
+                    user,
+                    seconds: number("seconds", 600).clamp(1, 1_209_600),
+                    reason,
+                },
+                "ban" => SlashCommand::Ban { user, reason },
+                other => bail!("\"action\" must be timeout or ban, got '{}'", other),
+            })
+        }
+        "ai" => ApiRequest::Ai {
+            id: body
+                .get("id")
+                .and_then(Value::as_u64)
+                .context("Give the request's \"id\"")?,
+            action: match text("action").unwrap_or_default() {
+                "approve" => AiAction::Approve,
+                "reject" => AiAction::Reject,
+                "retry" => AiAction::Retry,
+                other => bail!(
+                    "\"action\" must be approve, reject or retry, got '{}'",
+                    other
+                ),
+            },
+        },
+        "message" | "sound" => {
+            let name = match text("name") {
+                Some(name) if !name.is_empty() => name.to_lowercase(),
+                _ => bail!("Give the \"name\""),
+            };
+            match endpoint {
+                "message" => ApiRequest::Message(name),
+                _ => ApiRequest::Sound(name),
+            }
+        }
+        "settings" => ApiRequest::Settings {
+            timers: body.get("timers").and_then(Value::as_bool),
+            ai_approval: body.get("ai_approval").and_then(Value::as_bool),
+        },
+        other => bail!("No such command '{}'", other),
+    })
+}
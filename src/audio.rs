@@ -1,64 +1,404 @@
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
 use std::io::BufReader;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
+use std::time::Duration;
 
-pub fn play_sound(path: String) {
-    thread::spawn(move || {
-        // rodio requires the OutputStream to stay alive while playing.
-        // It plays on the thread it's created on (mostly), or at least the stream handle must be kept.
-        let (_stream, stream_handle) = match rodio::OutputStream::try_default() {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("Audio Error: Failed to get output stream: {}", e);
-                return;
-            }
-        };
+use crate::config::{VolumeChannel, Volumes};
+use crate::state::AppEvent;
+use crate::tts::{Playback, Speaker, SpeechKind, Tts, VoiceSettings, DUCKED_LEVEL};
+use tokio::sync::mpsc;
 
-        let file = match File::open(&path) {
-            Ok(f) => f,
-            Err(e) => {
-                eprintln!("Audio Error: Failed to open file '{}': {}", path, e);
-                return;
-            }
-        };
+struct Utterance {
+    kind: SpeechKind,
+    // Read before normal messages, never merged or dropped
+    priority: bool,
+    speaker: String,
+    // What the overlay shows while it's read
+    shown: String,
+    spoken: String,
+}
+
+// One worker thread reads queued messages in order, so busy chat doesn't
+// talk over itself. When the queue is full the oldest waiting message is
+// dropped to keep up with chat, and a speaker's messages that are still
+// waiting get merged into one reading. Sound effects are turned down while
+// a message is read, and alert sounds turn the reading down in turn.
+// A silent queue (for `bench`) goes through the same motions without an
+// engine, taking as long as reading aloud would.
+pub struct TtsQueue {
+    pending: Arc<(Mutex<VecDeque<Utterance>>, Condvar)>,
+    playback: Arc<Playback>,
+    // Percent
+    volume: Arc<AtomicU8>,
+    settings: Arc<Mutex<VoiceSettings>>,
+    max_len: usize,
+    tx: mpsc::UnboundedSender<AppEvent>,
+}
 
-        let source = match rodio::Decoder::new(BufReader::new(file)) {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("Audio Error: Failed to decode audio: {}", e);
-                return;
+// About 180 words a minute, or until skipped
+fn pretend_to_read(text: &str, playback: &Playback) {
+    let words = text.split_whitespace().count() as u32;
+    let until = std::time::Instant::now() + Duration::from_millis(330) * words;
+    while std::time::Instant::now() < until && !playback.skip.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+impl TtsQueue {
+    pub fn start(
+        tx: mpsc::UnboundedSender<AppEvent>,
+        tts: Tts,
+        silent: bool,
+        max_len: usize,
+        volume: u8,
+        sounds: AudioEngine,
+    ) -> Self {
+        let pending = Arc::new((Mutex::new(VecDeque::<Utterance>::new()), Condvar::new()));
+        let playback = Arc::new(Playback::default());
+        sounds.duck_during_alerts(playback.clone());
+        let worker_pending = pending.clone();
+        let worker_playback = playback.clone();
+        let volume = Arc::new(AtomicU8::new(volume));
+        let worker_volume = volume.clone();
+        let settings = Arc::new(Mutex::new(tts.settings.clone()));
+        let worker_settings = settings.clone();
+        let worker_tx = tx.clone();
+        thread::spawn(move || {
+            let mut speaker = match Speaker::new(tts) {
+                Ok(speaker) => Some(speaker).filter(|_| !silent),
+                Err(e) => {
+                    let _ = worker_tx.send(AppEvent::Error(format!("TTS unavailable: {:#}", e)));
+                    return;
+                }
+            };
+            let (queue, ready) = &*worker_pending;
+            // TtsStarted/TtsFinished bracket each reading for the overlay
+            for id in 1.. {
+                let next = {
+                    let mut queue = queue.lock().unwrap();
+                    loop {
+                        match queue.pop_front() {
+                            Some(next) => break next,
+                            None => queue = ready.wait(queue).unwrap(),
+                        }
+                    }
+                };
+                worker_playback.skip.store(false, Ordering::Relaxed);
+                // Detected on the message alone, without the "user says:" part
+                let language = speaker
+                    .as_ref()
+                    .and_then(|speaker| speaker.language_of(&next.shown));
+                let _ = worker_tx.send(AppEvent::TtsStarted {
+                    id,
+                    speaker: next.speaker,
+                    text: next.shown,
+                });
+                let volume = worker_volume.load(Ordering::Relaxed) as f32 / 100.0;
+                let settings = worker_settings.lock().unwrap().clone();
+                sounds.speaking(true);
+                let spoken = match speaker.as_mut() {
+                    Some(speaker) => speaker.speak(
+                        next.kind,
+                        &next.spoken,
+                        language,
+                        &worker_playback,
+                        volume,
+                        &settings,
+                    ),
+                    None => {
+                        pretend_to_read(&next.spoken, &worker_playback);
+                        Ok(())
+                    }
+                };
+                if let Err(e) = spoken {
+                    let _ = worker_tx.send(AppEvent::Debug(format!("TTS failed: {:#}", e)));
+                }
+                sounds.speaking(false);
+                let _ = worker_tx.send(AppEvent::TtsFinished(id));
             }
-        };
+        });
+        Self {
+            pending,
+            playback,
+            volume,
+            settings,
+            max_len: max_len.max(1),
+            tx,
+        }
+    }
+
+    // Queue `spoken` to be read aloud, showing `speaker: shown` in the overlay
+    pub fn speak(&self, kind: SpeechKind, speaker: &str, shown: &str, spoken: String) {
+        let (queue, ready) = &*self.pending;
+        let mut queue = queue.lock().unwrap();
 
-        if let Err(e) = stream_handle.play_raw(rodio::Source::convert_samples(source)) {
-            eprintln!("Audio Error: Failed to play sound: {}", e);
+        if let Some(last) = queue
+            .back_mut()
+            .filter(|last| !last.priority && last.kind == kind && last.speaker == speaker)
+        {
+            last.shown.push_str(" / ");
+            last.shown.push_str(shown);
+            last.spoken.push_str(". ");
+            last.spoken.push_str(shown);
             return;
         }
+        if queue.len() >= self.max_len {
+            let oldest = queue.iter().position(|waiting| !waiting.priority);
+            if let Some(dropped) = oldest.and_then(|i| queue.remove(i)) {
+                let _ = self.tx.send(AppEvent::Debug(format!(
+                    "TTS queue full, skipped {}: {}",
+                    dropped.speaker, dropped.shown
+                )));
+            }
+        }
+        queue.push_back(Utterance {
+            kind,
+            priority: false,
+            speaker: speaker.to_string(),
+            shown: shown.to_string(),
+            spoken,
+        });
+        ready.notify_one();
+    }
+
+    // Like speak(), but jumps ahead of everything except earlier priority messages
+    pub fn speak_next(&self, kind: SpeechKind, speaker: &str, shown: &str, spoken: String) {
+        let (queue, ready) = &*self.pending;
+        let mut queue = queue.lock().unwrap();
+        let position = queue
+            .iter()
+            .position(|waiting| !waiting.priority)
+            .unwrap_or(queue.len());
+        queue.insert(
+            position,
+            Utterance {
+                kind,
+                priority: true,
+                speaker: speaker.to_string(),
+                shown: shown.to_string(),
+                spoken,
+            },
+        );
+        ready.notify_one();
+    }
+
+    // Takes effect from the next message
+    pub fn set_volume(&self, percent: u8) {
+        self.volume.store(percent, Ordering::Relaxed);
+    }
+
+    // Rate, pitch and voice; takes effect from the next message
+    pub fn set_settings(&self, settings: VoiceSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
 
-        // Keep thread alive until finished?
-        // rodio's play_raw is non-blocking on the handle, but we need to keep _stream alive.
-        // A simple way is to sleep, or use Sink which controls playback better.
-        // Let's use Sink.
+    // Stop the message being read right now; the queue carries on
+    pub fn skip(&self) {
+        self.playback.skip.store(true, Ordering::Relaxed);
+    }
+
+    // Messages waiting to be read, not counting the one being read
+    pub fn waiting(&self) -> usize {
+        self.pending.0.lock().unwrap().len()
+    }
+
+    /// Drop every waiting message. Returns how many there were.
+    pub fn clear(&self) -> usize {
+        let mut queue = self.pending.0.lock().unwrap();
+        let dropped = queue.len();
+        queue.clear();
+        dropped
+    }
+}
+
+// Sound effects the engine can play
+#[derive(Debug, Clone)]
+pub enum Sound {
+    File(String),
+    // Short, quiet two-note chime for mentions
+    Chime,
+    // Rising arpeggio for a completed follower/sub goal
+    Fanfare,
+}
+
+enum Command {
+    Play(Sound, VolumeChannel),
+    Stop,
+    SetVolumes(Volumes),
+    // TTS started or finished a reading
+    Speaking(bool),
+    // The reading to duck while alert sounds play
+    DuckDuringAlerts(Arc<Playback>),
+}
+
+// Handle to the audio thread, which owns the one output stream every sound
+// effect plays through. Decoded files are cached, so frequent sounds (joins)
+// aren't read and decoded again each time.
+#[derive(Clone)]
+pub struct AudioEngine {
+    commands: std::sync::mpsc::Sender<Command>,
+}
 
-        let sink = match rodio::Sink::try_new(&stream_handle) {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("Audio Error: Failed to create sink: {}", e);
-                return;
+// Past this many cached files the cache starts over
+const MAX_CACHED_SOUNDS: usize = 32;
+
+type Clip = rodio::source::Buffered<rodio::Decoder<BufReader<File>>>;
+
+impl AudioEngine {
+    pub fn start(tx: mpsc::UnboundedSender<AppEvent>, volumes: Volumes) -> Self {
+        let (commands, receiver) = std::sync::mpsc::channel();
+        // OutputStream isn't Send, so it's created on the thread that keeps it
+        thread::spawn(move || run_engine(receiver, tx, volumes));
+        Self { commands }
+    }
+
+    pub fn play(&self, sound: Sound, channel: VolumeChannel) {
+        let _ = self.commands.send(Command::Play(sound, channel));
+    }
+
+    // Cut off every sound effect that's still playing
+    pub fn stop(&self) {
+        let _ = self.commands.send(Command::Stop);
+    }
+
+    // Applies to sounds already playing too
+    pub fn set_volumes(&self, volumes: Volumes) {
+        let _ = self.commands.send(Command::SetVolumes(volumes));
+    }
+
+    // Sound effects other than alerts are ducked while TTS is speaking
+    fn speaking(&self, speaking: bool) {
+        let _ = self.commands.send(Command::Speaking(speaking));
+    }
+
+    fn duck_during_alerts(&self, playback: Arc<Playback>) {
+        let _ = self.commands.send(Command::DuckDuringAlerts(playback));
+    }
+}
+
+// How often the engine checks for finished sounds while any are playing
+const FINISHED_POLL: Duration = Duration::from_millis(100);
+
+fn run_engine(
+    commands: std::sync::mpsc::Receiver<Command>,
+    tx: mpsc::UnboundedSender<AppEvent>,
+    mut volumes: Volumes,
+) {
+    let (_stream, handle) = match rodio::OutputStream::try_default() {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = tx.send(AppEvent::Error(format!("No audio output: {}", e)));
+            return;
+        }
+    };
+    // Alerts are what the stream wants heard, so TTS doesn't duck them
+    let level = |volumes: &Volumes, channel, speaking: bool| {
+        let ducked = speaking && channel != VolumeChannel::Alert;
+        volumes.get(channel) as f32 / 100.0 * if ducked { DUCKED_LEVEL } else { 1.0 }
+    };
+    let mut speaking = false;
+    let mut speech: Option<Arc<Playback>> = None;
+    let mut cache: HashMap<String, Clip> = HashMap::new();
+    // Sinks stop when dropped, so they're kept until their sound ends
+    let mut playing: Vec<(VolumeChannel, rodio::Sink)> = Vec::new();
+
+    loop {
+        // Nothing to watch while silent; otherwise wake up to notice sounds ending
+        let command = if playing.is_empty() {
+            match commands.recv() {
+                Ok(command) => Some(command),
+                Err(_) => break,
+            }
+        } else {
+            match commands.recv_timeout(FINISHED_POLL) {
+                Ok(command) => Some(command),
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => None,
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
             }
         };
+        playing.retain(|(_, sink)| !sink.empty());
 
-        // Re-open file/decode for Sink (simpler)
-        let file = match File::open(&path) {
-            Ok(f) => f,
-            Err(_) => return, // Already logged
-        };
-        let source = match rodio::Decoder::new(BufReader::new(file)) {
-            Ok(s) => s,
-            Err(_) => return,
-        };
+        match command {
+            None => {}
+            Some(Command::Play(sound, channel)) => {
+                let sink = match rodio::Sink::try_new(&handle) {
+                    Ok(sink) => sink,
+                    Err(e) => {
+                        let _ = tx.send(AppEvent::Debug(format!("Audio sink failed: {}", e)));
+                        continue;
+                    }
+                };
+                sink.set_volume(level(&volumes, channel, speaking));
+                match sound {
+                    Sound::File(path) => match cached_clip(&mut cache, &path) {
+                        Ok(clip) => sink.append(clip),
+                        Err(e) => {
+                            let _ =
+                                tx.send(AppEvent::Debug(format!("Can't play {}: {:#}", path, e)));
+                            continue;
+                        }
+                    },
+                    Sound::Chime => {
+                        for freq in [880.0, 1320.0] {
+                            sink.append(tone(freq, 90, 0.15));
+                        }
+                    }
+                    // C major up to the octave, holding the last note
+                    Sound::Fanfare => {
+                        for (freq, millis) in
+                            [(523.0, 120), (659.0, 120), (784.0, 120), (1047.0, 400)]
+                        {
+                            sink.append(tone(freq, millis, 0.2));
+                        }
+                    }
+                }
+                playing.push((channel, sink));
+            }
+            Some(Command::Stop) => {
+                for (_, sink) in playing.drain(..) {
+                    sink.stop();
+                }
+            }
+            Some(Command::SetVolumes(new)) => volumes = new,
+            Some(Command::Speaking(now)) => speaking = now,
+            Some(Command::DuckDuringAlerts(playback)) => speech = Some(playback),
+        }
+
+        for (channel, sink) in &playing {
+            sink.set_volume(level(&volumes, *channel, speaking));
+        }
+        if let Some(speech) = &speech {
+            let alert_playing = playing
+                .iter()
+                .any(|(channel, _)| *channel == VolumeChannel::Alert);
+            speech.ducked.store(alert_playing, Ordering::Relaxed);
+        }
+    }
+}
+
+// Buffered sources share their decoded samples between clones
+fn cached_clip(cache: &mut HashMap<String, Clip>, path: &str) -> anyhow::Result<Clip> {
+    use rodio::Source;
+    if let Some(clip) = cache.get(path) {
+        return Ok(clip.clone());
+    }
+    let file = File::open(path)?;
+    let clip = rodio::Decoder::new(BufReader::new(file))?.buffered();
+    if cache.len() >= MAX_CACHED_SOUNDS {
+        cache.clear();
+    }
+    cache.insert(path.to_string(), clip.clone());
+    Ok(clip)
+}
 
-        sink.append(source);
-        sink.sleep_until_end();
-    });
+fn tone(freq: f32, millis: u64, amplitude: f32) -> impl rodio::Source<Item = f32> {
+    use rodio::Source;
+    use std::time::Duration;
+    rodio::source::SineWave::new(freq)
+        .take_duration(Duration::from_millis(millis))
+        .fade_in(Duration::from_millis(10))
+        .amplify(amplitude)
 }
@@ -0,0 +1,103 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Canned answers to the questions chat asks every stream ("!discord", "what
+// elo?"), given without asking the AI, so they're instant and cost no quota.
+// Checked after the chat commands and before the AI triggers.
+//
+// AUTO_REPLIES holds them separated by '|', each written as
+// "<message> => <reply>" for a message that's exactly that (ignoring case and
+// a trailing ?, ! or .), or "/<regex>/ => <reply>" for one the regex finds
+// something in. The reply can't contain '|'; {user} in it is the asker. The
+// same answer is given at most once per AUTO_REPLY_COOLDOWN.
+
+#[derive(Debug, Clone)]
+pub enum Trigger {
+    // Lowercase, without trailing punctuation
+    Exact(String),
+    Pattern(Regex),
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoReply {
+    pub trigger: Trigger,
+    pub reply: String,
+}
+
+// "What ELO?!" -> "what elo"
+fn normalize(text: &str) -> String {
+    text.trim()
+        .trim_end_matches(['?', '!', '.'])
+        .trim_end()
+        .to_lowercase()
+}
+
+impl AutoReply {
+    pub fn parse(entry: &str) -> Result<Self> {
+        let usage = || {
+            format!(
+                "Auto replies look like \"what elo => Diamond 2\", got '{}'",
+                entry
+            )
+        };
+        let (trigger, reply) = entry.split_once("=>").with_context(usage)?;
+        let (trigger, reply) = (trigger.trim(), reply.trim());
+        if trigger.is_empty() || reply.is_empty() {
+            bail!(usage());
+        }
+        let trigger = match trigger
+            .strip_prefix('/')
+            .and_then(|pattern| pattern.strip_suffix('/'))
+        {
+            Some(pattern) => Trigger::Pattern(
+                Regex::new(pattern)
+                    .with_context(|| format!("'{}' is not a valid regex", pattern))?,
+            ),
+            None => Trigger::Exact(normalize(trigger)),
+        };
+        Ok(Self {
+            trigger,
+            reply: reply.to_string(),
+        })
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        match &self.trigger {
+            Trigger::Exact(exact) => normalize(text) == *exact,
+            Trigger::Pattern(regex) => regex.is_match(text),
+        }
+    }
+
+    /// `what elo => Diamond 2`
+    pub fn describe(&self) -> String {
+        match &self.trigger {
+            Trigger::Exact(exact) => format!("{} => {}", exact, self.reply),
+            Trigger::Pattern(regex) => format!("/{}/ => {}", regex.as_str(), self.reply),
+        }
+    }
+}
+
+/// The first of `replies` that answers `text`.
+pub fn find<'a>(replies: &'a [AutoReply], text: &str) -> Option<&'a AutoReply> {
+    replies.iter().find(|reply| reply.matches(text))
+}
+
+// When each answer was last given
+#[derive(Debug, Default)]
+pub struct AutoReplies {
+    given: HashMap<String, Instant>,
+}
+
+impl AutoReplies {
+    /// The reply for `user`, or None while it's cooling down.
+    pub fn give(&mut self, reply: &AutoReply, cooldown: Duration, user: &str) -> Option<String> {
+        let key = reply.describe();
+        if self.given.get(&key).is_some_and(|t| t.elapsed() < cooldown) {
+            return None;
+        }
+        self.given.insert(key, Instant::now());
+        Some(reply.reply.replace("{user}", user))
+    }
+}
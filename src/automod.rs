@@ -0,0 +1,85 @@
+use crate::modlog::{ModEvent, ModKind};
+
+// Messages AutoMod is holding for review. They're listed above the
+// Moderation tab until someone decides: Ctrl+A allows the selected one (the
+// oldest if none is selected), Ctrl+X denies it, through Helix's Manage Held
+// AutoMod Messages. Decisions made elsewhere (another mod, the dashboard) and
+// messages Twitch let expire come in as automod.message.update and take them
+// off the list too.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeldMessage {
+    // Twitch's message id, what's allowed or denied
+    pub id: String,
+    pub user: String,
+    pub text: String,
+    // Why AutoMod held it, e.g. "swearing", and how sure it is (1 to 4)
+    pub category: String,
+    pub level: u8,
+}
+
+impl HeldMessage {
+    /// The hold, for the mod log
+    pub fn mod_event(&self) -> ModEvent {
+        ModEvent {
+            kind: ModKind::AutoModHold,
+            user: Some(self.user.clone()),
+            moderator: None,
+            detail: format!("{} (level {}): {}", self.category, self.level, self.text),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HeldMessages {
+    // Oldest first
+    held: Vec<HeldMessage>,
+    pub selected: Option<usize>,
+}
+
+impl HeldMessages {
+    pub fn hold(&mut self, message: HeldMessage) {
+        self.held.push(message);
+    }
+
+    /// Take a message off the list once it's been decided on
+    pub fn resolve(&mut self, id: &str) -> Option<HeldMessage> {
+        let index = self.held.iter().position(|held| held.id == id)?;
+        let message = self.held.remove(index);
+        self.selected = match self.selected {
+            _ if self.held.is_empty() => None,
+            Some(i) if i > index => Some(i - 1),
+            Some(i) => Some(i.min(self.held.len() - 1)),
+            None => None,
+        };
+        Some(message)
+    }
+
+    /// The one Ctrl+A and Ctrl+X act on: the selected message, or the
+    /// oldest when none is selected
+    pub fn current(&self) -> Option<&HeldMessage> {
+        self.held.get(self.selected.unwrap_or(0))
+    }
+
+    pub fn select_step(&mut self, up: bool) {
+        let last = self.held.len().checked_sub(1);
+        self.selected = match (self.selected, last) {
+            (_, None) => None,
+            (None, _) => Some(0),
+            (Some(i), _) if up => Some(i.saturating_sub(1)),
+            (Some(i), Some(last)) => Some((i + 1).min(last)),
+        };
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &HeldMessage> {
+        self.held.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.held.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.held.is_empty()
+    }
+}
@@ -0,0 +1,646 @@
+//! Benchmark scenarios: made-up chat, joins and raids sent at a set rate, with
+//! the CPU, the time each event takes to get through and the results as JSON.
+//!
+//! `benchmark` (src/bin) sends them through a bare event bus, to measure the
+//! channel; `choui-the-no-gui-chatbot bench` sends them through the bot's own
+//! event loop, middleware, triggers, outbox rate limit and rendering, with
+//! canned AI replies and a silent TTS standing in for the real ones.
+
+use crate::state::{AppEvent, ChatMessage, Fragment, StreamAlert};
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+
+const OPTIONS: &str = "[--messages <n>] [--duration <seconds>] [--rate <events/s>]
+       [--join-ratio <0-1>] [--raid <viewers>@<seconds>]... [--json <path>]
+
+  --messages <n>            Events to send, raids aside (50, or no limit with --duration)
+  --duration <seconds>      Stop sending after this long
+  --rate <events/s>         How fast events are sent (10)
+  --join-ratio <0-1>        Share of the events that are joins rather than chat (0.2)
+  --raid <viewers>@<secs>   A raid that many seconds in: the alert, then every raider
+                            joins and says hi at once. May be given more than once
+  --json <path>             Also write the scenario and results as JSON, - for stdout";
+
+// How long the bot gets to catch up once everything is sent
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+// Sent first: the scenario starts once the bot has got through whatever it
+// queued while starting up
+const WARM_UP: &str = "Benchmark: starting";
+
+/// What to simulate, from the command line
+#[derive(Clone)]
+pub struct Scenario {
+    pub messages: Option<usize>,
+    pub duration: Option<Duration>,
+    pub rate: f64,
+    pub join_ratio: f64,
+    // (viewers, when), in the order they happen
+    pub raids: Vec<(u32, Duration)>,
+    pub json: Option<PathBuf>,
+}
+
+impl Scenario {
+    /// `command` is what --help shows the options after
+    pub fn from_args(args: impl IntoIterator<Item = String>, command: &str) -> Result<Self> {
+        let usage = format!("Usage: {} {}", command, OPTIONS);
+        let mut scenario = Scenario {
+            messages: None,
+            duration: None,
+            rate: 10.0,
+            join_ratio: 0.2,
+            raids: Vec::new(),
+            json: None,
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--messages" => scenario.messages = Some(number(&arg, &value()?)?),
+                "--duration" => {
+                    let secs: f64 = number(&arg, &value()?)?;
+                    scenario.duration = Some(Duration::from_secs_f64(secs));
+                }
+                "--rate" => scenario.rate = number(&arg, &value()?)?,
+                "--join-ratio" => scenario.join_ratio = number(&arg, &value()?)?,
+                "--raid" => {
+                    let raid = value()?;
+                    let (viewers, at) = raid.split_once('@').with_context(|| {
+                        format!("--raid is <viewers>@<seconds>, got '{}'", raid)
+                    })?;
+                    let at: f64 = number(&arg, at)?;
+                    scenario
+                        .raids
+                        .push((number(&arg, viewers)?, Duration::from_secs_f64(at)));
+                }
+                "--json" => scenario.json = Some(PathBuf::from(value()?)),
+                "-h" | "--help" => {
+                    println!("{}", usage);
+                    std::process::exit(0);
+                }
+                other => bail!("Unknown argument '{}'\n\n{}", other, usage),
+            }
+        }
+        if scenario.rate <= 0.0 {
+            bail!("--rate must be more than 0");
+        }
+        if !(0.0..=1.0).contains(&scenario.join_ratio) {
+            bail!("--join-ratio must be 0 to 1");
+        }
+        if scenario.messages.is_none() && scenario.duration.is_none() {
+            scenario.messages = Some(50);
+        }
+        scenario.raids.sort_by_key(|&(_, at)| at);
+        Ok(scenario)
+    }
+
+    pub fn describe(&self) {
+        println!("Configuration:");
+        match self.messages {
+            Some(messages) => println!("  Events to send: {}", messages),
+            None => println!("  Events to send: no limit"),
+        }
+        if let Some(duration) = self.duration {
+            println!("  Duration: {:.1}s", duration.as_secs_f64());
+        }
+        println!("  Rate: {} events/s", self.rate);
+        println!("  Joins: {:.0}% of events", self.join_ratio * 100.0);
+        for (viewers, at) in &self.raids {
+            println!("  Raid: {} viewers at {:.1}s", viewers, at.as_secs_f64());
+        }
+        println!();
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "messages": self.messages,
+            "duration_secs": self.duration.map(|d| d.as_secs_f64()),
+            "rate": self.rate,
+            "join_ratio": self.join_ratio,
+            "raids": self.raids.iter().map(|(viewers, at)| {
+                json!({ "viewers": viewers, "at_secs": at.as_secs_f64() })
+            }).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Events that can be in flight at once: raids arrive all together
+    pub fn burst(&self) -> usize {
+        let biggest_raid = self.raids.iter().map(|&(viewers, _)| viewers as usize);
+        1 + 2 * biggest_raid.max().unwrap_or(0)
+    }
+
+    /// Send the events at the scenario's rate, raids on time, sampling `cpu`
+    /// as it goes. How many were sent.
+    pub async fn send_events(&self, cpu: &mut CpuMonitor, mut send: impl FnMut(AppEvent)) -> usize {
+        let start = Instant::now();
+        cpu.sample();
+
+        // A join whenever the running share of joins falls behind
+        // --join-ratio, so runs are the same every time
+        let interval = Duration::from_secs_f64(1.0 / self.rate);
+        let mut raids = self.raids.iter().enumerate().peekable();
+        let mut sent = 0;
+        for i in 0.. {
+            if self.messages.is_some_and(|messages| i >= messages)
+                || self
+                    .duration
+                    .is_some_and(|duration| start.elapsed() >= duration)
+            {
+                break;
+            }
+            while let Some((number, &(viewers, _))) =
+                raids.next_if(|(_, (_, at))| start.elapsed() >= *at)
+            {
+                for event in raid(number, viewers) {
+                    send(event);
+                    sent += 1;
+                }
+            }
+
+            let joins_before = (i as f64 * self.join_ratio) as usize;
+            let event = if ((i + 1) as f64 * self.join_ratio) as usize > joins_before {
+                AppEvent::UserJoined(format!("TestUser{}", joins_before))
+            } else {
+                let user = format!("User{}", i % 5);
+                let text = format!("Test message number {} with some content to process", i);
+                chat_message(i, user, text)
+            };
+            send(event);
+            sent += 1;
+
+            if i % 10 == 0 {
+                cpu.sample();
+            }
+            tokio::time::sleep(interval).await;
+        }
+        sent
+    }
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .with_context(|| format!("{} needs a number, got '{}'", flag, value))
+}
+
+// CPU time so far, in clock ticks (Linux only): the whole machine's, busy
+// and in all, summed over its cores, and this process's
+#[derive(Clone, Copy)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+    process: u64,
+}
+
+fn cpu_times() -> Option<CpuTimes> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let fields: Vec<u64> = stat
+        .lines()
+        .next()?
+        .strip_prefix("cpu ")?
+        .split_whitespace()
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    if fields.len() < 5 {
+        return None;
+    }
+    // user nice system idle iowait irq softirq steal; guest time is already
+    // counted in user
+    let total: u64 = fields.iter().take(8).sum();
+    let idle = fields[3] + fields[4];
+
+    // The command name is in parentheses and may hold spaces; utime and
+    // stime are the 12th and 13th fields after it
+    let own = std::fs::read_to_string("/proc/self/stat").ok()?;
+    let after_name = &own[own.rfind(')')? + 1..];
+    let mut own_fields = after_name.split_whitespace().skip(11);
+    let utime: u64 = own_fields.next()?.parse().ok()?;
+    let stime: u64 = own_fields.next()?.parse().ok()?;
+
+    Some(CpuTimes {
+        busy: total - idle,
+        total,
+        process: utime + stime,
+    })
+}
+
+fn cpu_count() -> usize {
+    std::fs::read_to_string("/proc/stat")
+        .map(|stat| {
+            stat.lines()
+                .filter(|line| line.starts_with("cpu") && !line.starts_with("cpu "))
+                .count()
+        })
+        .unwrap_or(0)
+        .max(1)
+}
+
+/// CPU use between samples: the whole system's, where other programs count
+/// too, and this process's alone
+pub struct CpuMonitor {
+    last: Option<CpuTimes>,
+    cpus: usize,
+    // Percent of all cores
+    system: Vec<f64>,
+    // Percent of one core, so a process keeping two cores busy is at 200
+    process: Vec<f64>,
+    start_time: Instant,
+}
+
+impl Default for CpuMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CpuMonitor {
+    pub fn new() -> Self {
+        Self {
+            last: None,
+            cpus: cpu_count(),
+            system: Vec::new(),
+            process: Vec::new(),
+            start_time: Instant::now(),
+        }
+    }
+
+    /// The use since the last call; the first one only starts the clock
+    pub fn sample(&mut self) {
+        let Some(now) = cpu_times() else {
+            return;
+        };
+        if let Some(last) = self.last.filter(|last| now.total > last.total) {
+            let elapsed = (now.total - last.total) as f64;
+            self.system
+                .push(now.busy.saturating_sub(last.busy) as f64 / elapsed * 100.0);
+            // The total counts every core's ticks
+            let per_core = elapsed / self.cpus as f64;
+            self.process
+                .push(now.process.saturating_sub(last.process) as f64 / per_core * 100.0);
+        }
+        self.last = Some(now);
+    }
+
+    /// Average, min and max
+    fn summary(samples: &[f64]) -> Option<(f64, f64, f64)> {
+        if samples.is_empty() {
+            return None;
+        }
+        let avg = samples.iter().sum::<f64>() / samples.len() as f64;
+        let max = samples.iter().cloned().fold(0.0_f64, f64::max);
+        let min = samples.iter().cloned().fold(f64::MAX, f64::min);
+        Some((avg, min, max))
+    }
+
+    pub fn report(&self) {
+        let (Some(system), Some(process)) =
+            (Self::summary(&self.system), Self::summary(&self.process))
+        else {
+            println!("No CPU samples collected.");
+            return;
+        };
+
+        println!("\n=== CPU Usage Report ===");
+        println!("Duration: {:.2}s", self.start_time.elapsed().as_secs_f64());
+        println!("Samples: {}", self.system.len());
+        println!(
+            "Process CPU: {:.2}% avg, {:.2}% min, {:.2}% max (of one core)",
+            process.0, process.1, process.2
+        );
+        println!(
+            "System CPU: {:.2}% avg, {:.2}% min, {:.2}% max (of all cores)",
+            system.0, system.1, system.2
+        );
+        println!("Cores: {}", self.cpus);
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let summary = |samples: &[f64]| match Self::summary(samples) {
+            Some((avg, min, max)) => json!({ "avg": avg, "min": min, "max": max }),
+            None => serde_json::Value::Null,
+        };
+        json!({
+            "cores": self.cpus,
+            "process": summary(&self.process),
+            "system": summary(&self.system),
+        })
+    }
+}
+
+/// Durations (send-to-processed, or a frame's drawing) for percentiles
+#[derive(Default)]
+pub struct Latencies {
+    samples: Vec<Duration>,
+}
+
+impl Latencies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// From `since` until now
+    pub fn record(&mut self, since: Instant) {
+        self.samples.push(since.elapsed());
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// p50, p95, p99 and max in microseconds, None without samples
+    fn percentiles(&self) -> Option<[f64; 4]> {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        // Nearest rank
+        let rank = |p: f64| {
+            let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+            sorted.get(rank.clamp(1, sorted.len()) - 1).copied()
+        };
+        let micros = |d: Duration| d.as_secs_f64() * 1_000_000.0;
+        Some([
+            micros(rank(50.0)?),
+            micros(rank(95.0)?),
+            micros(rank(99.0)?),
+            micros(*sorted.last()?),
+        ])
+    }
+
+    pub fn report(&self, title: &str) {
+        let Some([p50, p95, p99, max]) = self.percentiles() else {
+            println!("No {} samples collected.", title.to_lowercase());
+            return;
+        };
+        println!("\n=== {} ===", title);
+        println!("Samples: {}", self.samples.len());
+        println!("p50: {:.1}µs", p50);
+        println!("p95: {:.1}µs", p95);
+        println!("p99: {:.1}µs", p99);
+        println!("Max: {:.1}µs", max);
+    }
+
+    /// In microseconds
+    pub fn to_json(&self) -> serde_json::Value {
+        let Some([p50, p95, p99, max]) = self.percentiles() else {
+            return serde_json::Value::Null;
+        };
+        json!({
+            "events": self.samples.len(),
+            "p50": p50,
+            "p95": p95,
+            "p99": p99,
+            "max": max,
+        })
+    }
+}
+
+fn chat_message(id: usize, user: String, text: String) -> AppEvent {
+    AppEvent::ChatMessage(Arc::new(ChatMessage {
+        id: format!("msg-{}", id),
+        display_name: user.clone(),
+        user,
+        fragments: vec![Fragment::Text(text.clone())],
+        text,
+        color: None,
+        badges: Vec::new(),
+        timestamp: jiff::Timestamp::now(),
+    }))
+}
+
+/// The raid alert, then every raider joining and saying hi
+fn raid(number: usize, viewers: u32) -> Vec<AppEvent> {
+    let from = format!("Raider{}", number);
+    let mut events = vec![AppEvent::Alert(StreamAlert::Raid {
+        from: from.clone(),
+        from_id: String::new(),
+        viewers,
+    })];
+    for i in 0..viewers as usize {
+        let user = format!("{}Viewer{}", from, i);
+        events.push(AppEvent::UserJoined(user.clone()));
+        events.push(chat_message(
+            (number + 1) * 1_000_000 + i,
+            user,
+            format!("{} Raid! hype", from),
+        ));
+    }
+    events
+}
+
+/// Git commit of the working tree, to tell runs apart
+pub fn commit() -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Write the results to `path`, or stdout for "-"
+pub fn write_json(path: &Path, results: &serde_json::Value) -> Result<()> {
+    let results = serde_json::to_string_pretty(results)?;
+    if path.as_os_str() == "-" {
+        println!("{}", results);
+    } else {
+        std::fs::write(path, results + "\n")
+            .with_context(|| format!("Writing {} failed", path.display()))?;
+        println!("Results written to {}", path.display());
+    }
+    Ok(())
+}
+
+/// The bot's database during `bench`, so made-up viewers don't end up in the
+/// real one
+pub fn database() -> PathBuf {
+    std::env::temp_dir().join(format!("choui-bench-{}.db", std::process::id()))
+}
+
+/// The settings `bench` runs with: the demo's made-up channel, a throwaway
+/// database, TTS on but muted, and nothing that reaches outside the process
+/// (raid shoutouts, tips, the API, Matrix, MQTT). Unlike the demo these win
+/// over the configured ones. Call before any threads are started.
+pub fn fill_in_settings() {
+    crate::demo::fill_in_settings();
+    let database = database();
+    for (name, value) in [
+        ("CHAT_LOG", database.to_string_lossy().as_ref()),
+        ("TTS_ENABLED", "true"),
+        ("TTS_VOLUME", "0"),
+        ("JOIN_VOLUME", "0"),
+        ("ALERT_VOLUME", "0"),
+        ("RAID_WELCOME", "false"),
+        ("RAID_SHOUTOUT", "false"),
+        ("STREAMELEMENTS_TOKEN", ""),
+        ("STREAMLABS_TOKEN", ""),
+        ("API_ENABLED", "false"),
+        ("MATRIX_ENABLED", "false"),
+        ("MQTT_ENABLED", "false"),
+    ] {
+        std::env::set_var(name, value);
+    }
+}
+
+// Which scenario event this is, for matching it up when it comes out of the
+// event loop
+fn key(event: &AppEvent) -> Option<String> {
+    match event {
+        AppEvent::ChatMessage(message) => Some(format!("chat:{}", message.id)),
+        AppEvent::UserJoined(user) => Some(format!("join:{}", user)),
+        AppEvent::Alert(alert) => Some(format!("alert:{}", alert.describe())),
+        AppEvent::Debug(text) if text == WARM_UP => Some(WARM_UP.to_string()),
+        _ => None,
+    }
+}
+
+/// What the scenario's sender hands back once the bot has taken every event
+/// off its queue
+pub struct Sent {
+    events: usize,
+    cpu: CpuMonitor,
+    // Until the last event was handled
+    elapsed: Duration,
+}
+
+/// A `bench` run in progress. The scenario goes into the bot's event stream
+/// from a task of its own, and the event loop asks `sent_at` for every event
+/// it takes off the stream, recording how long the event took once handled.
+pub struct Run {
+    scenario: Scenario,
+    stamps: Arc<Mutex<HashMap<String, Instant>>>,
+    /// From being sent to being handled
+    pub latencies: Latencies,
+    /// How long each frame took to draw
+    pub frames: Latencies,
+}
+
+impl Run {
+    pub fn start(
+        scenario: Scenario,
+        tx: mpsc::UnboundedSender<AppEvent>,
+    ) -> (Self, oneshot::Receiver<Sent>) {
+        scenario.describe();
+        let run = Self {
+            scenario,
+            stamps: Arc::default(),
+            latencies: Latencies::new(),
+            frames: Latencies::new(),
+        };
+        let (done_tx, done) = oneshot::channel();
+        let stamps = run.stamps.clone();
+        let scenario = run.scenario.clone();
+        tokio::spawn(async move {
+            let drained = || async {
+                let waiting = Instant::now();
+                while !stamps.lock().unwrap().is_empty() && waiting.elapsed() < DRAIN_TIMEOUT {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            };
+            stamps
+                .lock()
+                .unwrap()
+                .insert(WARM_UP.to_string(), Instant::now());
+            let _ = tx.send(AppEvent::Debug(WARM_UP.to_string()));
+            drained().await;
+
+            let started = Instant::now();
+            let mut cpu = CpuMonitor::new();
+            let sent = scenario
+                .send_events(&mut cpu, |event| {
+                    if let Some(key) = key(&event) {
+                        stamps.lock().unwrap().insert(key, Instant::now());
+                    }
+                    let _ = tx.send(event);
+                })
+                .await;
+            drained().await;
+            cpu.sample();
+            let _ = done_tx.send(Sent {
+                events: sent,
+                cpu,
+                elapsed: started.elapsed(),
+            });
+        });
+        (run, done)
+    }
+
+    /// When `event` was sent, if it's one of the scenario's; each is only
+    /// answered for once
+    pub fn sent_at(&self, event: &AppEvent) -> Option<Instant> {
+        let key = key(event)?;
+        let sent = self.stamps.lock().unwrap().remove(&key);
+        sent.filter(|_| key != WARM_UP)
+    }
+
+    /// Print the results, write them as JSON if asked to, and remove the
+    /// throwaway database. Call once the bot has shut down.
+    pub fn finish(self, sent: Sent) -> Result<()> {
+        let Sent {
+            events: sent,
+            cpu,
+            elapsed,
+        } = sent;
+        let missed = self.stamps.lock().unwrap().len();
+        println!("\nBenchmark completed in {:.2}s", elapsed.as_secs_f64());
+        if missed > 0 {
+            println!("{} events weren't handled in time", missed);
+        }
+        cpu.report();
+        self.latencies.report("Pipeline Latency");
+        self.frames.report("Frame Render Time");
+        let events_per_second = self.latencies.len() as f64 / elapsed.as_secs_f64();
+        println!("\nThroughput: {:.2} events/second", events_per_second);
+
+        if let Some(path) = &self.scenario.json {
+            let results = json!({
+                "commit": commit(),
+                "mode": "pipeline",
+                "scenario": self.scenario.to_json(),
+                "elapsed_secs": elapsed.as_secs_f64(),
+                "sent": sent,
+                "missed": missed,
+                "throughput": events_per_second,
+                "cpu": cpu.to_json(),
+                "latency_us": self.latencies.to_json(),
+                "render_us": self.frames.to_json(),
+            });
+            write_json(path, &results)?;
+        }
+        let database = database();
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = database.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+        Ok(())
+    }
+}
+
+/// Resolves with what was sent once a run's events have all been handled;
+/// never, without a run
+pub async fn finished(done: &mut Option<oneshot::Receiver<Sent>>) -> Sent {
+    match done.as_mut() {
+        Some(receiver) => match receiver.await {
+            Ok(sent) => {
+                *done = None;
+                sent
+            }
+            Err(_) => std::future::pending().await,
+        },
+        None => std::future::pending().await,
+    }
+}
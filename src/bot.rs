@@ -0,0 +1,382 @@
+use crate::config::Config;
+use crate::irc;
+use crate::outbox::{Outbox, Priority};
+use crate::state::{AppEvent, Role};
+use crate::twitch::{
+    authenticate_via_device_flow, delete_eventsub_subscription, get_user_id, get_user_login,
+    load_token_cache, refresh_token, save_token_cache, send_chat_message,
+    subscribe_to_alert_events, subscribe_to_chat_messages, validate_token,
+};
+use crate::ws::connect_eventsub_ws;
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+// The part of the bot that talks to Twitch, without any of the features or a
+// frontend: log in, connect, subscribe, hand out what happens as AppEvents and
+// post in chat. The features are the session's (see session.rs), which the
+// terminal UI and the headless mode in main.rs drive; another frontend could
+// use either (see the example in lib.rs).
+
+// The longest each step of shutting down may take
+pub const SHUTDOWN_STEP: Duration = Duration::from_secs(3);
+
+pub struct BotCore {
+    pub client: reqwest::Client,
+    // With the OAuth token and the user ids filled in; shared with every
+    // task that needs it
+    pub config: Arc<Config>,
+    pub bot_login: String,
+    tx: mpsc::UnboundedSender<AppEvent>,
+    rx: Option<mpsc::UnboundedReceiver<AppEvent>>,
+    // Cancelled on shutdown, which closes the connections
+    shutdown: CancellationToken,
+    // EventSub subscription ids, for unsubscribing on the way out
+    subscriptions: Vec<String>,
+    eventsub_task: Option<JoinHandle<Result<()>>>,
+    irc_task: Option<JoinHandle<()>>,
+    // Raw lines for the IRC connection, once connected
+    irc: Option<mpsc::UnboundedSender<String>>,
+    // What `say` sends, within the chat rate limit once subscribed
+    outbox: Outbox,
+}
+
+impl BotCore {
+    /// Log in (with the token `config` comes with, the cached one, by
+    /// refreshing it, or with the device flow) and look up the ids `config`
+    /// leaves out. Progress goes to stdout, since there's no frontend yet.
+    pub async fn connect(mut config: Config) -> Result<Self> {
+        let client = reqwest::Client::new();
+
+        println!("Authenticating...");
+        let token = 'auth: {
+            if let Some(token) = config.oauth_token.take() {
+                if !validate_token(&client, &config, &token).await? {
+                    bail!("The OAuth token given is invalid or expired");
+                }
+                break 'auth token;
+            }
+            if let Ok(cached) = load_token_cache(&config) {
+                println!("Found cached token. Validating...");
+                if validate_token(&client, &config, &cached.access_token)
+                    .await
+                    .unwrap_or(false)
+                {
+                    println!("Token is valid!");
+                    break 'auth cached.access_token;
+                }
+
+                println!("Token expired or invalid.");
+                if let Some(rt) = cached.refresh_token {
+                    println!("Attempting refresh...");
+                    if let Ok(new_token) = refresh_token(&client, &config, &rt).await {
+                        println!("Refresh successful!");
+                        let _ = save_token_cache(&config, &new_token);
+                        break 'auth new_token.access_token;
+                    }
+                    println!("Refresh failed.");
+                }
+            }
+
+            println!("Starting Device Authorization Flow...");
+            let token_resp = authenticate_via_device_flow(&client, &config).await?;
+            token_resp.access_token
+        };
+        config.oauth_token = Some(token);
+        println!("Authentication successful!");
+
+        // Resolve bot_user_id if it's not a numeric ID
+        if config.bot_user_id.chars().any(|c| !c.is_numeric()) {
+            println!(
+                "Detected username for BOT_USER_ID: {}. Resolving to ID...",
+                config.bot_user_id
+            );
+            let id = get_user_id(&client, &config, &config.bot_user_id).await?;
+            println!("Resolved Bot ID: {}", id);
+            config.bot_user_id = id;
+        }
+
+        // Resolve channel_user_id if missing
+        if config.channel_user_id.is_none() {
+            let Some(name) = &config.channel_name else {
+                bail!("Neither CHANNEL_USER_ID nor CHANNEL_NAME is set");
+            };
+            println!("Resolving ID for channel: {}", name);
+            let id = get_user_id(&client, &config, name).await?;
+            println!("Resolved ID: {}", id);
+            config.channel_user_id = Some(id);
+        }
+
+        // Ensure we have channel_name (needed for IRC)
+        if config.channel_name.is_none() {
+            if let Some(id) = &config.channel_user_id {
+                println!("Resolving Name for channel ID: {}", id);
+                let name = get_user_login(&client, &config, id).await?;
+                println!("Resolved Name: {}", name);
+                config.channel_name = Some(name);
+            }
+        }
+
+        println!("Resolving Bot Login...");
+        let bot_login = get_user_login(&client, &config, &config.bot_user_id).await?;
+        println!("Bot Login: {}", bot_login);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        Ok(Self {
+            client,
+            config: Arc::new(config),
+            bot_login,
+            tx,
+            rx: Some(rx),
+            shutdown: CancellationToken::new(),
+            subscriptions: Vec::new(),
+            eventsub_task: None,
+            irc_task: None,
+            irc: None,
+            outbox: Outbox::default(),
+        })
+    }
+
+    /// A bot in a made-up channel, for --demo: no login, and `subscribe`
+    /// starts demo chat instead of connecting (see demo.rs).
+    pub fn demo(mut config: Config) -> Self {
+        config.demo = true;
+        config.oauth_token = Some("demo".to_string());
+        if config.channel_user_id.is_none() {
+            config.channel_user_id = Some("0".to_string());
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            client: reqwest::Client::new(),
+            config: Arc::new(config),
+            bot_login: "choui_demo".to_string(),
+            tx,
+            rx: Some(rx),
+            shutdown: CancellationToken::new(),
+            subscriptions: Vec::new(),
+            eventsub_task: None,
+            irc_task: None,
+            irc: None,
+            outbox: Outbox::default(),
+        }
+    }
+
+    /// Everything that happens, from Twitch and from whatever else was given
+    /// `sender()`. There's one stream; None once it's been taken.
+    pub fn events(&mut self) -> Option<mpsc::UnboundedReceiver<AppEvent>> {
+        self.rx.take()
+    }
+
+    /// For adding events of your own to the stream.
+    pub fn sender(&self) -> mpsc::UnboundedSender<AppEvent> {
+        self.tx.clone()
+    }
+
+    /// Raw lines for the IRC connection, for the commands that still go that
+    /// way. None before `subscribe`.
+    pub fn irc(&self) -> Option<&mpsc::UnboundedSender<String>> {
+        self.irc.as_ref()
+    }
+
+    /// Open the EventSub and IRC connections and subscribe to chat and the
+    /// alerts. Missing alerts (subs and cheers need the broadcaster's token)
+    /// are reported on the stream, not as errors.
+    pub async fn subscribe(&mut self) -> Result<()> {
+        if self.config.demo {
+            self.run_offline();
+            crate::demo::start(
+                &self.config,
+                &self.bot_login,
+                self.tx.clone(),
+                self.shutdown.clone(),
+            );
+            return Ok(());
+        }
+        tokio::spawn(self.outbox.clone().run(
+            self.config.clone(),
+            self.tx.clone(),
+            self.shutdown.clone(),
+        ));
+        let (session_id, ws_handle) = connect_eventsub_ws(
+            self.client.clone(),
+            self.config.clone(),
+            self.tx.clone(),
+            self.shutdown.clone(),
+        )
+        .await?;
+        self.eventsub_task = Some(ws_handle);
+
+        // Join/Part events only come over IRC
+        let (irc_handle, irc_tx) =
+            irc::connect(self.config.clone(), self.tx.clone(), self.shutdown.clone()).await?;
+        self.irc_task = Some(irc_handle);
+        self.irc = Some(irc_tx);
+
+        match subscribe_to_chat_messages(&self.client, &session_id, &self.config).await {
+            Ok(id) => {
+                self.subscriptions.push(id);
+                let _ = self.tx.send(AppEvent::Info("Subscribed to chat".into()));
+            }
+            Err(e) => {
+                let _ = self
+                    .tx
+                    .send(AppEvent::Error(format!("Subscription failed: {}", e)));
+            }
+        }
+        for (event_type, result) in
+            subscribe_to_alert_events(&self.client, &session_id, &self.config).await
+        {
+            match result {
+                Ok(id) => self.subscriptions.push(id),
+                Err(e) => {
+                    let _ = self
+                        .tx
+                        .send(AppEvent::Info(format!("No {} alerts: {}", event_type, e)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Start the outbox without connecting anywhere or making up chat, so
+    /// the only events are those given `sender()`. Raw IRC lines go nowhere.
+    pub fn run_offline(&mut self) {
+        tokio::spawn(self.outbox.clone().run(
+            self.config.clone(),
+            self.tx.clone(),
+            self.shutdown.clone(),
+        ));
+        self.irc = Some(mpsc::unbounded_channel().0);
+    }
+
+    /// Keep the chatter list (with moderators and VIPs) and the stream stats
+    /// coming, once a minute, until the stream is dropped or the bot shuts
+    /// down.
+    pub fn poll_channel(&self) {
+        if self.config.demo {
+            // The demo channel's chatters and stats come with the chat
+            return;
+        }
+        let (client, config, tx) = (self.client.clone(), self.config.clone(), self.tx.clone());
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            use crate::twitch::{get_chatters, get_moderators, get_vips};
+
+            let mut roles = Vec::new();
+            if let Some(name) = &config.channel_name {
+                roles.push((name.to_lowercase(), Role::Broadcaster));
+            }
+            match get_moderators(&client, &config).await {
+                Ok(mods) => roles.extend(mods.into_iter().map(|m| (m, Role::Moderator))),
+                Err(e) => {
+                    let _ = tx.send(AppEvent::Debug(format!("Moderator list: {}", e)));
+                }
+            }
+            match get_vips(&client, &config).await {
+                Ok(vips) => roles.extend(vips.into_iter().map(|v| (v, Role::Vip))),
+                Err(e) => {
+                    let _ = tx.send(AppEvent::Debug(format!("VIP list: {}", e)));
+                }
+            }
+            let _ = tx.send(AppEvent::ChatterRoles(roles));
+
+            // JOIN/PART alone misses lurkers
+            loop {
+                match get_chatters(&client, &config).await {
+                    Ok(logins) => {
+                        if tx.send(AppEvent::ChatterList(logins)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.send(AppEvent::Debug(format!("Chatter list: {}", e)));
+                    }
+                }
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+                }
+            }
+        });
+
+        let (client, config, tx) = (self.client.clone(), self.config.clone(), self.tx.clone());
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            use crate::twitch::get_stream_stats;
+
+            loop {
+                match get_stream_stats(&client, &config).await {
+                    Ok(stats) => {
+                        if tx.send(AppEvent::StreamStats(stats)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.send(AppEvent::Debug(format!("Stream stats: {}", e)));
+                    }
+                }
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+                }
+            }
+        });
+    }
+
+    /// Post in chat as the bot, right away, past the outbox.
+    pub async fn send(&self, message: &str) -> Result<()> {
+        send_chat_message(message, &self.config).await
+    }
+
+    /// Post in chat through the outbox, as a reply; a failure comes back on
+    /// the stream as SendFailed, with the message.
+    pub fn say(&self, message: String) {
+        self.outbox.say(Priority::Normal, message);
+    }
+
+    /// The queue `say` uses, for saying things at another priority. Clones
+    /// share it.
+    pub fn outbox(&self) -> &Outbox {
+        &self.outbox
+    }
+
+    /// Ask the AI, as the bot in chat would be (persona and all).
+    pub async fn ask(&self, prompt: &str) -> Result<String> {
+        crate::ai::ask_ai(prompt, &self.config).await
+    }
+
+    /// Say `goodbye`, if any, unsubscribe and close the connections, each
+    /// step giving up after SHUTDOWN_STEP. Problems are printed, since the
+    /// frontend is usually gone by now.
+    pub async fn shutdown(self, goodbye: Option<&str>) {
+        if let Some(message) = goodbye {
+            let sent = tokio::time::timeout(SHUTDOWN_STEP, self.send(message)).await;
+            if let Ok(Err(e)) = sent {
+                eprintln!("Offline message not sent: {:#}", e);
+            }
+        }
+        let unsubscribed = futures_util::future::join_all(
+            self.subscriptions
+                .iter()
+                .map(|id| delete_eventsub_subscription(&self.client, &self.config, id)),
+        );
+        if let Ok(results) = tokio::time::timeout(SHUTDOWN_STEP, unsubscribed).await {
+            if let Some(Err(e)) = results.into_iter().find(Result::is_err) {
+                eprintln!("EventSub: {:#}", e);
+            }
+        }
+        self.shutdown.cancel();
+        let _ = tokio::time::timeout(SHUTDOWN_STEP, async {
+            if let Some(task) = self.eventsub_task {
+                let _ = task.await;
+            }
+            if let Some(task) = self.irc_task {
+                let _ = task.await;
+            }
+        })
+        .await;
+    }
+}
@@ -0,0 +1,61 @@
+use crate::state::Role;
+
+// Commands viewers type in chat, like "!quote 12". Parsed here, run by the
+// event loop, which answers in chat. Unlike the TUI's slash commands
+// (commands.rs) unknown ones are ignored: other bots in the channel have
+// commands of their own.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatCommand<'a> {
+    // Lowercase, without the '!'
+    pub name: String,
+    pub args: &'a str,
+}
+
+pub fn parse(text: &str) -> Option<ChatCommand<'_>> {
+    let rest = text.trim().strip_prefix('!')?;
+    let (name, args) = match rest.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (rest, ""),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some(ChatCommand {
+        name: name.to_lowercase(),
+        args,
+    })
+}
+
+/// Commands the bot itself has, which custom commands can't take over.
+pub const BUILT_IN: &[&str] = &[
+    "quote",
+    "quotes",
+    "poll",
+    "addcmd",
+    "editcmd",
+    "delcmd",
+    "sr",
+    "song",
+    "queue",
+    "wrongsong",
+    "skip",
+    "points",
+    "gamble",
+    "duel",
+    "accept",
+    "deny",
+    "8ball",
+    "permit",
+    "lurk",
+    "unlurk",
+    "topcheers",
+    "topgifters",
+    "translate",
+    "shield",
+];
+
+/// Moderators and the broadcaster.
+pub fn is_mod(role: Role) -> bool {
+    role <= Role::Moderator
+}
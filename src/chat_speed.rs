@@ -0,0 +1,210 @@
+use crate::ai;
+use crate::config::{var, Config};
+use anyhow::{bail, Context, Result};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// What the bot does when chat gets fast. Viewer messages are counted over the
+// last minute; at CHAT_SPEED_THRESHOLD a minute or more, chat is fast and
+// CHAT_SPEED_ACTIONS kick in:
+//
+//   slow       slow mode on, CHAT_SPEED_SLOW_MODE seconds between messages
+//   pause_ai   no AI replies to chat
+//   mute_tts   chat isn't read aloud (alerts and the like still are)
+//   summary    "chat is moving fast" with what it's about, by the AI when
+//              it answers, and again every few minutes while it lasts
+//
+// Chat is calm again once it's below half the threshold, after at least
+// MIN_FAST; the actions are undone then.
+
+// How long chat stays fast at least, so it doesn't flap around the threshold
+const MIN_FAST: Duration = Duration::from_secs(120);
+// Between two summaries while chat stays fast
+const SUMMARY_EVERY: Duration = Duration::from_secs(180);
+// Messages the summary is written from
+const SUMMARY_MESSAGES: usize = 30;
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedAction {
+    SlowMode,
+    PauseAi,
+    MuteTts,
+    Summary,
+}
+
+impl SpeedAction {
+    const ALL: [SpeedAction; 4] = [
+        SpeedAction::SlowMode,
+        SpeedAction::PauseAi,
+        SpeedAction::MuteTts,
+        SpeedAction::Summary,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            SpeedAction::SlowMode => "slow",
+            SpeedAction::PauseAi => "pause_ai",
+            SpeedAction::MuteTts => "mute_tts",
+            SpeedAction::Summary => "summary",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatSpeedSettings {
+    // Messages a minute that make chat fast; 0 turns this off
+    pub threshold: u32,
+    pub actions: Vec<SpeedAction>,
+    // Seconds between a viewer's messages in slow mode
+    pub slow_mode: u32,
+}
+
+impl ChatSpeedSettings {
+    // CHAT_SPEED_THRESHOLD, CHAT_SPEED_ACTIONS, CHAT_SPEED_SLOW_MODE
+    pub fn from_env() -> Result<Self> {
+        let number = |name: &str, default: u32| -> Result<u32> {
+            match var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse::<u32>()
+                    .with_context(|| format!("{} must be a number, got '{}'", name, value)),
+                Err(_) => Ok(default),
+            }
+        };
+        let mut actions = Vec::new();
+        let list = var("CHAT_SPEED_ACTIONS").unwrap_or_else(|_| "pause_ai,mute_tts".to_string());
+        for name in list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let Some(action) = SpeedAction::ALL
+                .into_iter()
+                .find(|action| action.name().eq_ignore_ascii_case(name))
+            else {
+                bail!(
+                    "Unknown action '{}' in CHAT_SPEED_ACTIONS (expected slow, pause_ai, mute_tts or summary)",
+                    name
+                );
+            };
+            actions.push(action);
+        }
+        let slow_mode = number("CHAT_SPEED_SLOW_MODE", 10)?;
+        if !(3..=120).contains(&slow_mode) {
+            bail!(
+                "CHAT_SPEED_SLOW_MODE must be 3 to 120 seconds, got {}",
+                slow_mode
+            );
+        }
+        Ok(Self {
+            threshold: number("CHAT_SPEED_THRESHOLD", 0)?,
+            actions,
+            slow_mode,
+        })
+    }
+
+    pub fn does(&self, action: SpeedAction) -> bool {
+        self.actions.contains(&action)
+    }
+}
+
+/// Chat got fast or calmed down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedChange {
+    Fast { per_minute: usize },
+    Calm,
+}
+
+#[derive(Debug, Default)]
+pub struct ChatSpeed {
+    // When each message of the last minute came in
+    arrivals: VecDeque<Instant>,
+    // The latest messages as "user: text", for the summary
+    recent: VecDeque<String>,
+    // Since when chat is fast
+    fast_since: Option<Instant>,
+    last_summary: Option<Instant>,
+}
+
+impl ChatSpeed {
+    pub fn count(&mut self, user: &str, text: &str, now: Instant) {
+        self.arrivals.push_back(now);
+        self.forget(now);
+        if self.recent.len() == SUMMARY_MESSAGES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(format!("{}: {}", user, text));
+    }
+
+    fn forget(&mut self, now: Instant) {
+        while self
+            .arrivals
+            .front()
+            .is_some_and(|&arrival| now.duration_since(arrival) > WINDOW)
+        {
+            self.arrivals.pop_front();
+        }
+    }
+
+    pub fn per_minute(&mut self, now: Instant) -> usize {
+        self.forget(now);
+        self.arrivals.len()
+    }
+
+    pub fn is_fast(&self) -> bool {
+        self.fast_since.is_some()
+    }
+
+    /// Whether chat just got fast or calm. Chat is never fast with a
+    /// threshold of 0.
+    pub fn check(&mut self, now: Instant, threshold: u32) -> Option<SpeedChange> {
+        let per_minute = self.per_minute(now);
+        let threshold = threshold as usize;
+        match self.fast_since {
+            None if threshold > 0 && per_minute >= threshold => {
+                self.fast_since = Some(now);
+                self.last_summary = None;
+                Some(SpeedChange::Fast { per_minute })
+            }
+            Some(since)
+                if threshold == 0
+                    || (per_minute < threshold / 2 && now.duration_since(since) >= MIN_FAST) =>
+            {
+                self.fast_since = None;
+                Some(SpeedChange::Calm)
+            }
+            _ => None,
+        }
+    }
+
+    /// The messages to summarize, when it's time for a summary: as soon as
+    /// chat got fast, then every SUMMARY_EVERY while it stays fast
+    pub fn summary_due(&mut self, now: Instant) -> Option<Vec<String>> {
+        self.fast_since?;
+        if self
+            .last_summary
+            .is_some_and(|last| now.duration_since(last) < SUMMARY_EVERY)
+        {
+            return None;
+        }
+        self.last_summary = Some(now);
+        Some(self.recent.iter().cloned().collect())
+    }
+}
+
+/// "Chat is moving fast!" with what it's about, when the AI answers
+pub async fn summary(messages: &[String], config: &Config) -> String {
+    let fallback = String::from("Chat is moving fast! Hang on, not every message gets an answer");
+    if messages.is_empty() {
+        return fallback;
+    }
+    let prompt = format!(
+        "Chat is moving too fast to read. In a single short sentence starting with \"Chat is moving fast\", say what chat is talking about, from these latest messages:\n{}",
+        messages.join("\n")
+    );
+    match ai::ask_ai(&prompt, config).await {
+        Ok(summary) if !summary.trim().is_empty() => summary.trim().to_string(),
+        _ => fallback,
+    }
+}
@@ -0,0 +1,393 @@
+use std::time::Instant;
+
+use crate::{
+    audio::Sound,
+    auto_replies::{self, AutoReply},
+    chat_commands::{self, ChatCommand},
+    chat_speed::SpeedAction,
+    chatlog::Record,
+    config::VolumeChannel,
+    links,
+    outbox::Priority,
+    pipeline::{Context, Dedup, Flow, Pipeline, Stage},
+    shield::{ShieldAction, Trigger},
+    state::{App, AppEvent, ChatLine, ChatMessage, Role, Tab},
+    translate::{self, TranslateMode},
+    tts::SpeechKind,
+};
+
+// The bot's chat message stages (see pipeline.rs), in the order they run.
+// They decide what happens to a message; doing it (reading aloud, asking the
+// AI, deleting a message on Twitch) is left to the ChatContext, which is the
+// session's event loop in the app (see session.rs) and a fake in the tests.
+
+// Messages seen lately, for dedup
+const DEDUP_CAPACITY: usize = 1000;
+
+/// What the stages work with, for one message.
+pub trait ChatContext {
+    /// Settings, chatters, polls, chat speed and the outbox
+    fn app(&self) -> &App;
+    fn app_mut(&mut self) -> &mut App;
+    /// For the overlay and the event loop
+    fn send(&self, event: AppEvent);
+    /// Into the chat log (CHAT_LOG)
+    fn record(&self, record: Record);
+    fn play(&self, sound: Sound, channel: VolumeChannel);
+    fn speak(&self, kind: SpeechKind, user: &str, text: &str);
+    /// Download an emote for showing it inline; it comes back as an
+    /// AppEvent::InlineEmote
+    fn fetch_emote(&self, name: &str, id: &str);
+    fn raise_shield(&mut self, trigger: Trigger);
+    /// Whether a mod let `user` post links with !permit
+    fn permitted(&mut self, user: &str) -> bool;
+    fn remove_link(&mut self, message: &ChatMessage, link: &str);
+    /// Hand the message to the plugins. Returns whether one handled it.
+    fn run_plugins(&mut self, message: &ChatMessage) -> bool;
+    /// Count a viewer's message toward the timers and their game points
+    fn count_message(&mut self, user: &str);
+    /// Whether `user` asked for their messages not to be translated
+    fn opted_out(&self, user: &str) -> bool;
+    fn translate(&self, message: &ChatMessage, language: &'static str);
+    /// Run a viewer's !command. Returns false for commands the bot doesn't
+    /// have.
+    fn run_command(&mut self, message: &ChatMessage, command: ChatCommand) -> bool;
+    /// The canned answer, unless it's cooling down
+    fn auto_reply(&mut self, reply: &AutoReply, user: &str) -> Option<String>;
+    fn ask_ai(&mut self, user: &str, prompt: String);
+    /// What `triggers` found to ask the AI, for `ai`
+    fn prompt(&mut self) -> &mut Option<String>;
+}
+
+pub struct Chat;
+
+impl Context for Chat {
+    type Of<'a> = dyn ChatContext + 'a;
+}
+
+fn is_own(cx: &dyn ChatContext, message: &ChatMessage) -> bool {
+    message.user.eq_ignore_ascii_case(&cx.app().bot_login)
+}
+
+pub fn pipeline() -> Pipeline<Chat> {
+    Pipeline::default()
+        .then(Dedup::new(DEDUP_CAPACITY))
+        .then(Display)
+        .then(RaidShield)
+        .then(SpamFilter)
+        .then(Speech)
+        .then(PluginHooks)
+        .then(Activity)
+        .then(Translation)
+        .then(Commands)
+        .then(Triggers)
+        .then(Ai::default())
+}
+
+// Into the chat log and the Chat tab, with emotes fetched for inline use
+pub struct Display;
+
+impl Stage<Chat> for Display {
+    fn name(&self) -> &'static str {
+        "display"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        let (user, text) = (&message.user, &message.text);
+        cx.record(Record::Message {
+            message_id: message.id.clone(),
+            user: user.clone(),
+            text: text.clone(),
+        });
+        let role = message.role();
+
+        // Fetch inline renders for emotes we haven't seen yet
+        for (name, id) in message.emotes() {
+            let app = cx.app_mut();
+            if app.inline_emotes.contains_key(name) || !app.inline_pending.insert(name.to_string())
+            {
+                continue;
+            }
+            let known = app
+                .emote_images
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, img)| img.clone());
+            match known {
+                Some(img) => app.add_inline_emote(name.to_string(), img),
+                None => cx.fetch_emote(name, id),
+            }
+        }
+
+        let emote_names = message.emotes().map(|(name, _)| name.to_string()).collect();
+        let mut line = ChatLine::chat(
+            user.clone(),
+            text.clone(),
+            message.color.clone(),
+            role,
+            emote_names,
+        );
+        if !is_own(cx, message) && cx.app().is_mention(text) {
+            line.mention = true;
+            cx.play(Sound::Chime, VolumeChannel::Alert);
+        }
+        let app = cx.app_mut();
+        app.mark_unread();
+        app.push(Tab::Chat, line);
+        let chatter = app.chatter(user);
+        chatter.present = true;
+        chatter.message_count += 1;
+        chatter.role = chatter.role.min(role);
+        Flow::Next
+    }
+}
+
+// Viewers' messages counted toward the hate raid triggers (SHIELD), before
+// link protection deletes any
+pub struct RaidShield;
+
+impl Stage<Chat> for RaidShield {
+    fn name(&self) -> &'static str {
+        "shield"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        if !cx.app().config.shield.enabled || message.role() <= Role::Vip || is_own(cx, message) {
+            return Flow::Next;
+        }
+        let app = cx.app_mut();
+        let has_link = links::forbidden_link(&message.text, &app.config.links).is_some();
+        let now = Instant::now();
+        let settings = &app.config.shield;
+        if let Some(trigger) =
+            app.shield
+                .message(&message.user, &message.text, has_link, now, settings)
+        {
+            cx.raise_shield(trigger);
+        }
+        Flow::Next
+    }
+}
+
+// Links from viewers without a !permit are deleted (LINK_PROTECTION)
+pub struct SpamFilter;
+
+impl Stage<Chat> for SpamFilter {
+    fn name(&self) -> &'static str {
+        "spam_filter"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        if !cx.app().config.links.enabled
+            || message.role() <= Role::Vip
+            || is_own(cx, message)
+            || cx.permitted(&message.user)
+        {
+            return Flow::Next;
+        }
+        match links::forbidden_link(&message.text, &cx.app().config.links) {
+            Some(link) => {
+                let link = link.to_string();
+                cx.remove_link(message, &link);
+                Flow::Done
+            }
+            None => Flow::Next,
+        }
+    }
+}
+
+// Read aloud while TTS is on, unless the viewer is lurking, chat is too
+// fast to keep up with or the shield is up
+pub struct Speech;
+
+impl Stage<Chat> for Speech {
+    fn name(&self) -> &'static str {
+        "speech"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        let app = cx.app();
+        let muted = (app.chat_speed.is_fast() && app.config.chat_speed.does(SpeedAction::MuteTts))
+            || app.shielded(ShieldAction::MuteTts);
+        if app.tts_enabled && !muted && !app.lurkers.is_lurking(&message.user) {
+            // The bot's own messages are its AI replies, which can have their own voice
+            let kind = if is_own(cx, message) {
+                SpeechKind::Ai
+            } else {
+                SpeechKind::Chat
+            };
+            cx.speak(kind, &message.user, &message.text);
+        }
+        Flow::Next
+    }
+}
+
+// Viewers' messages go to the plugins first, which can keep them
+pub struct PluginHooks;
+
+impl Stage<Chat> for PluginHooks {
+    fn name(&self) -> &'static str {
+        "plugins"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        if !is_own(cx, message) && cx.run_plugins(message) {
+            Flow::Done
+        } else {
+            Flow::Next
+        }
+    }
+}
+
+// What a viewer's message counts towards: timers, game points, poll votes,
+// chat speed
+pub struct Activity;
+
+impl Stage<Chat> for Activity {
+    fn name(&self) -> &'static str {
+        "activity"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        if is_own(cx, message) {
+            return Flow::Next;
+        }
+        cx.count_message(&message.user);
+        let app = cx.app_mut();
+        app.chat_speed
+            .count(&message.user, &message.text, Instant::now());
+        let mut voted = None;
+        if let Some(poll) = app.poll.as_mut() {
+            if poll.vote(&message.user, &message.text) {
+                voted = Some(poll.clone());
+            }
+        }
+        if let Some(poll) = voted {
+            cx.send(AppEvent::Poll(poll));
+        }
+        Flow::Next
+    }
+}
+
+// With TRANSLATE=auto, viewers' messages in another language are translated,
+// unless they opted out, or chat is too fast (or the shield up) for AI replies
+pub struct Translation;
+
+impl Stage<Chat> for Translation {
+    fn name(&self) -> &'static str {
+        "translate"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        let app = cx.app();
+        let settings = &app.config.translate;
+        if settings.mode != TranslateMode::Auto
+            || is_own(cx, message)
+            || chat_commands::parse(&message.text).is_some()
+            || (app.chat_speed.is_fast() && app.config.chat_speed.does(SpeedAction::PauseAi))
+            || app.shielded(ShieldAction::PauseAi)
+            || cx.opted_out(&message.user)
+        {
+            return Flow::Next;
+        }
+        if let Some(language) = translate::foreign_language(&message.text, &settings.language) {
+            cx.translate(message, language);
+        }
+        Flow::Next
+    }
+}
+
+// Chat commands (!quote ...) and canned answers, instead of the AI
+pub struct Commands;
+
+impl Stage<Chat> for Commands {
+    fn name(&self) -> &'static str {
+        "commands"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        if is_own(cx, message) {
+            return Flow::Next;
+        }
+        if let Some(command) = chat_commands::parse(&message.text) {
+            if cx.run_command(message, command) {
+                return Flow::Done;
+            }
+        }
+        // Canned answers spare the AI; one cooling down isn't asked about either
+        let Some(reply) = auto_replies::find(&cx.app().config.auto_replies, &message.text).cloned()
+        else {
+            return Flow::Next;
+        };
+        if let Some(reply) = cx.auto_reply(&reply, &message.user) {
+            cx.app().outbox.say(Priority::Normal, reply);
+        }
+        Flow::Done
+    }
+}
+
+// Trigger words, questions and the !bot command (see AiTriggers). Own
+// messages are usually left alone (AI_REPLY_TO_SELF).
+pub struct Triggers;
+
+impl Stage<Chat> for Triggers {
+    fn name(&self) -> &'static str {
+        "triggers"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        let app = cx.app();
+        if is_own(cx, message) && !app.config.ai_triggers.reply_to_self {
+            return Flow::Done;
+        }
+        let (triggers, text) = (&app.config.ai_triggers, &message.text);
+        let roll = || triggers.is_command(text) || app.config.ai_params.should_reply();
+        let prompt = triggers.prompt(text).filter(|_| roll()).map(String::from);
+        let found = prompt.is_some();
+        *cx.prompt() = prompt;
+        if found {
+            Flow::Next
+        } else {
+            Flow::Done
+        }
+    }
+}
+
+// Asks the AI what `triggers` found, rate limited overall (AI_COOLDOWN) and
+// per viewer, and not at all while chat is too fast with CHAT_SPEED_ACTIONS
+// pause_ai, or the shield is up with SHIELD_ACTIONS pause_ai
+#[derive(Default)]
+pub struct Ai {
+    // When the AI last answered anyone
+    last_reply: Option<Instant>,
+}
+
+impl Stage<Chat> for Ai {
+    fn name(&self) -> &'static str {
+        "ai"
+    }
+
+    fn run(&mut self, cx: &mut (dyn ChatContext + '_), message: &ChatMessage) -> Flow {
+        let Some(prompt) = cx.prompt().take() else {
+            return Flow::Next;
+        };
+        let app = cx.app_mut();
+        if (app.chat_speed.is_fast() && app.config.chat_speed.does(SpeedAction::PauseAi))
+            || app.shielded(ShieldAction::PauseAi)
+        {
+            return Flow::Next;
+        }
+        let user = &message.user;
+        let cooldown = app.config.ai_triggers.cooldown;
+        let rested = self.last_reply.is_none_or(|t| t.elapsed() >= cooldown);
+        if rested && !app.on_ai_cooldown(user) {
+            let now = Instant::now();
+            self.last_reply = Some(now);
+            app.chatter(user).last_ai_reply = Some(now);
+
+            // Format prompt with username for context
+            cx.ask_ai(user, format!("User {}: {}", user, prompt));
+        }
+        Flow::Next
+    }
+}
@@ -0,0 +1,160 @@
+use crate::sqlite::{Connection, Store, Value};
+use crate::state::AppEvent;
+use anyhow::Result;
+use std::path::Path;
+use std::sync::mpsc;
+
+// Everything that happens in chat, kept in an SQLite database (CHAT_LOG) for
+// stats, quotes and looking back after the stream. Writes go through a
+// thread of their own, so a slow disk never holds up the event loop.
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY,
+    message_id TEXT,
+    channel TEXT NOT NULL,
+    user TEXT NOT NULL,
+    message TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS messages_user ON messages (user);
+CREATE INDEX IF NOT EXISTS messages_timestamp ON messages (timestamp);
+
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    channel TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    user TEXT,
+    detail TEXT,
+    timestamp TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_kind ON events (kind, timestamp);
+";
+
+pub enum Record {
+    Message {
+        message_id: String,
+        user: String,
+        text: String,
+    },
+    // join, leave, alert, redemption, moderation, ...
+    Event {
+        kind: &'static str,
+        user: Option<String>,
+        detail: String,
+    },
+}
+
+/// UTC, to the second; sorts as text.
+pub fn timestamp() -> String {
+    jiff::Timestamp::now()
+        .strftime("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
+// A disabled log (CHAT_LOG=off, or the database couldn't be opened) takes
+// records and drops them
+#[derive(Clone, Default)]
+pub struct ChatLog {
+    tx: Option<mpsc::Sender<(String, Record)>>,
+}
+
+impl ChatLog {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Open (or create) the database and start the writer. Write errors are
+    /// reported on `events`.
+    pub fn open(
+        path: &Path,
+        channel: String,
+        events: tokio::sync::mpsc::UnboundedSender<AppEvent>,
+    ) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?.into_connection();
+        db.execute_batch("PRAGMA journal_mode = WAL;")?;
+
+        let (tx, rx) = mpsc::channel();
+        crate::sqlite::spawn_writer(move || write_records(db, channel, rx, events));
+        Ok(Self { tx: Some(tx) })
+    }
+
+    pub fn record(&self, record: Record) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send((timestamp(), record));
+        }
+    }
+}
+
+fn write_records(
+    db: Connection,
+    channel: String,
+    rx: mpsc::Receiver<(String, Record)>,
+    events: tokio::sync::mpsc::UnboundedSender<AppEvent>,
+) {
+    // Report a failing disk once, not for every message
+    let mut failing = false;
+    while let Ok(first) = rx.recv() {
+        // Whatever piled up meanwhile goes into the same transaction
+        let batch: Vec<_> = std::iter::once(first).chain(rx.try_iter()).collect();
+        let written = db.execute_batch("BEGIN").and_then(|()| {
+            for (timestamp, record) in &batch {
+                insert(&db, &channel, timestamp, record)?;
+            }
+            db.execute_batch("COMMIT")
+        });
+        let written = written.or_else(|_| {
+            let _ = db.execute_batch("ROLLBACK");
+            // One record that can't be written shouldn't take the rest of
+            // the batch with it: they go in one at a time instead, and only
+            // the failing ones are lost
+            let mut first_error = None;
+            for (timestamp, record) in &batch {
+                if let Err(e) = insert(&db, &channel, timestamp, record) {
+                    first_error.get_or_insert(e);
+                }
+            }
+            first_error.map_or(Ok(()), Err)
+        });
+        match written {
+            Ok(()) => failing = false,
+            Err(e) => {
+                if !failing {
+                    let _ = events.send(AppEvent::Error(format!("Chat log write failed: {:#}", e)));
+                }
+                failing = true;
+            }
+        }
+    }
+}
+
+fn insert(db: &Connection, channel: &str, timestamp: &str, record: &Record) -> Result<()> {
+    match record {
+        Record::Message {
+            message_id,
+            user,
+            text,
+        } => db.execute(
+            "INSERT INTO messages (message_id, channel, user, message, timestamp)
+             VALUES (?, ?, ?, ?, ?)",
+            &[
+                Value::from(Some(message_id.as_str()).filter(|id| !id.is_empty())),
+                channel.into(),
+                user.as_str().into(),
+                text.as_str().into(),
+                timestamp.into(),
+            ],
+        ),
+        Record::Event { kind, user, detail } => db.execute(
+            "INSERT INTO events (channel, kind, user, detail, timestamp) VALUES (?, ?, ?, ?, ?)",
+            &[
+                channel.into(),
+                (*kind).into(),
+                user.as_deref().into(),
+                detail.as_str().into(),
+                timestamp.into(),
+            ],
+        ),
+    }?;
+    Ok(())
+}
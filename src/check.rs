@@ -0,0 +1,335 @@
+use choui_the_no_gui_chatbot::{
+    ai,
+    config::{self, Config, OverlayConfig},
+    config_file::config_path,
+    keys::Keymap,
+    secrets::{self, SecretStore, SECRET_VARS},
+    tts::Backend,
+    twitch::{self, get_user_id, load_token_cache, refresh_token, save_token_cache, token_info},
+};
+
+// `choui check`: runs through everything the bot needs before going live and
+// prints one pass/fail line per item, so problems show up before stream time
+// instead of in the middle of it. Nothing is posted to chat.
+
+enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Default)]
+struct Report {
+    failures: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn line(&mut self, outcome: Outcome, what: &str, detail: impl std::fmt::Display) {
+        let tag = match outcome {
+            Outcome::Pass => " ok ",
+            Outcome::Warn => {
+                self.warnings += 1;
+                "warn"
+            }
+            Outcome::Fail => {
+                self.failures += 1;
+                "FAIL"
+            }
+        };
+        println!("[{}] {:<16} {}", tag, what, detail);
+    }
+
+    fn result(&mut self, what: &str, result: anyhow::Result<impl std::fmt::Display>) {
+        match result {
+            Ok(detail) => self.line(Outcome::Pass, what, detail),
+            Err(e) => self.line(Outcome::Fail, what, format!("{:#}", e)),
+        }
+    }
+}
+
+/// `file_loaded` is how reading .env and choui.toml went.
+/// Returns whether everything passed (warnings allowed).
+pub async fn run(file_loaded: anyhow::Result<()>) -> bool {
+    let mut report = Report::default();
+    println!("Checking choui setup...\n");
+
+    let path = config_path();
+    match file_loaded {
+        Err(e) => report.line(Outcome::Fail, "Config file", format!("{:#}", e)),
+        Ok(()) if path.exists() => report.line(Outcome::Pass, "Config file", path.display()),
+        Ok(()) => report.line(
+            Outcome::Warn,
+            "Config file",
+            "No choui.toml, using the environment only",
+        ),
+    }
+    let config = match Config::from_env() {
+        Ok(config) => {
+            report.line(Outcome::Pass, "Settings", "All values valid");
+            Some(config)
+        }
+        Err(e) => {
+            report.line(Outcome::Fail, "Settings", format!("{:#}", e));
+            None
+        }
+    };
+    report.result(
+        "Overlay config",
+        OverlayConfig::load().map(|overlay| format!("{} window(s)", overlay.windows.len())),
+    );
+    report.result(
+        "Goal",
+        config::load_goal().map(|goal| match goal {
+            Some(goal) => format!("{} {}/{}", goal.label, goal.current, goal.target),
+            None => "None set".to_string(),
+        }),
+    );
+    report.result("Key bindings", Keymap::load().map(|_| "Valid"));
+
+    match config {
+        Some(mut config) => {
+            check_secrets(&mut report, &config);
+            check_twitch(&mut report, &mut config).await;
+            report.result("AI provider", ai::ping(&config).await);
+            check_tts(&mut report, &config);
+            check_audio(&mut report, &config);
+        }
+        None => report.line(
+            Outcome::Warn,
+            "Connections",
+            "Skipped Twitch, AI and audio checks until the settings are fixed",
+        ),
+    }
+    check_graphics(&mut report);
+
+    println!();
+    if report.failures == 0 {
+        println!("All checks passed ({} warnings).", report.warnings);
+    } else {
+        println!(
+            "{} check(s) failed, {} warning(s).",
+            report.failures, report.warnings
+        );
+    }
+    report.failures == 0
+}
+
+fn check_secrets(report: &mut Report, config: &Config) {
+    if config.secret_store != SecretStore::Keyring {
+        return;
+    }
+    // Any lookup shows whether the keyring answers
+    match secrets::get(SECRET_VARS[0]) {
+        Ok(_) => report.line(Outcome::Pass, "Keyring", "Reachable"),
+        Err(e) => report.line(
+            Outcome::Warn,
+            "Keyring",
+            format!("{:#}; secrets stay in files", e),
+        ),
+    }
+}
+
+async fn check_twitch(report: &mut Report, config: &mut Config) {
+    let client = reqwest::Client::new();
+    let cached = match load_token_cache(config) {
+        Ok(cached) => cached,
+        Err(_) => {
+            report.line(
+                Outcome::Fail,
+                "Twitch token",
+                "Not signed in yet, start the bot once to authorize it",
+            );
+            return;
+        }
+    };
+
+    let mut token = cached.access_token;
+    let mut info = token_info(&client, config, &token).await;
+    if matches!(info, Ok(None)) {
+        // Same as startup: an expired token gets refreshed
+        if let Some(rt) = cached.refresh_token {
+            if let Ok(new_token) = refresh_token(&client, config, &rt).await {
+                let _ = save_token_cache(config, &new_token);
+                token = new_token.access_token;
+                info = token_info(&client, config, &token).await;
+            }
+        }
+    }
+    let info = match info {
+        Ok(Some(info)) => info,
+        Ok(None) => {
+            report.line(
+                Outcome::Fail,
+                "Twitch token",
+                "Expired and couldn't be refreshed, start the bot to sign in again",
+            );
+            return;
+        }
+        Err(e) => {
+            report.line(
+                Outcome::Fail,
+                "Twitch token",
+                format!("Can't reach Twitch: {:#}", e),
+            );
+            return;
+        }
+    };
+    report.line(
+        Outcome::Pass,
+        "Twitch token",
+        format!(
+            "Signed in as {}, valid for {}h",
+            info.login.as_deref().unwrap_or("?"),
+            info.expires_in / 3600
+        ),
+    );
+
+    let missing: Vec<&str> = twitch::SCOPES
+        .iter()
+        .copied()
+        .filter(|scope| !info.scopes.iter().any(|s| s == scope))
+        .collect();
+    if missing.is_empty() {
+        report.line(Outcome::Pass, "Token scopes", "All granted");
+    } else {
+        report.line(
+            Outcome::Fail,
+            "Token scopes",
+            format!(
+                "Missing {}; delete {} and sign in again",
+                missing.join(", "),
+                twitch::token_cache_path(config)
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|_| "the token file".to_string())
+            ),
+        );
+    }
+
+    config.oauth_token = Some(token);
+    match (&config.channel_user_id, &config.channel_name) {
+        (Some(id), _) => report.line(Outcome::Pass, "Channel", format!("id {}", id)),
+        (None, Some(name)) => {
+            let resolved = get_user_id(&client, config, name)
+                .await
+                .map(|id| format!("{} (id {})", name, id));
+            report.result("Channel", resolved);
+        }
+        (None, None) => report.line(
+            Outcome::Fail,
+            "Channel",
+            "Set CHANNEL_NAME or CHANNEL_USER_ID",
+        ),
+    }
+}
+
+fn check_tts(report: &mut Report, config: &Config) {
+    let tts = &config.tts;
+    let programs: &[&str] = match tts.backend {
+        Backend::Say => &["say"],
+        Backend::Sapi => &["powershell"],
+        Backend::Piper => &["piper"],
+        Backend::Espeak => &["espeak-ng", "espeak"],
+    };
+    match programs.iter().find(|program| on_path(program)) {
+        Some(program) => report.line(
+            Outcome::Pass,
+            "TTS engine",
+            format!("{} ({})", tts.backend.name(), program),
+        ),
+        // espeak is the fallback for the others, so only its absence is fatal
+        None if tts.backend != Backend::Espeak && (on_path("espeak-ng") || on_path("espeak")) => {
+            report.line(
+                Outcome::Warn,
+                "TTS engine",
+                format!("{} not found, espeak will read instead", programs[0]),
+            )
+        }
+        None => report.line(
+            Outcome::Fail,
+            "TTS engine",
+            format!("{} not found on PATH", programs.join(" or ")),
+        ),
+    }
+    if let Some(model) = &tts.piper_model {
+        if !std::path::Path::new(model).exists() {
+            report.line(Outcome::Fail, "Piper model", format!("{} not found", model));
+        }
+    }
+    for (kind, voice) in &tts.cloud {
+        report.line(
+            Outcome::Pass,
+            "Cloud voice",
+            format!(
+                "{} messages via {:?} ({})",
+                kind.name(),
+                voice.provider,
+                voice.voice
+            ),
+        );
+    }
+}
+
+fn check_audio(report: &mut Report, config: &Config) {
+    let (_stream, _handle) = match rodio::OutputStream::try_default() {
+        Ok(output) => output,
+        Err(e) => {
+            report.line(Outcome::Fail, "Audio output", e);
+            return;
+        }
+    };
+    report.line(Outcome::Pass, "Audio output", "Default device opened");
+
+    let join_sound = std::fs::File::open(&config.join_sound)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| Ok(rodio::Decoder::new(std::io::BufReader::new(file))?));
+    match join_sound {
+        Ok(_) => report.line(Outcome::Pass, "Join sound", &config.join_sound),
+        Err(e) => report.line(
+            Outcome::Warn,
+            "Join sound",
+            format!("{}: {}", config.join_sound, e),
+        ),
+    }
+}
+
+// Emotes render as images only with a graphics protocol; otherwise they fall
+// back to half-block characters, which still works but looks rough
+fn check_graphics(report: &mut Report) {
+    use ratatui_image::picker::{Picker, ProtocolType};
+    match Picker::from_termios() {
+        Ok(mut picker) => match picker.guess_protocol() {
+            ProtocolType::Halfblocks => report.line(
+                Outcome::Warn,
+                "Terminal images",
+                "No graphics protocol, emotes will be drawn with half blocks",
+            ),
+            protocol => report.line(
+                Outcome::Pass,
+                "Terminal images",
+                format!(
+                    "{:?}, font {}x{}px",
+                    protocol, picker.font_size.0, picker.font_size.1
+                ),
+            ),
+        },
+        Err(e) => report.line(
+            Outcome::Warn,
+            "Terminal images",
+            format!(
+                "Can't query the terminal ({}), run this in the one you stream from",
+                e
+            ),
+        ),
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| {
+        let candidate = dir.join(program);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::io::Write;
+use std::sync::Mutex;
+
+// Copy text to the system clipboard through arboard (X11, Wayland, macOS,
+// Windows). Over SSH, or without a display server to talk to, it falls back
+// to the OSC 52 escape sequence: the terminal does the copying then, if it
+// supports it; tmux needs `set -g set-clipboard on`.
+
+// On X11 and Wayland the copied text is served by whoever copied it, for as
+// long as they're around, so the handle is kept for the whole run
+static SYSTEM: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+/// Where the text went
+#[derive(Debug)]
+pub enum Copied {
+    System,
+    // The system clipboard couldn't be used (why, if it was tried); the
+    // terminal may or may not have taken the escape sequence
+    Terminal(Option<String>),
+}
+
+pub fn copy(text: &str) -> Result<Copied> {
+    // Over SSH the system clipboard would be the remote machine's
+    if std::env::var_os("SSH_CONNECTION").is_some() {
+        return osc52(text).map(|_| Copied::Terminal(None));
+    }
+    match copy_system(text) {
+        Ok(()) => Ok(Copied::System),
+        Err(e) => osc52(text).map(|_| Copied::Terminal(Some(format!("{:#}", e)))),
+    }
+}
+
+fn copy_system(text: &str) -> Result<()> {
+    let mut system = SYSTEM.lock().unwrap_or_else(|e| e.into_inner());
+    let clipboard = match system.as_mut() {
+        Some(clipboard) => clipboard,
+        None => system.insert(arboard::Clipboard::new()?),
+    };
+    clipboard.set_text(text)?;
+    Ok(())
+}
+
+fn osc52(text: &str) -> Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut sequence = format!("\x1b]52;c;{}\x07", encoded);
+
+    // tmux swallows OSC 52 unless it's wrapped in a passthrough sequence
+    if std::env::var_os("TMUX").is_some() {
+        sequence = format!("\x1bPtmux;\x1b{}\x1b\\", sequence);
+    }
+
+    let mut stdout = std::io::stdout();
+    stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|_| stdout.flush())
+        .context("Failed to write clipboard escape sequence")
+}
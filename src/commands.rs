@@ -0,0 +1,476 @@
+use crate::config::{Config, VolumeChannel};
+use crate::modlog::{ModEvent, ModKind};
+use crate::timers::Timer;
+use crate::tts::{PITCH_RANGE, RATE_RANGE};
+use crate::twitch::{ban_user, create_clip, get_user_id, send_announcement, update_channel_title};
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use tokio::sync::mpsc;
+
+// Slash commands typed into the TUI input box.
+// Anything starting with '/' is parsed here instead of being sent raw to chat.
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "timeout",
+        usage: "/timeout <user> [seconds] [reason]",
+        help: "Time a user out (default 600s, max 1209600s).",
+    },
+    CommandSpec {
+        name: "ban",
+        usage: "/ban <user> [reason]",
+        help: "Permanently ban a user from the channel.",
+    },
+    CommandSpec {
+        name: "title",
+        usage: "/title <text>",
+        help: "Change the stream title.",
+    },
+    CommandSpec {
+        name: "clip",
+        usage: "/clip",
+        help: "Create a clip of the last ~30 seconds.",
+    },
+    CommandSpec {
+        name: "announce",
+        usage: "/announce [blue|green|orange|purple] <message>",
+        help: "Send a highlighted announcement to chat.",
+    },
+    CommandSpec {
+        name: "me",
+        usage: "/me <text>",
+        help: "Send an action message (italic, in your name color).",
+    },
+    CommandSpec {
+        name: "mute",
+        usage: "/mute <user>",
+        help: "Hide (or unhide) a user's messages in this TUI only.",
+    },
+    CommandSpec {
+        name: "tts",
+        usage: "/tts [on|off|skip|clear|rate N|pitch N|voice [name]]",
+        help: "Toggle TTS (F8), skip the current message (Shift+F8), clear the queue (Ctrl+F8), or set rate/pitch (percent) and voice.",
+    },
+    CommandSpec {
+        name: "volume",
+        usage: "/volume [tts|join|alert] [0-100]",
+        help: "Show volumes, or set the volume of TTS, join sounds or alert sounds.",
+    },
+    CommandSpec {
+        name: "timer",
+        usage: "/timer [list|add <minutes>[/<messages>] <text>|remove <n>|on|off]",
+        help: "List the timed messages, add or remove one until the next restart, or pause them all.",
+    },
+    CommandSpec {
+        name: "retry",
+        usage: "/retry",
+        help: "Send the chat messages that failed to go out again.",
+    },
+    CommandSpec {
+        name: "diagnostics",
+        usage: "/diagnostics",
+        help: "Save the bot's state and recent events to a JSON file for a bug report (Ctrl+G).",
+    },
+    CommandSpec {
+        name: "help",
+        usage: "/help [command]",
+        help: "List commands, or show usage for one command.",
+    },
+];
+
+const MAX_TIMEOUT_SECS: u32 = 1_209_600; // 2 weeks, Twitch's limit
+const DEFAULT_TIMEOUT_SECS: u32 = 600;
+const MAX_TITLE_LEN: usize = 140;
+const ANNOUNCE_COLORS: &[&str] = &["blue", "green", "orange", "purple"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlashCommand {
+    Timeout {
+        user: String,
+        seconds: u32,
+        reason: String,
+    },
+    Ban {
+        user: String,
+        reason: String,
+    },
+    Title(String),
+    Clip,
+    Announce {
+        message: String,
+        color: String,
+    },
+    Me(String),
+    Mute(String),
+    Tts(TtsControl),
+    // No channel shows every volume, no level shows that channel's
+    Volume {
+        channel: Option<VolumeChannel>,
+        level: Option<u8>,
+    },
+    Timer(TimerControl),
+    Retry,
+    Diagnostics,
+    Help(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TtsControl {
+    Toggle,
+    On,
+    Off,
+    Skip,
+    Clear,
+    // No value shows the current settings
+    Rate(Option<u16>),
+    Pitch(Option<u16>),
+    // No name goes back to the default voice
+    Voice(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimerControl {
+    List,
+    Add(Timer),
+    // Numbered as in the list, from 1
+    Remove(usize),
+    On,
+    Off,
+}
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    let name = name.trim_start_matches('/');
+    COMMANDS.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+/// Parse the input box contents.
+/// Returns `None` when the input is not a slash command (send it as chat),
+/// or `Some(Err)` with a user-facing message when validation fails.
+pub fn parse(input: &str) -> Option<Result<SlashCommand>> {
+    let input = input.trim();
+    let rest = input.strip_prefix('/')?;
+
+    let (name, args) = match rest.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (rest, ""),
+    };
+
+    Some(parse_command(&name.to_lowercase(), args))
+}
+
+fn parse_command(name: &str, args: &str) -> Result<SlashCommand> {
+    let spec = match find_command(name) {
+        Some(spec) => spec,
+        None => bail!("Unknown command /{}. Type /help for a list.", name),
+    };
+    let usage = || format!("Usage: {}", spec.usage);
+
+    let cmd = match spec.name {
+        "timeout" => {
+            let mut parts = args.splitn(2, char::is_whitespace);
+            let user = parse_user(parts.next()).with_context(usage)?;
+            let rest = parts.next().unwrap_or("").trim();
+
+            // Seconds are optional; if the next word isn't a number it's the start of the reason
+            let (seconds, reason) = match rest.split_once(char::is_whitespace) {
+                Some((first, reason)) if first.parse::<u32>().is_ok() => {
+                    (first.parse::<u32>()?, reason.trim())
+                }
+                _ => match rest.parse::<u32>() {
+                    Ok(secs) => (secs, ""),
+                    Err(_) => (DEFAULT_TIMEOUT_SECS, rest),
+                },
+            };
+
+            if seconds == 0 || seconds > MAX_TIMEOUT_SECS {
+                bail!(
+                    "Timeout must be between 1 and {} seconds. {}",
+                    MAX_TIMEOUT_SECS,
+                    usage()
+                );
+            }
+
+            SlashCommand::Timeout {
+                user,
+                seconds,
+                reason: reason.to_string(),
+            }
+        }
+        "ban" => {
+            let mut parts = args.splitn(2, char::is_whitespace);
+            let user = parse_user(parts.next()).with_context(usage)?;
+            SlashCommand::Ban {
+                user,
+                reason: parts.next().unwrap_or("").trim().to_string(),
+            }
+        }
+        "title" => {
+            if args.is_empty() {
+                bail!(usage());
+            }
+            if args.chars().count() > MAX_TITLE_LEN {
+                bail!("Title is limited to {} characters.", MAX_TITLE_LEN);
+            }
+            SlashCommand::Title(args.to_string())
+        }
+        "clip" => SlashCommand::Clip,
+        "announce" => {
+            let (color, message) = match args.split_once(char::is_whitespace) {
+                Some((first, rest)) if ANNOUNCE_COLORS.contains(&first.to_lowercase().as_str()) => {
+                    (first.to_lowercase(), rest.trim())
+                }
+                _ => ("primary".to_string(), args),
+            };
+            if message.is_empty() {
+                bail!(usage());
+            }
+            SlashCommand::Announce {
+                message: message.to_string(),
+                color,
+            }
+        }
+        "me" => {
+            if args.is_empty() {
+                bail!(usage());
+            }
+            SlashCommand::Me(args.to_string())
+        }
+        "mute" => SlashCommand::Mute(parse_user(Some(args)).with_context(usage)?),
+        "tts" => {
+            let (action, value) = match args.split_once(char::is_whitespace) {
+                Some((action, value)) => (action, value.trim()),
+                None => (args, ""),
+            };
+            let percent = |range: std::ops::RangeInclusive<u16>| -> Result<Option<u16>> {
+                if value.is_empty() {
+                    return Ok(None);
+                }
+                value
+                    .trim_end_matches('%')
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|value| range.contains(value))
+                    .map(Some)
+                    .with_context(|| {
+                        format!(
+                            "{} must be {} to {} percent.",
+                            action,
+                            range.start(),
+                            range.end()
+                        )
+                    })
+            };
+            SlashCommand::Tts(match action.to_lowercase().as_str() {
+                "" => TtsControl::Toggle,
+                "on" | "unmute" => TtsControl::On,
+                "off" | "mute" => TtsControl::Off,
+                "skip" => TtsControl::Skip,
+                "clear" => TtsControl::Clear,
+                "rate" | "speed" => TtsControl::Rate(percent(RATE_RANGE)?),
+                "pitch" => TtsControl::Pitch(percent(PITCH_RANGE)?),
+                // Voice names can have spaces ("Microsoft Zira Desktop")
+                "voice" => TtsControl::Voice(
+                    Some(value.to_string()).filter(|name| !name.is_empty() && name != "default"),
+                ),
+                _ => bail!(usage()),
+            })
+        }
+        "volume" => {
+            let mut parts = args.split_whitespace();
+            let channel = match parts.next() {
+                None => None,
+                Some(name) => Some(VolumeChannel::from_name(name).with_context(usage)?),
+            };
+            let level = match parts.next() {
+                None => None,
+                Some(level) => Some(
+                    level
+                        .trim_end_matches('%')
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|level| *level <= 100)
+                        .with_context(|| format!("Volume must be 0 to 100. {}", usage()))?,
+                ),
+            };
+            if parts.next().is_some() {
+                bail!(usage());
+            }
+            SlashCommand::Volume { channel, level }
+        }
+        "timer" => {
+            let (action, value) = match args.split_once(char::is_whitespace) {
+                Some((action, value)) => (action, value.trim()),
+                None => (args, ""),
+            };
+            SlashCommand::Timer(match action.to_lowercase().as_str() {
+                "" | "list" => TimerControl::List,
+                "add" => TimerControl::Add(Timer::parse(value)?),
+                "remove" | "delete" => {
+                    TimerControl::Remove(value.parse().ok().filter(|n| *n > 0).with_context(usage)?)
+                }
+                "on" => TimerControl::On,
+                "off" => TimerControl::Off,
+                _ => bail!(usage()),
+            })
+        }
+        "retry" => SlashCommand::Retry,
+        "diagnostics" => SlashCommand::Diagnostics,
+        "help" => SlashCommand::Help(if args.is_empty() {
+            None
+        } else {
+            Some(args.trim_start_matches('/').to_lowercase())
+        }),
+        _ => unreachable!("every CommandSpec has a parser"),
+    };
+
+    Ok(cmd)
+}
+
+fn parse_user(arg: Option<&str>) -> Result<String> {
+    let user = arg.unwrap_or("").trim().trim_start_matches('@');
+    if user.is_empty() {
+        bail!("Missing user");
+    }
+    if !user.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("'{}' is not a valid Twitch username", user);
+    }
+    Ok(user.to_lowercase())
+}
+
+/// Lines of help text for `/help` (all commands) or `/help <command>`.
+pub fn help_lines(command: Option<&str>) -> Vec<String> {
+    match command {
+        Some(name) => match find_command(name) {
+            Some(spec) => vec![format!("{} - {}", spec.usage, spec.help)],
+            None => vec![format!("Unknown command /{}", name)],
+        },
+        None => COMMANDS
+            .iter()
+            .map(|spec| format!("{:<45} {}", spec.usage, spec.help))
+            .collect(),
+    }
+}
+
+/// Tab-completion for command names.
+/// Returns the new input value plus all candidates, or `None` if nothing matches.
+pub fn complete(input: &str) -> Option<(String, Vec<&'static str>)> {
+    let prefix = input.strip_prefix('/')?;
+    if prefix.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let prefix = prefix.to_lowercase();
+    let candidates: Vec<&'static str> = COMMANDS
+        .iter()
+        .map(|c| c.name)
+        .filter(|name| name.starts_with(&prefix))
+        .collect();
+
+    let completed = match candidates.as_slice() {
+        [] => return None,
+        [only] => format!("/{} ", only),
+        [first, rest @ ..] => {
+            // Extend to the longest common prefix of all candidates
+            let mut common = first.len();
+            for name in rest {
+                common = common.min(
+                    first
+                        .bytes()
+                        .zip(name.bytes())
+                        .take_while(|(a, b)| a == b)
+                        .count(),
+                );
+            }
+            format!("/{}", &first[..common])
+        }
+    };
+
+    Some((completed, candidates))
+}
+
+/// The mod log entry for a ban or timeout by `moderator`; None for other commands.
+pub fn mod_event(cmd: &SlashCommand, moderator: &str) -> Option<ModEvent> {
+    let (kind, user, detail) = match cmd {
+        SlashCommand::Timeout {
+            user,
+            seconds,
+            reason,
+        } if reason.is_empty() => (ModKind::Timeout, user, format!("{}s", seconds)),
+        SlashCommand::Timeout {
+            user,
+            seconds,
+            reason,
+        } => (ModKind::Timeout, user, format!("{}s, {}", seconds, reason)),
+        SlashCommand::Ban { user, reason } => (ModKind::Ban, user, reason.clone()),
+        _ => return None,
+    };
+    Some(ModEvent {
+        kind,
+        user: Some(user.clone()),
+        moderator: Some(moderator.to_string()),
+        detail,
+    })
+}
+
+/// Run a parsed command against Helix (or IRC for `/me`).
+/// Returns a short confirmation to show in the TUI.
+pub async fn execute(
+    cmd: SlashCommand,
+    client: &Client,
+    config: &Config,
+    irc_tx: &mpsc::UnboundedSender<String>,
+) -> Result<String> {
+    match cmd {
+        SlashCommand::Timeout {
+            user,
+            seconds,
+            reason,
+        } => {
+            let user_id = get_user_id(client, config, &user).await?;
+            ban_user(client, config, &user_id, Some(seconds), &reason).await?;
+            Ok(format!("Timed out {} for {}s", user, seconds))
+        }
+        SlashCommand::Ban { user, reason } => {
+            let user_id = get_user_id(client, config, &user).await?;
+            ban_user(client, config, &user_id, None, &reason).await?;
+            Ok(format!("Banned {}", user))
+        }
+        SlashCommand::Title(title) => {
+            update_channel_title(client, config, &title).await?;
+            Ok(format!("Title changed to: {}", title))
+        }
+        SlashCommand::Clip => {
+            let url = create_clip(client, config).await?;
+            Ok(format!("Clip created: {}", url))
+        }
+        SlashCommand::Announce { message, color } => {
+            send_announcement(client, config, &message, &color).await?;
+            Ok("Announcement sent".to_string())
+        }
+        SlashCommand::Me(text) => {
+            // Helix has no action messages, so /me goes over the IRC connection
+            let channel = config
+                .channel_name
+                .as_ref()
+                .context("Channel name missing")?;
+            irc_tx
+                .send(format!("PRIVMSG #{} :\x01ACTION {}\x01", channel, text))
+                .context("IRC connection closed")?;
+            Ok(format!("* {}", text))
+        }
+        // Local-only commands, handled by the TUI before execute() is reached
+        SlashCommand::Mute(user) => Ok(format!("Toggled mute for {}", user)),
+        SlashCommand::Tts(control) => Ok(format!("TTS: {:?}", control)),
+        SlashCommand::Volume { .. } => Ok("Volume changed".to_string()),
+        SlashCommand::Timer(control) => Ok(format!("Timers: {:?}", control)),
+        SlashCommand::Retry => Ok("Sending again".to_string()),
+        SlashCommand::Diagnostics => Ok("Diagnostics saved".to_string()),
+        SlashCommand::Help(name) => Ok(help_lines(name.as_deref()).join("\n")),
+    }
+}
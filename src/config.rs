@@ -1,5 +1,27 @@
-use anyhow::{Context, Result};
+use crate::api::ApiSettings;
+use crate::auto_replies::AutoReply;
+use crate::chat_speed::ChatSpeedSettings;
+use crate::config_file::{config_path, ConfigFile, Value};
+use crate::follows::FollowSettings;
+use crate::links::LinkSettings;
+use crate::matrix::MatrixSettings;
+use crate::minigames::GameSettings;
+use crate::mqtt::MqttSettings;
+use crate::secrets::{self, SecretStore, SECRET_VARS};
+use crate::shield::ShieldSettings;
+use crate::songs::SongSettings;
+use crate::state::{AlertKind, Goal, GoalKind};
+use crate::theme::Theme;
+use crate::timers::Timer;
+use crate::tips::TipSettings;
+use crate::translate::TranslateSettings;
+use crate::tts::Tts;
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub enum LlmProvider {
@@ -7,6 +29,268 @@ pub enum LlmProvider {
     Ollama,
 }
 
+// When the TUI drops to a few frames per second to save CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerMode {
+    // Low power while the terminal is unfocused or there's been no input for a while
+    Auto,
+    On,
+    Off,
+}
+
+impl PowerMode {
+    pub fn next(self) -> Self {
+        match self {
+            PowerMode::Auto => PowerMode::On,
+            PowerMode::On => PowerMode::Off,
+            PowerMode::Off => PowerMode::Auto,
+        }
+    }
+}
+
+// What a sound is for, so each kind gets its own volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeChannel {
+    Tts,
+    Join,
+    // Mention chime and goal fanfare
+    Alert,
+}
+
+impl VolumeChannel {
+    pub const ALL: [VolumeChannel; 3] = [
+        VolumeChannel::Tts,
+        VolumeChannel::Join,
+        VolumeChannel::Alert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VolumeChannel::Tts => "tts",
+            VolumeChannel::Join => "join",
+            VolumeChannel::Alert => "alert",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<VolumeChannel> {
+        Self::ALL
+            .into_iter()
+            .find(|channel| channel.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+// Playback volumes in percent (0-100), changed at runtime with /volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Volumes {
+    pub tts: u8,
+    pub join: u8,
+    pub alert: u8,
+}
+
+impl Volumes {
+    // TTS_VOLUME, JOIN_VOLUME, ALERT_VOLUME
+    fn from_env() -> Result<Self> {
+        let mut volumes = Self {
+            tts: 100,
+            join: 100,
+            alert: 100,
+        };
+        for channel in VolumeChannel::ALL {
+            let name = format!("{}_VOLUME", channel.name().to_uppercase());
+            if let Ok(value) = var(&name) {
+                let level = value
+                    .trim()
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|level| *level <= 100)
+                    .with_context(|| format!("{} must be 0 to 100, got '{}'", name, value))?;
+                volumes.set(channel, level);
+            }
+        }
+        Ok(volumes)
+    }
+
+    pub fn get(&self, channel: VolumeChannel) -> u8 {
+        match channel {
+            VolumeChannel::Tts => self.tts,
+            VolumeChannel::Join => self.join,
+            VolumeChannel::Alert => self.alert,
+        }
+    }
+
+    pub fn set(&mut self, channel: VolumeChannel, level: u8) {
+        let level = level.min(100);
+        match channel {
+            VolumeChannel::Tts => self.tts = level,
+            VolumeChannel::Join => self.join = level,
+            VolumeChannel::Alert => self.alert = level,
+        }
+    }
+}
+
+// When the bot answers chat on its own. Together these make it a greeter
+// (greetings only), a Q&A bot (command and questions) or a chatterbox.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AiTriggers {
+    // Welcome viewers as they join
+    pub greet_joins: bool,
+    // A viewer who's been to this many streams is a regular, and greeted as
+    // one coming back after missing at least `returning_after` in a row
+    pub regular_streams: u32,
+    pub returning_after: u32,
+    // Words or phrases that get a reply wherever they appear as whole words
+    pub words: Vec<String>,
+    // Messages ending in a question mark
+    pub questions: bool,
+    // Prefix that always asks the bot, the rest of the message is the prompt
+    pub command: Option<String>,
+    // Least time between two replies, and before the same viewer gets another
+    pub cooldown: Duration,
+    pub user_cooldown: Duration,
+    // Answer the bot's own messages; only the cooldown keeps it from talking
+    // to itself
+    pub reply_to_self: bool,
+}
+
+impl AiTriggers {
+    // AI_GREET_JOINS, AI_REGULAR_STREAMS, AI_RETURNING_AFTER,
+    // AI_TRIGGER_WORDS, AI_TRIGGER_QUESTIONS, AI_COMMAND, AI_COOLDOWN,
+    // AI_USER_COOLDOWN, AI_REPLY_TO_SELF
+    fn from_env() -> Result<Self> {
+        let streams = |name: &str, default: u32| -> Result<u32> {
+            match var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .with_context(|| {
+                        format!("{} must be a number of streams, got '{}'", name, value)
+                    }),
+                Err(_) => Ok(default),
+            }
+        };
+        Ok(Self {
+            greet_joins: env_flag("AI_GREET_JOINS", true)?,
+            regular_streams: streams("AI_REGULAR_STREAMS", 3)?,
+            returning_after: streams("AI_RETURNING_AFTER", 2)?,
+            words: var("AI_TRIGGER_WORDS")
+                .unwrap_or_else(|_| "hey,hello,hi,intro".to_string())
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            questions: env_flag("AI_TRIGGER_QUESTIONS", true)?,
+            command: var("AI_COMMAND")
+                .unwrap_or_else(|_| "!bot".to_string())
+                .split_whitespace()
+                .next()
+                .map(str::to_lowercase),
+            cooldown: seconds("AI_COOLDOWN", 1.0)?,
+            user_cooldown: seconds("AI_USER_COOLDOWN", 30.0)?,
+            reply_to_self: env_flag("AI_REPLY_TO_SELF", false)?,
+        })
+    }
+
+    // The text after the command, if the message starts with it
+    fn command_args<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let command = self.command.as_ref()?;
+        text.get(..command.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(command))
+            .map(|_| text[command.len()..].trim_end())
+            .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+            .map(str::trim_start)
+    }
+
+    /// Whether the message asks the bot directly with the command.
+    pub fn is_command(&self, text: &str) -> bool {
+        self.command_args(text.trim()).is_some()
+    }
+
+    /// The prompt to send for a chat message, if it should get a reply.
+    pub fn prompt<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let text = text.trim();
+        if let Some(rest) = self.command_args(text) {
+            return Some(rest).filter(|rest| !rest.is_empty());
+        }
+        if self.questions && text.ends_with('?') {
+            return Some(text);
+        }
+
+        // Compare on word boundaries, so "hi" doesn't fire on "this"
+        let words: String = text
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+            .collect();
+        let words = format!(
+            " {} ",
+            words.split_whitespace().collect::<Vec<_>>().join(" ")
+        );
+        self.words
+            .iter()
+            .any(|word| words.contains(&format!(" {} ", word)))
+            .then_some(text)
+    }
+}
+
+// Where Twitch and Gemini are. They can point elsewhere to try the bot
+// against a mock, like the Twitch CLI's `twitch mock-api start` and
+// `twitch event websocket start-server`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoints {
+    // Helix, e.g. https://api.twitch.tv/helix
+    pub helix: String,
+    // OAuth, e.g. https://id.twitch.tv/oauth2
+    pub auth: String,
+    pub eventsub: String,
+    // Chat over IRC, for joins and parts
+    pub irc: String,
+    // Up to the API version, e.g. .../v1beta
+    pub gemini: String,
+    // Socket.IO WebSockets for tips, with the query string up to the token
+    pub streamelements: String,
+    pub streamlabs: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            helix: "https://api.twitch.tv/helix".to_string(),
+            auth: "https://id.twitch.tv/oauth2".to_string(),
+            eventsub: "wss://eventsub.wss.twitch.tv/ws".to_string(),
+            irc: "wss://irc-ws.chat.twitch.tv:443".to_string(),
+            gemini: "https://generativelanguage.googleapis.com/v1beta".to_string(),
+            streamelements:
+                "wss://realtime.streamelements.com/socket.io/?EIO=3&transport=websocket".to_string(),
+            streamlabs: "wss://sockets.streamlabs.com/socket.io/?EIO=3&transport=websocket"
+                .to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    // TWITCH_API_URL, TWITCH_AUTH_URL, EVENTSUB_URL, GEMINI_URL
+    fn from_env() -> Self {
+        let defaults = Self::default();
+        let url = |name: &str, default: String| {
+            var(name)
+                .ok()
+                .map(|url| url.trim().trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty())
+                .unwrap_or(default)
+        };
+        Self {
+            helix: url("TWITCH_API_URL", defaults.helix),
+            auth: url("TWITCH_AUTH_URL", defaults.auth),
+            eventsub: url("EVENTSUB_URL", defaults.eventsub),
+            gemini: url("GEMINI_URL", defaults.gemini),
+            // IRC and the tip sockets are only ever changed by tests
+            ..defaults
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub bot_user_id: String,
@@ -16,17 +300,1074 @@ pub struct Config {
     pub client_id: String,
     // Access token is populated at runtime
     pub oauth_token: Option<String>,
+    // Chat messages the bot may send per 30 seconds (see outbox.rs)
+    pub chat_rate_limit: usize,
 
     pub llm_provider: LlmProvider,
     pub gemini_api_key: Option<String>,
     pub gemini_model: String,
     pub ollama_model: String,
     pub ollama_host: String,
+    // Hold AI replies on the AI tab until approved (Ctrl+A)
+    pub ai_require_approval: bool,
+    pub ai_triggers: AiTriggers,
+    pub ai_params: AiParams,
+
+    // Chat display filters (render-time only, the log keeps everything)
+    pub hide_bots: bool,
+    pub bot_accounts: Vec<String>,
+    pub hide_commands: bool,
+    pub mute_regex: Option<String>,
+    // Chat message stages turned off (see pipeline.rs), checked by main
+    pub skip_stages: Vec<String>,
+
+    pub theme: Theme,
+    // Show the name once for consecutive messages from the same user
+    pub group_messages: bool,
+    // Viewer counts that trigger a milestone alert when first reached, ascending
+    pub viewer_milestones: Vec<u64>,
+    // Initial state, toggled at runtime with F8
+    pub tts_enabled: bool,
+    pub tts: Tts,
+    // Messages waiting to be read before the oldest gets dropped
+    pub tts_queue_max: usize,
+    pub volumes: Volumes,
+    // Channel point reward (title or id) whose message is read aloud
+    pub tts_reward: Option<String>,
+    // Played when a viewer joins
+    pub join_sound: String,
+    pub power_mode: PowerMode,
+    // Redraws per second at most, however fast events come in
+    pub max_fps: u32,
+    // Where the Twitch token and API keys are kept
+    pub secret_store: SecretStore,
+    // The token, UI state, database and diagnostics (DATA_DIR), and emote
+    // downloads (CACHE_DIR); the platform's directories by default (paths.rs)
+    pub data_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    // Where the token and UI state older versions wrote are moved over from:
+    // the working directory, unless DATA_DIR was given
+    pub legacy_dir: Option<PathBuf>,
+    // The choui.toml in use (see config_path()), whether or not it exists
+    pub config_file: PathBuf,
+    // SQLite database every message and event is written to, None when off
+    pub chat_log: Option<PathBuf>,
+    // Lines each tab keeps in memory; older chat is only in the chat log
+    pub history_lines: usize,
+    // Posted in chat when the bot is quit
+    pub offline_message: Option<String>,
+    // Let the AI pick the quote for "!quote <request>" instead of a word match
+    pub quotes_ai: bool,
+    // Start !poll as a native Twitch poll instead of counting chat votes
+    pub poll_native: bool,
+    // How long a !poll runs unless it says otherwise
+    pub poll_duration: Duration,
+    // Let the AI announce the result
+    pub poll_ai: bool,
+    // Messages posted every so often while chat is active
+    pub timers: Vec<Timer>,
+    // Percent each timer's wait may be longer or shorter
+    pub timer_jitter: u8,
+    // !sr and the song request queue
+    pub songs: SongSettings,
+    // !gamble, !duel, !8ball and the points they're played for
+    pub games: GameSettings,
+    // !lurk and !unlurk, and whether the AI sees lurkers off
+    pub lurk_enabled: bool,
+    pub lurk_ai: bool,
+    // Thanking new followers, a batch at a time
+    pub follows: FollowSettings,
+    // What happens when chat gets fast
+    pub chat_speed: ChatSpeedSettings,
+    // Translating chat with the AI
+    pub translate: TranslateSettings,
+    // Spotting hate raids and bot spam, and what's done about them
+    pub shield: ShieldSettings,
+    // Welcome raiders with a message about what their streamer was streaming,
+    // by the AI when it answers
+    pub raid_welcome: bool,
+    // Give raiding channels a Twitch shoutout
+    pub raid_shoutout: bool,
+    // Deleting links from viewers without a !permit
+    pub links: LinkSettings,
+    // Canned answers given before the AI is asked, and how often each may be
+    pub auto_replies: Vec<AutoReply>,
+    pub auto_reply_cooldown: Duration,
+    // Where the Lua scripts are (see scripts.rs)
+    pub scripts_dir: PathBuf,
+    // The HTTP API for remote control
+    pub api: ApiSettings,
+    // Mirroring into a Matrix room
+    pub matrix: MatrixSettings,
+    // Publishing events to an MQTT broker
+    pub mqtt: MqttSettings,
+    // Donations from StreamElements and Streamlabs
+    pub tips: TipSettings,
+    pub endpoints: Endpoints,
+    // --demo: made-up chat instead of Twitch and canned AI replies (see demo.rs)
+    pub demo: bool,
+}
+
+// choui.toml keys and the environment variables they stand in for
+const FILE_KEYS: &[(&str, &str, &str)] = &[
+    ("twitch", "bot_user_id", "BOT_USER_ID"),
+    ("twitch", "channel_user_id", "CHANNEL_USER_ID"),
+    ("twitch", "channel_name", "CHANNEL_NAME"),
+    ("twitch", "client_id", "CLIENT_ID"),
+    ("twitch", "api_url", "TWITCH_API_URL"),
+    ("twitch", "auth_url", "TWITCH_AUTH_URL"),
+    ("twitch", "eventsub_url", "EVENTSUB_URL"),
+    ("twitch", "chat_rate_limit", "CHAT_RATE_LIMIT"),
+    ("ai", "provider", "LLM_PROVIDER"),
+    ("ai", "gemini_api_key", "GEMINI_API_KEY"),
+    ("ai", "gemini_model", "GEMINI_MODEL"),
+    ("ai", "gemini_url", "GEMINI_URL"),
+    ("ai", "ollama_host", "OLLAMA_HOST"),
+    ("ai", "ollama_model", "OLLAMA_MODEL"),
+    ("ai", "require_approval", "AI_REQUIRE_APPROVAL"),
+    ("ai", "greet_joins", "AI_GREET_JOINS"),
+    ("ai", "regular_streams", "AI_REGULAR_STREAMS"),
+    ("ai", "returning_after", "AI_RETURNING_AFTER"),
+    ("ai", "trigger_words", "AI_TRIGGER_WORDS"),
+    ("ai", "trigger_questions", "AI_TRIGGER_QUESTIONS"),
+    ("ai", "command", "AI_COMMAND"),
+    ("ai", "cooldown", "AI_COOLDOWN"),
+    ("ai", "user_cooldown", "AI_USER_COOLDOWN"),
+    ("ai", "reply_to_self", "AI_REPLY_TO_SELF"),
+    ("ai", "temperature", "AI_TEMPERATURE"),
+    ("ai", "max_tokens", "AI_MAX_TOKENS"),
+    ("ai", "reply_chance", "AI_REPLY_CHANCE"),
+    ("ai", "reply_style", "AI_REPLY_STYLE"),
+    ("ai", "memory_turns", "AI_MEMORY_TURNS"),
+    ("ai", "memory_ttl", "AI_MEMORY_TTL"),
+    ("ai", "personas", "AI_PERSONAS"),
+    ("ai", "persona", "AI_PERSONA"),
+    ("chat", "hide_bots", "HIDE_BOTS"),
+    ("chat", "bot_accounts", "BOT_ACCOUNTS"),
+    ("chat", "hide_commands", "HIDE_COMMANDS"),
+    ("chat", "mute_regex", "MUTE_REGEX"),
+    ("chat", "group_messages", "GROUP_MESSAGES"),
+    ("chat", "viewer_milestones", "VIEWER_MILESTONES"),
+    ("chat", "low_power", "LOW_POWER"),
+    ("chat", "max_fps", "MAX_FPS"),
+    ("chat", "log", "CHAT_LOG"),
+    ("chat", "history_lines", "HISTORY_LINES"),
+    ("chat", "offline_message", "OFFLINE_MESSAGE"),
+    ("chat", "skip_stages", "SKIP_STAGES"),
+    ("quotes", "ai", "QUOTES_AI"),
+    ("polls", "mode", "POLL_MODE"),
+    ("polls", "duration", "POLL_DURATION"),
+    ("polls", "ai", "POLL_AI"),
+    ("timers", "messages", "TIMERS"),
+    ("timers", "jitter", "TIMER_JITTER"),
+    ("songs", "requests", "SONG_REQUESTS"),
+    ("songs", "user_limit", "SONG_USER_LIMIT"),
+    ("songs", "queue_max", "SONG_QUEUE_MAX"),
+    ("songs", "player", "SONG_PLAYER"),
+    ("songs", "mpv_socket", "SONG_MPV_SOCKET"),
+    ("songs", "mpd_address", "SONG_MPD_ADDRESS"),
+    ("games", "enabled", "GAMES_ENABLED"),
+    ("games", "points_per_message", "GAMES_POINTS_PER_MESSAGE"),
+    ("games", "starting_points", "GAMES_STARTING_POINTS"),
+    ("games", "cooldown", "GAMES_COOLDOWN"),
+    ("games", "ai", "GAMES_AI"),
+    ("lurk", "enabled", "LURK_ENABLED"),
+    ("lurk", "ai", "LURK_AI"),
+    ("follows", "thank", "FOLLOW_THANKS"),
+    ("follows", "window", "FOLLOW_THANKS_WINDOW"),
+    ("follows", "cooldown", "FOLLOW_THANKS_COOLDOWN"),
+    ("chat_speed", "threshold", "CHAT_SPEED_THRESHOLD"),
+    ("chat_speed", "actions", "CHAT_SPEED_ACTIONS"),
+    ("chat_speed", "slow_mode", "CHAT_SPEED_SLOW_MODE"),
+    ("translate", "mode", "TRANSLATE"),
+    ("translate", "language", "TRANSLATE_LANGUAGE"),
+    ("translate", "output", "TRANSLATE_OUTPUT"),
+    ("shield", "enabled", "SHIELD"),
+    ("shield", "repeats", "SHIELD_REPEATS"),
+    ("shield", "links", "SHIELD_LINKS"),
+    ("shield", "fresh_joins", "SHIELD_FRESH_JOINS"),
+    ("shield", "account_age", "SHIELD_ACCOUNT_AGE"),
+    ("shield", "actions", "SHIELD_ACTIONS"),
+    ("shield", "duration", "SHIELD_DURATION"),
+    ("raids", "welcome", "RAID_WELCOME"),
+    ("raids", "shoutout", "RAID_SHOUTOUT"),
+    ("links", "protection", "LINK_PROTECTION"),
+    ("links", "permit_seconds", "LINK_PERMIT_SECONDS"),
+    ("links", "allowed_domains", "LINK_ALLOWED_DOMAINS"),
+    ("auto_replies", "replies", "AUTO_REPLIES"),
+    ("auto_replies", "cooldown", "AUTO_REPLY_COOLDOWN"),
+    ("scripts", "dir", "SCRIPTS_DIR"),
+    ("api", "enabled", "API_ENABLED"),
+    ("api", "address", "API_ADDRESS"),
+    ("api", "token", "API_TOKEN"),
+    ("api", "messages", "API_MESSAGES"),
+    ("api", "sounds", "API_SOUNDS"),
+    ("matrix", "enabled", "MATRIX_ENABLED"),
+    ("matrix", "homeserver", "MATRIX_HOMESERVER"),
+    ("matrix", "token", "MATRIX_TOKEN"),
+    ("matrix", "room", "MATRIX_ROOM"),
+    ("matrix", "admins", "MATRIX_ADMINS"),
+    ("mqtt", "enabled", "MQTT_ENABLED"),
+    ("mqtt", "broker", "MQTT_BROKER"),
+    ("mqtt", "tls", "MQTT_TLS"),
+    ("mqtt", "prefix", "MQTT_PREFIX"),
+    ("mqtt", "username", "MQTT_USERNAME"),
+    ("mqtt", "password", "MQTT_PASSWORD"),
+    ("tips", "streamelements_token", "STREAMELEMENTS_TOKEN"),
+    ("tips", "streamlabs_token", "STREAMLABS_TOKEN"),
+    ("tips", "read", "TIPS_READ"),
+    ("tips", "thank", "TIPS_THANK"),
+    ("theme", "name", "THEME"),
+    ("theme", "border", "THEME_BORDER"),
+    ("theme", "text", "THEME_TEXT"),
+    ("theme", "highlight", "THEME_HIGHLIGHT"),
+    ("theme", "selection", "THEME_SELECTION"),
+    ("theme", "mention", "THEME_MENTION"),
+    ("theme", "alert", "THEME_ALERT"),
+    ("theme", "input", "THEME_INPUT"),
+    ("theme", "emote", "THEME_EMOTE"),
+    ("theme", "dim", "THEME_DIM"),
+    ("theme", "username", "THEME_USERNAME"),
+    ("tts", "enabled", "TTS_ENABLED"),
+    ("tts", "backend", "TTS_BACKEND"),
+    ("tts", "piper_model", "PIPER_MODEL"),
+    ("tts", "rate", "TTS_RATE"),
+    ("tts", "pitch", "TTS_PITCH"),
+    ("tts", "voice", "TTS_LOCAL_VOICE"),
+    ("tts", "queue_max", "TTS_QUEUE_MAX"),
+    ("tts", "reward", "TTS_REWARD"),
+    ("tts", "redeem_voice", "TTS_REDEEM_VOICE"),
+    ("tts", "detect_language", "TTS_DETECT_LANGUAGE"),
+    ("tts", "language_voices", "TTS_LANGUAGE_VOICES"),
+    ("tts", "cloud_chat", "TTS_VOICE_CHAT"),
+    ("tts", "cloud_join", "TTS_VOICE_JOIN"),
+    ("tts", "cloud_ai", "TTS_VOICE_AI"),
+    ("tts", "cloud_redeem", "TTS_VOICE_REDEEM"),
+    ("tts", "cloud_char_limit", "TTS_CLOUD_CHAR_LIMIT"),
+    ("tts", "elevenlabs_api_key", "ELEVENLABS_API_KEY"),
+    ("tts", "elevenlabs_voice_id", "ELEVENLABS_VOICE_ID"),
+    ("tts", "elevenlabs_model", "ELEVENLABS_MODEL"),
+    ("tts", "openai_api_key", "OPENAI_API_KEY"),
+    ("tts", "openai_voice", "OPENAI_TTS_VOICE"),
+    ("tts", "openai_model", "OPENAI_TTS_MODEL"),
+    ("tts", "azure_speech_key", "AZURE_SPEECH_KEY"),
+    ("tts", "azure_region", "AZURE_SPEECH_REGION"),
+    ("tts", "azure_voice", "AZURE_TTS_VOICE"),
+    ("sounds", "join", "JOIN_SOUND"),
+    ("sounds", "tts_volume", "TTS_VOLUME"),
+    ("sounds", "join_volume", "JOIN_VOLUME"),
+    ("sounds", "alert_volume", "ALERT_VOLUME"),
+    ("secrets", "store", "SECRET_STORE"),
+    ("paths", "data", "DATA_DIR"),
+    ("paths", "cache", "CACHE_DIR"),
+];
+
+// Settings are looked up by their environment variable name: the process
+// environment first, then .env, then choui.toml. The last two are kept here
+// instead of being copied into the environment, so they can be reloaded
+// while the bot runs.
+static SETTINGS: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<HashMap<String, String>> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+thread_local! {
+    // Settings given to a ConfigBuilder, while it builds
+    static OVERRIDES: std::cell::RefCell<HashMap<String, String>> = Default::default();
+}
+
+/// The names of the settings choui.toml can hold, in file order.
+pub fn setting_names() -> impl Iterator<Item = &'static str> {
+    FILE_KEYS.iter().map(|(_, _, name)| *name)
+}
+
+/// One setting, like `std::env::var` but including .env and choui.toml.
+pub fn var(name: &str) -> Result<String, env::VarError> {
+    if let Some(value) = OVERRIDES.with(|o| o.borrow().get(name).cloned()) {
+        return Ok(value);
+    }
+    match env::var(name) {
+        Err(env::VarError::NotPresent) => settings()
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or(env::VarError::NotPresent),
+        found => found,
+    }
+}
+
+/// (Re)read .env and choui.toml (see config_path() for where from). On error
+/// the settings read before stay in place.
+pub fn load_settings() -> Result<()> {
+    let mut layered = HashMap::new();
+
+    let path = config_path();
+    if let Some(file) = ConfigFile::load(&path)? {
+        file_settings(&file, &mut layered)
+            .with_context(|| format!("Invalid {}", path.display()))?;
+    }
+    // .env goes on top of the file
+    if let Some(path) = dotenv_path() {
+        // The iterator is dotenv's only way to read the file without setting
+        // the variables
+        #[allow(deprecated)]
+        let items = dotenv::from_path_iter(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        for item in items {
+            let (name, value) = item.with_context(|| format!("Invalid {}", path.display()))?;
+            layered.insert(name, value);
+        }
+    }
+
+    // API keys kept in the keyring fill in for ones not set in the files
+    let store = env::var("SECRET_STORE")
+        .ok()
+        .or_else(|| layered.get("SECRET_STORE").cloned());
+    if store.is_some_and(|store| SecretStore::parse(&store).ok() == Some(SecretStore::Keyring)) {
+        for (name, value) in keyring_secrets() {
+            if env::var(name).is_err() && !layered.contains_key(*name) {
+                layered.insert(name.to_string(), value.clone());
+            }
+        }
+    }
+
+    *settings().write().unwrap() = layered;
+    Ok(())
+}
+
+// The keyring is asked once, on the first load: a reload follows every save
+// of choui.toml, and on some desktops each lookup can bring up an unlock
+// prompt. A secret stored while the bot runs is picked up on its next start.
+fn keyring_secrets() -> &'static [(&'static str, String)] {
+    static SECRETS: OnceLock<Vec<(&'static str, String)>> = OnceLock::new();
+    SECRETS.get_or_init(|| {
+        SECRET_VARS
+            .iter()
+            .filter_map(|name| Some((*name, secrets::get(name).ok()??)))
+            .collect()
+    })
+}
+
+/// The .env in use: the first one found from the current directory up.
+pub fn dotenv_path() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(".env"))
+        .find(|path| path.is_file())
+}
+
+fn file_settings(file: &ConfigFile, settings: &mut HashMap<String, String>) -> Result<()> {
+    let sections: HashSet<&str> = FILE_KEYS.iter().map(|(section, _, _)| *section).collect();
+    for section in sections {
+        // Catch typos; a misspelled key would otherwise be silently ignored
+        if let Some(key) = file
+            .keys(section)
+            .find(|key| !FILE_KEYS.iter().any(|(s, k, _)| *s == section && k == key))
+        {
+            bail!("Unknown key [{}] {}", section, key);
+        }
+    }
+    for (section, key, name) in FILE_KEYS {
+        let value = match file.get(section, key) {
+            // Timer messages, replies, personas and canned messages can have
+            // commas of their own
+            Some(Value::Array(items))
+                if [
+                    "TIMERS",
+                    "AUTO_REPLIES",
+                    "AI_PERSONAS",
+                    "API_MESSAGES",
+                    "API_SOUNDS",
+                ]
+                .contains(name) =>
+            {
+                items
+                    .iter()
+                    .map(Value::to_env_string)
+                    .collect::<Vec<_>>()
+                    .join("|")
+            }
+            Some(value) => value.to_env_string(),
+            None => continue,
+        };
+        settings.insert(name.to_string(), value);
+    }
+    Ok(())
+}
+
+const DEFAULT_BOT_ACCOUNTS: &str =
+    "nightbot,streamelements,streamlabs,moobot,fossabot,wizebot,soundalerts,sery_bot";
+
+// How long the bot's answers run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyStyle {
+    // A sentence or two
+    Short,
+    // Up to a full chat message
+    Long,
+}
+
+// Who the bot is in its replies instead of the built-in weasel, written as
+// "<name> => <description>", e.g. "pirate => You are Captain Choui, a pirate
+// weasel who talks like a sailor."
+#[derive(Debug, Clone, PartialEq)]
+pub struct Persona {
+    pub name: String,
+    pub prompt: String,
+}
+
+impl Persona {
+    fn parse(entry: &str) -> Result<Self> {
+        match entry.split_once("=>") {
+            Some((name, prompt)) if !name.trim().is_empty() && !prompt.trim().is_empty() => {
+                Ok(Self {
+                    name: name.trim().to_lowercase(),
+                    prompt: prompt.trim().to_string(),
+                })
+            }
+            _ => bail!(
+                "Personas look like \"pirate => You are a pirate weasel\", got '{}'",
+                entry
+            ),
+        }
+    }
+}
+
+// Generation settings passed to the model, and how often triggered replies
+// actually get sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct AiParams {
+    // None leaves the model's own default
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    // Percent of trigger word and question matches that get a reply; the
+    // command always does
+    pub reply_chance: u8,
+    pub style: ReplyStyle,
+    // Exchanges remembered per viewer (0 = no memory) and for how long
+    pub memory_turns: usize,
+    pub memory_ttl: Duration,
+    // AI_PERSONAS, and the one in use (None for the built-in one)
+    pub personas: Vec<Persona>,
+    pub persona: Option<String>,
+}
+
+impl AiParams {
+    // AI_TEMPERATURE, AI_MAX_TOKENS, AI_REPLY_CHANCE, AI_REPLY_STYLE,
+    // AI_MEMORY_TURNS, AI_MEMORY_TTL, AI_PERSONAS, AI_PERSONA
+    fn from_env() -> Result<Self> {
+        let temperature = match var("AI_TEMPERATURE") {
+            Ok(value) if !value.trim().is_empty() => Some(
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|t| (0.0..=2.0).contains(t))
+                    .with_context(|| format!("AI_TEMPERATURE must be 0 to 2, got '{}'", value))?,
+            ),
+            _ => None,
+        };
+        let max_tokens = match var("AI_MAX_TOKENS") {
+            Ok(value) if !value.trim().is_empty() => Some(
+                value
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|max| *max > 0)
+                    .with_context(|| {
+                        format!("AI_MAX_TOKENS must be a positive number, got '{}'", value)
+                    })?,
+            ),
+            _ => None,
+        };
+        let reply_chance = match var("AI_REPLY_CHANCE") {
+            Ok(value) => value
+                .trim()
+                .trim_end_matches('%')
+                .parse::<u8>()
+                .ok()
+                .filter(|chance| *chance <= 100)
+                .with_context(|| format!("AI_REPLY_CHANCE must be 0 to 100, got '{}'", value))?,
+            Err(_) => 100,
+        };
+        let style = match var("AI_REPLY_STYLE")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "short" => ReplyStyle::Short,
+            "long" => ReplyStyle::Long,
+            other => bail!("AI_REPLY_STYLE must be short or long, got '{}'", other),
+        };
+        let memory_turns = match var("AI_MEMORY_TURNS") {
+            Ok(value) => value
+                .trim()
+                .parse::<usize>()
+                .with_context(|| format!("AI_MEMORY_TURNS must be a number, got '{}'", value))?,
+            Err(_) => 5,
+        };
+        let memory_ttl = match var("AI_MEMORY_TTL") {
+            Ok(value) => value
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|minutes| *minutes > 0)
+                .map(|minutes| Duration::from_secs(minutes * 60))
+                .with_context(|| {
+                    format!(
+                        "AI_MEMORY_TTL must be a positive number of minutes, got '{}'",
+                        value
+                    )
+                })?,
+            Err(_) => Duration::from_secs(6 * 60 * 60),
+        };
+        let personas = var("AI_PERSONAS")
+            .unwrap_or_default()
+            .split('|')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(Persona::parse)
+            .collect::<Result<_>>()
+            .context("AI_PERSONAS")?;
+        let mut params = Self {
+            temperature,
+            max_tokens,
+            reply_chance,
+            style,
+            memory_turns,
+            memory_ttl,
+            personas,
+            persona: None,
+        };
+        let persona = var("AI_PERSONA").unwrap_or_default();
+        if !persona.trim().is_empty() {
+            params.set_persona(Some(&persona)).context("AI_PERSONA")?;
+        }
+        Ok(params)
+    }
+
+    /// Switch to one of the personas by name, or back to the built-in one.
+    pub fn set_persona(&mut self, name: Option<&str>) -> Result<()> {
+        self.persona = match name.map(|name| name.trim().to_lowercase()) {
+            Some(name) if !self.personas.iter().any(|persona| persona.name == name) => {
+                let names: Vec<_> = self.personas.iter().map(|p| p.name.as_str()).collect();
+                bail!(
+                    "No persona '{}' (AI_PERSONAS has {})",
+                    name,
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                );
+            }
+            name => name,
+        };
+        Ok(())
+    }
+
+    /// The description of the persona in use.
+    pub fn persona_prompt(&self) -> Option<&str> {
+        let name = self.persona.as_ref()?;
+        self.personas
+            .iter()
+            .find(|persona| persona.name == *name)
+            .map(|persona| persona.prompt.as_str())
+    }
+
+    /// Roll for a triggered reply, per `reply_chance`.
+    pub fn should_reply(&self) -> bool {
+        fastrand::u8(0..100) < self.reply_chance
+    }
+}
+
+// A duration given in (fractional) seconds
+fn seconds(name: &str, default: f64) -> Result<Duration> {
+    let Ok(value) = var(name) else {
+        return Ok(Duration::from_secs_f64(default));
+    };
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+        .with_context(|| format!("{} must be a number of seconds, got '{}'", name, value))
+}
+
+/// A setting that's on or off: 1, true, yes or on, or 0, false, no or off,
+/// `default` when it isn't set. Anything else ("ture") is an error rather
+/// than quietly off.
+pub fn env_flag(name: &str, default: bool) -> Result<bool> {
+    let Ok(value) = var(name) else {
+        return Ok(default);
+    };
+    match value.trim().to_lowercase().as_str() {
+        "" => Ok(default),
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        other => bail!("{} must be true or false, got '{}'", name, other),
+    }
+}
+
+// Settings for the Iced overlay, from the [overlay] section of choui.toml.
+// Read separately from Config since the overlay runs on its own thread and
+// may start without Twitch credentials.
+#[derive(Debug, Clone)]
+pub struct OverlayConfig {
+    // The windows to open, the first one is the main window. Never empty.
+    pub windows: Vec<(OverlayWindow, WindowPlacement)>,
+    pub font_size: u16,
+    pub alert_font_size: u16,
+    pub text_color: Rgb,
+    pub background: Rgb,
+    // Opacity of the chat background, 0.0 (clear) to 1.0
+    pub opacity: f32,
+    // Alert kinds that get a card; everything else is ignored by the overlay
+    pub alerts: HashSet<AlertKind>,
+    // Live viewers / uptime / category line above the chat
+    pub show_stats: bool,
+    pub animation: AlertAnimation,
+    // Counters shown as "Deaths: 5", in this order
+    pub counters: Vec<String>,
+    // Cycle through the top cheerers and gifters, this stream and all time
+    pub leaderboard: bool,
+}
+
+pub type Rgb = (u8, u8, u8);
+
+// What an overlay window shows. `split = true` in [overlay] gives the chat
+// feed and the alerts a window each, so they can sit apart in the scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayWindow {
+    Combined,
+    Chat,
+    Alerts,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WindowPlacement {
+    pub width: f32,
+    pub height: f32,
+    // Top-left corner on screen; None lets the window manager place it
+    pub position: Option<(f32, f32)>,
+}
+
+impl WindowPlacement {
+    // Missing keys keep the given defaults
+    fn from_file(file: &ConfigFile, section: &str, mut placement: Self) -> Result<Self> {
+        if let Some(width) = file.get_float(section, "width")? {
+            placement.width = width as f32;
+        }
+        if let Some(height) = file.get_float(section, "height")? {
+            placement.height = height as f32;
+        }
+        if placement.width <= 0.0 || placement.height <= 0.0 {
+            bail!("[{}] width and height must be positive", section);
+        }
+        placement.position = match (file.get_float(section, "x")?, file.get_float(section, "y")?) {
+            (Some(x), Some(y)) => Some((x as f32, y as f32)),
+            (None, None) => placement.position,
+            _ => bail!("[{}] x and y must be set together", section),
+        };
+        Ok(placement)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    // Map animation progress 0..=1 onto the curve (cubic)
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Easing> {
+        match name.trim().to_lowercase().as_str() {
+            "linear" => Some(Easing::Linear),
+            "ease-in" => Some(Easing::EaseIn),
+            "ease-out" => Some(Easing::EaseOut),
+            "ease-in-out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+}
+
+// How alert cards enter and leave. The same curve runs backwards on exit.
+#[derive(Debug, Clone, Copy)]
+pub struct AlertAnimation {
+    pub duration: std::time::Duration,
+    pub easing: Easing,
+    pub slide: bool,
+    pub scale: bool,
+    pub fade: bool,
+}
+
+impl Default for AlertAnimation {
+    fn default() -> Self {
+        Self {
+            duration: std::time::Duration::from_millis(400),
+            easing: Easing::EaseOut,
+            slide: true,
+            scale: false,
+            fade: true,
+        }
+    }
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            windows: vec![(
+                OverlayWindow::Combined,
+                WindowPlacement {
+                    width: 400.0,
+                    height: 600.0,
+                    position: None,
+                },
+            )],
+            font_size: 22,
+            alert_font_size: 48,
+            text_color: (255, 255, 255),
+            background: (0, 0, 0),
+            opacity: 0.7,
+            alerts: AlertKind::ALL.into_iter().collect(),
+            show_stats: true,
+            animation: AlertAnimation::default(),
+            counters: Vec::new(),
+            leaderboard: false,
+        }
+    }
+}
+
+impl OverlayConfig {
+    pub fn load() -> Result<Self> {
+        let file = ConfigFile::load_default()?;
+        Self::from_file(&file)
+    }
+
+    // Missing keys keep their defaults; OVERLAY_ALERTS in the environment
+    // overrides the file's `alerts`
+    pub fn from_file(file: &ConfigFile) -> Result<Self> {
+        const SECTION: &str = "overlay";
+        let mut config = Self::default();
+
+        let combined = WindowPlacement::from_file(file, SECTION, config.windows[0].1)?;
+        config.windows = if file.get_bool(SECTION, "split")?.unwrap_or(false) {
+            let defaults = [
+                (OverlayWindow::Chat, "overlay.chat_window", combined),
+                (
+                    OverlayWindow::Alerts,
+                    "overlay.alerts_window",
+                    WindowPlacement {
+                        width: 900.0,
+                        height: 200.0,
+                        position: None,
+                    },
+                ),
+            ];
+            let mut windows = Vec::new();
+            for (window, section, placement) in defaults {
+                if file.get_bool(section, "enabled")?.unwrap_or(true) {
+                    windows.push((
+                        window,
+                        WindowPlacement::from_file(file, section, placement)?,
+                    ));
+                }
+            }
+            if windows.is_empty() {
+                bail!("[overlay.chat_window] and [overlay.alerts_window] are both disabled, turn off split or --no-overlay instead");
+            }
+            windows
+        } else {
+            vec![(OverlayWindow::Combined, combined)]
+        };
+
+        let font_size = |key: &str, default: u16| -> Result<u16> {
+            match file.get_int(SECTION, key)? {
+                None => Ok(default),
+                Some(size) if (1..=512).contains(&size) => Ok(size as u16),
+                Some(_) => bail!("[overlay] {} must be between 1 and 512", key),
+            }
+        };
+        config.font_size = font_size("font_size", config.font_size)?;
+        config.alert_font_size = font_size("alert_font_size", config.alert_font_size)?;
+
+        if let Some(color) = file.get_str(SECTION, "text_color")? {
+            config.text_color = parse_hex_color(&color).context("[overlay] text_color")?;
+        }
+        if let Some(color) = file.get_str(SECTION, "background")? {
+            config.background = parse_hex_color(&color).context("[overlay] background")?;
+        }
+        if let Some(opacity) = file.get_float(SECTION, "opacity")? {
+            if !(0.0..=1.0).contains(&opacity) {
+                bail!("[overlay] opacity must be between 0.0 and 1.0");
+            }
+            config.opacity = opacity as f32;
+        }
+
+        if let Some(show) = file.get_bool(SECTION, "stats")? {
+            config.show_stats = show;
+        }
+        if let Some(counters) = file.get_str_list(SECTION, "counters")? {
+            config.counters = counters
+                .iter()
+                .map(|name| name.trim().trim_start_matches('!').to_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect();
+        }
+        if let Some(leaderboard) = file.get_bool(SECTION, "leaderboard")? {
+            config.leaderboard = leaderboard;
+        }
+        if let Some(effects) = file.get_str_list(SECTION, "animation")? {
+            let animation = &mut config.animation;
+            (animation.slide, animation.scale, animation.fade) = (false, false, false);
+            for effect in effects {
+                match effect.trim().to_lowercase().as_str() {
+                    "slide" => animation.slide = true,
+                    "scale" => animation.scale = true,
+                    "fade" => animation.fade = true,
+                    "none" => {}
+                    other => bail!(
+                        "Unknown effect '{}' in [overlay] animation (expected slide, scale, fade or none)",
+                        other
+                    ),
+                }
+            }
+        }
+        match file.get_int(SECTION, "animation_ms")? {
+            Some(ms) if (0..=10_000).contains(&ms) => {
+                config.animation.duration = std::time::Duration::from_millis(ms as u64);
+            }
+            Some(_) => bail!("[overlay] animation_ms must be between 0 and 10000"),
+            None => {}
+        }
+        if let Some(easing) = file.get_str(SECTION, "easing")? {
+            config.animation.easing = Easing::from_name(&easing).with_context(|| {
+                format!(
+                    "Unknown easing '{}' in [overlay] (expected linear, ease-in, ease-out or ease-in-out)",
+                    easing
+                )
+            })?;
+        }
+
+        match var("OVERLAY_ALERTS") {
+            Ok(list) if !list.trim().is_empty() => {
+                config.alerts = parse_alert_list(list.split(','), "OVERLAY_ALERTS")?;
+            }
+            _ => {
+                if let Some(list) = file.get_str_list(SECTION, "alerts")? {
+                    config.alerts =
+                        parse_alert_list(list.iter().map(String::as_str), "[overlay] alerts")?;
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+// "none" alone turns every alert off
+fn parse_alert_list<'a>(
+    names: impl Iterator<Item = &'a str>,
+    source: &str,
+) -> Result<HashSet<AlertKind>> {
+    let names: Vec<&str> = names.map(str::trim).filter(|n| !n.is_empty()).collect();
+    if names.len() == 1 && names[0].eq_ignore_ascii_case("none") {
+        return Ok(HashSet::new());
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            AlertKind::from_name(name).with_context(|| {
+                format!(
+                    "Unknown alert '{}' in {} (expected join, follow, sub, gift, raid, cheer, milestone, tip or none)",
+                    name, source
+                )
+            })
+        })
+        .collect()
+}
+
+// "#RRGGBB" (the # is optional)
+pub fn parse_hex_color(text: &str) -> Result<Rgb> {
+    let hex = text.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("'{}' is not a #RRGGBB color", text);
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+    Ok((channel(0), channel(2), channel(4)))
+}
+
+pub fn load_goal() -> Result<Option<Goal>> {
+    let file = ConfigFile::load_default()?;
+    goal_from_file(&file)
+}
+
+// [goal] in choui.toml; without a target there's no goal bar
+pub fn goal_from_file(file: &ConfigFile) -> Result<Option<Goal>> {
+    const SECTION: &str = "goal";
+    let Some(target) = file.get_int(SECTION, "target")? else {
+        return Ok(None);
+    };
+    if target < 1 {
+        bail!("[goal] target must be at least 1");
+    }
+    let kind = match file.get_str(SECTION, "kind")? {
+        None => GoalKind::Follow,
+        Some(kind) => match kind.trim().to_lowercase().as_str() {
+            "follow" | "follower" | "followers" => GoalKind::Follow,
+            "sub" | "subs" | "subscriber" | "subscribers" => GoalKind::Sub,
+            other => bail!("[goal] kind must be follow or sub, got '{}'", other),
+        },
+    };
+    let current = file.get_int(SECTION, "current")?.unwrap_or(0);
+    if current < 0 {
+        bail!("[goal] current can't be negative");
+    }
+    let label = file.get_str(SECTION, "label")?.unwrap_or_else(|| {
+        match kind {
+            GoalKind::Follow => "Follower goal",
+            GoalKind::Sub => "Sub goal",
+        }
+        .to_string()
+    });
+
+    Ok(Some(Goal {
+        kind,
+        label,
+        target: target as u64,
+        current: current as u64,
+    }))
+}
+
+/// Settings for using the crate as a library, in code instead of (or on top
+/// of) the environment. Anything not given is read like the app reads it:
+/// the environment, then .env and choui.toml if `load_settings` was called,
+/// then the defaults.
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// use choui_the_no_gui_chatbot::Config;
+///
+/// let config = Config::builder("my-client-id", "my_bot")
+///     .channel("my_channel")
+///     .ollama("http://localhost:11434", "llama3")
+///     .setting("AI_COOLDOWN", "5")
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    // By environment variable name, as documented in .env.example
+    settings: HashMap<String, String>,
+    oauth_token: Option<String>,
+    endpoints: Option<Endpoints>,
+}
+
+impl ConfigBuilder {
+    /// Any setting from .env.example, by its variable name.
+    pub fn setting(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.settings.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// The channel to join, by login.
+    pub fn channel(self, name: &str) -> Self {
+        self.setting("CHANNEL_NAME", name)
+    }
+
+    /// A user access token to use as it is, instead of BotCore::connect
+    /// logging in.
+    pub fn oauth_token(mut self, token: impl Into<String>) -> Self {
+        self.oauth_token = Some(token.into());
+        self
+    }
+
+    pub fn gemini(self, api_key: &str, model: &str) -> Self {
+        self.setting("LLM_PROVIDER", "gemini")
+            .setting("GEMINI_API_KEY", api_key)
+            .setting("GEMINI_MODEL", model)
+    }
+
+    pub fn ollama(self, host: &str, model: &str) -> Self {
+        self.setting("LLM_PROVIDER", "ollama")
+            .setting("OLLAMA_HOST", host)
+            .setting("OLLAMA_MODEL", model)
+    }
+
+    /// Where Twitch and Gemini are, e.g. a mock server.
+    pub fn endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = Some(endpoints);
+        self
+    }
+
+    /// Check and parse everything, like `Config::from_env`.
+    pub fn build(self) -> Result<Config> {
+        // from_env reads through var(), on this thread
+        OVERRIDES.with(|o| *o.borrow_mut() = self.settings);
+        let config = Config::from_env();
+        OVERRIDES.with(|o| o.borrow_mut().clear());
+        let mut config = config?;
+        if self.oauth_token.is_some() {
+            config.oauth_token = self.oauth_token;
+        }
+        if let Some(endpoints) = self.endpoints {
+            config.endpoints = endpoints;
+        }
+        Ok(config)
+    }
 }
 
 impl Config {
+    /// Start settings in code; the bot needs a Twitch application's client id
+    /// and the bot account (a login or a user id).
+    pub fn builder(client_id: &str, bot_user: &str) -> ConfigBuilder {
+        ConfigBuilder::default()
+            .setting("CLIENT_ID", client_id)
+            .setting("BOT_USER_ID", bot_user)
+    }
+
+    /// The SQLite database for quotes and the like: the chat log's, or where
+    /// it would be while logging is off.
+    pub fn database(&self) -> PathBuf {
+        self.chat_log
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("chat.db"))
+    }
+
     pub fn from_env() -> Result<Self> {
-        let llm_provider = match env::var("LLM_PROVIDER")
+        let dir = |name: &str, default: fn() -> PathBuf| match var(name) {
+            Ok(dir) if !dir.trim().is_empty() => PathBuf::from(dir.trim()),
+            _ => default(),
+        };
+        let data_dir = dir("DATA_DIR", crate::paths::data_dir);
+        let config_file = match var("CHOUI_CONFIG") {
+            Ok(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+            _ => config_path(),
+        };
+        let llm_provider = match var("LLM_PROVIDER")
             .unwrap_or_default()
             .to_lowercase()
             .as_str()
@@ -35,23 +1376,223 @@ impl Config {
             _ => LlmProvider::Gemini, // Default to Gemini
         };
 
+        let power_mode = match var("LOW_POWER")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "auto" => PowerMode::Auto,
+            "on" | "true" | "1" => PowerMode::On,
+            "off" | "false" | "0" => PowerMode::Off,
+            other => bail!("LOW_POWER must be auto, on or off, got '{}'", other),
+        };
+
+        let mut viewer_milestones = var("VIEWER_MILESTONES")
+            .unwrap_or_else(|_| "10,25,50,100,250,500,1000".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<u64>()
+                    .with_context(|| format!("VIEWER_MILESTONES: '{}' is not a number", s))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        viewer_milestones.sort_unstable();
+        viewer_milestones.dedup();
+
+        let mute_regex = var("MUTE_REGEX").ok().filter(|s| !s.trim().is_empty());
+        if let Some(pattern) = &mute_regex {
+            regex::Regex::new(pattern).context("MUTE_REGEX is not a valid regex")?;
+        }
+
         Ok(Self {
-            bot_user_id: env::var("BOT_USER_ID").context("BOT_USER_ID not set")?,
-            channel_user_id: env::var("CHANNEL_USER_ID").ok(),
-            channel_name: env::var("CHANNEL_NAME").ok(),
-            client_id: env::var("CLIENT_ID").context("CLIENT_ID not set")?,
+            bot_user_id: var("BOT_USER_ID").context("BOT_USER_ID not set")?,
+            channel_user_id: var("CHANNEL_USER_ID").ok(),
+            channel_name: var("CHANNEL_NAME").ok(),
+            client_id: var("CLIENT_ID").context("CLIENT_ID not set")?,
             oauth_token: None,
             llm_provider,
-            gemini_api_key: env::var("GEMINI_API_KEY").ok(),
-            gemini_model: env::var("GEMINI_MODEL")
+            gemini_api_key: var("GEMINI_API_KEY").ok(),
+            gemini_model: var("GEMINI_MODEL")
                 .map(|s| s.trim().to_string())
                 .unwrap_or_else(|_| "gemini-2.0-flash".to_string()),
-            ollama_model: env::var("OLLAMA_MODEL")
+            ollama_model: var("OLLAMA_MODEL")
                 .map(|s| s.trim().to_string())
                 .unwrap_or_else(|_| "llama3.2:1b".to_string()),
-            ollama_host: env::var("OLLAMA_HOST")
+            ollama_host: var("OLLAMA_HOST")
                 .map(|s| s.trim().to_string())
                 .unwrap_or_else(|_| "http://localhost:11434".to_string()),
+            ai_require_approval: env_flag("AI_REQUIRE_APPROVAL", false)?,
+            ai_triggers: AiTriggers::from_env()?,
+            ai_params: AiParams::from_env()?,
+            hide_bots: env_flag("HIDE_BOTS", true)?,
+            bot_accounts: var("BOT_ACCOUNTS")
+                .unwrap_or_else(|_| DEFAULT_BOT_ACCOUNTS.to_string())
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            hide_commands: env_flag("HIDE_COMMANDS", false)?,
+            mute_regex,
+            skip_stages: var("SKIP_STAGES")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            theme: Theme::from_env()?,
+            tts_enabled: env_flag("TTS_ENABLED", true)?,
+            tts: Tts::from_env()?,
+            volumes: Volumes::from_env()?,
+            tts_reward: var("TTS_REWARD")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            chat_rate_limit: match var("CHAT_RATE_LIMIT") {
+                Ok(limit) => limit
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&limit| (1..=100).contains(&limit))
+                    .with_context(|| {
+                        format!("CHAT_RATE_LIMIT must be 1 to 100, got '{}'", limit)
+                    })?,
+                Err(_) => 20,
+            },
+            tts_queue_max: match var("TTS_QUEUE_MAX") {
+                Ok(max) => max
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&max| max > 0)
+                    .with_context(|| {
+                        format!("TTS_QUEUE_MAX must be a positive number, got '{}'", max)
+                    })?,
+                Err(_) => 5,
+            },
+            join_sound: var("JOIN_SOUND")
+                .map(|s| s.trim().to_string())
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "assets/sounds/join.mp3".to_string()),
+            group_messages: env_flag("GROUP_MESSAGES", true)?,
+            viewer_milestones,
+            power_mode,
+            max_fps: match var("MAX_FPS") {
+                Ok(fps) => fps
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|&fps| (1..=240).contains(&fps))
+                    .with_context(|| format!("MAX_FPS must be 1 to 240, got '{}'", fps))?,
+                Err(_) => 30,
+            },
+            secret_store: SecretStore::parse(&var("SECRET_STORE").unwrap_or_default())?,
+            cache_dir: dir("CACHE_DIR", crate::paths::cache_dir),
+            chat_log: match var("CHAT_LOG").map(|s| s.trim().to_string()) {
+                Ok(path) if path.is_empty() || path.eq_ignore_ascii_case("off") => None,
+                Ok(path) => Some(PathBuf::from(path)),
+                Err(_) => Some(data_dir.join("chat.db")),
+            },
+            legacy_dir: match var("DATA_DIR") {
+                Ok(dir) if !dir.trim().is_empty() => None,
+                _ => Some(PathBuf::from(".")),
+            },
+            data_dir,
+            history_lines: match var("HISTORY_LINES") {
+                Ok(lines) => lines
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&lines| lines > 0)
+                    .with_context(|| {
+                        format!("HISTORY_LINES must be a positive number, got '{}'", lines)
+                    })?,
+                Err(_) => 5000,
+            },
+            offline_message: var("OFFLINE_MESSAGE")
+                .map(|message| message.trim().to_string())
+                .ok()
+                .filter(|message| !message.is_empty()),
+            quotes_ai: env_flag("QUOTES_AI", false)?,
+            poll_native: match var("POLL_MODE")
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+                .as_str()
+            {
+                "" | "chat" => false,
+                "twitch" | "native" => true,
+                other => bail!("POLL_MODE must be chat or twitch, got '{}'", other),
+            },
+            poll_duration: match var("POLL_DURATION") {
+                Ok(secs) => secs
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|secs| crate::polls::DURATIONS.contains(secs))
+                    .map(Duration::from_secs)
+                    .with_context(|| {
+                        format!("POLL_DURATION must be 15 to 1800 seconds, got '{}'", secs)
+                    })?,
+                Err(_) => Duration::from_secs(60),
+            },
+            poll_ai: env_flag("POLL_AI", true)?,
+            timers: var("TIMERS")
+                .unwrap_or_default()
+                .split('|')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(Timer::parse)
+                .collect::<Result<_>>()
+                .context("TIMERS")?,
+            timer_jitter: match var("TIMER_JITTER") {
+                Ok(jitter) => jitter
+                    .trim()
+                    .trim_end_matches('%')
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|&jitter| jitter <= 100)
+                    .with_context(|| {
+                        format!("TIMER_JITTER must be 0 to 100 percent, got '{}'", jitter)
+                    })?,
+                Err(_) => 20,
+            },
+            songs: SongSettings::from_env()?,
+            games: GameSettings::from_env()?,
+            lurk_enabled: env_flag("LURK_ENABLED", true)?,
+            lurk_ai: env_flag("LURK_AI", true)?,
+            follows: FollowSettings::from_env()?,
+            chat_speed: ChatSpeedSettings::from_env()?,
+            translate: TranslateSettings::from_env()?,
+            shield: ShieldSettings::from_env()?,
+            raid_welcome: env_flag("RAID_WELCOME", true)?,
+            raid_shoutout: env_flag("RAID_SHOUTOUT", true)?,
+            links: LinkSettings::from_env()?,
+            auto_replies: var("AUTO_REPLIES")
+                .unwrap_or_default()
+                .split('|')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(AutoReply::parse)
+                .collect::<Result<_>>()
+                .context("AUTO_REPLIES")?,
+            auto_reply_cooldown: seconds("AUTO_REPLY_COOLDOWN", 30.0)?,
+            // Next to choui.toml by default
+            scripts_dir: match var("SCRIPTS_DIR").map(|s| s.trim().to_string()) {
+                Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+                _ => config_file
+                    .parent()
+                    .unwrap_or(std::path::Path::new(""))
+                    .join("scripts"),
+            },
+            config_file,
+            api: ApiSettings::from_env()?,
+            matrix: MatrixSettings::from_env()?,
+            mqtt: MqttSettings::from_env()?,
+            tips: TipSettings::from_env()?,
+            endpoints: Endpoints::from_env(),
+            // Set by BotCore::demo
+            demo: false,
         })
     }
 }
@@ -0,0 +1,192 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// choui.toml reader. The file is parsed by the toml crate; the config needs
+// [section] tables holding strings, integers, floats, booleans and arrays of
+// those, and anything else in it (dates, arrays of tables) is an error rather
+// than silently ignored.
+
+pub const CONFIG_FILE: &str = "choui.toml";
+
+/// Where choui.toml is read from, first match wins: the path in
+/// $CHOUI_CONFIG (also set by --config), ./choui.toml, then choui.toml in
+/// choui's config directory (paths.rs).
+/// With none of them present it's ./choui.toml, which then just doesn't exist.
+pub fn config_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("CHOUI_CONFIG").filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+    let local = PathBuf::from(CONFIG_FILE);
+    if local.exists() {
+        return local;
+    }
+    crate::paths::config_dir()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .filter(|path| path.exists())
+        .unwrap_or(local)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+// Keys before the first [section] header live in the "" section
+#[derive(Debug, Default, Clone)]
+pub struct ConfigFile {
+    sections: HashMap<String, HashMap<String, Value>>,
+}
+
+impl ConfigFile {
+    /// `Ok(None)` if the file doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        Self::parse(&text)
+            .with_context(|| format!("Invalid {}", path.display()))
+            .map(Some)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let table: toml::Table = toml::from_str(text)?;
+        let mut file = ConfigFile::default();
+        file.add_section(String::new(), table)?;
+        Ok(file)
+    }
+
+    // Tables within tables are sections of their own, named by their dotted
+    // path: [overlay.chat_window] is "overlay.chat_window", not in [overlay]
+    fn add_section(&mut self, name: String, table: toml::Table) -> Result<()> {
+        self.sections.entry(name.clone()).or_default();
+        for (key, item) in table {
+            if let toml::Value::Table(table) = item {
+                let path = if name.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", name, key)
+                };
+                self.add_section(path, table)?;
+                continue;
+            }
+            let value = Value::from_toml(item).with_context(|| format!("[{}] {}", name, key))?;
+            self.sections
+                .entry(name.clone())
+                .or_default()
+                .insert(key, value);
+        }
+        Ok(())
+    }
+
+    /// The file at config_path(), or an empty one if there is none.
+    pub fn load_default() -> Result<Self> {
+        Ok(Self::load(config_path())?.unwrap_or_default())
+    }
+
+    /// Last modification time, for polling the file for changes.
+    pub fn modified(path: impl AsRef<Path>) -> Option<std::time::SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&Value> {
+        self.sections.get(section)?.get(key)
+    }
+
+    pub fn keys(&self, section: &str) -> impl Iterator<Item = &str> {
+        self.sections
+            .get(section)
+            .into_iter()
+            .flat_map(|keys| keys.keys().map(String::as_str))
+    }
+
+    pub fn get_str(&self, section: &str, key: &str) -> Result<Option<String>> {
+        match self.get(section, key) {
+            None => Ok(None),
+            Some(Value::Str(s)) => Ok(Some(s.clone())),
+            Some(_) => bail!("[{}] {} must be a string", section, key),
+        }
+    }
+
+    pub fn get_bool(&self, section: &str, key: &str) -> Result<Option<bool>> {
+        match self.get(section, key) {
+            None => Ok(None),
+            Some(Value::Bool(b)) => Ok(Some(*b)),
+            Some(_) => bail!("[{}] {} must be true or false", section, key),
+        }
+    }
+
+    pub fn get_float(&self, section: &str, key: &str) -> Result<Option<f64>> {
+        match self.get(section, key) {
+            None => Ok(None),
+            Some(Value::Float(f)) => Ok(Some(*f)),
+            Some(Value::Int(i)) => Ok(Some(*i as f64)),
+            Some(_) => bail!("[{}] {} must be a number", section, key),
+        }
+    }
+
+    pub fn get_int(&self, section: &str, key: &str) -> Result<Option<i64>> {
+        match self.get(section, key) {
+            None => Ok(None),
+            Some(Value::Int(i)) => Ok(Some(*i)),
+            Some(_) => bail!("[{}] {} must be a whole number", section, key),
+        }
+    }
+
+    pub fn get_str_list(&self, section: &str, key: &str) -> Result<Option<Vec<String>>> {
+        match self.get(section, key) {
+            None => Ok(None),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| match item {
+                    Value::Str(s) => Ok(s.clone()),
+                    _ => bail!("[{}] {} must be a list of strings", section, key),
+                })
+                .collect::<Result<_>>()
+                .map(Some),
+            Some(_) => bail!("[{}] {} must be a list of strings", section, key),
+        }
+    }
+}
+
+impl Value {
+    fn from_toml(value: toml::Value) -> Result<Self> {
+        Ok(match value {
+            toml::Value::String(s) => Value::Str(s),
+            toml::Value::Integer(i) => Value::Int(i),
+            toml::Value::Float(f) => Value::Float(f),
+            toml::Value::Boolean(b) => Value::Bool(b),
+            toml::Value::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .map(Value::from_toml)
+                    .collect::<Result<_>>()?,
+            ),
+            toml::Value::Datetime(_) => bail!("Dates are not supported"),
+            toml::Value::Table(_) => bail!("Tables within arrays are not supported"),
+        })
+    }
+
+    /// The value as an environment variable would spell it; arrays become
+    /// comma-separated lists.
+    pub fn to_env_string(&self) -> String {
+        match self {
+            Value::Str(s) => s.clone(),
+            Value::Int(i) => i.to_string(),
+            Value::Float(f) => f.to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Array(items) => items
+                .iter()
+                .map(Value::to_env_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::sqlite::{Store, Writer};
+use crate::state::AppEvent;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::sync::mpsc::UnboundedSender;
+
+// Counters kept in the chat database, like the death count:
+//
+//   !deaths                 the count
+//   !deaths+ / !deaths-     one more / one less (mods), or !deaths+ 3
+//   !deaths set <n>         (mods)
+//   !deaths reset           back to 0 (mods)
+//   !deaths delete          forget it (mods)
+//
+// A mod's first !name+ creates the counter. Those listed in [overlay]
+// counters are shown on the overlay.
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS counters (
+    name TEXT PRIMARY KEY,
+    value INTEGER NOT NULL,
+    timestamp TEXT NOT NULL
+);
+";
+
+const MAX_NAME_LEN: usize = 25;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CounterCommand {
+    Show,
+    Add(i64),
+    Set(i64),
+    Delete,
+}
+
+impl CounterCommand {
+    /// Parse `!<name> <args>` as a counter command. Returns the counter's
+    /// name too, which is `name` without the + or -. None when it can't be one.
+    pub fn parse(name: &str, args: &str) -> Option<(String, Result<Self>)> {
+        let args = args.trim();
+        let amount = || match args {
+            "" => Ok(1),
+            _ => args
+                .parse::<i64>()
+                .ok()
+                .filter(|n| *n > 0)
+                .with_context(|| format!("'{}' isn't a number to count by", args)),
+        };
+        let (counter, command) = if let Some(counter) = name.strip_suffix('+') {
+            (counter, amount().map(CounterCommand::Add))
+        } else if let Some(counter) = name.strip_suffix('-') {
+            (counter, amount().map(|n| CounterCommand::Add(-n)))
+        } else {
+            let (first, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            let command = match first.to_lowercase().as_str() {
+                "+" => Ok(CounterCommand::Add(1)),
+                "-" => Ok(CounterCommand::Add(-1)),
+                "reset" => Ok(CounterCommand::Set(0)),
+                "set" => rest
+                    .trim()
+                    .parse()
+                    .map(CounterCommand::Set)
+                    .context("Usage: !<counter> set <number>"),
+                "delete" | "remove" => Ok(CounterCommand::Delete),
+                _ => Ok(CounterCommand::Show),
+            };
+            (name, command)
+        };
+        let valid = !counter.is_empty()
+            && counter.len() <= MAX_NAME_LEN
+            && counter.chars().all(|c| c.is_alphanumeric() || c == '_');
+        valid.then(|| (counter.to_string(), command))
+    }
+
+    pub fn mod_only(&self) -> bool {
+        !matches!(self, CounterCommand::Show)
+    }
+}
+
+/// `Deaths: 5`
+pub fn describe(name: &str, value: i64) -> String {
+    let mut chars = name.chars();
+    let title: String = chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default();
+    format!("{}: {}", title, value)
+}
+
+// In memory like the custom commands, written back in order by a thread of
+// their own
+pub struct Counters {
+    values: HashMap<String, i64>,
+    writes: Writer,
+}
+
+impl Counters {
+    pub fn open(path: &Path, events: UnboundedSender<AppEvent>) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?;
+        let values = db
+            .query("SELECT name, value FROM counters", &[])?
+            .iter()
+            .filter_map(|row| Some((row.first()?.as_str()?.to_string(), row.get(1)?.as_int()?)))
+            .collect();
+
+        let writes = db.writer("counters", events);
+        Ok(Self { values, writes })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    /// Apply a command, creating the counter if it's new. Returns the value
+    /// after it, which is 0 for a deleted counter.
+    pub fn apply(&mut self, name: &str, command: &CounterCommand) -> i64 {
+        let value = match command {
+            CounterCommand::Show => return self.values.get(name).copied().unwrap_or(0),
+            CounterCommand::Delete => {
+                self.values.remove(name);
+                self.writes
+                    .write("DELETE FROM counters WHERE name = ?", vec![name.into()]);
+                return 0;
+            }
+            CounterCommand::Add(n) => self
+                .values
+                .get(name)
+                .copied()
+                .unwrap_or(0)
+                .saturating_add(*n),
+            CounterCommand::Set(n) => *n,
+        };
+        self.values.insert(name.to_string(), value);
+        self.writes.write(
+            "INSERT OR REPLACE INTO counters (name, value, timestamp) VALUES (?, ?, ?)",
+            vec![
+                name.into(),
+                value.into(),
+                crate::chatlog::timestamp().into(),
+            ],
+        );
+        value
+    }
+}
@@ -0,0 +1,290 @@
+use crate::sqlite::{Store, Value, Writer};
+use crate::state::{AppEvent, Role};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+// Commands mods add from chat, kept in the chat database:
+//
+//   !addcmd !name [-ul=<level>] [-cd=<seconds>] <response>
+//   !editcmd !name [-ul=<level>] [-cd=<seconds>] [<response>]
+//   !delcmd !name
+//
+// Levels are everyone (the default), vip, mod and broadcaster. Responses can
+// use {user} (who used the command), {target} (the first word after it,
+// without the @, or else the user) and {count} (how often it's been used).
+// Mods skip the cooldowns.
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS commands (
+    name TEXT PRIMARY KEY,
+    response TEXT NOT NULL,
+    level TEXT NOT NULL,
+    cooldown INTEGER NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    added_by TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+);
+";
+
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomCommand {
+    // Lowercase, without the '!'
+    pub name: String,
+    pub response: String,
+    // The lowest role that may use it
+    pub level: Role,
+    pub cooldown: Duration,
+    pub count: i64,
+}
+
+impl CustomCommand {
+    fn from_row(row: &[Value]) -> Option<Self> {
+        Some(Self {
+            name: row.first()?.as_str()?.to_string(),
+            response: row.get(1)?.as_str()?.to_string(),
+            level: parse_level(row.get(2)?.as_str()?).unwrap_or(Role::Viewer),
+            cooldown: Duration::from_secs(row.get(3)?.as_int()?.max(0) as u64),
+            count: row.get(4)?.as_int()?,
+        })
+    }
+
+    /// The response with the template variables filled in.
+    pub fn render(&self, user: &str, args: &str) -> String {
+        let target = args
+            .split_whitespace()
+            .next()
+            .map(|word| word.trim_start_matches('@'))
+            .filter(|word| !word.is_empty())
+            .unwrap_or(user);
+        self.response
+            .replace("{user}", user)
+            .replace("{target}", target)
+            .replace("{count}", &self.count.to_string())
+    }
+}
+
+fn parse_level(text: &str) -> Option<Role> {
+    match text.to_lowercase().as_str() {
+        "everyone" | "viewer" | "all" => Some(Role::Viewer),
+        "vip" => Some(Role::Vip),
+        "mod" | "mods" | "moderator" => Some(Role::Moderator),
+        "broadcaster" | "owner" | "streamer" => Some(Role::Broadcaster),
+        _ => None,
+    }
+}
+
+fn level_name(level: Role) -> &'static str {
+    match level {
+        Role::Viewer => "everyone",
+        Role::Vip => "vip",
+        Role::Moderator => "mod",
+        Role::Broadcaster => "broadcaster",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManageCommand {
+    Add(CustomCommand),
+    Edit {
+        name: String,
+        response: Option<String>,
+        level: Option<Role>,
+        cooldown: Option<Duration>,
+    },
+    Delete(String),
+}
+
+impl ManageCommand {
+    /// `command` is addcmd, editcmd or delcmd.
+    pub fn parse(command: &str, args: &str) -> Result<Self> {
+        let usage = match command {
+            "addcmd" => "Usage: !addcmd !name [-ul=<level>] [-cd=<seconds>] <response>",
+            "editcmd" => "Usage: !editcmd !name [-ul=<level>] [-cd=<seconds>] [<response>]",
+            _ => "Usage: !delcmd !name",
+        };
+        let mut words = args.split_whitespace().peekable();
+        let name = words
+            .next()
+            .map(|name| name.trim_start_matches('!').to_lowercase())
+            .filter(|name| !name.is_empty())
+            .context(usage)?;
+
+        let (mut level, mut cooldown) = (None, None);
+        while let Some(option) = words.peek().and_then(|word| word.strip_prefix('-')) {
+            match option.split_once('=') {
+                Some(("ul", value)) => {
+                    level = Some(parse_level(value).with_context(|| {
+                        format!("Unknown level {}: everyone, vip, mod or broadcaster", value)
+                    })?)
+                }
+                Some(("cd", value)) => {
+                    let seconds = value
+                        .parse()
+                        .with_context(|| format!("The cooldown is in seconds, not {}", value))?;
+                    cooldown = Some(Duration::from_secs(seconds))
+                }
+                _ => break,
+            }
+            words.next();
+        }
+        let response = Some(words.collect::<Vec<_>>().join(" ")).filter(|r| !r.is_empty());
+
+        Ok(match command {
+            "addcmd" => ManageCommand::Add(CustomCommand {
+                name,
+                response: response.context(usage)?,
+                level: level.unwrap_or(Role::Viewer),
+                cooldown: cooldown.unwrap_or(DEFAULT_COOLDOWN),
+                count: 0,
+            }),
+            "editcmd" if response.is_none() && level.is_none() && cooldown.is_none() => {
+                bail!(usage)
+            }
+            "editcmd" => ManageCommand::Edit {
+                name,
+                response,
+                level,
+                cooldown,
+            },
+            _ => ManageCommand::Delete(name),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ManageCommand::Add(command) => &command.name,
+            ManageCommand::Edit { name, .. } | ManageCommand::Delete(name) => name,
+        }
+    }
+}
+
+// Commands are kept in memory, so the event loop can tell right away whether
+// a message is one of them. Changes are written back, in order, by a thread
+// of their own.
+pub struct CustomCommands {
+    commands: HashMap<String, CustomCommand>,
+    last_used: HashMap<String, Instant>,
+    writes: Writer,
+}
+
+impl CustomCommands {
+    pub fn open(path: &Path, events: UnboundedSender<AppEvent>) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?;
+        let commands = db
+            .query(
+                "SELECT name, response, level, cooldown, count FROM commands",
+                &[],
+            )?
+            .iter()
+            .filter_map(|row| CustomCommand::from_row(row))
+            .map(|command| (command.name.clone(), command))
+            .collect();
+
+        let writes = db.writer("commands", events);
+        Ok(Self {
+            commands,
+            last_used: HashMap::new(),
+            writes,
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+    }
+
+    /// The reply when `user` uses the command, or None when they may not or
+    /// it's still cooling down.
+    pub fn run(&mut self, name: &str, user: &str, role: Role, args: &str) -> Option<String> {
+        let command = self.commands.get_mut(name)?;
+        if role > command.level {
+            return None;
+        }
+        let rested = self
+            .last_used
+            .get(name)
+            .is_none_or(|t| t.elapsed() >= command.cooldown);
+        if !rested && role > Role::Moderator {
+            return None;
+        }
+        self.last_used.insert(name.to_string(), Instant::now());
+        command.count += 1;
+        let reply = command.render(user, args);
+        self.writes.write(
+            "UPDATE commands SET count = count + 1 WHERE name = ?",
+            vec![name.into()],
+        );
+        Some(reply)
+    }
+
+    /// Apply an !addcmd, !editcmd or !delcmd and return the reply for chat.
+    pub fn manage(&mut self, manage: ManageCommand, user: &str) -> Result<String> {
+        Ok(match manage {
+            ManageCommand::Add(command) => {
+                if self.contains(&command.name) {
+                    bail!(
+                        "!{} already exists, use !editcmd to change it",
+                        command.name
+                    );
+                }
+                self.writes.write(
+                    "INSERT INTO commands (name, response, level, cooldown, count, added_by, timestamp)
+                     VALUES (?, ?, ?, ?, 0, ?, ?)",
+                    vec![
+                        command.name.as_str().into(),
+                        command.response.as_str().into(),
+                        level_name(command.level).into(),
+                        (command.cooldown.as_secs() as i64).into(),
+                        user.into(),
+                        crate::chatlog::timestamp().into(),
+                    ],
+                );
+                let reply = format!("Added !{}", command.name);
+                self.commands.insert(command.name.clone(), command);
+                reply
+            }
+            ManageCommand::Edit {
+                name,
+                response,
+                level,
+                cooldown,
+            } => {
+                let command = self
+                    .commands
+                    .get_mut(&name)
+                    .with_context(|| format!("There's no !{}", name))?;
+                if let Some(response) = response {
+                    command.response = response;
+                }
+                command.level = level.unwrap_or(command.level);
+                command.cooldown = cooldown.unwrap_or(command.cooldown);
+                let params = vec![
+                    command.response.as_str().into(),
+                    level_name(command.level).into(),
+                    (command.cooldown.as_secs() as i64).into(),
+                    name.as_str().into(),
+                ];
+                self.writes.write(
+                    "UPDATE commands SET response = ?, level = ?, cooldown = ? WHERE name = ?",
+                    params,
+                );
+                format!("Changed !{}", name)
+            }
+            ManageCommand::Delete(name) => {
+                if self.commands.remove(&name).is_none() {
+                    bail!("There's no !{}", name);
+                }
+                self.last_used.remove(&name);
+                self.writes.write(
+                    "DELETE FROM commands WHERE name = ?",
+                    vec![name.as_str().into()],
+                );
+                format!("Deleted !{}", name)
+            }
+        })
+    }
+}
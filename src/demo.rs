@@ -0,0 +1,220 @@
+use crate::config::Config;
+use crate::state::{AppEvent, Badge, ChatMessage, Fragment, Role, StreamAlert};
+use crate::twitch::StreamStats;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+// --demo: the bot without Twitch or an AI provider, for trying the terminal
+// UI and the overlay before setting either up. Made-up viewers chat, join,
+// follow, subscribe and cheer on a timer, the AI answers with canned replies
+// and what the bot says shows up in chat without going anywhere. Everything
+// else that needs Twitch (slash commands, polls, shoutouts) fails like it
+// would with a bad token.
+
+const VIEWERS: &[&str] = &[
+    "pixel_pam",
+    "lurkmaster3000",
+    "dotadad",
+    "cozy_kat",
+    "speedrunsteve",
+    "weasel_fan",
+    "midlane_mo",
+    "quietquinn",
+];
+
+const LINES: &[&str] = &[
+    "hello chat!",
+    "that was a clean play",
+    "Kappa",
+    "LUL what just happened",
+    "first time here, love the vibe",
+    "gg",
+    "what hero is this?",
+    "how long have you been streaming today?",
+    "can you explain that item build?",
+    "the music is so good",
+    "PogChamp",
+    "back from dinner, what did I miss?",
+    "@choui_demo are you a real weasel?",
+    "!quote",
+    "this overlay looks great <3",
+    "is the bot alive?",
+];
+
+// Emote names in LINES and their Twitch ids
+const EMOTES: &[(&str, &str)] = &[
+    ("Kappa", "25"),
+    ("LUL", "425618"),
+    ("PogChamp", "305954156"),
+    ("<3", "555555584"),
+];
+
+const REPLIES: &[&str] = &[
+    "*Squeak!* Great question! This is demo mode, so I'm making it up as I go 🦦",
+    "Welcome welcome! Grab a snack and enjoy the stream! 🎉",
+    "I'd tell you, but I'm just a demo weasel for now. Set up an AI provider to hear the real me!",
+    "That play was 200% weasel approved! 🐾",
+    "Hehe, chat is on fire today! 🔥",
+];
+
+// Where the bot's own messages go: straight back into the stream, like
+// Twitch echoes them
+static CHAT: OnceLock<(mpsc::UnboundedSender<AppEvent>, String)> = OnceLock::new();
+
+/// Make up the Twitch settings that aren't set, so a fresh install can start.
+/// Call before any threads are started.
+pub fn fill_in_settings() {
+    for (name, value) in [
+        ("BOT_USER_ID", "choui_demo"),
+        ("CLIENT_ID", "demo"),
+        ("CHANNEL_NAME", "demo_channel"),
+    ] {
+        if crate::config::var(name).is_err() {
+            std::env::set_var(name, value);
+        }
+    }
+}
+
+fn message(user: &str, text: &str) -> Arc<ChatMessage> {
+    let mut fragments = Vec::new();
+    let mut plain = String::new();
+    for word in text.split_inclusive(' ') {
+        match EMOTES.iter().find(|(name, _)| *name == word.trim_end()) {
+            Some((name, id)) => {
+                if !plain.is_empty() {
+                    fragments.push(Fragment::Text(std::mem::take(&mut plain)));
+                }
+                fragments.push(Fragment::Emote {
+                    name: name.to_string(),
+                    id: id.to_string(),
+                });
+                plain.push_str(&word[name.len()..]);
+            }
+            None => plain.push_str(word),
+        }
+    }
+    if !plain.is_empty() {
+        fragments.push(Fragment::Text(plain));
+    }
+    let badges = match fastrand::u8(..10) {
+        0 => vec![Badge {
+            set_id: "moderator".to_string(),
+            id: "1".to_string(),
+            info: String::new(),
+        }],
+        1..=3 => vec![Badge {
+            set_id: "subscriber".to_string(),
+            id: "3".to_string(),
+            info: "3".to_string(),
+        }],
+        _ => Vec::new(),
+    };
+    const COLORS: &[&str] = &["#FF7F50", "#1E90FF", "#9ACD32", "#DAA520", "#FF69B4"];
+    Arc::new(ChatMessage {
+        id: format!("demo-{}", fastrand::u64(..)),
+        user: user.to_string(),
+        display_name: user.to_string(),
+        text: text.to_string(),
+        color: Some(COLORS[fastrand::usize(..COLORS.len())].to_string()),
+        badges,
+        fragments,
+        timestamp: jiff::Timestamp::now(),
+    })
+}
+
+/// Put `text` in chat as the bot, as send_chat_message does in demo mode.
+pub fn say(text: &str) {
+    if let Some((tx, bot_login)) = CHAT.get() {
+        let _ = tx.send(AppEvent::ChatMessage(message(bot_login, text)));
+    }
+}
+
+/// A canned answer, after a moment's thought.
+pub async fn reply() -> String {
+    tokio::time::sleep(Duration::from_millis(fastrand::u64(500..1500))).await;
+    REPLIES[fastrand::usize(..REPLIES.len())].to_string()
+}
+
+fn viewer() -> &'static str {
+    VIEWERS[fastrand::usize(..VIEWERS.len())]
+}
+
+fn alert() -> StreamAlert {
+    // No raids: welcoming one looks the raider up on Twitch
+    match fastrand::u8(..4) {
+        0 => StreamAlert::Follow {
+            user: viewer().to_string(),
+        },
+        1 => StreamAlert::Subscribe {
+            user: viewer().to_string(),
+            tier: "1000".to_string(),
+        },
+        2 => StreamAlert::GiftSub {
+            user: Some(viewer().to_string()),
+            count: fastrand::u32(1..=5),
+            tier: "1000".to_string(),
+        },
+        _ => StreamAlert::Cheer {
+            user: Some(viewer().to_string()),
+            bits: [100, 250, 500][fastrand::usize(..3)],
+            message: "Cheer100 keep it up!".to_string(),
+        },
+    }
+}
+
+/// Start the made-up channel: the bot's messages echo into `tx`, and until
+/// `shutdown` there's a chat message every few seconds, a join now and then
+/// and an alert about every half minute.
+pub fn start(
+    config: &Config,
+    bot_login: &str,
+    tx: mpsc::UnboundedSender<AppEvent>,
+    shutdown: CancellationToken,
+) {
+    let _ = CHAT.set((tx.clone(), bot_login.to_string()));
+    let channel = config.channel_name.clone().unwrap_or_default();
+
+    tokio::spawn(async move {
+        use crate::state::{ConnectionState, Service};
+        for service in [Service::EventSub, Service::Irc] {
+            let _ = tx.send(AppEvent::Connection(service, ConnectionState::Connected));
+        }
+        let _ = tx.send(AppEvent::Info(format!(
+            "Demo mode: #{} is made up, nothing goes to Twitch",
+            channel
+        )));
+        let mut roles = vec![(channel.to_lowercase(), Role::Broadcaster)];
+        roles.push((VIEWERS[0].to_string(), Role::Moderator));
+        let _ = tx.send(AppEvent::ChatterRoles(roles));
+        let _ = tx.send(AppEvent::ChatterList(
+            VIEWERS[..4].iter().map(|v| v.to_string()).collect(),
+        ));
+        let _ = tx.send(AppEvent::StreamStats(Some(StreamStats {
+            viewer_count: 42,
+            started_at: jiff::Timestamp::now() - jiff::SignedDuration::from_mins(65),
+            game_name: "Dota 2".to_string(),
+        })));
+
+        let mut ticks = 0u32;
+        loop {
+            let wait = Duration::from_millis(fastrand::u64(1500..4500));
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(wait) => {}
+            }
+            ticks += 1;
+            let event = if ticks.is_multiple_of(10) {
+                AppEvent::Alert(alert())
+            } else if ticks.is_multiple_of(4) {
+                AppEvent::UserJoined(viewer().to_string())
+            } else {
+                AppEvent::ChatMessage(message(viewer(), LINES[fastrand::usize(..LINES.len())]))
+            };
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,123 @@
+use crate::config;
+use crate::secrets::SECRET_VARS;
+use crate::state::{AiStatus, App, ChatLine};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+// Ctrl+G or /diagnostics: everything worth attaching to a bug report in one
+// JSON file, instead of a screenshot and a guess. Secrets are left out, the
+// rest is as the TUI has it right now.
+
+// How much of each tab goes in
+const RECENT_LINES: usize = 200;
+
+fn lines(lines: &VecDeque<ChatLine>) -> Vec<String> {
+    let start = lines.len().saturating_sub(RECENT_LINES);
+    lines
+        .range(start..)
+        .map(|line| match &line.user {
+            Some(user) => format!("{}: {}", user, line.text),
+            None => line.text.clone(),
+        })
+        .collect()
+}
+
+// Every setting that's set, from wherever, with the secrets masked
+fn settings() -> serde_json::Map<String, Value> {
+    config::setting_names()
+        .filter_map(|name| {
+            let value = config::var(name).ok()?;
+            let shown = if SECRET_VARS.contains(&name) {
+                "(set)".to_string()
+            } else {
+                value
+            };
+            Some((name.to_string(), Value::String(shown)))
+        })
+        .collect()
+}
+
+/// The state of `app` as JSON. `queues` are the depths of queues the App
+/// doesn't own, like the TTS queue, by name.
+pub fn snapshot(app: &App, queues: &[(&str, usize)]) -> Value {
+    let mut depths: serde_json::Map<String, Value> = queues
+        .iter()
+        .map(|(name, depth)| (name.to_string(), json!(depth)))
+        .collect();
+    let ai_pending = app
+        .ai_requests
+        .iter()
+        .filter(|r| matches!(r.status, AiStatus::Pending | AiStatus::AwaitingApproval(_)))
+        .count();
+    depths.insert("ai_requests".into(), json!(ai_pending));
+    depths.insert("chat".into(), json!(app.outbox.waiting()));
+    depths.insert("songs".into(), json!(app.songs.queue.len()));
+    depths.insert("unsent".into(), json!(app.unsent.len()));
+
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "taken_at": jiff::Timestamp::now().to_string(),
+        "os": std::env::consts::OS,
+        "bot_login": app.bot_login,
+        "channel": app.config.channel_name,
+        "demo": app.config.demo,
+        "connections": {
+            "eventsub": format!("{:?}", app.eventsub),
+            "irc": format!("{:?}", app.irc),
+        },
+        "queues": depths,
+        "ui": {
+            "tab": format!("{:?}", app.tab),
+            "focused": app.focused,
+            "power_mode": format!("{:?}", app.power_mode),
+            "image_protocol": app.protocol_name,
+            "emotes_loaded": app.emote_images.len(),
+            "inline_emotes": app.inline_emotes.len(),
+            "filters_enabled": app.filters.enabled,
+            "muted_users": app.filters.muted_users.len(),
+        },
+        "tts": {
+            "enabled": app.tts_enabled,
+            "volumes": format!("{:?}", app.volumes),
+            "speech": format!("{:?}", app.speech),
+        },
+        "stream": app.stream.as_ref().map(|stream| format!("{:?}", stream)),
+        "goal": app.goal.as_ref().map(|goal| format!("{:?}", goal)),
+        "poll": app.poll.as_ref().map(|poll| format!("{:?}", poll)),
+        "chatters": {
+            "known": app.chatters.len(),
+            "present": app.chatters.values().filter(|c| c.present).count(),
+        },
+        "ai_requests": app.ai_requests.iter().rev().take(50).map(|r| json!({
+            "id": r.id,
+            "user": r.user,
+            "status": format!("{:?}", r.status),
+            "seconds": r.elapsed().as_secs_f64(),
+        })).collect::<Vec<_>>(),
+        "unsent": app.unsent,
+        "notifications": app.notifications.iter().map(|n| {
+            format!("{:?}: {}", n.severity, n.text)
+        }).collect::<Vec<_>>(),
+        "settings": settings(),
+        "recent": {
+            "log": lines(&app.log),
+            "chat": lines(&app.messages),
+            "moderation": lines(&app.mod_queue),
+            "ai": lines(&app.ai_activity),
+        },
+    })
+}
+
+/// Write `snapshot` to a timestamped file in the data directory's
+/// diagnostics folder and return where.
+pub fn dump(app: &App, queues: &[(&str, usize)]) -> Result<PathBuf> {
+    let dir = app.config.data_dir.join("diagnostics");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let stamp = jiff::Zoned::now().strftime("%Y%m%d-%H%M%S").to_string();
+    let path = dir.join(format!("choui-{}.json", stamp));
+    let json = serde_json::to_string_pretty(&snapshot(app, queues))?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
@@ -0,0 +1,77 @@
+use crate::config::Config;
+use crate::state::{AppEvent, EMOJIS};
+use crate::twitch::{download_emote, get_global_emotes};
+use futures_util::StreamExt;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+// The emote picker's images. The emotes shipped in assets/emotes are used as
+// they are, the others are downloaded once into the cache directory. Several
+// load at a time, each is decoded on a blocking thread, and they're handed
+// over in picker order with an EmotesLoading after each.
+
+// Downloads at once
+const CONCURRENCY: usize = 8;
+
+/// Load every emote in EMOJIS, sending EmoteImage for the ones that could be
+/// had and EmotesLoading as they're done.
+pub async fn load(client: Client, config: Arc<Config>, tx: mpsc::UnboundedSender<AppEvent>) {
+    // Without the map (as in --demo) the bundled and cached emotes still load
+    let map = match get_global_emotes(&client, &config).await {
+        Ok(map) => {
+            let _ = tx.send(AppEvent::Info("Global emote map fetched.".into()));
+            map
+        }
+        Err(e) => {
+            if !config.demo {
+                let _ = tx.send(AppEvent::Error(format!("Failed to fetch emotes: {}", e)));
+            }
+            HashMap::new()
+        }
+    };
+    let cache = config.cache_dir.join("emotes");
+    let _ = fs::create_dir_all(&cache);
+
+    let total = EMOJIS.len();
+    let _ = tx.send(AppEvent::EmotesLoading { loaded: 0, total });
+    let loads: Vec<_> = EMOJIS
+        .iter()
+        .map(|&name| load_one(&client, &map, &cache, name))
+        .collect();
+    let mut images = futures_util::stream::iter(loads).buffered(CONCURRENCY);
+    let mut loaded = 0;
+    while let Some(image) = images.next().await {
+        loaded += 1;
+        if let Some((name, image)) = image {
+            let _ = tx.send(AppEvent::EmoteImage(name.to_string(), Arc::new(image)));
+        }
+        let _ = tx.send(AppEvent::EmotesLoading { loaded, total });
+    }
+}
+
+async fn load_one(
+    client: &Client,
+    map: &HashMap<String, String>,
+    cache: &Path,
+    name: &'static str,
+) -> Option<(&'static str, image::DynamicImage)> {
+    let file_name = format!("{}.png", name);
+    let bundled = Path::new("assets/emotes").join(&file_name);
+    let path = cache.join(&file_name);
+
+    let bytes = if bundled.exists() {
+        tokio::fs::read(&bundled).await.ok()?
+    } else if path.exists() {
+        tokio::fs::read(&path).await.ok()?
+    } else {
+        let bytes = download_emote(client, map.get(name)?).await.ok()?;
+        let _ = tokio::fs::write(&path, &bytes).await;
+        bytes
+    };
+    let image = tokio::task::spawn_blocking(move || image::load_from_memory(&bytes));
+    Some((name, image.await.ok()?.ok()?))
+}
@@ -0,0 +1,139 @@
+use crate::state::AppEvent;
+use ratatui::layout::Rect;
+use ratatui_image::picker::{Picker, ProtocolType};
+use ratatui_image::protocol::Protocol;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use tokio::sync::mpsc;
+
+// Emote images are encoded for the terminal (sixel, kitty...) on a worker
+// thread, one at a time in the order asked for, and come back as
+// EmoteEncoded events; encoding a picker's worth of emotes in the event loop
+// stalled the UI. Encoded emotes are kept per protocol, so cycling back to a
+// protocol with Ctrl+P doesn't encode them again.
+
+/// Which render of an emote: a cell in the emote picker, or inline in chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EmoteSize {
+    Picker,
+    Inline,
+}
+
+impl EmoteSize {
+    /// In terminal cells
+    pub fn area(self) -> Rect {
+        match self {
+            EmoteSize::Picker => Rect::new(0, 0, 3, 2),
+            EmoteSize::Inline => Rect::new(0, 0, 2, 1),
+        }
+    }
+}
+
+/// An encoded emote, shared so the event carrying it can be cloned
+#[derive(Clone)]
+pub struct Encoded(pub Arc<dyn Protocol>);
+
+impl std::fmt::Debug for Encoded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Encoded")
+    }
+}
+
+struct Job {
+    protocol: ProtocolType,
+    size: EmoteSize,
+    name: String,
+    image: Arc<image::DynamicImage>,
+}
+
+pub struct Encoder {
+    jobs: std::sync::mpsc::Sender<Job>,
+}
+
+impl Encoder {
+    /// The worker keeps its own copy of `picker` (font size, tmux, and the
+    /// kitty image ids, which must not repeat); each job says which protocol.
+    pub fn start(mut picker: Picker, tx: mpsc::UnboundedSender<AppEvent>) -> Self {
+        let (jobs, receiver) = std::sync::mpsc::channel::<Job>();
+        thread::spawn(move || {
+            for job in receiver {
+                picker.protocol_type = job.protocol;
+                let result = picker
+                    .new_protocol(
+                        Arc::unwrap_or_clone(job.image),
+                        job.size.area(),
+                        ratatui_image::Resize::Fit(None),
+                    )
+                    .map(|protocol| Encoded(Arc::from(protocol)))
+                    .map_err(|e| e.to_string());
+                let event = AppEvent::EmoteEncoded {
+                    name: job.name,
+                    size: job.size,
+                    protocol: job.protocol,
+                    result,
+                };
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { jobs }
+    }
+
+    pub fn encode(
+        &self,
+        protocol: ProtocolType,
+        size: EmoteSize,
+        name: String,
+        image: Arc<image::DynamicImage>,
+    ) {
+        let _ = self.jobs.send(Job {
+            protocol,
+            size,
+            name,
+            image,
+        });
+    }
+}
+
+type ByEmote = HashMap<(EmoteSize, String), Encoded>;
+
+/// Encoded emotes by protocol, then size and name
+#[derive(Default)]
+pub struct ProtocolCache {
+    // ProtocolType can't be a map key
+    by_protocol: Vec<(ProtocolType, ByEmote)>,
+}
+
+impl ProtocolCache {
+    pub fn get(&self, protocol: ProtocolType, size: EmoteSize, name: &str) -> Option<&Encoded> {
+        let (_, encoded) = self.by_protocol.iter().find(|(p, _)| *p == protocol)?;
+        encoded.get(&(size, name.to_string()))
+    }
+
+    pub fn insert(
+        &mut self,
+        protocol: ProtocolType,
+        size: EmoteSize,
+        name: String,
+        encoded: Encoded,
+    ) {
+        let index = match self.by_protocol.iter().position(|(p, _)| *p == protocol) {
+            Some(index) => index,
+            None => {
+                self.by_protocol.push((protocol, HashMap::new()));
+                self.by_protocol.len() - 1
+            }
+        };
+        self.by_protocol[index].1.insert((size, name), encoded);
+    }
+
+    /// How many emotes are encoded for `protocol`
+    pub fn len(&self, protocol: ProtocolType) -> usize {
+        self.by_protocol
+            .iter()
+            .find(|(p, _)| *p == protocol)
+            .map_or(0, |(_, encoded)| encoded.len())
+    }
+}
@@ -0,0 +1,1572 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+use crate::{
+    ai::ask_ai,
+    api::{AiAction, ApiRequest},
+    audio,
+    auto_replies::AutoReplies,
+    chat_commands::{self, ChatCommand},
+    chat_speed::{self, SpeedAction, SpeedChange},
+    commands,
+    config::{Config, VolumeChannel},
+    counters::{self, CounterCommand, Counters},
+    custom_commands::{CustomCommands, ManageCommand},
+    diagnostics,
+    filters::Filters,
+    leaderboards::{Board, Leaderboards, Scope},
+    links::Permits,
+    lurk,
+    minigames::{GameCommand, Games},
+    modlog::{ModEvent, ModKind},
+    outbox::{Outbox, Priority},
+    plugins::{PluginAction, Plugins},
+    polls::{Poll, PollCommand},
+    quotes::{QuoteCommand, Quotes},
+    scripts::Scripts,
+    shield::{self, ShieldAction, Trigger},
+    songs::PlayerCommand,
+    state::{AiStatus, App, AppEvent, ChatLine, ConnectionState, Role, Severity, StreamAlert, Tab},
+    timers::Timers,
+    translate::{self, OptOuts, TranslateMode},
+    tts::SpeechKind,
+    twitch::{
+        create_poll, delete_chat_message, end_poll, get_account_ages, get_channel_info, get_poll,
+        manage_held_message, send_shoutout, set_followers_only, set_shield_mode, set_slow_mode,
+    },
+    viewers::Viewers,
+};
+
+// What the bot does for each feature: chat and slash commands, AI requests,
+// songs, polls, raids, the shield and settings changes. The session (see
+// session.rs) and the chat stages call these with the parts they need.
+
+// Slash commands that go to Twitch, in the background; the outcome comes back
+// as an event
+pub(crate) fn execute_command(
+    app: &App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    irc_tx: &mpsc::UnboundedSender<String>,
+    cmd: commands::SlashCommand,
+) {
+    let mod_event = commands::mod_event(&cmd, &app.bot_login);
+    let (client, config, irc_tx, tx) = (
+        client.clone(),
+        app.config.clone(),
+        irc_tx.clone(),
+        tx.clone(),
+    );
+    tokio::spawn(async move {
+        let event = match (
+            commands::execute(cmd, &client, &config, &irc_tx).await,
+            mod_event,
+        ) {
+            (Ok(_), Some(mod_event)) => AppEvent::Moderation(mod_event),
+            (Ok(msg), None) => AppEvent::Info(msg),
+            (Err(e), _) => AppEvent::Error(format!("Command failed: {}", e)),
+        };
+        let _ = tx.send(event);
+    });
+}
+
+// CHAT_SPEED_ACTIONS once chat got fast, undone when it calms down, and the
+// summaries while it stays fast
+pub(crate) fn react_to_chat_speed(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+) {
+    let now = std::time::Instant::now();
+    let config = app.config.clone();
+    let settings = &config.chat_speed;
+    let slow_mode = match app.chat_speed.check(now, settings.threshold) {
+        Some(SpeedChange::Fast { per_minute }) => {
+            app.notify(
+                Severity::Warning,
+                format!("Chat is moving fast: {} messages a minute", per_minute),
+            );
+            Some(Some(settings.slow_mode))
+        }
+        Some(SpeedChange::Calm) => {
+            app.notify(Severity::Info, "Chat calmed down");
+            Some(None)
+        }
+        None => None,
+    };
+    if let Some(wait) = slow_mode.filter(|_| settings.does(SpeedAction::SlowMode)) {
+        let (client, config, tx) = (client.clone(), config.clone(), tx.clone());
+        tokio::spawn(async move {
+            if let Err(e) = set_slow_mode(&client, &config, wait).await {
+                let _ = tx.send(AppEvent::Error(format!("{:#}", e)));
+            }
+        });
+    }
+    if settings.does(SpeedAction::Summary) {
+        if let Some(messages) = app.chat_speed.summary_due(now) {
+            let (config, outbox) = (config.clone(), app.outbox.clone());
+            tokio::spawn(async move {
+                outbox.say(Priority::Low, chat_speed::summary(&messages, &config).await);
+            });
+        }
+    }
+}
+
+// Joins looked up for fresh accounts once enough came in, and the shield
+// lowered once nothing set it off for SHIELD_DURATION
+pub(crate) fn watch_for_raids(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+) {
+    let now = std::time::Instant::now();
+    let settings = &app.config.shield;
+    if !settings.enabled {
+        return;
+    }
+    if let Some(users) = app.shield.accounts_to_check(now, settings) {
+        let (client, config, tx) = (client.clone(), app.config.clone(), tx.clone());
+        tokio::spawn(async move {
+            match get_account_ages(&client, &config, &users).await {
+                Ok(ages) => {
+                    let fresh = shield::fresh_accounts(
+                        &ages,
+                        jiff::Timestamp::now(),
+                        config.shield.account_age,
+                    );
+                    if !fresh.is_empty() {
+                        let _ = tx.send(AppEvent::FreshAccounts(fresh));
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(AppEvent::Debug(format!("Looking up joins failed: {:#}", e)));
+                }
+            }
+        });
+    }
+    if app.shield.expired(now, settings) {
+        lower_shield(app, tx, client, None);
+    }
+}
+
+// Up with the shield: loudly in the TUI and the overlay, and SHIELD_ACTIONS
+// on Twitch. Another trigger while it's up only keeps it up for longer.
+pub(crate) fn raise_shield(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    trigger: Trigger,
+) {
+    if !app.shield.raise(std::time::Instant::now()) {
+        return;
+    }
+    let reason = trigger.describe();
+    app.notify(Severity::Error, format!("Shield up: {}", reason));
+    let _ = tx.send(AppEvent::Shield(Some(reason)));
+    shield_actions(app, tx, client, true);
+}
+
+// Down with the shield, undoing SHIELD_ACTIONS; `by` is the mod who said so
+pub(crate) fn lower_shield(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    by: Option<&str>,
+) {
+    if !app.shield.lower() {
+        return;
+    }
+    let text = match by {
+        Some(moderator) => format!("Shield down, lowered by {}", moderator),
+        None => String::from("Shield down, things are quiet again"),
+    };
+    app.notify(Severity::Info, text);
+    let _ = tx.send(AppEvent::Shield(None));
+    shield_actions(app, tx, client, false);
+}
+
+// Shield Mode and followers-only chat on Twitch, on or off
+pub(crate) fn shield_actions(
+    app: &App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    on: bool,
+) {
+    let settings = &app.config.shield;
+    let (shield_mode, followers_only) = (
+        settings.does(ShieldAction::ShieldMode),
+        settings.does(ShieldAction::FollowersOnly),
+    );
+    if !shield_mode && !followers_only {
+        return;
+    }
+    let (client, config, tx) = (client.clone(), app.config.clone(), tx.clone());
+    tokio::spawn(async move {
+        if shield_mode {
+            if let Err(e) = set_shield_mode(&client, &config, on).await {
+                let _ = tx.send(AppEvent::Error(format!("{:#}", e)));
+            }
+        }
+        if followers_only {
+            let min_follow = on.then_some(shield::FOLLOWERS_ONLY);
+            if let Err(e) = set_followers_only(&client, &config, min_follow).await {
+                let _ = tx.send(AppEvent::Error(format!("{:#}", e)));
+            }
+        }
+    });
+}
+
+// Allow or deny the held message picked on the Moderation tab. It stays
+// listed until Twitch confirms.
+pub(crate) fn decide_held_message(
+    app: &App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    allow: bool,
+) {
+    let Some(held) = app.held.current() else {
+        return;
+    };
+    let (client, config, tx, id) = (
+        client.clone(),
+        app.config.clone(),
+        tx.clone(),
+        held.id.clone(),
+    );
+    let bot = app.bot_login.clone();
+    tokio::spawn(async move {
+        let event = match manage_held_message(&client, &config, &id, allow).await {
+            Ok(()) => AppEvent::AutoModResolved {
+                id,
+                status: if allow { "approved" } else { "denied" }.to_string(),
+                moderator: Some(bot),
+            },
+            Err(e) => AppEvent::Error(format!("AutoMod: {:#}", e)),
+        };
+        let _ = tx.send(event);
+    });
+}
+
+// What a client of the HTTP API asked for (see api.rs)
+pub(crate) fn run_api_request(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    broadcast_tx: &tokio::sync::broadcast::Sender<AppEvent>,
+    tts: &audio::TtsQueue,
+    sounds: &audio::AudioEngine,
+    timers: &mut Timers,
+    request: ApiRequest,
+) -> Result<serde_json::Value, String> {
+    use serde_json::json;
+    match request {
+        ApiRequest::Status => {
+            let connection = |state: ConnectionState| format!("{:?}", state).to_lowercase();
+            let ai_waiting = |status: fn(&AiStatus) -> bool| {
+                app.ai_requests.iter().filter(|r| status(&r.status)).count()
+            };
+            let stream = app.stream.as_ref().map(|stream| {
+                json!({
+                    "viewers": stream.viewer_count,
+                    "uptime": stream.uptime(),
+                    "category": stream.game_name,
+                })
+            });
+            let params = &app.config.ai_params;
+            Ok(json!({
+                "channel": app.config.channel_name,
+                "bot": app.bot_login,
+                "eventsub": connection(app.eventsub),
+                "chat": connection(app.irc),
+                "stream": stream,
+                "chatters": app.chatters.values().filter(|c| c.present).count(),
+                "tts": app.tts_enabled,
+                "ai": {
+                    "pending": ai_waiting(|s| *s == AiStatus::Pending),
+                    "awaiting_approval": ai_waiting(|s| matches!(s, AiStatus::AwaitingApproval(_))),
+                },
+                "persona": params.persona,
+                "personas": params.personas.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(),
+                "timers": timers.enabled,
+                "ai_approval": app.config.ai_require_approval,
+                "messages": app.config.api.messages.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+                "sounds": app.config.api.sounds.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            }))
+        }
+        ApiRequest::Feed => {
+            let lines = |lines: &VecDeque<ChatLine>, count: usize| {
+                lines
+                    .range(lines.len().saturating_sub(count)..)
+                    .map(|line| {
+                        json!({
+                            "user": line.user,
+                            "text": line.text,
+                            "color": line.color,
+                            "role": format!("{:?}", line.role).to_lowercase(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let ai = app.ai_requests[app.ai_requests.len().saturating_sub(20)..]
+                .iter()
+                .map(|request| {
+                    let (status, reply) = match &request.status {
+                        AiStatus::Pending => ("pending", None),
+                        AiStatus::AwaitingApproval(reply) => ("awaiting_approval", Some(reply)),
+                        AiStatus::Sent(reply) => ("sent", Some(reply)),
+                        AiStatus::Rejected(reply) => ("rejected", Some(reply)),
+                        AiStatus::Failed(error) => ("failed", Some(error)),
+                        AiStatus::Cancelled => ("cancelled", None),
+                    };
+                    json!({
+                        "id": request.id,
+                        "user": request.user,
+                        "prompt": request.prompt,
+                        "status": status,
+                        "reply": reply,
+                        "seconds": request.elapsed().as_secs(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            Ok(json!({
+                "chat": lines(&app.messages, 100),
+                "moderation": lines(&app.mod_queue, 50),
+                "ai": ai,
+            }))
+        }
+        ApiRequest::Settings {
+            timers: timers_on,
+            ai_approval,
+        } => {
+            if let Some(on) = timers_on {
+                let control = if on {
+                    commands::TimerControl::On
+                } else {
+                    commands::TimerControl::Off
+                };
+                control_timers(app, timers, control);
+            }
+            if let Some(on) = ai_approval {
+                Arc::make_mut(&mut app.config).ai_require_approval = on;
+            }
+            Ok(json!({ "timers": timers.enabled, "ai_approval": app.config.ai_require_approval }))
+        }
+        ApiRequest::Moderate(_) | ApiRequest::Ai { .. } => {
+            unreachable!("answered in the event loop")
+        }
+        ApiRequest::Say(message) => {
+            app.outbox.say(Priority::High, message);
+            Ok(json!({ "ok": true }))
+        }
+        ApiRequest::Alert(alert) => {
+            // Only shown, not counted toward the goal or thanked for
+            app.push(Tab::Log, format!("API alert: {}", alert.describe()));
+            let _ = broadcast_tx.send(AppEvent::Alert(alert));
+            Ok(json!({ "ok": true }))
+        }
+        ApiRequest::Tts(control) => {
+            control_tts(app, tts, sounds, tx, control);
+            Ok(json!({ "tts": app.tts_enabled }))
+        }
+        ApiRequest::Persona(name) => {
+            Arc::make_mut(&mut app.config)
+                .ai_params
+                .set_persona(name.as_deref())
+                .map_err(|e| e.to_string())?;
+            let persona = app.config.ai_params.persona.clone();
+            app.notify(
+                Severity::Info,
+                format!("AI persona: {}", persona.as_deref().unwrap_or("default")),
+            );
+            Ok(json!({ "persona": persona }))
+        }
+        ApiRequest::Message(name) => {
+            let message = canned(&app.config.api.messages, &name, "API_MESSAGES")?;
+            app.outbox.say(Priority::High, message.to_string());
+            Ok(json!({ "ok": true }))
+        }
+        ApiRequest::Sound(name) => {
+            let path = canned(&app.config.api.sounds, &name, "API_SOUNDS")?;
+            sounds.play(audio::Sound::File(path.to_string()), VolumeChannel::Alert);
+            Ok(json!({ "ok": true }))
+        }
+    }
+}
+
+// The API_MESSAGES or API_SOUNDS entry called `name`
+pub(crate) fn canned<'a>(
+    entries: &'a [(String, String)],
+    name: &str,
+    setting: &str,
+) -> Result<&'a str, String> {
+    entries
+        .iter()
+        .find(|(entry, _)| entry == name)
+        .map(|(_, value)| value.as_str())
+        .ok_or_else(|| {
+            let names: Vec<&str> = entries.iter().map(|(entry, _)| entry.as_str()).collect();
+            if names.is_empty() {
+                format!("No {} are set", setting)
+            } else {
+                format!("No '{}' in {}; there's {}", name, setting, names.join(", "))
+            }
+        })
+}
+
+// The plugins compiled into the bot (see plugins.rs); register yours here
+pub(crate) fn plugins(config: &Config) -> Plugins {
+    let mut plugins = Plugins::default();
+    plugins.register(Scripts::new(config.scripts_dir.clone()));
+    plugins
+}
+
+// Do what the plugins asked. Returns whether one handled the chat message.
+pub(crate) fn run_plugin_actions(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    ai_tasks: &mut HashMap<u64, tokio::task::AbortHandle>,
+    sounds: &audio::AudioEngine,
+    tts: &audio::TtsQueue,
+    features: &mut ChatFeatures,
+    actions: Vec<PluginAction>,
+) -> bool {
+    let mut handled = false;
+    for action in actions {
+        match action {
+            PluginAction::Say(message) => app.outbox.say(Priority::Normal, message),
+            PluginAction::PlaySound(path) => {
+                sounds.play(audio::Sound::File(path), VolumeChannel::Alert)
+            }
+            PluginAction::AskAi { user, prompt } => {
+                spawn_ai_request(app, tx, ai_tasks, &user, prompt, Priority::Normal)
+            }
+            PluginAction::Speak(text) => {
+                if app.tts_enabled {
+                    tts.speak(SpeechKind::Ai, &app.bot_login, &text, text.clone());
+                }
+            }
+            PluginAction::AddPoints { user, points } => match features.games.as_mut() {
+                Some(games) => {
+                    games.add(&app.config.games, &user, points);
+                }
+                None => app.notify(
+                    Severity::Warning,
+                    format!("No points for {}: the points aren't available", user),
+                ),
+            },
+            PluginAction::Log(text) => app.push(Tab::Log, text),
+            PluginAction::Handled => handled = true,
+        }
+    }
+    handled
+}
+
+// Ask the LLM in the background; the outcome comes back as AppEvent::AiFinished
+pub(crate) fn spawn_ai_request(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    ai_tasks: &mut HashMap<u64, tokio::task::AbortHandle>,
+    user: &str,
+    prompt: String,
+    priority: Priority,
+) {
+    let id = app.start_ai_request(user, &prompt, priority);
+    let _ = tx.send(AppEvent::AiStarted {
+        id,
+        user: user.to_string(),
+    });
+    let prompt = app.ai_memory.with_context(user, &prompt);
+    let config = app.config.clone();
+    let tx = tx.clone();
+    let handle = tokio::spawn(async move {
+        let result = ask_ai(&prompt, &config).await.map_err(|e| e.to_string());
+        let _ = tx.send(AppEvent::AiFinished { id, result });
+    });
+    ai_tasks.insert(id, handle.abort_handle());
+}
+
+pub(crate) fn send_ai_reply(outbox: &Outbox, priority: Priority, user: &str, reply: &str) {
+    outbox.say(priority, format!("@{} {}", user, reply));
+}
+
+// /retry: everything that failed to send goes out again, in order. What fails
+// again comes back to `unsent`.
+pub(crate) fn retry_unsent(app: &mut App) {
+    if app.unsent.is_empty() {
+        app.notify(Severity::Info, "Nothing to send again");
+        return;
+    }
+    let unsent = std::mem::take(&mut app.unsent);
+    app.notify(
+        Severity::Info,
+        format!("Sending {} message(s) again", unsent.len()),
+    );
+    for message in unsent {
+        app.outbox.say(Priority::High, message);
+    }
+}
+
+// What the chat commands work with. None for the ones whose database
+// couldn't be opened.
+pub(crate) struct ChatFeatures {
+    pub(crate) client: reqwest::Client,
+    pub(crate) quotes: Option<Quotes>,
+    pub(crate) custom_commands: Option<CustomCommands>,
+    pub(crate) counters: Option<Counters>,
+    // Points and the games played for them
+    pub(crate) games: Option<Games>,
+    // Which streams each viewer has been to, for the join greeting
+    pub(crate) viewers: Option<Viewers>,
+    // Bits cheered and subs gifted, for !topcheers and !topgifters
+    pub(crate) leaderboards: Option<Leaderboards>,
+    // Viewers whose messages aren't translated automatically
+    pub(crate) opt_outs: Option<OptOuts>,
+    // mpv or MPD playing the requested songs, if SONG_PLAYER names one
+    pub(crate) player: Option<mpsc::UnboundedSender<PlayerCommand>>,
+    // Who a mod let post links with !permit
+    pub(crate) permits: Permits,
+    // When each AUTO_REPLIES answer was last given
+    pub(crate) auto_replies: AutoReplies,
+}
+
+// Viewers' !commands (see chat_commands.rs). Returns false for commands this
+// bot doesn't have, which are left to other bots and the AI triggers.
+pub(crate) fn run_chat_command(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    features: &mut ChatFeatures,
+    user: &str,
+    role: Role,
+    command: ChatCommand,
+) -> bool {
+    let client = &features.client;
+    let quotes = features.quotes.as_ref();
+    let custom_commands = features.custom_commands.as_mut();
+    match command.name.as_str() {
+        "quote" | "quotes" => {
+            let Some(quotes) = quotes else {
+                app.outbox.say(
+                    Priority::Normal,
+                    format!("@{} Quotes aren't available right now", user),
+                );
+                return true;
+            };
+            let quote = match QuoteCommand::parse(command.args) {
+                Ok(quote) => quote,
+                Err(e) => {
+                    app.outbox.say(Priority::Normal, format!("@{} {}", user, e));
+                    return true;
+                }
+            };
+            if quote.mod_only() && !chat_commands::is_mod(role) {
+                app.outbox.say(
+                    Priority::Normal,
+                    format!("@{} Only mods can add or delete quotes", user),
+                );
+                return true;
+            }
+            let (quotes, config, tx, outbox, user) = (
+                quotes.clone(),
+                app.config.clone(),
+                tx.clone(),
+                app.outbox.clone(),
+                user.to_string(),
+            );
+            tokio::spawn(async move {
+                let reply = match quotes.run(quote, user.clone(), &config).await {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        let _ = tx.send(AppEvent::Error(format!("!quote failed: {:#}", e)));
+                        format!("@{} Couldn't get to the quotes, sorry", user)
+                    }
+                };
+                outbox.say(Priority::Normal, reply);
+            });
+            true
+        }
+        "poll" => {
+            let poll_command = match PollCommand::parse(command.args) {
+                Ok(poll_command) => poll_command,
+                Err(e) => {
+                    app.outbox.say(Priority::Normal, format!("@{} {}", user, e));
+                    return true;
+                }
+            };
+            if poll_command.mod_only() && !chat_commands::is_mod(role) {
+                app.outbox.say(
+                    Priority::Normal,
+                    format!("@{} Only mods can start or end polls", user),
+                );
+                return true;
+            }
+            let running = app.poll.as_ref().filter(|poll| !poll.ended);
+            match poll_command {
+                PollCommand::Show => {
+                    let reply = match (running, &app.poll) {
+                        (Some(poll), _) => format!(
+                            "Poll \"{}\" ({}s left): {}",
+                            poll.question,
+                            poll.time_left().as_secs(),
+                            poll.standings()
+                        ),
+                        (None, Some(poll)) => poll.summary(),
+                        (None, None) => "No poll yet".to_string(),
+                    };
+                    app.outbox.say(Priority::Normal, reply);
+                }
+                PollCommand::End => match running.map(|poll| poll.native_id.clone()) {
+                    None => app
+                        .outbox
+                        .say(Priority::Normal, format!("@{} No poll running", user)),
+                    // Twitch ends it; its progress updates bring the result
+                    Some(Some(id)) => {
+                        let (client, config, tx) = (client.clone(), app.config.clone(), tx.clone());
+                        tokio::spawn(async move {
+                            if let Err(e) = end_poll(&client, &config, &id).await {
+                                let _ = tx.send(AppEvent::Error(format!("{:#}", e)));
+                            }
+                        });
+                    }
+                    Some(None) => end_chat_poll(app, tx),
+                },
+                PollCommand::Start { .. } if running.is_some() => {
+                    app.outbox.say(
+                        Priority::Normal,
+                        format!("@{} A poll is already running, !poll end stops it", user),
+                    );
+                }
+                PollCommand::Start {
+                    question,
+                    options,
+                    duration,
+                } => {
+                    let duration = duration.unwrap_or(app.config.poll_duration);
+                    start_poll(app, tx, client, question, options, duration);
+                }
+            }
+            true
+        }
+        "sr" | "song" | "queue" | "wrongsong" | "skip" if app.config.songs.enabled => {
+            run_song_command(app, tx, features.player.as_ref(), user, role, command);
+            true
+        }
+        "points" | "gamble" | "duel" | "accept" | "deny" | "8ball" if app.config.games.enabled => {
+            let Some(games) = features.games.as_mut() else {
+                app.outbox.say(
+                    Priority::Normal,
+                    format!("@{} The games aren't available right now", user),
+                );
+                return true;
+            };
+            let Some(game) = GameCommand::parse(&command.name, command.args) else {
+                return false;
+            };
+            let unlimited = chat_commands::is_mod(role);
+            match game.and_then(|game| games.play(&app.config.games, user, unlimited, game)) {
+                Ok(outcome) => {
+                    let (config, outbox) = (app.config.clone(), app.outbox.clone());
+                    tokio::spawn(async move {
+                        let announcement = outcome.announcement(&config).await;
+                        outbox.say(Priority::Normal, announcement);
+                    });
+                }
+                Err(e) => app.outbox.say(Priority::Normal, format!("@{} {}", user, e)),
+            }
+            true
+        }
+        "lurk" if app.config.lurk_enabled => {
+            // A second !lurk is already answered
+            if app.lurkers.start(user) {
+                let (config, outbox, user) =
+                    (app.config.clone(), app.outbox.clone(), user.to_string());
+                tokio::spawn(async move {
+                    outbox.say(Priority::Low, lurk::send_off(&user, &config).await);
+                });
+            }
+            true
+        }
+        "unlurk" if app.config.lurk_enabled => {
+            if let Some(lurked) = app.lurkers.stop(user) {
+                let watched = features
+                    .viewers
+                    .as_mut()
+                    .map(|viewers| viewers.add_watch_time(user, lurked));
+                let reply = match watched {
+                    Some(watched) => format!(
+                        "Welcome back @{}! You lurked for {} ({} watched in all)",
+                        user,
+                        lurk::describe(lurked),
+                        lurk::describe(watched)
+                    ),
+                    None => format!(
+                        "Welcome back @{}! You lurked for {}",
+                        user,
+                        lurk::describe(lurked)
+                    ),
+                };
+                app.outbox.say(Priority::Normal, reply);
+            }
+            true
+        }
+        "topcheers" | "topgifters" => {
+            let Some(leaderboards) = features.leaderboards.as_ref() else {
+                app.outbox.say(
+                    Priority::Normal,
+                    format!("@{} The leaderboards aren't available right now", user),
+                );
+                return true;
+            };
+            let board = if command.name == "topcheers" {
+                Board::Cheers
+            } else {
+                Board::Gifts
+            };
+            app.outbox
+                .say(Priority::Normal, leaderboards.describe(board));
+            true
+        }
+        "translate" if app.config.translate.mode != TranslateMode::Off => {
+            let args = command.args.trim();
+            match args.to_lowercase().as_str() {
+                "" => app.outbox.say(
+                    Priority::Normal,
+                    format!(
+                        "@{} Usage: !translate <text>, or !translate off to keep your messages from being translated",
+                        user
+                    ),
+                ),
+                setting @ ("off" | "on") => {
+                    let opted_out = setting == "off";
+                    let reply = match features.opt_outs.as_mut() {
+                        Some(opt_outs) => {
+                            opt_outs.set(user, opted_out);
+                            if opted_out {
+                                format!("@{} Your messages won't be translated", user)
+                            } else {
+                                format!("@{} Your messages will be translated again", user)
+                            }
+                        }
+                        None => format!("@{} Translation settings aren't available right now", user),
+                    };
+                    app.outbox.say(Priority::Normal, reply);
+                }
+                _ => {
+                    let (config, outbox, tx) = (app.config.clone(), app.outbox.clone(), tx.clone());
+                    let (user, text) = (user.to_string(), args.to_string());
+                    tokio::spawn(async move {
+                        match translate::translate(&text, &config.translate.language, &config).await {
+                            Ok(translation) => {
+                                outbox.say(Priority::Normal, format!("@{} {}", user, translation))
+                            }
+                            Err(e) => {
+                                let _ = tx.send(AppEvent::Error(format!("!translate: {:#}", e)));
+                            }
+                        }
+                    });
+                }
+            }
+            true
+        }
+        "shield" if app.config.shield.enabled => {
+            // Left to other bots' !shield for everyone else
+            if !chat_commands::is_mod(role) {
+                return false;
+            }
+            let client = client.clone();
+            match command.args.trim().to_lowercase().as_str() {
+                "on" => {
+                    let trigger = Trigger::Manual {
+                        moderator: user.to_string(),
+                    };
+                    raise_shield(app, tx, &client, trigger);
+                }
+                "off" => lower_shield(app, tx, &client, Some(user)),
+                _ => {
+                    let state = if app.shield.is_raised() { "up" } else { "down" };
+                    app.outbox.say(
+                        Priority::Normal,
+                        format!("@{} The shield is {}. Usage: !shield on | off", user, state),
+                    );
+                }
+            }
+            true
+        }
+        "permit" if app.config.links.enabled => {
+            // Left to other bots' !permit for everyone else
+            if !chat_commands::is_mod(role) {
+                return false;
+            }
+            let reply = match command.args.split_whitespace().next() {
+                Some(target) => {
+                    features.permits.grant(target);
+                    format!(
+                        "@{} You can post a link in the next {}s",
+                        target.trim_start_matches('@'),
+                        app.config.links.permit.as_secs()
+                    )
+                }
+                None => format!("@{} Usage: !permit <user>", user),
+            };
+            app.outbox.say(Priority::Normal, reply);
+            true
+        }
+        "addcmd" | "editcmd" | "delcmd" => {
+            // Left to other bots' commands of the same name for everyone else
+            if !chat_commands::is_mod(role) {
+                return false;
+            }
+            let Some(custom_commands) = custom_commands else {
+                app.outbox.say(
+                    Priority::Normal,
+                    format!("@{} Custom commands aren't available right now", user),
+                );
+                return true;
+            };
+            let reply = ManageCommand::parse(&command.name, command.args).and_then(|manage| {
+                let name = manage.name();
+                let ai_command = app.config.ai_triggers.is_command(&format!("!{}", name));
+                if chat_commands::BUILT_IN.contains(&name) || ai_command {
+                    anyhow::bail!("!{} is one of the bot's own commands", name);
+                }
+                if features.counters.as_ref().is_some_and(|c| c.contains(name)) {
+                    anyhow::bail!("!{} is a counter", name);
+                }
+                custom_commands.manage(manage, user)
+            });
+            let reply = reply.unwrap_or_else(|e| e.to_string());
+            app.outbox
+                .say(Priority::Normal, format!("@{} {}", user, reply));
+            true
+        }
+        name => {
+            let counter = CounterCommand::parse(name, command.args);
+            match custom_commands {
+                Some(custom_commands) if custom_commands.contains(name) => {
+                    // Not allowed or cooling down: answered with silence
+                    if let Some(reply) = custom_commands.run(name, user, role, command.args) {
+                        app.outbox.say(Priority::Normal, reply);
+                    }
+                    return true;
+                }
+                // A counter can't take a custom command's name
+                Some(custom_commands)
+                    if counter
+                        .as_ref()
+                        .is_some_and(|(counter, _)| custom_commands.contains(counter)) =>
+                {
+                    return false;
+                }
+                _ => {}
+            }
+            match (features.counters.as_mut(), counter) {
+                (Some(counters), Some((counter, counter_command))) => {
+                    run_counter_command(app, tx, counters, user, role, &counter, counter_command)
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
+// !deaths and the like (see counters.rs). Returns false when there's no such
+// counter and this doesn't create one.
+pub(crate) fn run_counter_command(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    counters: &mut Counters,
+    user: &str,
+    role: Role,
+    counter: &str,
+    command: anyhow::Result<CounterCommand>,
+) -> bool {
+    if !counters.contains(counter) {
+        // A mod's !name+ starts a new one, unless the name is taken
+        let creates = chat_commands::is_mod(role) && matches!(command, Ok(CounterCommand::Add(_)));
+        let ai_command = app.config.ai_triggers.is_command(&format!("!{}", counter));
+        if !creates || chat_commands::BUILT_IN.contains(&counter) || ai_command {
+            return false;
+        }
+    }
+    // Only changes fail to parse
+    let allowed = chat_commands::is_mod(role) || matches!(command, Ok(CounterCommand::Show));
+    let reply = match command {
+        _ if !allowed => format!("@{} Only mods can change counters", user),
+        Err(e) => format!("@{} {}", user, e),
+        Ok(command) => {
+            let value = counters.apply(counter, &command);
+            if command.mod_only() {
+                let _ = tx.send(AppEvent::Counter {
+                    name: counter.to_string(),
+                    value,
+                });
+            }
+            match command {
+                CounterCommand::Delete => format!("Deleted the {} counter", counter),
+                _ => counters::describe(counter, value),
+            }
+        }
+    };
+    app.outbox.say(Priority::Normal, reply);
+    true
+}
+
+// !sr and friends (see songs.rs)
+pub(crate) fn run_song_command(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    player: Option<&mpsc::UnboundedSender<PlayerCommand>>,
+    user: &str,
+    role: Role,
+    command: ChatCommand,
+) {
+    let reply = match command.name.as_str() {
+        "sr" => {
+            let unlimited = chat_commands::is_mod(role);
+            match app
+                .songs
+                .request(&app.config.songs, user, command.args, unlimited)
+            {
+                Ok(place) => {
+                    app.push(Tab::Songs, format!("+ {} requested {}", user, command.args));
+                    if place == 0 {
+                        play_song(app, player);
+                    }
+                    song_changed(app, tx);
+                    match place {
+                        0 => format!("@{} Playing your song now", user),
+                        _ => format!("@{} Added, #{} in the queue", user, place),
+                    }
+                }
+                Err(e) => format!("@{} {}", user, e),
+            }
+        }
+        "song" => match &app.songs.now_playing {
+            Some(song) => format!("Now playing: {}", song.describe()),
+            None => "Nothing's playing. Request a song with !sr".to_string(),
+        },
+        "queue" => {
+            let next: Vec<String> = app
+                .songs
+                .queue
+                .iter()
+                .take(3)
+                .enumerate()
+                .map(|(i, song)| format!("{}. {}", i + 1, song.describe()))
+                .collect();
+            if next.is_empty() {
+                "The queue is empty. Request a song with !sr".to_string()
+            } else {
+                format!(
+                    "{} in the queue, up next: {}",
+                    app.songs.queue.len(),
+                    next.join(" | ")
+                )
+            }
+        }
+        "wrongsong" => match app.songs.wrong_song(user) {
+            Some(song) => {
+                app.push(Tab::Songs, format!("- {} took back {}", user, song.request));
+                song_changed(app, tx);
+                format!("@{} Removed {}", user, song.request)
+            }
+            None => format!("@{} You have no songs in the queue", user),
+        },
+        // skip
+        _ if !chat_commands::is_mod(role) => format!("@{} Only mods can skip songs", user),
+        _ => {
+            if app.songs.now_playing.is_none() {
+                format!("@{} Nothing's playing", user)
+            } else {
+                app.push(Tab::Songs, format!(">> {} skipped the song", user));
+                skip_song(app, tx, player);
+                match &app.songs.now_playing {
+                    Some(song) => format!("Skipped. Now playing: {}", song.describe()),
+                    None => "Skipped. The queue is empty".to_string(),
+                }
+            }
+        }
+    };
+    app.outbox.say(Priority::Normal, reply);
+}
+
+// Ctrl+U promote, Ctrl+X remove, Ctrl+N skip on the Songs tab
+pub(crate) fn handle_song_key(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    player: Option<&mpsc::UnboundedSender<PlayerCommand>>,
+    action: char,
+) {
+    if action == 'n' {
+        app.push(Tab::Songs, ">> skipped".to_string());
+        skip_song(app, tx, player);
+        return;
+    }
+    let Some(index) = app.song_selected else {
+        return;
+    };
+    if action == 'u' {
+        if let Some(song) = app.songs.promote(index) {
+            let line = format!("^ {} is up next", song.request);
+            app.push(Tab::Songs, line);
+            app.song_selected = Some(0);
+        }
+    } else if let Some(song) = app.songs.remove(index) {
+        app.push(Tab::Songs, format!("- removed {}", song.request));
+    }
+    song_changed(app, tx);
+}
+
+pub(crate) fn skip_song(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    player: Option<&mpsc::UnboundedSender<PlayerCommand>>,
+) {
+    app.songs.advance();
+    if let Some(song) = &app.songs.now_playing {
+        let line = format!(">> now playing {}", song.describe());
+        app.push(Tab::Songs, line);
+    }
+    play_song(app, player);
+    song_changed(app, tx);
+}
+
+// Hand the current song to the player, or stop it when there's none
+pub(crate) fn play_song(app: &App, player: Option<&mpsc::UnboundedSender<PlayerCommand>>) {
+    if let Some(player) = player {
+        let command = match &app.songs.now_playing {
+            Some(song) => PlayerCommand::Play(song.clone()),
+            None => PlayerCommand::Stop,
+        };
+        let _ = player.send(command);
+    }
+}
+
+// After the queue moved: keep the selection in range and tell the overlay
+pub(crate) fn song_changed(app: &mut App, tx: &mpsc::UnboundedSender<AppEvent>) {
+    let last = app.songs.queue.len().checked_sub(1);
+    app.song_selected = app.song_selected.zip(last).map(|(i, last)| i.min(last));
+    let _ = tx.send(AppEvent::Songs {
+        now_playing: app.songs.now_playing.clone(),
+        up_next: app.songs.up_next().cloned(),
+    });
+}
+
+// Polls come in as AppEvent::Poll. A native poll that Twitch won't take
+// (the channel isn't an affiliate, the token lacks the scope) is counted from
+// chat instead.
+pub(crate) fn start_poll(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    question: String,
+    options: Vec<String>,
+    duration: std::time::Duration,
+) {
+    if !app.config.poll_native {
+        let _ = tx.send(AppEvent::Poll(Poll::new(question, options, duration, None)));
+        return;
+    }
+    let (client, config, tx) = (client.clone(), app.config.clone(), tx.clone());
+    tokio::spawn(async move {
+        let id = match create_poll(&client, &config, &question, &options, duration).await {
+            Ok(id) => id,
+            Err(e) => {
+                let _ = tx.send(AppEvent::Error(format!(
+                    "Native poll failed, counting chat votes instead: {:#}",
+                    e
+                )));
+                let _ = tx.send(AppEvent::Poll(Poll::new(question, options, duration, None)));
+                return;
+            }
+        };
+        let mut poll = Poll::new(question, options, duration, Some(id.clone()));
+        let _ = tx.send(AppEvent::Poll(poll.clone()));
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+        while !poll.ended {
+            interval.tick().await;
+            match get_poll(&client, &config, &id).await {
+                Ok(progress) => {
+                    poll.update(&progress);
+                    if tx.send(AppEvent::Poll(poll.clone())).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(AppEvent::Debug(format!("Poll progress: {}", e)));
+                }
+            }
+        }
+    });
+}
+
+// LINK_PROTECTION: delete the message through Helix and tell its sender why
+pub(crate) fn remove_link(
+    app: &App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    user: &str,
+    message_id: &str,
+    link: &str,
+) {
+    let event = ModEvent {
+        kind: ModKind::Delete,
+        user: Some(user.to_string()),
+        moderator: Some(app.bot_login.clone()),
+        detail: format!("link without a permit: {}", link),
+    };
+    let warning = format!(
+        "@{} Please ask a mod for a !permit before posting links",
+        user
+    );
+    let (config, tx, client) = (app.config.clone(), tx.clone(), client.clone());
+    let outbox = app.outbox.clone();
+    let message_id = message_id.to_string();
+    tokio::spawn(async move {
+        match delete_chat_message(&client, &config, &message_id).await {
+            Ok(()) => {
+                let _ = tx.send(AppEvent::Moderation(event));
+                outbox.say(Priority::High, warning);
+            }
+            Err(e) => {
+                let _ = tx.send(AppEvent::Error(format!("{:#}", e)));
+            }
+        }
+    });
+}
+
+// RAID_WELCOME and RAID_SHOUTOUT: look up what the raider was streaming, have
+// the AI welcome their viewers with it, then shout them out
+pub(crate) fn welcome_raid(
+    config: &Arc<Config>,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    outbox: &Outbox,
+    client: &reqwest::Client,
+    from: &str,
+    from_id: &str,
+    viewers: u32,
+) {
+    if !config.raid_welcome && !config.raid_shoutout {
+        return;
+    }
+    let (config, tx, client) = (config.clone(), tx.clone(), client.clone());
+    let outbox = outbox.clone();
+    let (from, from_id) = (from.to_string(), from_id.to_string());
+    tokio::spawn(async move {
+        if config.raid_welcome {
+            let channel = get_channel_info(&client, &config, &from_id)
+                .await
+                .map_err(|e| {
+                    let _ = tx.send(AppEvent::Error(format!("Raider's channel: {:#}", e)));
+                })
+                .ok();
+            let streaming = channel
+                .as_ref()
+                .filter(|channel| !channel.game_name.is_empty())
+                .map(|channel| format!("{} (\"{}\")", channel.game_name, channel.title));
+            let prompt = format!(
+                "Streamer {} just raided the channel with {} viewers{}. Welcome the raiders \
+                 excitedly in one or two short sentences, mentioning what {} was streaming if \
+                 you know it. Do not ask any questions.",
+                from,
+                viewers,
+                streaming
+                    .as_ref()
+                    .map(|streaming| format!(" after streaming {}", streaming))
+                    .unwrap_or_default(),
+                from
+            );
+            let welcome = match ask_ai(&prompt, &config).await {
+                Ok(reply) if !reply.trim().is_empty() => reply.trim().to_string(),
+                _ => match &channel {
+                    Some(channel) if !channel.game_name.is_empty() => format!(
+                        "Welcome raiders from {}! They were streaming {}, go give them a follow!",
+                        from, channel.game_name
+                    ),
+                    _ => format!("Welcome raiders from {}!", from),
+                },
+            };
+            outbox.say(Priority::Normal, welcome);
+        }
+        if config.raid_shoutout {
+            if let Err(e) = send_shoutout(&client, &config, &from_id).await {
+                let _ = tx.send(AppEvent::Error(format!("{:#}", e)));
+            }
+        }
+    });
+}
+
+// Twitch shows native polls itself; chat polls need telling how to vote
+// Reads the tip's message aloud (TIPS_READ) and has the AI thank the tipper
+// (TIPS_THANK)
+pub(crate) fn thank_tipper(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    ai_tasks: &mut HashMap<u64, tokio::task::AbortHandle>,
+    tts: &audio::TtsQueue,
+    alert: &StreamAlert,
+) {
+    let StreamAlert::Tip { user, message, .. } = alert else {
+        return;
+    };
+    let settings = &app.config.tips;
+    if settings.read && app.tts_enabled && !message.is_empty() {
+        tts.speak(
+            SpeechKind::Redeem,
+            user,
+            message,
+            format!("{} says: {}", user, message),
+        );
+    }
+    if settings.thank {
+        let prompt = format!(
+            "{} Thank them warmly in a single short sentence{}. Do not ask any questions.",
+            alert.describe(),
+            if message.is_empty() {
+                String::new()
+            } else {
+                format!(", they wrote: \"{}\"", message)
+            }
+        );
+        spawn_ai_request(app, tx, ai_tasks, user, prompt, Priority::Normal);
+    }
+}
+
+// Both scopes of each of `boards`, for the overlay
+pub(crate) fn send_leaderboards(
+    leaderboards: &Leaderboards,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    boards: &[Board],
+) {
+    for &board in boards {
+        for scope in [Scope::Stream, Scope::AllTime] {
+            let _ = tx.send(AppEvent::Leaderboard(leaderboards.standings(board, scope)));
+        }
+    }
+}
+
+pub(crate) fn announce_poll(app: &mut App, poll: &Poll) {
+    app.push(Tab::Chat, format!("** Poll started: {}", poll.question));
+    if poll.native_id.is_none() {
+        let options: Vec<String> = poll
+            .options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| format!("{} {}", i + 1, option))
+            .collect();
+        let message = format!(
+            "POLL: {} Vote by typing {} ({}s)",
+            poll.question,
+            options.join(", "),
+            poll.time_left().as_secs()
+        );
+        app.outbox.say(Priority::Normal, message);
+    }
+}
+
+pub(crate) fn end_chat_poll(app: &mut App, tx: &mpsc::UnboundedSender<AppEvent>) {
+    if let Some(poll) = app.poll.as_mut() {
+        poll.ended = true;
+        let _ = tx.send(AppEvent::Poll(poll.clone()));
+        finish_poll(app);
+    }
+}
+
+pub(crate) fn finish_poll(app: &mut App) {
+    let Some(poll) = app.poll.clone() else {
+        return;
+    };
+    app.push(
+        Tab::Chat,
+        format!("** {} ({})", poll.summary(), poll.standings()),
+    );
+    let (config, outbox) = (app.config.clone(), app.outbox.clone());
+    tokio::spawn(async move {
+        let announcement = poll.announcement(&config).await;
+        outbox.say(Priority::Normal, announcement);
+    });
+}
+
+// AI tab: Ctrl+X cancels (or rejects), Ctrl+R resends, Ctrl+A approves the
+// selected request; the dashboard does the same by id. Returns whether the
+// request was in a state for `action`.
+pub(crate) fn handle_ai_request(
+    app: &mut App,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    ai_tasks: &mut HashMap<u64, tokio::task::AbortHandle>,
+    id: u64,
+    action: AiAction,
+) -> bool {
+    let Some(request) = app.ai_requests.iter_mut().find(|request| request.id == id) else {
+        return false;
+    };
+    let (user, prompt, priority) = (
+        request.user.clone(),
+        request.prompt.clone(),
+        request.priority,
+    );
+
+    match (action, request.status.clone()) {
+        (AiAction::Reject, AiStatus::Pending) => {
+            if let Some(task) = ai_tasks.remove(&id) {
+                task.abort();
+            }
+            request.status = AiStatus::Cancelled;
+            request.finished = Some(std::time::Instant::now());
+            app.push(Tab::Ai, format!("xx #{} cancelled", id));
+            // Lets the overlay drop its indicator; ignored here since it's no longer pending
+            let _ = tx.send(AppEvent::AiFinished {
+                id,
+                result: Err("Cancelled".to_string()),
+            });
+        }
+        (AiAction::Reject, AiStatus::AwaitingApproval(reply)) => {
+            request.status = AiStatus::Rejected(reply);
+            app.push(Tab::Ai, format!("xx #{} rejected", id));
+        }
+        (AiAction::Approve, AiStatus::AwaitingApproval(reply)) => {
+            request.status = AiStatus::Sent(reply.clone());
+            app.push(Tab::Ai, format!("ok #{} approved", id));
+            send_ai_reply(&app.outbox, priority, &user, &reply);
+            app.ai_memory.remember(&user, &prompt, &reply);
+        }
+        (AiAction::Retry, AiStatus::Failed(_) | AiStatus::Cancelled | AiStatus::Rejected(_)) => {
+            spawn_ai_request(app, tx, ai_tasks, &user, prompt, priority);
+            app.ai_selected = app.ai_requests.len().checked_sub(1);
+        }
+        _ => return false,
+    }
+    true
+}
+
+// Ctrl+G and /diagnostics
+pub(crate) fn dump_diagnostics(app: &mut App, tts: &audio::TtsQueue) {
+    match diagnostics::dump(app, &[("tts", tts.waiting())]) {
+        Ok(path) => app.notify(
+            Severity::Info,
+            format!("Diagnostics saved to {}", path.display()),
+        ),
+        Err(e) => app.notify(Severity::Error, format!("Diagnostics not saved: {:#}", e)),
+    }
+}
+
+// F8 / Shift+F8 / Ctrl+F8 and /tts. Muting also cuts off the current message,
+// empties the queue and stops sound effects, so the bot goes quiet immediately.
+pub(crate) fn control_tts(
+    app: &mut App,
+    tts: &audio::TtsQueue,
+    sounds: &audio::AudioEngine,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    control: commands::TtsControl,
+) {
+    use commands::TtsControl;
+    let enabled = match control {
+        TtsControl::Toggle => !app.tts_enabled,
+        TtsControl::On => true,
+        TtsControl::Off => false,
+        TtsControl::Skip => {
+            tts.skip();
+            return;
+        }
+        TtsControl::Clear => {
+            let dropped = tts.clear();
+            app.notify(
+                Severity::Info,
+                format!("Cleared {} queued TTS messages", dropped),
+            );
+            return;
+        }
+        TtsControl::Rate(None) | TtsControl::Pitch(None) => {
+            let line = format!("TTS {}", app.speech.describe());
+            app.push(Tab::Chat, line);
+            return;
+        }
+        TtsControl::Rate(Some(rate)) => {
+            app.speech.rate = rate;
+            return change_speech(app, tts);
+        }
+        TtsControl::Pitch(Some(pitch)) => {
+            app.speech.pitch = pitch;
+            return change_speech(app, tts);
+        }
+        TtsControl::Voice(voice) => {
+            app.speech.voice = voice;
+            return change_speech(app, tts);
+        }
+    };
+    if !enabled {
+        tts.clear();
+        tts.skip();
+        sounds.stop();
+    }
+    if enabled != app.tts_enabled {
+        app.tts_enabled = enabled;
+        let _ = tx.send(AppEvent::TtsMuted(!enabled));
+        app.notify(Severity::Info, if enabled { "TTS on" } else { "TTS muted" });
+    }
+}
+
+// /tts rate|pitch|voice, from the next message on
+pub(crate) fn change_speech(app: &mut App, tts: &audio::TtsQueue) {
+    tts.set_settings(app.speech.clone());
+    let line = format!("TTS {}", app.speech.describe());
+    app.notify(Severity::Info, line);
+}
+
+// Settings changed on disk. Everything read per message or per request
+// (theme, filters, AI, volumes, voice) applies right away; connections, the
+// TTS engine and the Twitch identity stay as they are until a restart.
+pub(crate) fn reload_config(
+    app: &mut App,
+    sounds: &audio::AudioEngine,
+    tts: &audio::TtsQueue,
+    timers: &mut Timers,
+    loaded: &mut Config,
+    mut config: Config,
+) {
+    let restart = loaded.bot_user_id != config.bot_user_id
+        || loaded.channel_user_id != config.channel_user_id
+        || loaded.channel_name != config.channel_name
+        || loaded.client_id != config.client_id
+        || loaded.tts.backend != config.tts.backend
+        || loaded.tts.piper_model != config.tts.piper_model
+        || loaded.tts_queue_max != config.tts_queue_max
+        || loaded.chat_log != config.chat_log
+        || loaded.endpoints != config.endpoints
+        || loaded.matrix != config.matrix
+        || loaded.mqtt != config.mqtt;
+
+    // Live changes made with /volume and /tts stay unless the file changed them
+    if config.volumes != loaded.volumes {
+        app.volumes = config.volumes;
+        sounds.set_volumes(app.volumes);
+        tts.set_volume(app.volumes.tts);
+    }
+    if config.tts.settings != loaded.tts.settings {
+        app.speech = config.tts.settings.clone();
+        tts.set_settings(app.speech.clone());
+    }
+    app.outbox.set_rate_limit(config.chat_rate_limit);
+    // Same for timers added or removed with /timer
+    if config.timers != loaded.timers || config.timer_jitter != loaded.timer_jitter {
+        timers.replace(&config.timers, config.timer_jitter);
+    }
+    let keep_persona = config.ai_params.persona == loaded.ai_params.persona;
+    let keep_approval = config.ai_require_approval == loaded.ai_require_approval;
+    *loaded = config.clone();
+    // And the persona and approval set through the API, if the persona's
+    // still there
+    if keep_persona {
+        let live = app.config.ai_params.persona.clone();
+        let _ = config.ai_params.set_persona(live.as_deref());
+    }
+    if keep_approval {
+        config.ai_require_approval = app.config.ai_require_approval;
+    }
+
+    // Resolved at startup or in use by the running connections
+    let running = &app.config;
+    let config = Config {
+        bot_user_id: running.bot_user_id.clone(),
+        channel_user_id: running.channel_user_id.clone(),
+        channel_name: running.channel_name.clone(),
+        client_id: running.client_id.clone(),
+        oauth_token: running.oauth_token.clone(),
+        data_dir: running.data_dir.clone(),
+        cache_dir: running.cache_dir.clone(),
+        legacy_dir: running.legacy_dir.clone(),
+        config_file: running.config_file.clone(),
+        tts: running.tts.clone(),
+        tts_queue_max: running.tts_queue_max,
+        chat_log: running.chat_log.clone(),
+        endpoints: running.endpoints.clone(),
+        matrix: running.matrix.clone(),
+        mqtt: running.mqtt.clone(),
+        demo: running.demo,
+        ..config
+    };
+    app.filters = Filters {
+        enabled: app.filters.enabled,
+        muted_users: std::mem::take(&mut app.filters.muted_users),
+        ..Filters::from_config(&config)
+    };
+    app.config = Arc::new(config);
+
+    app.push(Tab::Log, "Config reloaded".to_string());
+    if restart {
+        app.notify(
+            Severity::Warning,
+            "Config reloaded; Twitch, TTS engine, chat log, Matrix and MQTT changes need a restart"
+                .to_string(),
+        );
+    }
+}
+
+// /timer: changes last until the next restart or config reload that
+// changes the timers
+pub(crate) fn control_timers(app: &mut App, timers: &mut Timers, control: commands::TimerControl) {
+    use commands::TimerControl;
+    match control {
+        TimerControl::List => {
+            let lines = timers.list();
+            if lines.is_empty() {
+                app.push(Tab::Chat, "No timers (set them with TIMERS)".to_string());
+            }
+            let state = if timers.enabled { "on" } else { "paused" };
+            for line in lines {
+                app.push(Tab::Chat, format!("Timer {} ({})", line, state));
+            }
+        }
+        TimerControl::Add(timer) => {
+            app.notify(Severity::Info, format!("Timer added: {}", timer.describe()));
+            timers.add(timer);
+        }
+        TimerControl::Remove(n) => match timers.remove(n) {
+            Some(timer) => app.notify(Severity::Info, format!("Timer removed: {}", timer.message)),
+            None => app.notify(Severity::Error, format!("There's no timer {}", n)),
+        },
+        TimerControl::On | TimerControl::Off => {
+            timers.enabled = control == TimerControl::On;
+            let state = if timers.enabled { "on" } else { "paused" };
+            app.notify(Severity::Info, format!("Timers {}", state));
+        }
+    }
+}
+
+// /volume: set one channel, or list the current levels
+pub(crate) fn change_volume(
+    app: &mut App,
+    sounds: &audio::AudioEngine,
+    tts: &audio::TtsQueue,
+    channel: Option<VolumeChannel>,
+    level: Option<u8>,
+) {
+    match (channel, level) {
+        (Some(channel), Some(level)) => {
+            app.volumes.set(channel, level);
+            sounds.set_volumes(app.volumes);
+            tts.set_volume(app.volumes.tts);
+            app.notify(
+                Severity::Info,
+                format!("{} volume {}%", channel.name(), level),
+            );
+        }
+        (Some(channel), None) => {
+            let line = format!("{} volume {}%", channel.name(), app.volumes.get(channel));
+            app.push(Tab::Chat, line);
+        }
+        (None, _) => {
+            let levels: Vec<String> = VolumeChannel::ALL
+                .iter()
+                .map(|channel| format!("{} {}%", channel.name(), app.volumes.get(*channel)))
+                .collect();
+            app.push(Tab::Chat, format!("Volume: {}", levels.join(", ")));
+        }
+    }
+}
+
+pub(crate) fn save_ui_state(app: &mut App) {
+    if let Err(e) = app.save_ui_state() {
+        app.notify(Severity::Error, format!("Failed to save UI state: {}", e));
+    }
+}
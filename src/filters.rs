@@ -0,0 +1,68 @@
+use crate::config::Config;
+use crate::state::ChatLine;
+use regex::Regex;
+use std::collections::HashSet;
+
+// Display filters for the Chat tab. They only decide what gets rendered;
+// filtered messages stay in the log (and search) untouched.
+pub struct Filters {
+    pub enabled: bool,
+    pub hide_bots: bool,
+    pub bot_accounts: HashSet<String>,
+    pub hide_commands: bool,
+    pub mute_regex: Option<Regex>,
+    pub muted_users: HashSet<String>,
+}
+
+impl Filters {
+    pub fn from_config(config: &Config) -> Self {
+        // Config::from_env already rejected invalid patterns
+        let mute_regex = config
+            .mute_regex
+            .as_ref()
+            .and_then(|pattern| Regex::new(pattern).ok());
+
+        Self {
+            enabled: true,
+            hide_bots: config.hide_bots,
+            bot_accounts: config.bot_accounts.iter().cloned().collect(),
+            hide_commands: config.hide_commands,
+            mute_regex,
+            muted_users: HashSet::new(),
+        }
+    }
+
+    pub fn hides(&self, line: &ChatLine) -> bool {
+        // System lines (joins, errors, ...) are never filtered
+        let Some(user) = &line.user else {
+            return false;
+        };
+        if !self.enabled {
+            return false;
+        }
+
+        let user = user.to_lowercase();
+        self.muted_users.contains(&user)
+            || (self.hide_bots && self.bot_accounts.contains(&user))
+            || (self.hide_commands && line.text.trim_start().starts_with('!'))
+            || self
+                .mute_regex
+                .as_ref()
+                .is_some_and(|re| re.is_match(&line.text))
+    }
+
+    pub fn is_muted(&self, user: &str) -> bool {
+        self.muted_users.contains(&user.to_lowercase())
+    }
+
+    /// Toggle a per-user mute. Returns true if the user is now muted.
+    pub fn toggle_mute(&mut self, user: &str) -> bool {
+        let user = user.to_lowercase();
+        if self.muted_users.remove(&user) {
+            false
+        } else {
+            self.muted_users.insert(user);
+            true
+        }
+    }
+}
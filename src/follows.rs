@@ -0,0 +1,125 @@
+use crate::ai;
+use crate::config::{env_flag, var, Config};
+use anyhow::{Context, Result};
+use std::time::{Duration, Instant};
+
+// Thanking new followers in chat. Follows are collected for FOLLOW_THANKS_WINDOW
+// seconds after the first one and thanked together in a single message, by
+// the AI when it answers. During a follow storm the bot thanks at most once
+// per FOLLOW_THANKS_COOLDOWN; follows that come in meanwhile wait for the next
+// message.
+
+// Names listed in one message, the rest are "and N more"
+const MAX_NAMES: usize = 10;
+
+const FALLBACKS: [&str; 4] = [
+    "Thanks for the follow{s}, {names}!",
+    "Welcome aboard {names}, thanks for following!",
+    "{names}: thank you for the follow{s}, glad to have you here!",
+    "Big thanks to {names} for following!",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowSettings {
+    pub thank: bool,
+    // How long to wait after a follow for others to thank together
+    pub window: Duration,
+    // Least time between two thank-you messages
+    pub cooldown: Duration,
+}
+
+impl FollowSettings {
+    // FOLLOW_THANKS, FOLLOW_THANKS_WINDOW, FOLLOW_THANKS_COOLDOWN
+    pub fn from_env() -> Result<Self> {
+        let seconds = |name: &str, default: u64| -> Result<Duration> {
+            match var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse::<u64>()
+                    .map(Duration::from_secs)
+                    .with_context(|| format!("{} must be seconds, got '{}'", name, value)),
+                Err(_) => Ok(Duration::from_secs(default)),
+            }
+        };
+        let thank = env_flag("FOLLOW_THANKS", true)?;
+        Ok(Self {
+            thank,
+            window: seconds("FOLLOW_THANKS_WINDOW", 20)?,
+            cooldown: seconds("FOLLOW_THANKS_COOLDOWN", 120)?,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FollowThanks {
+    // Waiting to be thanked, in the order they followed
+    pending: Vec<String>,
+    // When the first of them followed
+    since: Option<Instant>,
+    last_thanks: Option<Instant>,
+}
+
+impl FollowThanks {
+    pub fn add(&mut self, user: &str, now: Instant) {
+        if self
+            .pending
+            .iter()
+            .any(|pending| pending.eq_ignore_ascii_case(user))
+        {
+            return;
+        }
+        self.pending.push(user.to_string());
+        self.since.get_or_insert(now);
+    }
+
+    /// The followers to thank now, once the window since the first of them
+    /// is over and the last thank-you is at least the cooldown ago.
+    pub fn due(&mut self, now: Instant, settings: &FollowSettings) -> Option<Vec<String>> {
+        let since = self.since?;
+        if now.duration_since(since) < settings.window {
+            return None;
+        }
+        if self
+            .last_thanks
+            .is_some_and(|last| now.duration_since(last) < settings.cooldown)
+        {
+            return None;
+        }
+        self.since = None;
+        self.last_thanks = Some(now);
+        Some(std::mem::take(&mut self.pending))
+    }
+}
+
+/// "@a", "@a and @b", "@a, @b and @c", or "@a, @b ... and 5 more"
+pub fn names(users: &[String]) -> String {
+    let mut names: Vec<String> = users
+        .iter()
+        .take(MAX_NAMES)
+        .map(|user| format!("@{}", user))
+        .collect();
+    if users.len() > MAX_NAMES {
+        names.push(format!("{} more", users.len() - MAX_NAMES));
+    }
+    match names.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// One thank-you for all of `users`: the AI's if it answers, a canned one
+/// picked at random otherwise.
+pub async fn thank(users: &[String], config: &Config) -> String {
+    let names = names(users);
+    let prompt = format!(
+        "{} just followed the channel. Thank them together in a single short sentence that mentions every one of them as written; vary it, don't start with \"Thanks\". Do not ask any questions.",
+        names
+    );
+    match ai::ask_ai(&prompt, config).await {
+        Ok(thanks) if !thanks.trim().is_empty() => thanks.trim().to_string(),
+        _ => FALLBACKS[fastrand::usize(..FALLBACKS.len())]
+            .replace("{s}", if users.len() == 1 { "" } else { "s" })
+            .replace("{names}", &names),
+    }
+}
@@ -1,113 +1,790 @@
 use iced::futures::SinkExt;
-use iced::widget::{column, container, scrollable, text};
-use iced::{executor, time, Application, Command, Element, Length, Subscription, Theme};
+use iced::multi_window::Application;
+use iced::widget::{column, container, progress_bar, row, scrollable, text};
+use iced::{executor, time, window, Command, Element, Length, Subscription, Theme};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
 
-use choui_the_no_gui_chatbot::state::AppEvent;
+use choui_the_no_gui_chatbot::config::{
+    parse_hex_color, OverlayConfig, OverlayWindow, Rgb, WindowPlacement,
+};
+use choui_the_no_gui_chatbot::counters;
+use choui_the_no_gui_chatbot::leaderboards::{Board, Scope, Standings};
+use choui_the_no_gui_chatbot::polls::Poll;
+use choui_the_no_gui_chatbot::songs::Song;
+use choui_the_no_gui_chatbot::state::{AlertKind, AppEvent, ChatMessage, Goal};
+use choui_the_no_gui_chatbot::twitch::StreamStats;
+
+// Chat messages the overlay shows, newest last
+const OVERLAY_MESSAGES: usize = 20;
 
 pub struct Overlay {
-    messages: Vec<String>,
-    alert: Option<(String, std::time::Instant)>,
+    messages: VecDeque<Arc<ChatMessage>>,
+    alert: Option<(AlertCard, std::time::Instant)>,
+    // Cards waiting for the current one to finish, oldest first
+    queue: VecDeque<AlertCard>,
+    stream: Option<StreamStats>,
+    goal: Option<Goal>,
+    // When the goal was completed, for the celebration in goal_view()
+    celebrating: Option<std::time::Instant>,
+    // The running poll, and for a while the result of the last one
+    poll: Option<Poll>,
+    poll_ended: Option<std::time::Instant>,
+    // Counter values by name; [overlay] counters picks the ones shown
+    counters: HashMap<String, i64>,
+    // Cheer and gift leaders, by board and scope
+    leaderboards: HashMap<(Board, Scope), Standings>,
+    // Song requests: the one playing and the one after it
+    now_playing: Option<Song>,
+    up_next: Option<Song>,
+    // AI requests in flight, by id
+    thinking: HashSet<u64>,
+    // (TTS id, speaker, text) of the message being read aloud
+    speaking: Option<(u64, String, String)>,
+    tts_muted: bool,
+    // What raised the shield and when, while it's up
+    shield: Option<(String, std::time::Instant)>,
+    // Time of the last tick, for animating the card in view()
+    now: std::time::Instant,
+    started: std::time::Instant,
     receiver: Arc<Mutex<Option<broadcast::Receiver<AppEvent>>>>,
+    config: OverlayConfig,
+    // What each open window shows
+    windows: HashMap<window::Id, (OverlayWindow, WindowPlacement)>,
+}
+
+// Settings for one overlay window: borderless, see-through and on top
+pub fn window_settings(placement: &WindowPlacement) -> window::Settings {
+    window::Settings {
+        transparent: true,
+        decorations: false, // Remove title bar for overlay look
+        level: window::Level::AlwaysOnTop,
+        size: iced::Size {
+            width: placement.width,
+            height: placement.height,
+        },
+        position: match placement.position {
+            Some((x, y)) => window::Position::Specific(iced::Point { x, y }),
+            None => window::Position::Default,
+        },
+        ..Default::default()
+    }
+}
+
+pub struct Flags {
+    pub receiver: Arc<Mutex<Option<broadcast::Receiver<AppEvent>>>>,
+    pub config: OverlayConfig,
+}
+
+struct AlertCard {
+    kind: AlertKind,
+    text: String,
+    // Same-kind alerts folded into this card once the queue was full
+    more: usize,
+}
+
+// How long the bar flashes after the goal is reached
+const CELEBRATION_TIME: std::time::Duration = std::time::Duration::from_secs(6);
+
+// How long a poll's result stays up after it ends
+const POLL_RESULT_TIME: std::time::Duration = std::time::Duration::from_secs(15);
+
+// How long each leaderboard stays up before the next one
+const LEADERBOARD_TIME: std::time::Duration = std::time::Duration::from_secs(10);
+
+// While others are waiting a card only stays up this long, so bursts drain
+const MIN_ALERT_TIME: std::time::Duration = std::time::Duration::from_secs(2);
+// Past this many waiting cards, new alerts are counted on a queued card of
+// the same kind ("+N more") instead of getting their own
+const MAX_QUEUED_ALERTS: usize = 8;
+
+// Icon, accent color and how long the card stays up, per alert kind
+fn card_look(kind: AlertKind) -> (&'static str, iced::Color, std::time::Duration) {
+    use std::time::Duration;
+    match kind {
+        AlertKind::Join => (
+            "→",
+            iced::Color::from_rgb(1.0, 0.3, 0.3),
+            Duration::from_secs(5),
+        ),
+        AlertKind::Follow => (
+            "♥",
+            iced::Color::from_rgb(1.0, 0.45, 0.7),
+            Duration::from_secs(5),
+        ),
+        AlertKind::Subscribe => (
+            "★",
+            iced::Color::from_rgb(0.6, 0.4, 1.0),
+            Duration::from_secs(7),
+        ),
+        AlertKind::GiftSub => (
+            "✦",
+            iced::Color::from_rgb(0.3, 0.8, 1.0),
+            Duration::from_secs(8),
+        ),
+        AlertKind::Raid => (
+            "»",
+            iced::Color::from_rgb(1.0, 0.6, 0.1),
+            Duration::from_secs(10),
+        ),
+        AlertKind::Milestone => (
+            "▲",
+            iced::Color::from_rgb(1.0, 0.85, 0.2),
+            Duration::from_secs(6),
+        ),
+        AlertKind::Cheer => (
+            "◆",
+            iced::Color::from_rgb(0.3, 1.0, 0.5),
+            Duration::from_secs(7),
+        ),
+        AlertKind::Tip => (
+            "$",
+            iced::Color::from_rgb(0.2, 0.9, 0.8),
+            Duration::from_secs(8),
+        ),
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     EventOccurred(AppEvent),
     Tick(std::time::Instant),
-    None,
+}
+
+impl Overlay {
+    // Kinds turned off in OVERLAY_ALERTS are dropped
+    fn show_alert(&mut self, kind: AlertKind, text: String) {
+        if !self.config.alerts.contains(&kind) {
+            return;
+        }
+        if self.queue.len() >= MAX_QUEUED_ALERTS {
+            if let Some(card) = self.queue.iter_mut().rev().find(|card| card.kind == kind) {
+                card.more += 1;
+                return;
+            }
+        }
+        self.queue.push_back(AlertCard {
+            kind,
+            text,
+            more: 0,
+        });
+        if self.alert.is_none() {
+            self.next_alert(std::time::Instant::now());
+        }
+    }
+
+    fn next_alert(&mut self, now: std::time::Instant) {
+        self.alert = self.queue.pop_front().map(|card| (card, now));
+    }
+
+    fn display_time(&self, card: &AlertCard) -> std::time::Duration {
+        let (_, _, duration) = card_look(card.kind);
+        if self.queue.is_empty() {
+            duration
+        } else {
+            duration.min(MIN_ALERT_TIME)
+        }
+    }
+
+    // 0.0 (hidden) to 1.0 (fully shown): eased in after the card appears and
+    // back out before it leaves
+    fn alert_progress(&self, card: &AlertCard, start: std::time::Instant) -> f32 {
+        let animation = self.config.animation;
+        if animation.duration.is_zero() {
+            return 1.0;
+        }
+        let shown = self.now.saturating_duration_since(start);
+        let left = self.display_time(card).saturating_sub(shown);
+        let t = shown.min(left).as_secs_f32() / animation.duration.as_secs_f32();
+        animation.easing.apply(t)
+    }
+}
+
+impl Overlay {
+    fn chat_view(&self) -> Element<'_, Message> {
+        let chat_log = scrollable(
+            column(
+                self.messages
+                    .iter()
+                    .map(|msg| {
+                        // Names in the color the viewer picked on Twitch
+                        let name_color = msg
+                            .color
+                            .as_deref()
+                            .and_then(|color| parse_hex_color(color).ok())
+                            .unwrap_or(self.config.text_color);
+                        row![
+                            text(format!("{}: ", msg.display_name))
+                                .size(self.config.font_size)
+                                .style(rgb(name_color, 1.0)),
+                            text(&msg.text)
+                                .size(self.config.font_size)
+                                .style(rgb(self.config.text_color, 1.0))
+                                .width(Length::Fill),
+                        ]
+                        .into()
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .spacing(8),
+        )
+        .height(Length::Fill);
+
+        container(chat_log)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(10)
+            .style(iced::theme::Container::Custom(Box::new(
+                ChatBackgroundStyle(rgb(self.config.background, self.config.opacity)),
+            )))
+            .into()
+    }
+
+    // "● LIVE  42 viewers · 1:05 · Just Chatting", hidden while offline
+    fn stats_view(&self) -> Option<Element<'_, Message>> {
+        let stats = self.stream.as_ref().filter(|_| self.config.show_stats)?;
+        let mut label = format!(
+            "● LIVE  {} viewers · {}",
+            stats.viewer_count,
+            stats.uptime()
+        );
+        if !stats.game_name.is_empty() {
+            label.push_str(&format!(" · {}", stats.game_name));
+        }
+        Some(
+            container(
+                text(label)
+                    .size(self.config.font_size)
+                    .style(rgb(self.config.text_color, 1.0)),
+            )
+            .width(Length::Fill)
+            .padding(8)
+            .style(iced::theme::Container::Custom(Box::new(
+                ChatBackgroundStyle(rgb(self.config.background, self.config.opacity)),
+            )))
+            .into(),
+        )
+    }
+
+    // "Follower goal 31 / 100" over a progress bar. For a while after the goal
+    // is reached the label pulses and the bar cycles through colors.
+    fn goal_view(&self) -> Option<Element<'_, Message>> {
+        let goal = self.goal.as_ref()?;
+        let mut label = format!("{} {} / {}", goal.label, goal.current, goal.target);
+        let mut size = self.config.font_size as f32;
+        let mut bar_color = iced::Color::from_rgb(0.6, 0.4, 1.0);
+        if let Some(start) = self.celebrating {
+            let t = self.now.saturating_duration_since(start).as_secs_f32();
+            label = format!(
+                "🎉 {} reached! {} / {}",
+                goal.label, goal.current, goal.target
+            );
+            size *= 1.0 + 0.15 * (t * std::f32::consts::TAU * 1.5).sin().abs();
+            bar_color = hue((t * 0.5).fract());
+        } else if goal.is_complete() {
+            bar_color = iced::Color::from_rgb(0.3, 1.0, 0.5);
+        }
+
+        let bar = progress_bar(0.0..=1.0, goal.fraction()).height(12).style(
+            iced::theme::ProgressBar::Custom(Box::new(GoalBarStyle(
+                bar_color,
+                self.config.opacity,
+            ))),
+        );
+        Some(
+            container(
+                column![
+                    text(label)
+                        .size(size)
+                        .style(rgb(self.config.text_color, 1.0)),
+                    bar
+                ]
+                .spacing(6),
+            )
+            .width(Length::Fill)
+            .padding(10)
+            .style(iced::theme::Container::Custom(Box::new(
+                ChatBackgroundStyle(rgb(self.config.background, self.config.opacity)),
+            )))
+            .into(),
+        )
+    }
+
+    // A pulsing red banner on top of every window while the shield is up, so
+    // a hate raid can't go unnoticed
+    fn shield_view(&self) -> Option<Element<'_, Message>> {
+        let (reason, since) = self.shield.as_ref()?;
+        let t = self.now.saturating_duration_since(*since).as_secs_f32();
+        let pulse = 0.6 + 0.4 * (t * std::f32::consts::PI).sin().abs();
+        Some(
+            container(
+                column![
+                    text("🛡 SHIELD UP")
+                        .size(self.config.font_size as f32 * 1.6)
+                        .style(iced::Color::WHITE),
+                    text(reason)
+                        .size(self.config.font_size)
+                        .style(iced::Color::WHITE),
+                ]
+                .spacing(4),
+            )
+            .width(Length::Fill)
+            .padding(12)
+            .style(iced::theme::Container::Custom(Box::new(
+                ChatBackgroundStyle(iced::Color::from_rgba(0.85, 0.05, 0.05, pulse)),
+            )))
+            .into(),
+        )
+    }
+
+    // The question, and a bar with the votes for each option. The winner
+    // lights up once the poll is over.
+    fn poll_view(&self) -> Option<Element<'_, Message>> {
+        let poll = self.poll.as_ref()?;
+        let header = if poll.ended {
+            format!("📊 {}", poll.question)
+        } else {
+            format!("📊 {} ({}s)", poll.question, poll.time_left().as_secs())
+        };
+        let winners = if poll.ended {
+            poll.winners()
+        } else {
+            Vec::new()
+        };
+        let mut rows = column![text(header)
+            .size(self.config.font_size)
+            .style(rgb(self.config.text_color, 1.0))]
+        .spacing(4);
+        for (i, option) in poll.options.iter().enumerate() {
+            let color = if winners.contains(&option.as_str()) {
+                iced::Color::from_rgb(0.3, 1.0, 0.5)
+            } else {
+                iced::Color::from_rgb(0.6, 0.4, 1.0)
+            };
+            rows = rows.push(
+                text(format!(
+                    "{} {}: {} ({}%)",
+                    i + 1,
+                    option,
+                    poll.votes[i],
+                    poll.percent(i)
+                ))
+                .size(self.config.font_size)
+                .style(rgb(self.config.text_color, 1.0)),
+            );
+            rows = rows.push(
+                progress_bar(0.0..=100.0, poll.percent(i) as f32)
+                    .height(8)
+                    .style(iced::theme::ProgressBar::Custom(Box::new(GoalBarStyle(
+                        color,
+                        self.config.opacity,
+                    )))),
+            );
+        }
+        Some(
+            container(rows)
+                .width(Length::Fill)
+                .padding(10)
+                .style(iced::theme::Container::Custom(Box::new(
+                    ChatBackgroundStyle(rgb(self.config.background, self.config.opacity)),
+                )))
+                .into(),
+        )
+    }
+
+    // "Deaths: 5 · Wins: 2" for the counters in [overlay] counters
+    fn counters_view(&self) -> Option<Element<'_, Message>> {
+        if self.config.counters.is_empty() {
+            return None;
+        }
+        let label = self
+            .config
+            .counters
+            .iter()
+            .map(|name| counters::describe(name, self.counters.get(name).copied().unwrap_or(0)))
+            .collect::<Vec<_>>()
+            .join(" · ");
+        Some(
+            container(
+                text(label)
+                    .size(self.config.font_size)
+                    .style(rgb(self.config.text_color, 1.0)),
+            )
+            .width(Length::Fill)
+            .padding(8)
+            .style(iced::theme::Container::Custom(Box::new(
+                ChatBackgroundStyle(rgb(self.config.background, self.config.opacity)),
+            )))
+            .into(),
+        )
+    }
+
+    // With [overlay] leaderboard, the top cheerers and gifters this stream
+    // and all time, one board at a time; boards nobody is on yet are skipped
+    fn leaderboard_view(&self) -> Option<Element<'_, Message>> {
+        if !self.config.leaderboard {
+            return None;
+        }
+        let boards: Vec<&Standings> = Board::ALL
+            .into_iter()
+            .flat_map(|board| [(board, Scope::Stream), (board, Scope::AllTime)])
+            .filter_map(|key| self.leaderboards.get(&key))
+            .filter(|standings| !standings.leaders.is_empty())
+            .collect();
+        let turn = self.now.duration_since(self.started).as_secs() / LEADERBOARD_TIME.as_secs();
+        let standings = boards.get(turn as usize % boards.len().max(1))?;
+        let mut rows = column![text(format!(
+            "{} ({})",
+            standings.board.title(),
+            standings.scope.name()
+        ))
+        .size(self.config.font_size)
+        .style(rgb(self.config.text_color, 1.0))]
+        .spacing(4);
+        for (i, (user, amount)) in standings.leaders.iter().enumerate() {
+            rows = rows.push(
+                text(format!(
+                    "{}. {} · {}",
+                    i + 1,
+                    user,
+                    standings.board.amount(*amount)
+                ))
+                .size(self.config.font_size)
+                .style(rgb(self.config.text_color, 0.8)),
+            );
+        }
+        Some(
+            container(rows)
+                .width(Length::Fill)
+                .padding(8)
+                .style(iced::theme::Container::Custom(Box::new(
+                    ChatBackgroundStyle(rgb(self.config.background, self.config.opacity)),
+                )))
+                .into(),
+        )
+    }
+
+    // "Now playing" and "Up next" of the song requests, while a song plays
+    fn songs_view(&self) -> Option<Element<'_, Message>> {
+        let playing = self.now_playing.as_ref()?;
+        let mut rows = column![text(format!("♫ Now playing: {}", playing.describe()))
+            .size(self.config.font_size)
+            .style(rgb(self.config.text_color, 1.0))]
+        .spacing(4);
+        if let Some(next) = &self.up_next {
+            rows = rows.push(
+                text(format!("Up next: {}", next.describe()))
+                    .size(self.config.font_size)
+                    .style(rgb(self.config.text_color, 0.7)),
+            );
+        }
+        Some(
+            container(rows)
+                .width(Length::Fill)
+                .padding(10)
+                .style(iced::theme::Container::Custom(Box::new(
+                    ChatBackgroundStyle(rgb(self.config.background, self.config.opacity)),
+                )))
+                .into(),
+        )
+    }
+
+    // "CHOUIBOT is thinking..." with cycling dots while AI requests run
+    fn thinking_view(&self) -> Option<Element<'_, Message>> {
+        if self.thinking.is_empty() {
+            return None;
+        }
+        let phase = (self.now.duration_since(self.started).as_millis() / 400) as usize % 3;
+        let label = format!("CHOUIBOT is thinking{:<3}", ".".repeat(phase + 1));
+        Some(
+            container(
+                text(label)
+                    .size(self.config.font_size)
+                    .style(rgb(self.config.text_color, 0.8)),
+            )
+            .padding(10)
+            .into(),
+        )
+    }
+
+    // "Now speaking" strip under the chat while TTS reads a message
+    fn speaking_view(&self) -> Option<Element<'_, Message>> {
+        let (_, speaker, message) = self.speaking.as_ref()?;
+        let label = format!("♪ {}: {}", speaker, message);
+        Some(
+            container(
+                text(label)
+                    .size(self.config.font_size)
+                    .style(rgb(self.config.text_color, 1.0)),
+            )
+            .width(Length::Fill)
+            .padding(10)
+            .style(iced::theme::Container::Custom(Box::new(AlertStyle(
+                iced::Color::from_rgb(0.3, 0.8, 1.0),
+                self.config.opacity,
+            ))))
+            .into(),
+        )
+    }
+
+    // The current alert card, animated; `width` is the window's, for sliding in
+    fn alert_view(&self, width: f32) -> Option<Element<'_, Message>> {
+        let (card, start) = self.alert.as_ref()?;
+        let (icon, mut color, _) = card_look(card.kind);
+        let mut label = format!("{} {}", icon, card.text);
+        if card.more > 0 {
+            label.push_str(&format!("\n+{} more", card.more));
+        }
+
+        let animation = self.config.animation;
+        let progress = self.alert_progress(card, *start);
+        let alpha = if animation.fade { progress } else { 1.0 };
+        color.a = alpha;
+        let mut size = self.config.alert_font_size as f32;
+        if animation.scale {
+            size *= 0.5 + 0.5 * progress;
+        }
+        // Slides in from the right edge of the window
+        let offset = if animation.slide {
+            (1.0 - progress) * width
+        } else {
+            0.0
+        };
+
+        let card = container(text(label).size(size).style(color))
+            .padding(20)
+            .style(iced::theme::Container::Custom(Box::new(AlertStyle(
+                color, alpha,
+            ))));
+        Some(
+            container(card)
+                .padding(iced::Padding {
+                    top: 0.0,
+                    right: 0.0,
+                    bottom: 0.0,
+                    left: offset,
+                })
+                .into(),
+        )
+    }
 }
 
 impl Application for Overlay {
     type Executor = executor::Default;
     type Message = Message;
     type Theme = Theme;
-    type Flags = Arc<Mutex<Option<broadcast::Receiver<AppEvent>>>>;
+    type Flags = Flags;
 
     fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
+        // main.rs opens the first window; the others are spawned here
+        let mut windows = HashMap::new();
+        let mut commands = Vec::new();
+        for (i, (kind, placement)) in flags.config.windows.iter().enumerate() {
+            let id = if i == 0 {
+                window::Id::MAIN
+            } else {
+                let (id, spawn) = window::spawn(window_settings(placement));
+                commands.push(spawn);
+                id
+            };
+            windows.insert(id, (*kind, *placement));
+        }
+
         (
             Self {
-                messages: Vec::new(),
+                messages: VecDeque::with_capacity(OVERLAY_MESSAGES),
                 alert: None,
-                receiver: flags,
+                queue: VecDeque::new(),
+                stream: None,
+                goal: None,
+                poll: None,
+                poll_ended: None,
+                counters: HashMap::new(),
+                leaderboards: HashMap::new(),
+                now_playing: None,
+                up_next: None,
+                celebrating: None,
+                thinking: HashSet::new(),
+                speaking: None,
+                tts_muted: false,
+                shield: None,
+                now: std::time::Instant::now(),
+                started: std::time::Instant::now(),
+                receiver: flags.receiver,
+                config: flags.config,
+                windows,
             },
-            Command::none(),
+            Command::batch(commands),
         )
     }
 
-    fn title(&self) -> String {
-        String::from("CHOUIBOT Overlay")
+    fn title(&self, window: window::Id) -> String {
+        match self.windows.get(&window).map(|(kind, _)| kind) {
+            Some(OverlayWindow::Chat) => String::from("CHOUIBOT Chat"),
+            Some(OverlayWindow::Alerts) => String::from("CHOUIBOT Alerts"),
+            _ => String::from("CHOUIBOT Overlay"),
+        }
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::EventOccurred(event) => {
                 match event {
-                    AppEvent::ChatMessage { user, text } => {
-                        let msg = format!("{}: {}", user, text);
-                        self.messages.push(msg);
-                        if self.messages.len() > 20 {
-                            self.messages.remove(0);
+                    AppEvent::ChatMessage(message) => {
+                        if self.messages.len() == OVERLAY_MESSAGES {
+                            self.messages.pop_front();
                         }
-                        // TTS is now handled in main.rs (bot thread) so it plays regardless of focus
+                        self.messages.push_back(message);
+                        // TTS is now handled in the session (bot thread) so it plays regardless of focus
                     }
                     AppEvent::UserJoined(user) => {
-                        self.alert = Some((
+                        self.show_alert(
+                            AlertKind::Join,
                             format!("{} JOINED!", user.to_uppercase()),
-                            std::time::Instant::now(),
-                        ));
-                        // TTS is now handled in main.rs (bot thread) so it plays regardless of focus
+                        );
+                        // TTS is now handled in the session (bot thread) so it plays regardless of focus
+                    }
+                    AppEvent::Alert(alert) => {
+                        self.show_alert(alert.kind(), alert.describe());
+                    }
+                    AppEvent::StreamStats(stats) => {
+                        self.stream = stats;
+                    }
+                    AppEvent::Goal(goal) => {
+                        // Only a live completion celebrates, not one restored at startup
+                        let was_complete = self.goal.as_ref().map(Goal::is_complete);
+                        if goal.is_complete() && was_complete == Some(false) {
+                            self.celebrating = Some(std::time::Instant::now());
+                        }
+                        self.goal = Some(goal);
+                    }
+                    AppEvent::Poll(poll) => {
+                        self.poll_ended = poll.ended.then(std::time::Instant::now);
+                        self.poll = Some(poll);
+                    }
+                    AppEvent::Counter { name, value } => {
+                        self.counters.insert(name, value);
+                    }
+                    AppEvent::Leaderboard(standings) => {
+                        self.leaderboards
+                            .insert((standings.board, standings.scope), standings);
+                    }
+                    AppEvent::Songs {
+                        now_playing,
+                        up_next,
+                    } => {
+                        self.now_playing = now_playing;
+                        self.up_next = up_next;
+                    }
+                    AppEvent::AiStarted { id, .. } => {
+                        self.thinking.insert(id);
+                    }
+                    AppEvent::AiFinished { id, .. } => {
+                        self.thinking.remove(&id);
+                    }
+                    AppEvent::TtsStarted { id, speaker, text } => {
+                        self.speaking = Some((id, speaker, text));
+                    }
+                    // Readings can overlap; only clear if this one is still shown
+                    AppEvent::TtsFinished(id)
+                        if self
+                            .speaking
+                            .as_ref()
+                            .is_some_and(|(shown, _, _)| *shown == id) =>
+                    {
+                        self.speaking = None;
+                    }
+                    AppEvent::TtsMuted(muted) => {
+                        self.tts_muted = muted;
+                    }
+                    AppEvent::Shield(reason) => {
+                        self.shield = reason.map(|reason| (reason, std::time::Instant::now()));
+                    }
+                    AppEvent::OverlayConfig(config) => {
+                        // Windows can't be moved or opened from here, so the layout
+                        // only changes on restart; the look applies right away
+                        let windows = std::mem::take(&mut self.config.windows);
+                        self.config = OverlayConfig { windows, ..*config };
                     }
                     _ => {}
                 }
             }
             Message::Tick(now) => {
-                if let Some((_, start)) = self.alert {
-                    if now.duration_since(start).as_secs() > 5 {
-                        self.alert = None;
+                self.now = now;
+                if let Some((card, start)) = &self.alert {
+                    if now.duration_since(*start) > self.display_time(card) {
+                        self.next_alert(now);
                     }
                 }
+                if self
+                    .celebrating
+                    .is_some_and(|start| now.duration_since(start) > CELEBRATION_TIME)
+                {
+                    self.celebrating = None;
+                }
+                if self
+                    .poll_ended
+                    .is_some_and(|ended| now.duration_since(ended) > POLL_RESULT_TIME)
+                {
+                    self.poll = None;
+                    self.poll_ended = None;
+                }
             }
-            Message::None => {}
         }
         Command::none()
     }
 
-    fn view(&self) -> Element<Message> {
-        let chat_log = scrollable(
-            column(
-                self.messages
-                    .iter()
-                    .map(|msg| text(msg).size(22).style(iced::Color::WHITE).into())
-                    .collect::<Vec<_>>(),
-            )
-            .spacing(8),
-        )
-        .height(Length::Fill);
-
-        let chat_container = container(chat_log)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .padding(10)
-            .style(iced::theme::Container::Custom(Box::new(
-                ChatBackgroundStyle,
-            )));
-
-        let content = if let Some((alert_text, _)) = &self.alert {
-            column![
-                container(
-                    text(alert_text)
-                        .size(48)
-                        .style(iced::Color::from_rgb(1.0, 0.3, 0.3))
-                )
-                .padding(20)
-                .style(iced::theme::Container::Custom(Box::new(AlertStyle))),
-                chat_container
-            ]
-        } else {
-            column![chat_container]
+    fn view(&self, window: window::Id) -> Element<'_, Message> {
+        let Some((kind, placement)) = self.windows.get(&window) else {
+            return column![].into();
         };
+        let mut content = column![];
+        if let Some(shield) = self.shield_view() {
+            content = content.push(shield);
+        }
+        if *kind != OverlayWindow::Chat {
+            if let Some(alert) = self.alert_view(placement.width) {
+                content = content.push(alert);
+            }
+            if let Some(goal) = self.goal_view() {
+                content = content.push(goal);
+            }
+            if let Some(poll) = self.poll_view() {
+                content = content.push(poll);
+            }
+            if let Some(counters) = self.counters_view() {
+                content = content.push(counters);
+            }
+            if let Some(leaderboard) = self.leaderboard_view() {
+                content = content.push(leaderboard);
+            }
+            if let Some(songs) = self.songs_view() {
+                content = content.push(songs);
+            }
+        }
+        if *kind != OverlayWindow::Alerts {
+            if let Some(stats) = self.stats_view() {
+                content = content.push(stats);
+            }
+            content = content.push(self.chat_view());
+            if let Some(thinking) = self.thinking_view() {
+                content = content.push(thinking);
+            }
+            if let Some(speaking) = self.speaking_view() {
+                content = content.push(speaking);
+            }
+            if self.tts_muted {
+                content = content.push(
+                    container(
+                        text("🔇 TTS muted")
+                            .size(self.config.font_size)
+                            .style(rgb(self.config.text_color, 0.6)),
+                    )
+                    .padding(10),
+                );
+            }
+        }
 
         container(content)
             .width(Length::Fill)
@@ -118,7 +795,12 @@ impl Application for Overlay {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        let tick = time::every(std::time::Duration::from_millis(100)).map(Message::Tick);
+        // Every frame while a card is up so it animates smoothly, otherwise a slow tick
+        let tick = if self.alert.is_some() || self.celebrating.is_some() {
+            iced::window::frames().map(Message::Tick)
+        } else {
+            time::every(std::time::Duration::from_millis(100)).map(Message::Tick)
+        };
 
         struct EventLoop;
 
@@ -131,7 +813,7 @@ impl Application for Overlay {
                 // move receiver_arc in
                 let receiver_arc = receiver_arc.clone();
                 async move {
-                    let mut rx_opt = receiver_arc.lock().unwrap().take();
+                    let rx_opt = receiver_arc.lock().unwrap().take();
                     if let Some(mut rx) = rx_opt {
                         loop {
                             match rx.recv().await {
@@ -160,16 +842,17 @@ impl Application for Overlay {
     }
 }
 
-struct AlertStyle;
+// Border in the alert kind's accent color; the second field fades the card
+struct AlertStyle(iced::Color, f32);
 impl container::StyleSheet for AlertStyle {
     type Style = Theme;
 
     fn appearance(&self, _style: &Self::Style) -> container::Appearance {
         container::Appearance {
             text_color: Some(iced::Color::WHITE),
-            background: Some(iced::Color::BLACK.into()),
+            background: Some(iced::Color::from_rgba(0.0, 0.0, 0.0, self.1).into()),
             border: iced::Border {
-                color: iced::Color::from_rgb(1.0, 0.0, 0.0),
+                color: self.0,
                 width: 2.0,
                 radius: 5.0.into(),
             },
@@ -192,14 +875,42 @@ impl container::StyleSheet for TransparentStyle {
     }
 }
 
-struct ChatBackgroundStyle;
+fn rgb((r, g, b): Rgb, alpha: f32) -> iced::Color {
+    iced::Color::from_rgba8(r, g, b, alpha)
+}
+
+// Fully saturated color at `h` (0.0 to 1.0) around the color wheel
+fn hue(h: f32) -> iced::Color {
+    let channel = |offset: f32| {
+        let x = ((h + offset).fract() * 6.0 - 3.0).abs() - 1.0;
+        x.clamp(0.0, 1.0)
+    };
+    iced::Color::from_rgb(channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0))
+}
+
+// Goal bar: accent color on a dark track with the overlay's opacity
+struct GoalBarStyle(iced::Color, f32);
+impl progress_bar::StyleSheet for GoalBarStyle {
+    type Style = Theme;
+
+    fn appearance(&self, _style: &Self::Style) -> progress_bar::Appearance {
+        progress_bar::Appearance {
+            background: iced::Color::from_rgba(1.0, 1.0, 1.0, 0.15 * self.1).into(),
+            bar: self.0.into(),
+            border_radius: 6.0.into(),
+        }
+    }
+}
+
+// Background color (with the configured opacity) behind the chat feed
+struct ChatBackgroundStyle(iced::Color);
 impl container::StyleSheet for ChatBackgroundStyle {
     type Style = Theme;
 
     fn appearance(&self, _style: &Self::Style) -> container::Appearance {
         container::Appearance {
             text_color: Some(iced::Color::WHITE),
-            background: Some(iced::Color::from_rgba(0.0, 0.0, 0.0, 0.7).into()), // Semi-transparent dark
+            background: Some(self.0.into()),
             border: iced::Border {
                 color: iced::Color::from_rgba(1.0, 1.0, 1.0, 0.2),
                 width: 1.0,
@@ -0,0 +1,61 @@
+// Emote hints for the input box: words that are emotes render highlighted,
+// and words a typo away from one are flagged with the closest emote name.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hint {
+    Emote,
+    NearMiss(String),
+}
+
+pub fn classify(word: &str, known: &[&str]) -> Option<Hint> {
+    if word.is_empty() {
+        return None;
+    }
+    if known.contains(&word) {
+        return Some(Hint::Emote);
+    }
+
+    // Emote names are case-sensitive, so a case-only difference is the closest miss
+    let lower = word.to_lowercase();
+    if let Some(name) = known.iter().find(|name| name.to_lowercase() == lower) {
+        return Some(Hint::NearMiss(name.to_string()));
+    }
+
+    let len = word.chars().count();
+    let max_distance = match len {
+        0..=3 => return None,
+        4..=6 => 1,
+        _ => 2,
+    };
+    known
+        .iter()
+        .map(|name| (edit_distance(&lower, &name.to_lowercase()), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| Hint::NearMiss(name.to_string()))
+}
+
+// Replace the last word of `input` with its suggestion, if it's a near miss
+pub fn fix_last_word(input: &str, known: &[&str]) -> Option<String> {
+    let start = input.rfind(' ').map(|i| i + 1).unwrap_or(0);
+    match classify(&input[start..], known)? {
+        Hint::NearMiss(name) => Some(format!("{}{} ", &input[..start], name)),
+        Hint::Emote => None,
+    }
+}
+
+// Levenshtein distance over chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,500 @@
+use std::time::Instant;
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind,
+};
+use tui_input::backend::crossterm::EventHandler;
+
+use crate::{
+    api::AiAction,
+    audio, clipboard, commands,
+    features::{
+        change_volume, control_timers, control_tts, decide_held_message, dump_diagnostics,
+        execute_command, handle_ai_request, handle_song_key, retry_unsent, save_ui_state,
+    },
+    hints,
+    keys::Action,
+    outbox::Priority,
+    search::Search,
+    session::Session,
+    state::{App, AppEvent, Severity, Tab, EMOJIS},
+    ui::EmoteGrid,
+};
+use tokio::sync::mpsc;
+
+// Keys and the mouse in the terminal UI, for the session (see session.rs)
+
+impl Session {
+    // Returns whether to redraw
+    pub fn handle_terminal_event(&mut self, event: Event) -> bool {
+        match event {
+            Event::Key(key) => {
+                self.app.last_input = Instant::now();
+                // Key releases (with keyboard enhancement) change nothing
+                if key.kind != KeyEventKind::Press {
+                    return false;
+                }
+                self.handle_key(key);
+                true
+            }
+            Event::Mouse(mouse) => {
+                self.app.last_input = Instant::now();
+                // Moves, and drags that aren't selecting or resizing, don't
+                // need a render of their own
+                let redraw = match mouse.kind {
+                    MouseEventKind::Down(_)
+                    | MouseEventKind::Up(_)
+                    | MouseEventKind::ScrollDown
+                    | MouseEventKind::ScrollUp => true,
+                    MouseEventKind::Drag(_) => self.app.selecting || self.app.resizing_emotes,
+                    _ => false,
+                };
+                self.handle_mouse(mouse);
+                redraw
+            }
+            Event::FocusGained | Event::FocusLost => {
+                self.app.focused = matches!(event, Event::FocusGained);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn handle_key(&mut self, key: event::KeyEvent) {
+        if handle_search_key(&mut self.app, key) {
+            return;
+        }
+        if let Some(action) = self.keymap.action(&key) {
+            run_key_action(&mut self.app, &self.tts, &self.sounds, &self.tx, action);
+            return;
+        }
+        if key.code == KeyCode::Enter {
+            self.run_input();
+            return;
+        }
+        let Session {
+            app,
+            tx,
+            client,
+            features,
+            ai_tasks,
+            ..
+        } = self;
+        match key.code {
+            KeyCode::Esc if app.selection.is_some() => {
+                app.selection = None;
+            }
+            KeyCode::Esc => {
+                app.exit = true;
+            }
+            KeyCode::Up | KeyCode::Down if app.tab == Tab::Ai && key.modifiers.is_empty() => {
+                app.ai_select_step(key.code == KeyCode::Up);
+            }
+            KeyCode::Char(action @ ('x' | 'r' | 'a'))
+                if app.tab == Tab::Ai && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                let action = match action {
+                    'a' => AiAction::Approve,
+                    'r' => AiAction::Retry,
+                    _ => AiAction::Reject,
+                };
+                if let Some(id) = app.selected_ai_request().map(|request| request.id) {
+                    handle_ai_request(app, tx, ai_tasks, id, action);
+                }
+            }
+            KeyCode::Up | KeyCode::Down
+                if app.tab == Tab::Moderation
+                    && !app.held.is_empty()
+                    && key.modifiers.is_empty() =>
+            {
+                app.held.select_step(key.code == KeyCode::Up);
+            }
+            KeyCode::Char(action @ ('a' | 'x'))
+                if app.tab == Tab::Moderation && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                decide_held_message(app, tx, client, action == 'a');
+            }
+            KeyCode::Up | KeyCode::Down if app.tab == Tab::Songs && key.modifiers.is_empty() => {
+                app.song_select_step(key.code == KeyCode::Up);
+            }
+            KeyCode::Char(action @ ('u' | 'x' | 'n'))
+                if app.tab == Tab::Songs && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                handle_song_key(app, tx, features.player.as_ref(), action);
+            }
+            KeyCode::Up | KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+                app.select_step(
+                    key.code == KeyCode::Up,
+                    key.modifiers.contains(KeyModifiers::SHIFT),
+                );
+            }
+            KeyCode::Up | KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let height = if key.code == KeyCode::Up {
+                    app.emote_panel.height + 1
+                } else {
+                    app.emote_panel.height.saturating_sub(1)
+                };
+                app.emote_panel.resize(height);
+                save_ui_state(app);
+            }
+            KeyCode::Right if key.modifiers.contains(KeyModifiers::ALT) => {
+                app.tab = app.tab.next();
+            }
+            KeyCode::Left if key.modifiers.contains(KeyModifiers::ALT) => {
+                app.tab = app.tab.prev();
+            }
+            KeyCode::PageUp => {
+                app.scroll_up(app.page_size());
+            }
+            KeyCode::PageDown => {
+                app.scroll_down(app.page_size());
+            }
+            KeyCode::Char('c') | KeyCode::Char('d')
+                if key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                app.exit = true;
+            }
+            KeyCode::Tab => {
+                if let Some((completed, candidates)) = commands::complete(app.input.value()) {
+                    if candidates.len() > 1 {
+                        app.push(Tab::Chat, format!("Commands: /{}", candidates.join("  /")));
+                    }
+                    app.input = std::mem::take(&mut app.input).with_value(completed);
+                } else if let Some(fixed) =
+                    hints::fix_last_word(app.input.value(), &app.known_emotes())
+                {
+                    app.input = std::mem::take(&mut app.input).with_value(fixed);
+                }
+            }
+            _ => {
+                app.input.handle_event(&Event::Key(key));
+            }
+        }
+    }
+
+    // Enter: a slash command, or a message for chat
+    fn run_input(&mut self) {
+        let Session {
+            app,
+            tx,
+            client,
+            irc_tx,
+            sounds,
+            tts,
+            timers,
+            ..
+        } = self;
+        let text: String = app.input.value().into();
+        if let Some(parsed) = commands::parse(&text) {
+            app.input.reset();
+            match parsed {
+                Ok(commands::SlashCommand::Mute(user)) => {
+                    let state = if app.filters.toggle_mute(&user) {
+                        "Muted"
+                    } else {
+                        "Unmuted"
+                    };
+                    app.notify(Severity::Info, format!("{} {}", state, user));
+                }
+                Ok(commands::SlashCommand::Tts(control)) => {
+                    control_tts(app, tts, sounds, tx, control);
+                }
+                Ok(commands::SlashCommand::Volume { channel, level }) => {
+                    change_volume(app, sounds, tts, channel, level);
+                }
+                Ok(commands::SlashCommand::Timer(control)) => {
+                    control_timers(app, timers, control);
+                }
+                Ok(commands::SlashCommand::Retry) => {
+                    retry_unsent(app);
+                }
+                Ok(commands::SlashCommand::Diagnostics) => {
+                    dump_diagnostics(app, tts);
+                }
+                Ok(commands::SlashCommand::Help(name)) => {
+                    for line in commands::help_lines(name.as_deref()) {
+                        app.push(Tab::Chat, line);
+                    }
+                }
+                Ok(cmd) => {
+                    app.push(Tab::Chat, format!("Me: {}", text));
+                    execute_command(app, tx, client, irc_tx, cmd);
+                }
+                Err(e) => {
+                    app.notify(Severity::Error, format!("{:#}", e));
+                }
+            }
+        } else if !text.trim().is_empty() {
+            app.push(Tab::Chat, format!("Me: {}", text));
+            app.input.reset();
+            app.outbox.say(Priority::High, text);
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: event::MouseEvent) {
+        let app = &mut self.app;
+        // Wheel over the active tab view scrolls it
+        let view = app.tab_area;
+        if mouse.column >= view.x
+            && mouse.column < view.x + view.width
+            && mouse.row >= view.y
+            && mouse.row < view.y + view.height
+        {
+            match mouse.kind {
+                MouseEventKind::ScrollUp => app.scroll_up(3),
+                MouseEventKind::ScrollDown => app.scroll_down(3),
+                _ => {}
+            }
+        }
+
+        // Dragging the emote panel's top border resizes it (the right end is the scroll arrow)
+        let emotes = app.emote_area;
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left)
+                if !app.emote_panel.collapsed
+                    && mouse.row == emotes.y
+                    && mouse.column + 1 < emotes.right() =>
+            {
+                app.resizing_emotes = true;
+                return;
+            }
+            MouseEventKind::Drag(MouseButton::Left) if app.resizing_emotes => {
+                app.emote_panel
+                    .resize(emotes.bottom().saturating_sub(mouse.row));
+                return;
+            }
+            MouseEventKind::Up(MouseButton::Left) if app.resizing_emotes => {
+                app.resizing_emotes = false;
+                save_ui_state(app);
+                return;
+            }
+            _ => {}
+        }
+
+        // Left click selects a chat line (Shift extends), dragging extends it and
+        // releasing copies it. Right click copies.
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let extend = mouse.modifiers.contains(KeyModifiers::SHIFT);
+                app.selecting = app.select_at(mouse.column, mouse.row, extend).is_some();
+                app.selection_dragged = extend && app.selecting;
+            }
+            MouseEventKind::Drag(MouseButton::Left) if app.selecting => {
+                app.drag_select_to(mouse.column, mouse.row);
+            }
+            MouseEventKind::Up(MouseButton::Left) if app.selecting => {
+                app.selecting = false;
+                if app.selection_dragged {
+                    copy_selection(app);
+                }
+            }
+            MouseEventKind::Down(MouseButton::Right) => {
+                // Right click inside the selection copies all of it, elsewhere just that line
+                let clicked = app.line_at(mouse.column, mouse.row);
+                let in_selection = app
+                    .active_selection()
+                    .zip(clicked)
+                    .is_some_and(|(sel, i)| sel.contains(i));
+                if in_selection || app.select_at(mouse.column, mouse.row, false).is_some() {
+                    copy_selection(app);
+                }
+            }
+            _ => {}
+        }
+
+        // Clicking a name in the user list toggles its mute
+        let users = app.users_area;
+        if mouse.kind == MouseEventKind::Down(MouseButton::Left)
+            && mouse.column >= users.x
+            && mouse.column < users.x + users.width
+            && mouse.row > users.y
+            && mouse.row < users.y + users.height.saturating_sub(1)
+        {
+            let row = (mouse.row - users.y - 1) as usize;
+            let login = app
+                .sorted_chatters()
+                .get(row)
+                .map(|(login, _)| login.to_string());
+            if let Some(login) = login {
+                let state = if app.filters.toggle_mute(&login) {
+                    "Muted"
+                } else {
+                    "Unmuted"
+                };
+                app.notify(Severity::Info, format!("{} {}", state, login));
+            }
+        }
+
+        if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
+            click_emote_panel(app, mouse.column, mouse.row);
+        }
+    }
+}
+
+// Hotkeys from the keymap (F keys by default, see keys.rs)
+pub(crate) fn run_key_action(
+    app: &mut App,
+    tts: &audio::TtsQueue,
+    sounds: &audio::AudioEngine,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    action: Action,
+) {
+    match action {
+        Action::TabChat => app.tab = Tab::Chat,
+        Action::TabLog => app.tab = Tab::Log,
+        Action::TabModeration => app.tab = Tab::Moderation,
+        Action::TabAi => app.tab = Tab::Ai,
+        Action::TabSongs => app.tab = Tab::Songs,
+        Action::ToggleUsers => app.show_users = !app.show_users,
+        Action::ToggleFilters => app.filters.enabled = !app.filters.enabled,
+        Action::TtsToggle => control_tts(app, tts, sounds, tx, commands::TtsControl::Toggle),
+        Action::TtsSkip => control_tts(app, tts, sounds, tx, commands::TtsControl::Skip),
+        Action::TtsClear => control_tts(app, tts, sounds, tx, commands::TtsControl::Clear),
+        Action::Notifications => app.toggle_notifications(),
+        Action::EmotePanel => {
+            app.emote_panel.collapsed = !app.emote_panel.collapsed;
+            save_ui_state(app);
+        }
+        Action::PowerMode => {
+            app.power_mode = app.power_mode.next();
+            let message = format!("Low-power rendering: {:?}", app.power_mode);
+            app.notify(Severity::Info, message);
+        }
+        Action::Search => app.search = Some(Search::new()),
+        Action::Copy => copy_selection(app),
+        Action::CycleProtocol => {
+            if let Some(current_picker) = &app.picker {
+                let next_proto = current_picker.protocol_type.next();
+                app.protocol_choice = Some(next_proto);
+                app.set_image_protocol(next_proto);
+                save_ui_state(app);
+            }
+        }
+        Action::SimulateJoin => {
+            let _ = tx.send(AppEvent::UserJoined("TestUser".to_string()));
+            app.push(Tab::Chat, "Debug: Simulated User Join".to_string());
+        }
+        Action::Diagnostics => dump_diagnostics(app, tts),
+    }
+}
+
+// Ctrl+Y / right click: put the selected lines (or the newest one) on the clipboard
+pub(crate) fn copy_selection(app: &mut App) {
+    let Some(text) = app.selected_text() else {
+        return;
+    };
+    let lines = text.lines().count();
+    match clipboard::copy(&text) {
+        Ok(clipboard::Copied::System) => app.notify(
+            Severity::Info,
+            format!("Copied {} line(s) to clipboard", lines),
+        ),
+        Ok(clipboard::Copied::Terminal(None)) => app.notify(
+            Severity::Info,
+            format!(
+                "Sent {} line(s) to the terminal's clipboard (OSC 52)",
+                lines
+            ),
+        ),
+        Ok(clipboard::Copied::Terminal(Some(why))) => app.notify(
+            Severity::Warning,
+            format!(
+                "System clipboard unavailable ({}); sent {} line(s) to the terminal's clipboard (OSC 52) instead",
+                why, lines
+            ),
+        ),
+        Err(e) => app.notify(Severity::Error, format!("Copy failed: {:#}", e)),
+    }
+}
+
+// Keys for an open search. Returns true if the key was consumed.
+pub(crate) fn handle_search_key(app: &mut App, key: event::KeyEvent) -> bool {
+    let Some(search) = app.search.as_mut() else {
+        return false;
+    };
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+    if search.editing {
+        match key.code {
+            KeyCode::Esc => app.search = None,
+            KeyCode::Enter => {
+                search.editing = false;
+                search.current = None;
+                app.search_step(true);
+            }
+            KeyCode::Char('r') if ctrl => search.regex = !search.regex,
+            _ => {
+                search.query.handle_event(&Event::Key(key));
+            }
+        }
+        return true;
+    }
+
+    match key.code {
+        KeyCode::Esc => {
+            app.search = None;
+            app.tab_scroll[app.tab.index()] = 0;
+        }
+        KeyCode::Char('n') => app.search_step(true),
+        KeyCode::Char('N') => app.search_step(false),
+        KeyCode::Char('f') if ctrl => {
+            search.filter = !search.filter;
+            app.reveal_search_match();
+        }
+        KeyCode::Char('/') => search.editing = true,
+        _ => return false,
+    }
+    true
+}
+
+// A left click in the emote panel: the scrollbar's arrows scroll it, an
+// emote goes into the input
+fn click_emote_panel(app: &mut App, column: u16, row: u16) {
+    let area = app.emote_area;
+    if column < area.x || column >= area.right() || row < area.y || row >= area.bottom() {
+        return;
+    }
+    let grid = EmoteGrid::new(area, app.emote_images.len());
+    if column == area.right() - 1 {
+        if row == area.y {
+            app.emote_scroll = app.emote_scroll.saturating_sub(1);
+        } else if row == area.bottom() - 1 && app.emote_scroll < grid.max_scroll() {
+            app.emote_scroll += 1;
+        }
+        return;
+    }
+
+    if !app.emote_images.is_empty() {
+        if let Some(index) = grid.index_at(column, row, app.emote_scroll) {
+            let (name, _) = &app.emote_images[index];
+            let separator = if app.input.value().is_empty() {
+                ""
+            } else {
+                " "
+            };
+            let new_val = format!("{}{}{} ", app.input.value(), separator, name);
+            app.input = std::mem::take(&mut app.input).with_value(new_val);
+        }
+        return;
+    }
+    // Without images the panel lists EMOJIS, laid out as ui.rs does
+    let width = area.width as usize;
+    let click_x = column.saturating_sub(area.x) as usize;
+    let click_y = row.saturating_sub(area.y) as usize;
+    let mut current_x = 0;
+    let mut current_y = 0;
+    for emoji in EMOJIS {
+        let emoji_len = emoji.chars().count();
+        let item_width = emoji_len + 2;
+        if current_x + item_width > width {
+            current_x = 0;
+            current_y += 1;
+        }
+        if current_y == click_y && click_x >= current_x && click_x < current_x + emoji_len {
+            let new_val = format!("{}{}", app.input.value(), emoji);
+            app.input = std::mem::take(&mut app.input).with_value(new_val);
+            break;
+        }
+        current_x += item_width;
+    }
+}
@@ -0,0 +1,387 @@
+use crate::config::Config;
+use crate::modlog::{ModEvent, ModKind};
+use crate::state::{AppEvent, ConnectionState, Service};
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+// Twitch chat over IRC (on a WebSocket), used for what EventSub doesn't send:
+// joins and parts, bans and deleted messages as they happen, and raw lines
+// like /me. Lines are parsed with their IRCv3 tags into `Line`s and then
+// into `Command`s. Who's in chat is kept in `Members`, so the JOINs Twitch
+// repeats after a reconnect don't look like everyone arriving again. The
+// connection is made again when Twitch asks (RECONNECT) or drops it, and
+// lines sent are held to Twitch's rate limit.
+
+// Lines per window, what Twitch allows an account that isn't a moderator
+const RATE_LIMIT: usize = 20;
+const RATE_WINDOW: Duration = Duration::from_secs(30);
+// Between attempts after losing the connection, doubling up to the most
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+const MAX_RETRY: Duration = Duration::from_secs(60);
+
+/// One IRC line: `@tags :source COMMAND params :trailing`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub tags: HashMap<String, String>,
+    // "nick!user@host" or a server name
+    pub source: Option<String>,
+    pub command: String,
+    // The trailing parameter, if any, is the last one
+    pub params: Vec<String>,
+}
+
+impl Line {
+    pub fn parse(text: &str) -> Option<Line> {
+        let mut rest = text.trim_end_matches(['\r', '\n']);
+        let mut tags = HashMap::new();
+        if let Some(tagged) = rest.strip_prefix('@') {
+            let (raw, after) = tagged.split_once(' ')?;
+            for tag in raw.split(';') {
+                let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                tags.insert(key.to_string(), unescape(value));
+            }
+            rest = after.trim_start();
+        }
+        let mut source = None;
+        if let Some(sourced) = rest.strip_prefix(':') {
+            let (raw, after) = sourced.split_once(' ')?;
+            source = Some(raw.to_string());
+            rest = after.trim_start();
+        }
+        let (middle, trailing) = match rest.split_once(" :") {
+            Some((middle, trailing)) => (middle, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = middle.split(' ').filter(|word| !word.is_empty());
+        let command = words.next()?.to_uppercase();
+        let mut params: Vec<String> = words.map(String::from).collect();
+        params.extend(trailing.map(String::from));
+        Some(Line {
+            tags,
+            source,
+            command,
+            params,
+        })
+    }
+
+    /// A tag's value, None when it's missing or empty.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Who sent it, from "nick!user@host".
+    pub fn nick(&self) -> Option<&str> {
+        let source = self.source.as_deref()?;
+        let (nick, _) = source.split_once('!')?;
+        Some(nick).filter(|nick| !nick.is_empty())
+    }
+
+    fn param(&self, i: usize) -> Option<&str> {
+        self.params.get(i).map(String::as_str)
+    }
+}
+
+// Tag values escape what would end them (IRCv3 message tags)
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// What a line means to the bot.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    // The server's token, to PONG back
+    Ping(String),
+    Join(String),
+    Part(String),
+    // Twitch is about to restart the server; connect again
+    Reconnect,
+    // Bans and timeouts (CLEARCHAT), deleted messages (CLEARMSG)
+    Moderation(ModEvent),
+    // The rest: chat (EventSub has it), capabilities, numerics
+    Other,
+}
+
+impl Command {
+    pub fn from_line(line: &Line) -> Command {
+        match line.command.as_str() {
+            "PING" => Command::Ping(line.param(0).unwrap_or("tmi.twitch.tv").to_string()),
+            "JOIN" => line
+                .nick()
+                .map_or(Command::Other, |nick| Command::Join(nick.to_string())),
+            "PART" => line
+                .nick()
+                .map_or(Command::Other, |nick| Command::Part(nick.to_string())),
+            "RECONNECT" => Command::Reconnect,
+            // @ban-duration=600;room-id=1;target-user-id=2 :tmi.twitch.tv CLEARCHAT #channel :viewer
+            "CLEARCHAT" => {
+                let user = line.param(1).map(String::from);
+                let (kind, detail) = match (line.tag("ban-duration"), &user) {
+                    (_, None) => (ModKind::Clear, String::new()),
+                    (Some(seconds), _) => (ModKind::Timeout, format!("{}s", seconds)),
+                    (None, _) => (ModKind::Ban, String::new()),
+                };
+                Command::Moderation(ModEvent {
+                    kind,
+                    user,
+                    moderator: None,
+                    detail,
+                })
+            }
+            // @login=viewer;target-msg-id=abc :tmi.twitch.tv CLEARMSG #channel :the message
+            "CLEARMSG" => Command::Moderation(ModEvent {
+                kind: ModKind::Delete,
+                user: line.tag("login").map(String::from),
+                moderator: None,
+                detail: line.param(1).unwrap_or_default().to_string(),
+            }),
+            _ => Command::Other,
+        }
+    }
+}
+
+/// Who's in chat, going by JOINs and PARTs.
+#[derive(Debug, Default)]
+pub struct Members {
+    present: HashSet<String>,
+}
+
+impl Members {
+    /// Returns whether `user` wasn't already here.
+    pub fn join(&mut self, user: &str) -> bool {
+        self.present.insert(user.to_lowercase())
+    }
+
+    pub fn part(&mut self, user: &str) {
+        self.present.remove(&user.to_lowercase());
+    }
+
+    pub fn contains(&self, user: &str) -> bool {
+        self.present.contains(&user.to_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.present.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.present.is_empty()
+    }
+}
+
+/// Lines sent in the last window, to stay under the limit.
+#[derive(Debug)]
+pub struct RateLimit {
+    sent: VecDeque<Instant>,
+    limit: usize,
+    window: Duration,
+}
+
+impl RateLimit {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            sent: VecDeque::new(),
+            limit: limit.max(1),
+            window,
+        }
+    }
+
+    /// How long until another line may go, zero if it may now.
+    pub fn wait(&mut self) -> Duration {
+        while self
+            .sent
+            .front()
+            .is_some_and(|at| at.elapsed() >= self.window)
+        {
+            self.sent.pop_front();
+        }
+        match self.sent.len() < self.limit {
+            true => Duration::ZERO,
+            false => (self.sent[self.sent.len() - self.limit] + self.window)
+                .saturating_duration_since(Instant::now()),
+        }
+    }
+
+    pub fn record(&mut self) {
+        self.sent.push_back(Instant::now());
+    }
+}
+
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+async fn login(config: &Config) -> Result<Socket> {
+    let token = config.oauth_token.as_ref().context("Token missing")?;
+    let channel = config
+        .channel_name
+        .as_ref()
+        .context("Channel name missing")?;
+    let (mut socket, _) = tokio_tungstenite::connect_async(&config.endpoints.irc)
+        .await
+        .context("Failed to connect to IRC")?;
+    for line in [
+        "CAP REQ :twitch.tv/membership twitch.tv/tags twitch.tv/commands".to_string(),
+        format!("PASS oauth:{}", token),
+        "NICK infobot".to_string(),
+        format!("JOIN #{}", channel.to_lowercase()),
+    ] {
+        socket.send(Message::Text(line.into())).await?;
+    }
+    Ok(socket)
+}
+
+// How a connection ended
+enum End {
+    Shutdown,
+    // Asked to by Twitch, or the connection dropped
+    Reconnect,
+}
+
+/// Connect and stay connected until `shutdown`. Returns the task and a
+/// sender for raw outgoing lines (e.g. PRIVMSG for /me), which are sent
+/// within the rate limit.
+pub async fn connect(
+    config: Arc<Config>,
+    event_tx: mpsc::UnboundedSender<AppEvent>,
+    shutdown: CancellationToken,
+) -> Result<(tokio::task::JoinHandle<()>, mpsc::UnboundedSender<String>)> {
+    let mut socket = login(&config).await?;
+    let _ = event_tx.send(AppEvent::Info("IRC Connected - Listening for Joins".into()));
+    let connection = |state| AppEvent::Connection(Service::Irc, state);
+    let _ = event_tx.send(connection(ConnectionState::Connected));
+
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<String>();
+    let handle = tokio::spawn(async move {
+        let mut members = Members::default();
+        let mut outgoing = Outgoing {
+            lines: &mut out_rx,
+            waiting: VecDeque::new(),
+            limit: RateLimit::new(RATE_LIMIT, RATE_WINDOW),
+        };
+        loop {
+            let end = session(socket, &event_tx, &shutdown, &mut members, &mut outgoing).await;
+            if matches!(end, End::Shutdown) {
+                break;
+            }
+            let _ = event_tx.send(connection(ConnectionState::Connecting));
+            let mut retry = FIRST_RETRY;
+            socket = loop {
+                match login(&config).await {
+                    Ok(socket) => break socket,
+                    Err(e) => {
+                        let _ = event_tx.send(AppEvent::Debug(format!("IRC: {:#}", e)));
+                    }
+                }
+                tokio::select! {
+                    _ = shutdown.cancelled() => {
+                        let _ = event_tx.send(connection(ConnectionState::Disconnected));
+                        return;
+                    }
+                    _ = tokio::time::sleep(retry) => {}
+                }
+                retry = (retry * 2).min(MAX_RETRY);
+            };
+            let _ = event_tx.send(AppEvent::Debug("IRC reconnected".into()));
+            let _ = event_tx.send(connection(ConnectionState::Connected));
+        }
+        let _ = event_tx.send(connection(ConnectionState::Disconnected));
+    });
+
+    Ok((handle, out_tx))
+}
+
+// Raw lines to send, kept across reconnects
+struct Outgoing<'a> {
+    lines: &'a mut mpsc::UnboundedReceiver<String>,
+    waiting: VecDeque<String>,
+    limit: RateLimit,
+}
+
+async fn session(
+    mut socket: Socket,
+    event_tx: &mpsc::UnboundedSender<AppEvent>,
+    shutdown: &CancellationToken,
+    members: &mut Members,
+    outgoing: &mut Outgoing<'_>,
+) -> End {
+    loop {
+        let wait = outgoing.limit.wait();
+        let frame = tokio::select! {
+            _ = shutdown.cancelled() => {
+                let _ = socket.send(Message::Close(None)).await;
+                return End::Shutdown;
+            }
+            frame = socket.next() => frame,
+            Some(line) = outgoing.lines.recv() => {
+                outgoing.waiting.push_back(line);
+                continue;
+            }
+            _ = tokio::time::sleep(wait), if !outgoing.waiting.is_empty() => {
+                if let Some(line) = outgoing.waiting.pop_front() {
+                    outgoing.limit.record();
+                    if socket.send(Message::Text(line.into())).await.is_err() {
+                        return End::Reconnect;
+                    }
+                }
+                continue;
+            }
+        };
+        let text = match frame {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return End::Reconnect,
+            Some(Ok(_)) => continue,
+        };
+        for line in text.lines().filter_map(Line::parse) {
+            match Command::from_line(&line) {
+                Command::Ping(token) => {
+                    let pong = Message::Text(format!("PONG :{}", token).into());
+                    if socket.send(pong).await.is_err() {
+                        return End::Reconnect;
+                    }
+                }
+                Command::Join(user) => {
+                    if members.join(&user) {
+                        let _ = event_tx.send(AppEvent::UserJoined(user));
+                    }
+                }
+                Command::Part(user) => {
+                    members.part(&user);
+                    let _ = event_tx.send(AppEvent::UserLeft(user));
+                }
+                Command::Reconnect => {
+                    let _ = socket.send(Message::Close(None)).await;
+                    return End::Reconnect;
+                }
+                Command::Moderation(event) => {
+                    let _ = event_tx.send(AppEvent::Moderation(event));
+                }
+                Command::Other => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::config_file::ConfigFile;
+use anyhow::{bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// Global hotkeys of the terminal UI. Each can be rebound in the [keys]
+// section of choui.toml, e.g. `tts_toggle = "ctrl+t"`. Editing and
+// navigation keys (Enter, Tab, Esc, arrows, paging, Ctrl+C) stay fixed.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    TabChat,
+    TabLog,
+    TabModeration,
+    TabAi,
+    TabSongs,
+    ToggleUsers,
+    ToggleFilters,
+    TtsToggle,
+    TtsSkip,
+    TtsClear,
+    Notifications,
+    EmotePanel,
+    PowerMode,
+    Search,
+    Copy,
+    CycleProtocol,
+    SimulateJoin,
+    Diagnostics,
+}
+
+impl Action {
+    // Default bindings, in the order the F keys run
+    const ALL: [(Action, &'static str, &'static str); 18] = [
+        (Action::SimulateJoin, "simulate_join", "f1"),
+        (Action::TabChat, "tab_chat", "f2"),
+        (Action::TabLog, "tab_log", "f3"),
+        (Action::TabModeration, "tab_moderation", "f4"),
+        (Action::TabAi, "tab_ai", "f5"),
+        (Action::ToggleUsers, "toggle_users", "f6"),
+        (Action::ToggleFilters, "toggle_filters", "f7"),
+        (Action::TtsSkip, "tts_skip", "shift+f8"),
+        (Action::TtsClear, "tts_clear", "ctrl+f8"),
+        (Action::TtsToggle, "tts_toggle", "f8"),
+        (Action::Notifications, "notifications", "f9"),
+        (Action::EmotePanel, "emote_panel", "f10"),
+        (Action::TabSongs, "tab_songs", "f11"),
+        (Action::PowerMode, "power_mode", "f12"),
+        (Action::Search, "search", "ctrl+f"),
+        (Action::Copy, "copy", "ctrl+y"),
+        (Action::CycleProtocol, "cycle_protocol", "ctrl+p"),
+        (Action::Diagnostics, "diagnostics", "ctrl+g"),
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Binding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Binding {
+    // "f8", "shift+f8", "ctrl+alt+t". Plain characters need ctrl or alt,
+    // so the binding doesn't swallow typing.
+    fn parse(text: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let key = parts
+            .pop()
+            .filter(|key| !key.is_empty())
+            .context("Missing key")?;
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                other => bail!("Unknown modifier '{}'", other),
+            };
+        }
+
+        let lower = key.to_lowercase();
+        let code = match lower.as_str() {
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            _ if lower.len() > 1 && lower.starts_with('f') => {
+                let n = lower[1..]
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|n| (1..=24).contains(n))
+                    .with_context(|| format!("Unknown key '{}'", key))?;
+                KeyCode::F(n)
+            }
+            _ => {
+                let mut chars = lower.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => bail!("Unknown key '{}'", key),
+                }
+            }
+        };
+        if matches!(code, KeyCode::Char(_))
+            && !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            bail!("'{}' needs ctrl or alt, or it would take over typing", text);
+        }
+        Ok(Self { code, modifiers })
+    }
+
+    fn matches(&self, key: &KeyEvent) -> bool {
+        match (self.code, key.code) {
+            // Shift is already in the character's case
+            (KeyCode::Char(bound), KeyCode::Char(pressed)) => {
+                bound == pressed.to_ascii_lowercase()
+                    && self.modifiers == key.modifiers - KeyModifiers::SHIFT
+            }
+            (bound, pressed) => bound == pressed && self.modifiers == key.modifiers,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Binding, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|(action, _, key)| (Binding::parse(key).expect("valid default key"), *action))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn load() -> Result<Self> {
+        Self::from_file(&ConfigFile::load_default()?)
+    }
+
+    // Keys left out of [keys] keep their default binding
+    pub fn from_file(file: &ConfigFile) -> Result<Self> {
+        const SECTION: &str = "keys";
+        if let Some(key) = file
+            .keys(SECTION)
+            .find(|key| !Action::ALL.iter().any(|(_, name, _)| name == key))
+        {
+            bail!("Unknown key [{}] {}", SECTION, key);
+        }
+
+        let mut keymap = Self::default();
+        for (action, name, _) in Action::ALL {
+            if let Some(text) = file.get_str(SECTION, name)? {
+                let binding = Binding::parse(&text)
+                    .with_context(|| format!("[{}] {} = \"{}\"", SECTION, name, text))?;
+                keymap.bindings.retain(|(_, bound)| *bound != action);
+                keymap.bindings.insert(0, (binding, action));
+            }
+        }
+        Ok(keymap)
+    }
+
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.matches(key))
+            .map(|(_, action)| *action)
+    }
+}
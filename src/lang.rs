@@ -0,0 +1,80 @@
+use lingua::{Language, LanguageDetector, LanguageDetectorBuilder};
+use std::sync::OnceLock;
+
+// Language guess for chat messages, to pick a TTS voice and to tell what to
+// translate, by lingua. Only the languages below are told apart (each one
+// is a Cargo feature of lingua, for its model). Returns ISO 639-1 codes, or
+// None when the message is too short or ambiguous to tell.
+
+const LANGUAGES: &[(Language, &str, &str)] = &[
+    (Language::English, "en", "English"),
+    (Language::Spanish, "es", "Spanish"),
+    (Language::German, "de", "German"),
+    (Language::French, "fr", "French"),
+    (Language::Portuguese, "pt", "Portuguese"),
+    (Language::Italian, "it", "Italian"),
+    (Language::Dutch, "nl", "Dutch"),
+    (Language::Polish, "pl", "Polish"),
+    (Language::Russian, "ru", "Russian"),
+    (Language::Ukrainian, "uk", "Ukrainian"),
+    (Language::Japanese, "ja", "Japanese"),
+    (Language::Korean, "ko", "Korean"),
+    (Language::Chinese, "zh", "Chinese"),
+    (Language::Arabic, "ar", "Arabic"),
+    (Language::Hebrew, "he", "Hebrew"),
+    (Language::Greek, "el", "Greek"),
+    (Language::Thai, "th", "Thai"),
+    (Language::Hindi, "hi", "Hindi"),
+];
+
+// How far ahead of the runner-up lingua's likeliest language has to be.
+// Russian and Ukrainian share most of "спасибо за стрим", Spanish and
+// Portuguese all of "hola amigos"; lines like those are left undecided.
+const MIN_RELATIVE_DISTANCE: f64 = 0.2;
+
+pub fn detect(text: &str) -> Option<&'static str> {
+    let detector = detector();
+    let language = detector.detect_language_of(text)?;
+    // One word ("gg", "KEKW", "hey") could be from most of them, only a
+    // script no other language here uses tells
+    let words = text
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphabetic))
+        .count();
+    if words < 2 && detector.compute_language_confidence(text, language) < 1.0 {
+        return None;
+    }
+    LANGUAGES
+        .iter()
+        .find(|(known, _, _)| *known == language)
+        .map(|(_, code, _)| *code)
+}
+
+/// Whether `code` is one detect() can return.
+pub fn is_supported(code: &str) -> bool {
+    name(code).is_some()
+}
+
+/// "Spanish" for "es"
+pub fn name(code: &str) -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(_, known, _)| *known == code)
+        .map(|(_, _, name)| *name)
+}
+
+/// The codes detect() can return, e.g. for error messages.
+pub fn codes() -> impl Iterator<Item = &'static str> {
+    LANGUAGES.iter().map(|(_, code, _)| *code)
+}
+
+// The models are loaded on first use and kept
+fn detector() -> &'static LanguageDetector {
+    static DETECTOR: OnceLock<LanguageDetector> = OnceLock::new();
+    DETECTOR.get_or_init(|| {
+        let languages: Vec<Language> = LANGUAGES.iter().map(|(language, _, _)| *language).collect();
+        LanguageDetectorBuilder::from_languages(&languages)
+            .with_minimum_relative_distance(MIN_RELATIVE_DISTANCE)
+            .build()
+    })
+}
@@ -0,0 +1,239 @@
+use crate::sqlite::{Store, Value, Writer};
+use crate::state::{AppEvent, StreamAlert};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::sync::mpsc::UnboundedSender;
+
+// Bits cheered and subs gifted, per viewer and stream, kept in the chat
+// database:
+//
+//   !topcheers     the top cheerers this stream and of all time
+//   !topgifters    the same for gifted subs
+//
+// Anonymous cheers and gifts aren't counted. A stream is told apart by its
+// start time from Helix like in viewers.rs; what comes in while the channel
+// is offline only counts toward all time. With [overlay] leaderboard on, the
+// overlay cycles through the boards.
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS leaderboard (
+    board TEXT NOT NULL,
+    user TEXT NOT NULL,
+    stream TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    PRIMARY KEY (board, user, stream)
+);
+";
+
+// Places shown by the commands and the overlay
+pub const TOP: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Board {
+    Cheers,
+    Gifts,
+}
+
+impl Board {
+    pub const ALL: [Board; 2] = [Board::Cheers, Board::Gifts];
+
+    fn name(self) -> &'static str {
+        match self {
+            Board::Cheers => "cheers",
+            Board::Gifts => "gifts",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Board> {
+        Self::ALL.into_iter().find(|board| board.name() == name)
+    }
+
+    /// What the board is titled, e.g. "Top cheerers"
+    pub fn title(self) -> &'static str {
+        match self {
+            Board::Cheers => "Top cheerers",
+            Board::Gifts => "Top gifters",
+        }
+    }
+
+    /// "500 bits", "1 sub"
+    pub fn amount(self, amount: i64) -> String {
+        let unit = match self {
+            Board::Cheers => "bit",
+            Board::Gifts => "sub",
+        };
+        format!("{} {}{}", amount, unit, if amount == 1 { "" } else { "s" })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Stream,
+    AllTime,
+}
+
+impl Scope {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::Stream => "this stream",
+            Scope::AllTime => "all time",
+        }
+    }
+}
+
+/// One board's leaders, best first, as sent to the overlay
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standings {
+    pub board: Board,
+    pub scope: Scope,
+    pub leaders: Vec<(String, i64)>,
+}
+
+impl Standings {
+    /// "1. viewer (500 bits), 2. other (100 bits)"
+    pub fn describe(&self) -> String {
+        if self.leaders.is_empty() {
+            return String::from("nobody yet");
+        }
+        self.leaders
+            .iter()
+            .enumerate()
+            .map(|(i, (user, amount))| {
+                format!("{}. {} ({})", i + 1, user, self.board.amount(*amount))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+type Totals = HashMap<(Board, String), i64>;
+
+pub struct Leaderboards {
+    all_time: Totals,
+    // By stream start time, "" while offline
+    by_stream: HashMap<String, Totals>,
+    current: String,
+    writes: Writer,
+}
+
+impl Leaderboards {
+    pub fn open(path: &Path, events: UnboundedSender<AppEvent>) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?;
+        let mut all_time = Totals::new();
+        let mut by_stream: HashMap<String, Totals> = HashMap::new();
+        for row in db.query("SELECT board, user, stream, amount FROM leaderboard", &[])? {
+            let (Some(board), Some(user), Some(stream), Some(amount)) = (
+                row.first()
+                    .and_then(Value::as_str)
+                    .and_then(Board::from_name),
+                row.get(1).and_then(Value::as_str),
+                row.get(2).and_then(Value::as_str),
+                row.get(3).and_then(Value::as_int),
+            ) else {
+                continue;
+            };
+            *all_time.entry((board, user.to_string())).or_insert(0) += amount;
+            by_stream
+                .entry(stream.to_string())
+                .or_default()
+                .insert((board, user.to_string()), amount);
+        }
+
+        let writes = db.writer("leaderboards", events);
+        Ok(Self {
+            all_time,
+            by_stream,
+            current: String::new(),
+            writes,
+        })
+    }
+
+    /// The live stream, by its start time, or None when the channel went
+    /// offline. True if that's a different stream than before.
+    pub fn set_stream(&mut self, started: Option<&str>) -> bool {
+        let started = started.unwrap_or_default();
+        if self.current == started {
+            return false;
+        }
+        self.current = started.to_string();
+        true
+    }
+
+    /// Count a cheer or gift sub. Returns the board it went to, None for
+    /// other alerts and anonymous ones.
+    pub fn record(&mut self, alert: &StreamAlert) -> Option<Board> {
+        let (board, user, amount) = match alert {
+            StreamAlert::Cheer {
+                user: Some(user),
+                bits,
+                ..
+            } => (Board::Cheers, user, *bits),
+            StreamAlert::GiftSub {
+                user: Some(user),
+                count,
+                ..
+            } => (Board::Gifts, user, *count),
+            _ => return None,
+        };
+        let user = user.to_lowercase();
+        let amount = i64::from(amount);
+        *self.all_time.entry((board, user.clone())).or_insert(0) += amount;
+        *self
+            .by_stream
+            .entry(self.current.clone())
+            .or_default()
+            .entry((board, user.clone()))
+            .or_insert(0) += amount;
+        self.writes.write(
+            "INSERT INTO leaderboard (board, user, stream, amount, timestamp)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (board, user, stream) DO UPDATE SET
+                 amount = amount + excluded.amount, timestamp = excluded.timestamp",
+            vec![
+                board.name().into(),
+                user.into(),
+                self.current.as_str().into(),
+                amount.into(),
+                crate::chatlog::timestamp().into(),
+            ],
+        );
+        Some(board)
+    }
+
+    /// The top `TOP` of a board, most first; ties go alphabetically
+    pub fn standings(&self, board: Board, scope: Scope) -> Standings {
+        let totals = match scope {
+            Scope::AllTime => Some(&self.all_time),
+            // Nothing counts as "this stream" while offline
+            Scope::Stream if self.current.is_empty() => None,
+            Scope::Stream => self.by_stream.get(&self.current),
+        };
+        let mut leaders: Vec<(String, i64)> = totals
+            .into_iter()
+            .flatten()
+            .filter(|((b, _), _)| *b == board)
+            .map(|((_, user), amount)| (user.clone(), *amount))
+            .collect();
+        leaders.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+        leaders.truncate(TOP);
+        Standings {
+            board,
+            scope,
+            leaders,
+        }
+    }
+
+    /// "Top cheerers this stream: 1. a (500 bits) | all time: 1. b (9000 bits)"
+    pub fn describe(&self, board: Board) -> String {
+        format!(
+            "{} {}: {} | {}: {}",
+            board.title(),
+            Scope::Stream.name(),
+            self.standings(board, Scope::Stream).describe(),
+            Scope::AllTime.name(),
+            self.standings(board, Scope::AllTime).describe()
+        )
+    }
+}
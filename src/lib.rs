@@ -1,6 +1,102 @@
+//! Choui's bot as a library, for building Twitch tools of your own on it.
+//! The app (terminal UI, overlay, headless) is one frontend built this way.
+//!
+//! The stable part is what's re-exported here:
+//!
+//! - [`Config`] and [`ConfigBuilder`]: settings, from the environment like the
+//!   app or in code
+//! - [`BotCore`]: logging in, connecting, the [`AppEvent`] stream, and saying
+//!   things in chat
+//! - [`ask_ai`] and [`ask`]: the configured LLM (Gemini or Ollama)
+//!
+//! The modules behind them are the app's and change with it.
+//!
+//! ```no_run
+//! use choui_the_no_gui_chatbot::{AppEvent, BotCore, Config};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let config = Config::builder("my-client-id", "my_bot")
+//!     .channel("my_channel")
+//!     .build()?;
+//! let mut bot = BotCore::connect(config).await?;
+//! let mut events = bot.events().expect("not taken yet");
+//! bot.subscribe().await?;
+//!
+//! while let Some(event) = events.recv().await {
+//!     if let AppEvent::ChatMessage(message) = event {
+//!         if let Some(question) = message.text.strip_prefix("!ask ") {
+//!             let answer = bot.ask(question).await?;
+//!             bot.say(format!("@{} {}", message.display_name, answer));
+//!         }
+//!     }
+//! }
+//! bot.shutdown(None).await;
+//! # Ok(())
+//! # }
+//! ```
+
 pub mod ai;
+pub mod api;
+pub mod audio;
+pub mod auto_replies;
+pub mod automod;
+pub mod bench;
+pub mod bot;
+pub mod chat_commands;
+pub mod chat_speed;
+pub mod chat_stages;
+pub mod chatlog;
+pub mod clipboard;
+pub mod commands;
 pub mod config;
+pub mod config_file;
+pub mod counters;
+pub mod custom_commands;
+pub mod demo;
+pub mod diagnostics;
+pub mod emotes;
+pub mod encoder;
+pub mod features;
+pub mod filters;
+pub mod follows;
+pub mod hints;
+pub mod input;
+pub mod irc;
+pub mod keys;
+pub mod lang;
+pub mod leaderboards;
+pub mod links;
+pub mod lurk;
+pub mod matrix;
+pub mod memory;
+pub mod minigames;
+pub mod modlog;
+pub mod mqtt;
+pub mod outbox;
+pub mod paths;
+pub mod pipeline;
+pub mod plugins;
+pub mod polls;
+pub mod quotes;
+pub mod scripts;
+pub mod search;
+pub mod secrets;
+pub mod session;
+pub mod shield;
+pub mod songs;
+pub mod sqlite;
 pub mod state;
+pub mod theme;
+pub mod timers;
+pub mod tips;
+pub mod translate;
+pub mod tts;
 pub mod twitch;
 pub mod ui;
+pub mod viewers;
 pub mod ws;
+
+pub use ai::{ask, ask_ai};
+pub use bot::BotCore;
+pub use config::{Config, ConfigBuilder, Endpoints, LlmProvider};
+pub use state::{AppEvent, Badge, ChatMessage, Fragment, Role, StreamAlert};
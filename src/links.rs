@@ -0,0 +1,98 @@
+use crate::config::{env_flag, var};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+// Link protection: with LINK_PROTECTION on, a message with a link from a
+// viewer (not a VIP, mod or the broadcaster) is deleted and they're warned,
+// unless a mod gave them a pass with !permit <user> in the last
+// LINK_PERMIT_SECONDS. Links to LINK_ALLOWED_DOMAINS (and their subdomains)
+// are always fine.
+
+// Full URLs, and bare domains ending in a common TLD, so "lol.ok" isn't one
+// but "example.com" is
+static LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(?:https?://[^\s/$.?#][^\s]*|(?:[a-z0-9](?:[a-z0-9-]*[a-z0-9])?\.)+(?:com|net|org|tv|gg|io|co|me|ly|be|gl|xyz|info|ru|de|uk|us|ca|app|dev|link|live|shop|site|online|store|club|top)\b(?:/\S*)?)",
+    )
+    .expect("link pattern")
+});
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkSettings {
+    pub enabled: bool,
+    pub permit: Duration,
+    // Lowercase, e.g. "clips.twitch.tv"
+    pub allowed_domains: Vec<String>,
+}
+
+impl LinkSettings {
+    // LINK_PROTECTION, LINK_PERMIT_SECONDS, LINK_ALLOWED_DOMAINS
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            enabled: env_flag("LINK_PROTECTION", false)?,
+            permit: match var("LINK_PERMIT_SECONDS") {
+                Ok(value) => value
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .map(Duration::from_secs)
+                    .with_context(|| {
+                        format!(
+                            "LINK_PERMIT_SECONDS must be a positive number, got '{}'",
+                            value
+                        )
+                    })?,
+                Err(_) => Duration::from_secs(60),
+            },
+            allowed_domains: var("LINK_ALLOWED_DOMAINS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|domain| domain.trim().trim_start_matches("*.").to_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect(),
+        })
+    }
+}
+
+// The host of a link: "https://www.Example.com/x" -> "www.example.com"
+fn host(link: &str) -> String {
+    let rest = link.split_once("://").map_or(link, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    host.split(':').next().unwrap_or(host).to_lowercase()
+}
+
+/// The first link in `text` that isn't to an allowed domain.
+pub fn forbidden_link<'a>(text: &'a str, settings: &LinkSettings) -> Option<&'a str> {
+    LINK.find_iter(text).map(|link| link.as_str()).find(|link| {
+        let host = host(link);
+        !settings.allowed_domains.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    })
+}
+
+// Who may post links right now
+#[derive(Debug, Default)]
+pub struct Permits {
+    granted: HashMap<String, Instant>,
+}
+
+impl Permits {
+    pub fn grant(&mut self, user: &str) {
+        self.granted
+            .insert(user.trim_start_matches('@').to_lowercase(), Instant::now());
+    }
+
+    pub fn allows(&mut self, settings: &LinkSettings, user: &str) -> bool {
+        self.granted.retain(|_, at| at.elapsed() < settings.permit);
+        self.granted.contains_key(&user.to_lowercase())
+    }
+}
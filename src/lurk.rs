@@ -0,0 +1,72 @@
+use crate::ai;
+use crate::config::Config;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// !lurk and !unlurk. A lurking viewer isn't greeted when their join shows up
+// and isn't read aloud, until they !unlurk or leave. The time they lurked is
+// added to their watch time (see viewers.rs).
+
+#[derive(Debug, Default)]
+pub struct Lurkers {
+    // When each one started lurking, by login
+    since: HashMap<String, Instant>,
+}
+
+impl Lurkers {
+    /// Start lurking. False if `user` already is.
+    pub fn start(&mut self, user: &str) -> bool {
+        let user = user.to_lowercase();
+        if self.since.contains_key(&user) {
+            return false;
+        }
+        self.since.insert(user, Instant::now());
+        true
+    }
+
+    /// Stop lurking, with how long it lasted. None if `user` wasn't.
+    pub fn stop(&mut self, user: &str) -> Option<Duration> {
+        let since = self.since.remove(&user.to_lowercase())?;
+        Some(since.elapsed())
+    }
+
+    pub fn is_lurking(&self, user: &str) -> bool {
+        self.since.contains_key(&user.to_lowercase())
+    }
+
+    /// Everyone stops, e.g. when the bot quits
+    pub fn stop_all(&mut self) -> Vec<(String, Duration)> {
+        self.since
+            .drain()
+            .map(|(user, since)| (user, since.elapsed()))
+            .collect()
+    }
+}
+
+/// What the bot says to `user` going to lurk: the AI's send-off if LURK_AI is
+/// on and the AI answers.
+pub async fn send_off(user: &str, config: &Config) -> String {
+    let fallback = format!("@{} Enjoy the lurk! !unlurk when you're back", user);
+    if !config.lurk_ai {
+        return fallback;
+    }
+    let prompt = format!(
+        "User {} is going to lurk: keep watching without chatting. See them off playfully with a single short sentence. Do not ask any questions.",
+        user
+    );
+    match ai::ask_ai(&prompt, config).await {
+        Ok(quip) if !quip.trim().is_empty() => format!("@{} {}", user, quip.trim()),
+        _ => fallback,
+    }
+}
+
+/// "2h 5m", "12m" or "40s"
+pub fn describe(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes) = (secs / 3600, secs % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", secs),
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
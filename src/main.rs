@@ -1,41 +1,279 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use dotenv::dotenv;
 
 use futures_util::StreamExt;
-use ratatui::{backend::CrosstermBackend, Terminal};
+use ratatui::{backend::CrosstermBackend, layout::Rect, Terminal, TerminalOptions, Viewport};
 
 use std::fs;
+use std::io::Write;
 use tokio::sync::mpsc;
-use tui_input::backend::crossterm::EventHandler;
 
 use choui_the_no_gui_chatbot::{
-    ai::ask_ai,
-    config::Config,
-    state::{App, AppEvent},
-    twitch::{
-        authenticate_via_device_flow, get_user_id, get_user_login, load_token_cache, refresh_token,
-        save_token_cache, send_chat_message, subscribe_to_chat_messages, validate_token,
-    },
+    api, bench,
+    bot::BotCore,
+    config::{self, Config, OverlayConfig},
+    emotes,
+    encoder::Encoder,
+    matrix, modlog, mqtt,
+    session::Session,
+    state::{self, App, AppEvent, ChatLine, Tab, UiState},
+    tips::{self, TipService},
     ui::ui,
-    ws::{connect_eventsub_ws, connect_irc_ws},
 };
 
-mod audio;
+mod check;
 mod gui;
 
 use std::sync::{Arc, Mutex};
 
+// Which frontends to run. With neither the bot runs headless and logs to
+// stdout (and the --log-file, if given).
+struct Frontends {
+    tui: bool,
+    overlay: bool,
+    log_file: Option<String>,
+    // Made-up chat instead of Twitch (see demo.rs)
+    demo: bool,
+    // `check` runs the setup checks instead of the bot
+    check: bool,
+    // `secret set|delete <NAME>` edits the keyring instead
+    secret: Option<(String, String)>,
+    // `modlog [<user>] [--limit <n>]` prints the mod log instead
+    modlog: Option<(Option<String>, usize)>,
+    // `bench [<scenario>]` runs the scenario through the bot, headless and
+    // in the demo channel, then reports
+    bench: Option<bench::Scenario>,
+}
+
+const USAGE: &str =
+    "Usage: choui-the-no-gui-chatbot [--no-overlay | --no-tui | --headless] [--log-file <path>]
+                               [--demo] [--config <path>]
+       choui-the-no-gui-chatbot check [--config <path>]
+       choui-the-no-gui-chatbot secret (set | delete) <NAME>
+       choui-the-no-gui-chatbot modlog [<user>] [--limit <n>] [--config <path>]
+       choui-the-no-gui-chatbot bench [<scenario options>] [--config <path>]
+
+  check              Check settings, the Twitch token and scopes, the AI provider, audio
+                     and terminal graphics, then exit (non-zero if anything failed)
+  secret             Store an API key (read from stdin) in the system keyring, or remove
+                     it; used with SECRET_STORE=keyring. NAME is one of
+{secret_names}
+  modlog             Print the latest moderation actions from the chat database (50
+                     unless --limit says otherwise), or only those about <user>
+  bench              Send made-up chat, joins and raids through the bot as configured, with
+                     canned AI replies, a silent TTS and an offscreen terminal UI, then
+                     report CPU, per-event latency and render times (bench --help for the
+                     scenario options)
+
+  --no-overlay       Terminal UI only, don't open the overlay window (env NO_OVERLAY=1)
+  --no-tui           Overlay only, no terminal UI; Ctrl+C quits (env NO_TUI=1)
+  --headless         Neither, just the bot, logging to stdout; Ctrl+C quits (env HEADLESS=1)
+  --log-file <path>  Also append the log to a file when there's no terminal UI (env LOG_FILE)
+  --demo             Try it out without Twitch or an AI provider: made-up viewers chat, join,
+                     follow and cheer, the AI gives canned replies and nothing is sent (env DEMO=1)
+  --config <path>    Settings file (env CHOUI_CONFIG), instead of looking for choui.toml in
+                     the current directory, then the user config directory";
+
+// USAGE with the names `secret` takes, wrapped to the description column
+fn usage() -> String {
+    use choui_the_no_gui_chatbot::secrets::SECRET_VARS;
+
+    let indent = " ".repeat(21);
+    let mut lines: Vec<String> = Vec::new();
+    for name in SECRET_VARS {
+        match lines.last_mut() {
+            Some(line) if line.len() + 2 + name.len() <= 90 => {
+                line.push_str(", ");
+                line.push_str(name);
+            }
+            _ => lines.push(format!("{}{}", indent, name)),
+        }
+    }
+    USAGE.replace("{secret_names}", &lines.join(",\n"))
+}
+
+fn parse_frontends() -> Result<Frontends> {
+    let headless = config::env_flag("HEADLESS", false)?;
+    let mut frontends = Frontends {
+        tui: !headless && !config::env_flag("NO_TUI", false)?,
+        overlay: !headless && !config::env_flag("NO_OVERLAY", false)?,
+        log_file: config::var("LOG_FILE")
+            .ok()
+            .filter(|p| !p.trim().is_empty()),
+        demo: config::env_flag("DEMO", false)?,
+        check: false,
+        secret: None,
+        modlog: None,
+        bench: None,
+    };
+
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "check" => frontends.check = true,
+            "secret" => {
+                let action = args.next().context("secret needs set or delete")?;
+                let name = args.next().context("secret needs a name")?;
+                frontends.secret = Some((action, name));
+            }
+            "modlog" => {
+                let user = args
+                    .next_if(|arg| !arg.starts_with('-'))
+                    .map(|user| user.trim_start_matches('@').to_lowercase());
+                frontends.modlog = Some((user, 50));
+            }
+            "bench" => {
+                // The rest is the scenario, apart from --config
+                let mut scenario = Vec::new();
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--config" => {
+                            args.next().context("--config needs a path")?;
+                        }
+                        _ => scenario.push(arg),
+                    }
+                }
+                let command = "choui-the-no-gui-chatbot bench";
+                frontends.bench = Some(bench::Scenario::from_args(scenario, command)?);
+                frontends.tui = false;
+                frontends.overlay = false;
+                frontends.demo = true;
+            }
+            "--limit" => {
+                let limit = args.next().context("--limit needs a number")?;
+                let limit = limit
+                    .parse()
+                    .with_context(|| format!("--limit needs a number, got '{}'", limit))?;
+                match &mut frontends.modlog {
+                    Some((_, modlog_limit)) => *modlog_limit = limit,
+                    None => anyhow::bail!("--limit goes with modlog\n\n{}", usage()),
+                }
+            }
+            "--no-overlay" => frontends.overlay = false,
+            "--demo" => frontends.demo = true,
+            "--no-tui" => frontends.tui = false,
+            "--headless" => {
+                frontends.tui = false;
+                frontends.overlay = false;
+            }
+            "--log-file" => {
+                let path = args.next().context("--log-file needs a path")?;
+                frontends.log_file = Some(path);
+            }
+            // Already applied by config_arg()
+            "--config" => {
+                args.next().context("--config needs a path")?;
+            }
+            "-h" | "--help" => {
+                println!("{}", usage());
+                std::process::exit(0);
+            }
+            other => anyhow::bail!("Unknown argument '{}'\n\n{}", other, usage()),
+        }
+    }
+    Ok(frontends)
+}
+
+fn manage_secret(action: &str, name: &str) -> Result<()> {
+    use choui_the_no_gui_chatbot::secrets::{self, SECRET_VARS};
+
+    let name = name.to_uppercase();
+    if !SECRET_VARS.contains(&name.as_str()) {
+        anyhow::bail!(
+            "Unknown secret '{}', expected one of {}",
+            name,
+            SECRET_VARS.join(", ")
+        );
+    }
+    match action {
+        "set" => {
+            eprint!("{}: ", name);
+            let mut value = String::new();
+            std::io::stdin().read_line(&mut value)?;
+            let value = value.trim();
+            if value.is_empty() {
+                anyhow::bail!("No value given");
+            }
+            secrets::set(&name, value)?;
+            println!(
+                "Stored {} in the keyring. With SECRET_STORE=keyring it's used whenever .env and choui.toml don't set it.",
+                name
+            );
+        }
+        "delete" => {
+            secrets::delete(&name)?;
+            println!("Removed {} from the keyring.", name);
+        }
+        other => anyhow::bail!("Unknown secret action '{}', expected set or delete", other),
+    }
+    Ok(())
+}
+
+// --config decides which file the other settings come from, so it's picked
+// out before everything else
+fn config_arg() {
+    let mut args = std::env::args().skip_while(|arg| arg != "--config").skip(1);
+    if let Some(path) = args.next() {
+        // Read by config_path(), before any other thread is running
+        std::env::set_var("CHOUI_CONFIG", path);
+    }
+}
+
 fn main() -> Result<()> {
+    config_arg();
+    // The environment wins over .env, which wins over the file
+    let file_loaded = config::load_settings();
+    let frontends = parse_frontends()?;
+
+    if frontends.check {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let passed = rt.block_on(check::run(file_loaded));
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+    if let Some((action, name)) = &frontends.secret {
+        return manage_secret(action, name);
+    }
+    file_loaded?;
+    // Before the bot's threads start
+    if frontends.bench.is_some() {
+        bench::fill_in_settings();
+    } else if frontends.demo {
+        choui_the_no_gui_chatbot::demo::fill_in_settings();
+    }
+    if let Some((user, limit)) = &frontends.modlog {
+        let database = Config::from_env()?.database();
+        for entry in modlog::query(&database, user.as_deref(), *limit)? {
+            println!("{}", entry.describe());
+        }
+        return Ok(());
+    }
+
     // 1. Create Broadcast Channel
     let (tx, _rx) = tokio::sync::broadcast::channel(100);
 
+    if !frontends.overlay {
+        // No window to keep on the main thread, so the bot gets it
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        return rt.block_on(run_bot(tx, frontends));
+    }
+    let overlay_config = OverlayConfig::load()?;
+
     // 2. Spawn Bot Thread
     let tx_for_bot = tx.clone();
+    let bot_frontends = Frontends {
+        log_file: frontends.log_file.clone(),
+        ..frontends
+    };
     std::thread::spawn(move || {
         let rt = tokio::runtime::Builder::new_multi_thread()
             .enable_all()
@@ -43,29 +281,21 @@ fn main() -> Result<()> {
             .unwrap();
 
         rt.block_on(async {
-            if let Err(e) = run_bot(tx_for_bot).await {
+            if let Err(e) = run_bot(tx_for_bot, bot_frontends).await {
                 eprintln!("Bot Error: {}", e);
             }
         });
     });
 
-    // 3. Run Iced GUI
-    use iced::window;
-    use iced::Application;
+    // 3. Run Iced GUI (the first configured window; gui spawns any others)
+    use iced::multi_window::Application;
 
     let settings = iced::Settings {
-        flags: Arc::new(Mutex::new(Some(tx.subscribe()))),
-        window: window::Settings {
-            transparent: true,
-            decorations: false, // Remove title bar for overlay look
-            level: window::Level::AlwaysOnTop,
-            size: iced::Size {
-                width: 400.0,
-                height: 600.0,
-            },
-            ..Default::default()
-        },
-        ..Default::default()
+        window: gui::window_settings(&overlay_config.windows[0].1),
+        ..iced::Settings::with_flags(gui::Flags {
+            receiver: Arc::new(Mutex::new(Some(tx.subscribe()))),
+            config: overlay_config,
+        })
     };
 
     gui::Overlay::run(settings)?;
@@ -73,608 +303,360 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-async fn run_bot(broadcast_tx: tokio::sync::broadcast::Sender<AppEvent>) -> Result<()> {
+async fn run_bot(
+    broadcast_tx: tokio::sync::broadcast::Sender<AppEvent>,
+    mut frontends: Frontends,
+) -> Result<()> {
+    let tui = frontends.tui;
     // env_logger::init(); // Disable logger output to stdout to avoid breaking TUI
-    dotenv().ok();
 
     println!("Twitch EventSub Chat Bot (Rust) starting...");
 
-    let mut config = Config::from_env()?;
+    let config = Config::from_env()?;
+    // As read, before the ids get resolved; reloads are compared against it
+    let loaded_config = config.clone();
 
-    if let Ok(mut file) = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("debug.log")
-    {
-        use std::io::Write;
-        let key_status = if config.gemini_api_key.is_some() {
-            "Present"
+    // Startup diagnostics, shown in the Log tab once the TUI is up
+    let mut startup_log = Vec::new();
+    let key_status = if config.gemini_api_key.is_some() {
+        "Present"
+    } else {
+        "MISSING"
+    };
+    startup_log.push(format!(
+        "Startup Config Check: GEMINI_API_KEY is {}",
+        key_status
+    ));
+    startup_log.push(format!(
+        "Startup Config Check: GEMINI_MODEL is '{}'",
+        config.gemini_model
+    ));
+
+    // Also check raw env var just in case parsing failed silently
+    let raw_env = config::var("GEMINI_API_KEY").unwrap_or_else(|_| "Not found in env".to_string());
+    startup_log.push(format!(
+        "Raw Env Check: GEMINI_API_KEY is '{}'",
+        if raw_env.len() > 5 {
+            "Set (masked)"
         } else {
-            "MISSING"
-        };
-        writeln!(
-            file,
-            "Startup Config Check: GEMINI_API_KEY is {}",
-            key_status
-        )
-        .unwrap_or(());
-        writeln!(
-            file,
-            "Startup Config Check: GEMINI_MODEL is '{}'",
-            config.gemini_model
-        )
-        .unwrap_or(());
-
-        // Also check raw env var just in case parsing failed silently
-        let raw_env =
-            std::env::var("GEMINI_API_KEY").unwrap_or_else(|_| "Not found in env".to_string());
-        writeln!(
-            file,
-            "Raw Env Check: GEMINI_API_KEY is '{}'",
-            if raw_env.len() > 5 {
-                "Set (masked)"
-            } else {
-                &raw_env
-            }
-        )
-        .unwrap_or(());
-    }
-
-    let client = reqwest::Client::new();
-
-    // Authenticate (Device Flow or Cache)
-    println!("Authenticating...");
-    let token = 'auth: {
-        if let Ok(cached) = load_token_cache() {
-            println!("Found cached token. Validating...");
-            if validate_token(&client, &cached.access_token)
-                .await
-                .unwrap_or(false)
-            {
-                println!("Token is valid!");
-                break 'auth cached.access_token;
-            }
-
-            println!("Token expired or invalid.");
-            if let Some(rt) = cached.refresh_token {
-                println!("Attempting refresh...");
-                if let Ok(new_token) = refresh_token(&client, &config, &rt).await {
-                    println!("Refresh successful!");
-                    let _ = save_token_cache(&new_token);
-                    break 'auth new_token.access_token;
-                }
-                println!("Refresh failed.");
-            }
+            &raw_env
         }
+    ));
 
-        println!("Starting Device Authorization Flow...");
-        let token_resp = authenticate_via_device_flow(&client, &config).await?;
-        token_resp.access_token
+    // Logs in and fills in the ids
+    let mut bot = if frontends.demo {
+        BotCore::demo(config)
+    } else {
+        BotCore::connect(config).await?
     };
-    config.oauth_token = Some(token);
-    println!("Authentication successful!");
-
-    // Resolve bot_user_id if it's not a numeric ID
-    if config.bot_user_id.chars().any(|c| !c.is_numeric()) {
-        println!(
-            "Detected username for BOT_USER_ID: {}. Resolving to ID...",
-            config.bot_user_id
-        );
-        let id = get_user_id(&client, &config, &config.bot_user_id).await?;
-        println!("Resolved Bot ID: {}", id);
-        config.bot_user_id = id;
-    }
+    let (client, config) = (bot.client.clone(), bot.config.clone());
 
-    // Resolve channel_user_id if missing
-    if config.channel_user_id.is_none() {
-        if let Some(name) = &config.channel_name {
-            println!("Resolving ID for channel: {}", name);
-            let id = get_user_id(&client, &config, name).await?;
-            println!("Resolved ID: {}", id);
-            config.channel_user_id = Some(id);
-        } else {
-            // Panic or error if neither is set, but Config::from_env might handle it differently?
-            // Actually Config::from_env just loads vars.
-            // Let's rely on logic.
-            anyhow::bail!("Neither CHANNEL_USER_ID nor CHANNEL_NAME is set");
+    // --- TUI Setup ---
+    // Without the TUI the same event loop runs, it just never draws or reads
+    // keys. The benchmark draws, but offscreen.
+    let mut terminal: Option<Terminal<CrosstermBackend<Box<dyn Write>>>> = if tui {
+        println!("Starting UI...");
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        execute!(
+            stdout,
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableFocusChange
+        )?;
+        Some(Terminal::new(CrosstermBackend::new(
+            Box::new(stdout) as Box<dyn Write>
+        ))?)
+    } else if frontends.bench.is_some() {
+        let sink: Box<dyn Write> = Box::new(std::io::sink());
+        let viewport = Viewport::Fixed(Rect::new(0, 0, 120, 40));
+        Some(Terminal::with_options(
+            CrosstermBackend::new(sink),
+            TerminalOptions { viewport },
+        )?)
+    } else {
+        println!("Running without the terminal UI, press Ctrl+C to quit.");
+        None
+    };
+
+    let mut app = App::new(config.clone(), bot.bot_login.clone());
+    app.outbox = bot.outbox().clone();
+    if !tui && frontends.bench.is_none() {
+        app.echo.push(Box::new(std::io::stdout()));
+        if let Some(path) = &frontends.log_file {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path))?;
+            app.echo.push(Box::new(file));
         }
     }
+    let ui_state = UiState::load(&app.config);
+    app.emote_panel = ui_state.emote_panel;
+    app.log.extend(startup_log.into_iter().map(ChatLine::from));
+
+    // Font size from termios, protocol from querying the terminal (this also
+    // notices tmux). A protocol saved from Ctrl+P wins over the guess.
+    if tui {
+        let mut picker = ratatui_image::picker::Picker::from_termios()
+            .unwrap_or(ratatui_image::picker::Picker::new((8, 12)));
+        let detected = picker.guess_protocol();
+        app.protocol_choice = ui_state
+            .image_protocol
+            .as_deref()
+            .and_then(state::protocol_from_name);
+        picker.protocol_type = app.protocol_choice.unwrap_or(detected);
+        app.protocol_name = state::protocol_name(picker.protocol_type);
+        app.picker = Some(picker);
+        app.push(
+            Tab::Log,
+            format!(
+                "Image protocol: {} (detected {})",
+                app.protocol_name,
+                state::protocol_name(detected)
+            ),
+        );
+    }
+    app.push(
+        Tab::Log,
+        format!("TTS engine: {}", app.config.tts.backend.name()),
+    );
+    for (kind, voice) in &config.tts.cloud {
+        app.push(
+            Tab::Log,
+            format!(
+                "TTS for {}: {:?} voice {}",
+                kind.name(),
+                voice.provider,
+                voice.voice
+            ),
+        );
+    }
 
-    // Ensure we have channel_name (needed for IRC)
-    if config.channel_name.is_none() {
-        if let Some(id) = &config.channel_user_id {
-            println!("Resolving Name for channel ID: {}", id);
-            let name = get_user_login(&client, &config, id).await?;
-            println!("Resolved Name: {}", name);
-            config.channel_name = Some(name);
-        }
+    let tx = bot.sender();
+    if let Some(picker) = app.picker {
+        app.encoder = Some(Encoder::start(picker, tx.clone()));
     }
+    let mut rx = bot.events().expect("the event stream is only taken here");
 
-    // Fetch bot login name
-    println!("Resolving Bot Login...");
-    let bot_login = get_user_login(&client, &config, &config.bot_user_id).await?;
-    println!("Bot Login: {}", bot_login);
+    // The picker's emotes, a few at a time. The benchmark waits for them so
+    // they aren't part of what it measures.
+    let emotes = tokio::spawn(emotes::load(client.clone(), config.clone(), tx.clone()));
+    if frontends.bench.is_some() {
+        let _ = emotes.await;
+    }
 
-    println!("Starting UI...");
-    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    // Chat, alerts and joins, then the chatter list and stream stats. The
+    // benchmark's events are the only ones.
+    if frontends.bench.is_some() {
+        bot.run_offline();
+    } else {
+        bot.subscribe().await?;
+    }
+    bot.poll_channel();
+
+    let mut session = Session::new(
+        app,
+        &bot,
+        broadcast_tx.clone(),
+        &ui_state,
+        loaded_config,
+        frontends.bench.is_some(),
+    )?;
 
-    // --- TUI Setup ---
-    enable_raw_mode()?;
-    let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    let mut app = App::new(config.clone(), bot_login);
-
-    // Use automatic detection for font size, but default to Sixel as requested
-    let mut picker = ratatui_image::picker::Picker::from_termios()
-        .unwrap_or(ratatui_image::picker::Picker::new((8, 12)));
-    picker.protocol_type = ratatui_image::picker::ProtocolType::Sixel;
-    app.protocol_name = format!("{:?}", picker.protocol_type);
-    app.picker = Some(picker.clone());
-
-    // Fetch Global Emotes (Async in background, but updating state needs care)
-    // For simplicity, let's fetch BEFORE event loop or in separate task that sends Event?
-    // Let's do it before TUI starts to keep it simple for now, or use channel?
-    // Better: Helper function to populate App.
-    // Fetching 300 emotes will be SLOW sequentially.
-    // Let's try to fetch just the GLOBAL ones first.
-
-    println!("Fetching Global Emotes...");
-    let client_clone = client.clone();
-    let config_clone = config.clone();
-
-    // We can't easily wait here because TUI isn't up, but we want status.
-    // Let's spawn a task to load them and send AppEvent::EmotesLoaded?
-    // Implementation:
-    // 1. Fetch map
-    // 2. Filter map for EMOJIS list (so we only download what we need)
-    // 3. Download images
-    // 4. Decode
-    // 5. Send to main thread
-
-    // Since AppEvent doesn't carry images easily (Protocol is not Send/Sync sometimes?),
-    // Actually Protocol IS Send.
-    // But `dyn Protocol`...
-    // Let's simplify: Just load for "Kappa" and "PogChamp" first to verify.
-    // Or try to load all in background.
-
-    // Let's spawn the loader.
-    let (tx, mut rx) = mpsc::unbounded_channel();
-
-    let tx_loader = tx.clone();
+    // Re-read the settings whenever choui.toml or .env is saved
+    let tx_overlay = tx.clone();
+    let tx_reload = tx.clone();
     tokio::spawn(async move {
-        use choui_the_no_gui_chatbot::state::EMOJIS;
-        use choui_the_no_gui_chatbot::twitch::{download_emote, get_global_emotes};
-
-        match get_global_emotes(&client_clone, &config_clone).await {
-            Ok(map) => {
-                let _ = tx_loader.send(AppEvent::Info("Global emote map fetched.".into()));
-                // Ensure assets directory exists
-                let _ = fs::create_dir_all("assets/emotes");
-
-                for &name in EMOJIS {
-                    let file_path = format!("assets/emotes/{}.png", name);
-                    let path = std::path::Path::new(&file_path);
-
-                    let img_data = if path.exists() {
-                        // Load from file
-                        fs::read(path).ok()
-                    } else {
-                        // Download
-                        if let Some(url) = map.get(name) {
-                            match download_emote(&client_clone, url).await {
-                                Ok(bytes) => {
-                                    // Save to file
-                                    let _ = fs::write(path, &bytes);
-                                    Some(bytes)
-                                }
-                                Err(_) => None,
-                            }
-                        } else {
-                            None
-                        }
-                    };
-
-                    if let Some(bytes) = img_data {
-                        if let Ok(dyn_img) = image::load_from_memory(&bytes) {
-                            let _ = tx_loader.send(AppEvent::EmoteImage(name.to_string(), dyn_img));
-                        }
-                    }
-                }
+        use choui_the_no_gui_chatbot::config_file::{config_path, ConfigFile};
+
+        let paths = || [Some(config_path()), config::dotenv_path()];
+        let modified = || paths().map(|path| path.and_then(|path| ConfigFile::modified(&path)));
+        let mut last_modified = modified();
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let now = modified();
+            if now == last_modified {
+                continue;
+            }
+            last_modified = now;
+            let event = match OverlayConfig::load() {
+                Ok(config) => AppEvent::OverlayConfig(Box::new(config)),
+                Err(e) => AppEvent::Error(format!("Overlay config not reloaded: {:#}", e)),
+            };
+            if tx_overlay.send(event).is_err() {
+                break;
             }
-            Err(e) => {
-                let _ = tx_loader.send(AppEvent::Error(format!("Failed to fetch emotes: {}", e)));
+            let event = match config::load_settings().and_then(|()| Config::from_env()) {
+                Ok(config) => AppEvent::ConfigReloaded(Arc::new(config)),
+                Err(e) => AppEvent::Error(format!("Config not reloaded: {:#}", e)),
+            };
+            if tx_reload.send(event).is_err() {
+                break;
             }
         }
     });
 
-    // Oops, I can't easily modify AppEvent without another step.
-    // Let's MODIFY src/state.rs FIRST to accept Images.
-    // Aborting this specific edit to fix state first.
-
-    // Actually, I can use `write_to_file` to update AppEvent?
-    // No, I should do it in `src/state.rs`.
-
-    // Let's continue with basic TUI setup but without the loader task fully wired yet.
-    // I will return to state.rs.
-
-    // --- Broadcast Channel for Overlay ---
-    // --- Broadcast Channel passed in ---
-
-    // Start Web Server -> REMOVED
-
-    let (session_id, _ws_handle) =
-        connect_eventsub_ws(client.clone(), config.clone(), tx.clone()).await?;
-
-    // Connect to IRC WebSocket (for Join/Part events)
-    let _irc_handle = connect_irc_ws(config.clone(), tx.clone()).await?;
-
-    // Subscribe
-    match subscribe_to_chat_messages(&client, &session_id, &config).await {
-        Ok(_) => {
-            let _ = tx.send(AppEvent::Info("Subscribed to chat".into()));
-        }
-        Err(e) => {
-            let _ = tx.send(AppEvent::Error(format!("Subscription failed: {}", e)));
-        }
-    }
-
-    let mut event_stream = crossterm::event::EventStream::new();
-
+    let mut event_stream = tui.then(crossterm::event::EventStream::new);
     // Flag to control redraws
     let mut should_render = true;
-    let mut last_ai_reply = std::time::Instant::now()
-        .checked_sub(std::time::Duration::from_secs(10))
-        .unwrap();
+    // Redraws elapsed times on the AI tab while requests are in flight, and
+    // posts the timed messages
+    let mut ai_ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+    // Requests to the HTTP API, answered below (API_ENABLED)
+    let (api_tx, mut api_rx) = mpsc::unbounded_channel();
+    // Donations (STREAMELEMENTS_TOKEN, STREAMLABS_TOKEN) come in as alerts
+    for (service, token) in TipService::configured(&session.app.config.tips) {
+        let (config, tx_tips) = (session.app.config.clone(), tx.clone());
+        tokio::spawn(async move {
+            if let Err(e) = tips::run(service, token, config, tx_tips.clone()).await {
+                let _ = tx_tips.send(AppEvent::Error(format!("Tips stopped: {:#}", e)));
+            }
+        });
+    }
+    // Matrix admins' commands are answered the same way (MATRIX_ENABLED)
+    if session.app.config.matrix.enabled {
+        let settings = session.app.config.matrix.clone();
+        let (client, tx_matrix) = (client.clone(), tx.clone());
+        let (events, calls) = (broadcast_tx.subscribe(), api_tx.clone());
+        tokio::spawn(async move {
+            if let Err(e) = matrix::run(settings, client, tx_matrix.clone(), events, calls).await {
+                let _ = tx_matrix.send(AppEvent::Error(format!("Matrix stopped: {:#}", e)));
+            }
+        });
+    }
+    // Alerts, mentions and going live, for home automation (MQTT_ENABLED)
+    if session.app.config.mqtt.enabled {
+        let settings = session.app.config.mqtt.clone();
+        let mut names = vec![session.app.bot_login.clone()];
+        names.extend(session.app.config.channel_name.clone());
+        let (tx_mqtt, events) = (tx.clone(), broadcast_tx.subscribe());
+        tokio::spawn(async move {
+            if let Err(e) = mqtt::run(settings, names, tx_mqtt.clone(), events).await {
+                let _ = tx_mqtt.send(AppEvent::Error(format!("MQTT stopped: {:#}", e)));
+            }
+        });
+    }
+    if session.app.config.api.enabled {
+        let settings = session.app.config.api.clone();
+        session
+            .app
+            .push(Tab::Log, format!("API listening on {}", settings.address));
+        let tx_api = tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = api::serve(settings, api_tx).await {
+                let _ = tx_api.send(AppEvent::Error(format!("API stopped: {:#}", e)));
+            }
+        });
+    }
+
+    // Redraws are coalesced: a render requested sooner than the frame interval
+    // (MAX_FPS, or slower in low-power mode) after the last one waits for the
+    // deadline below, so a flood of events costs a few frames. Each frame only
+    // writes the cells that changed since the last one (ratatui diffs them).
+    let mut last_draw = tokio::time::Instant::now();
+    // The benchmark starts sending once everything's up, and is done when
+    // its events have all been handled
+    let (mut bench_run, mut bench_done) = match frontends.bench.take() {
+        Some(scenario) => {
+            let (run, done) = bench::Run::start(scenario, tx.clone());
+            (Some(run), Some(done))
+        }
+        None => (None, None),
+    };
+    let mut bench_result = None;
 
     loop {
-        if should_render {
-            terminal.draw(|f| ui(f, &mut app))?;
+        let frame_interval = session.app.frame_interval();
+        if should_render && last_draw.elapsed() >= frame_interval {
+            session.app.note_presence();
+            if let Some(terminal) = terminal.as_mut() {
+                let started = std::time::Instant::now();
+                terminal.draw(|f| ui(f, &mut session.app))?;
+                if let Some(run) = bench_run.as_mut() {
+                    run.frames.record(started);
+                }
+            }
             should_render = false;
+            last_draw = tokio::time::Instant::now();
         }
 
         tokio::select! {
-           Some(evt) = rx.recv() => {
-               // Broadcast ALL events to Overlay
-               let _ = broadcast_tx.send(evt.clone());
-
-               should_render = true;
-               match evt {
-                   AppEvent::ChatMessage { user, text } => {
-                       app.messages.push(format!("{}: {}", user, text));
-
-                       // TTS: Speak the message (runs in bot thread, always plays)
-                       let tts_msg = format!("{} says: {}", user, text);
-                       std::thread::spawn(move || {
-                           let _ = std::process::Command::new("espeak").arg(&tts_msg).spawn();
-                       });
-
-                       // Ignore own messages for AI response
-                       if user.eq_ignore_ascii_case(&app.bot_login) {
-                           continue;
-                       }
-
-                       let bot_name = app.config.channel_name.as_deref().unwrap_or("unknown");
-
-                       // Logic:
-                       // 1. Incognito (only reply if "hey", "hello", "intro", OR direct mention/!bot)
-                       // 2. Mocking/Antagonistic (handled by AI prompt, but we just trigger)
-                       // 3. Rate Limit (1s)
-
-                       let text_lower = text.to_lowercase();
-                       let is_trigger = text_lower.contains("hey") ||
-                                        text_lower.contains("hello") ||
-                                        text_lower.contains("hi ") || // "hi " to avoid matching "this"
-                                        text_lower.contains("intro") ||
-                                        text.ends_with("?") ||
-                                        text_lower.starts_with("!bot");
-
-                        if is_trigger {
-                            // Check Rate Limit
-                            if last_ai_reply.elapsed() >= std::time::Duration::from_secs(1) {
-                                last_ai_reply = std::time::Instant::now();
-
-                                let prompt = if text_lower.starts_with("!bot") {
-                                    text.trim_start_matches("!bot ").trim()
-                                } else {
-                                    text.trim()
-                                };
-
-                                if !prompt.is_empty() {
-                                    let config_clone = app.config.clone();
-                                    // Format prompt with username for context
-                                    let user_clone = user.clone();
-                                    let prompt_string = format!("User {}: {}", user_clone, prompt);
-
-                                    tokio::spawn(async move {
-                                        match ask_ai(&prompt_string, &config_clone).await {
-                                            Ok(reply) => {
-                                                let full_reply = format!("@{} {}", user_clone, reply);
-                                                if let Err(e) = send_chat_message(&full_reply, &config_clone).await {
-                                                     // log
-                                                }
-                                            }
-                                            Err(_) => {}
-                                        }
-                                    });
-                                }
-                            }
-                        }
-                   }
-                    AppEvent::UserJoined(user) => {
-                        app.messages.push(format!("-> {} joined", user));
-                        audio::play_sound("assets/sounds/join.mp3".to_string());
-
-                        // TTS: Announce the join (runs in bot thread, always plays)
-                        let join_msg = format!("{} has joined the chat!", user);
-                        std::thread::spawn(move || {
-                            let _ = std::process::Command::new("espeak").arg(&join_msg).spawn();
-                        });
-                        // Generate AI Greeting
-                        let config_clone = app.config.clone();
-                        let user_clone = user.clone();
-                        tokio::spawn(async move {
-                            let prompt = format!("User {} just joined. Welcome them excitedly with a single short sentence. Do not ask any questions.", user_clone);
-                             match ask_ai(&prompt, &config_clone).await {
-                                Ok(reply) => {
-                                    let full_reply = format!("@{} {}", user_clone, reply);
-                                    if let Err(_e) = send_chat_message(&full_reply, &config_clone).await {
-                                         // log
-                                    }
-                                }
-                                Err(_) => {}
-                            }
-                        });
-                    }
-                    AppEvent::UserLeft(user) => {
-                        app.messages.push(format!("<- {} left", user));
-                    }
-                    AppEvent::EmoteImage(name, dyn_img) => {
-                        // Create Protocol
-                        if let Some(picker) = &mut app.picker {
-                             if let Ok(protocol) = picker.new_protocol(dyn_img.clone(), ratatui::layout::Rect::new(0,0,3,2), ratatui_image::Resize::Fit(None)) {
-                                 // Store Name, Source Image, and Protocol
-                                 app.emote_images.push((name, dyn_img, protocol));
-                             }
-                        }
-                    }
-                    AppEvent::Error(msg) => {
-                        app.messages.push(format!("Error: {}", msg));
-                    }
-                    AppEvent::Info(msg) => {
-                        app.messages.push(format!("Info: {}", msg));
-                    }
-               }
-           }
-           Some(Ok(event)) = event_stream.next() => {
-               match event {
-                    Event::Key(key) => {
-                       should_render = true;
-                       if key.kind == event::KeyEventKind::Press {
-                           match key.code {
-                               KeyCode::Esc => {
-                                   app.exit = true;
-                               }
-                               KeyCode::F(1) => {
-                                   let _ = tx.send(AppEvent::UserJoined("TestUser".to_string()));
-                                   app.messages.push("Debug: Simulated User Join".to_string());
-                               }
-                               KeyCode::Char('c') | KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                   app.exit = true;
-                               }
-                               // Cycle Protocol: Ctrl+P
-                               KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                   if let Some(current_picker) = &app.picker {
-                                       let current_proto = current_picker.protocol_type;
-                                       let next_proto = match current_proto {
-                                           ratatui_image::picker::ProtocolType::Halfblocks => ratatui_image::picker::ProtocolType::Sixel,
-                                           ratatui_image::picker::ProtocolType::Sixel => ratatui_image::picker::ProtocolType::Kitty,
-                                           ratatui_image::picker::ProtocolType::Kitty => ratatui_image::picker::ProtocolType::Iterm2,
-                                           ratatui_image::picker::ProtocolType::Iterm2 => ratatui_image::picker::ProtocolType::Halfblocks,
-                                       };
-
-                                       // Create new picker
-                                       let mut new_picker = ratatui_image::picker::Picker::new((8, 12));
-                                       new_picker.protocol_type = next_proto;
-                                       app.protocol_name = format!("{:?}", next_proto);
-                                       app.picker = Some(new_picker.clone());
-
-                                       // Regenerate all protocols
-                                       let mut new_list = Vec::new();
-                                       for (name, dyn_img, _) in &app.emote_images {
-                                           if let Ok(protocol) = new_picker.new_protocol(dyn_img.clone(), ratatui::layout::Rect::new(0,0,3,2), ratatui_image::Resize::Fit(None)) {
-                                               new_list.push((name.clone(), dyn_img.clone(), protocol));
-                                           }
-                                       }
-                                       app.emote_images = new_list;
-                                   }
-                               }
-                               KeyCode::Enter => {
-                                   let text: String = app.input.value().into();
-                                   if !text.trim().is_empty() {
-                                       let config_clone = app.config.clone();
-                                       let text_clone = text.clone();
-                                       app.messages.push(format!("Me: {}", text));
-                                       app.input.reset();
-
-                                       tokio::spawn(async move {
-                                          if let Err(_e) = send_chat_message(&text_clone, &config_clone).await {
-                                              // log error somehow? -> channel
-                                          }
-                                       });
-                                   }
-                               }
-                               _ => {
-                                   app.input.handle_event(&Event::Key(key));
-                               }
-                           }
-                       }
-                    }
-                    Event::Mouse(mouse) => {
-                       match mouse.kind {
-                            event::MouseEventKind::Down(_) | event::MouseEventKind::Up(_) | event::MouseEventKind::Drag(_) | event::MouseEventKind::ScrollDown | event::MouseEventKind::ScrollUp => {
-                                should_render = true;
-                            }
-                            _ => {
-                                // Ignore Move events for rendering
-                                should_render = false;
-                            }
-                       }
-
-                       if mouse.kind == event::MouseEventKind::Down(event::MouseButton::Left) {
-                            // Check if mouse is within Emoji Chunk using Stored Area
-                            let area = app.emote_area;
-                            if mouse.column >= area.x && mouse.column < area.x + area.width &&
-                               mouse.row >= area.y && mouse.row < area.y + area.height
-                            {
-                                match mouse.kind {
-                                    event::MouseEventKind::ScrollDown => {
-                                        should_render = true; // Ensure render
-
-                                        // Calculate max scroll
-                                        let img_width = 3 + 1;
-                                        // let img_height = 2; // Unused
-                                        // Use area.width (which is the Block outer width, or inner? ui.rs stored chunks[1] = outer)
-                                        // Wait, ui.rs stored "chunks[1]". That is the OUTER rect.
-                                        // So input area needs to account for borders.
-                                        let inner_width = area.width.saturating_sub(2) as usize;
-                                        let inner_height = area.height.saturating_sub(2) as usize;
-                                        let img_height = 2;
-
-                                        let items_per_row = (inner_width / img_width).max(1);
-                                        let total_rows = (app.emote_images.len() + items_per_row - 1) / items_per_row;
-                                        let visible_rows = inner_height / img_height;
-
-                                        let max_scroll = total_rows.saturating_sub(visible_rows);
-
-                                        if app.emote_scroll < max_scroll {
-                                            app.emote_scroll += 1;
-                                        }
-                                    }
-                                    event::MouseEventKind::ScrollUp => {
-                                        should_render = true;
-                                        app.emote_scroll = app.emote_scroll.saturating_sub(1);
-                                    }
-                                    event::MouseEventKind::Down(event::MouseButton::Left) => {
-                                        should_render = true;
-
-                                        // Scrollbar Interaction Logic
-                                        if mouse.column == area.x + area.width - 1 {
-                                            // Clicked on the Scrollbar/Right Border
-                                            if mouse.row == area.y {
-                                                // Top Arrow
-                                                app.emote_scroll = app.emote_scroll.saturating_sub(1);
-                                            } else if mouse.row == area.y + area.height - 1 {
-                                                // Bottom Arrow
-                                                // Re-calculate max scroll to be safe
-                                                let img_width = 3 + 1;
-                                                let inner_width = area.width.saturating_sub(2) as usize;
-                                                let inner_height = area.height.saturating_sub(2) as usize;
-                                                let img_height = 2; // Fixed height for Sixels
-
-                                                let items_per_row = (inner_width / img_width).max(1);
-                                                let total_rows = (app.emote_images.len() + items_per_row - 1) / items_per_row;
-                                                let visible_rows = inner_height / img_height;
-                                                let max_scroll = total_rows.saturating_sub(visible_rows);
-
-                                                if app.emote_scroll < max_scroll {
-                                                    app.emote_scroll += 1;
-                                                }
-                                            } else {
-                                                // Clicked middle of scrollbar?
-                                                // Maybe implement Page Up/Down later.
-                                                // For now, do nothing, just preventing Emote Click.
-                                            }
-                                            // RETURN EARLY to prevent falling into grid logic
-                                            continue;
-                                        }
-
-
-                                        // Image Grid Logic (Unified for Text too for now, or split?)
-                                        if !app.emote_images.is_empty() {
-                                            let rel_x = mouse.column.saturating_sub(area.x);
-                                            let rel_y = mouse.row.saturating_sub(area.y);
-                                            // Debug
-                                            // app.messages.push(format!("Click in Emote Area! Rel: {},{}", rel_x, rel_y));
-
-                                            let img_width = 3 + 1; // 3 width + 1 spacing
-                                            let img_height = 2; // 2 height
-
-                                            let grid_row = rel_y as usize / img_height as usize;
-                                            let grid_col = rel_x as usize / img_width as usize;
-
-                                            let items_per_row = (area.width as usize / img_width as usize).max(1);
-
-                                            let absolute_row = grid_row + app.emote_scroll;
-                                            let index = absolute_row * items_per_row + grid_col;
-
-                                            if index < app.emote_images.len() {
-                                                let (name, _, _) = &app.emote_images[index];
-                                                // app.messages.push(format!("Selected: {}", name));
-                                                let new_val = format!("{}{}{} ", app.input.value(), if app.input.value().is_empty() { "" } else { " " }, name);
-                                                app.input = app.input.with_value(new_val);
-                                            }
-                                        } else {
-                                           // Text Mode Logic using Area
-                                            let rel_x = mouse.column.saturating_sub(area.x);
-                                            let rel_y = mouse.row.saturating_sub(area.y);
-
-                                            // Reuse old text logic but with simple relative coords
-                                            let width = area.width as usize;
-                                            let click_x = rel_x as usize;
-                                            let click_y = rel_y as usize;
-
-                                            use choui_the_no_gui_chatbot::state::EMOJIS;
-                                            let mut current_x = 0;
-                                            let mut current_y = 0;
-
-                                            for emoji in EMOJIS {
-                                                 let emoji_len = emoji.chars().count();
-                                                 let item_width = emoji_len + 2;
-
-                                                 if current_x + item_width > width {
-                                                     current_x = 0;
-                                                     current_y += 1;
-                                                 }
-
-                                                 if current_y == click_y && click_x >= current_x && click_x < current_x + emoji_len {
-                                                     let new_val = format!("{}{}", app.input.value(), emoji);
-                                                     app.input = app.input.with_value(new_val);
-                                                     break;
-                                                 }
-                                                 current_x += item_width;
-                                            }
-                                        }
-                                    }
-                                   _ => {}
-                               }
-                           }
-                       }
-                    }
-                    Event::Resize(_, _) => {
-                         should_render = true;
-                         let _ = terminal.autoresize(); // Ensure backend knows about resize logic if needed
+            _ = tokio::time::sleep_until(last_draw + frame_interval), if should_render => {}
+            _ = ai_ticker.tick() => {
+                should_render |= session.tick();
+            }
+            Some(call) = api_rx.recv() => {
+                should_render = true;
+                session.handle_api(call);
+            }
+            Some(event) = rx.recv() => {
+                let sent = bench_run.as_ref().and_then(|run| run.sent_at(&event));
+                should_render |= session.handle_event(event);
+                if let (Some(run), Some(sent)) = (bench_run.as_mut(), sent) {
+                    run.latencies.record(sent);
+                }
+            }
+            sent = bench::finished(&mut bench_done) => {
+                bench_result = Some(sent);
+                session.app.exit = true;
+            }
+            _ = tokio::signal::ctrl_c(), if !tui => {
+                session.app.exit = true;
+            }
+            Some(Ok(event)) = next_terminal_event(&mut event_stream) => {
+                if let Event::Resize(_, _) = event {
+                    should_render = true;
+                    if let Some(terminal) = terminal.as_mut() {
+                        let _ = terminal.autoresize();
                     }
-                    _ => {}
+                } else {
+                    should_render |= session.handle_terminal_event(event);
                 }
             }
         }
 
-        if app.exit {
+        if session.app.exit {
             break;
         }
     }
 
     // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    if let Some(mut terminal) = terminal.filter(|_| tui) {
+        disable_raw_mode()?;
+        execute!(
+            terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableFocusChange
+        )?;
+        terminal.show_cursor()?;
+    }
+
+    println!("Shutting down...");
+    session.stop();
+    bot.shutdown(session.app.config.offline_message.as_deref())
+        .await;
+    if !session.close(bench_run.is_none()) {
+        eprintln!("Some database writes may not have been saved");
+    }
+    if let (Some(run), Some(sent)) = (bench_run, bench_result) {
+        run.finish(sent)?;
+    }
 
     Ok(())
 }
+
+// Next key/mouse event, or never when running without the TUI
+async fn next_terminal_event(
+    stream: &mut Option<crossterm::event::EventStream>,
+) -> Option<std::io::Result<Event>> {
+    match stream {
+        Some(stream) => stream.next().await,
+        None => std::future::pending().await,
+    }
+}
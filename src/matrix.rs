@@ -0,0 +1,355 @@
+use crate::api::{AiAction, ApiCall, ApiRequest};
+use crate::commands::{SlashCommand, TtsControl};
+use crate::config::{env_flag, var};
+use crate::state::AppEvent;
+use anyhow::{bail, Context, Result};
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+// A Matrix bridge for self-hosters: chat and alerts are mirrored into a room
+// as notices, and the accounts in MATRIX_ADMINS can drive the bot from there
+// with the same requests as the HTTP API (see api.rs), e.g. "!say hi" or
+// "!tts skip"; "!help" lists them. It talks to the homeserver's client-server
+// API as whatever account the MATRIX_TOKEN belongs to, which must have joined
+// MATRIX_ROOM.
+
+// How long a sync waits on the homeserver for something new
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+// Before trying again after the homeserver couldn't be reached
+const RETRY: Duration = Duration::from_secs(10);
+// Chat is collected this long and mirrored as one notice
+const BATCH: Duration = Duration::from_secs(2);
+
+const HELP: &str = "!status, !say <message>, !tts on|off|toggle|skip|clear, \
+                    !persona [name], !timeout <user> [seconds] [reason], !ban <user> [reason], \
+                    !approve|!reject|!retry <id>, !timers on|off, !approval on|off";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixSettings {
+    pub enabled: bool,
+    // e.g. https://matrix.example.org
+    pub homeserver: String,
+    pub token: String,
+    // The room's id, "!abc:example.org"
+    pub room: String,
+    // Matrix ids allowed to give commands, "@me:example.org"
+    pub admins: Vec<String>,
+}
+
+impl MatrixSettings {
+    // MATRIX_ENABLED, MATRIX_HOMESERVER, MATRIX_TOKEN, MATRIX_ROOM, MATRIX_ADMINS
+    pub fn from_env() -> Result<Self> {
+        let enabled = env_flag("MATRIX_ENABLED", false)?;
+        let setting = |name| var(name).unwrap_or_default().trim().to_string();
+        let settings = Self {
+            enabled,
+            homeserver: setting("MATRIX_HOMESERVER")
+                .trim_end_matches('/')
+                .to_string(),
+            token: setting("MATRIX_TOKEN"),
+            room: setting("MATRIX_ROOM"),
+            admins: setting("MATRIX_ADMINS")
+                .split(',')
+                .map(str::trim)
+                .filter(|admin| !admin.is_empty())
+                .map(String::from)
+                .collect(),
+        };
+        if enabled {
+            for (name, value) in [
+                ("MATRIX_HOMESERVER", &settings.homeserver),
+                ("MATRIX_TOKEN", &settings.token),
+                ("MATRIX_ROOM", &settings.room),
+            ] {
+                if value.is_empty() {
+                    bail!("MATRIX_ENABLED needs {} too", name);
+                }
+            }
+            Url::parse(&settings.homeserver).context("MATRIX_HOMESERVER isn't a URL")?;
+            if !settings.room.starts_with('!') {
+                bail!(
+                    "MATRIX_ROOM must be the room's id (\"!...:server\"), got '{}'",
+                    settings.room
+                );
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Mirror `events` into the room and pass admins' commands on as `calls`,
+/// until the event stream ends or the token is refused.
+pub async fn run(
+    settings: MatrixSettings,
+    client: Client,
+    tx: mpsc::UnboundedSender<AppEvent>,
+    events: broadcast::Receiver<AppEvent>,
+    calls: mpsc::UnboundedSender<ApiCall>,
+) -> Result<()> {
+    let room = Room {
+        settings,
+        client,
+        txn: Arc::new(AtomicU64::new(0)),
+    };
+    let _ = tx.send(AppEvent::Info(format!(
+        "Matrix: mirroring into {}",
+        room.settings.room
+    )));
+    tokio::select! {
+        result = room.clone().mirror(events, tx.clone()) => result,
+        result = room.listen(tx, calls) => result,
+    }
+}
+
+#[derive(Clone)]
+struct Room {
+    settings: MatrixSettings,
+    client: Client,
+    // Transaction ids, unique for this session's messages
+    txn: Arc<AtomicU64>,
+}
+
+impl Room {
+    fn url(&self, segments: &[&str]) -> Result<Url> {
+        let mut url = Url::parse(&self.settings.homeserver)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("MATRIX_HOMESERVER isn't a web address"))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn send(&self, body: &str) -> Result<()> {
+        let txn = self.txn.fetch_add(1, Ordering::Relaxed);
+        let txn = format!("choui-{}-{}", std::process::id(), txn);
+        let url = self.url(&["rooms", &self.settings.room, "send", "m.room.message", &txn])?;
+        let resp = self
+            .client
+            .put(url)
+            .bearer_auth(&self.settings.token)
+            .json(&json!({ "msgtype": "m.notice", "body": body }))
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            bail!("Sending to Matrix failed ({}): {}", status, text);
+        }
+        Ok(())
+    }
+
+    // Chat and alerts, a batch at a time
+    async fn mirror(
+        self,
+        mut events: broadcast::Receiver<AppEvent>,
+        tx: mpsc::UnboundedSender<AppEvent>,
+    ) -> Result<()> {
+        loop {
+            let mut lines = Vec::new();
+            match events.recv().await {
+                Ok(event) => lines.extend(describe(&event)),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            }
+            if lines.is_empty() {
+                continue;
+            }
+            tokio::time::sleep(BATCH).await;
+            loop {
+                match events.try_recv() {
+                    Ok(event) => lines.extend(describe(&event)),
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                    Err(_) => break,
+                }
+            }
+            if let Err(e) = self.send(&lines.join("\n")).await {
+                let _ = tx.send(AppEvent::Debug(format!("Matrix: {:#}", e)));
+            }
+        }
+    }
+
+    // Long-polls /sync for commands in the room. The first sync only says
+    // where to start, so commands from before the bot started aren't run.
+    async fn listen(
+        self,
+        tx: mpsc::UnboundedSender<AppEvent>,
+        calls: mpsc::UnboundedSender<ApiCall>,
+    ) -> Result<()> {
+        let mut since: Option<String> = None;
+        loop {
+            let mut url = self.url(&["sync"])?;
+            match &since {
+                Some(since) => {
+                    url.query_pairs_mut()
+                        .append_pair("since", since)
+                        .append_pair("timeout", &SYNC_TIMEOUT.as_millis().to_string());
+                }
+                None => {
+                    url.query_pairs_mut()
+                        .append_pair("filter", r#"{"room":{"timeline":{"limit":0}}}"#);
+                }
+            }
+            let resp = self
+                .client
+                .get(url)
+                .bearer_auth(&self.settings.token)
+                .timeout(SYNC_TIMEOUT + Duration::from_secs(10))
+                .send()
+                .await;
+            let body: Value = match resp {
+                Ok(resp) if resp.status() == reqwest::StatusCode::UNAUTHORIZED => {
+                    bail!("Matrix refused MATRIX_TOKEN")
+                }
+                Ok(resp) if resp.status().is_success() => resp.json().await?,
+                Ok(resp) => {
+                    let _ = tx.send(AppEvent::Debug(format!(
+                        "Matrix sync failed: {}",
+                        resp.status()
+                    )));
+                    tokio::time::sleep(RETRY).await;
+                    continue;
+                }
+                Err(e) => {
+                    let _ = tx.send(AppEvent::Debug(format!("Matrix sync failed: {}", e)));
+                    tokio::time::sleep(RETRY).await;
+                    continue;
+                }
+            };
+            let first = since.is_none();
+            since = body["next_batch"].as_str().map(String::from);
+            if first {
+                continue;
+            }
+            let timeline = &body["rooms"]["join"][&self.settings.room]["timeline"]["events"];
+            for event in timeline.as_array().into_iter().flatten() {
+                let (Some(sender), Some(text)) =
+                    (event["sender"].as_str(), event["content"]["body"].as_str())
+                else {
+                    continue;
+                };
+                if event["type"] != "m.room.message"
+                    || event["content"]["msgtype"] != "m.text"
+                    || !self.settings.admins.iter().any(|admin| admin == sender)
+                {
+                    continue;
+                }
+                let answer = match parse(text) {
+                    Some(Ok(request)) => ask(&calls, request).await,
+                    Some(Err(e)) => format!("{:#}", e),
+                    None => continue,
+                };
+                if let Err(e) = self.send(&answer).await {
+                    let _ = tx.send(AppEvent::Debug(format!("Matrix: {:#}", e)));
+                }
+            }
+        }
+    }
+}
+
+// A line for the room, for the events that are mirrored
+fn describe(event: &AppEvent) -> Option<String> {
+    match event {
+        AppEvent::ChatMessage(message) => {
+            Some(format!("<{}> {}", message.display_name, message.text))
+        }
+        AppEvent::Alert(alert) => Some(format!("** {}", alert.describe())),
+        _ => None,
+    }
+}
+
+async fn ask(calls: &mpsc::UnboundedSender<ApiCall>, request: ApiRequest) -> String {
+    let (reply, answer) = oneshot::channel();
+    if calls.send(ApiCall { request, reply }).is_err() {
+        return "The bot is shutting down".to_string();
+    }
+    match answer.await {
+        Ok(Ok(body)) if body == json!({ "ok": true }) => "Done".to_string(),
+        Ok(Ok(body)) => body.to_string(),
+        Ok(Err(e)) => e,
+        Err(_) => "The bot is shutting down".to_string(),
+    }
+}
+
+/// An admin's "!command", as the API request it stands for. None if the
+/// message isn't a command; "!help" comes back as an error listing them.
+pub fn parse(text: &str) -> Option<Result<ApiRequest>> {
+    let text = text.trim().strip_prefix('!')?;
+    let (command, rest) = text.split_once(' ').unwrap_or((text, ""));
+    let (command, rest) = (command.to_lowercase(), rest.trim());
+    let mut words = rest.split_whitespace();
+    let on_off = |value: &str| match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        other => bail!("!{} takes on or off, got '{}'", command, other),
+    };
+    let request = || -> Result<ApiRequest> {
+        Ok(match command.as_str() {
+            "status" => ApiRequest::Status,
+            "say" if rest.is_empty() => bail!("!say what?"),
+            "say" => ApiRequest::Say(rest.to_string()),
+            "tts" => ApiRequest::Tts(match rest {
+                "on" => TtsControl::On,
+                "off" => TtsControl::Off,
+                "toggle" => TtsControl::Toggle,
+                "skip" => TtsControl::Skip,
+                "clear" => TtsControl::Clear,
+                other => bail!("!tts takes on, off, toggle, skip or clear, got '{}'", other),
+            }),
+            "persona" => {
+                ApiRequest::Persona(Some(rest.to_string()).filter(|name| !name.is_empty()))
+            }
+            "timeout" | "ban" => {
+                let user = match words.next() {
+                    Some(user) => user.trim_start_matches('@').to_string(),
+                    None => bail!("!{} who?", command),
+                };
+                if command == "ban" {
+                    let reason = words.collect::<Vec<_>>().join(" ");
+                    ApiRequest::Moderate(SlashCommand::Ban { user, reason })
+                } else {
+                    let mut words = words.peekable();
+                    let seconds = match words.peek().and_then(|w| w.parse::<u32>().ok()) {
+                        Some(seconds) => {
+                            words.next();
+                            seconds.clamp(1, 1_209_600)
+                        }
+                        None => 600,
+                    };
+                    let reason = words.collect::<Vec<_>>().join(" ");
+                    ApiRequest::Moderate(SlashCommand::Timeout {
+                        user,
+                        seconds,
+                        reason,
+                    })
+                }
+            }
+            "approve" | "reject" | "retry" => ApiRequest::Ai {
+                id: rest
+                    .trim_start_matches('#')
+                    .parse()
+                    .with_context(|| format!("!{} takes the AI request's number", command))?,
+                action: match command.as_str() {
+                    "approve" => AiAction::Approve,
+                    "reject" => AiAction::Reject,
+                    _ => AiAction::Retry,
+                },
+            },
+            "timers" => ApiRequest::Settings {
+                timers: Some(on_off(rest)?),
+                ai_approval: None,
+            },
+            "approval" => ApiRequest::Settings {
+                timers: None,
+                ai_approval: Some(on_off(rest)?),
+            },
+            "help" => bail!("Commands: {}", HELP),
+            other => bail!("Unknown command !{}; try !help", other),
+        })
+    };
+    Some(request())
+}
@@ -0,0 +1,210 @@
+use crate::sqlite::{Store, Value, Writer};
+use crate::state::AppEvent;
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+
+// What CHOUIBOT remembers between replies: the last few exchanges with each
+// viewer and its own latest replies in chat, so it can follow up on a
+// conversation and doesn't tell the same joke twice in a row. Kept in the
+// chat database, so a restart mid-stream doesn't wipe it. Exchanges older
+// than AI_MEMORY_TTL are forgotten, and each viewer keeps at most
+// AI_MEMORY_TURNS of them (0 turns memory off).
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS ai_memory (
+    id INTEGER PRIMARY KEY,
+    user TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    reply TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS ai_memory_user ON ai_memory (user, id);
+";
+
+// The bot's own latest replies to other viewers, shown to the model
+const RECENT_REPLIES: usize = 5;
+// Exchanges kept overall, however many viewers there are
+const MAX_TURNS: usize = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turn {
+    pub user: String,
+    // As sent to the model: "User <name>: <message>"
+    pub prompt: String,
+    pub reply: String,
+    pub timestamp: String,
+}
+
+// Exchanges in memory, oldest first, mirrored to the database by a thread of
+// their own. Without a database (it couldn't be opened) it still remembers
+// until the bot quits.
+#[derive(Default)]
+pub struct Memory {
+    turns: Vec<Turn>,
+    writes: Option<Writer>,
+    max_turns: usize,
+    ttl: Duration,
+}
+
+impl Memory {
+    pub fn new(max_turns: usize, ttl: Duration) -> Self {
+        Self {
+            max_turns,
+            ttl,
+            ..Self::default()
+        }
+    }
+
+    pub fn open(
+        path: &Path,
+        max_turns: usize,
+        ttl: Duration,
+        events: tokio::sync::mpsc::UnboundedSender<AppEvent>,
+    ) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?;
+        let mut memory = Self::new(max_turns, ttl);
+        memory.turns = db
+            .query(
+                "SELECT user, prompt, reply, timestamp FROM ai_memory ORDER BY id",
+                &[],
+            )?
+            .iter()
+            .filter_map(|row| {
+                Some(Turn {
+                    user: row.first()?.as_str()?.to_string(),
+                    prompt: row.get(1)?.as_str()?.to_string(),
+                    reply: row.get(2)?.as_str()?.to_string(),
+                    timestamp: row.get(3)?.as_str()?.to_string(),
+                })
+            })
+            .collect();
+
+        let writes = db.writer("the AI's memory", events);
+        memory.writes = Some(writes);
+        memory.prune();
+        Ok(memory)
+    }
+
+    fn write(&self, sql: &'static str, params: Vec<Value>) {
+        if let Some(writes) = &self.writes {
+            writes.write(sql, params);
+        }
+    }
+
+    // Oldest kept timestamp; they're UTC text that sorts in time order
+    fn cutoff(&self) -> String {
+        let ttl = jiff::SignedDuration::try_from(self.ttl).unwrap_or(jiff::SignedDuration::MAX);
+        jiff::Timestamp::now()
+            .checked_sub(ttl)
+            .unwrap_or(jiff::Timestamp::MIN)
+            .strftime("%Y-%m-%dT%H:%M:%SZ")
+            .to_string()
+    }
+
+    // Forget what's too old, too much for one viewer or too much overall
+    fn prune(&mut self) {
+        let cutoff = self.cutoff();
+        self.turns.retain(|turn| turn.timestamp >= cutoff);
+        let mut per_user = std::collections::HashMap::<&str, usize>::new();
+        let mut keep: Vec<bool> = self
+            .turns
+            .iter()
+            .rev()
+            .map(|turn| {
+                let count = per_user.entry(turn.user.as_str()).or_default();
+                *count += 1;
+                *count <= self.max_turns
+            })
+            .collect();
+        keep.reverse();
+        let mut keep = keep.into_iter();
+        self.turns.retain(|_| keep.next().unwrap_or(false));
+        let excess = self.turns.len().saturating_sub(MAX_TURNS);
+        self.turns.drain(..excess);
+
+        self.write(
+            "DELETE FROM ai_memory WHERE timestamp < ?",
+            vec![cutoff.into()],
+        );
+        self.write(
+            "DELETE FROM ai_memory WHERE id IN (
+                 SELECT id FROM ai_memory AS t WHERE (
+                     SELECT COUNT(*) FROM ai_memory AS newer
+                     WHERE newer.user = t.user AND newer.id > t.id
+                 ) >= ?
+             )",
+            vec![(self.max_turns as i64).into()],
+        );
+        self.write(
+            "DELETE FROM ai_memory WHERE id NOT IN (
+                 SELECT id FROM ai_memory ORDER BY id DESC LIMIT ?
+             )",
+            vec![(MAX_TURNS as i64).into()],
+        );
+    }
+
+    /// Remember a reply that went out to chat.
+    pub fn remember(&mut self, user: &str, prompt: &str, reply: &str) {
+        if self.max_turns == 0 {
+            return;
+        }
+        let turn = Turn {
+            user: user.to_lowercase(),
+            prompt: prompt.to_string(),
+            reply: reply.to_string(),
+            timestamp: crate::chatlog::timestamp(),
+        };
+        self.write(
+            "INSERT INTO ai_memory (user, prompt, reply, timestamp) VALUES (?, ?, ?, ?)",
+            vec![
+                turn.user.as_str().into(),
+                turn.prompt.as_str().into(),
+                turn.reply.as_str().into(),
+                turn.timestamp.as_str().into(),
+            ],
+        );
+        self.turns.push(turn);
+        self.prune();
+    }
+
+    /// `prompt` with what the bot remembers put in front of it.
+    pub fn with_context(&self, user: &str, prompt: &str) -> String {
+        if self.max_turns == 0 {
+            return prompt.to_string();
+        }
+        let cutoff = self.cutoff();
+        let live = || self.turns.iter().filter(|turn| turn.timestamp >= cutoff);
+        let mut context = String::new();
+
+        let with_user: Vec<&Turn> = live()
+            .filter(|turn| turn.user.eq_ignore_ascii_case(user))
+            .collect();
+        if !with_user.is_empty() {
+            context.push_str(&format!("Your earlier conversation with {}:\n", user));
+            for turn in with_user {
+                context.push_str(&format!("{}\nYou: {}\n", turn.prompt, turn.reply));
+            }
+            context.push('\n');
+        }
+
+        let recent: Vec<&Turn> = live()
+            .rev()
+            .filter(|turn| !turn.user.eq_ignore_ascii_case(user))
+            .take(RECENT_REPLIES)
+            .collect();
+        if !recent.is_empty() {
+            context.push_str("Your latest replies to others in chat (don't repeat yourself):\n");
+            for turn in recent.into_iter().rev() {
+                context.push_str(&format!("- to {}: {}\n", turn.user, turn.reply));
+            }
+            context.push('\n');
+        }
+
+        if context.is_empty() {
+            prompt.to_string()
+        } else {
+            format!("{}Now:\n{}", context, prompt)
+        }
+    }
+}
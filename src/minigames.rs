@@ -0,0 +1,419 @@
+use crate::ai;
+use crate::config::{env_flag, var, Config};
+use crate::sqlite::{Store, Writer};
+use crate::state::AppEvent;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+// Games viewers bet points on. Points are earned by chatting
+// (GAMES_POINTS_PER_MESSAGE, at most once a minute) and kept in the chat
+// database.
+//
+//   !points [@user]      a balance
+//   !gamble <n|all>      double or nothing
+//   !duel @user <n>      challenge someone, who has a minute to !accept or !deny
+//   !8ball <question>    the magic 8-ball, free
+//
+// Each viewer plays at most once per GAMES_COOLDOWN; mods skip it. With
+// GAMES_AI on the AI adds a line of flavor to the outcome.
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS points (
+    user TEXT PRIMARY KEY,
+    balance INTEGER NOT NULL,
+    timestamp TEXT NOT NULL
+);
+";
+
+// Chatting earns points at most this often
+const EARN_INTERVAL: Duration = Duration::from_secs(60);
+// How long a challenge waits for !accept
+const DUEL_TIMEOUT: Duration = Duration::from_secs(60);
+
+const EIGHT_BALL: &[&str] = &[
+    "It is certain",
+    "It is decidedly so",
+    "Without a doubt",
+    "Yes, definitely",
+    "You may rely on it",
+    "As I see it, yes",
+    "Most likely",
+    "Outlook good",
+    "Yes",
+    "Signs point to yes",
+    "Reply hazy, try again",
+    "Ask again later",
+    "Better not tell you now",
+    "Cannot predict now",
+    "Concentrate and ask again",
+    "Don't count on it",
+    "My reply is no",
+    "My sources say no",
+    "Outlook not so good",
+    "Very doubtful",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSettings {
+    // The game commands; off leaves them to other bots
+    pub enabled: bool,
+    pub points_per_message: i64,
+    // What a viewer has before earning any
+    pub starting_points: i64,
+    pub cooldown: Duration,
+    // Let the AI add flavor to the outcomes
+    pub ai: bool,
+}
+
+impl GameSettings {
+    // GAMES_ENABLED, GAMES_POINTS_PER_MESSAGE, GAMES_STARTING_POINTS,
+    // GAMES_COOLDOWN, GAMES_AI
+    pub fn from_env() -> Result<Self> {
+        let number = |name: &str, default: u64| -> Result<u64> {
+            match var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse::<u64>()
+                    .with_context(|| format!("{} must be a number, got '{}'", name, value)),
+                Err(_) => Ok(default),
+            }
+        };
+        Ok(Self {
+            enabled: env_flag("GAMES_ENABLED", false)?,
+            points_per_message: number("GAMES_POINTS_PER_MESSAGE", 10)? as i64,
+            starting_points: number("GAMES_STARTING_POINTS", 100)? as i64,
+            cooldown: Duration::from_secs(number("GAMES_COOLDOWN", 30)?),
+            ai: env_flag("GAMES_AI", true)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wager {
+    Points(i64),
+    All,
+}
+
+impl Wager {
+    fn parse(text: &str) -> Result<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "all" | "allin" => Ok(Wager::All),
+            amount => amount
+                .parse::<i64>()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(Wager::Points)
+                .with_context(|| format!("'{}' isn't a number of points to bet", text.trim())),
+        }
+    }
+
+    fn of(self, balance: i64) -> i64 {
+        match self {
+            Wager::Points(n) => n,
+            Wager::All => balance,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameCommand {
+    Points(Option<String>),
+    Gamble(Wager),
+    Duel { opponent: String, wager: Wager },
+    Accept,
+    Deny,
+    EightBall(String),
+}
+
+impl GameCommand {
+    /// Parse `!<name> <args>`. None when it isn't a game command.
+    pub fn parse(name: &str, args: &str) -> Option<Result<Self>> {
+        let args = args.trim();
+        let user = |word: &str| word.trim_start_matches('@').to_lowercase();
+        let command = match name {
+            "points" => Ok(GameCommand::Points(
+                args.split_whitespace().next().map(user),
+            )),
+            "gamble" => match args {
+                "" => Err(anyhow::anyhow!("Usage: !gamble <points|all>")),
+                args => Wager::parse(args).map(GameCommand::Gamble),
+            },
+            "duel" => match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [opponent, wager] => Wager::parse(wager).map(|wager| GameCommand::Duel {
+                    opponent: user(opponent),
+                    wager,
+                }),
+                _ => Err(anyhow::anyhow!("Usage: !duel @user <points|all>")),
+            },
+            "accept" => Ok(GameCommand::Accept),
+            "deny" => Ok(GameCommand::Deny),
+            "8ball" => match args {
+                "" => Err(anyhow::anyhow!("Usage: !8ball <question>")),
+                question => Ok(GameCommand::EightBall(question.to_string())),
+            },
+            _ => return None,
+        };
+        Some(command)
+    }
+
+    // Looking up points and answering a challenge aren't playing
+    fn cools_down(&self) -> bool {
+        matches!(
+            self,
+            GameCommand::Gamble(_) | GameCommand::Duel { .. } | GameCommand::EightBall(_)
+        )
+    }
+}
+
+/// What a game says in chat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    // The result, said as is
+    pub text: String,
+    // Asks the AI for a line of flavor to go with it
+    pub flavor: Option<String>,
+}
+
+impl Outcome {
+    fn plain(text: String) -> Self {
+        Self { text, flavor: None }
+    }
+
+    /// The result, with the AI's flavor after it if GAMES_AI is on and the AI
+    /// answers.
+    pub async fn announcement(&self, config: &Config) -> String {
+        let Some(prompt) = self.flavor.as_ref().filter(|_| config.games.ai) else {
+            return self.text.clone();
+        };
+        match ai::ask_ai(prompt, config).await {
+            Ok(flavor) if !flavor.trim().is_empty() => format!("{} {}", self.text, flavor.trim()),
+            _ => self.text.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Duel {
+    challenger: String,
+    opponent: String,
+    wager: i64,
+    at: Instant,
+}
+
+// Balances in memory like the counters, written back by a thread of their
+// own, with the games' cooldowns and open challenges
+pub struct Games {
+    balances: HashMap<String, i64>,
+    writes: Writer,
+    earned: HashMap<String, Instant>,
+    played: HashMap<String, Instant>,
+    duels: Vec<Duel>,
+}
+
+impl Games {
+    pub fn open(path: &Path, events: UnboundedSender<AppEvent>) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?;
+        let balances = db
+            .query("SELECT user, balance FROM points", &[])?
+            .iter()
+            .filter_map(|row| Some((row.first()?.as_str()?.to_string(), row.get(1)?.as_int()?)))
+            .collect();
+
+        let writes = db.writer("points", events);
+        Ok(Self {
+            balances,
+            writes,
+            earned: HashMap::new(),
+            played: HashMap::new(),
+            duels: Vec::new(),
+        })
+    }
+
+    pub fn balance(&self, settings: &GameSettings, user: &str) -> i64 {
+        self.balances
+            .get(&user.to_lowercase())
+            .copied()
+            .unwrap_or(settings.starting_points)
+    }
+
+    /// Give `user` points, or take them with a negative number; a balance
+    /// doesn't go below 0. Returns the new one.
+    pub fn add(&mut self, settings: &GameSettings, user: &str, points: i64) -> i64 {
+        let balance = self.balance(settings, user).saturating_add(points).max(0);
+        let user = user.to_lowercase();
+        self.writes.write(
+            "INSERT OR REPLACE INTO points (user, balance, timestamp) VALUES (?, ?, ?)",
+            vec![
+                user.as_str().into(),
+                balance.into(),
+                crate::chatlog::timestamp().into(),
+            ],
+        );
+        self.balances.insert(user, balance);
+        balance
+    }
+
+    /// Points for a chat message, once a minute at most.
+    pub fn earn(&mut self, settings: &GameSettings, user: &str) {
+        let user = user.to_lowercase();
+        if settings.points_per_message == 0
+            || self
+                .earned
+                .get(&user)
+                .is_some_and(|t| t.elapsed() < EARN_INTERVAL)
+        {
+            return;
+        }
+        self.earned.insert(user.clone(), Instant::now());
+        self.add(settings, &user, settings.points_per_message);
+    }
+
+    /// Play. The error is the reason it can't be played, for chat.
+    pub fn play(
+        &mut self,
+        settings: &GameSettings,
+        user: &str,
+        unlimited: bool,
+        command: GameCommand,
+    ) -> Result<Outcome> {
+        let user = user.to_lowercase();
+        self.duels.retain(|duel| duel.at.elapsed() < DUEL_TIMEOUT);
+        let cools_down = command.cools_down() && !unlimited;
+        if cools_down {
+            if let Some(left) = self
+                .played
+                .get(&user)
+                .map(|t| settings.cooldown.saturating_sub(t.elapsed()))
+                .filter(|left| !left.is_zero())
+            {
+                bail!("You can play again in {}s", left.as_secs().max(1));
+            }
+        }
+        let outcome = match command {
+            GameCommand::Points(other) => {
+                let whose = other.unwrap_or_else(|| user.clone());
+                Outcome::plain(format!(
+                    "{} has {} points",
+                    whose,
+                    self.balance(settings, &whose)
+                ))
+            }
+            GameCommand::Gamble(wager) => {
+                let wager = self.wager(settings, &user, wager)?;
+                let (balance, result) = if fastrand::bool() {
+                    (self.add(settings, &user, wager), "won")
+                } else {
+                    (self.add(settings, &user, -wager), "lost")
+                };
+                Outcome {
+                    text: format!(
+                        "@{} {} {} points and now has {}",
+                        user, result, wager, balance
+                    ),
+                    flavor: Some(format!(
+                        "A viewer named {} bet {} points on a coin flip and {} them. React to it \
+                         in one short, playful sentence.",
+                        user, wager, result
+                    )),
+                }
+            }
+            GameCommand::Duel { opponent, wager } => {
+                if opponent == user {
+                    bail!("You can't duel yourself");
+                }
+                if self
+                    .duels
+                    .iter()
+                    .any(|duel| duel.challenger == user || duel.opponent == opponent)
+                {
+                    bail!("There's already a challenge waiting for an answer");
+                }
+                let wager = self.wager(settings, &user, wager)?;
+                if self.balance(settings, &opponent) < wager {
+                    bail!("{} doesn't have {} points", opponent, wager);
+                }
+                self.duels.push(Duel {
+                    challenger: user.clone(),
+                    opponent: opponent.clone(),
+                    wager,
+                    at: Instant::now(),
+                });
+                Outcome::plain(format!(
+                    "@{} {} challenges you to a duel for {} points! !accept or !deny within {}s",
+                    opponent,
+                    user,
+                    wager,
+                    DUEL_TIMEOUT.as_secs()
+                ))
+            }
+            GameCommand::Accept | GameCommand::Deny => {
+                let i = self
+                    .duels
+                    .iter()
+                    .position(|duel| duel.opponent == user)
+                    .context("Nobody has challenged you")?;
+                let duel = self.duels.remove(i);
+                if command == GameCommand::Deny {
+                    return Ok(Outcome::plain(format!(
+                        "@{} {} turned down the duel",
+                        duel.challenger, user
+                    )));
+                }
+                // Either could have spent their points since
+                for who in [&duel.challenger, &duel.opponent] {
+                    if self.balance(settings, who) < duel.wager {
+                        bail!("{} doesn't have {} points anymore", who, duel.wager);
+                    }
+                }
+                let (winner, loser) = if fastrand::bool() {
+                    (duel.challenger, duel.opponent)
+                } else {
+                    (duel.opponent, duel.challenger)
+                };
+                self.add(settings, &loser, -duel.wager);
+                let balance = self.add(settings, &winner, duel.wager);
+                Outcome {
+                    text: format!(
+                        "{} beat {} in a duel and won {} points (now {})",
+                        winner, loser, duel.wager, balance
+                    ),
+                    flavor: Some(format!(
+                        "Viewers {} and {} fought a duel in chat and {} won. Describe the \
+                         winning move in one short, dramatic sentence.",
+                        winner, loser, winner
+                    )),
+                }
+            }
+            GameCommand::EightBall(question) => {
+                let answer = EIGHT_BALL[fastrand::usize(..EIGHT_BALL.len())];
+                Outcome {
+                    text: format!("@{} 🎱 {}", user, answer),
+                    flavor: Some(format!(
+                        "A viewer asked the magic 8-ball \"{}\" and it answered \"{}\". Add one \
+                         short, mystical sentence that doesn't change the answer.",
+                        question, answer
+                    )),
+                }
+            }
+        };
+        if cools_down {
+            self.played.insert(user, Instant::now());
+        }
+        Ok(outcome)
+    }
+
+    fn wager(&self, settings: &GameSettings, user: &str, wager: Wager) -> Result<i64> {
+        let balance = self.balance(settings, user);
+        let wager = wager.of(balance);
+        if wager == 0 {
+            bail!("You don't have any points yet, chat a bit to earn some");
+        }
+        if wager > balance {
+            bail!("You only have {} points", balance);
+        }
+        Ok(wager)
+    }
+}
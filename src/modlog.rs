@@ -0,0 +1,221 @@
+use crate::sqlite::{Store, Value, Writer};
+use crate::state::AppEvent;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Every moderation action seen in chat (bans, timeouts, deleted messages,
+// cleared chat, AutoMod holds) and every one the bot took itself, kept in the
+// chat database for settling disputes later. The Moderation tab starts with
+// the latest entries; `choui-the-no-gui-chatbot modlog` lists them.
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS modlog (
+    id INTEGER PRIMARY KEY,
+    channel TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    user TEXT,
+    moderator TEXT,
+    detail TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS modlog_user ON modlog (user, timestamp);
+";
+
+// Twitch echoes the bot's own bans and timeouts back over IRC; within this
+// long the echo isn't logged a second time
+const ECHO_WINDOW: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModKind {
+    Ban,
+    Timeout,
+    Delete,
+    Clear,
+    AutoModHold,
+}
+
+impl ModKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ModKind::Ban => "ban",
+            ModKind::Timeout => "timeout",
+            ModKind::Delete => "delete",
+            ModKind::Clear => "clear",
+            ModKind::AutoModHold => "automod",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        [
+            ModKind::Ban,
+            ModKind::Timeout,
+            ModKind::Delete,
+            ModKind::Clear,
+            ModKind::AutoModHold,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str() == text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModEvent {
+    pub kind: ModKind,
+    // Who it was done to; None for a cleared chat
+    pub user: Option<String>,
+    // Who did it: the bot's login for its own actions, None when Twitch
+    // doesn't say (IRC never does)
+    pub moderator: Option<String>,
+    // The reason, the timeout's length, the deleted or held message
+    pub detail: String,
+}
+
+impl ModEvent {
+    /// `timeout viewer by somemod: 600s`
+    pub fn describe(&self) -> String {
+        let mut line = self.kind.as_str().to_string();
+        if let Some(user) = &self.user {
+            line.push_str(&format!(" {}", user));
+        }
+        if let Some(moderator) = &self.moderator {
+            line.push_str(&format!(" by {}", moderator));
+        }
+        if !self.detail.is_empty() {
+            line.push_str(&format!(": {}", self.detail));
+        }
+        line
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub timestamp: String,
+    pub channel: String,
+    pub event: ModEvent,
+}
+
+impl Entry {
+    fn from_row(row: &[Value]) -> Option<Self> {
+        let text = |i: usize| row.get(i).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            timestamp: text(0)?,
+            channel: text(1)?,
+            event: ModEvent {
+                kind: ModKind::parse(row.get(2)?.as_str()?)?,
+                user: text(3),
+                moderator: text(4),
+                detail: text(5).unwrap_or_default(),
+            },
+        })
+    }
+
+    /// `2024-05-01 20:14  #channel  timeout viewer by somemod: 600s`
+    pub fn describe(&self) -> String {
+        format!(
+            "{}  #{}  {}",
+            self.timestamp
+                .get(..16)
+                .unwrap_or(&self.timestamp)
+                .replace('T', " "),
+            self.channel,
+            self.event.describe()
+        )
+    }
+}
+
+// A disabled log (the database couldn't be opened) takes events and drops
+// them, like ChatLog
+#[derive(Default)]
+pub struct ModLog {
+    writes: Option<Writer>,
+    channel: String,
+    // The bot's own recent actions, to recognize their echoes
+    own: Vec<(ModKind, String, Instant)>,
+    bot_login: String,
+}
+
+impl ModLog {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn open(
+        path: &Path,
+        channel: String,
+        bot_login: String,
+        events: tokio::sync::mpsc::UnboundedSender<AppEvent>,
+    ) -> Result<Self> {
+        let writes = Store::open(path, SCHEMA)?.writer("the mod log", events);
+        Ok(Self {
+            writes: Some(writes),
+            channel,
+            own: Vec::new(),
+            bot_login,
+        })
+    }
+
+    /// Log an action. Returns false for the echo of one the bot took itself,
+    /// which was logged already.
+    pub fn record(&mut self, event: &ModEvent) -> bool {
+        self.own.retain(|(_, _, at)| at.elapsed() < ECHO_WINDOW);
+        let user = event.user.clone().unwrap_or_default();
+        let by_bot = event
+            .moderator
+            .as_ref()
+            .is_some_and(|moderator| moderator.eq_ignore_ascii_case(&self.bot_login));
+        if by_bot {
+            self.own.push((event.kind, user, Instant::now()));
+        } else if event.moderator.is_none() {
+            if let Some(i) = self
+                .own
+                .iter()
+                .position(|(kind, own, _)| *kind == event.kind && own.eq_ignore_ascii_case(&user))
+            {
+                self.own.remove(i);
+                return false;
+            }
+        }
+        if let Some(writes) = &self.writes {
+            writes.write(
+                "INSERT INTO modlog (channel, kind, user, moderator, detail, timestamp)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                vec![
+                    self.channel.as_str().into(),
+                    event.kind.as_str().into(),
+                    event.user.as_deref().into(),
+                    event.moderator.as_deref().into(),
+                    event.detail.as_str().into(),
+                    crate::chatlog::timestamp().into(),
+                ],
+            );
+        }
+        true
+    }
+}
+
+/// The latest entries, oldest first, optionally only those about `user`.
+pub fn query(path: &Path, user: Option<&str>, limit: usize) -> Result<Vec<Entry>> {
+    if !path.exists() {
+        bail!("No database at {}", path.display());
+    }
+    let db = Store::open(path, SCHEMA)?;
+    let columns = "SELECT timestamp, channel, kind, user, moderator, detail FROM modlog";
+    let rows = match user {
+        Some(user) => db.query(
+            &format!(
+                "{} WHERE user = ? COLLATE NOCASE ORDER BY id DESC LIMIT ?",
+                columns
+            ),
+            &[user.into(), (limit as i64).into()],
+        ),
+        None => db.query(
+            &format!("{} ORDER BY id DESC LIMIT ?", columns),
+            &[(limit as i64).into()],
+        ),
+    }
+    .with_context(|| format!("Failed to read the mod log in {}", path.display()))?;
+    let mut entries: Vec<Entry> = rows.iter().filter_map(|row| Entry::from_row(row)).collect();
+    entries.reverse();
+    Ok(entries)
+}
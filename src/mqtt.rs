@@ -0,0 +1,252 @@
+use crate::config::{env_flag, var};
+use crate::state::{mentions, AppEvent, StreamAlert};
+use anyhow::{bail, Context, Result};
+use rumqttc::{
+    AsyncClient, ConnectReturnCode, ConnectionError, Event, EventLoop, MqttOptions, Outgoing, QoS,
+    Transport,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+
+// Stream events for home automation: the alerts (follows, raids, subs...),
+// chat that mentions the streamer or the bot, and the stream going live or
+// offline are published to an MQTT broker as JSON under MQTT_PREFIX, e.g.
+// "choui/raid" or "choui/online", for Home Assistant or anything else that
+// speaks MQTT to flash the lights with. Nothing is subscribed to; everything
+// goes out at QoS 0, through rumqttc.
+
+// Before connecting again after losing the broker
+const RETRY: Duration = Duration::from_secs(10);
+// The broker drops a client it hasn't heard from in 1.5 times this
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+// Publications waiting for the connection; more than that while the broker
+// is away are dropped, like events missed then
+const QUEUE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttSettings {
+    pub enabled: bool,
+    // host:port
+    pub broker: String,
+    // Connect over TLS, checking the broker against the system's certificates
+    pub tls: bool,
+    // Topics are "<prefix>/<event>"
+    pub prefix: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl MqttSettings {
+    // MQTT_ENABLED, MQTT_BROKER, MQTT_TLS, MQTT_PREFIX, MQTT_USERNAME,
+    // MQTT_PASSWORD
+    pub fn from_env() -> Result<Self> {
+        let enabled = env_flag("MQTT_ENABLED", false)?;
+        let setting = |name| {
+            var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let tls = env_flag("MQTT_TLS", false)?;
+        let mut broker = setting("MQTT_BROKER").unwrap_or_else(|| "localhost".to_string());
+        if !broker.contains(':') {
+            broker.push_str(if tls { ":8883" } else { ":1883" });
+        }
+        if enabled && host_port(&broker).is_none() {
+            bail!("MQTT_BROKER must be host:port, got '{}'", broker);
+        }
+        let prefix = setting("MQTT_PREFIX")
+            .unwrap_or_else(|| "choui".to_string())
+            .trim_matches('/')
+            .to_string();
+        if enabled && (prefix.is_empty() || prefix.contains(['+', '#'])) {
+            bail!(
+                "MQTT_PREFIX must be a topic without wildcards, got '{}'",
+                prefix
+            );
+        }
+        let (username, password) = (setting("MQTT_USERNAME"), setting("MQTT_PASSWORD"));
+        // MQTT 3.1.1 has no password without a username, brokers hang up on it
+        if enabled && username.is_none() && password.is_some() {
+            bail!("MQTT_PASSWORD is set without MQTT_USERNAME");
+        }
+        Ok(Self {
+            enabled,
+            broker,
+            tls,
+            prefix,
+            username,
+            password,
+        })
+    }
+
+    fn options(&self) -> Result<MqttOptions> {
+        let (host, port) = host_port(&self.broker)
+            .with_context(|| format!("MQTT_BROKER must be host:port, got '{}'", self.broker))?;
+        let id = format!("choui-{}", std::process::id());
+        let mut options = MqttOptions::new(id, host, port);
+        options.set_keep_alive(KEEP_ALIVE);
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.as_deref().unwrap_or_default());
+        }
+        if self.tls {
+            options.set_transport(Transport::tls_with_default_config());
+        }
+        Ok(options)
+    }
+}
+
+fn host_port(broker: &str) -> Option<(&str, u16)> {
+    let (host, port) = broker.rsplit_once(':')?;
+    Some((host, port.parse().ok()?)).filter(|(host, _)| !host.is_empty())
+}
+
+/// Which events get published, and as what.
+pub struct Topics {
+    prefix: String,
+    // The bot's login and the channel's name, for mentions
+    names: Vec<String>,
+    // None until the first stream poll
+    live: Option<bool>,
+}
+
+impl Topics {
+    pub fn new(prefix: &str, names: Vec<String>) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            names,
+            live: None,
+        }
+    }
+
+    /// The topic and payload to publish for `event`, if it's one of ours.
+    pub fn publication(&mut self, event: &AppEvent) -> Option<(String, Value)> {
+        let (event, payload) = match event {
+            AppEvent::Alert(alert) => (alert.kind().name(), alert_payload(alert)),
+            AppEvent::ChatMessage(message) => {
+                let names: Vec<&str> = self.names.iter().map(String::as_str).collect();
+                let own = names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&message.user));
+                if own || !mentions(&message.text, &names) {
+                    return None;
+                }
+                let payload = json!({ "user": message.display_name, "text": message.text });
+                ("mention", payload)
+            }
+            AppEvent::StreamStats(stats) => {
+                let was = self.live.replace(stats.is_some());
+                // The first poll only says where things stand
+                match (was, stats) {
+                    (Some(false), Some(stats)) => (
+                        "online",
+                        json!({ "game": stats.game_name, "viewers": stats.viewer_count }),
+                    ),
+                    (Some(true), None) => ("offline", json!({})),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some((format!("{}/{}", self.prefix, event), payload))
+    }
+}
+
+fn alert_payload(alert: &StreamAlert) -> Value {
+    let mut payload = json!({ "user": alert.user(), "text": alert.describe() });
+    match alert {
+        StreamAlert::Raid { viewers, .. } => payload["viewers"] = json!(viewers),
+        StreamAlert::ViewerMilestone { viewers } => payload["viewers"] = json!(viewers),
+        StreamAlert::GiftSub { count, .. } => payload["count"] = json!(count),
+        StreamAlert::Cheer { bits, .. } => payload["bits"] = json!(bits),
+        StreamAlert::Tip {
+            amount, currency, ..
+        } => {
+            payload["amount"] = json!(amount);
+            payload["currency"] = json!(currency);
+        }
+        StreamAlert::Follow { .. } | StreamAlert::Subscribe { .. } => {}
+    }
+    payload
+}
+
+/// Publish `events` to the broker, connecting again whenever it goes away,
+/// until the event stream ends or the broker refuses the login.
+pub async fn run(
+    settings: MqttSettings,
+    names: Vec<String>,
+    tx: mpsc::UnboundedSender<AppEvent>,
+    mut events: broadcast::Receiver<AppEvent>,
+) -> Result<()> {
+    let mut topics = Topics::new(&settings.prefix, names);
+    let (client, connection) = AsyncClient::new(settings.options()?, QUEUE);
+    let _ = tx.send(AppEvent::Info(format!(
+        "MQTT: publishing to {} under {}/",
+        settings.broker, settings.prefix
+    )));
+    // Polling isn't interrupted for events, a poll dropped halfway through
+    // connecting would drop the connection with it
+    let mut connection = tokio::spawn(poll(connection, tx));
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if let Some((topic, payload)) = topics.publication(&event) {
+                        // Only fails with the queue full, while the broker is away
+                        let _ = client.try_publish(topic, QoS::AtMostOnce, false, payload.to_string());
+                    }
+                }
+                // Whatever was missed while the broker was away
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            ended = &mut connection => return ended?,
+        }
+    }
+
+    // Send the DISCONNECT, if the broker is there to take it
+    let _ = client.try_disconnect();
+    if tokio::time::timeout(Duration::from_secs(1), &mut connection)
+        .await
+        .is_err()
+    {
+        connection.abort();
+    }
+    Ok(())
+}
+
+// Connect, send what was published and keep the connection alive, until the
+// DISCONNECT is out or the broker refuses the login. After any other error
+// the next poll connects again.
+async fn poll(mut connection: EventLoop, tx: mpsc::UnboundedSender<AppEvent>) -> Result<()> {
+    loop {
+        match connection.poll().await {
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => return Ok(()),
+            Ok(_) => {}
+            Err(ConnectionError::ConnectionRefused(code))
+                if code != ConnectReturnCode::ServiceUnavailable =>
+            {
+                bail!("The MQTT broker refused the connection: {}", refused(code));
+            }
+            Err(e) => {
+                let _ = tx.send(AppEvent::Debug(format!("MQTT: {}", e)));
+                tokio::time::sleep(RETRY).await;
+            }
+        }
+    }
+}
+
+// Why the broker said no, which trying again won't fix
+fn refused(code: ConnectReturnCode) -> &'static str {
+    match code {
+        ConnectReturnCode::RefusedProtocolVersion => "it doesn't speak MQTT 3.1.1",
+        ConnectReturnCode::BadClientId => "it didn't accept the client id",
+        ConnectReturnCode::BadUserNamePassword => "wrong MQTT_USERNAME or MQTT_PASSWORD",
+        ConnectReturnCode::NotAuthorized => "not authorized",
+        ConnectReturnCode::Success | ConnectReturnCode::ServiceUnavailable => {
+            "for an unknown reason"
+        }
+    }
+}
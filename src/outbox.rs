@@ -0,0 +1,156 @@
+use crate::config::Config;
+use crate::state::AppEvent;
+use crate::twitch::send_chat_message;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+// Everything the bot says in chat goes through one queue, so a burst of
+// greetings can't run into Twitch's limit of messages per 30 seconds (20 for
+// a regular account, 100 for a moderator or the broadcaster). Messages wait
+// by priority: moderation and the operator first, then replies, then
+// greetings and timers. Waiting low-priority messages are merged into one
+// chat message while they fit, and the oldest are dropped once too many
+// pile up; an identical message that's already waiting isn't queued twice.
+
+const WINDOW: Duration = Duration::from_secs(30);
+// Twitch cuts messages off at 500 characters
+const MAX_LEN: usize = 500;
+const MAX_LOW: usize = 10;
+const SEPARATOR: &str = " | ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    // Greetings and timed messages, which can wait or be merged
+    Low,
+    // Replies: the AI's, commands', auto replies
+    Normal,
+    // Moderation notices and what the operator types
+    High,
+}
+
+#[derive(Debug, Default)]
+struct Waiting {
+    // By priority, low first; oldest first in each
+    queues: [VecDeque<String>; 3],
+}
+
+impl Waiting {
+    fn push(&mut self, priority: Priority, message: String) {
+        if self
+            .queues
+            .iter()
+            .flatten()
+            .any(|waiting| *waiting == message)
+        {
+            return;
+        }
+        let queue = &mut self.queues[priority as usize];
+        if priority == Priority::Low {
+            if let Some(last) = queue
+                .back_mut()
+                .filter(|last| last.len() + SEPARATOR.len() + message.len() <= MAX_LEN)
+            {
+                last.push_str(SEPARATOR);
+                last.push_str(&message);
+                return;
+            }
+            if queue.len() == MAX_LOW {
+                queue.pop_front();
+            }
+        }
+        queue.push_back(message);
+    }
+
+    fn pop(&mut self) -> Option<String> {
+        self.queues.iter_mut().rev().find_map(VecDeque::pop_front)
+    }
+
+    fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+}
+
+/// A handle on the queue; clones share it. Nothing is sent until `run` is
+/// started.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    waiting: Arc<Mutex<Waiting>>,
+    ready: Arc<Notify>,
+    // Messages per WINDOW
+    limit: Arc<AtomicUsize>,
+}
+
+impl Default for Outbox {
+    fn default() -> Self {
+        Self {
+            waiting: Default::default(),
+            ready: Default::default(),
+            limit: Arc::new(AtomicUsize::new(20)),
+        }
+    }
+}
+
+impl Outbox {
+    pub fn say(&self, priority: Priority, message: String) {
+        self.waiting.lock().unwrap().push(priority, message);
+        self.ready.notify_one();
+    }
+
+    /// Messages waiting to be sent.
+    pub fn waiting(&self) -> usize {
+        self.waiting.lock().unwrap().len()
+    }
+
+    /// Messages per 30 seconds; takes effect right away.
+    pub fn set_rate_limit(&self, limit: usize) {
+        self.limit.store(limit.max(1), Ordering::Relaxed);
+    }
+
+    /// Send what's queued, in order and within the rate limit, until
+    /// `shutdown`. A message that fails comes back as SendFailed.
+    pub async fn run(
+        self,
+        config: Arc<Config>,
+        tx: mpsc::UnboundedSender<AppEvent>,
+        shutdown: CancellationToken,
+    ) {
+        self.set_rate_limit(config.chat_rate_limit);
+        // When the messages of the last WINDOW went out
+        let mut sent: VecDeque<Instant> = VecDeque::new();
+        loop {
+            let notified = self.ready.notified();
+            if self.waiting() == 0 {
+                tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    _ = notified => continue,
+                }
+            }
+            while sent.front().is_some_and(|at| at.elapsed() >= WINDOW) {
+                sent.pop_front();
+            }
+            let limit = self.limit.load(Ordering::Relaxed);
+            if sent.len() >= limit {
+                // Until enough of the window's messages are old enough
+                let free = sent[sent.len() - limit] + WINDOW;
+                tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    _ = tokio::time::sleep_until(free) => continue,
+                }
+            }
+            // Whatever is most urgent by now
+            let Some(message) = self.waiting.lock().unwrap().pop() else {
+                continue;
+            };
+            sent.push_back(Instant::now());
+            if let Err(e) = send_chat_message(&message, &config).await {
+                let error = e.to_string();
+                let _ = tx.send(AppEvent::SendFailed { message, error });
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Where choui keeps its files, per the platform's conventions (from the
+// directories crate):
+//
+//            Linux                           macOS                              Windows
+// config     $XDG_CONFIG_HOME/choui or       ~/Library/Application Support/     %APPDATA%\choui\config
+//            ~/.config/choui                 choui
+// data       $XDG_DATA_HOME/choui or         ~/Library/Application Support/     %APPDATA%\choui\data
+//            ~/.local/share/choui            choui
+// cache      $XDG_CACHE_HOME/choui or        ~/Library/Caches/choui             %LOCALAPPDATA%\choui\cache
+//            ~/.cache/choui
+//
+// Files older versions wrote to the working directory are moved over the
+// first time they're needed, unless DATA_DIR points somewhere else.
+
+// None without a home directory
+fn project() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "choui")
+}
+
+/// Where choui.toml is looked for after the working directory.
+pub fn config_dir() -> Option<PathBuf> {
+    project().map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Things that should survive a restart: the Twitch token, UI state.
+/// Without a home directory everything stays in the working directory.
+pub fn data_dir() -> PathBuf {
+    project()
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Things that can be fetched again: emote images.
+pub fn cache_dir() -> PathBuf {
+    project()
+        .map(|dirs| dirs.cache_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// `dir`/`name`, with the directory created. The file an older version left
+/// (`legacy`, if it's still looked for) is moved there.
+pub fn file_in(dir: &Path, name: &str, legacy: Option<PathBuf>) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(name);
+    let Some(legacy) = legacy else {
+        return Ok(path);
+    };
+    if !path.exists() && legacy.is_file() && legacy != path {
+        move_file(&legacy, &path).with_context(|| {
+            format!("Failed to move {} to {}", legacy.display(), path.display())
+        })?;
+    }
+    Ok(path)
+}
+
+// rename() can't cross filesystems, the working directory and the home
+// directory often are on different ones
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
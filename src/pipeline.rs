@@ -0,0 +1,125 @@
+use crate::state::ChatMessage;
+use anyhow::{bail, Result};
+use std::collections::{HashSet, VecDeque};
+
+// What happens to a chat message, as a list of stages it passes through in
+// order: each one does its part and passes the message on, or finishes with
+// it (a deleted link, a command that was answered). The stages work on a
+// context of the frontend's choosing, which borrows whatever they need for
+// the one message; the bot's are in chat_stages.rs. Any stage can be skipped
+// by name with SKIP_STAGES.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Next,
+    // The message goes no further
+    Done,
+}
+
+/// Names the context stages get, `Of<'a>`, for any borrow `'a`. It can be
+/// a trait object, for stages that work with any implementation.
+pub trait Context {
+    type Of<'a>: ?Sized;
+}
+
+pub trait Stage<C: Context> {
+    // For SKIP_STAGES and the log
+    fn name(&self) -> &'static str;
+
+    fn run(&mut self, cx: &mut C::Of<'_>, message: &ChatMessage) -> Flow;
+}
+
+pub struct Pipeline<C: Context> {
+    stages: Vec<Box<dyn Stage<C>>>,
+    skipped: HashSet<&'static str>,
+}
+
+impl<C: Context> Default for Pipeline<C> {
+    fn default() -> Self {
+        Self {
+            stages: Vec::new(),
+            skipped: HashSet::new(),
+        }
+    }
+}
+
+impl<C: Context> Pipeline<C> {
+    /// Add a stage after the others.
+    pub fn then(mut self, stage: impl Stage<C> + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    /// Skip the stages named, and only those; an unknown name is an error.
+    pub fn skip(&mut self, names: &[String]) -> Result<()> {
+        let mut skipped = HashSet::new();
+        for name in names {
+            match self.stages.iter().find(|stage| stage.name() == name) {
+                Some(stage) => skipped.insert(stage.name()),
+                None => bail!(
+                    "Unknown stage '{}', expected one of {}",
+                    name,
+                    self.names().join(", ")
+                ),
+            };
+        }
+        self.skipped = skipped;
+        Ok(())
+    }
+
+    /// Pass `message` through. Returns the stage that finished with it, if
+    /// one did.
+    pub fn run(&mut self, cx: &mut C::Of<'_>, message: &ChatMessage) -> Option<&'static str> {
+        for stage in &mut self.stages {
+            if self.skipped.contains(stage.name()) {
+                continue;
+            }
+            if stage.run(cx, message) == Flow::Done {
+                return Some(stage.name());
+            }
+        }
+        None
+    }
+}
+
+/// Drops a message Twitch delivers twice (EventSub delivers at least once),
+/// going by the message id.
+pub struct Dedup {
+    seen: HashSet<String>,
+    // Oldest first, to forget them in order
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Dedup {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+}
+
+impl<C: Context> Stage<C> for Dedup {
+    fn name(&self) -> &'static str {
+        "dedup"
+    }
+
+    fn run(&mut self, _cx: &mut C::Of<'_>, message: &ChatMessage) -> Flow {
+        if !self.seen.insert(message.id.clone()) {
+            return Flow::Done;
+        }
+        self.order.push_back(message.id.clone());
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        Flow::Next
+    }
+}
@@ -0,0 +1,78 @@
+use crate::state::{ChatMessage, StreamAlert};
+
+// Features that live outside the event loop, like a giveaway or a game: a
+// plugin is told what happens in chat and answers with actions for the bot to
+// take. They're compiled in and registered at startup (see `plugins()` in
+// features.rs); a plugin's `name` shows in the log.
+//
+// Plugins see every viewer's chat message before the chat commands and the
+// AI do, except messages deleted for link protection. Returning
+// `PluginAction::Handled` for one keeps it from going any further.
+
+/// What happened, as a plugin sees it.
+#[derive(Debug, Clone)]
+pub enum PluginEvent {
+    Chat(ChatMessage),
+    Join {
+        user: String,
+    },
+    // Follows, subs, gifted subs, raids, cheers and viewer milestones
+    Alert(StreamAlert),
+    Redemption {
+        user: String,
+        reward: String,
+        // What the viewer typed, empty for rewards without text
+        input: String,
+    },
+    // Once a second, for plugins that time things
+    Tick,
+}
+
+/// What a plugin wants done.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginAction {
+    // Post in chat as the bot
+    Say(String),
+    // A sound file, played on the alert volume
+    PlaySound(String),
+    // Ask the AI, answering `user` in chat like the AI triggers do (with
+    // AI_REQUIRE_APPROVAL and the AI tab)
+    AskAi { user: String, prompt: String },
+    // Read aloud in the bot's voice, while TTS is on
+    Speak(String),
+    // Give (or with a negative number take) a viewer's game points
+    AddPoints { user: String, points: i64 },
+    // A line in the Log tab
+    Log(String),
+    // The chat message was the plugin's; don't pass it on
+    Handled,
+}
+
+pub trait Plugin: Send {
+    fn name(&self) -> &str;
+
+    fn on_event(&mut self, event: &PluginEvent) -> Vec<PluginAction>;
+}
+
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl Plugins {
+    pub fn register(&mut self, plugin: impl Plugin + 'static) {
+        self.plugins.push(Box::new(plugin));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.plugins.iter().map(|plugin| plugin.name()).collect()
+    }
+
+    /// Every plugin's actions for `event`, in the order they were registered.
+    pub fn dispatch(&mut self, event: &PluginEvent) -> Vec<PluginAction> {
+        self.plugins
+            .iter_mut()
+            .flat_map(|plugin| plugin.on_event(event))
+            .collect()
+    }
+}
@@ -0,0 +1,234 @@
+use crate::ai;
+use crate::config::Config;
+use crate::twitch::PollProgress;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// !poll "Question" option1 option2 ...: a native Twitch poll (POLL_MODE=twitch)
+// or one counted from chat, where viewers vote by typing an option or its
+// number. The standings show in the TUI status bar and the overlay, and the
+// result is announced in chat, told by the AI if POLL_AI is on.
+//
+//   !poll "Question" <option> <option> [...] [-d=<seconds>]   start one (mods)
+//   !poll end                                                 end it early (mods)
+//   !poll                                                     the standings
+
+// Twitch's limits, kept for chat polls too
+const MAX_OPTIONS: usize = 5;
+pub const DURATIONS: std::ops::RangeInclusive<u64> = 15..=1800;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PollCommand {
+    Start {
+        question: String,
+        options: Vec<String>,
+        // None for POLL_DURATION
+        duration: Option<Duration>,
+    },
+    End,
+    Show,
+}
+
+const USAGE: &str = "Usage: !poll \"Question\" <option> <option> [...] [-d=<seconds>]";
+
+impl PollCommand {
+    pub fn parse(args: &str) -> Result<Self> {
+        match args.trim().to_lowercase().as_str() {
+            "" | "results" => return Ok(PollCommand::Show),
+            "end" | "stop" => return Ok(PollCommand::End),
+            _ => {}
+        }
+        let mut words = split_quoted(args)?;
+        let mut duration = None;
+        if let Some(i) = words.iter().position(|word| word.starts_with("-d=")) {
+            let seconds: u64 = words.remove(i)[3..]
+                .parse()
+                .ok()
+                .filter(|seconds| DURATIONS.contains(seconds))
+                .with_context(|| {
+                    format!(
+                        "Polls run {} to {} seconds",
+                        DURATIONS.start(),
+                        DURATIONS.end()
+                    )
+                })?;
+            duration = Some(Duration::from_secs(seconds));
+        }
+        if words.len() < 3 {
+            bail!(USAGE);
+        }
+        let question = words.remove(0);
+        if words.len() > MAX_OPTIONS {
+            bail!("Polls have at most {} options", MAX_OPTIONS);
+        }
+        Ok(PollCommand::Start {
+            question,
+            options: words,
+            duration,
+        })
+    }
+
+    pub fn mod_only(&self) -> bool {
+        !matches!(self, PollCommand::Show)
+    }
+}
+
+// Words, with "double quoted" ones kept together
+fn split_quoted(text: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let (word, after) = quoted.split_once('"').context("Missing closing quote")?;
+            words.push(word.trim().to_string());
+            rest = after.trim_start();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            words.push(rest[..end].to_string());
+            rest = rest[end..].trim_start();
+        }
+    }
+    words.retain(|word| !word.is_empty());
+    Ok(words)
+}
+
+#[derive(Debug, Clone)]
+pub struct Poll {
+    // Tells updates to this poll from a new one
+    pub id: u64,
+    pub question: String,
+    pub options: Vec<String>,
+    // Per option, in order
+    pub votes: Vec<u64>,
+    // Twitch's id for a native poll, None for one counted from chat
+    pub native_id: Option<String>,
+    // Chat polls: the option each viewer voted for, the latest vote counts
+    voters: HashMap<String, usize>,
+    pub ends: Instant,
+    pub ended: bool,
+}
+
+impl Poll {
+    pub fn new(
+        question: String,
+        options: Vec<String>,
+        duration: Duration,
+        native_id: Option<String>,
+    ) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            question,
+            votes: vec![0; options.len()],
+            options,
+            native_id,
+            voters: HashMap::new(),
+            ends: Instant::now() + duration,
+            ended: false,
+        }
+    }
+
+    pub fn time_left(&self) -> Duration {
+        self.ends.saturating_duration_since(Instant::now())
+    }
+
+    /// Count a chat message as a vote if it's an option or an option's
+    /// number. Returns whether it was one.
+    pub fn vote(&mut self, user: &str, text: &str) -> bool {
+        if self.ended || self.native_id.is_some() {
+            return false;
+        }
+        let text = text.trim();
+        let choice = match text.parse::<usize>() {
+            Ok(n) if (1..=self.options.len()).contains(&n) => n - 1,
+            _ => match self
+                .options
+                .iter()
+                .position(|option| option.eq_ignore_ascii_case(text))
+            {
+                Some(i) => i,
+                None => return false,
+            },
+        };
+        if let Some(previous) = self.voters.insert(user.to_lowercase(), choice) {
+            self.votes[previous] -= 1;
+        }
+        self.votes[choice] += 1;
+        true
+    }
+
+    /// Votes from Helix for a native poll.
+    pub fn update(&mut self, progress: &PollProgress) {
+        if progress.votes.len() == self.votes.len() {
+            self.votes.clone_from(&progress.votes);
+        }
+        self.ended |= !progress.active;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.votes.iter().sum()
+    }
+
+    pub fn percent(&self, i: usize) -> u64 {
+        (self.votes[i] * 100).checked_div(self.total()).unwrap_or(0)
+    }
+
+    /// `pizza 5 (62%), tacos 3 (38%)`
+    pub fn standings(&self) -> String {
+        self.options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| format!("{} {} ({}%)", option, self.votes[i], self.percent(i)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The options with the most votes; none without any votes.
+    pub fn winners(&self) -> Vec<&str> {
+        let top = self.votes.iter().copied().max().unwrap_or(0);
+        if top == 0 {
+            return Vec::new();
+        }
+        self.options
+            .iter()
+            .zip(&self.votes)
+            .filter(|(_, votes)| **votes == top)
+            .map(|(option, _)| option.as_str())
+            .collect()
+    }
+
+    /// The plain result: `Poll "Snack?": pizza wins with 5 of 8 votes`
+    pub fn summary(&self) -> String {
+        let outcome = match self.winners().as_slice() {
+            [] => "nobody voted".to_string(),
+            [winner] => format!(
+                "{} wins with {} of {} votes",
+                winner,
+                self.votes.iter().max().unwrap_or(&0),
+                self.total()
+            ),
+            tied => format!("it's a tie between {}", tied.join(" and ")),
+        };
+        format!("Poll \"{}\": {}", self.question, outcome)
+    }
+
+    /// The result as the bot announces it: told by the AI, or the summary
+    /// when that's off or fails.
+    pub async fn announcement(&self, config: &Config) -> String {
+        if !config.poll_ai {
+            return self.summary();
+        }
+        let prompt = format!(
+            "The chat poll \"{}\" just ended. Results: {}. Announce the result to chat in one \
+             or two sentences.",
+            self.question,
+            self.standings()
+        );
+        match ai::ask_ai(&prompt, config).await {
+            Ok(reply) if !reply.trim().is_empty() => reply.trim().to_string(),
+            _ => self.summary(),
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::ai;
+use crate::config::Config;
+use crate::sqlite::{Connection, Store, Value};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// !quote: memorable lines from the stream, numbered and kept in the chat
+// database. Anyone can read them; adding and deleting is for mods.
+//
+//   !quote [random]       a random quote
+//   !quote <id>           that quote
+//   !quote add <text>     save a quote (mods)
+//   !quote delete <id>    remove one (mods)
+//   !quote <words>        a quote matching the words, or with QUOTES_AI the
+//                         one the model thinks fits ("about dying to Pudge")
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS quotes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    added_by TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+);
+";
+
+// Most quotes the model gets to choose from
+const AI_CANDIDATES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub id: i64,
+    pub text: String,
+    pub added_by: String,
+    pub timestamp: String,
+}
+
+impl Quote {
+    fn from_row(row: &[Value]) -> Option<Self> {
+        Some(Self {
+            id: row.first()?.as_int()?,
+            text: row.get(1)?.as_str()?.to_string(),
+            added_by: row.get(2)?.as_str()?.to_string(),
+            timestamp: row.get(3)?.as_str()?.to_string(),
+        })
+    }
+
+    /// `Quote #12: "text" (2024-05-01)`
+    pub fn describe(&self) -> String {
+        let date = self.timestamp.get(..10).unwrap_or(&self.timestamp);
+        format!("Quote #{}: \"{}\" ({})", self.id, self.text, date)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuoteCommand {
+    Random,
+    Show(i64),
+    Add(String),
+    Delete(i64),
+    Find(String),
+}
+
+impl QuoteCommand {
+    pub fn parse(args: &str) -> Result<Self> {
+        let args = args.trim();
+        let (first, rest) = match args.split_once(char::is_whitespace) {
+            Some((first, rest)) => (first, rest.trim()),
+            None => (args, ""),
+        };
+        let id = |text: &str| {
+            text.trim_start_matches('#')
+                .parse::<i64>()
+                .ok()
+                .filter(|id| *id > 0)
+        };
+        Ok(match first.to_lowercase().as_str() {
+            "" | "random" => QuoteCommand::Random,
+            "add" if rest.is_empty() => bail!("Usage: !quote add <text>"),
+            "add" => QuoteCommand::Add(rest.to_string()),
+            "delete" | "del" | "remove" => {
+                QuoteCommand::Delete(id(rest).context("Usage: !quote delete <id>")?)
+            }
+            _ => match id(args) {
+                Some(id) => QuoteCommand::Show(id),
+                None => QuoteCommand::Find(args.to_string()),
+            },
+        })
+    }
+
+    pub fn mod_only(&self) -> bool {
+        matches!(self, QuoteCommand::Add(_) | QuoteCommand::Delete(_))
+    }
+}
+
+#[derive(Clone)]
+pub struct Quotes {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl Quotes {
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?.into_connection();
+        Ok(Self {
+            db: Arc::new(Mutex::new(db)),
+        })
+    }
+
+    // SQLite calls block, so they run off the async threads
+    async fn with_db<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || f(&db.lock().unwrap())).await?
+    }
+
+    async fn select(&self, filter: &'static str, params: Vec<Value>) -> Result<Vec<Quote>> {
+        self.with_db(move |db| {
+            let sql = format!(
+                "SELECT id, text, added_by, timestamp FROM quotes {}",
+                filter
+            );
+            Ok(db
+                .query(&sql, &params)?
+                .iter()
+                .filter_map(|row| Quote::from_row(row))
+                .collect())
+        })
+        .await
+    }
+
+    pub async fn add(&self, text: String, added_by: String) -> Result<Quote> {
+        let timestamp = crate::chatlog::timestamp();
+        let quote = Quote {
+            id: 0,
+            text,
+            added_by,
+            timestamp,
+        };
+        self.with_db(move |db| {
+            db.execute(
+                "INSERT INTO quotes (text, added_by, timestamp) VALUES (?, ?, ?)",
+                &[
+                    quote.text.as_str().into(),
+                    quote.added_by.as_str().into(),
+                    quote.timestamp.as_str().into(),
+                ],
+            )?;
+            Ok(Quote {
+                id: db.last_insert_rowid(),
+                ..quote
+            })
+        })
+        .await
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<Quote>> {
+        Ok(self.select("WHERE id = ?", vec![id.into()]).await?.pop())
+    }
+
+    pub async fn random(&self) -> Result<Option<Quote>> {
+        Ok(self
+            .select("ORDER BY random() LIMIT 1", vec![])
+            .await?
+            .pop())
+    }
+
+    /// Returns whether there was such a quote.
+    pub async fn delete(&self, id: i64) -> Result<bool> {
+        self.with_db(move |db| Ok(db.execute("DELETE FROM quotes WHERE id = ?", &[id.into()])? > 0))
+            .await
+    }
+
+    /// A random quote containing every one of the words.
+    pub async fn search(&self, words: &str) -> Result<Option<Quote>> {
+        let words: Vec<String> = words.split_whitespace().map(str::to_lowercase).collect();
+        let quotes = self.select("ORDER BY random()", vec![]).await?;
+        Ok(quotes.into_iter().find(|quote| {
+            let text = quote.text.to_lowercase();
+            words.iter().all(|word| text.contains(word))
+        }))
+    }
+
+    // Let the model pick the quote that fits a free-form request best
+    async fn pick_with_ai(&self, request: &str, config: &Config) -> Result<Option<Quote>> {
+        let quotes = self
+            .select(
+                "ORDER BY id DESC LIMIT ?",
+                vec![(AI_CANDIDATES as i64).into()],
+            )
+            .await?;
+        if quotes.is_empty() {
+            return Ok(None);
+        }
+        let list: Vec<String> = quotes
+            .iter()
+            .map(|quote| format!("{}: {}", quote.id, quote.text))
+            .collect();
+        let system = "You pick quotes from a numbered list. Answer with the number of the \
+                      quote that best matches the request and nothing else, or 0 if none fits.";
+        let prompt = format!("Request: {}\n\nQuotes:\n{}", request, list.join("\n"));
+        let answer = ai::ask(&prompt, system, config).await?;
+        let id = answer
+            .split(|c: char| !c.is_ascii_digit())
+            .find_map(|n| n.parse::<i64>().ok());
+        Ok(id.and_then(|id| quotes.into_iter().find(|quote| quote.id == id)))
+    }
+
+    /// Run a command for `user` and return the reply for chat.
+    pub async fn run(
+        &self,
+        command: QuoteCommand,
+        user: String,
+        config: &Config,
+    ) -> Result<String> {
+        Ok(match command {
+            QuoteCommand::Random => match self.random().await? {
+                Some(quote) => quote.describe(),
+                None => "No quotes yet. Mods can add one with !quote add <text>".to_string(),
+            },
+            QuoteCommand::Show(id) => match self.get(id).await? {
+                Some(quote) => quote.describe(),
+                None => format!("There's no quote #{}", id),
+            },
+            QuoteCommand::Add(text) => {
+                let quote = self.add(text, user).await?;
+                format!("Added quote #{}", quote.id)
+            }
+            QuoteCommand::Delete(id) => {
+                if self.delete(id).await? {
+                    format!("Deleted quote #{}", id)
+                } else {
+                    format!("There's no quote #{}", id)
+                }
+            }
+            QuoteCommand::Find(request) => {
+                let found = if config.quotes_ai {
+                    self.pick_with_ai(&request, config).await?
+                } else {
+                    self.search(&request).await?
+                };
+                match found {
+                    Some(quote) => quote.describe(),
+                    None => format!("No quote found for \"{}\"", request),
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,342 @@
+use crate::plugins::{Plugin, PluginAction, PluginEvent};
+use crate::state::Role;
+use anyhow::{Context, Result};
+use mlua::{Function, HookTriggers, Lua, LuaOptions, StdLib, Variadic, VmState};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant, SystemTime};
+
+// Lua scripts as a plugin: every *.lua file in SCRIPTS_DIR is run in a state
+// of its own, and the functions it defines are called when things happen:
+//
+//   on_chat(user, text, role)          role: broadcaster, moderator, vip, viewer
+//   on_join(user)
+//   on_alert(kind, user, description) kind as in ALERT_SOUNDS, user "" if none
+//   on_redemption(user, reward, input)
+//   on_tick()                          once a second
+//
+// and they can call send_chat(text), tts(text), add_points(user, points),
+// ask_ai(user, prompt), play_sound(path) and log(text) (print(...) logs
+// too, its values separated by tabs). Of Lua's own
+// libraries they get string, table, math, utf8 and coroutine; nothing that
+// reaches files, programs or the environment (io, os, require, dofile).
+//
+// The scripts run on a thread of their own, and the bot never waits for
+// them: what they ask for comes back with the next event, at the latest the
+// next tick. That's also why they can't keep a chat message from the
+// commands and the AI the way other plugins can. The directory is checked
+// for added, changed or removed files every RESCAN, and everything is loaded
+// again when it changed. The scripts get SCRIPT_TIME for each event; a call
+// still running then is stopped.
+
+// How long the scripts may take for one event, all of them together
+const SCRIPT_TIME: Duration = Duration::from_millis(200);
+// Events the scripts may have waiting; past that new ones are dropped rather
+// than queued up behind a slow script
+const QUEUE: usize = 4;
+const RESCAN: Duration = Duration::from_secs(2);
+// What a script's state may allocate
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+thread_local! {
+    // What the script being called asked for, collected by the functions
+    // below since they can't see the worker
+    static ACTIONS: RefCell<Vec<PluginAction>> = const { RefCell::new(Vec::new()) };
+}
+
+fn push(action: PluginAction) {
+    ACTIONS.with(|actions| actions.borrow_mut().push(action));
+}
+
+// When the running call has to end, checked by every script's hook
+type Deadline = Rc<Cell<Instant>>;
+
+struct Script {
+    // The file name, for the log
+    name: String,
+    lua: Lua,
+}
+
+impl Script {
+    fn load(path: &Path, deadline: &Deadline) -> Result<Self> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let lua = Lua::new_with(
+            StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8 | StdLib::COROUTINE,
+            LuaOptions::default(),
+        )?;
+        lua.set_memory_limit(MEMORY_LIMIT)?;
+        let deadline = deadline.clone();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(1000),
+            move |_, _| {
+                if Instant::now() > deadline.get() {
+                    Err(mlua::Error::runtime("script ran too long"))
+                } else {
+                    Ok(VmState::Continue)
+                }
+            },
+        )?;
+
+        let globals = lua.globals();
+        // The base library reads files with these
+        globals.set("dofile", mlua::Nil)?;
+        globals.set("loadfile", mlua::Nil)?;
+        // Lua's own print would write over the terminal UI
+        globals.set(
+            "print",
+            lua.create_function(|_, values: Variadic<mlua::Value>| {
+                let text = values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<mlua::Result<Vec<_>>>()?;
+                push(PluginAction::Log(text.join("\t")));
+                Ok(())
+            })?,
+        )?;
+        globals.set(
+            "send_chat",
+            lua.create_function(|_, text: String| {
+                push(PluginAction::Say(text));
+                Ok(())
+            })?,
+        )?;
+        globals.set(
+            "tts",
+            lua.create_function(|_, text: String| {
+                push(PluginAction::Speak(text));
+                Ok(())
+            })?,
+        )?;
+        globals.set(
+            "add_points",
+            lua.create_function(|_, (user, points): (String, i64)| {
+                push(PluginAction::AddPoints { user, points });
+                Ok(())
+            })?,
+        )?;
+        globals.set(
+            "ask_ai",
+            lua.create_function(|_, (user, prompt): (String, String)| {
+                push(PluginAction::AskAi { user, prompt });
+                Ok(())
+            })?,
+        )?;
+        globals.set(
+            "play_sound",
+            lua.create_function(|_, path: String| {
+                push(PluginAction::PlaySound(path));
+                Ok(())
+            })?,
+        )?;
+        globals.set(
+            "log",
+            lua.create_function(|_, text: String| {
+                push(PluginAction::Log(text));
+                Ok(())
+            })?,
+        )?;
+
+        lua.load(source).set_name(format!("@{}", name)).exec()?;
+        Ok(Self { name, lua })
+    }
+
+    // Call the global function `function` with string arguments, if the
+    // script defined one
+    fn call(&self, function: &str, args: &[&str]) -> Result<()> {
+        if let Some(function) = self.lua.globals().get::<Option<Function>>(function)? {
+            function.call::<()>(Variadic::from_iter(args.iter().copied()))?;
+        }
+        Ok(())
+    }
+}
+
+// Owns the Lua states, on the scripts' thread
+struct Worker {
+    dir: PathBuf,
+    scripts: Vec<Script>,
+    // The files and when they were changed, as last loaded
+    loaded: Vec<(PathBuf, Option<SystemTime>)>,
+    // None before the first load
+    scanned: Option<Instant>,
+    deadline: Deadline,
+}
+
+impl Worker {
+    fn run(mut self, events: Receiver<PluginEvent>, replies: Sender<Vec<PluginAction>>) {
+        for event in events {
+            if self.scanned.is_none_or(|at| at.elapsed() >= RESCAN) {
+                self.reload();
+            }
+            self.deadline.set(Instant::now() + SCRIPT_TIME);
+            self.handle(&event);
+            if replies
+                .send(ACTIONS.with(|actions| actions.take()))
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    // The *.lua files, sorted so scripts run in a stable order
+    fn files(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let mut files: Vec<_> = std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+            .map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    // Load every script again if any was added, changed or removed
+    fn reload(&mut self) {
+        let files = self.files();
+        let first = self.scanned.replace(Instant::now()).is_none();
+        if !first && self.loaded == files {
+            return;
+        }
+        self.scripts.clear();
+        for (path, _) in &files {
+            // Each gets the time an event would to run its top level
+            self.deadline.set(Instant::now() + SCRIPT_TIME);
+            match Script::load(path, &self.deadline) {
+                Ok(script) => self.scripts.push(script),
+                Err(e) => push(PluginAction::Log(format!(
+                    "Script {} not loaded: {:#}",
+                    path.display(),
+                    e
+                ))),
+            }
+        }
+        if !self.scripts.is_empty() {
+            let names: Vec<_> = self.scripts.iter().map(|s| s.name.as_str()).collect();
+            push(PluginAction::Log(format!("Scripts: {}", names.join(", "))));
+        }
+        self.loaded = files;
+    }
+
+    // Call `function` in every script that has it
+    fn call(&self, function: &str, args: &[&str]) {
+        for script in &self.scripts {
+            if let Err(e) = script.call(function, args) {
+                push(PluginAction::Log(format!(
+                    "Script {} failed in {}: {:#}",
+                    script.name, function, e
+                )));
+            }
+        }
+    }
+
+    fn handle(&self, event: &PluginEvent) {
+        match event {
+            PluginEvent::Chat(message) => {
+                let role = match message.role() {
+                    Role::Broadcaster => "broadcaster",
+                    Role::Moderator => "moderator",
+                    Role::Vip => "vip",
+                    Role::Viewer => "viewer",
+                };
+                self.call("on_chat", &[&message.user, &message.text, role]);
+            }
+            PluginEvent::Join { user } => {
+                self.call("on_join", &[user]);
+            }
+            PluginEvent::Alert(alert) => {
+                self.call(
+                    "on_alert",
+                    &[
+                        alert.kind().name(),
+                        alert.user().unwrap_or(""),
+                        &alert.describe(),
+                    ],
+                );
+            }
+            PluginEvent::Redemption {
+                user,
+                reward,
+                input,
+            } => {
+                self.call("on_redemption", &[user, reward, input]);
+            }
+            PluginEvent::Tick => {
+                self.call("on_tick", &[]);
+            }
+        }
+    }
+}
+
+pub struct Scripts {
+    events: Sender<PluginEvent>,
+    replies: Receiver<Vec<PluginAction>>,
+    // Events the worker hasn't answered yet
+    behind: usize,
+    // Whether events are being dropped, so that's logged once
+    skipping: bool,
+}
+
+impl Scripts {
+    pub fn new(dir: PathBuf) -> Self {
+        let (events, worker_events) = mpsc::channel();
+        let (worker_replies, replies) = mpsc::channel();
+        // Lua states can't move between threads, so they're made on theirs
+        std::thread::spawn(move || {
+            let worker = Worker {
+                dir,
+                scripts: Vec::new(),
+                loaded: Vec::new(),
+                scanned: None,
+                deadline: Rc::new(Cell::new(Instant::now())),
+            };
+            worker.run(worker_events, worker_replies)
+        });
+        Self {
+            events,
+            replies,
+            behind: 0,
+            skipping: false,
+        }
+    }
+}
+
+impl Plugin for Scripts {
+    fn name(&self) -> &str {
+        "scripts"
+    }
+
+    fn on_event(&mut self, event: &PluginEvent) -> Vec<PluginAction> {
+        // What the scripts asked for since the last event
+        let mut actions = Vec::new();
+        while let Ok(reply) = self.replies.try_recv() {
+            self.behind -= 1;
+            actions.extend(reply);
+        }
+        if self.behind >= QUEUE {
+            if !self.skipping {
+                self.skipping = true;
+                actions.push(PluginAction::Log(
+                    "Scripts are taking too long, skipping events until they catch up".to_string(),
+                ));
+            }
+            return actions;
+        }
+        self.skipping = false;
+        if self.events.send(event.clone()).is_ok() {
+            self.behind += 1;
+        }
+        actions
+    }
+}
@@ -0,0 +1,72 @@
+use regex::{Regex, RegexBuilder};
+use tui_input::Input;
+
+// Ctrl+F search over the active tab.
+// While `editing`, keystrokes go to the query; afterwards n/N step through matches.
+#[derive(Default)]
+pub struct Search {
+    pub query: Input,
+    pub regex: bool,
+    pub editing: bool,
+    // Show only matching lines instead of highlighting them in place
+    pub filter: bool,
+    // Index (into the tab's lines) of the match the view is parked on
+    pub current: Option<usize>,
+}
+
+pub enum Matcher {
+    Plain(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Plain(needle) => text.to_lowercase().contains(needle),
+            Matcher::Regex(re) => re.is_match(text),
+        }
+    }
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self {
+            editing: true,
+            ..Default::default()
+        }
+    }
+
+    /// `Ok(None)` for an empty query, `Err` with the message for an invalid regex.
+    pub fn matcher(&self) -> Result<Option<Matcher>, String> {
+        let query = self.query.value();
+        if query.is_empty() {
+            return Ok(None);
+        }
+
+        if self.regex {
+            RegexBuilder::new(query)
+                .case_insensitive(true)
+                .build()
+                .map(|re| Some(Matcher::Regex(re)))
+                .map_err(|e| e.to_string())
+        } else {
+            Ok(Some(Matcher::Plain(query.to_lowercase())))
+        }
+    }
+
+    pub fn title(&self) -> String {
+        let mut title = String::from("Search");
+        if self.regex {
+            title.push_str(" [regex]");
+        }
+        if self.filter {
+            title.push_str(" [filter]");
+        }
+        if self.editing {
+            title.push_str(" (Enter: find, Ctrl+R: regex, Esc: cancel)");
+        } else {
+            title.push_str(" (n/N: older/newer, Ctrl+F: filter, Esc: close)");
+        }
+        title
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::{bail, Context, Result};
+use keyring::Entry;
+
+// Optional storage for secrets (the Twitch token, API keys) in the system
+// keyring instead of plaintext files next to the bot, through the keyring
+// crate: Secret Service on Linux, the Keychain on macOS and the Credential
+// Manager on Windows. SECRET_STORE=keyring turns it on; when the keyring
+// can't be reached the files are used as before.
+
+const SERVICE: &str = "choui";
+
+// Settings that may come from the keyring when they aren't set anywhere else
+pub const SECRET_VARS: &[&str] = &[
+    "GEMINI_API_KEY",
+    "ELEVENLABS_API_KEY",
+    "OPENAI_API_KEY",
+    "AZURE_SPEECH_KEY",
+    "API_TOKEN",
+    "MATRIX_TOKEN",
+    "MQTT_PASSWORD",
+    "STREAMELEMENTS_TOKEN",
+    "STREAMLABS_TOKEN",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretStore {
+    File,
+    Keyring,
+}
+
+impl SecretStore {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "file" => Ok(Self::File),
+            "keyring" => Ok(Self::Keyring),
+            other => bail!("SECRET_STORE must be file or keyring, got '{}'", other),
+        }
+    }
+
+    // Config::from_env reports invalid values, here they just mean files
+    pub fn current() -> Self {
+        crate::config::var("SECRET_STORE")
+            .ok()
+            .and_then(|value| Self::parse(&value).ok())
+            .unwrap_or(Self::File)
+    }
+}
+
+/// Look up a secret. Ok(None) if the keyring has no entry for it.
+pub fn get(name: &str) -> Result<Option<String>> {
+    keyring(|| match entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {} from the keyring", name)),
+    })
+}
+
+/// Store a secret, replacing any earlier value.
+pub fn set(name: &str, value: &str) -> Result<()> {
+    keyring(|| {
+        entry(name)?
+            .set_password(value)
+            .with_context(|| format!("Failed to store {} in the keyring", name))
+    })
+}
+
+/// Remove a secret. Removing one that isn't there is not an error.
+pub fn delete(name: &str) -> Result<()> {
+    keyring(|| match entry(name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to remove {} from the keyring", name)),
+    })
+}
+
+fn entry(name: &str) -> Result<Entry> {
+    Entry::new(SERVICE, name).context("Keyring not available")
+}
+
+// The Secret Service client blocks on a tokio runtime of its own, which
+// tokio refuses to do from inside the bot's runtime, so every call gets a
+// thread of its own. Secrets are read and written rarely enough for that.
+fn keyring<T: Send>(call: impl FnOnce() -> Result<T> + Send) -> Result<T> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(call)
+            .join()
+            .unwrap_or_else(|_| bail!("Keyring access panicked"))
+    })
+}
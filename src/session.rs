@@ -0,0 +1,928 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{
+    api::{AiAction, ApiCall, ApiRequest},
+    audio,
+    auto_replies::{AutoReplies, AutoReply},
+    bot::{BotCore, SHUTDOWN_STEP},
+    chat_commands::ChatCommand,
+    chat_stages::{self, Chat, ChatContext},
+    chatlog::{ChatLog, Record},
+    config::{self, Config, VolumeChannel},
+    config_file::ConfigFile,
+    counters::Counters,
+    custom_commands::CustomCommands,
+    features::{
+        announce_poll, end_chat_poll, execute_command, finish_poll, handle_ai_request, plugins,
+        raise_shield, react_to_chat_speed, reload_config, remove_link, run_api_request,
+        run_chat_command, run_plugin_actions, save_ui_state, send_ai_reply, send_leaderboards,
+        skip_song, spawn_ai_request, thank_tipper, watch_for_raids, welcome_raid, ChatFeatures,
+    },
+    follows,
+    keys::Keymap,
+    leaderboards::{Board, Leaderboards},
+    links::Permits,
+    memory::Memory,
+    minigames::Games,
+    modlog::{self, ModLog},
+    outbox::Priority,
+    pipeline::Pipeline,
+    plugins::{PluginEvent, Plugins},
+    quotes::Quotes,
+    shield::{ShieldAction, Trigger},
+    songs, sqlite,
+    state::{
+        AiStatus, App, AppEvent, ChatMessage, ConnectionState, Service, Severity, StreamAlert, Tab,
+        UiState,
+    },
+    timers::Timers,
+    translate::{self, OptOuts},
+    tts::SpeechKind,
+    twitch::{download_emote, emote_cdn_url},
+    viewers::{Arrival, Viewers},
+};
+
+// What the bot does with its events, whichever frontend shows them: the
+// terminal UI, the overlay or none. The frontend owns the event loop and
+// hands everything that comes in to a Session: the bot's events, the HTTP
+// API's and Matrix's calls, the once a second tick and, in the terminal,
+// keys and the mouse (see input.rs).
+pub struct Session {
+    // What the terminal UI draws
+    pub app: App,
+    pub(crate) tx: mpsc::UnboundedSender<AppEvent>,
+    // The overlay's, Matrix's and MQTT's copy of every event
+    pub(crate) broadcast_tx: broadcast::Sender<AppEvent>,
+    pub(crate) client: reqwest::Client,
+    pub(crate) irc_tx: mpsc::UnboundedSender<String>,
+    pub(crate) chat_log: ChatLog,
+    pub(crate) mod_log: ModLog,
+    pub(crate) sounds: audio::AudioEngine,
+    pub(crate) tts: audio::TtsQueue,
+    pub(crate) features: ChatFeatures,
+    pub(crate) plugins: Plugins,
+    // What happens to each chat message, minus SKIP_STAGES
+    pub(crate) pipeline: Pipeline<Chat>,
+    pub(crate) timers: Timers,
+    pub(crate) keymap: Keymap,
+    // Abort handles of in-flight AI requests, by request id
+    pub(crate) ai_tasks: HashMap<u64, tokio::task::AbortHandle>,
+    // As read, before the ids get resolved; reloads are compared against it
+    pub(crate) loaded_config: Config,
+}
+
+impl Session {
+    // Opens the databases and starts the audio. `bot` has subscribed
+    // already; `silent` is for the benchmark's TTS.
+    pub fn new(
+        mut app: App,
+        bot: &BotCore,
+        broadcast_tx: broadcast::Sender<AppEvent>,
+        ui_state: &UiState,
+        loaded_config: Config,
+        silent: bool,
+    ) -> Result<Self> {
+        let tx = bot.sender();
+        let client = bot.client.clone();
+        let irc_tx = bot
+            .irc()
+            .expect("subscribed before the session starts")
+            .clone();
+        let chat_log = match &app.config.chat_log {
+            Some(path) => {
+                let channel = app.config.channel_name.clone().unwrap_or_default();
+                ChatLog::open(path, channel, tx.clone()).unwrap_or_else(|e| {
+                    app.notify(Severity::Warning, format!("Chat log off: {:#}", e));
+                    ChatLog::disabled()
+                })
+            }
+            None => ChatLog::disabled(),
+        };
+        let channel = app.config.channel_name.clone().unwrap_or_default();
+        let mod_log = ModLog::open(
+            &app.config.database(),
+            channel,
+            app.bot_login.clone(),
+            tx.clone(),
+        )
+        .unwrap_or_else(|e| {
+            app.notify(Severity::Warning, format!("Mod log off: {:#}", e));
+            ModLog::disabled()
+        });
+        let params = &app.config.ai_params;
+        let (turns, ttl) = (params.memory_turns, params.memory_ttl);
+        app.ai_memory = Memory::open(&app.config.database(), turns, ttl, tx.clone())
+            .unwrap_or_else(|e| {
+                app.notify(
+                    Severity::Warning,
+                    format!("AI memory won't survive a restart: {:#}", e),
+                );
+                Memory::new(turns, ttl)
+            });
+        // The Moderation tab picks up where the last session left off
+        if let Ok(entries) = modlog::query(&app.config.database(), None, 50) {
+            for entry in entries {
+                app.push_quiet(Tab::Moderation, entry.describe());
+            }
+        }
+        let features = ChatFeatures {
+            client: client.clone(),
+            quotes: Quotes::open(&app.config.database())
+                .map_err(|e| app.notify(Severity::Warning, format!("Quotes off: {:#}", e)))
+                .ok(),
+            custom_commands: CustomCommands::open(&app.config.database(), tx.clone())
+                .map_err(|e| app.notify(Severity::Warning, format!("Custom commands off: {:#}", e)))
+                .ok(),
+            counters: Counters::open(&app.config.database(), tx.clone())
+                .map_err(|e| app.notify(Severity::Warning, format!("Counters off: {:#}", e)))
+                .ok(),
+            games: Games::open(&app.config.database(), tx.clone())
+                .map_err(|e| app.notify(Severity::Warning, format!("Minigames off: {:#}", e)))
+                .ok(),
+            viewers: Viewers::open(&app.config.database(), tx.clone())
+                .map_err(|e| {
+                    app.notify(
+                        Severity::Warning,
+                        format!("Returning viewers won't be recognized: {:#}", e),
+                    )
+                })
+                .ok(),
+            leaderboards: Leaderboards::open(&app.config.database(), tx.clone())
+                .map_err(|e| app.notify(Severity::Warning, format!("Leaderboards off: {:#}", e)))
+                .ok(),
+            opt_outs: OptOuts::open(&app.config.database(), tx.clone())
+                .map_err(|e| {
+                    app.notify(
+                        Severity::Warning,
+                        format!("Translation opt-outs won't be kept: {:#}", e),
+                    )
+                })
+                .ok(),
+            player: songs::start_player(&app.config.songs.player, tx.clone()),
+            permits: Permits::default(),
+            auto_replies: AutoReplies::default(),
+        };
+        // Their values for the overlay
+        for (name, value) in features.counters.iter().flat_map(Counters::iter) {
+            let _ = tx.send(AppEvent::Counter {
+                name: name.to_string(),
+                value,
+            });
+        }
+        if let Some(leaderboards) = &features.leaderboards {
+            send_leaderboards(leaderboards, &tx, &Board::ALL);
+        }
+        let sounds = audio::AudioEngine::start(tx.clone(), app.volumes);
+        let tts = audio::TtsQueue::start(
+            tx.clone(),
+            app.config.tts.clone(),
+            silent,
+            app.config.tts_queue_max,
+            app.volumes.tts,
+            sounds.clone(),
+        );
+        if !app.tts_enabled {
+            let _ = tx.send(AppEvent::TtsMuted(true));
+        }
+
+        // Goal progress survives restarts as long as [goal] stays the same
+        let file = ConfigFile::load(&app.config.config_file)?.unwrap_or_default();
+        app.goal = config::goal_from_file(&file)?;
+        let keymap = Keymap::from_file(&file)?;
+        if let Some(goal) = &mut app.goal {
+            if let Some(saved) = &ui_state.goal {
+                goal.restore(saved);
+            }
+            let _ = tx.send(AppEvent::Goal(goal.clone()));
+        }
+
+        let timers = Timers::new(&app.config.timers, app.config.timer_jitter);
+        let plugins = plugins(&app.config);
+        if !plugins.names().is_empty() {
+            app.push(Tab::Log, format!("Plugins: {}", plugins.names().join(", ")));
+        }
+        let mut pipeline = chat_stages::pipeline();
+        if let Err(e) = pipeline.skip(&app.config.skip_stages) {
+            app.notify(Severity::Warning, format!("SKIP_STAGES: {:#}", e));
+        }
+        Ok(Self {
+            app,
+            tx,
+            broadcast_tx,
+            client,
+            irc_tx,
+            chat_log,
+            mod_log,
+            sounds,
+            tts,
+            features,
+            plugins,
+            pipeline,
+            timers,
+            keymap,
+            ai_tasks: HashMap::new(),
+            loaded_config,
+        })
+    }
+
+    // Once a second: elapsed times on the AI tab, polls running out, the
+    // timed messages and the checks that go with them. Returns whether to
+    // redraw.
+    pub fn tick(&mut self) -> bool {
+        let Session {
+            app,
+            tx,
+            client,
+            sounds,
+            tts,
+            features,
+            plugins,
+            timers,
+            ai_tasks,
+            ..
+        } = self;
+        // Elapsed times on the AI tab and the status bar indicator
+        let mut redraw = app.ai_in_flight();
+        if let Some(poll) = app.poll.as_ref().filter(|poll| !poll.ended) {
+            // Time left in the status bar; chat polls end here, native ones on Twitch
+            redraw = true;
+            if poll.native_id.is_none() && poll.time_left().is_zero() {
+                end_chat_poll(app, tx);
+            }
+        }
+        if let Some(message) = timers.due() {
+            app.outbox.say(Priority::Low, message);
+        }
+        react_to_chat_speed(app, tx, client);
+        watch_for_raids(app, tx, client);
+        if let Some(users) = app
+            .follow_thanks
+            .due(std::time::Instant::now(), &app.config.follows)
+        {
+            let (config, outbox) = (app.config.clone(), app.outbox.clone());
+            tokio::spawn(async move {
+                outbox.say(Priority::Normal, follows::thank(&users, &config).await);
+            });
+        }
+        let actions = plugins.dispatch(&PluginEvent::Tick);
+        run_plugin_actions(app, tx, ai_tasks, sounds, tts, features, actions);
+        redraw
+    }
+
+    // A call from the HTTP API or Matrix (see api.rs)
+    pub fn handle_api(&mut self, call: ApiCall) {
+        let Session {
+            app,
+            tx,
+            broadcast_tx,
+            client,
+            irc_tx,
+            sounds,
+            tts,
+            timers,
+            ai_tasks,
+            ..
+        } = self;
+
+        let result = match call.request {
+            ApiRequest::Moderate(command) => {
+                execute_command(app, tx, client, irc_tx, command);
+                Ok(serde_json::json!({ "ok": true }))
+            }
+            ApiRequest::Ai { id, action } => {
+                if handle_ai_request(app, tx, ai_tasks, id, action) {
+                    Ok(serde_json::json!({ "ok": true }))
+                } else {
+                    let done = match action {
+                        AiAction::Approve => "approved",
+                        AiAction::Reject => "rejected",
+                        AiAction::Retry => "retried",
+                    };
+                    Err(format!("AI request #{} can't be {} now", id, done))
+                }
+            }
+            request => run_api_request(app, tx, broadcast_tx, tts, sounds, timers, request),
+        };
+        let _ = call.reply.send(result);
+    }
+
+    // One of the bot's events; all of them go on to the overlay too. Returns
+    // whether to redraw.
+    pub fn handle_event(&mut self, event: AppEvent) -> bool {
+        let _ = self.broadcast_tx.send(event.clone());
+        let redraw = self.app.redraws_for(&event);
+        self.dispatch(event);
+        redraw
+    }
+
+    fn dispatch(&mut self, event: AppEvent) {
+        let Session {
+            app,
+            tx,
+            client,
+            chat_log,
+            mod_log,
+            sounds,
+            tts,
+            features,
+            plugins,
+            pipeline,
+            timers,
+            keymap,
+            ai_tasks,
+            loaded_config,
+            ..
+        } = self;
+        match event {
+            AppEvent::ChatMessage(message) => {
+                let mut cx = Stages {
+                    app,
+                    tx,
+                    client,
+                    chat_log,
+                    sounds,
+                    tts,
+                    timers,
+                    plugins,
+                    features,
+                    ai_tasks,
+                    prompt: None,
+                };
+                pipeline.run(&mut cx, &message);
+            }
+            AppEvent::UserJoined(user) => {
+                chat_log.record(Record::Event {
+                    kind: "join",
+                    user: Some(user.clone()),
+                    detail: String::new(),
+                });
+                app.push(Tab::Chat, format!("-> {} joined", user));
+                app.chatter(&user).present = true;
+                if app.config.shield.enabled {
+                    app.shield.join(&user, std::time::Instant::now());
+                }
+                sounds.play(
+                    audio::Sound::File(app.config.join_sound.clone()),
+                    VolumeChannel::Join,
+                );
+                // Joins come in late, often after a viewer's !lurk
+                let lurking = app.lurkers.is_lurking(&user);
+
+                // TTS: Announce the join (runs in bot thread, plays regardless of focus)
+                if app.tts_enabled && !lurking && !app.shielded(ShieldAction::MuteTts) {
+                    let join_msg = "has joined the chat!";
+                    tts.speak(
+                        SpeechKind::Join,
+                        &user,
+                        join_msg,
+                        format!("{} {}", user, join_msg),
+                    );
+                }
+                let actions = plugins.dispatch(&PluginEvent::Join { user: user.clone() });
+                run_plugin_actions(app, tx, ai_tasks, sounds, tts, features, actions);
+                // Generate AI Greeting, for first-timers and regulars coming back too
+                let triggers = &app.config.ai_triggers;
+                let arrival = features.viewers.as_mut().and_then(|viewers| {
+                    viewers.arrive(&user, triggers.regular_streams, triggers.returning_after)
+                });
+                // Not during a hate raid, when the joins are likely bots
+                if triggers.greet_joins && !lurking && !app.shielded(ShieldAction::PauseAi) {
+                    let prompt = match arrival {
+                                Some(Arrival::New) => format!("User {} just joined for the first time ever. Welcome them to the community excitedly with a single short sentence. Do not ask any questions.", user),
+                                Some(Arrival::Returning { missed }) => format!("User {}, a regular who missed the last {} streams, just came back. Welcome them back excitedly with a single short sentence. Do not ask any questions.", user, missed),
+                                _ => format!("User {} just joined. Welcome them excitedly with a single short sentence. Do not ask any questions.", user),
+                            };
+                    spawn_ai_request(app, tx, ai_tasks, &user, prompt, Priority::Low);
+                }
+            }
+            // Only the overlay shows what TTS is reading
+            AppEvent::TtsStarted { .. } | AppEvent::TtsFinished(_) | AppEvent::TtsMuted(_) => {}
+            AppEvent::OverlayConfig(_) => {
+                app.push(Tab::Log, "Overlay config reloaded".to_string());
+            }
+            AppEvent::ConfigReloaded(config) => {
+                reload_config(
+                    app,
+                    sounds,
+                    tts,
+                    timers,
+                    loaded_config,
+                    Arc::unwrap_or_clone(config),
+                );
+                if let Err(e) = pipeline.skip(&app.config.skip_stages) {
+                    app.notify(Severity::Warning, format!("SKIP_STAGES: {:#}", e));
+                }
+                let file = ConfigFile::load(&app.config.config_file);
+                match file.and_then(|file| Keymap::from_file(&file.unwrap_or_default())) {
+                    Ok(reloaded) => *keymap = reloaded,
+                    Err(e) => app.push(Tab::Log, format!("Key bindings not reloaded: {:#}", e)),
+                }
+            }
+            AppEvent::Alert(alert) => {
+                chat_log.record(Record::Event {
+                    kind: alert.kind().name(),
+                    user: alert.user().map(String::from),
+                    detail: alert.describe(),
+                });
+                app.push(Tab::Chat, format!("** {}", alert.describe()));
+                if let StreamAlert::Raid {
+                    from,
+                    from_id,
+                    viewers,
+                } = &alert
+                {
+                    welcome_raid(
+                        &app.config,
+                        tx,
+                        &app.outbox,
+                        client,
+                        from,
+                        from_id,
+                        *viewers,
+                    );
+                }
+                if matches!(alert, StreamAlert::Tip { .. }) {
+                    thank_tipper(app, tx, ai_tasks, tts, &alert);
+                }
+                if let Some(leaderboards) = features.leaderboards.as_mut() {
+                    if let Some(board) = leaderboards.record(&alert) {
+                        send_leaderboards(leaderboards, tx, &[board]);
+                    }
+                }
+                if let StreamAlert::Follow { user } = &alert {
+                    if app.config.follows.thank {
+                        app.follow_thanks.add(user, std::time::Instant::now());
+                    }
+                }
+                let actions = plugins.dispatch(&PluginEvent::Alert(alert.clone()));
+                run_plugin_actions(app, tx, ai_tasks, sounds, tts, features, actions);
+                if let Some(goal) = &mut app.goal {
+                    let before = goal.current;
+                    let completed = goal.count(&alert);
+                    if goal.current != before {
+                        let _ = tx.send(AppEvent::Goal(goal.clone()));
+                        if completed {
+                            let label = goal.label.clone();
+                            app.push(Tab::Chat, format!("** {} reached!", label));
+                            sounds.play(audio::Sound::Fanfare, VolumeChannel::Alert);
+                        }
+                        save_ui_state(app);
+                    }
+                }
+            }
+            AppEvent::Redemption {
+                user,
+                reward_id,
+                reward,
+                input,
+            } => {
+                let detail = if input.is_empty() {
+                    reward.clone()
+                } else {
+                    format!("{}: {}", reward, input)
+                };
+                chat_log.record(Record::Event {
+                    kind: "redemption",
+                    user: Some(user.clone()),
+                    detail,
+                });
+                let actions = plugins.dispatch(&PluginEvent::Redemption {
+                    user: user.clone(),
+                    reward: reward.clone(),
+                    input: input.clone(),
+                });
+                run_plugin_actions(app, tx, ai_tasks, sounds, tts, features, actions);
+                if input.is_empty() {
+                    app.push(Tab::Chat, format!("** {} redeemed {}", user, reward));
+                } else {
+                    app.push(
+                        Tab::Chat,
+                        format!("** {} redeemed {}: {}", user, reward, input),
+                    );
+                }
+                let is_tts_reward = app.config.tts_reward.as_ref().is_some_and(|wanted| {
+                    wanted.eq_ignore_ascii_case(&reward) || *wanted == reward_id
+                });
+                if is_tts_reward && !input.trim().is_empty() {
+                    let reply = if app.tts_enabled {
+                        tts.speak_next(
+                            SpeechKind::Redeem,
+                            &user,
+                            &input,
+                            format!("{} says: {}", user, input),
+                        );
+                        format!("@{} your message is up next!", user)
+                    } else {
+                        format!(
+                            "@{} TTS is paused right now, so your message wasn't read.",
+                            user
+                        )
+                    };
+                    app.outbox.say(Priority::Normal, reply);
+                }
+            }
+            // Already applied to app.goal; sent on for the overlay
+            AppEvent::Goal(_) => {}
+            // For the overlay
+            AppEvent::Counter { .. } | AppEvent::Leaderboard(_) | AppEvent::Shield(_) => {}
+            AppEvent::FreshAccounts(users) => {
+                app.push(
+                    Tab::Log,
+                    format!("New accounts joined: {}", users.join(", ")),
+                );
+                let now = std::time::Instant::now();
+                if let Some(trigger) = app
+                    .shield
+                    .fresh_joined(users.len(), now, &app.config.shield)
+                {
+                    raise_shield(app, tx, client, trigger);
+                }
+            }
+            AppEvent::Translated {
+                user,
+                language,
+                text,
+            } => {
+                app.push(Tab::Chat, format!("↳ {} ({}): {}", user, language, text));
+            }
+            // Sent when the queue moves; already shown in the TUI
+            AppEvent::Songs { .. } => {}
+            AppEvent::SongFinished => {
+                skip_song(app, tx, features.player.as_ref());
+            }
+            AppEvent::Poll(poll) => {
+                // Chat polls are counted in place, their events are for the
+                // overlay. New polls and native polls' votes from Helix land here.
+                let current = app.poll.as_ref().filter(|current| current.id == poll.id);
+                if current.is_none_or(|current| current.native_id.is_some()) {
+                    let was_open = current.is_some_and(|current| !current.ended);
+                    if current.is_none() {
+                        announce_poll(app, &poll);
+                    }
+                    let ended = poll.ended;
+                    app.poll = Some(poll);
+                    if ended && was_open {
+                        finish_poll(app);
+                    }
+                }
+            }
+            AppEvent::UserLeft(user) => {
+                chat_log.record(Record::Event {
+                    kind: "leave",
+                    user: Some(user.clone()),
+                    detail: String::new(),
+                });
+                app.push(Tab::Chat, format!("<- {} left", user));
+                app.chatter(&user).present = false;
+                // Leaving ends a lurk; the time still counts
+                if let Some(lurked) = app.lurkers.stop(&user) {
+                    if let Some(viewers) = features.viewers.as_mut() {
+                        viewers.add_watch_time(&user, lurked);
+                    }
+                }
+            }
+            AppEvent::ChatterList(logins) => {
+                // Get Chatters is authoritative; JOIN/PART only fill in between polls
+                for chatter in app.chatters.values_mut() {
+                    chatter.present = false;
+                }
+                for login in logins {
+                    app.chatter(&login).present = true;
+                }
+            }
+            AppEvent::ChatterRoles(roles) => {
+                for (login, role) in roles {
+                    let chatter = app.chatter(&login);
+                    chatter.role = chatter.role.min(role);
+                }
+            }
+            AppEvent::EmoteImage(name, dyn_img) => {
+                app.add_emote_image(name.clone(), dyn_img.clone());
+                app.add_inline_emote(name, dyn_img);
+            }
+            AppEvent::EmoteEncoded {
+                name,
+                size,
+                protocol,
+                result,
+            } => {
+                app.emote_encoded(name, size, protocol, result);
+            }
+            AppEvent::EmotesLoading { loaded, total } => {
+                app.emote_progress = (loaded < total).then_some((loaded, total));
+            }
+            AppEvent::InlineEmote(name, dyn_img) => {
+                app.add_inline_emote(name, dyn_img);
+            }
+            AppEvent::Error(msg) => {
+                app.notify(Severity::Error, msg);
+            }
+            AppEvent::Info(msg) => {
+                app.notify(Severity::Info, msg);
+            }
+            AppEvent::SendFailed { message, error } => {
+                app.send_failed(message, &error);
+            }
+            AppEvent::Debug(msg) => {
+                app.push_quiet(Tab::Log, msg);
+            }
+            AppEvent::Moderation(event) => {
+                // The echo of the bot's own action was shown already
+                if !mod_log.record(&event) {
+                    return;
+                }
+                let msg = event.describe();
+                app.push(Tab::Moderation, msg.clone());
+                app.push(Tab::Log, format!("Moderation: {}", msg));
+            }
+            AppEvent::AutoModHeld(held) => {
+                app.held.hold(held);
+            }
+            AppEvent::AutoModResolved {
+                id,
+                status,
+                moderator,
+            } => {
+                // Gone already if the bot decided on it
+                if let Some(held) = app.held.resolve(&id) {
+                    let by = moderator
+                        .map(|moderator| format!(" by {}", moderator))
+                        .unwrap_or_default();
+                    app.push(
+                        Tab::Moderation,
+                        format!(
+                            "automod {}'s message {}{}: {}",
+                            held.user, status, by, held.text
+                        ),
+                    );
+                }
+            }
+            AppEvent::AiStarted { .. } => {}
+            AppEvent::AiFinished { id, result } => {
+                ai_tasks.remove(&id);
+                // Ignore late results for requests cancelled in the meantime
+                let Some((user, prompt, priority)) = app
+                    .ai_request(id)
+                    .filter(|r| r.status == AiStatus::Pending)
+                    .map(|r| (r.user.clone(), r.prompt.clone(), r.priority))
+                else {
+                    return;
+                };
+                let (status, line) = match result {
+                    Ok(reply) if app.config.ai_require_approval => {
+                        let line = format!("<- #{} (awaiting approval) {}", id, reply);
+                        (AiStatus::AwaitingApproval(reply), line)
+                    }
+                    Ok(reply) => {
+                        send_ai_reply(&app.outbox, priority, &user, &reply);
+                        app.ai_memory.remember(&user, &prompt, &reply);
+                        let line = format!("<- #{} {}", id, reply);
+                        (AiStatus::Sent(reply), line)
+                    }
+                    Err(e) => {
+                        let line = format!("!! #{} {}", id, e);
+                        (AiStatus::Failed(e), line)
+                    }
+                };
+                if let Some(request) = app.ai_request(id) {
+                    request.status = status;
+                    request.finished = Some(std::time::Instant::now());
+                }
+                app.push(Tab::Ai, line);
+            }
+            AppEvent::Connection(service, state) => {
+                if state == ConnectionState::Disconnected {
+                    app.notify(Severity::Warning, format!("{:?} connection lost", service));
+                } else {
+                    app.push(Tab::Log, format!("{:?}: {:?}", service, state));
+                }
+                match service {
+                    Service::EventSub => app.eventsub = state,
+                    Service::Irc => app.irc = state,
+                }
+            }
+            AppEvent::StreamStats(stats) => {
+                match &stats {
+                    // The first sample only sets the baseline, so starting the bot
+                    // mid-stream doesn't fire every milestone below the current count
+                    Some(stats) if app.stream.is_some() => {
+                        for &viewers in &app.config.viewer_milestones {
+                            if app.viewer_peak < viewers && viewers <= stats.viewer_count {
+                                let _ = tx.send(AppEvent::Alert(StreamAlert::ViewerMilestone {
+                                    viewers,
+                                }));
+                            }
+                        }
+                        app.viewer_peak = app.viewer_peak.max(stats.viewer_count);
+                    }
+                    Some(stats) => app.viewer_peak = stats.viewer_count,
+                    // Milestones count again next stream
+                    None => app.viewer_peak = 0,
+                }
+                if let Some(viewers) = features.viewers.as_mut() {
+                    let started = stats.as_ref().map(|stats| stats.started_at.to_string());
+                    viewers.set_stream(started.as_deref());
+                }
+                if let Some(leaderboards) = features.leaderboards.as_mut() {
+                    let started = stats.as_ref().map(|stats| stats.started_at.to_string());
+                    if leaderboards.set_stream(started.as_deref()) {
+                        send_leaderboards(leaderboards, tx, &Board::ALL);
+                    }
+                }
+                app.stream = stats;
+            }
+        }
+    }
+
+    // Nothing new gets started or said. Next the bot says goodbye, then the
+    // session closes.
+    pub fn stop(&self) {
+        for task in self.ai_tasks.values() {
+            task.abort();
+        }
+        self.tts.clear();
+        self.tts.skip();
+        self.sounds.stop();
+    }
+
+    // Returns whether everything queued for the database was written
+    pub fn close(mut self, save_ui: bool) -> bool {
+        if save_ui {
+            save_ui_state(&mut self.app);
+        }
+        // Lurkers still here get their time so far
+        for (user, lurked) in self.app.lurkers.stop_all() {
+            if let Some(viewers) = self.features.viewers.as_mut() {
+                viewers.add_watch_time(&user, lurked);
+            }
+        }
+        // Everything that writes to the database goes, so the writers can
+        // finish what's queued and stop
+        drop(self);
+        tokio::task::block_in_place(|| sqlite::wait_for_writers(SHUTDOWN_STEP))
+    }
+}
+
+// What the chat stages get of the session, borrowed for one message
+struct Stages<'a> {
+    app: &'a mut App,
+    tx: &'a mpsc::UnboundedSender<AppEvent>,
+    client: &'a reqwest::Client,
+    chat_log: &'a ChatLog,
+    sounds: &'a audio::AudioEngine,
+    tts: &'a audio::TtsQueue,
+    timers: &'a mut Timers,
+    plugins: &'a mut Plugins,
+    features: &'a mut ChatFeatures,
+    ai_tasks: &'a mut HashMap<u64, tokio::task::AbortHandle>,
+    prompt: Option<String>,
+}
+
+impl ChatContext for Stages<'_> {
+    fn app(&self) -> &App {
+        self.app
+    }
+
+    fn app_mut(&mut self) -> &mut App {
+        self.app
+    }
+
+    fn send(&self, event: AppEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    fn record(&self, record: Record) {
+        self.chat_log.record(record);
+    }
+
+    fn play(&self, sound: audio::Sound, channel: VolumeChannel) {
+        self.sounds.play(sound, channel);
+    }
+
+    fn speak(&self, kind: SpeechKind, user: &str, text: &str) {
+        self.tts
+            .speak(kind, user, text, format!("{} says: {}", user, text));
+    }
+
+    fn fetch_emote(&self, name: &str, id: &str) {
+        let (client, tx) = (self.client.clone(), self.tx.clone());
+        let (name, url) = (name.to_string(), emote_cdn_url(id));
+        tokio::spawn(async move {
+            if let Ok(bytes) = download_emote(&client, &url).await {
+                if let Ok(img) = image::load_from_memory(&bytes) {
+                    let _ = tx.send(AppEvent::InlineEmote(name, Arc::new(img)));
+                }
+            }
+        });
+    }
+
+    fn raise_shield(&mut self, trigger: Trigger) {
+        raise_shield(self.app, self.tx, self.client, trigger);
+    }
+
+    fn permitted(&mut self, user: &str) -> bool {
+        self.features.permits.allows(&self.app.config.links, user)
+    }
+
+    fn remove_link(&mut self, message: &ChatMessage, link: &str) {
+        remove_link(
+            self.app,
+            self.tx,
+            self.client,
+            &message.user,
+            &message.id,
+            link,
+        );
+    }
+
+    fn run_plugins(&mut self, message: &ChatMessage) -> bool {
+        let actions = self.plugins.dispatch(&PluginEvent::Chat(message.clone()));
+        run_plugin_actions(
+            self.app,
+            self.tx,
+            self.ai_tasks,
+            self.sounds,
+            self.tts,
+            self.features,
+            actions,
+        )
+    }
+
+    fn count_message(&mut self, user: &str) {
+        self.timers.count_message();
+        let settings = &self.app.config.games;
+        if let Some(games) = self.features.games.as_mut().filter(|_| settings.enabled) {
+            games.earn(settings, user);
+        }
+    }
+
+    fn opted_out(&self, user: &str) -> bool {
+        let opt_outs = self.features.opt_outs.as_ref();
+        opt_outs.is_some_and(|opt_outs| opt_outs.contains(user))
+    }
+
+    fn translate(&self, message: &ChatMessage, language: &'static str) {
+        let (config, outbox, tx) = (
+            self.app.config.clone(),
+            self.app.outbox.clone(),
+            self.tx.clone(),
+        );
+        let (user, text) = (message.user.clone(), message.text.clone());
+        tokio::spawn(async move {
+            let settings = &config.translate;
+            match translate::translate(&text, &settings.language, &config).await {
+                Ok(translation) if settings.to_chat => {
+                    outbox.say(
+                        Priority::Low,
+                        format!("{} ({}): {}", user, language, translation),
+                    );
+                }
+                Ok(translation) => {
+                    let _ = tx.send(AppEvent::Translated {
+                        user,
+                        language: language.to_string(),
+                        text: translation,
+                    });
+                }
+                Err(e) => {
+                    let _ = tx.send(AppEvent::Debug(format!("Translating failed: {:#}", e)));
+                }
+            }
+        });
+    }
+
+    fn run_command(&mut self, message: &ChatMessage, command: ChatCommand) -> bool {
+        run_chat_command(
+            self.app,
+            self.tx,
+            self.features,
+            &message.user,
+            message.role(),
+            command,
+        )
+    }
+
+    fn auto_reply(&mut self, reply: &AutoReply, user: &str) -> Option<String> {
+        let cooldown = self.app.config.auto_reply_cooldown;
+        self.features.auto_replies.give(reply, cooldown, user)
+    }
+
+    fn ask_ai(&mut self, user: &str, prompt: String) {
+        spawn_ai_request(
+            self.app,
+            self.tx,
+            self.ai_tasks,
+            user,
+            prompt,
+            Priority::Normal,
+        );
+    }
+
+    fn prompt(&mut self) -> &mut Option<String> {
+        &mut self.prompt
+    }
+}
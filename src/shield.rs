@@ -0,0 +1,302 @@
+use crate::config::{env_flag, var};
+use anyhow::{bail, Context, Result};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Hate raids and bot spam. With SHIELD on, the last minute of chat is watched
+// for:
+//
+//   SHIELD_REPEATS       viewers sending the same message
+//   SHIELD_LINKS         messages with links (other than LINK_ALLOWED_DOMAINS)
+//   SHIELD_FRESH_JOINS   accounts younger than SHIELD_ACCOUNT_AGE days joining
+//
+// Mods, VIPs and the broadcaster don't count. Once any of them is reached the
+// shield goes up: the overlay and the TUI say so loudly, and SHIELD_ACTIONS
+// run:
+//
+//   shield_mode      Twitch's Shield Mode on
+//   followers_only   followers-only chat (followed FOLLOWERS_ONLY ago or more)
+//   mute_tts         chat isn't read aloud
+//   pause_ai         no AI replies to chat
+//
+// The shield comes down by itself once nothing set it off for SHIELD_DURATION
+// minutes (never with 0), or when a mod says !shield off; !shield on raises it
+// by hand. Coming down undoes the actions.
+//
+// Joins are only looked up on Helix (for the account's age) once enough of
+// them came in within the minute to matter.
+
+const WINDOW: Duration = Duration::from_secs(60);
+// Shorter messages ("lol", "GG", an emote) are left out of the repeats, as
+// real chat sends those together all the time
+const MIN_REPEAT_CHARS: usize = 10;
+// How long viewers must have followed to chat in followers-only mode
+pub const FOLLOWERS_ONLY: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShieldAction {
+    ShieldMode,
+    FollowersOnly,
+    MuteTts,
+    PauseAi,
+}
+
+impl ShieldAction {
+    const ALL: [ShieldAction; 4] = [
+        ShieldAction::ShieldMode,
+        ShieldAction::FollowersOnly,
+        ShieldAction::MuteTts,
+        ShieldAction::PauseAi,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ShieldAction::ShieldMode => "shield_mode",
+            ShieldAction::FollowersOnly => "followers_only",
+            ShieldAction::MuteTts => "mute_tts",
+            ShieldAction::PauseAi => "pause_ai",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShieldSettings {
+    pub enabled: bool,
+    // How many of each within a minute raise the shield; 0 leaves one out
+    pub repeats: u32,
+    pub links: u32,
+    pub fresh_joins: u32,
+    // Accounts younger than this are fresh
+    pub account_age: Duration,
+    pub actions: Vec<ShieldAction>,
+    // Without a new trigger for this long the shield comes down; zero keeps
+    // it up until !shield off
+    pub duration: Duration,
+}
+
+impl ShieldSettings {
+    // SHIELD, SHIELD_REPEATS, SHIELD_LINKS, SHIELD_FRESH_JOINS,
+    // SHIELD_ACCOUNT_AGE, SHIELD_ACTIONS, SHIELD_DURATION
+    pub fn from_env() -> Result<Self> {
+        let number = |name: &str, default: u32| -> Result<u32> {
+            match var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse::<u32>()
+                    .with_context(|| format!("{} must be a number, got '{}'", name, value)),
+                Err(_) => Ok(default),
+            }
+        };
+        let enabled = env_flag("SHIELD", false)?;
+        let mut actions = Vec::new();
+        let list =
+            var("SHIELD_ACTIONS").unwrap_or_else(|_| "shield_mode,mute_tts,pause_ai".to_string());
+        for name in list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let Some(action) = ShieldAction::ALL
+                .into_iter()
+                .find(|action| action.name().eq_ignore_ascii_case(name))
+            else {
+                bail!(
+                    "Unknown action '{}' in SHIELD_ACTIONS (expected shield_mode, followers_only, mute_tts or pause_ai)",
+                    name
+                );
+            };
+            actions.push(action);
+        }
+        Ok(Self {
+            enabled,
+            repeats: number("SHIELD_REPEATS", 5)?,
+            links: number("SHIELD_LINKS", 8)?,
+            fresh_joins: number("SHIELD_FRESH_JOINS", 10)?,
+            account_age: Duration::from_secs(
+                u64::from(number("SHIELD_ACCOUNT_AGE", 7)?) * 24 * 60 * 60,
+            ),
+            actions,
+            duration: Duration::from_secs(u64::from(number("SHIELD_DURATION", 10)?) * 60),
+        })
+    }
+
+    pub fn does(&self, action: ShieldAction) -> bool {
+        self.actions.contains(&action)
+    }
+}
+
+/// What raised the shield
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    Repeats { viewers: usize },
+    Links { messages: usize },
+    FreshJoins { accounts: usize },
+    // !shield on
+    Manual { moderator: String },
+}
+
+impl Trigger {
+    /// "6 viewers sent the same message"
+    pub fn describe(&self) -> String {
+        match self {
+            Trigger::Repeats { viewers } => format!("{} viewers sent the same message", viewers),
+            Trigger::Links { messages } => format!("{} links in a minute", messages),
+            Trigger::FreshJoins { accounts } => {
+                format!("{} brand-new accounts joined", accounts)
+            }
+            Trigger::Manual { moderator } => format!("raised by {}", moderator),
+        }
+    }
+}
+
+// "K Y S!!!" and "kys" alike: letters and digits only, lowercase
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn forget<T>(queue: &mut VecDeque<T>, now: Instant, at: impl Fn(&T) -> Instant) {
+    while queue
+        .front()
+        .is_some_and(|item| now.duration_since(at(item)) > WINDOW)
+    {
+        queue.pop_front();
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Shield {
+    // (when, who, what) of the last minute's messages long enough to compare
+    messages: VecDeque<(Instant, String, String)>,
+    // When each message with a link came in
+    links: VecDeque<Instant>,
+    // Joins of the last minute not looked up yet
+    joins: VecDeque<(Instant, String)>,
+    // When each fresh account was found to have joined
+    fresh: VecDeque<Instant>,
+    raised: bool,
+    last_trigger: Option<Instant>,
+}
+
+impl Shield {
+    /// Count a viewer's message. Returns what it set off, if anything.
+    pub fn message(
+        &mut self,
+        user: &str,
+        text: &str,
+        has_link: bool,
+        now: Instant,
+        settings: &ShieldSettings,
+    ) -> Option<Trigger> {
+        forget(&mut self.messages, now, |(at, _, _)| *at);
+        forget(&mut self.links, now, |at| *at);
+        if has_link {
+            self.links.push_back(now);
+            if settings.links > 0 && self.links.len() >= settings.links as usize {
+                return Some(Trigger::Links {
+                    messages: self.links.len(),
+                });
+            }
+        }
+        let text = normalize(text);
+        if text.chars().count() < MIN_REPEAT_CHARS {
+            return None;
+        }
+        self.messages
+            .push_back((now, user.to_lowercase(), text.clone()));
+        let mut senders: Vec<&str> = self
+            .messages
+            .iter()
+            .filter(|(_, _, sent)| *sent == text)
+            .map(|(_, sender, _)| sender.as_str())
+            .collect();
+        senders.sort_unstable();
+        senders.dedup();
+        (settings.repeats > 0 && senders.len() >= settings.repeats as usize).then_some(
+            Trigger::Repeats {
+                viewers: senders.len(),
+            },
+        )
+    }
+
+    pub fn join(&mut self, user: &str, now: Instant) {
+        self.joins.push_back((now, user.to_lowercase()));
+    }
+
+    /// The joins to look up, once SHIELD_FRESH_JOINS of them came in within
+    /// the minute; they're taken off the list
+    pub fn accounts_to_check(
+        &mut self,
+        now: Instant,
+        settings: &ShieldSettings,
+    ) -> Option<Vec<String>> {
+        forget(&mut self.joins, now, |(at, _)| *at);
+        if settings.fresh_joins == 0 || self.joins.len() < settings.fresh_joins as usize {
+            return None;
+        }
+        Some(self.joins.drain(..).map(|(_, user)| user).collect())
+    }
+
+    /// Count the fresh accounts among the joins looked up. Returns the
+    /// trigger once there were SHIELD_FRESH_JOINS of them within the minute.
+    pub fn fresh_joined(
+        &mut self,
+        accounts: usize,
+        now: Instant,
+        settings: &ShieldSettings,
+    ) -> Option<Trigger> {
+        forget(&mut self.fresh, now, |at| *at);
+        self.fresh.extend(std::iter::repeat_n(now, accounts));
+        (settings.fresh_joins > 0 && self.fresh.len() >= settings.fresh_joins as usize).then_some(
+            Trigger::FreshJoins {
+                accounts: self.fresh.len(),
+            },
+        )
+    }
+
+    /// Up it goes, or stays up for longer. True if it wasn't up yet.
+    pub fn raise(&mut self, now: Instant) -> bool {
+        self.last_trigger = Some(now);
+        !std::mem::replace(&mut self.raised, true)
+    }
+
+    /// Down it comes, and what was counted is forgotten, so the same spam
+    /// doesn't raise it again right away. True if it was up.
+    pub fn lower(&mut self) -> bool {
+        self.messages.clear();
+        self.links.clear();
+        self.joins.clear();
+        self.fresh.clear();
+        std::mem::replace(&mut self.raised, false)
+    }
+
+    pub fn is_raised(&self) -> bool {
+        self.raised
+    }
+
+    /// Whether it's time for it to come down by itself
+    pub fn expired(&self, now: Instant, settings: &ShieldSettings) -> bool {
+        self.raised
+            && !settings.duration.is_zero()
+            && self
+                .last_trigger
+                .is_some_and(|last| now.duration_since(last) >= settings.duration)
+    }
+}
+
+/// Who of `accounts` (login, created at) was created less than `max_age`
+/// before `now`
+pub fn fresh_accounts(
+    accounts: &[(String, jiff::Timestamp)],
+    now: jiff::Timestamp,
+    max_age: Duration,
+) -> Vec<String> {
+    let max_age = jiff::SignedDuration::try_from(max_age).unwrap_or(jiff::SignedDuration::MAX);
+    accounts
+        .iter()
+        .filter(|(_, created)| now.duration_since(*created) < max_age)
+        .map(|(login, _)| login.clone())
+        .collect()
+}
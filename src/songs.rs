@@ -0,0 +1,435 @@
+use crate::config::{env_flag, var};
+use crate::state::AppEvent;
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+// Song requests: !sr <link or search> puts a song in the queue, which the
+// Songs tab shows and reorders (Up/Down select, Ctrl+U promote, Ctrl+X
+// remove, Ctrl+N skip). The songs can play in a local player, mpv through its
+// JSON IPC socket (`mpv --idle --input-ipc-server=/tmp/mpvsocket`) or MPD,
+// which then moves the queue along by itself. Without one the queue is only
+// shown, in the TUI and the overlay's "now playing / up next", and moved
+// along by hand.
+//
+//   !sr <link or search>   request a song
+//   !song                  what's playing
+//   !queue                 what's coming up
+//   !wrongsong             take back your last request
+//   !skip                  next song (mods)
+
+const MAX_REQUEST_LEN: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SongPlayer {
+    // Show the queue only
+    None,
+    // Path of mpv's --input-ipc-server socket (a named pipe on Windows)
+    Mpv(PathBuf),
+    // host:port
+    Mpd(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SongSettings {
+    // !sr and the other song commands; off leaves them to other bots
+    pub enabled: bool,
+    // Songs one viewer may have queued (or playing) at once; mods aren't limited
+    pub user_limit: usize,
+    pub queue_max: usize,
+    pub player: SongPlayer,
+}
+
+impl SongSettings {
+    // SONG_REQUESTS, SONG_USER_LIMIT, SONG_QUEUE_MAX, SONG_PLAYER,
+    // SONG_MPV_SOCKET, SONG_MPD_ADDRESS
+    pub fn from_env() -> Result<Self> {
+        let number = |name: &str, default: usize| -> Result<usize> {
+            match var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .with_context(|| {
+                        format!("{} must be a positive number, got '{}'", name, value)
+                    }),
+                Err(_) => Ok(default),
+            }
+        };
+        let player = match var("SONG_PLAYER")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "none" => SongPlayer::None,
+            "mpv" => SongPlayer::Mpv(
+                var("SONG_MPV_SOCKET")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| default_mpv_socket()),
+            ),
+            "mpd" => SongPlayer::Mpd(
+                var("SONG_MPD_ADDRESS").unwrap_or_else(|_| "localhost:6600".to_string()),
+            ),
+            other => bail!("SONG_PLAYER must be none, mpv or mpd, got '{}'", other),
+        };
+        Ok(Self {
+            enabled: env_flag("SONG_REQUESTS", false)?,
+            user_limit: number("SONG_USER_LIMIT", 2)?,
+            queue_max: number("SONG_QUEUE_MAX", 25)?,
+            player,
+        })
+    }
+}
+
+fn default_mpv_socket() -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(r"\\.\pipe\mpvsocket")
+    } else {
+        PathBuf::from("/tmp/mpvsocket")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Song {
+    pub id: u64,
+    // A link, or words to search for
+    pub request: String,
+    pub user: String,
+}
+
+impl Song {
+    pub fn is_link(&self) -> bool {
+        self.request.starts_with("https://") || self.request.starts_with("http://")
+    }
+
+    // What mpv gets: searches go through its youtube-dl/yt-dlp hook
+    fn mpv_target(&self) -> String {
+        if self.is_link() {
+            self.request.clone()
+        } else {
+            format!("ytdl://ytsearch1:{}", self.request)
+        }
+    }
+
+    /// `never gonna give you up (by viewer)`
+    pub fn describe(&self) -> String {
+        format!("{} (by {})", self.request, self.user)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SongQueue {
+    pub now_playing: Option<Song>,
+    // Up next first
+    pub queue: Vec<Song>,
+    next_id: u64,
+}
+
+impl SongQueue {
+    /// Queue a request. Returns its place: 0 when it plays right away, 1 when
+    /// it's up next, and so on.
+    pub fn request(
+        &mut self,
+        settings: &SongSettings,
+        user: &str,
+        request: &str,
+        unlimited: bool,
+    ) -> Result<usize> {
+        let request = request.trim();
+        if request.is_empty() {
+            bail!("Usage: !sr <link or search>");
+        }
+        if request.chars().count() > MAX_REQUEST_LEN {
+            bail!("That request is too long");
+        }
+        let song = Song {
+            id: self.next_id,
+            request: request.to_string(),
+            user: user.to_string(),
+        };
+        if matches!(settings.player, SongPlayer::Mpd(_)) && !song.is_link() {
+            bail!("Only links can be requested here");
+        }
+        if self.queue.len() >= settings.queue_max {
+            bail!("The queue is full, try again later");
+        }
+        let requested = self
+            .queue
+            .iter()
+            .chain(&self.now_playing)
+            .filter(|song| song.user.eq_ignore_ascii_case(user))
+            .count();
+        if !unlimited && requested >= settings.user_limit {
+            bail!(
+                "You already have {} songs in the queue",
+                settings.user_limit
+            );
+        }
+        if self
+            .queue
+            .iter()
+            .chain(&self.now_playing)
+            .any(|queued| queued.request.eq_ignore_ascii_case(&song.request))
+        {
+            bail!("That one's already in the queue");
+        }
+        self.next_id += 1;
+        if self.now_playing.is_none() {
+            self.now_playing = Some(song);
+            return Ok(0);
+        }
+        self.queue.push(song);
+        Ok(self.queue.len())
+    }
+
+    /// Move on to the next song. Returns the one that was playing.
+    pub fn advance(&mut self) -> Option<Song> {
+        let next = (!self.queue.is_empty()).then(|| self.queue.remove(0));
+        std::mem::replace(&mut self.now_playing, next)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Song> {
+        (index < self.queue.len()).then(|| self.queue.remove(index))
+    }
+
+    /// Make the song at `index` the next one.
+    pub fn promote(&mut self, index: usize) -> Option<&Song> {
+        if index >= self.queue.len() {
+            return None;
+        }
+        let song = self.queue.remove(index);
+        self.queue.insert(0, song);
+        self.queue.first()
+    }
+
+    /// Take back the user's latest request that hasn't started playing.
+    pub fn wrong_song(&mut self, user: &str) -> Option<Song> {
+        let index = self
+            .queue
+            .iter()
+            .rposition(|song| song.user.eq_ignore_ascii_case(user))?;
+        self.remove(index)
+    }
+
+    pub fn up_next(&self) -> Option<&Song> {
+        self.queue.first()
+    }
+}
+
+pub enum PlayerCommand {
+    Play(Song),
+    Stop,
+}
+
+/// Start the player connection. It reports songs that played to the end as
+/// AppEvent::SongFinished and trouble as AppEvent::Error.
+pub fn start_player(
+    player: &SongPlayer,
+    events: UnboundedSender<AppEvent>,
+) -> Option<UnboundedSender<PlayerCommand>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    match player {
+        SongPlayer::None => return None,
+        SongPlayer::Mpv(socket) => {
+            tokio::spawn(run_mpv(socket.clone(), rx, events));
+        }
+        SongPlayer::Mpd(address) => {
+            tokio::spawn(run_mpd(address.clone(), rx, events));
+        }
+    }
+    Some(tx)
+}
+
+type Reader = Box<dyn AsyncRead + Unpin + Send>;
+type Writer = Box<dyn AsyncWrite + Unpin + Send>;
+
+#[cfg(unix)]
+async fn connect_mpv(socket: &std::path::Path) -> Result<(Reader, Writer)> {
+    let stream = tokio::net::UnixStream::connect(socket).await?;
+    let (read, write) = tokio::io::split(stream);
+    Ok((Box::new(read), Box::new(write)))
+}
+
+#[cfg(windows)]
+async fn connect_mpv(socket: &std::path::Path) -> Result<(Reader, Writer)> {
+    let pipe = tokio::net::windows::named_pipe::ClientOptions::new().open(socket)?;
+    let (read, write) = tokio::io::split(pipe);
+    Ok((Box::new(read), Box::new(write)))
+}
+
+// mpv is (re)connected whenever there's something to play, so it can be
+// started before or after choui
+async fn run_mpv(
+    socket: PathBuf,
+    mut commands: UnboundedReceiver<PlayerCommand>,
+    events: UnboundedSender<AppEvent>,
+) {
+    let mut connection: Option<Writer> = None;
+    while let Some(command) = commands.recv().await {
+        if connection.is_none() {
+            match connect_mpv(&socket).await {
+                Ok((read, write)) => {
+                    tokio::spawn(watch_mpv(read, events.clone()));
+                    connection = Some(write);
+                }
+                Err(e) => {
+                    let _ = events.send(AppEvent::Error(format!(
+                        "mpv not reachable at {} ({}); start it with --idle --input-ipc-server={}",
+                        socket.display(),
+                        e,
+                        socket.display()
+                    )));
+                    continue;
+                }
+            }
+        }
+        let request = match command {
+            PlayerCommand::Play(song) => {
+                serde_json::json!({ "command": ["loadfile", song.mpv_target(), "replace"] })
+            }
+            PlayerCommand::Stop => serde_json::json!({ "command": ["stop"] }),
+        };
+        let line = format!("{}\n", request);
+        if let Some(write) = connection.as_mut() {
+            if let Err(e) = write.write_all(line.as_bytes()).await {
+                let _ = events.send(AppEvent::Error(format!("mpv: {}", e)));
+                connection = None;
+            }
+        }
+    }
+}
+
+// Replacing or stopping a song also ends its file, so only the end of the
+// file itself (or an error playing it) moves the queue along
+async fn watch_mpv(read: Reader, events: UnboundedSender<AppEvent>) {
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        if message["event"] == "end-file" {
+            match message["reason"].as_str() {
+                Some("eof") => {
+                    let _ = events.send(AppEvent::SongFinished);
+                }
+                Some("error") => {
+                    let error = message["file_error"].as_str().unwrap_or("unknown error");
+                    let _ = events.send(AppEvent::Error(format!(
+                        "mpv couldn't play the song: {}",
+                        error
+                    )));
+                    let _ = events.send(AppEvent::SongFinished);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// MPD's protocol: a command per line, answered by lines ending with "OK" or
+// an "ACK ..." error
+async fn mpd_command(
+    lines: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+    write: &mut tokio::net::tcp::OwnedWriteHalf,
+    command: &str,
+) -> Result<Vec<String>> {
+    write.write_all(format!("{}\n", command).as_bytes()).await?;
+    let mut response = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        if line == "OK" {
+            return Ok(response);
+        }
+        if let Some(error) = line.strip_prefix("ACK ") {
+            bail!("{}", error);
+        }
+        response.push(line);
+    }
+    bail!("Connection closed")
+}
+
+async fn connect_mpd(
+    address: &str,
+) -> Result<(
+    tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+    tokio::net::tcp::OwnedWriteHalf,
+)> {
+    let stream = tokio::net::TcpStream::connect(address).await?;
+    let (read, write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let greeting = lines.next_line().await?.unwrap_or_default();
+    if !greeting.starts_with("OK MPD") {
+        bail!("Not an MPD server");
+    }
+    Ok((lines, write))
+}
+
+fn mpd_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// One connection per command, and one that waits for the player to stop,
+// which means the song ended
+async fn run_mpd(
+    address: String,
+    mut commands: UnboundedReceiver<PlayerCommand>,
+    events: UnboundedSender<AppEvent>,
+) {
+    let playing = Arc::new(AtomicBool::new(false));
+    let mut watching = false;
+    while let Some(command) = commands.recv().await {
+        // Our own clear stops the player too, which isn't a song ending
+        playing.store(false, Ordering::SeqCst);
+        let result: Result<()> = async {
+            let (mut lines, mut write) = connect_mpd(&address).await?;
+            mpd_command(&mut lines, &mut write, "clear").await?;
+            if let PlayerCommand::Play(song) = &command {
+                let add = format!("add {}", mpd_quote(&song.request));
+                mpd_command(&mut lines, &mut write, &add).await?;
+                mpd_command(&mut lines, &mut write, "play").await?;
+            }
+            Ok(())
+        }
+        .await;
+        match result {
+            Ok(()) => {
+                playing.store(matches!(command, PlayerCommand::Play(_)), Ordering::SeqCst);
+                if !watching {
+                    watching = true;
+                    tokio::spawn(watch_mpd(address.clone(), playing.clone(), events.clone()));
+                }
+            }
+            Err(e) => {
+                let _ = events.send(AppEvent::Error(format!("MPD at {}: {:#}", address, e)));
+            }
+        }
+    }
+}
+
+async fn watch_mpd(address: String, playing: Arc<AtomicBool>, events: UnboundedSender<AppEvent>) {
+    loop {
+        let result: Result<()> = async {
+            let (mut lines, mut write) = connect_mpd(&address).await?;
+            loop {
+                mpd_command(&mut lines, &mut write, "idle player").await?;
+                let status = mpd_command(&mut lines, &mut write, "status").await?;
+                let stopped = status.iter().any(|line| line == "state: stop");
+                if stopped && playing.swap(false, Ordering::SeqCst) {
+                    let _ = events.send(AppEvent::SongFinished);
+                }
+            }
+        }
+        .await;
+        if let Err(e) = result {
+            let _ = events.send(AppEvent::Debug(format!("MPD watcher: {:#}", e)));
+        }
+        if events.is_closed() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    }
+}
@@ -0,0 +1,227 @@
+use crate::state::AppEvent;
+use anyhow::{Context, Result};
+use rusqlite::types::{ToSqlOutput, ValueRef};
+use rusqlite::{params_from_iter, OpenFlags, ToSql};
+use std::path::Path;
+use std::sync::{mpsc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+// What the chat log and the other stores need of SQLite: open a database,
+// run statements with parameters, read rows as values. rusqlite does the
+// work, with SQLite built in (the `bundled` feature), so nothing has to be
+// installed alongside the bot.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Real(f64),
+    Text(String),
+}
+
+impl Value {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Int(n)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Real(n)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+impl ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Borrowed(match self {
+            Value::Null => ValueRef::Null,
+            Value::Int(n) => ValueRef::Integer(*n),
+            Value::Real(n) => ValueRef::Real(*n),
+            Value::Text(text) => ValueRef::Text(text.as_bytes()),
+        }))
+    }
+}
+
+impl From<ValueRef<'_>> for Value {
+    fn from(value: ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Integer(n) => Value::Int(n),
+            ValueRef::Real(n) => Value::Real(n),
+            ValueRef::Text(text) => Value::Text(String::from_utf8_lossy(text).into_owned()),
+            // Blobs aren't stored by anything here
+            ValueRef::Null | ValueRef::Blob(_) => Value::Null,
+        }
+    }
+}
+
+pub struct Connection {
+    db: rusqlite::Connection,
+}
+
+impl Connection {
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = rusqlite::Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        )
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+        // Wait instead of failing when something else has the file locked
+        db.busy_timeout(Duration::from_secs(5))?;
+        Ok(Self { db })
+    }
+
+    /// Run a statement that returns no rows. Returns the number of rows changed.
+    pub fn execute(&self, sql: &str, params: &[Value]) -> Result<usize> {
+        self.db
+            .execute(sql, params_from_iter(params))
+            .with_context(|| format!("in: {}", sql.trim()))
+    }
+
+    /// Run one or more statements separated by semicolons, without parameters.
+    pub fn execute_batch(&self, sql: &str) -> Result<()> {
+        self.db
+            .execute_batch(sql)
+            .with_context(|| format!("in: {}", sql.trim()))
+    }
+
+    /// Run a query and collect all its rows.
+    pub fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Vec<Value>>> {
+        let mut statement = self
+            .db
+            .prepare(sql)
+            .with_context(|| format!("in: {}", sql.trim()))?;
+        let columns = statement.column_count();
+        let rows = statement
+            .query_map(params_from_iter(params), |row| {
+                (0..columns)
+                    .map(|i| row.get_ref(i).map(Value::from))
+                    .collect()
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(rows)
+    }
+
+    pub fn last_insert_rowid(&self) -> i64 {
+        self.db.last_insert_rowid()
+    }
+}
+
+/// A feature's database: the file, and the directory it's in, created if
+/// need be, and the feature's tables set up.
+pub struct Store {
+    db: Connection,
+}
+
+impl Store {
+    pub fn open(path: &Path, schema: &str) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let db = Connection::open(path)?;
+        db.execute_batch(schema)
+            .with_context(|| format!("Failed to set up {}", path.display()))?;
+        Ok(Self { db })
+    }
+
+    /// Read what's stored, to start from.
+    pub fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Vec<Value>>> {
+        self.db.query(sql, params)
+    }
+
+    /// Hand the database to a writer thread, which runs the statements in
+    /// the order they're written. Failures are reported on `events` as
+    /// "Saving `what` failed".
+    pub fn writer(self, what: &'static str, events: UnboundedSender<AppEvent>) -> Writer {
+        let (tx, rx) = mpsc::channel::<(&'static str, Vec<Value>)>();
+        let db = self.db;
+        spawn_writer(move || {
+            for (sql, params) in rx {
+                if let Err(e) = db.execute(sql, &params) {
+                    let _ =
+                        events.send(AppEvent::Error(format!("Saving {} failed: {:#}", what, e)));
+                }
+            }
+        });
+        Writer { tx }
+    }
+
+    /// For features that run their own statements or writer.
+    pub fn into_connection(self) -> Connection {
+        self.db
+    }
+}
+
+/// Writes to a Store, done in the background. The writer thread ends once
+/// every clone of this is dropped.
+#[derive(Clone)]
+pub struct Writer {
+    tx: mpsc::Sender<(&'static str, Vec<Value>)>,
+}
+
+impl Writer {
+    pub fn write(&self, sql: &'static str, params: Vec<Value>) {
+        let _ = self.tx.send((sql, params));
+    }
+}
+
+// The threads writing to databases in the background, so shutdown can wait
+// for what they still have queued
+static WRITERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// Run a database writer on a thread of its own. It should end once its
+/// channel closes, that is when whatever sends to it is dropped.
+pub fn spawn_writer(writer: impl FnOnce() + Send + 'static) {
+    WRITERS.lock().unwrap().push(std::thread::spawn(writer));
+}
+
+/// Wait up to `timeout` for the writers to finish. Returns whether they all
+/// did; one whose sender is still alive somewhere never will.
+pub fn wait_for_writers(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut writers = WRITERS.lock().unwrap();
+    while Instant::now() < deadline {
+        writers.retain(|writer| !writer.is_finished());
+        if writers.is_empty() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
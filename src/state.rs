@@ -1,14 +1,536 @@
-use crate::config::Config;
+use crate::config::{Config, PowerMode, Volumes};
+use crate::encoder::{EmoteSize, Encoded};
+use crate::filters::Filters;
+use crate::search::Search;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Arc;
 use tui_input::Input;
 
 #[derive(Debug, Clone)]
+// Events are cloned for every frontend listening (the overlay, Matrix, MQTT),
+// so the big ones are shared rather than copied
 pub enum AppEvent {
-    ChatMessage { user: String, text: String },
+    ChatMessage(Arc<ChatMessage>),
     UserJoined(String),
     UserLeft(String),
     Error(String),
     Info(String),
-    EmoteImage(String, image::DynamicImage),
+    // A chat message that didn't go out, kept for /retry
+    SendFailed {
+        message: String,
+        error: String,
+    },
+    // Verbose diagnostics that only show up in the Log tab (used to go to debug.log)
+    Debug(String),
+    // A ban, timeout, deletion or AutoMod hold, seen in chat or done by the bot
+    Moderation(crate::modlog::ModEvent),
+    // AutoMod is holding a message for a mod to allow or deny
+    AutoModHeld(crate::automod::HeldMessage),
+    // A held message was decided on ("approved", "denied") or "expired";
+    // `moderator` is who decided, when Twitch says
+    AutoModResolved {
+        id: String,
+        status: String,
+        moderator: Option<String>,
+    },
+    // An AI request was sent to the LLM
+    AiStarted {
+        id: u64,
+        user: String,
+    },
+    // An AI request finished: Ok(reply) or Err(message). Cancelled requests
+    // finish with an error too, so frontends can stop their "thinking" indicator.
+    AiFinished {
+        id: u64,
+        result: Result<String, String>,
+    },
+    EmoteImage(String, Arc<image::DynamicImage>),
+    // An emote encoded for the terminal, or why it couldn't be (see encoder.rs)
+    EmoteEncoded {
+        name: String,
+        size: EmoteSize,
+        protocol: ratatui_image::picker::ProtocolType,
+        result: Result<Encoded, String>,
+    },
+    // How far the picker's emotes are along (see emotes.rs)
+    EmotesLoading {
+        loaded: usize,
+        total: usize,
+    },
+    Connection(Service, ConnectionState),
+    // Polled from Helix; None while the channel is offline
+    StreamStats(Option<crate::twitch::StreamStats>),
+    // Emote seen in chat that isn't part of the picker set
+    InlineEmote(String, Arc<image::DynamicImage>),
+    // Full list of logins currently in chat (from Get Chatters)
+    ChatterList(Vec<String>),
+    ChatterRoles(Vec<(String, Role)>),
+    // Follow/sub/raid/cheer notifications from EventSub
+    Alert(StreamAlert),
+    // Channel point reward redeemed; `input` is the viewer's text, if the reward asks for one
+    Redemption {
+        user: String,
+        reward_id: String,
+        reward: String,
+        input: String,
+    },
+    // TTS started reading `text` (what the overlay shows) from `speaker`
+    TtsStarted {
+        id: u64,
+        speaker: String,
+        text: String,
+    },
+    TtsFinished(u64),
+    // TTS muted (true) or unmuted from the TUI, for the overlay's indicator
+    TtsMuted(bool),
+    // choui.toml changed on disk; the overlay applies the new look live
+    OverlayConfig(Box<crate::config::OverlayConfig>),
+    // choui.toml or .env changed on disk and the settings were read again
+    ConfigReloaded(Arc<crate::config::Config>),
+    // Follower/sub goal progress, sent at startup and whenever it moves
+    Goal(Goal),
+    // A poll started, got votes or ended
+    Poll(crate::polls::Poll),
+    // A counter changed (0 once deleted), and each one at startup
+    Counter {
+        name: String,
+        value: i64,
+    },
+    // A chat message translated for the Chat tab (TRANSLATE_OUTPUT=local)
+    Translated {
+        user: String,
+        // The language it was in, ISO 639-1
+        language: String,
+        text: String,
+    },
+    // Joins Helix says are accounts younger than SHIELD_ACCOUNT_AGE
+    FreshAccounts(Vec<String>),
+    // The shield went up, with what raised it, or came down (None)
+    Shield(Option<String>),
+    // A cheer or gift leaderboard moved, or the stream changed, and each
+    // one at startup
+    Leaderboard(crate::leaderboards::Standings),
+    // The player got to the end of the song
+    SongFinished,
+    // The song request queue moved, for the overlay's "now playing / up next"
+    Songs {
+        now_playing: Option<crate::songs::Song>,
+        up_next: Option<crate::songs::Song>,
+    },
+}
+
+/// Whether `text` has one of `names` in it as a word (with or without an @).
+pub fn mentions(text: &str, names: &[&str]) -> bool {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .any(|word| names.iter().any(|name| word.eq_ignore_ascii_case(name)))
+}
+
+/// A chat message as EventSub delivered it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    // Twitch's id for the message
+    pub id: String,
+    // The sender's login, and their name as shown in chat
+    pub user: String,
+    pub display_name: String,
+    pub text: String,
+    // "#RRGGBB" from Twitch, None if the user never picked one
+    pub color: Option<String>,
+    pub badges: Vec<Badge>,
+    // `text` in pieces, in order
+    pub fragments: Vec<Fragment>,
+    // When Twitch sent it
+    pub timestamp: jiff::Timestamp,
+}
+
+impl ChatMessage {
+    pub fn role(&self) -> Role {
+        Role::from_badges(&self.badges)
+    }
+
+    /// (name, emote id) for every emote in the message.
+    pub fn emotes(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fragments.iter().filter_map(|fragment| match fragment {
+            Fragment::Emote { name, id } => Some((name.as_str(), id.as_str())),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Badge {
+    // e.g. "broadcaster", "moderator", "vip", "subscriber"
+    pub set_id: String,
+    // The version within the set, e.g. "12" for a year's subscriber badge
+    pub id: String,
+    // Months subscribed for "subscriber", empty for most badges
+    pub info: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fragment {
+    Text(String),
+    Emote {
+        name: String,
+        id: String,
+    },
+    // "Cheer100": the cheermote and the bits it stands for
+    Cheermote {
+        text: String,
+        prefix: String,
+        bits: u32,
+    },
+    // "@someone", with the login it points at
+    Mention {
+        text: String,
+        user: String,
+    },
+}
+
+impl Fragment {
+    pub fn text(&self) -> &str {
+        match self {
+            Fragment::Text(text)
+            | Fragment::Emote { name: text, .. }
+            | Fragment::Cheermote { text, .. }
+            | Fragment::Mention { text, .. } => text,
+        }
+    }
+}
+
+// Kinds of overlay alert, each can be turned off with OVERLAY_ALERTS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    Join,
+    Follow,
+    Subscribe,
+    GiftSub,
+    Raid,
+    Cheer,
+    Milestone,
+    Tip,
+}
+
+impl AlertKind {
+    pub const ALL: [AlertKind; 8] = [
+        AlertKind::Join,
+        AlertKind::Follow,
+        AlertKind::Subscribe,
+        AlertKind::GiftSub,
+        AlertKind::Raid,
+        AlertKind::Cheer,
+        AlertKind::Milestone,
+        AlertKind::Tip,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertKind::Join => "join",
+            AlertKind::Follow => "follow",
+            AlertKind::Subscribe => "sub",
+            AlertKind::GiftSub => "gift",
+            AlertKind::Raid => "raid",
+            AlertKind::Cheer => "cheer",
+            AlertKind::Milestone => "milestone",
+            AlertKind::Tip => "tip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AlertKind> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StreamAlert {
+    Follow {
+        user: String,
+    },
+    Subscribe {
+        user: String,
+        tier: String,
+    },
+    // `user` is None for anonymous gifts
+    GiftSub {
+        user: Option<String>,
+        count: u32,
+        tier: String,
+    },
+    Raid {
+        from: String,
+        // The raiding channel's user id, for Helix
+        from_id: String,
+        viewers: u32,
+    },
+    Cheer {
+        user: Option<String>,
+        bits: u32,
+        message: String,
+    },
+    // Viewer count reached one of VIEWER_MILESTONES
+    ViewerMilestone {
+        viewers: u64,
+    },
+    // A donation through StreamElements or Streamlabs (see tips.rs)
+    Tip {
+        user: String,
+        amount: f64,
+        currency: String,
+        message: String,
+    },
+}
+
+impl StreamAlert {
+    pub fn kind(&self) -> AlertKind {
+        match self {
+            StreamAlert::Follow { .. } => AlertKind::Follow,
+            StreamAlert::Subscribe { .. } => AlertKind::Subscribe,
+            StreamAlert::GiftSub { .. } => AlertKind::GiftSub,
+            StreamAlert::Raid { .. } => AlertKind::Raid,
+            StreamAlert::Cheer { .. } => AlertKind::Cheer,
+            StreamAlert::ViewerMilestone { .. } => AlertKind::Milestone,
+            StreamAlert::Tip { .. } => AlertKind::Tip,
+        }
+    }
+
+    // Who the alert is about, if anyone
+    pub fn user(&self) -> Option<&str> {
+        match self {
+            StreamAlert::Follow { user }
+            | StreamAlert::Subscribe { user, .. }
+            | StreamAlert::Tip { user, .. } => Some(user),
+            StreamAlert::GiftSub { user, .. } | StreamAlert::Cheer { user, .. } => user.as_deref(),
+            StreamAlert::Raid { from, .. } => Some(from),
+            StreamAlert::ViewerMilestone { .. } => None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        // EventSub tiers are "1000", "2000", "3000"
+        let tier = |tier: &str| format!("Tier {}", tier.trim_end_matches('0'));
+        match self {
+            StreamAlert::Follow { user } => format!("{} followed!", user),
+            StreamAlert::Subscribe { user, tier: t } => {
+                format!("{} subscribed ({})!", user, tier(t))
+            }
+            StreamAlert::GiftSub {
+                user,
+                count,
+                tier: t,
+            } => format!(
+                "{} gifted {} {} sub{}!",
+                user.as_deref().unwrap_or("Anonymous"),
+                count,
+                tier(t),
+                if *count == 1 { "" } else { "s" }
+            ),
+            StreamAlert::Raid { from, viewers, .. } => {
+                format!("{} is raiding with {} viewers!", from, viewers)
+            }
+            StreamAlert::Cheer { user, bits, .. } => format!(
+                "{} cheered {} bits!",
+                user.as_deref().unwrap_or("Anonymous"),
+                bits
+            ),
+            StreamAlert::ViewerMilestone { viewers } => format!("{} viewers!", viewers),
+            StreamAlert::Tip {
+                user,
+                amount,
+                currency,
+                ..
+            } => format!("{} tipped {:.2} {}!", user, amount, currency),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GoalKind {
+    Follow,
+    Sub,
+}
+
+// Follower or sub goal from [goal] in choui.toml, shown as a progress bar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Goal {
+    pub kind: GoalKind,
+    pub label: String,
+    pub target: u64,
+    pub current: u64,
+}
+
+impl Goal {
+    /// Count an alert toward the goal. Returns true if it just completed the goal.
+    pub fn count(&mut self, alert: &StreamAlert) -> bool {
+        let added = match (self.kind, alert) {
+            (GoalKind::Follow, StreamAlert::Follow { .. }) => 1,
+            (GoalKind::Sub, StreamAlert::Subscribe { .. }) => 1,
+            (GoalKind::Sub, StreamAlert::GiftSub { count, .. }) => *count as u64,
+            _ => return false,
+        };
+        let was_complete = self.is_complete();
+        self.current += added;
+        !was_complete && self.is_complete()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.current >= self.target
+    }
+
+    /// 0.0 to 1.0, capped once the goal is passed
+    pub fn fraction(&self) -> f32 {
+        (self.current as f32 / self.target.max(1) as f32).min(1.0)
+    }
+
+    // Progress saved from a previous run counts if it was for the same goal
+    pub fn restore(&mut self, saved: &Goal) {
+        if saved.kind == self.kind && saved.target == self.target && saved.label == self.label {
+            self.current = self.current.max(saved.current);
+        }
+    }
+}
+
+// Ordered so that sorting puts the broadcaster first, then mods, then VIPs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Broadcaster,
+    Moderator,
+    Vip,
+    Viewer,
+}
+
+impl Role {
+    pub fn from_badges(badges: &[Badge]) -> Role {
+        badges
+            .iter()
+            .map(|badge| match badge.set_id.as_str() {
+                "broadcaster" => Role::Broadcaster,
+                "moderator" => Role::Moderator,
+                "vip" => Role::Vip,
+                _ => Role::Viewer,
+            })
+            .min()
+            .unwrap_or(Role::Viewer)
+    }
+}
+
+// One line in a tab view. System lines (joins, info, errors) have no user.
+#[derive(Debug, Clone)]
+pub struct ChatLine {
+    pub user: Option<String>,
+    pub text: String,
+    pub color: Option<String>,
+    pub role: Role,
+    // Words in `text` that are emotes
+    pub emotes: Vec<String>,
+    // Mentions the bot or the streamer
+    pub mention: bool,
+}
+
+impl ChatLine {
+    pub fn chat(
+        user: String,
+        text: String,
+        color: Option<String>,
+        role: Role,
+        emotes: Vec<String>,
+    ) -> Self {
+        Self {
+            user: Some(user),
+            text,
+            color,
+            role,
+            emotes,
+            mention: false,
+        }
+    }
+}
+
+impl From<String> for ChatLine {
+    fn from(text: String) -> Self {
+        Self {
+            user: None,
+            text,
+            color: None,
+            role: Role::Viewer,
+            emotes: Vec::new(),
+            mention: false,
+        }
+    }
+}
+
+impl std::fmt::Display for ChatLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.user {
+            Some(user) => write!(f, "{}: {}", user, self.text),
+            None => write!(f, "{}", self.text),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Chatter {
+    pub role: Role,
+    pub message_count: u32,
+    pub present: bool,
+    pub last_ai_reply: Option<std::time::Instant>,
+}
+
+impl Default for Chatter {
+    fn default() -> Self {
+        Self {
+            role: Role::Viewer,
+            message_count: 0,
+            present: false,
+            last_ai_reply: None,
+        }
+    }
+}
+
+// Frame interval in low-power mode, and how long without input counts as idle
+const LOW_POWER_FRAME: std::time::Duration = std::time::Duration::from_millis(250);
+const IDLE_AFTER: std::time::Duration = std::time::Duration::from_secs(60);
+// Failed chat messages kept for /retry; older ones are dropped
+const MAX_UNSENT: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Chat,
+    Log,
+    Moderation,
+    Ai,
+    Songs,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 5] = [Tab::Chat, Tab::Log, Tab::Moderation, Tab::Ai, Tab::Songs];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Tab::Chat => "Chat",
+            Tab::Log => "Log",
+            Tab::Moderation => "Moderation",
+            Tab::Ai => "AI",
+            Tab::Songs => "Songs",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn next(self) -> Tab {
+        Tab::ALL[(self.index() + 1) % Tab::ALL.len()]
+    }
+
+    pub fn prev(self) -> Tab {
+        Tab::ALL[(self.index() + Tab::ALL.len() - 1) % Tab::ALL.len()]
+    }
 }
 
 pub const EMOJIS: &[&str] = &[
@@ -320,36 +842,937 @@ pub const EMOJIS: &[&str] = &[
 ];
 
 pub struct App {
-    pub messages: Vec<String>,
+    // Each tab's lines are a ring buffer of the last HISTORY_LINES
+    pub messages: VecDeque<ChatLine>,
     pub input: Input,
     pub exit: bool,
-    pub config: Config,
+    pub config: Arc<Config>,
     // Add image protocol
     pub picker: Option<ratatui_image::picker::Picker>,
-    pub emote_images: Vec<(
-        String,
-        image::DynamicImage,
-        Box<dyn ratatui_image::protocol::Protocol>,
-    )>,
+    // Encodes emotes with the picker's protocol, off the event loop
+    pub encoder: Option<crate::encoder::Encoder>,
+    // The emote picker's images, in order; drawn once encoded
+    pub emote_images: Vec<(String, Arc<image::DynamicImage>)>,
+    // Emotes seen in chat, drawn inline (2x1 cells) in chat lines once encoded
+    pub inline_emotes: std::collections::HashMap<String, Arc<image::DynamicImage>>,
+    pub protocols: crate::encoder::ProtocolCache,
+    pub inline_pending: std::collections::HashSet<String>,
     pub emote_scroll: usize,
+    // Picker emotes loaded of the total, while they're loading
+    pub emote_progress: Option<(usize, usize)>,
+    pub emote_panel: EmotePanel,
+    // Dragging the emote panel's top border
+    pub resizing_emotes: bool,
     pub emote_area: ratatui::layout::Rect, // Store the actual rendered area
     pub protocol_name: String,
+    // Protocol picked with Ctrl+P, saved so it survives restarts.
+    // Automatic fallbacks don't touch it.
+    pub protocol_choice: Option<ratatui_image::picker::ProtocolType>,
     pub bot_login: String,
+    // Tabs: each view keeps its own lines and scroll offset (rows from the bottom, 0 = follow)
+    pub tab: Tab,
+    pub tab_scroll: [usize; Tab::ALL.len()],
+    pub tab_area: ratatui::layout::Rect,
+    pub log: VecDeque<ChatLine>,
+    pub mod_queue: VecDeque<ChatLine>,
+    pub ai_activity: VecDeque<ChatLine>,
+    pub ai_requests: Vec<AiRequest>,
+    // Index into ai_requests of the highlighted request on the AI tab
+    pub ai_selected: Option<usize>,
+    next_ai_id: u64,
+    pub song_activity: VecDeque<ChatLine>,
+    // What the AI remembers of its conversations; main opens the stored one
+    pub ai_memory: crate::memory::Memory,
+    pub songs: crate::songs::SongQueue,
+    // Index into songs.queue of the highlighted song on the Songs tab
+    pub song_selected: Option<usize>,
+    // Who's lurking (!lurk), so they're left alone
+    pub lurkers: crate::lurk::Lurkers,
+    // Messages a minute, and whether chat is too fast (see chat_speed.rs)
+    pub chat_speed: crate::chat_speed::ChatSpeed,
+    // Messages AutoMod is holding, listed on the Moderation tab
+    pub held: crate::automod::HeldMessages,
+    // New followers waiting for the next thank-you
+    pub follow_thanks: crate::follows::FollowThanks,
+    // Watching for hate raids, and whether the shield is up (see shield.rs)
+    pub shield: crate::shield::Shield,
+    // User list sidebar
+    pub chatters: std::collections::HashMap<String, Chatter>,
+    pub show_users: bool,
+    pub users_area: ratatui::layout::Rect,
+    pub search: Option<Search>,
+    pub filters: Filters,
+    pub selection: Option<Selection>,
+    // Left button held after clicking a chat line; `selection_dragged` once it moved
+    pub selecting: bool,
+    pub selection_dragged: bool,
+    pub notifications: Vec<Notification>,
+    // What the bot says in chat waits here (see outbox.rs)
+    pub outbox: crate::outbox::Outbox,
+    // Chat messages that failed to send, oldest first, until /retry
+    pub unsent: Vec<String>,
+    pub show_notifications: bool,
+    // Errors that arrived while the notification pane was collapsed
+    pub unread_errors: usize,
+    // Terminal focus, from crossterm focus events
+    pub focused: bool,
+    pub power_mode: PowerMode,
+    pub last_input: std::time::Instant,
+    // Index into `messages` of the first message that arrived while away
+    pub unread_marker: Option<usize>,
+    // The operator has been back since the marker was placed
+    marker_seen: bool,
+    pub eventsub: ConnectionState,
+    pub irc: ConnectionState,
+    pub tts_enabled: bool,
+    pub volumes: Volumes,
+    // TTS rate, pitch and voice as changed with /tts
+    pub speech: crate::tts::VoiceSettings,
+    pub stream: Option<crate::twitch::StreamStats>,
+    // Highest viewer count this stream, so each milestone fires once
+    pub viewer_peak: u64,
+    pub goal: Option<Goal>,
+    // The running poll, or the last one once it ended
+    pub poll: Option<crate::polls::Poll>,
+    // Line index (into the active tab) of each row drawn in the main view, top to bottom.
+    // None for decoration rows like the unread marker.
+    pub view_rows: Vec<Option<usize>>,
+    // Without the TUI every pushed line is also written to these (stdout, --log-file)
+    pub echo: Vec<Box<dyn std::io::Write + Send>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AiStatus {
+    Pending,
+    // Reply held until the operator approves it (AI_REQUIRE_APPROVAL)
+    AwaitingApproval(String),
+    Sent(String),
+    Rejected(String),
+    Failed(String),
+    Cancelled,
+}
+
+// One call to the LLM, from trigger to outcome, for the AI tab
+#[derive(Debug, Clone)]
+pub struct AiRequest {
+    pub id: u64,
+    pub user: String,
+    pub prompt: String,
+    pub started: std::time::Instant,
+    pub finished: Option<std::time::Instant>,
+    pub status: AiStatus,
+    // How urgently the reply goes out; greetings can wait
+    pub priority: crate::outbox::Priority,
+}
+
+impl AiRequest {
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.finished
+            .unwrap_or_else(std::time::Instant::now)
+            .duration_since(self.started)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+// Errors and status messages, kept out of chat in their own pane
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub severity: Severity,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    EventSub,
+    Irc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionState {
+    #[default]
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+// Emote picker size, remembered across runs in ui_state.json
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmotePanel {
+    pub height: u16,
+    pub collapsed: bool,
+}
+
+impl Default for EmotePanel {
+    fn default() -> Self {
+        Self {
+            height: 12,
+            collapsed: false,
+        }
+    }
+}
+
+impl EmotePanel {
+    const MIN_HEIGHT: u16 = 4;
+    const MAX_HEIGHT: u16 = 40;
+
+    pub fn rendered_height(&self) -> u16 {
+        if self.collapsed {
+            0
+        } else {
+            self.height
+        }
+    }
+
+    pub fn resize(&mut self, height: u16) {
+        self.height = height.clamp(Self::MIN_HEIGHT, Self::MAX_HEIGHT);
+        self.collapsed = false;
+    }
+}
+
+// Everything kept in ui_state.json in the data directory (see paths.rs). The panel fields stay at the top level
+// so files written before the protocol was saved still load.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    #[serde(flatten)]
+    pub emote_panel: EmotePanel,
+    pub image_protocol: Option<String>,
+    pub goal: Option<Goal>,
+}
+
+impl UiState {
+    fn path(config: &Config) -> Result<std::path::PathBuf> {
+        let legacy = config.legacy_dir.as_ref();
+        crate::paths::file_in(
+            &config.data_dir,
+            "ui_state.json",
+            legacy.map(|dir| dir.join(".ui_state.json")),
+        )
+    }
+
+    /// The state saved in the data directory
+    pub fn load(config: &Config) -> Self {
+        Self::path(config)
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        std::fs::write(Self::path(config)?, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+pub fn protocol_name(protocol: ratatui_image::picker::ProtocolType) -> String {
+    format!("{:?}", protocol)
+}
+
+pub fn protocol_from_name(name: &str) -> Option<ratatui_image::picker::ProtocolType> {
+    use ratatui_image::picker::ProtocolType;
+    [
+        ProtocolType::Halfblocks,
+        ProtocolType::Sixel,
+        ProtocolType::Kitty,
+        ProtocolType::Iterm2,
+    ]
+    .into_iter()
+    .find(|p| protocol_name(*p).eq_ignore_ascii_case(name.trim()))
+}
+
+// A range of lines picked for copying, by index into `tab`'s lines
+#[derive(Debug, Clone, Copy)]
+pub struct Selection {
+    pub tab: Tab,
+    pub anchor: usize,
+    pub cursor: usize,
+}
+
+impl Selection {
+    pub fn contains(&self, index: usize) -> bool {
+        let (start, end) = self.bounds();
+        (start..=end).contains(&index)
+    }
+
+    pub fn bounds(&self) -> (usize, usize) {
+        (self.anchor.min(self.cursor), self.anchor.max(self.cursor))
+    }
 }
 
 impl App {
-    pub fn new(config: Config, bot_login: String) -> Self {
+    pub fn new(config: Arc<Config>, bot_login: String) -> Self {
         Self {
-            messages: Vec::new(),
+            messages: VecDeque::new(),
             input: Input::default(),
             exit: false,
-            config,
             picker: None,
+            encoder: None,
             emote_images: Vec::new(),
+            inline_emotes: std::collections::HashMap::new(),
+            protocols: Default::default(),
+            inline_pending: std::collections::HashSet::new(),
             emote_scroll: 0,
+            emote_progress: None,
+            emote_panel: EmotePanel::default(),
+            resizing_emotes: false,
             emote_area: ratatui::layout::Rect::default(),
             protocol_name: "Unknown".to_string(),
+            protocol_choice: None,
             bot_login,
+            tab: Tab::Chat,
+            tab_scroll: [0; Tab::ALL.len()],
+            tab_area: ratatui::layout::Rect::default(),
+            log: VecDeque::new(),
+            mod_queue: VecDeque::new(),
+            ai_activity: VecDeque::new(),
+            ai_requests: Vec::new(),
+            ai_selected: None,
+            next_ai_id: 0,
+            song_activity: VecDeque::new(),
+            ai_memory: crate::memory::Memory::default(),
+            songs: crate::songs::SongQueue::default(),
+            song_selected: None,
+            lurkers: Default::default(),
+            chat_speed: Default::default(),
+            held: Default::default(),
+            follow_thanks: Default::default(),
+            shield: Default::default(),
+            chatters: std::collections::HashMap::new(),
+            show_users: true,
+            users_area: ratatui::layout::Rect::default(),
+            search: None,
+            filters: Filters::from_config(&config),
+            selection: None,
+            selecting: false,
+            selection_dragged: false,
+            notifications: Vec::new(),
+            outbox: Default::default(),
+            unsent: Vec::new(),
+            show_notifications: true,
+            unread_errors: 0,
+            focused: true,
+            power_mode: config.power_mode,
+            last_input: std::time::Instant::now(),
+            unread_marker: None,
+            marker_seen: true,
+            eventsub: ConnectionState::default(),
+            irc: ConnectionState::default(),
+            tts_enabled: config.tts_enabled,
+            volumes: config.volumes,
+            speech: config.tts.settings.clone(),
+            stream: None,
+            view_rows: Vec::new(),
+            viewer_peak: 0,
+            goal: None,
+            poll: None,
+            echo: Vec::new(),
+            config,
+        }
+    }
+
+    // Indices of lines in the active tab matching the current search query
+    pub fn search_matches(&self) -> Vec<usize> {
+        let Some(Ok(Some(matcher))) = self.search.as_ref().map(|s| s.matcher()) else {
+            return Vec::new();
+        };
+        self.tab_lines(self.tab)
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| matcher.is_match(&line.to_string()))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Move to the next older (or newer) match and scroll it into view
+    pub fn search_step(&mut self, older: bool) {
+        let matches = self.search_matches();
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+
+        let target = match search.current {
+            None => matches.last().copied(),
+            Some(current) if older => matches.iter().rev().find(|&&i| i < current).copied(),
+            Some(current) => matches.iter().find(|&&i| i > current).copied(),
+        };
+        if target.is_some() {
+            search.current = target;
+        }
+        self.reveal_search_match();
+    }
+
+    // Park the current match on the bottom row of the view
+    pub fn reveal_search_match(&mut self) {
+        let Some(current) = self.search.as_ref().and_then(|s| s.current) else {
+            return;
+        };
+
+        let width = self.view_width();
+        let rows_below: usize = self
+            .visible_lines()
+            .iter()
+            .filter(|(i, _)| *i > current)
+            .map(|(_, line)| crate::ui::wrapped_height(line, width))
+            .sum();
+        self.tab_scroll[self.tab.index()] = rows_below;
+    }
+
+    // Lines of the active tab that survive the chat filters and search filter mode,
+    // paired with their index into the full tab
+    pub fn visible_lines(&self) -> Vec<(usize, &ChatLine)> {
+        let filter = self.search.as_ref().is_some_and(|s| s.filter);
+        let matches = if filter {
+            self.search_matches()
+        } else {
+            Vec::new()
+        };
+
+        self.tab_lines(self.tab)
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| self.tab != Tab::Chat || !self.filters.hides(line))
+            .filter(|(i, _)| !filter || matches.binary_search(i).is_ok())
+            .collect()
+    }
+
+    // Selection on the active tab, if any
+    pub fn active_selection(&self) -> Option<Selection> {
+        self.selection.filter(|s| s.tab == self.tab)
+    }
+
+    // Move the selection cursor one visible line older/newer, starting from the
+    // newest line. With `extend` the anchor stays put and the range grows.
+    pub fn select_step(&mut self, older: bool, extend: bool) {
+        let visible: Vec<usize> = self.visible_lines().iter().map(|(i, _)| *i).collect();
+        let Some(&newest) = visible.last() else {
+            return;
+        };
+
+        let cursor = match self.active_selection() {
+            None => newest,
+            Some(sel) => {
+                let next = if older {
+                    visible.iter().rev().find(|&&i| i < sel.cursor)
+                } else {
+                    visible.iter().find(|&&i| i > sel.cursor)
+                };
+                next.copied().unwrap_or(sel.cursor)
+            }
+        };
+        let anchor = match self.active_selection() {
+            Some(sel) if extend => sel.anchor,
+            _ => cursor,
+        };
+        self.selection = Some(Selection {
+            tab: self.tab,
+            anchor,
+            cursor,
+        });
+        self.reveal_line(cursor);
+    }
+
+    // Line index drawn at a screen position in the main view
+    pub fn line_at(&self, column: u16, row: u16) -> Option<usize> {
+        let view = self.tab_area;
+        if column <= view.x
+            || column >= view.right().saturating_sub(1)
+            || row <= view.y
+            || row >= view.bottom().saturating_sub(1)
+        {
+            return None;
+        }
+        self.view_rows
+            .get((row - view.y - 1) as usize)
+            .copied()
+            .flatten()
+    }
+
+    // Select the line under the mouse
+    pub fn select_at(&mut self, column: u16, row: u16, extend: bool) -> Option<usize> {
+        let index = self.line_at(column, row)?;
+        let anchor = match self.active_selection() {
+            Some(sel) if extend => sel.anchor,
+            _ => index,
+        };
+        self.selection = Some(Selection {
+            tab: self.tab,
+            anchor,
+            cursor: index,
+        });
+        Some(index)
+    }
+
+    // Extend a mouse drag to the line under the pointer. Past the top or bottom
+    // edge the view scrolls so the selection can grow beyond one screen.
+    pub fn drag_select_to(&mut self, column: u16, row: u16) {
+        let view = self.tab_area;
+        if view.width < 3 || view.height < 3 {
+            return;
+        }
+        let top = view.y + 1;
+        let bottom = view.bottom() - 2;
+        if row < top {
+            self.scroll_up(1);
+        } else if row > bottom {
+            self.scroll_down(1);
+        }
+
+        let column = column.clamp(view.x + 1, view.right() - 2);
+        let row = row.clamp(top, bottom);
+        if let Some(index) = self.line_at(column, row) {
+            if self
+                .active_selection()
+                .is_some_and(|sel| sel.cursor != index)
+            {
+                self.selection_dragged = true;
+            }
+            self.select_at(column, row, true);
+        }
+    }
+
+    // Text of the selected lines (hidden lines skipped), or the newest line
+    pub fn selected_text(&self) -> Option<String> {
+        let visible = self.visible_lines();
+        let lines: Vec<String> = match self.active_selection() {
+            Some(sel) => visible
+                .iter()
+                .filter(|(i, _)| sel.contains(*i))
+                .map(|(_, line)| line.to_string())
+                .collect(),
+            None => visible
+                .last()
+                .map(|(_, line)| line.to_string())
+                .into_iter()
+                .collect(),
+        };
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    // Adjust the scroll just enough to bring a line fully into view
+    fn reveal_line(&mut self, index: usize) {
+        let width = self.view_width();
+        let mut rows_below = 0;
+        let mut height = 0;
+        for (i, line) in self.visible_lines() {
+            if i > index {
+                rows_below += crate::ui::wrapped_height(line, width);
+            } else if i == index {
+                height = crate::ui::wrapped_height(line, width);
+            }
+        }
+
+        let page = self.page_size();
+        let scroll = &mut self.tab_scroll[self.tab.index()];
+        if rows_below < *scroll {
+            *scroll = rows_below;
+        } else if rows_below + height > *scroll + page {
+            *scroll = (rows_below + height).saturating_sub(page);
+        }
+    }
+
+    pub fn save_ui_state(&self) -> Result<()> {
+        UiState {
+            emote_panel: self.emote_panel,
+            image_protocol: self.protocol_choice.map(protocol_name),
+            goal: self.goal.clone(),
+        }
+        .save(&self.config)
+    }
+
+    // Swap the picker's protocol (keeping font size and tmux detection) and
+    // encode the emotes it doesn't have yet
+    pub fn set_image_protocol(&mut self, protocol: ratatui_image::picker::ProtocolType) {
+        let Some(picker) = &mut self.picker else {
+            return;
+        };
+        picker.protocol_type = protocol;
+        self.protocol_name = protocol_name(protocol);
+
+        for (name, image) in &self.emote_images {
+            self.encode(EmoteSize::Picker, name, image);
+        }
+        for (name, image) in &self.inline_emotes {
+            self.encode(EmoteSize::Inline, name, image);
+        }
+    }
+
+    // Have `image` encoded with the current protocol, unless it already is
+    fn encode(&self, size: EmoteSize, name: &str, image: &Arc<image::DynamicImage>) {
+        let (Some(picker), Some(encoder)) = (&self.picker, &self.encoder) else {
+            return;
+        };
+        let protocol = picker.protocol_type;
+        if self.protocols.get(protocol, size, name).is_none() {
+            encoder.encode(protocol, size, name.to_string(), image.clone());
+        }
+    }
+
+    pub fn add_emote_image(&mut self, name: String, image: Arc<image::DynamicImage>) {
+        if self.picker.is_some() {
+            self.encode(EmoteSize::Picker, &name, &image);
+            self.emote_images.push((name, image));
+        }
+    }
+
+    pub fn add_inline_emote(&mut self, name: String, image: Arc<image::DynamicImage>) {
+        self.inline_pending.remove(&name);
+        if self.picker.is_some() {
+            self.encode(EmoteSize::Inline, &name, &image);
+            self.inline_emotes.insert(name, image);
+        }
+    }
+
+    // An emote back from the encoder. If the current protocol can't encode
+    // (e.g. the terminal or the encoder can't handle it), drop to halfblocks
+    // for good.
+    pub fn emote_encoded(
+        &mut self,
+        name: String,
+        size: EmoteSize,
+        protocol: ratatui_image::picker::ProtocolType,
+        result: Result<Encoded, String>,
+    ) {
+        use ratatui_image::picker::ProtocolType;
+
+        let error = match result {
+            Ok(encoded) => return self.protocols.insert(protocol, size, name, encoded),
+            Err(error) => error,
+        };
+        let current = self.picker.map(|picker| picker.protocol_type);
+        if current != Some(protocol) || protocol == ProtocolType::Halfblocks {
+            return;
+        }
+        self.notify(
+            Severity::Warning,
+            format!(
+                "{} image rendering failed ({}), falling back to halfblocks",
+                self.protocol_name, error
+            ),
+        );
+        self.set_image_protocol(ProtocolType::Halfblocks);
+    }
+
+    /// `name` encoded with the current protocol, once it's ready
+    pub fn emote_protocol(
+        &self,
+        size: EmoteSize,
+        name: &str,
+    ) -> Option<&dyn ratatui_image::protocol::Protocol> {
+        let protocol = self.picker?.protocol_type;
+        let encoded = self.protocols.get(protocol, size, name)?;
+        Some(encoded.0.as_ref())
+    }
+
+    // Halfblocks can't draw anything useful in a 2x1 cell, so emotes stay text there
+    pub fn inline_images_enabled(&self) -> bool {
+        self.picker
+            .is_some_and(|p| p.protocol_type != ratatui_image::picker::ProtocolType::Halfblocks)
+    }
+
+    pub fn chatter(&mut self, login: &str) -> &mut Chatter {
+        self.chatters.entry(login.to_lowercase()).or_default()
+    }
+
+    // Whether the shield is up and SHIELD_ACTIONS has `action`
+    pub fn shielded(&self, action: crate::shield::ShieldAction) -> bool {
+        self.shield.is_raised() && self.config.shield.does(action)
+    }
+
+    pub fn on_ai_cooldown(&self, login: &str) -> bool {
+        self.chatters
+            .get(&login.to_lowercase())
+            .and_then(|c| c.last_ai_reply)
+            .map(|t| t.elapsed() < self.config.ai_triggers.user_cooldown)
+            .unwrap_or(false)
+    }
+
+    // Present chatters, broadcaster/mods/VIPs first, then alphabetical
+    pub fn sorted_chatters(&self) -> Vec<(&String, &Chatter)> {
+        let mut list: Vec<(&String, &Chatter)> =
+            self.chatters.iter().filter(|(_, c)| c.present).collect();
+        list.sort_by(|a, b| a.1.role.cmp(&b.1.role).then_with(|| a.0.cmp(b.0)));
+        list
+    }
+
+    pub fn push(&mut self, tab: Tab, line: impl Into<ChatLine>) {
+        let line = line.into();
+        if !self.echo.is_empty() {
+            let entry = format!(
+                "{} [{}] {}\n",
+                jiff::Timestamp::now().strftime("%Y-%m-%dT%H:%M:%SZ"),
+                tab.title(),
+                line
+            );
+            for out in &mut self.echo {
+                let _ = out.write_all(entry.as_bytes());
+                let _ = out.flush();
+            }
         }
+        self.push_quiet(tab, line);
+    }
+
+    // Like push, but never echoed (for the raw protocol dumps of AppEvent::Debug)
+    pub fn push_quiet(&mut self, tab: Tab, line: impl Into<ChatLine>) {
+        let line = line.into();
+        // Keep a scrolled-back view anchored on the same rows
+        if self.tab_scroll[tab.index()] > 0 && !(tab == Tab::Chat && self.filters.hides(&line)) {
+            self.tab_scroll[tab.index()] += crate::ui::wrapped_height(&line, self.view_width());
+        }
+        let capacity = self.config.history_lines;
+        let lines = self.tab_lines_mut(tab);
+        lines.push_back(line);
+        let dropped = lines.len().saturating_sub(capacity);
+        lines.drain(..dropped);
+        if dropped > 0 {
+            self.forget_oldest(tab, dropped);
+        }
+    }
+
+    // Line indices into `tab` move down as its oldest lines are dropped; ones
+    // pointing at a dropped line go to the oldest one left
+    fn forget_oldest(&mut self, tab: Tab, dropped: usize) {
+        if tab == Tab::Chat {
+            if let Some(marker) = &mut self.unread_marker {
+                *marker = marker.saturating_sub(dropped);
+            }
+        }
+        if let Some(selection) = self.selection.as_mut().filter(|s| s.tab == tab) {
+            selection.anchor = selection.anchor.saturating_sub(dropped);
+            selection.cursor = selection.cursor.saturating_sub(dropped);
+        }
+        if tab != self.tab {
+            return;
+        }
+        if let Some(search) = &mut self.search {
+            search.current = search.current.and_then(|i| i.checked_sub(dropped));
+        }
+        for row in self.view_rows.iter_mut() {
+            *row = row.and_then(|i| i.checked_sub(dropped));
+        }
+    }
+
+    pub fn is_mention(&self, text: &str) -> bool {
+        let mut names = vec![self.bot_login.as_str()];
+        names.extend(self.config.channel_name.as_deref());
+        mentions(text, &names)
+    }
+
+    // Every emote name the input hints know about
+    pub fn known_emotes(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = EMOJIS.to_vec();
+        names.extend(self.emote_images.iter().map(|(name, _)| name.as_str()));
+        names.extend(self.inline_emotes.keys().map(|name| name.as_str()));
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    pub fn low_power(&self) -> bool {
+        match self.power_mode {
+            PowerMode::On => true,
+            PowerMode::Off => false,
+            PowerMode::Auto => !self.focused || self.last_input.elapsed() >= IDLE_AFTER,
+        }
+    }
+
+    // Minimum time between redraws
+    pub fn frame_interval(&self) -> std::time::Duration {
+        if self.low_power() {
+            LOW_POWER_FRAME.max(self.max_fps_frame())
+        } else {
+            self.max_fps_frame()
+        }
+    }
+
+    fn max_fps_frame(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(1) / self.config.max_fps.max(1)
+    }
+
+    // Whether handling `event` changes anything on screen. Events that don't
+    // (TTS progress for the overlay, Log tab debug lines while on another
+    // tab, emotes still waiting to be encoded) skip the redraw.
+    pub fn redraws_for(&self, event: &AppEvent) -> bool {
+        match event {
+            AppEvent::TtsStarted { .. }
+            | AppEvent::TtsFinished(_)
+            | AppEvent::TtsMuted(_)
+            | AppEvent::Counter { .. }
+            | AppEvent::Leaderboard(_)
+            | AppEvent::AiStarted { .. }
+            | AppEvent::InlineEmote(..) => false,
+            AppEvent::Debug(_) => self.tab == Tab::Log,
+            _ => true,
+        }
+    }
+
+    // Away means the newest chat isn't on screen: unfocused, scrolled back or on another tab
+    pub fn is_away(&self) -> bool {
+        !self.focused || self.tab != Tab::Chat || self.tab_scroll[Tab::Chat.index()] > 0
+    }
+
+    // Call before pushing a chat message. The first message of each away stretch
+    // moves the marker; it then stays put until the operator leaves again.
+    pub fn mark_unread(&mut self) {
+        if self.is_away() && self.marker_seen {
+            self.unread_marker = Some(self.messages.len());
+            self.marker_seen = false;
+        }
+    }
+
+    pub fn note_presence(&mut self) {
+        if !self.is_away() {
+            self.marker_seen = true;
+        }
+    }
+
+    // Record a new in-flight AI request and return its id
+    pub fn start_ai_request(
+        &mut self,
+        user: &str,
+        prompt: &str,
+        priority: crate::outbox::Priority,
+    ) -> u64 {
+        let id = self.next_ai_id;
+        self.next_ai_id += 1;
+        self.push(Tab::Ai, format!("-> #{} {}", id, prompt));
+        self.ai_requests.push(AiRequest {
+            id,
+            user: user.to_string(),
+            prompt: prompt.to_string(),
+            started: std::time::Instant::now(),
+            finished: None,
+            status: AiStatus::Pending,
+            priority,
+        });
+        id
+    }
+
+    pub fn ai_request(&mut self, id: u64) -> Option<&mut AiRequest> {
+        self.ai_requests.iter_mut().find(|r| r.id == id)
+    }
+
+    pub fn selected_ai_request(&mut self) -> Option<&mut AiRequest> {
+        let index = self.ai_selected?;
+        self.ai_requests.get_mut(index)
+    }
+
+    pub fn ai_select_step(&mut self, older: bool) {
+        let last = self.ai_requests.len().checked_sub(1);
+        self.ai_selected = match (self.ai_selected, last) {
+            (_, None) => None,
+            (None, last) => last,
+            (Some(i), _) if older => Some(i.saturating_sub(1)),
+            (Some(i), Some(last)) => Some((i + 1).min(last)),
+        };
+    }
+
+    pub fn song_select_step(&mut self, up: bool) {
+        let last = self.songs.queue.len().checked_sub(1);
+        self.song_selected = match (self.song_selected, last) {
+            (_, None) => None,
+            (None, _) => Some(0),
+            (Some(i), _) if up => Some(i.saturating_sub(1)),
+            (Some(i), Some(last)) => Some((i + 1).min(last)),
+        };
+    }
+
+    pub fn ai_in_flight(&self) -> bool {
+        self.ai_requests
+            .iter()
+            .any(|r| r.status == AiStatus::Pending)
+    }
+
+    // Seconds the oldest pending request has been running, for the status bar
+    pub fn ai_thinking_for(&self) -> Option<u64> {
+        self.ai_requests
+            .iter()
+            .filter(|r| r.status == AiStatus::Pending)
+            .map(|r| r.elapsed().as_secs())
+            .max()
+    }
+
+    pub fn notify(&mut self, severity: Severity, text: impl Into<String>) {
+        let text = text.into();
+        self.push(Tab::Log, format!("{:?}: {}", severity, text));
+        if severity == Severity::Error && !self.show_notifications {
+            self.unread_errors += 1;
+        }
+        self.notifications.push(Notification { severity, text });
+    }
+
+    /// Report a chat message that didn't go out and keep it for /retry.
+    pub fn send_failed(&mut self, message: String, error: &str) {
+        self.notify(
+            Severity::Error,
+            format!(
+                "Not sent: \"{}\" ({}), /retry sends it again",
+                message, error
+            ),
+        );
+        if self.unsent.len() == MAX_UNSENT {
+            self.unsent.remove(0);
+        }
+        self.unsent.push(message);
+    }
+
+    pub fn toggle_notifications(&mut self) {
+        self.show_notifications = !self.show_notifications;
+        if self.show_notifications {
+            self.unread_errors = 0;
+        }
+    }
+
+    pub fn tab_lines(&self, tab: Tab) -> &VecDeque<ChatLine> {
+        match tab {
+            Tab::Chat => &self.messages,
+            Tab::Log => &self.log,
+            Tab::Moderation => &self.mod_queue,
+            Tab::Ai => &self.ai_activity,
+            Tab::Songs => &self.song_activity,
+        }
+    }
+
+    fn tab_lines_mut(&mut self, tab: Tab) -> &mut VecDeque<ChatLine> {
+        match tab {
+            Tab::Chat => &mut self.messages,
+            Tab::Log => &mut self.log,
+            Tab::Moderation => &mut self.mod_queue,
+            Tab::Ai => &mut self.ai_activity,
+            Tab::Songs => &mut self.song_activity,
+        }
+    }
+
+    fn visible_rows(&self) -> usize {
+        self.tab_area.height.saturating_sub(2) as usize // Subtract 2 for borders
+    }
+
+    pub fn view_width(&self) -> usize {
+        self.tab_area.width.saturating_sub(2) as usize
+    }
+
+    // Scroll offsets count wrapped rows; ui() clamps them to the top of the history
+    pub fn scroll_up(&mut self, rows: usize) {
+        self.tab_scroll[self.tab.index()] += rows;
+    }
+
+    pub fn scroll_down(&mut self, rows: usize) {
+        let scroll = &mut self.tab_scroll[self.tab.index()];
+        *scroll = scroll.saturating_sub(rows);
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.visible_rows().max(1)
     }
 }
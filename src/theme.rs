@@ -0,0 +1,107 @@
+use anyhow::{bail, Context, Result};
+use ratatui::style::Color;
+use std::str::FromStr;
+
+// TUI colors. Pick a preset with THEME=dark|light, then override single
+// colors with THEME_<FIELD> (names like "yellow", "#ff8800" or 0-255).
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub border: Color,
+    pub text: Color,
+    // Name color for users without a Twitch color; None hashes into a palette
+    pub username: Option<Color>,
+    // Active tab, current search match, search bar
+    pub highlight: Color,
+    pub selection: Color,
+    // Background of chat lines that mention the bot or streamer
+    pub mention: Color,
+    // Error lines
+    pub alert: Color,
+    pub input: Color,
+    pub emote: Color,
+    // Secondary text: inactive tabs, counts, muted users, other search matches
+    pub dim: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            border: Color::Reset,
+            text: Color::Reset,
+            username: None,
+            highlight: Color::Yellow,
+            selection: Color::Blue,
+            mention: Color::Rgb(0x3a, 0x1f, 0x4a),
+            alert: Color::LightRed,
+            input: Color::Yellow,
+            emote: Color::Cyan,
+            dim: Color::DarkGray,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            border: Color::Gray,
+            text: Color::Black,
+            username: None,
+            highlight: Color::LightYellow,
+            selection: Color::LightBlue,
+            mention: Color::Rgb(0xf3, 0xe0, 0xff),
+            alert: Color::Red,
+            input: Color::Blue,
+            emote: Color::Magenta,
+            dim: Color::Gray,
+        }
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let mut theme = match crate::config::var("THEME")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "dark" => Self::dark(),
+            "light" => Self::light(),
+            other => bail!("THEME must be dark or light, got '{}'", other),
+        };
+
+        let fields: [(&str, &mut Color); 9] = [
+            ("THEME_BORDER", &mut theme.border),
+            ("THEME_TEXT", &mut theme.text),
+            ("THEME_HIGHLIGHT", &mut theme.highlight),
+            ("THEME_SELECTION", &mut theme.selection),
+            ("THEME_MENTION", &mut theme.mention),
+            ("THEME_ALERT", &mut theme.alert),
+            ("THEME_INPUT", &mut theme.input),
+            ("THEME_EMOTE", &mut theme.emote),
+            ("THEME_DIM", &mut theme.dim),
+        ];
+        for (name, field) in fields {
+            if let Some(color) = env_color(name)? {
+                *field = color;
+            }
+        }
+        if let Some(color) = env_color("THEME_USERNAME")? {
+            theme.username = Some(color);
+        }
+
+        Ok(theme)
+    }
+}
+
+fn env_color(name: &str) -> Result<Option<Color>> {
+    match crate::config::var(name) {
+        Ok(value) if !value.trim().is_empty() => Color::from_str(value.trim())
+            .ok()
+            .map(Some)
+            .with_context(|| format!("{} is not a valid color: '{}'", name, value)),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{bail, Context, Result};
+use std::time::{Duration, Instant};
+
+// Messages the bot posts on its own every so often ("Follow on socials!").
+// A timer only fires when chat has been active since its last post, so the
+// bot doesn't talk to an empty room, and every wait is stretched or shortened
+// at random (TIMER_JITTER) so the posts don't come like clockwork.
+//
+// TIMERS holds them separated by '|', each written as
+// "<minutes>[/<messages>] <text>": "15/5 Follow on socials!" posts every 15
+// minutes when at least 5 chat messages came in since the last time.
+
+const DEFAULT_MIN_MESSAGES: u32 = 5;
+// Timers that come due together are spread out by at least this much
+const MIN_GAP: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timer {
+    pub interval: Duration,
+    // Chat messages needed since the last post
+    pub min_messages: u32,
+    pub message: String,
+}
+
+impl Timer {
+    pub fn parse(entry: &str) -> Result<Self> {
+        let usage = || {
+            format!(
+                "Timers look like \"15/5 Follow on socials!\", got '{}'",
+                entry
+            )
+        };
+        let (schedule, message) = entry
+            .trim()
+            .split_once(char::is_whitespace)
+            .with_context(usage)?;
+        let (minutes, min_messages) = match schedule.split_once('/') {
+            Some((minutes, messages)) => (minutes, messages.parse().ok().with_context(usage)?),
+            None => (schedule, DEFAULT_MIN_MESSAGES),
+        };
+        let minutes: u64 = minutes.parse().ok().with_context(usage)?;
+        if minutes == 0 {
+            bail!("Timers run every 1 minute or more, got '{}'", entry);
+        }
+        Ok(Self {
+            interval: Duration::from_secs(minutes * 60),
+            min_messages,
+            message: message.trim().to_string(),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "every {} min after {} messages: {}",
+            self.interval.as_secs() / 60,
+            self.min_messages,
+            self.message
+        )
+    }
+}
+
+struct Running {
+    timer: Timer,
+    due: Instant,
+    messages: u32,
+}
+
+pub struct Timers {
+    running: Vec<Running>,
+    // Percent each wait may be longer or shorter
+    jitter: u8,
+    last_post: Option<Instant>,
+    pub enabled: bool,
+}
+
+impl Timers {
+    pub fn new(timers: &[Timer], jitter: u8) -> Self {
+        let mut new = Self {
+            running: Vec::new(),
+            jitter,
+            last_post: None,
+            enabled: true,
+        };
+        for timer in timers {
+            new.add(timer.clone());
+        }
+        new
+    }
+
+    fn next_due(&self, interval: Duration) -> Instant {
+        let jitter = self.jitter.min(100) as f64 / 100.0;
+        let factor = 1.0 + jitter * (fastrand::f64() * 2.0 - 1.0);
+        Instant::now() + interval.mul_f64(factor)
+    }
+
+    pub fn add(&mut self, timer: Timer) {
+        let due = self.next_due(timer.interval);
+        self.running.push(Running {
+            timer,
+            due,
+            messages: 0,
+        });
+    }
+
+    /// Remove the `n`th timer, counting from 1 as /timer list does.
+    pub fn remove(&mut self, n: usize) -> Option<Timer> {
+        (1..=self.running.len())
+            .contains(&n)
+            .then(|| self.running.remove(n - 1).timer)
+    }
+
+    /// Replace the timers with reloaded ones. Those that didn't change keep
+    /// their schedule.
+    pub fn replace(&mut self, timers: &[Timer], jitter: u8) {
+        self.jitter = jitter;
+        let mut old = std::mem::take(&mut self.running);
+        for timer in timers {
+            match old.iter().position(|running| running.timer == *timer) {
+                Some(i) => self.running.push(old.remove(i)),
+                None => self.add(timer.clone()),
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.running
+            .iter()
+            .enumerate()
+            .map(|(i, running)| format!("{}. {}", i + 1, running.timer.describe()))
+            .collect()
+    }
+
+    /// Count a viewer's chat message.
+    pub fn count_message(&mut self) {
+        for running in &mut self.running {
+            running.messages = running.messages.saturating_add(1);
+        }
+    }
+
+    /// The message to post now, if any.
+    pub fn due(&mut self) -> Option<String> {
+        let resting = self.last_post.is_some_and(|t| t.elapsed() < MIN_GAP);
+        if !self.enabled || resting {
+            return None;
+        }
+        let now = Instant::now();
+        let i = self.running.iter().position(|running| running.due <= now)?;
+        let interval = self.running[i].timer.interval;
+        let due = self.next_due(interval);
+        let running = &mut self.running[i];
+        running.due = due;
+        // A quiet chat skips a round rather than hearing it the moment
+        // someone speaks up
+        if running.messages < running.timer.min_messages {
+            return None;
+        }
+        running.messages = 0;
+        self.last_post = Some(now);
+        Some(running.timer.message.clone())
+    }
+}
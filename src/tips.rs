@@ -0,0 +1,243 @@
+use crate::config::{env_flag, var, Config};
+use crate::state::{AppEvent, StreamAlert};
+use anyhow::{bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+// Donations, which EventSub doesn't know about, from StreamElements and
+// Streamlabs. Both push them over Socket.IO (Engine.IO 3 on a WebSocket):
+// StreamElements with the channel's JWT sent after connecting, Streamlabs
+// with the socket API token in the URL. A tip becomes a StreamAlert::Tip and
+// goes wherever other alerts go; the session also reads its message aloud
+// (TIPS_READ) and has the AI thank the tipper (TIPS_THANK).
+
+// Before connecting again after losing the socket
+const RETRY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TipSettings {
+    // The JWT from StreamElements' dashboard (Account, Channels)
+    pub streamelements_token: Option<String>,
+    // The Socket API token from Streamlabs' dashboard (Settings, API Settings)
+    pub streamlabs_token: Option<String>,
+    pub read: bool,
+    pub thank: bool,
+}
+
+impl TipSettings {
+    // STREAMELEMENTS_TOKEN, STREAMLABS_TOKEN, TIPS_READ, TIPS_THANK
+    pub fn from_env() -> Result<Self> {
+        let token = |name| {
+            var(name)
+                .ok()
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty())
+        };
+        Ok(Self {
+            streamelements_token: token("STREAMELEMENTS_TOKEN"),
+            streamlabs_token: token("STREAMLABS_TOKEN"),
+            read: env_flag("TIPS_READ", true)?,
+            thank: env_flag("TIPS_THANK", true)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipService {
+    StreamElements,
+    Streamlabs,
+}
+
+impl TipService {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TipService::StreamElements => "StreamElements",
+            TipService::Streamlabs => "Streamlabs",
+        }
+    }
+
+    /// The services with a token set, and their tokens.
+    pub fn configured(settings: &TipSettings) -> Vec<(TipService, String)> {
+        [
+            (TipService::StreamElements, &settings.streamelements_token),
+            (TipService::Streamlabs, &settings.streamlabs_token),
+        ]
+        .into_iter()
+        .filter_map(|(service, token)| Some((service, token.clone()?)))
+        .collect()
+    }
+
+    /// The tips in a Socket.IO event from this service, if it's about any.
+    pub fn tips(&self, name: &str, data: &Value) -> Vec<StreamAlert> {
+        let text = |value: &Value| value.as_str().unwrap_or_default().trim().to_string();
+        // Either service sends amounts as numbers or as strings
+        let amount = |value: &Value| {
+            value
+                .as_f64()
+                .or_else(|| value.as_str()?.trim().parse().ok())
+        };
+        let tip = |user: &Value, amount: Option<f64>, currency: &Value, message: &Value| {
+            Some(StreamAlert::Tip {
+                user: Some(text(user))
+                    .filter(|user| !user.is_empty())
+                    .unwrap_or_else(|| "Anonymous".to_string()),
+                amount: amount?,
+                currency: text(currency),
+                message: text(message),
+            })
+        };
+        match self {
+            // ["event", {"type": "tip", "data": {"username", "amount", ...}}];
+            // Twitch's own events come this way too and are left to EventSub
+            TipService::StreamElements if name == "event" && data["type"] == "tip" => {
+                let tip_data = &data["data"];
+                tip(
+                    &tip_data["username"],
+                    amount(&tip_data["amount"]),
+                    &tip_data["currency"],
+                    &tip_data["message"],
+                )
+                .into_iter()
+                .collect()
+            }
+            // ["event", {"type": "donation", "message": [{"name", "amount", ...}]}]
+            TipService::Streamlabs if name == "event" && data["type"] == "donation" => data
+                ["message"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|donation| {
+                    tip(
+                        &donation["name"],
+                        amount(&donation["amount"]),
+                        &donation["currency"],
+                        &donation["message"],
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn url(&self, config: &Config, token: &str) -> String {
+        match self {
+            TipService::StreamElements => config.endpoints.streamelements.clone(),
+            TipService::Streamlabs => {
+                format!("{}&token={}", config.endpoints.streamlabs, token)
+            }
+        }
+    }
+}
+
+/// Stay connected to `service`, sending its tips as alerts, until its token
+/// is refused.
+pub async fn run(
+    service: TipService,
+    token: String,
+    config: Arc<Config>,
+    tx: mpsc::UnboundedSender<AppEvent>,
+) -> Result<()> {
+    loop {
+        match listen(service, &token, &config, &tx).await {
+            Ok(true) => {}
+            Ok(false) => bail!("{} refused the token", service.name()),
+            Err(e) => {
+                let _ = tx.send(AppEvent::Debug(format!("{}: {:#}", service.name(), e)));
+            }
+        }
+        tokio::time::sleep(RETRY).await;
+    }
+}
+
+// Until the socket closes. Returns whether it's worth connecting again.
+async fn listen(
+    service: TipService,
+    token: &str,
+    config: &Config,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+) -> Result<bool> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(service.url(config, token))
+        .await
+        .with_context(|| format!("Connecting to {} failed", service.name()))?;
+    // The client pings, as often as the open packet says
+    let mut ping = tokio::time::interval(Duration::from_secs(25));
+    loop {
+        let frame = tokio::select! {
+            frame = socket.next() => frame,
+            _ = ping.tick() => {
+                socket.send(Message::Text("2".into())).await?;
+                continue;
+            }
+        };
+        let text = match frame {
+            Some(Ok(Message::Text(text))) => text.to_string(),
+            Some(Ok(Message::Close(_))) | None => return Ok(true),
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+        };
+        match parse_packet(&text) {
+            Packet::Open { ping_interval } => {
+                ping = tokio::time::interval(ping_interval);
+                ping.tick().await;
+            }
+            Packet::Connected => {
+                let _ = tx.send(AppEvent::Info(format!("{} connected", service.name())));
+                if service == TipService::StreamElements {
+                    let authenticate = json!(["authenticate", { "method": "jwt", "token": token }]);
+                    socket
+                        .send(Message::Text(format!("42{}", authenticate).into()))
+                        .await?;
+                }
+            }
+            Packet::Event(name, _) if name == "unauthorized" => return Ok(false),
+            Packet::Event(name, data) => {
+                for tip in service.tips(&name, &data) {
+                    let _ = tx.send(AppEvent::Alert(tip));
+                }
+            }
+            Packet::Disconnected => return Ok(true),
+            Packet::Other => {}
+        }
+    }
+}
+
+/// An Engine.IO 3 packet with the Socket.IO packet in it, as far as the tips
+/// need them.
+#[derive(Debug, PartialEq)]
+pub enum Packet {
+    Open { ping_interval: Duration },
+    Connected,
+    // The event's name and its first argument
+    Event(String, Value),
+    Disconnected,
+    // Pongs and anything else
+    Other,
+}
+
+pub fn parse_packet(text: &str) -> Packet {
+    if let Some(open) = text.strip_prefix('0') {
+        let open: Value = serde_json::from_str(open).unwrap_or_default();
+        let ping_interval = open["pingInterval"].as_u64().unwrap_or(25_000);
+        return Packet::Open {
+            ping_interval: Duration::from_millis(ping_interval.max(1000)),
+        };
+    }
+    match text.strip_prefix('4') {
+        Some("0") => Packet::Connected,
+        Some("1") => Packet::Disconnected,
+        Some(event) if event.starts_with('2') => {
+            // 42["name", data] (no namespace or ack id is used)
+            let event: Value = serde_json::from_str(&event[1..]).unwrap_or_default();
+            match event[0].as_str() {
+                Some(name) => Packet::Event(name.to_string(), event[1].clone()),
+                None => Packet::Other,
+            }
+        }
+        _ if text == "1" => Packet::Disconnected,
+        _ => Packet::Other,
+    }
+}
@@ -0,0 +1,155 @@
+use crate::ai;
+use crate::config::{var, Config};
+use crate::sqlite::{Store, Writer};
+use crate::state::AppEvent;
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::path::Path;
+use tokio::sync::mpsc::UnboundedSender;
+
+// Chat translated by the AI into TRANSLATE_LANGUAGE. With TRANSLATE=auto,
+// every viewer message lang.rs takes for another language is translated,
+// and the translation posted in chat or only shown on the Chat tab
+// (TRANSLATE_OUTPUT). With TRANSLATE=command it's only on request:
+//
+//   !translate <text>    the translation, in chat
+//   !translate off       your messages aren't translated automatically
+//   !translate on        they are again
+//
+// Who opted out is kept in the chat database.
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS translate_opt_outs (
+    user TEXT PRIMARY KEY,
+    timestamp TEXT NOT NULL
+);
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranslateMode {
+    Off,
+    Command,
+    Auto,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranslateSettings {
+    pub mode: TranslateMode,
+    // ISO 639-1, e.g. "en"
+    pub language: String,
+    // Post automatic translations in chat, rather than only on the Chat tab
+    pub to_chat: bool,
+}
+
+impl TranslateSettings {
+    // TRANSLATE, TRANSLATE_LANGUAGE, TRANSLATE_OUTPUT
+    pub fn from_env() -> Result<Self> {
+        let mode = match var("TRANSLATE")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "off" => TranslateMode::Off,
+            "command" => TranslateMode::Command,
+            "auto" => TranslateMode::Auto,
+            other => bail!("TRANSLATE must be off, command or auto, got '{}'", other),
+        };
+        let language = var("TRANSLATE_LANGUAGE")
+            .map(|language| language.trim().to_lowercase())
+            .unwrap_or_else(|_| "en".to_string());
+        // Messages are only translated when lang.rs can tell they aren't
+        // in this language already
+        if !crate::lang::is_supported(&language) {
+            bail!(
+                "TRANSLATE_LANGUAGE must be one of {}, got '{}'",
+                crate::lang::codes().collect::<Vec<_>>().join(", "),
+                language
+            );
+        }
+        let to_chat = match var("TRANSLATE_OUTPUT")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "local" => false,
+            "chat" => true,
+            other => bail!("TRANSLATE_OUTPUT must be chat or local, got '{}'", other),
+        };
+        Ok(Self {
+            mode,
+            language,
+            to_chat,
+        })
+    }
+}
+
+/// The language `text` is in, if it's one lang.rs recognizes and not
+/// `language`
+pub fn foreign_language(text: &str, language: &str) -> Option<&'static str> {
+    crate::lang::detect(text).filter(|detected| *detected != language)
+}
+
+/// "Spanish" for "es"; codes lang.rs doesn't know stay as they are
+pub fn language_name(code: &str) -> &str {
+    crate::lang::name(code).unwrap_or(code)
+}
+
+/// `text` in `language`, by the AI
+pub async fn translate(text: &str, language: &str, config: &Config) -> Result<String> {
+    let name = language_name(language);
+    let system = format!(
+        "You translate Twitch chat messages into {}. Answer with the translation and \
+         nothing else, keeping names, emotes and the tone. If the message is already in \
+         {}, repeat it unchanged.",
+        name, name
+    );
+    let translation = ai::ask(text, &system, config).await?;
+    let translation = translation.trim();
+    if translation.is_empty() {
+        bail!("The AI gave no translation");
+    }
+    Ok(translation.to_string())
+}
+
+pub struct OptOuts {
+    users: HashSet<String>,
+    writes: Writer,
+}
+
+impl OptOuts {
+    pub fn open(path: &Path, events: UnboundedSender<AppEvent>) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?;
+        let users = db
+            .query("SELECT user FROM translate_opt_outs", &[])?
+            .iter()
+            .filter_map(|row| Some(row.first()?.as_str()?.to_string()))
+            .collect();
+
+        let writes = db.writer("translation opt-outs", events);
+        Ok(Self { users, writes })
+    }
+
+    pub fn contains(&self, user: &str) -> bool {
+        self.users.contains(&user.to_lowercase())
+    }
+
+    /// Opt `user` out of automatic translation, or back in
+    pub fn set(&mut self, user: &str, opted_out: bool) {
+        let user = user.to_lowercase();
+        if opted_out {
+            if self.users.insert(user.clone()) {
+                self.writes.write(
+                    "INSERT OR IGNORE INTO translate_opt_outs (user, timestamp) VALUES (?, ?)",
+                    vec![user.into(), crate::chatlog::timestamp().into()],
+                );
+            }
+        } else if self.users.remove(&user) {
+            self.writes.write(
+                "DELETE FROM translate_opt_outs WHERE user = ?",
+                vec![user.into()],
+            );
+        }
+    }
+}
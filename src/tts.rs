@@ -0,0 +1,812 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// Text-to-speech backends. There's no cross-platform speech crate we can
+// lean on, so each OS gets its built-in engine as a subprocess: `say` on
+// macOS, SAPI through PowerShell on Windows, piper (when a voice model is
+// configured) or espeak elsewhere. espeak is also the fallback whenever the
+// chosen backend fails to run.
+//
+// Cloud voices (ElevenLabs, OpenAI, Azure) can be picked per kind of message
+// on top of that; they synthesize MP3 over HTTP and play it with rodio.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Say,
+    Sapi,
+    Piper,
+    Espeak,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 4] = [Backend::Say, Backend::Sapi, Backend::Piper, Backend::Espeak];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Say => "say",
+            Backend::Sapi => "sapi",
+            Backend::Piper => "piper",
+            Backend::Espeak => "espeak",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Backend> {
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+// What's being read, so each kind can get its own voice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpeechKind {
+    Chat,
+    Join,
+    // The bot's own (AI) replies
+    Ai,
+    // Channel point "read my message" redemptions, and tips' messages
+    Redeem,
+}
+
+impl SpeechKind {
+    pub const ALL: [SpeechKind; 4] = [
+        SpeechKind::Chat,
+        SpeechKind::Join,
+        SpeechKind::Ai,
+        SpeechKind::Redeem,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpeechKind::Chat => "chat",
+            SpeechKind::Join => "join",
+            SpeechKind::Ai => "ai",
+            SpeechKind::Redeem => "redeem",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    ElevenLabs,
+    OpenAi,
+    Azure,
+}
+
+#[derive(Debug, Clone)]
+pub struct CloudVoice {
+    pub provider: Provider,
+    pub api_key: String,
+    // ElevenLabs voice id, OpenAI voice name or Azure voice name
+    pub voice: String,
+    // ElevenLabs model id or OpenAI model; Azure has none
+    pub model: String,
+    // Azure only
+    pub region: String,
+}
+
+impl CloudVoice {
+    fn from_env(provider: Provider) -> Result<Self> {
+        let var = |name: &str| crate::config::var(name).ok().map(|s| s.trim().to_string());
+        let key = |name: &str| {
+            var(name)
+                .filter(|s| !s.is_empty())
+                .with_context(|| format!("{} not set", name))
+        };
+        Ok(match provider {
+            Provider::ElevenLabs => Self {
+                provider,
+                api_key: key("ELEVENLABS_API_KEY")?,
+                voice: var("ELEVENLABS_VOICE_ID")
+                    .unwrap_or_else(|| "21m00Tcm4TlvDq8Ikt00".to_string()),
+                model: var("ELEVENLABS_MODEL").unwrap_or_else(|| "eleven_turbo_v2_5".to_string()),
+                region: String::new(),
+            },
+            Provider::OpenAi => Self {
+                provider,
+                api_key: key("OPENAI_API_KEY")?,
+                voice: var("OPENAI_TTS_VOICE").unwrap_or_else(|| "alloy".to_string()),
+                model: var("OPENAI_TTS_MODEL").unwrap_or_else(|| "tts-1".to_string()),
+                region: String::new(),
+            },
+            Provider::Azure => Self {
+                provider,
+                api_key: key("AZURE_SPEECH_KEY")?,
+                voice: var("AZURE_TTS_VOICE").unwrap_or_else(|| "en-US-JennyNeural".to_string()),
+                model: String::new(),
+                region: key("AZURE_SPEECH_REGION")?,
+            },
+        })
+    }
+
+    // MP3 audio of `text`. Rate applies to all providers (ElevenLabs within its
+    // narrower 70-120% range), pitch only to Azure.
+    async fn synthesize(
+        &self,
+        client: &reqwest::Client,
+        text: &str,
+        settings: &VoiceSettings,
+    ) -> Result<Vec<u8>> {
+        let request = match self.provider {
+            Provider::ElevenLabs => client
+                .post(format!(
+                    "https://api.elevenlabs.io/v1/text-to-speech/{}",
+                    self.voice
+                ))
+                .header("xi-api-key", &self.api_key)
+                .header("Accept", "audio/mpeg")
+                .json(&serde_json::json!({
+                    "text": text,
+                    "model_id": self.model,
+                    "voice_settings": { "speed": settings.speed().clamp(0.7, 1.2) },
+                })),
+            Provider::OpenAi => client
+                .post("https://api.openai.com/v1/audio/speech")
+                .bearer_auth(&self.api_key)
+                .json(&serde_json::json!({
+                    "model": self.model,
+                    "voice": self.voice,
+                    "input": text,
+                    "response_format": "mp3",
+                    "speed": settings.speed().clamp(0.25, 4.0),
+                })),
+            Provider::Azure => client
+                .post(format!(
+                    "https://{}.tts.speech.microsoft.com/cognitiveservices/v1",
+                    self.region
+                ))
+                .header("Ocp-Apim-Subscription-Key", &self.api_key)
+                .header("Content-Type", "application/ssml+xml")
+                .header(
+                    "X-Microsoft-OutputFormat",
+                    "audio-24khz-48kbitrate-mono-mp3",
+                )
+                .body(format!(
+                    "<speak version='1.0' xml:lang='en-US'><voice name='{}'>\
+                     <prosody rate='{:+}%' pitch='{:+}%'>{}</prosody></voice></speak>",
+                    self.voice,
+                    settings.rate_change(),
+                    settings.pitch_change(),
+                    escape_xml(text)
+                )),
+        };
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("{:?} TTS returned {}: {}", self.provider, status, body);
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+/// How messages are spoken, changed at runtime with /tts rate|pitch|voice.
+/// Rate and pitch are percent of the engine's normal; not every engine can
+/// change pitch (piper, OpenAI and ElevenLabs can't).
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceSettings {
+    pub rate: u16,
+    pub pitch: u16,
+    // Local voice name (model path for piper); None is the backend's default.
+    // Cloud voices keep the voice they were configured with.
+    pub voice: Option<String>,
+}
+
+pub const RATE_RANGE: std::ops::RangeInclusive<u16> = 50..=300;
+pub const PITCH_RANGE: std::ops::RangeInclusive<u16> = 50..=200;
+
+// Words per minute espeak and say read at by default
+const NORMAL_WPM: f32 = 175.0;
+
+impl Default for VoiceSettings {
+    fn default() -> Self {
+        Self {
+            rate: 100,
+            pitch: 100,
+            voice: None,
+        }
+    }
+}
+
+impl VoiceSettings {
+    /// TTS_RATE, TTS_PITCH and TTS_LOCAL_VOICE.
+    fn from_env() -> Result<Self> {
+        let percent = |name: &str, range: std::ops::RangeInclusive<u16>, default: u16| {
+            match crate::config::var(name) {
+                Ok(value) => value
+                    .trim()
+                    .trim_end_matches('%')
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|value| range.contains(value))
+                    .with_context(|| {
+                        format!(
+                            "{} must be {} to {} (percent), got '{}'",
+                            name,
+                            range.start(),
+                            range.end(),
+                            value
+                        )
+                    }),
+                Err(_) => Ok(default),
+            }
+        };
+        Ok(Self {
+            rate: percent("TTS_RATE", RATE_RANGE, 100)?,
+            pitch: percent("TTS_PITCH", PITCH_RANGE, 100)?,
+            voice: crate::config::var("TTS_LOCAL_VOICE")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "rate {}%, pitch {}%, voice {}",
+            self.rate,
+            self.pitch,
+            self.voice.as_deref().unwrap_or("default")
+        )
+    }
+
+    fn speed(&self) -> f32 {
+        self.rate as f32 / 100.0
+    }
+
+    fn wpm(&self) -> u32 {
+        (NORMAL_WPM * self.speed()).round() as u32
+    }
+
+    // Signed percent change, for SSML prosody
+    fn rate_change(&self) -> i32 {
+        self.rate as i32 - 100
+    }
+
+    fn pitch_change(&self) -> i32 {
+        self.pitch as i32 - 100
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tts {
+    pub backend: Backend,
+    // .onnx voice for piper
+    pub piper_model: Option<String>,
+    // Kinds of message read by a cloud voice; the rest stay local
+    pub cloud: HashMap<SpeechKind, CloudVoice>,
+    // Characters sent to cloud voices per run before falling back to local
+    pub cloud_char_limit: usize,
+    // Local voice for redemptions, so they stand out from chat; None uses
+    // the backend's default pick (see redeem_voice)
+    pub redeem_voice: Option<String>,
+    // Pick the voice by the language each message is written in
+    pub detect_language: bool,
+    // Language code -> voice (model path for piper), over the built-in picks
+    pub language_voices: HashMap<String, String>,
+    // Starting rate, pitch and voice
+    pub settings: VoiceSettings,
+}
+
+impl Tts {
+    /// TTS_BACKEND (auto, say, sapi, piper, espeak) and PIPER_MODEL.
+    /// "auto" picks the platform's engine, or piper on Linux if a model is set.
+    pub fn from_env() -> Result<Self> {
+        let piper_model = crate::config::var("PIPER_MODEL")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        let choice = crate::config::var("TTS_BACKEND").unwrap_or_default();
+        let backend = match choice.trim() {
+            "" | "auto" => Self::native(piper_model.is_some()),
+            name => Backend::from_name(name).with_context(|| {
+                format!(
+                    "TTS_BACKEND must be auto, say, sapi, piper or espeak, got '{}'",
+                    name
+                )
+            })?,
+        };
+        if backend == Backend::Piper && piper_model.is_none() {
+            bail!("TTS_BACKEND=piper needs PIPER_MODEL set to a voice .onnx file");
+        }
+
+        // TTS_VOICE_CHAT, TTS_VOICE_JOIN, TTS_VOICE_AI: local, elevenlabs, openai or azure
+        let mut cloud = HashMap::new();
+        for kind in SpeechKind::ALL {
+            let name = format!("TTS_VOICE_{}", kind.name().to_uppercase());
+            let choice = crate::config::var(&name).unwrap_or_default();
+            let provider = match choice.trim().to_lowercase().as_str() {
+                "" | "local" => continue,
+                "elevenlabs" => Provider::ElevenLabs,
+                "openai" => Provider::OpenAi,
+                "azure" => Provider::Azure,
+                other => bail!(
+                    "{} must be local, elevenlabs, openai or azure, got '{}'",
+                    name,
+                    other
+                ),
+            };
+            let voice = CloudVoice::from_env(provider)
+                .with_context(|| format!("{}={}", name, choice.trim()))?;
+            cloud.insert(kind, voice);
+        }
+        let cloud_char_limit = match crate::config::var("TTS_CLOUD_CHAR_LIMIT") {
+            Ok(limit) => limit.trim().parse().with_context(|| {
+                format!("TTS_CLOUD_CHAR_LIMIT must be a number, got '{}'", limit)
+            })?,
+            Err(_) => 20_000,
+        };
+
+        Ok(Self {
+            backend,
+            piper_model,
+            cloud,
+            cloud_char_limit,
+            redeem_voice: crate::config::var("TTS_REDEEM_VOICE")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            detect_language: crate::config::env_flag("TTS_DETECT_LANGUAGE", true)?,
+            language_voices: parse_language_voices(
+                &crate::config::var("TTS_LANGUAGE_VOICES").unwrap_or_default(),
+            )?,
+            settings: VoiceSettings::from_env()?,
+        })
+    }
+
+    fn native(have_piper_model: bool) -> Backend {
+        if cfg!(target_os = "macos") {
+            Backend::Say
+        } else if cfg!(windows) {
+            Backend::Sapi
+        } else if have_piper_model {
+            Backend::Piper
+        } else {
+            Backend::Espeak
+        }
+    }
+
+    // Voice name in the backend's terms (a piper model path for piper).
+    // Redemptions get their own voice; messages in another language than
+    // English get one that speaks it, which espeak combines with a variant.
+    // Anything else is read in the `chosen` voice.
+    fn voice(
+        &self,
+        kind: SpeechKind,
+        backend: Backend,
+        language: Option<&str>,
+        chosen: Option<&str>,
+    ) -> Option<String> {
+        let redeem = kind == SpeechKind::Redeem;
+        if let Some(voice) = self.redeem_voice.as_ref().filter(|_| redeem) {
+            return Some(voice.clone());
+        }
+        if let Some(language) = language.filter(|language| *language != "en") {
+            if let Some(voice) = self.language_voices.get(language) {
+                return Some(voice.clone());
+            }
+            let voice = match backend {
+                Backend::Espeak if redeem => Some(format!("{}+f3", language)),
+                Backend::Espeak => Some(language.to_string()),
+                Backend::Say => say_voice(language).map(str::to_string),
+                Backend::Sapi => sapi_voice(language).map(str::to_string),
+                Backend::Piper => None,
+            };
+            if voice.is_some() {
+                return voice;
+            }
+        }
+        if !redeem {
+            return chosen.map(str::to_string);
+        }
+        match backend {
+            Backend::Espeak => Some("en+f3".to_string()),
+            Backend::Say => Some("Samantha".to_string()),
+            Backend::Sapi => Some("Microsoft Zira Desktop".to_string()),
+            Backend::Piper => None,
+        }
+    }
+
+    /// Language to pick the voice for `message` by, if detection is on.
+    pub fn language_of(&self, message: &str) -> Option<&'static str> {
+        if self.detect_language {
+            crate::lang::detect(message)
+        } else {
+            None
+        }
+    }
+
+    /// Speak `text` at `volume` (0.0 to 1.0), blocking until it's done or
+    /// skipped through `playback`. Falls back to espeak if the configured
+    /// backend can't be run; the error covers both attempts.
+    pub fn speak(
+        &self,
+        kind: SpeechKind,
+        text: &str,
+        language: Option<&str>,
+        playback: &Playback,
+        volume: f32,
+        settings: &VoiceSettings,
+    ) -> Result<()> {
+        let voice = self.voice(kind, self.backend, language, settings.voice.as_deref());
+        match self.speak_with(
+            self.backend,
+            voice.as_deref(),
+            text,
+            playback,
+            volume,
+            settings,
+        ) {
+            Ok(()) => Ok(()),
+            // A custom voice name is usually specific to the failed backend
+            Err(e) if self.backend != Backend::Espeak => self
+                .speak_with(
+                    Backend::Espeak,
+                    self.voice(kind, Backend::Espeak, language, None)
+                        .filter(|_| self.redeem_voice.is_none())
+                        .as_deref(),
+                    text,
+                    playback,
+                    volume,
+                    settings,
+                )
+                .with_context(|| {
+                    format!("{} failed ({:#}), espeak fallback", self.backend.name(), e)
+                }),
+            Err(e) => Err(e),
+        }
+    }
+
+    // The subprocess engines play audio themselves, so volume goes to each
+    // one in its own terms; piper's output plays through rodio. They can't be
+    // turned down mid-reading, so they only start ducked.
+    fn speak_with(
+        &self,
+        backend: Backend,
+        voice: Option<&str>,
+        text: &str,
+        playback: &Playback,
+        volume: f32,
+        settings: &VoiceSettings,
+    ) -> Result<()> {
+        let level = playback.level(volume).clamp(0.0, 1.0);
+        let percent = (level * 100.0).round() as u32;
+        match backend {
+            // Text goes over stdin so a message starting with '-' isn't taken as a flag.
+            // [[volm]] and [[pbas]] are say's embedded volume and pitch commands;
+            // pitch moves in semitones.
+            Backend::Say => run_with_input(
+                Command::new("say")
+                    .args(["-f", "-", "-r", &settings.wpm().to_string()])
+                    .args(voice.map(|voice| ["-v", voice]).into_iter().flatten()),
+                &format!(
+                    "[[volm {:.2}]] {}{}",
+                    level,
+                    if settings.pitch == 100 {
+                        String::new()
+                    } else {
+                        format!(
+                            "[[pbas {:+.1}]] ",
+                            12.0 * (settings.pitch as f32 / 100.0).log2()
+                        )
+                    },
+                    text
+                ),
+                &playback.skip,
+            ),
+            Backend::Sapi => run_with_input(
+                Command::new("powershell").args([
+                    "-NoProfile",
+                    "-Command",
+                    &format!(
+                        "Add-Type -AssemblyName System.Speech; \
+                         $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+                         {}$s.Volume = {}; $s.Rate = {}; $t = [Console]::In.ReadToEnd(); {}",
+                        voice
+                            .map(|voice| format!(
+                                "try {{ $s.SelectVoice('{}') }} catch {{}}; ",
+                                voice.replace('\'', "''")
+                            ))
+                            .unwrap_or_default(),
+                        percent,
+                        sapi_rate(settings.rate),
+                        // SAPI has no pitch setting, only SSML prosody
+                        if settings.pitch == 100 {
+                            "$s.Speak($t)".to_string()
+                        } else {
+                            format!(
+                                "$s.SpeakSsml(\"<speak version='1.0' \
+                                 xmlns='http://www.w3.org/2001/10/synthesis' xml:lang='en-US'>\
+                                 <prosody pitch='{:+}%'>$([Security.SecurityElement]::Escape($t))\
+                                 </prosody></speak>\")",
+                                settings.pitch_change()
+                            )
+                        }
+                    ),
+                ]),
+                text,
+                &playback.skip,
+            ),
+            Backend::Piper => self.speak_piper(voice, text, playback, volume, settings),
+            Backend::Espeak => {
+                // espeak-ng is the maintained fork; plain espeak is the older package name.
+                // Amplitude 100 and pitch 50 are espeak's defaults.
+                let amplitude = percent.to_string();
+                let speed = settings.wpm().to_string();
+                let pitch = (settings.pitch / 2).min(99).to_string();
+                let espeak = |program: &str| {
+                    run_with_input(
+                        Command::new(program)
+                            .args(["-a", &amplitude, "-s", &speed, "-p", &pitch, "--stdin"])
+                            .args(voice.map(|voice| ["-v", voice]).into_iter().flatten()),
+                        text,
+                        &playback.skip,
+                    )
+                };
+                espeak("espeak-ng").or_else(|_| espeak("espeak"))
+            }
+        }
+    }
+
+    // piper only writes audio, so render to a temporary WAV and play it with rodio
+    fn speak_piper(
+        &self,
+        model: Option<&str>,
+        text: &str,
+        playback: &Playback,
+        volume: f32,
+        settings: &VoiceSettings,
+    ) -> Result<()> {
+        static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+        let model = model
+            .or(self.piper_model.as_deref())
+            .context("PIPER_MODEL not set")?;
+        let wav = std::env::temp_dir().join(format!(
+            "choui-tts-{}-{}.wav",
+            std::process::id(),
+            NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        run_with_input(
+            Command::new("piper")
+                .args(["--model", model, "--output_file"])
+                .arg(&wav)
+                // Length is the inverse of speed
+                .args(["--length_scale", &format!("{:.2}", 1.0 / settings.speed())]),
+            text,
+            &playback.skip,
+        )?;
+        let played = if playback.skip.load(Ordering::Relaxed) {
+            Ok(())
+        } else {
+            play_wav(&wav, playback, volume)
+        };
+        let _ = std::fs::remove_file(&wav);
+        played
+    }
+}
+
+// SAPI rates run -10 to 10, from a third to three times normal speed
+fn sapi_rate(rate: u16) -> i32 {
+    ((rate as f32 / 100.0).log(3.0) * 10.0)
+        .round()
+        .clamp(-10.0, 10.0) as i32
+}
+
+// "de=/voices/de.onnx, es=Monica"
+fn parse_language_voices(list: &str) -> Result<HashMap<String, String>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (language, voice) = entry.split_once('=').with_context(|| {
+                format!(
+                    "TTS_LANGUAGE_VOICES entries look like de=voice, got '{}'",
+                    entry
+                )
+            })?;
+            Ok((language.trim().to_lowercase(), voice.trim().to_string()))
+        })
+        .collect()
+}
+
+// Voices that ship with macOS
+fn say_voice(language: &str) -> Option<&'static str> {
+    Some(match language {
+        "es" => "Monica",
+        "de" => "Anna",
+        "fr" => "Thomas",
+        "it" => "Alice",
+        "pt" => "Luciana",
+        "nl" => "Xander",
+        "pl" => "Zosia",
+        "ru" => "Milena",
+        "el" => "Melina",
+        "he" => "Carmit",
+        "ar" => "Maged",
+        "hi" => "Lekha",
+        "th" => "Kanya",
+        "ja" => "Kyoko",
+        "ko" => "Yuna",
+        "zh" => "Tingting",
+        _ => return None,
+    })
+}
+
+// Windows voices from the matching language pack
+fn sapi_voice(language: &str) -> Option<&'static str> {
+    Some(match language {
+        "es" => "Microsoft Helena Desktop",
+        "de" => "Microsoft Hedda Desktop",
+        "fr" => "Microsoft Hortense Desktop",
+        "it" => "Microsoft Elsa Desktop",
+        "pt" => "Microsoft Maria Desktop",
+        "pl" => "Microsoft Paulina Desktop",
+        "ru" => "Microsoft Irina Desktop",
+        "ja" => "Microsoft Haruka Desktop",
+        "ko" => "Microsoft Heami Desktop",
+        "zh" => "Microsoft Huihui Desktop",
+        _ => return None,
+    })
+}
+
+// Reads messages for the TTS worker thread: cloud voices where configured
+// (until the character cap runs out), the local backend for everything else.
+pub struct Speaker {
+    tts: Tts,
+    client: reqwest::Client,
+    runtime: tokio::runtime::Runtime,
+    cloud_chars: usize,
+    cap_reported: bool,
+}
+
+impl Speaker {
+    pub fn new(tts: Tts) -> Result<Self> {
+        Ok(Self {
+            tts,
+            client: reqwest::Client::new(),
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?,
+            cloud_chars: 0,
+            cap_reported: false,
+        })
+    }
+
+    pub fn language_of(&self, message: &str) -> Option<&'static str> {
+        self.tts.language_of(message)
+    }
+
+    /// Blocks until `text` has been read, or skipped through `playback`. A
+    /// failed or capped cloud voice still reads the text locally; the error is
+    /// returned so it can be logged.
+    pub fn speak(
+        &mut self,
+        kind: SpeechKind,
+        text: &str,
+        language: Option<&str>,
+        playback: &Playback,
+        volume: f32,
+        settings: &VoiceSettings,
+    ) -> Result<()> {
+        let Some(voice) = self.tts.cloud.get(&kind) else {
+            return self
+                .tts
+                .speak(kind, text, language, playback, volume, settings);
+        };
+
+        let chars = text.chars().count();
+        if self.cloud_chars + chars > self.tts.cloud_char_limit {
+            self.tts
+                .speak(kind, text, language, playback, volume, settings)?;
+            if !self.cap_reported {
+                self.cap_reported = true;
+                bail!(
+                    "Cloud TTS cap of {} characters reached, reading locally from now on",
+                    self.tts.cloud_char_limit
+                );
+            }
+            return Ok(());
+        }
+        self.cloud_chars += chars;
+
+        let audio = self
+            .runtime
+            .block_on(voice.synthesize(&self.client, text, settings));
+        match audio.and_then(|audio| play_audio(audio, playback, volume)) {
+            Ok(()) => Ok(()),
+            Err(_) if playback.skip.load(Ordering::Relaxed) => Ok(()),
+            Err(e) => {
+                self.tts
+                    .speak(kind, text, language, playback, volume, settings)?;
+                Err(e.context("Cloud TTS failed, read locally instead"))
+            }
+        }
+    }
+}
+
+/// Controls for the reading in progress, shared with the thread reading it.
+#[derive(Debug, Default)]
+pub struct Playback {
+    /// Cut the reading short.
+    pub skip: AtomicBool,
+    /// Turn the reading down, while an alert sound plays over it.
+    pub ducked: AtomicBool,
+}
+
+/// Share of its volume a ducked sound keeps.
+pub const DUCKED_LEVEL: f32 = 0.3;
+
+impl Playback {
+    // Volume to play at right now
+    fn level(&self, volume: f32) -> f32 {
+        if self.ducked.load(Ordering::Relaxed) {
+            volume * DUCKED_LEVEL
+        } else {
+            volume
+        }
+    }
+}
+
+// How often a running reading checks whether it was skipped
+const SKIP_POLL: std::time::Duration = std::time::Duration::from_millis(50);
+
+// A skipped run is killed and counts as success, so it doesn't trigger the fallback
+fn run_with_input(command: &mut Command, input: &str, skip: &AtomicBool) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if skip.load(Ordering::Relaxed) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(());
+        }
+        std::thread::sleep(SKIP_POLL);
+    };
+    if !status.success() {
+        bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+fn play_wav(path: &std::path::Path, playback: &Playback, volume: f32) -> Result<()> {
+    play_audio(std::fs::read(path)?, playback, volume)
+}
+
+// Any format rodio can decode (WAV from piper, MP3 from the cloud voices)
+fn play_audio(audio: Vec<u8>, playback: &Playback, volume: f32) -> Result<()> {
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&handle)?;
+    sink.set_volume(playback.level(volume));
+    sink.append(rodio::Decoder::new(std::io::Cursor::new(audio))?);
+    while !sink.empty() {
+        if playback.skip.load(Ordering::Relaxed) {
+            sink.stop();
+            break;
+        }
+        sink.set_volume(playback.level(volume));
+        std::thread::sleep(SKIP_POLL);
+    }
+    Ok(())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
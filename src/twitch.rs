@@ -1,16 +1,22 @@
 use crate::config::Config;
+use crate::paths;
+use crate::secrets::{self, SecretStore};
 use anyhow::{bail, Context, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::fs;
 use std::io::Write;
-use std::path::Path;
+use std::path::PathBuf;
 
 pub async fn send_chat_message(message: &str, config: &Config) -> Result<()> {
     // Note: To send chat, we need 'user:write:chat' scope.
     // The device flow requested 'user:read:chat user:write:chat'.
 
+    if config.demo {
+        crate::demo::say(message);
+        return Ok(());
+    }
     let token = config.oauth_token.as_ref().context("Token not set")?;
     let broadcaster_id = config
         .channel_user_id
@@ -25,7 +31,7 @@ pub async fn send_chat_message(message: &str, config: &Config) -> Result<()> {
     });
 
     let resp = client
-        .post("https://api.twitch.tv/helix/chat/messages")
+        .post(format!("{}/chat/messages", config.endpoints.helix))
         .header("Authorization", format!("Bearer {}", token))
         .header("Client-Id", &config.client_id)
         .header("Content-Type", "application/json")
@@ -46,7 +52,7 @@ pub async fn get_user_id(client: &Client, config: &Config, login: &str) -> Resul
     let token = config.oauth_token.as_ref().context("Token not set")?;
 
     let resp = client
-        .get("https://api.twitch.tv/helix/users")
+        .get(format!("{}/users", config.endpoints.helix))
         .query(&[("login", login)])
         .header("Authorization", format!("Bearer {}", token))
         .header("Client-Id", &config.client_id)
@@ -71,7 +77,7 @@ pub async fn get_user_login(client: &Client, config: &Config, id: &str) -> Resul
     let token = config.oauth_token.as_ref().context("Token not set")?;
 
     let resp = client
-        .get("https://api.twitch.tv/helix/users")
+        .get(format!("{}/users", config.endpoints.helix))
         .query(&[("id", id)])
         .header("Authorization", format!("Bearer {}", token))
         .header("Client-Id", &config.client_id)
@@ -111,17 +117,47 @@ pub struct TokenResponse {
     pub expires_in: Option<u64>,
 }
 
+// Required scopes (chat, the moderation/broadcast calls used by slash commands,
+// the chatter/mod/VIP lists for the user sidebar, the overlay alerts and
+// channel point redemptions, native polls, AutoMod holds, raid shoutouts,
+// deleting links, slow mode when chat gets fast, Shield Mode and
+// followers-only chat against hate raids)
+pub const SCOPES: &[&str] = &[
+    "user:read:chat",
+    "user:write:chat",
+    "chat:read",
+    "chat:edit",
+    "moderator:manage:banned_users",
+    "moderator:manage:announcements",
+    "channel:manage:broadcast",
+    "clips:edit",
+    "moderator:read:chatters",
+    "moderation:read",
+    "channel:read:vips",
+    "moderator:read:followers",
+    "channel:read:subscriptions",
+    "bits:read",
+    "channel:read:redemptions",
+    "channel:manage:polls",
+    "moderator:manage:automod",
+    "moderator:manage:shoutouts",
+    "moderator:manage:chat_messages",
+    "moderator:manage:chat_settings",
+    "moderator:manage:shield_mode",
+];
+
 pub async fn authenticate_via_device_flow(
     client: &Client,
     config: &Config,
 ) -> Result<TokenResponse> {
-    let scopes = "user:read:chat user:write:chat"; // Required scopes
+    let scopes = SCOPES.join(" ");
+    let scopes = scopes.as_str();
 
     // Step 1: Request Device Code
     let params = [("client_id", config.client_id.as_str()), ("scopes", scopes)];
 
     let resp = client
-        .post("https://id.twitch.tv/oauth2/device")
+        .post(format!("{}/device", config.endpoints.auth))
         .form(&params)
         .send()
         .await?;
@@ -154,7 +190,7 @@ pub async fn authenticate_via_device_flow(
         tokio::time::sleep(interval).await;
 
         let token_resp = client
-            .post("https://id.twitch.tv/oauth2/token")
+            .post(format!("{}/token", config.endpoints.auth))
             .form(&token_url_params)
             .send()
             .await?;
@@ -162,7 +198,7 @@ pub async fn authenticate_via_device_flow(
         if token_resp.status().is_success() {
             let token_data: TokenResponse = token_resp.json().await?;
             // Cache token
-            save_token_cache(&token_data)?;
+            save_token_cache(config, &token_data)?;
             return Ok(token_data);
         } else {
             let error_text = token_resp.text().await?;
@@ -177,31 +213,90 @@ pub async fn authenticate_via_device_flow(
     }
 }
 
-pub fn save_token_cache(token: &TokenResponse) -> Result<()> {
+// Keyring entry for the token with SECRET_STORE=keyring
+const TOKEN_SECRET: &str = "twitch_token";
+
+/// The token file in the data directory (DATA_DIR).
+pub fn token_cache_path(config: &Config) -> Result<PathBuf> {
+    let legacy = config.legacy_dir.as_ref();
+    paths::file_in(
+        &config.data_dir,
+        "token.json",
+        legacy.map(|dir| dir.join(".token_cache.json")),
+    )
+}
+
+pub fn save_token_cache(config: &Config, token: &TokenResponse) -> Result<()> {
     let json = serde_json::to_string(token)?;
-    let mut file = std::fs::File::create(".token_cache.json")?;
+    let path = token_cache_path(config)?;
+    if SecretStore::current() == SecretStore::Keyring && secrets::set(TOKEN_SECRET, &json).is_ok() {
+        // Don't leave an older plaintext copy behind
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    let mut file = std::fs::File::create(&path)?;
     file.write_all(json.as_bytes())?;
     Ok(())
 }
 
-pub fn load_token_cache() -> Result<TokenResponse> {
-    if !Path::new(".token_cache.json").exists() {
+pub fn load_token_cache(config: &Config) -> Result<TokenResponse> {
+    let keyring = SecretStore::current() == SecretStore::Keyring;
+    if keyring {
+        if let Ok(Some(json)) = secrets::get(TOKEN_SECRET) {
+            return Ok(serde_json::from_str(&json)?);
+        }
+    }
+    let path = token_cache_path(config)?;
+    if !path.exists() {
         bail!("Cache file not found");
     }
-    let data = fs::read_to_string(".token_cache.json")?;
+    let data = fs::read_to_string(&path)?;
     let token: TokenResponse = serde_json::from_str(&data)?;
+    if keyring {
+        // Moves a token saved before the keyring was turned on
+        let _ = save_token_cache(config, &token);
+    }
     Ok(token)
 }
 
-pub async fn validate_token(client: &Client, token: &str) -> Result<bool> {
+pub async fn validate_token(client: &Client, config: &Config, token: &str) -> Result<bool> {
     let resp = client
-        .get("https://id.twitch.tv/oauth2/validate")
+        .get(format!("{}/validate", config.endpoints.auth))
         .header("Authorization", format!("OAuth {}", token))
         .send()
         .await?;
     Ok(resp.status().is_success())
 }
 
+// What Twitch knows about a token, from /oauth2/validate
+#[derive(Debug, Deserialize)]
+pub struct TokenInfo {
+    pub login: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Seconds left
+    pub expires_in: u64,
+}
+
+/// `Ok(None)` if the token is invalid or expired.
+pub async fn token_info(
+    client: &Client,
+    config: &Config,
+    token: &str,
+) -> Result<Option<TokenInfo>> {
+    let resp = client
+        .get(format!("{}/validate", config.endpoints.auth))
+        .header("Authorization", format!("OAuth {}", token))
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Ok(None);
+    }
+    Ok(Some(resp.json().await?))
+}
+
 pub async fn refresh_token(
     client: &Client,
     config: &Config,
@@ -214,7 +309,7 @@ pub async fn refresh_token(
     ];
 
     let resp = client
-        .post("https://id.twitch.tv/oauth2/token")
+        .post(format!("{}/token", config.endpoints.auth))
         .form(&params)
         .send()
         .await?;
@@ -228,20 +323,102 @@ pub async fn refresh_token(
     Ok(token_data)
 }
 
+/// Returns the subscription's id.
 pub async fn subscribe_to_chat_messages(
     client: &Client,
     session_id: &str,
     config: &Config,
-) -> Result<()> {
+) -> Result<String> {
+    let condition = json!({
+        "broadcaster_user_id": config.channel_user_id,
+        "user_id": config.bot_user_id
+    });
+    subscribe_to_event(
+        client,
+        session_id,
+        config,
+        "channel.chat.message",
+        "1",
+        condition,
+    )
+    .await
+}
+
+/// Subscribe to the events behind overlay alerts (follows, subs, gift subs,
+/// raids, cheers), and AutoMod holds for the mod log. Subs and cheers need the broadcaster's own token, so each
+/// subscription can fail on its own; the subscription id or failure is returned per event type.
+pub async fn subscribe_to_alert_events(
+    client: &Client,
+    session_id: &str,
+    config: &Config,
+) -> Vec<(&'static str, Result<String>)> {
+    let channel = &config.channel_user_id;
+    let subscriptions = [
+        (
+            "channel.follow",
+            "2",
+            json!({ "broadcaster_user_id": channel, "moderator_user_id": config.bot_user_id }),
+        ),
+        (
+            "channel.subscribe",
+            "1",
+            json!({ "broadcaster_user_id": channel }),
+        ),
+        (
+            "channel.subscription.gift",
+            "1",
+            json!({ "broadcaster_user_id": channel }),
+        ),
+        (
+            "channel.raid",
+            "1",
+            json!({ "to_broadcaster_user_id": channel }),
+        ),
+        (
+            "channel.cheer",
+            "1",
+            json!({ "broadcaster_user_id": channel }),
+        ),
+        (
+            "channel.channel_points_custom_reward_redemption.add",
+            "1",
+            json!({ "broadcaster_user_id": channel }),
+        ),
+        (
+            "automod.message.hold",
+            "1",
+            json!({ "broadcaster_user_id": channel, "moderator_user_id": config.bot_user_id }),
+        ),
+        (
+            "automod.message.update",
+            "1",
+            json!({ "broadcaster_user_id": channel, "moderator_user_id": config.bot_user_id }),
+        ),
+    ];
+
+    let mut results = Vec::new();
+    for (event_type, version, condition) in subscriptions {
+        let result =
+            subscribe_to_event(client, session_id, config, event_type, version, condition).await;
+        results.push((event_type, result));
+    }
+    results
+}
+
+async fn subscribe_to_event(
+    client: &Client,
+    session_id: &str,
+    config: &Config,
+    event_type: &str,
+    version: &str,
+    condition: serde_json::Value,
+) -> Result<String> {
     let token = config.oauth_token.as_ref().context("Token not set")?;
 
     let body = json!({
-        "type": "channel.chat.message",
-        "version": "1",
-        "condition": {
-            "broadcaster_user_id": config.channel_user_id,
-            "user_id": config.bot_user_id
-        },
+        "type": event_type,
+        "version": version,
+        "condition": condition,
         "transport": {
             "method": "websocket",
             "session_id": session_id
@@ -249,7 +426,7 @@ pub async fn subscribe_to_chat_messages(
     });
 
     let resp = client
-        .post("https://api.twitch.tv/helix/eventsub/subscriptions")
+        .post(format!("{}/eventsub/subscriptions", config.endpoints.helix))
         .header("Authorization", format!("Bearer {}", token))
         .header("Client-Id", &config.client_id)
         .json(&body)
@@ -263,25 +440,36 @@ pub async fn subscribe_to_chat_messages(
     }
 
     let json: serde_json::Value = resp.json().await?;
-    let _sub_id = json["data"][0]["id"]
+    let id = json["data"][0]["id"]
         .as_str()
         .context("No subscription id returned")?;
 
-    Ok(())
+    Ok(id.to_string())
 }
 
-#[derive(Debug, Deserialize)]
-struct EmoteData {
-    id: String,
-    name: String,
-    images: EmoteImages,
-}
+/// Unsubscribe, for a clean shutdown; Twitch would drop a websocket
+/// session's subscriptions some time after it closed anyway.
+pub async fn delete_eventsub_subscription(
+    client: &Client,
+    config: &Config,
+    id: &str,
+) -> Result<()> {
+    let token = config.oauth_token.as_ref().context("Token not set")?;
 
-#[derive(Debug, Deserialize)]
-struct EmoteImages {
-    url_1x: String,
-    url_2x: String,
-    url_4x: String,
+    let resp = client
+        .delete(format!("{}/eventsub/subscriptions", config.endpoints.helix))
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .query(&[("id", id)])
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Unsubscribing failed ({}): {}", status, text);
+    }
+    Ok(())
 }
 
 pub async fn get_global_emotes(
@@ -291,7 +479,7 @@ pub async fn get_global_emotes(
     let token = config.oauth_token.as_ref().context("Token not set")?;
 
     let resp = client
-        .get("https://api.twitch.tv/helix/chat/emotes/global")
+        .get(format!("{}/chat/emotes/global", config.endpoints.helix))
         .header("Authorization", format!("Bearer {}", token))
         .header("Client-Id", &config.client_id)
         .send()
@@ -315,6 +503,14 @@ pub async fn get_global_emotes(
     Ok(map)
 }
 
+// Any emote can be fetched from the CDN by id, no auth needed
+pub fn emote_cdn_url(id: &str) -> String {
+    format!(
+        "https://static-cdn.jtvnw.net/emoticons/v2/{}/default/dark/1.0",
+        id
+    )
+}
+
 pub async fn download_emote(client: &Client, url: &str) -> Result<Vec<u8>> {
     let resp = client.get(url).send().await?;
     if !resp.status().is_success() {
@@ -323,3 +519,701 @@ pub async fn download_emote(client: &Client, url: &str) -> Result<Vec<u8>> {
     let bytes = resp.bytes().await?;
     Ok(bytes.to_vec())
 }
+
+pub async fn ban_user(
+    client: &Client,
+    config: &Config,
+    user_id: &str,
+    duration: Option<u32>,
+    reason: &str,
+) -> Result<()> {
+    // Requires 'moderator:manage:banned_users'. A duration turns the ban into a timeout.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let mut data = json!({
+        "user_id": user_id,
+        "reason": reason
+    });
+    if let Some(duration) = duration {
+        data["duration"] = json!(duration);
+    }
+
+    let resp = client
+        .post(format!("{}/moderation/bans", config.endpoints.helix))
+        .query(&[
+            ("broadcaster_id", broadcaster_id.as_str()),
+            ("moderator_id", config.bot_user_id.as_str()),
+        ])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&json!({ "data": data }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to ban user ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+pub async fn delete_chat_message(client: &Client, config: &Config, message_id: &str) -> Result<()> {
+    // Requires 'moderator:manage:chat_messages'.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .delete(format!("{}/moderation/chat", config.endpoints.helix))
+        .query(&[
+            ("broadcaster_id", broadcaster_id.as_str()),
+            ("moderator_id", config.bot_user_id.as_str()),
+            ("message_id", message_id),
+        ])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to delete message ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+/// Allow or deny a message AutoMod is holding
+pub async fn manage_held_message(
+    client: &Client,
+    config: &Config,
+    message_id: &str,
+    allow: bool,
+) -> Result<()> {
+    // Requires 'moderator:manage:automod'.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+
+    let resp = client
+        .post(format!(
+            "{}/moderation/automod/message",
+            config.endpoints.helix
+        ))
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&json!({
+            "user_id": config.bot_user_id,
+            "msg_id": message_id,
+            "action": if allow { "ALLOW" } else { "DENY" },
+        }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to manage held message ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+/// Slow mode on with `wait` seconds between a viewer's messages, or off
+pub async fn set_slow_mode(client: &Client, config: &Config, wait: Option<u32>) -> Result<()> {
+    // Requires 'moderator:manage:chat_settings'.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let mut data = json!({ "slow_mode": wait.is_some() });
+    if let Some(wait) = wait {
+        data["slow_mode_wait_time"] = json!(wait);
+    }
+    let resp = client
+        .patch(format!("{}/chat/settings", config.endpoints.helix))
+        .query(&[
+            ("broadcaster_id", broadcaster_id.as_str()),
+            ("moderator_id", config.bot_user_id.as_str()),
+        ])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&data)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to change slow mode ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+/// Followers-only chat on for those who followed at least `min_follow` ago,
+/// or off
+pub async fn set_followers_only(
+    client: &Client,
+    config: &Config,
+    min_follow: Option<std::time::Duration>,
+) -> Result<()> {
+    // Requires 'moderator:manage:chat_settings'.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let mut data = json!({ "follower_mode": min_follow.is_some() });
+    if let Some(min_follow) = min_follow {
+        data["follower_mode_duration"] = json!(min_follow.as_secs() / 60);
+    }
+    let resp = client
+        .patch(format!("{}/chat/settings", config.endpoints.helix))
+        .query(&[
+            ("broadcaster_id", broadcaster_id.as_str()),
+            ("moderator_id", config.bot_user_id.as_str()),
+        ])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&data)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!(
+            "Failed to change followers-only mode ({}): {}",
+            status,
+            text
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn set_shield_mode(client: &Client, config: &Config, active: bool) -> Result<()> {
+    // Requires 'moderator:manage:shield_mode'.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .put(format!("{}/moderation/shield_mode", config.endpoints.helix))
+        .query(&[
+            ("broadcaster_id", broadcaster_id.as_str()),
+            ("moderator_id", config.bot_user_id.as_str()),
+        ])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&json!({ "is_active": active }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to change Shield Mode ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+/// When each of `logins` made their account, for those Helix knows
+pub async fn get_account_ages(
+    client: &Client,
+    config: &Config,
+    logins: &[String],
+) -> Result<Vec<(String, jiff::Timestamp)>> {
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+
+    let mut ages = Vec::new();
+    // Get Users takes up to 100 at once
+    for chunk in logins.chunks(100) {
+        let query: Vec<(&str, &str)> = chunk
+            .iter()
+            .map(|login| ("login", login.as_str()))
+            .collect();
+        let resp = client
+            .get(format!("{}/users", config.endpoints.helix))
+            .query(&query)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Client-Id", &config.client_id)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            let text = resp.text().await?;
+            bail!("Failed to get users: {}", text);
+        }
+
+        let json: serde_json::Value = resp.json().await?;
+        for user in json["data"].as_array().into_iter().flatten() {
+            let (Some(login), Some(created)) = (
+                user["login"].as_str(),
+                user["created_at"]
+                    .as_str()
+                    .and_then(|created| created.parse().ok()),
+            ) else {
+                continue;
+            };
+            ages.push((login.to_string(), created));
+        }
+    }
+
+    Ok(ages)
+}
+
+pub async fn update_channel_title(client: &Client, config: &Config, title: &str) -> Result<()> {
+    // Requires 'channel:manage:broadcast' on the broadcaster's own token.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .patch(format!("{}/channels", config.endpoints.helix))
+        .query(&[("broadcaster_id", broadcaster_id.as_str())])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&json!({ "title": title }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to update title ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+pub async fn create_clip(client: &Client, config: &Config) -> Result<String> {
+    // Requires 'clips:edit'. Returns the edit URL of the new clip.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .post(format!("{}/clips", config.endpoints.helix))
+        .query(&[("broadcaster_id", broadcaster_id.as_str())])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to create clip ({}): {}", status, text);
+    }
+
+    let json: serde_json::Value = resp.json().await?;
+    let edit_url = json["data"][0]["edit_url"]
+        .as_str()
+        .context("No clip returned")?
+        .to_string();
+
+    Ok(edit_url)
+}
+
+pub async fn send_announcement(
+    client: &Client,
+    config: &Config,
+    message: &str,
+    color: &str,
+) -> Result<()> {
+    // Requires 'moderator:manage:announcements'.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .post(format!("{}/chat/announcements", config.endpoints.helix))
+        .query(&[
+            ("broadcaster_id", broadcaster_id.as_str()),
+            ("moderator_id", config.bot_user_id.as_str()),
+        ])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&json!({ "message": message, "color": color }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to send announcement ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+pub async fn create_poll(
+    client: &Client,
+    config: &Config,
+    question: &str,
+    options: &[String],
+    duration: std::time::Duration,
+) -> Result<String> {
+    // Requires 'channel:manage:polls', and the channel to be an affiliate or
+    // partner. Returns the poll's id.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+    let choices: Vec<_> = options
+        .iter()
+        .map(|title| json!({ "title": title }))
+        .collect();
+
+    let resp = client
+        .post(format!("{}/polls", config.endpoints.helix))
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&json!({
+            "broadcaster_id": broadcaster_id,
+            "title": question,
+            "choices": choices,
+            "duration": duration.as_secs(),
+        }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to create poll ({}): {}", status, text);
+    }
+
+    let json: serde_json::Value = resp.json().await?;
+    Ok(json["data"][0]["id"]
+        .as_str()
+        .context("No poll returned")?
+        .to_string())
+}
+
+// What a channel is streaming, or last streamed
+#[derive(Debug, Clone)]
+pub struct ChannelInfo {
+    pub title: String,
+    // Category, e.g. "Just Chatting"
+    pub game_name: String,
+}
+
+pub async fn get_channel_info(
+    client: &Client,
+    config: &Config,
+    broadcaster_id: &str,
+) -> Result<ChannelInfo> {
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+
+    let resp = client
+        .get(format!("{}/channels", config.endpoints.helix))
+        .query(&[("broadcaster_id", broadcaster_id)])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to fetch channel ({}): {}", status, text);
+    }
+
+    let json: serde_json::Value = resp.json().await?;
+    let channel = &json["data"][0];
+    if channel.is_null() {
+        bail!("Channel {} not found", broadcaster_id);
+    }
+    Ok(ChannelInfo {
+        title: channel["title"].as_str().unwrap_or_default().to_string(),
+        game_name: channel["game_name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+pub async fn send_shoutout(
+    client: &Client,
+    config: &Config,
+    to_broadcaster_id: &str,
+) -> Result<()> {
+    // Requires 'moderator:manage:shoutouts', and the stream to be live
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .post(format!("{}/chat/shoutouts", config.endpoints.helix))
+        .query(&[
+            ("from_broadcaster_id", broadcaster_id.as_str()),
+            ("to_broadcaster_id", to_broadcaster_id),
+            ("moderator_id", config.bot_user_id.as_str()),
+        ])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to send shoutout ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+// A native poll's votes per choice, in order, and whether it's still running
+#[derive(Debug, Clone)]
+pub struct PollProgress {
+    pub votes: Vec<u64>,
+    pub active: bool,
+}
+
+pub async fn get_poll(client: &Client, config: &Config, id: &str) -> Result<PollProgress> {
+    // Requires 'channel:manage:polls' (or channel:read:polls)
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .get(format!("{}/polls", config.endpoints.helix))
+        .query(&[("broadcaster_id", broadcaster_id.as_str()), ("id", id)])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to fetch poll ({}): {}", status, text);
+    }
+
+    let json: serde_json::Value = resp.json().await?;
+    let poll = &json["data"][0];
+    if poll.is_null() {
+        bail!("Poll {} not found", id);
+    }
+    Ok(PollProgress {
+        votes: poll["choices"]
+            .as_array()
+            .map(|choices| {
+                choices
+                    .iter()
+                    .map(|choice| choice["votes"].as_u64().unwrap_or(0))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        active: poll["status"].as_str() == Some("ACTIVE"),
+    })
+}
+
+pub async fn end_poll(client: &Client, config: &Config, id: &str) -> Result<()> {
+    // Requires 'channel:manage:polls'. TERMINATED ends it and shows the result.
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .patch(format!("{}/polls", config.endpoints.helix))
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .json(&json!({ "broadcaster_id": broadcaster_id, "id": id, "status": "TERMINATED" }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to end poll ({}): {}", status, text);
+    }
+
+    Ok(())
+}
+
+// Walks a paginated Helix endpoint whose entries carry a `user_login` field.
+async fn get_paginated_logins(
+    client: &Client,
+    config: &Config,
+    url: &str,
+    query: &[(&str, &str)],
+) -> Result<Vec<String>> {
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+
+    let mut logins = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let mut request = client
+            .get(url)
+            .query(query)
+            .query(&[("first", "100")])
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Client-Id", &config.client_id);
+        if let Some(after) = &cursor {
+            request = request.query(&[("after", after.as_str())]);
+        }
+
+        let resp = request.send().await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await?;
+            bail!("Failed to fetch {} ({}): {}", url, status, text);
+        }
+
+        let json: serde_json::Value = resp.json().await?;
+        if let Some(data) = json["data"].as_array() {
+            logins.extend(
+                data.iter()
+                    .filter_map(|item| item["user_login"].as_str())
+                    .map(|login| login.to_string()),
+            );
+        }
+
+        match json["pagination"]["cursor"].as_str() {
+            Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+            _ => break,
+        }
+    }
+
+    Ok(logins)
+}
+
+pub async fn get_chatters(client: &Client, config: &Config) -> Result<Vec<String>> {
+    // Requires 'moderator:read:chatters'
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+    get_paginated_logins(
+        client,
+        config,
+        &format!("{}/chat/chatters", config.endpoints.helix),
+        &[
+            ("broadcaster_id", broadcaster_id.as_str()),
+            ("moderator_id", config.bot_user_id.as_str()),
+        ],
+    )
+    .await
+}
+
+pub async fn get_moderators(client: &Client, config: &Config) -> Result<Vec<String>> {
+    // Requires 'moderation:read' on the broadcaster's token
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+    get_paginated_logins(
+        client,
+        config,
+        &format!("{}/moderation/moderators", config.endpoints.helix),
+        &[("broadcaster_id", broadcaster_id.as_str())],
+    )
+    .await
+}
+
+pub async fn get_vips(client: &Client, config: &Config) -> Result<Vec<String>> {
+    // Requires 'channel:read:vips' on the broadcaster's token
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+    get_paginated_logins(
+        client,
+        config,
+        &format!("{}/channels/vips", config.endpoints.helix),
+        &[("broadcaster_id", broadcaster_id.as_str())],
+    )
+    .await
+}
+
+// Live stream numbers for the status bar and overlay
+#[derive(Debug, Clone)]
+pub struct StreamStats {
+    pub viewer_count: u64,
+    pub started_at: jiff::Timestamp,
+    // Category, e.g. "Just Chatting"
+    pub game_name: String,
+}
+
+impl StreamStats {
+    // "1:05" (hours:minutes) since the stream started
+    pub fn uptime(&self) -> String {
+        let secs = jiff::Timestamp::now()
+            .duration_since(self.started_at)
+            .as_secs()
+            .max(0);
+        format!("{}:{:02}", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+/// `Ok(None)` when the channel is offline.
+pub async fn get_stream_stats(client: &Client, config: &Config) -> Result<Option<StreamStats>> {
+    let token = config.oauth_token.as_ref().context("Token not set")?;
+    let broadcaster_id = config
+        .channel_user_id
+        .as_ref()
+        .context("Channel ID not set")?;
+
+    let resp = client
+        .get(format!("{}/streams", config.endpoints.helix))
+        .query(&[("user_id", broadcaster_id.as_str())])
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Client-Id", &config.client_id)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await?;
+        bail!("Failed to fetch stream ({}): {}", status, text);
+    }
+
+    let json: serde_json::Value = resp.json().await?;
+    let stream = &json["data"][0];
+    if stream.is_null() {
+        return Ok(None);
+    }
+
+    let started_at = stream["started_at"]
+        .as_str()
+        .context("Stream has no started_at")?
+        .parse()
+        .context("Invalid started_at timestamp")?;
+    Ok(Some(StreamStats {
+        viewer_count: stream["viewer_count"].as_u64().unwrap_or(0),
+        started_at,
+        game_name: stream["game_name"].as_str().unwrap_or_default().to_string(),
+    }))
+}
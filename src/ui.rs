@@ -1,105 +1,476 @@
-use crate::state::App;
+use crate::config::LlmProvider;
+use crate::encoder::EmoteSize;
+use crate::hints::{self, Hint};
+use crate::state::{AiStatus, App, ChatLine, ConnectionState, Role, Severity, Tab};
+use crate::theme::Theme;
 use ratatui::{
     layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs},
     Frame,
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub fn ui(f: &mut Frame, app: &mut App) {
+    let theme = app.config.theme.clone();
+    let border_style = Style::default().fg(theme.border);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
+            Constraint::Length(1), // Tab bar
             Constraint::Min(1),
-            Constraint::Length(12), // Taller as requested
-            Constraint::Length(3),  // Input
+            Constraint::Length(app.emote_panel.rendered_height()),
+            Constraint::Length(3), // Input
+            Constraint::Length(1), // Status bar
         ])
         .split(f.size());
 
-    // Store layout for click detection
-    app.emote_area = chunks[1];
+    // Optional notification pane under the main view
+    let (main_area, notifications_area) = if app.show_notifications {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(6)])
+            .split(chunks[1]);
+        (rows[0], Some(rows[1]))
+    } else {
+        (chunks[1], None)
+    };
 
-    // We need to scroll to the bottom.
-    // Ideally use stateful widget, but for now let's just create the list with the right items?
-    // Actually, List doesn't auto-scroll. We need to slice the messages or use ListState.
-    // Let's rely on re-slicing for simplicity as per plan.
-    let chat_area_height = chunks[0].height.saturating_sub(2) as usize; // Subtract 2 for borders
+    // Optional user list on the right of the main view
+    let (view_area, users_area) = if app.show_users {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(1), Constraint::Length(26)])
+            .split(main_area);
+        (columns[0], Some(columns[1]))
+    } else {
+        (main_area, None)
+    };
 
-    let start_index = app.messages.len().saturating_sub(chat_area_height);
+    // The AI tab lists requests above its activity log, the Songs tab the queue
+    let listed = match app.tab {
+        Tab::Ai => Some(app.ai_requests.len()),
+        Tab::Songs => Some(app.songs.queue.len() + 1),
+        Tab::Moderation if !app.held.is_empty() => Some(app.held.len()),
+        _ => None,
+    };
+    let (view_area, requests_area) = if let Some(listed) = listed {
+        let height = (listed as u16 + 2).clamp(3, view_area.height / 2);
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(height), Constraint::Min(1)])
+            .split(view_area);
+        (rows[1], Some(rows[0]))
+    } else {
+        (view_area, None)
+    };
+    if let Some(area) = requests_area {
+        match app.tab {
+            Tab::Songs => render_song_queue(f, app, &theme, area),
+            Tab::Moderation => render_held_messages(f, app, &theme, area),
+            _ => render_ai_requests(f, app, &theme, area),
+        }
+    }
 
-    let messages: Vec<ListItem> = app
-        .messages
-        .iter()
-        .skip(start_index)
-        .map(|m| ListItem::new(Line::from(vec![Span::raw(m)])))
-        .collect();
+    // Store layout for click detection and scroll math
+    app.tab_area = view_area;
+    app.emote_area = chunks[2];
 
-    let messages_list =
-        List::new(messages).block(Block::default().borders(Borders::ALL).title("Chat"));
-    f.render_widget(messages_list, chunks[0]);
+    let titles: Vec<Line> = Tab::ALL.iter().map(|tab| Line::from(tab.title())).collect();
+    let tabs = Tabs::new(titles)
+        .select(app.tab.index())
+        .style(Style::default().fg(theme.dim))
+        .highlight_style(
+            Style::default()
+                .fg(theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .divider("|");
+    f.render_widget(tabs, chunks[0]);
+    render_status_bar(f, app, &theme, chunks[4]);
 
-    // Emoji Bar
-    // We render images if available, otherwise fallback to text?
-    // Mixed rendering is hard.
-    // If we have images, we should render them in a grid.
+    // The main view is a stateful List with one (wrapped) item per line. The tab's
+    // scroll position counts rows from the bottom, so items are built bottom-up
+    // until the rows above it are covered, then the ListState offset is derived
+    // from it. An active search either highlights matches or (in filter mode)
+    // hides the rest.
+    let matches = app.search_matches();
+    let current_match = app.search.as_ref().and_then(|s| s.current);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border_style);
+    let inner = block.inner(view_area);
+    let view_height = inner.height as usize;
+    let view_width = inner.width as usize;
+    let wanted_scroll = app.tab_scroll[app.tab.index()];
 
-    // Check if we have loaded ANY images
-    if !app.emote_images.is_empty() {
-        // Render Sixel Images
-        let outer_block = Block::default().borders(Borders::ALL).title(format!(
-            "Emotes (Click) [{}] ({})",
-            app.emote_images.len(),
-            app.protocol_name
-        ));
+    let visible = app.visible_lines();
+    let hidden = if app.tab == Tab::Chat {
+        app.messages.len() - visible.len()
+    } else {
+        0
+    };
 
-        let inner_area = outer_block.inner(chunks[1]);
-        f.render_widget(outer_block, chunks[1]);
+    // The unread marker sits above the first visible line at or after it
+    let marker = app.unread_marker.filter(|_| app.tab == Tab::Chat);
+    let divider = || {
+        let label = " new messages ";
+        let side = "─".repeat(view_width.saturating_sub(label.len()) / 2);
+        vec![Line::from(Span::styled(
+            format!("{}{}{}", side, label, side),
+            Style::default().fg(theme.alert),
+        ))]
+    };
 
-        // Grid Logic
-        // Natural size: 3x2 (approx 28x28px)
-        let img_width: u16 = 3;
-        let img_height: u16 = 2;
+    // Entries are paired with their line index; the marker divider has none
+    let mut entries: Vec<(Option<usize>, Vec<Line>)> = Vec::new();
+    let mut rows = 0;
+    let mut newer: Option<usize> = None;
+    for (pos, (i, line)) in visible.iter().enumerate().rev() {
+        if marker.is_some_and(|m| *i < m && newer.is_some_and(|n| n >= m)) {
+            entries.push((None, divider()));
+            rows += 1;
+        }
+        // A divider between two messages starts a new group
+        let grouped = app.config.group_messages
+            && pos > 0
+            && same_sender(visible[pos - 1].1, line)
+            && !marker.is_some_and(|m| visible[pos - 1].0 < m && *i >= m);
+        let wrapped = wrap_spans(&chat_line(line, grouped, &theme).spans, view_width);
+        rows += wrapped.len();
+        entries.push((Some(*i), wrapped));
+        newer = Some(*i);
+        if rows >= wanted_scroll + view_height {
+            break;
+        }
+    }
+    if marker.is_some_and(|m| {
+        visible
+            .first()
+            .is_some_and(|(i, _)| Some(*i) == newer && *i >= m)
+    }) {
+        entries.push((None, divider()));
+    }
+    entries.reverse();
 
-        let inner_width = inner_area.width;
-        let inner_height = inner_area.height;
-        let items_per_row = (inner_width / (img_width + 1)).max(1);
+    // Skip whole entries from the bottom to reach the scroll position, then fill
+    // the view upwards; at the top of the history the List fills downwards instead.
+    let heights: Vec<usize> = entries.iter().map(|(_, lines)| lines.len()).collect();
+    let mut end = heights.len();
+    let mut skipped = 0;
+    while end > 0 && skipped + heights[end - 1] <= wanted_scroll {
+        skipped += heights[end - 1];
+        end -= 1;
+    }
+    let mut offset = end;
+    let mut used = 0;
+    while offset > 0 && (used == 0 || used + heights[offset - 1] <= view_height) {
+        used += heights[offset - 1];
+        offset -= 1;
+    }
+    // Show the tail of the next entry up in any leftover rows, so the newest
+    // line stays on the bottom row instead of leaving a gap below it
+    let leftover = view_height.saturating_sub(used);
+    if leftover > 0 && offset > 0 {
+        offset -= 1;
+        let lines = &mut entries[offset].1;
+        lines.drain(..lines.len() - leftover);
+    }
+    let heights: Vec<usize> = entries.iter().map(|(_, lines)| lines.len()).collect();
 
-        let total_items = app.emote_images.len();
-        let total_rows = (total_items + items_per_row as usize - 1) / items_per_row as usize;
+    let selection = app.active_selection();
+    let mut state = ListState::default().with_offset(offset);
+    if let Some(sel) = selection {
+        state.select(entries.iter().position(|(i, _)| *i == Some(sel.cursor)));
+    }
 
-        // Render images based on scroll
-        let start_row = app.emote_scroll;
-        let end_row = start_row + (inner_height / img_height) as usize + 1;
+    let items: Vec<ListItem> = entries
+        .iter()
+        .map(|(i, lines)| {
+            let item = ListItem::new(lines.clone());
+            let Some(i) = *i else {
+                return item;
+            };
+            if current_match == Some(i) {
+                item.style(Style::default().bg(theme.highlight).fg(Color::Black))
+            } else if selection.is_some_and(|sel| sel.contains(i)) {
+                item.style(Style::default().bg(theme.selection))
+            } else if matches.binary_search(&i).is_ok() {
+                item.style(Style::default().bg(theme.dim))
+            } else if app.tab_lines(app.tab)[i].mention {
+                item.style(Style::default().bg(theme.mention))
+            } else {
+                item
+            }
+        })
+        .collect();
 
-        let start_index = start_row * items_per_row as usize;
+    let scroll = rows_below(&heights, offset, view_height);
+    let mut title = app.tab.title().to_string();
+    if scroll > 0 {
+        title.push_str(&format!(" (scrolled, {} newer)", scroll));
+    }
+    if hidden > 0 {
+        title.push_str(&format!(" ({} filtered, F7 to show)", hidden));
+    } else if !app.filters.enabled && app.tab == Tab::Chat {
+        title.push_str(" (filters off)");
+    }
+    let list = List::new(items)
+        .style(Style::default().fg(theme.text))
+        .highlight_style(Style::default().bg(theme.selection))
+        .block(block.title(title));
+    f.render_stateful_widget(list, view_area, &mut state);
 
-        let mut x_offset = inner_area.x;
-        let mut y_offset = inner_area.y;
+    // The List may have moved the offset to keep the selection in view; read back
+    // where it ended up and record the rows drawn, for clicks and inline emotes.
+    let offset = state.offset();
+    app.tab_scroll[app.tab.index()] = rows_below(&heights, offset, view_height);
 
-        for (i, (_name, _dyn_img, protocol)) in
-            app.emote_images.iter().enumerate().skip(start_index)
-        {
-            let row_idx = i / items_per_row as usize;
-            if row_idx >= end_row {
+    let show_inline = app.inline_images_enabled();
+    let mut inline: Vec<(u16, u16, u16, String)> = Vec::new(); // (x, y, text width, name)
+    let mut view_rows = Vec::new();
+    for (i, lines) in &entries[offset..] {
+        for row in lines {
+            if view_rows.len() >= view_height {
                 break;
             }
+            if show_inline {
+                let mut x = 0;
+                for span in &row.spans {
+                    let word = span.content.trim_end();
+                    if span.style == emote_style(&theme) && app.inline_emotes.contains_key(word) {
+                        inline.push((
+                            inner.x + x as u16,
+                            inner.y + view_rows.len() as u16,
+                            word.width() as u16,
+                            word.to_string(),
+                        ));
+                    }
+                    x += span.content.width();
+                }
+            }
+            view_rows.push(*i);
+        }
+    }
+    app.view_rows = view_rows;
 
-            // Calculate position relative to inner_area
-            let visible_row = row_idx - start_row;
+    // Draw emote images over their names; the name stays as the fallback
+    // for terminals without graphics support.
+    for (x, y, width, name) in inline {
+        let Some(protocol) = app.emote_protocol(EmoteSize::Inline, &name) else {
+            continue;
+        };
+        let area = ratatui::layout::Rect::new(x, y, width.min(inner.right().saturating_sub(x)), 1);
+        if area.width < 2 {
+            continue;
+        }
+        f.render_widget(ratatui::widgets::Clear, area);
+        f.render_widget(
+            ratatui_image::Image::new(protocol),
+            ratatui::layout::Rect::new(x, y, 2, 1),
+        );
+    }
 
-            let col_idx = i % items_per_row as usize;
+    if let Some(area) = notifications_area {
+        render_notifications(f, app, &theme, area);
+    }
 
-            x_offset = inner_area.x + (col_idx as u16 * (img_width + 1));
-            y_offset = inner_area.y + (visible_row as u16 * img_height);
+    app.users_area = users_area.unwrap_or_default();
+    if let Some(area) = users_area {
+        render_user_list(f, app, &theme, area);
+    }
 
-            if y_offset + img_height > inner_area.bottom() {
-                break;
+    if app.emote_panel.collapsed {
+        app.emote_area = ratatui::layout::Rect::default();
+    } else {
+        render_emote_panel(f, app, &theme, chunks[2]);
+    }
+
+    // The input row doubles as the search bar while a search is open
+    if let Some(search) = &app.search {
+        let title = match search.matcher() {
+            Err(e) => format!("Search: invalid regex ({})", e),
+            Ok(_) => format!("{} - {} matches", search.title(), matches.len()),
+        };
+        let bar = Paragraph::new(search.query.value())
+            .style(Style::default().fg(theme.highlight))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border_style)
+                    .title(title),
+            );
+        f.render_widget(bar, chunks[3]);
+
+        if search.editing {
+            f.set_cursor(
+                chunks[3].x + search.query.visual_cursor() as u16 + 1,
+                chunks[3].y + 1,
+            );
+        }
+        return;
+    }
+
+    // Emote words are highlighted, near misses dimmed with a suggestion in the title
+    let known = app.known_emotes();
+    let mut suggestions = Vec::new();
+    let spans: Vec<Span> = app
+        .input
+        .value()
+        .split_inclusive(' ')
+        .map(|word| match hints::classify(word.trim_end(), &known) {
+            Some(Hint::Emote) => Span::styled(word, emote_style(&theme)),
+            Some(Hint::NearMiss(name)) => {
+                suggestions.push(format!("{} -> {}", word.trim_end(), name));
+                Span::styled(
+                    word,
+                    Style::default()
+                        .fg(theme.dim)
+                        .add_modifier(Modifier::UNDERLINED),
+                )
             }
+            None => Span::raw(word),
+        })
+        .collect();
+    let title = if suggestions.is_empty() {
+        "Input".to_string()
+    } else {
+        format!(
+            "Input (did you mean {}? Tab fixes the last word)",
+            suggestions.join(", ")
+        )
+    };
+
+    let input = Paragraph::new(Line::from(spans))
+        .style(Style::default().fg(theme.input))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(border_style)
+                .title(title),
+        );
+    f.render_widget(input, chunks[3]);
+
+    // Cursor
+    f.set_cursor(
+        chunks[3].x + app.input.visual_cursor() as u16 + 1,
+        chunks[3].y + 1,
+    );
+}
+
+/// Where the emote picker's images go: a grid of cells inside the panel's
+/// border, scrolled a row at a time. Drawing and mouse clicks both use it.
+#[derive(Debug, Clone, Copy)]
+pub struct EmoteGrid {
+    inner: ratatui::layout::Rect,
+    count: usize,
+}
 
-            let area = ratatui::layout::Rect::new(x_offset, y_offset, img_width, img_height);
+impl EmoteGrid {
+    // Natural size: 3x2 (approx 28x28px), with a column between images
+    const CELL_WIDTH: u16 = 3;
+    const CELL_HEIGHT: u16 = 2;
 
-            let image_widget = ratatui_image::Image::new(protocol.as_ref());
+    /// `area` is the panel with its border, holding `count` emotes
+    pub fn new(area: ratatui::layout::Rect, count: usize) -> Self {
+        let inner = Block::default().borders(Borders::ALL).inner(area);
+        Self { inner, count }
+    }
+
+    pub fn items_per_row(&self) -> usize {
+        (self.inner.width / (Self::CELL_WIDTH + 1)).max(1) as usize
+    }
+
+    pub fn total_rows(&self) -> usize {
+        self.count.div_ceil(self.items_per_row())
+    }
+
+    pub fn visible_rows(&self) -> usize {
+        (self.inner.height / Self::CELL_HEIGHT) as usize
+    }
+
+    /// How far down the panel scrolls before the last row shows
+    pub fn max_scroll(&self) -> usize {
+        self.total_rows().saturating_sub(self.visible_rows())
+    }
+
+    /// Where emote `index` is drawn when scrolled down `scroll` rows, if it
+    /// fits in the panel
+    pub fn cell(&self, index: usize, scroll: usize) -> Option<ratatui::layout::Rect> {
+        let per_row = self.items_per_row();
+        let row = (index / per_row).checked_sub(scroll)?;
+        if index >= self.count || row >= self.visible_rows() {
+            return None;
+        }
+        let x = self.inner.x + (index % per_row) as u16 * (Self::CELL_WIDTH + 1);
+        let y = self.inner.y + row as u16 * Self::CELL_HEIGHT;
+        Some(ratatui::layout::Rect::new(
+            x,
+            y,
+            Self::CELL_WIDTH,
+            Self::CELL_HEIGHT,
+        ))
+    }
+
+    /// The emote under the terminal cell at (`column`, `row`)
+    pub fn index_at(&self, column: u16, row: u16, scroll: usize) -> Option<usize> {
+        let (x, y) = (
+            column.checked_sub(self.inner.x)?,
+            row.checked_sub(self.inner.y)?,
+        );
+        if x >= self.inner.width || y >= self.inner.height {
+            return None;
+        }
+        let col = (x / (Self::CELL_WIDTH + 1)) as usize;
+        let index = (y / Self::CELL_HEIGHT) as usize + scroll;
+        let index = index * self.items_per_row() + col;
+        (col < self.items_per_row() && index < self.count).then_some(index)
+    }
+}
+
+// " loading 12/40" while the picker's emotes are still coming in
+fn emote_progress(app: &App) -> String {
+    match app.emote_progress {
+        Some((loaded, total)) => format!(" loading {}/{}", loaded, total),
+        None => String::new(),
+    }
+}
+
+fn render_emote_panel(f: &mut Frame, app: &App, theme: &Theme, area: ratatui::layout::Rect) {
+    let border_style = Style::default().fg(theme.border);
+
+    // Emoji Bar
+    // We render images if available, otherwise fallback to text?
+    // Mixed rendering is hard.
+    // If we have images, we should render them in a grid.
+
+    // Check if we have loaded ANY images
+    if !app.emote_images.is_empty() {
+        // Render Sixel Images
+        let outer_block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(border_style)
+            .title(format!(
+                "Emotes (Click) [{}] ({}){}",
+                app.emote_images.len(),
+                app.protocol_name,
+                emote_progress(app)
+            ));
+
+        f.render_widget(outer_block, area);
+
+        let grid = EmoteGrid::new(area, app.emote_images.len());
+        let start_index = app.emote_scroll * grid.items_per_row();
+        for (i, (name, _)) in app.emote_images.iter().enumerate().skip(start_index) {
+            let Some(area) = grid.cell(i, app.emote_scroll) else {
+                break;
+            };
+            // Left empty until the encoder gets to it
+            let Some(protocol) = app.emote_protocol(EmoteSize::Picker, name) else {
+                continue;
+            };
+            let image_widget = ratatui_image::Image::new(protocol);
             f.render_widget(image_widget, area);
         }
 
@@ -108,7 +479,7 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             .orientation(ratatui::widgets::ScrollbarOrientation::VerticalRight)
             .begin_symbol(Some("▲"))
             .end_symbol(Some("▼"));
-        let height_in_rows = inner_height as usize / img_height as usize;
+        let height_in_rows = grid.visible_rows();
         // let max_scroll = total_rows.saturating_sub(height_in_rows);
 
         // User requested inverted visual? "Switch this".
@@ -119,12 +490,12 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         // This usually implies logical error.
         // Let's try to ensure we use the robust Total/Viewport API.
 
-        let mut scrollbar_state = ratatui::widgets::ScrollbarState::new(total_rows)
+        let mut scrollbar_state = ratatui::widgets::ScrollbarState::new(grid.total_rows())
             .viewport_content_length(height_in_rows)
             .position(app.emote_scroll);
         f.render_stateful_widget(
             scrollbar,
-            chunks[1], // Render over the block
+            area, // Render over the block
             &mut scrollbar_state,
         );
     } else {
@@ -134,21 +505,498 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title("Emotes (Loading...)"),
+                    .border_style(border_style)
+                    .title(match app.emote_progress {
+                        Some((loaded, total)) => {
+                            format!("Emotes (Loading {}/{}...)", loaded, total)
+                        }
+                        None => "Emotes (Loading...)".to_string(),
+                    }),
             )
-            .style(Style::default().fg(Color::Cyan))
+            .style(Style::default().fg(theme.emote))
             .wrap(ratatui::widgets::Wrap { trim: true });
-        f.render_widget(emojis, chunks[1]);
+        f.render_widget(emojis, area);
     }
+}
 
-    let input = Paragraph::new(app.input.value())
-        .style(Style::default().fg(Color::Yellow))
-        .block(Block::default().borders(Borders::ALL).title("Input"));
-    f.render_widget(input, chunks[2]);
+fn render_status_bar(f: &mut Frame, app: &App, theme: &Theme, area: ratatui::layout::Rect) {
+    let connection = |name: &'static str, state: ConnectionState| {
+        let color = match state {
+            ConnectionState::Connected => Color::Green,
+            ConnectionState::Connecting => theme.highlight,
+            ConnectionState::Disconnected => theme.alert,
+        };
+        vec![
+            Span::styled("● ", Style::default().fg(color)),
+            Span::raw(name),
+        ]
+    };
+    let separator = || Span::styled(" | ", Style::default().fg(theme.dim));
 
-    // Cursor
-    f.set_cursor(
-        chunks[2].x + app.input.visual_cursor() as u16 + 1,
-        chunks[2].y + 1,
+    let (provider, model) = match app.config.llm_provider {
+        LlmProvider::Gemini => ("Gemini", &app.config.gemini_model),
+        LlmProvider::Ollama => ("Ollama", &app.config.ollama_model),
+    };
+    let stream = match &app.stream {
+        Some(stats) if stats.game_name.is_empty() => {
+            format!("Live {} viewers, up {}", stats.viewer_count, stats.uptime())
+        }
+        Some(stats) => format!(
+            "Live {} viewers, up {}, {}",
+            stats.viewer_count,
+            stats.uptime(),
+            stats.game_name
+        ),
+        None => "Offline".to_string(),
+    };
+
+    let mut spans = vec![Span::raw(" ")];
+    spans.extend(connection("EventSub", app.eventsub));
+    spans.push(Span::raw("  "));
+    spans.extend(connection("IRC", app.irc));
+    spans.push(separator());
+    spans.push(Span::raw(format!("{} {}", provider, model)));
+    spans.push(separator());
+    spans.push(Span::raw(if app.tts_enabled {
+        "TTS on"
+    } else {
+        "TTS muted (F8)"
+    }));
+    spans.push(separator());
+    spans.push(Span::raw(stream));
+    if let Some(goal) = &app.goal {
+        // Ten-cell bar, e.g. "Follower goal ▰▰▰▱▱▱▱▱▱▱ 31/100"
+        let filled = (goal.fraction() * 10.0).round() as usize;
+        let color = if goal.is_complete() {
+            Color::Green
+        } else {
+            theme.highlight
+        };
+        spans.push(separator());
+        spans.push(Span::raw(format!("{} ", goal.label)));
+        spans.push(Span::styled(
+            format!("{}{}", "▰".repeat(filled), "▱".repeat(10 - filled)),
+            Style::default().fg(color),
+        ));
+        spans.push(Span::raw(format!(" {}/{}", goal.current, goal.target)));
+    }
+    if let Some(poll) = app.poll.as_ref().filter(|poll| !poll.ended) {
+        // "Poll 42s: pizza 5 (62%), tacos 3 (38%)"
+        spans.push(separator());
+        spans.push(Span::styled(
+            format!("Poll {}s: {}", poll.time_left().as_secs(), poll.standings()),
+            Style::default().fg(theme.highlight),
+        ));
+    }
+    if let Some(secs) = app.ai_thinking_for() {
+        // Dots cycle once per second with the AI ticker's redraws
+        let dots = ".".repeat(secs as usize % 3 + 1);
+        spans.push(separator());
+        spans.push(Span::styled(
+            format!("{} is thinking{:<3}", app.bot_login, dots),
+            Style::default().fg(theme.highlight),
+        ));
+    }
+    if app.shield.is_raised() {
+        spans.push(separator());
+        spans.push(Span::styled(
+            "SHIELD UP (!shield off)",
+            Style::default()
+                .fg(theme.alert)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if app.chat_speed.is_fast() {
+        spans.push(separator());
+        spans.push(Span::styled("Chat fast", Style::default().fg(theme.alert)));
+    }
+    if app.low_power() {
+        spans.push(separator());
+        spans.push(Span::styled("Low power", Style::default().fg(theme.dim)));
+    }
+    if app.unread_errors > 0 {
+        spans.push(separator());
+        spans.push(Span::styled(
+            format!("{} unread errors (F9)", app.unread_errors),
+            Style::default()
+                .fg(theme.alert)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+
+    f.render_widget(
+        Paragraph::new(Line::from(spans)).style(Style::default().fg(theme.text)),
+        area,
     );
 }
+
+fn render_ai_requests(f: &mut Frame, app: &App, theme: &Theme, area: ratatui::layout::Rect) {
+    let items: Vec<ListItem> = app
+        .ai_requests
+        .iter()
+        .map(|request| {
+            let (label, color, outcome) = match &request.status {
+                AiStatus::Pending => ("pending ", theme.highlight, None),
+                AiStatus::AwaitingApproval(reply) => ("approve?", theme.selection, Some(reply)),
+                AiStatus::Sent(reply) => ("sent    ", Color::Green, Some(reply)),
+                AiStatus::Rejected(reply) => ("rejected", theme.dim, Some(reply)),
+                AiStatus::Failed(e) => ("failed  ", theme.alert, Some(e)),
+                AiStatus::Cancelled => ("canceled", theme.dim, None),
+            };
+            let mut spans = vec![
+                Span::styled(
+                    format!("#{:<3} ", request.id),
+                    Style::default().fg(theme.dim),
+                ),
+                Span::styled(
+                    label,
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!(" {:>5.1}s ", request.elapsed().as_secs_f32())),
+                Span::raw(request.prompt.as_str()),
+            ];
+            if let Some(outcome) = outcome {
+                spans.push(Span::styled(" -> ", Style::default().fg(theme.dim)));
+                spans.push(Span::styled(outcome.as_str(), Style::default().fg(color)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let mut state = ListState::default().with_selected(app.ai_selected);
+    if app.ai_selected.is_none() {
+        // Follow the newest request until one is picked
+        *state.offset_mut() = app
+            .ai_requests
+            .len()
+            .saturating_sub(area.height.saturating_sub(2) as usize);
+    }
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title("AI requests (Up/Down select, Ctrl+X cancel/reject, Ctrl+R resend, Ctrl+A approve)"),
+        );
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_held_messages(f: &mut Frame, app: &App, theme: &Theme, area: ratatui::layout::Rect) {
+    let items: Vec<ListItem> = app
+        .held
+        .iter()
+        .map(|held| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{} {} ", held.category, held.level),
+                    Style::default().fg(theme.alert),
+                ),
+                Span::styled(
+                    format!("{}: ", held.user),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(held.text.as_str()),
+            ]))
+        })
+        .collect();
+
+    // Without a selection Ctrl+A and Ctrl+X act on the oldest, so show it picked
+    let mut state = ListState::default().with_selected(Some(app.held.selected.unwrap_or(0)));
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title("Held by AutoMod (Up/Down select, Ctrl+A allow, Ctrl+X deny)"),
+        );
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_song_queue(f: &mut Frame, app: &App, theme: &Theme, area: ratatui::layout::Rect) {
+    let now_playing = match &app.songs.now_playing {
+        Some(song) => Line::from(vec![
+            Span::styled(
+                "now ",
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(song.request.as_str()),
+            Span::styled(format!(" ({})", song.user), Style::default().fg(theme.dim)),
+        ]),
+        None => Line::styled("nothing playing", Style::default().fg(theme.dim)),
+    };
+    let queue = app.songs.queue.iter().enumerate().map(|(i, song)| {
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{:<3} ", i + 1), Style::default().fg(theme.dim)),
+            Span::raw(song.request.as_str()),
+            Span::styled(format!(" ({})", song.user), Style::default().fg(theme.dim)),
+        ]))
+    });
+    let items: Vec<ListItem> = std::iter::once(ListItem::new(now_playing))
+        .chain(queue)
+        .collect();
+
+    // The first row is the song playing, the selection is in the queue below it
+    let mut state = ListState::default().with_selected(app.song_selected.map(|i| i + 1));
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title(format!(
+                    "Song queue: {} (Up/Down select, Ctrl+U play next, Ctrl+X remove, Ctrl+N skip)",
+                    app.songs.queue.len()
+                )),
+        );
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_notifications(f: &mut Frame, app: &App, theme: &Theme, area: ratatui::layout::Rect) {
+    let rows = area.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = app
+        .notifications
+        .iter()
+        .skip(app.notifications.len().saturating_sub(rows))
+        .map(|n| {
+            let (label, color) = match n.severity {
+                Severity::Info => ("info ", theme.text),
+                Severity::Warning => ("warn ", theme.highlight),
+                Severity::Error => ("error", theme.alert),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    label,
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" "),
+                Span::styled(n.text.as_str(), Style::default().fg(color)),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.border))
+            .title("Notifications (F9 to hide)"),
+    );
+    f.render_widget(list, area);
+}
+
+fn render_user_list(f: &mut Frame, app: &App, theme: &Theme, area: ratatui::layout::Rect) {
+    let chatters = app.sorted_chatters();
+
+    let items: Vec<ListItem> = chatters
+        .iter()
+        .map(|(login, chatter)| {
+            let (sigil, role_style) = role_sigil(chatter.role);
+            let mut name_style = role_style;
+            if app.on_ai_cooldown(login) {
+                name_style = name_style.add_modifier(Modifier::REVERSED);
+            }
+            if app.filters.is_muted(login) {
+                name_style = name_style.fg(theme.dim).add_modifier(Modifier::CROSSED_OUT);
+            }
+
+            ListItem::new(Line::from(vec![
+                Span::styled(sigil, role_style),
+                Span::styled(login.as_str(), name_style),
+                Span::styled(
+                    format!(" {}", chatter.message_count),
+                    Style::default().fg(theme.dim),
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .style(Style::default().fg(theme.text))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title(format!("Users ({})", chatters.len())),
+        );
+    f.render_widget(list, area);
+}
+
+// IRC-style prefixes for roles
+fn role_sigil(role: Role) -> (&'static str, Style) {
+    match role {
+        Role::Broadcaster => ("~", Style::default().fg(Color::Red)),
+        Role::Moderator => ("@", Style::default().fg(Color::Green)),
+        Role::Vip => ("+", Style::default().fg(Color::Magenta)),
+        Role::Viewer => ("", Style::default()),
+    }
+}
+
+// Twitch's default name colors, used when a user never picked one
+const DEFAULT_NAME_COLORS: [Color; 15] = [
+    Color::Rgb(0xFF, 0x00, 0x00),
+    Color::Rgb(0x00, 0x00, 0xFF),
+    Color::Rgb(0x00, 0x80, 0x00),
+    Color::Rgb(0xB2, 0x22, 0x22),
+    Color::Rgb(0xFF, 0x7F, 0x50),
+    Color::Rgb(0x9A, 0xCD, 0x32),
+    Color::Rgb(0xFF, 0x45, 0x00),
+    Color::Rgb(0x2E, 0x8B, 0x57),
+    Color::Rgb(0xDA, 0xA5, 0x20),
+    Color::Rgb(0xD2, 0x69, 0x1E),
+    Color::Rgb(0x5F, 0x9E, 0xA0),
+    Color::Rgb(0x1E, 0x90, 0xFF),
+    Color::Rgb(0xFF, 0x69, 0xB4),
+    Color::Rgb(0x8A, 0x2B, 0xE2),
+    Color::Rgb(0x00, 0xFF, 0x7F),
+];
+
+fn parse_hex_color(hex: &str) -> Option<Color> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(Color::Rgb(
+        (value >> 16) as u8,
+        (value >> 8) as u8,
+        value as u8,
+    ))
+}
+
+pub fn name_color(user: &str, color: Option<&str>, theme: &Theme) -> Color {
+    color
+        .and_then(parse_hex_color)
+        .or(theme.username)
+        .unwrap_or_else(|| {
+            let hash = user
+                .bytes()
+                .fold(0usize, |acc, b| acc.wrapping_add(b as usize));
+            DEFAULT_NAME_COLORS[hash % DEFAULT_NAME_COLORS.len()]
+        })
+}
+
+// Emote words in chat; also how the renderer finds where to draw inline images
+fn emote_style(theme: &Theme) -> Style {
+    Style::new().fg(theme.emote).add_modifier(Modifier::BOLD)
+}
+
+// Both lines are chat messages from the same user
+fn same_sender(a: &ChatLine, b: &ChatLine) -> bool {
+    a.user
+        .as_ref()
+        .zip(b.user.as_ref())
+        .is_some_and(|(a, b)| a.eq_ignore_ascii_case(b))
+}
+
+// `grouped` lines continue the previous message's group: the name is blanked
+// out (same width, so wrapping and scroll math don't change) to leave the text
+// aligned under the first message.
+fn chat_line<'a>(line: &'a ChatLine, grouped: bool, theme: &Theme) -> Line<'a> {
+    let Some(user) = &line.user else {
+        if line.text.starts_with("Error:") {
+            return Line::from(Span::styled(
+                line.text.as_str(),
+                Style::default().fg(theme.alert),
+            ));
+        }
+        return Line::from(Span::raw(line.text.as_str()));
+    };
+
+    let (sigil, sigil_style) = role_sigil(line.role);
+    let mut name_style = Style::default().fg(name_color(user, line.color.as_deref(), theme));
+    if line.role != Role::Viewer {
+        name_style = name_style.add_modifier(Modifier::BOLD);
+    }
+
+    let mut spans = if grouped {
+        let indent = sigil.width() + user.width() + 2;
+        vec![Span::raw(" ".repeat(indent))]
+    } else {
+        vec![
+            Span::styled(sigil, sigil_style),
+            Span::styled(user.as_str(), name_style),
+            Span::raw(": "),
+        ]
+    };
+    if line.emotes.is_empty() {
+        spans.push(Span::raw(line.text.as_str()));
+    } else {
+        for word in line.text.split_inclusive(' ') {
+            if line.emotes.iter().any(|e| e == word.trim_end()) {
+                spans.push(Span::styled(word, emote_style(theme)));
+            } else {
+                spans.push(Span::raw(word));
+            }
+        }
+    }
+    Line::from(spans)
+}
+
+pub fn wrapped_height(line: &ChatLine, width: usize) -> usize {
+    // Styling doesn't affect widths, so any theme will do
+    wrap_spans(&chat_line(line, false, &Theme::default()).spans, width).len()
+}
+
+// Rows of the entries after the last one that fits in a view starting at `offset`
+fn rows_below(heights: &[usize], offset: usize, view_height: usize) -> usize {
+    let mut used = 0;
+    let mut end = offset;
+    while end < heights.len() && (used == 0 || used + heights[end] <= view_height) {
+        used += heights[end];
+        end += 1;
+    }
+    heights[end..].iter().sum()
+}
+
+// Greedy word wrap over styled spans. Words longer than the width are hard-split.
+fn wrap_spans(spans: &[Span], width: usize) -> Vec<Line<'static>> {
+    let width = width.max(1);
+    let mut lines: Vec<Vec<Span<'static>>> = vec![Vec::new()];
+    let mut line_width = 0;
+
+    for span in spans {
+        for word in span.content.split_inclusive(' ') {
+            let word_width = word.width();
+            let trimmed_width = word.trim_end().width();
+
+            if line_width > 0 && line_width + trimmed_width > width {
+                lines.push(Vec::new());
+                line_width = 0;
+            }
+
+            if trimmed_width <= width.saturating_sub(line_width) {
+                lines
+                    .last_mut()
+                    .unwrap()
+                    .push(Span::styled(word.to_string(), span.style));
+                line_width += word_width;
+                continue;
+            }
+
+            // Too long for any line: break it character by character
+            let mut chunk = String::new();
+            for ch in word.chars() {
+                let ch_width = ch.width().unwrap_or(0);
+                if line_width + ch_width > width {
+                    lines
+                        .last_mut()
+                        .unwrap()
+                        .push(Span::styled(std::mem::take(&mut chunk), span.style));
+                    lines.push(Vec::new());
+                    line_width = 0;
+                }
+                chunk.push(ch);
+                line_width += ch_width;
+            }
+            lines
+                .last_mut()
+                .unwrap()
+                .push(Span::styled(chunk, span.style));
+        }
+    }
+
+    lines.into_iter().map(Line::from).collect()
+}
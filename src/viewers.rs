@@ -0,0 +1,178 @@
+use crate::sqlite::{Store, Writer};
+use crate::state::AppEvent;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+// Which streams each viewer has been to, kept in the chat database, so the
+// join greeting can tell a first-timer from a regular coming back after a
+// while (AI_REGULAR_STREAMS, AI_RETURNING_AFTER). A stream is told apart by
+// its start time from Helix, so restarting the bot mid-stream doesn't count
+// as a new one; while the channel is offline nobody is counted. Watch time
+// is what viewers spent lurking (see lurk.rs).
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS streams (
+    id INTEGER PRIMARY KEY,
+    started TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS viewers (
+    user TEXT PRIMARY KEY,
+    streams INTEGER NOT NULL,
+    last_stream INTEGER NOT NULL,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS watch_time (
+    user TEXT PRIMARY KEY,
+    seconds INTEGER NOT NULL
+);
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrival {
+    // Never seen before
+    New,
+    // A regular back after missing `missed` streams in a row
+    Returning { missed: i64 },
+    // Anyone else, including those already here this stream
+    Known,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Viewer {
+    // Streams they've been to
+    streams: i64,
+    last_stream: i64,
+}
+
+pub struct Viewers {
+    viewers: HashMap<String, Viewer>,
+    // Seconds watched, by login
+    watched: HashMap<String, i64>,
+    writes: Writer,
+    // Every stream's start time, oldest first; a stream's number is its place
+    // in here, from 1
+    streams: Vec<String>,
+    // The live stream's number, None while offline
+    current: Option<i64>,
+}
+
+impl Viewers {
+    pub fn open(path: &Path, events: UnboundedSender<AppEvent>) -> Result<Self> {
+        let db = Store::open(path, SCHEMA)?;
+        let streams = db
+            .query("SELECT started FROM streams ORDER BY id", &[])?
+            .iter()
+            .filter_map(|row| Some(row.first()?.as_str()?.to_string()))
+            .collect();
+        let viewers = db
+            .query("SELECT user, streams, last_stream FROM viewers", &[])?
+            .iter()
+            .filter_map(|row| {
+                Some((
+                    row.first()?.as_str()?.to_string(),
+                    Viewer {
+                        streams: row.get(1)?.as_int()?,
+                        last_stream: row.get(2)?.as_int()?,
+                    },
+                ))
+            })
+            .collect();
+        let watched = db
+            .query("SELECT user, seconds FROM watch_time", &[])?
+            .iter()
+            .filter_map(|row| Some((row.first()?.as_str()?.to_string(), row.get(1)?.as_int()?)))
+            .collect();
+
+        let writes = db.writer("viewers", events);
+        Ok(Self {
+            viewers,
+            watched,
+            writes,
+            streams,
+            current: None,
+        })
+    }
+
+    /// The live stream, by its start time, or None when the channel went
+    /// offline.
+    pub fn set_stream(&mut self, started: Option<&str>) {
+        self.current =
+            started.map(
+                |started| match self.streams.iter().position(|known| known == started) {
+                    Some(i) => i as i64 + 1,
+                    None => {
+                        self.streams.push(started.to_string());
+                        self.writes.write(
+                            "INSERT OR IGNORE INTO streams (started) VALUES (?)",
+                            vec![started.into()],
+                        );
+                        self.streams.len() as i64
+                    }
+                },
+            );
+    }
+
+    /// Count a viewer as here this stream. None while the channel is offline.
+    pub fn arrive(
+        &mut self,
+        user: &str,
+        regular_streams: u32,
+        returning_after: u32,
+    ) -> Option<Arrival> {
+        let current = self.current?;
+        let user = user.to_lowercase();
+        let now = crate::chatlog::timestamp();
+        let arrival = match self.viewers.get(&user).copied() {
+            Some(viewer) if viewer.last_stream == current => return Some(Arrival::Known),
+            Some(viewer) => {
+                let missed = current - viewer.last_stream - 1;
+                let regular = viewer.streams >= i64::from(regular_streams);
+                let away = missed >= i64::from(returning_after.max(1));
+                if regular && away {
+                    Arrival::Returning { missed }
+                } else {
+                    Arrival::Known
+                }
+            }
+            None => Arrival::New,
+        };
+        let viewer = self.viewers.entry(user.clone()).or_insert(Viewer {
+            streams: 0,
+            last_stream: current,
+        });
+        viewer.streams += 1;
+        viewer.last_stream = current;
+        self.writes.write(
+            "INSERT INTO viewers (user, streams, last_stream, first_seen, last_seen)
+             VALUES (?, 1, ?, ?, ?)
+             ON CONFLICT (user) DO UPDATE SET
+                 streams = streams + 1, last_stream = excluded.last_stream,
+                 last_seen = excluded.last_seen",
+            vec![
+                user.as_str().into(),
+                current.into(),
+                now.as_str().into(),
+                now.into(),
+            ],
+        );
+        Some(arrival)
+    }
+
+    /// Add to `user`'s watch time, and return all of it.
+    pub fn add_watch_time(&mut self, user: &str, watched: Duration) -> Duration {
+        let user = user.to_lowercase();
+        let seconds = watched.as_secs() as i64;
+        let total = self.watched.entry(user.clone()).or_insert(0);
+        *total += seconds;
+        self.writes.write(
+            "INSERT INTO watch_time (user, seconds) VALUES (?, ?)
+             ON CONFLICT (user) DO UPDATE SET seconds = seconds + excluded.seconds",
+            vec![user.into(), seconds.into()],
+        );
+        Duration::from_secs(*total as u64)
+    }
+}
@@ -1,13 +1,18 @@
+use crate::automod::HeldMessage;
 use crate::config::Config;
-use crate::state::AppEvent;
-use anyhow::{bail, Context, Result};
+use crate::state::{AppEvent, Badge, ChatMessage, ConnectionState, Fragment, Service, StreamAlert};
+use anyhow::{bail, Result};
 use futures_util::{SinkExt, StreamExt};
 use reqwest::Client;
 use serde::Deserialize;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
 
-const EVENTSUB_WS_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+const REDEMPTION_EVENT: &str = "channel.channel_points_custom_reward_redemption.add";
+const AUTOMOD_HOLD_EVENT: &str = "automod.message.hold";
+const AUTOMOD_UPDATE_EVENT: &str = "automod.message.update";
 
 #[derive(Debug, Deserialize)]
 struct SessionWelcomePayload {
@@ -25,24 +30,254 @@ struct Envelope {
 #[derive(Debug, Deserialize)]
 struct Metadata {
     message_type: String,
+    // Only set on notifications, e.g. "channel.chat.message"
+    #[serde(default)]
+    subscription_type: String,
+    // RFC 3339, when Twitch sent the message
+    #[serde(default)]
+    message_timestamp: String,
 }
 #[derive(Debug, Deserialize)]
 struct ChatMessageContent {
     text: String,
+    #[serde(default)]
+    fragments: Vec<ChatFragment>,
+}
+#[derive(Debug, Deserialize)]
+struct ChatFragment {
+    text: String,
+    emote: Option<FragmentEmote>,
+    cheermote: Option<FragmentCheermote>,
+    mention: Option<FragmentMention>,
+}
+#[derive(Debug, Deserialize)]
+struct FragmentEmote {
+    id: String,
+}
+#[derive(Debug, Deserialize)]
+struct FragmentCheermote {
+    prefix: String,
+    bits: u32,
+}
+#[derive(Debug, Deserialize)]
+struct FragmentMention {
+    user_login: String,
+}
+#[derive(Debug, Deserialize)]
+struct ChatBadge {
+    set_id: String,
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    info: String,
 }
 #[derive(Debug, Deserialize)]
 struct ChatMessageEvent {
+    #[serde(default)]
+    message_id: String,
     chatter_user_login: String,
+    #[serde(default)]
+    chatter_user_name: String,
     message: ChatMessageContent,
+    #[serde(default)]
+    color: String,
+    #[serde(default)]
+    badges: Vec<ChatBadge>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FollowEvent {
+    user_login: String,
+}
+#[derive(Debug, Deserialize)]
+struct SubscribeEvent {
+    user_login: String,
+    tier: String,
+    #[serde(default)]
+    is_gift: bool,
+}
+#[derive(Debug, Deserialize)]
+struct GiftEvent {
+    // Null for anonymous gifts
+    user_login: Option<String>,
+    total: u32,
+    tier: String,
+}
+#[derive(Debug, Deserialize)]
+struct RaidEvent {
+    from_broadcaster_user_id: String,
+    from_broadcaster_user_login: String,
+    viewers: u32,
+}
+#[derive(Debug, Deserialize)]
+struct CheerEvent {
+    user_login: Option<String>,
+    bits: u32,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AutoModHoldEvent {
+    user_login: String,
+    message_id: String,
+    message: AutoModMessage,
+    #[serde(default)]
+    category: String,
+    #[serde(default)]
+    level: u8,
+}
+#[derive(Debug, Deserialize)]
+struct AutoModMessage {
+    text: String,
+}
+// A held message was allowed or denied, or expired
+#[derive(Debug, Deserialize)]
+struct AutoModUpdateEvent {
+    message_id: String,
+    #[serde(default)]
+    moderator_user_login: Option<String>,
+    status: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct RedemptionEvent {
+    user_login: String,
+    #[serde(default)]
+    user_input: String,
+    reward: RedemptionReward,
+}
+#[derive(Debug, Deserialize)]
+struct RedemptionReward {
+    id: String,
+    title: String,
+}
+
+impl ChatFragment {
+    fn into_fragment(self) -> Fragment {
+        if let Some(emote) = self.emote {
+            Fragment::Emote {
+                name: self.text,
+                id: emote.id,
+            }
+        } else if let Some(cheermote) = self.cheermote {
+            Fragment::Cheermote {
+                text: self.text,
+                prefix: cheermote.prefix,
+                bits: cheermote.bits,
+            }
+        } else if let Some(mention) = self.mention {
+            Fragment::Mention {
+                text: self.text,
+                user: mention.user_login,
+            }
+        } else {
+            Fragment::Text(self.text)
+        }
+    }
+}
+
+impl ChatMessageEvent {
+    // `timestamp` is the notification's; the event itself has none
+    fn into_message(self, timestamp: &str) -> ChatMessage {
+        let display_name = if self.chatter_user_name.is_empty() {
+            self.chatter_user_login.clone()
+        } else {
+            self.chatter_user_name
+        };
+        ChatMessage {
+            id: self.message_id,
+            user: self.chatter_user_login,
+            display_name,
+            text: self.message.text,
+            color: Some(self.color).filter(|c| !c.is_empty()),
+            badges: self
+                .badges
+                .into_iter()
+                .map(|b| Badge {
+                    set_id: b.set_id,
+                    id: b.id,
+                    info: b.info,
+                })
+                .collect(),
+            fragments: self
+                .message
+                .fragments
+                .into_iter()
+                .map(ChatFragment::into_fragment)
+                .collect(),
+            timestamp: timestamp.parse().unwrap_or_else(|_| jiff::Timestamp::now()),
+        }
+    }
+}
+
+/// The chat message in a "channel.chat.message" notification, from the text
+/// of the WebSocket frame it came in.
+pub fn parse_chat_notification(text: &str) -> Result<ChatMessage> {
+    let envelope: Envelope = serde_json::from_str(text)?;
+    if envelope.metadata.subscription_type != "channel.chat.message" {
+        bail!(
+            "Not a chat message notification: {}",
+            envelope.metadata.message_type
+        );
+    }
+    let Some(event) = envelope.payload.get("event") else {
+        bail!("Chat notification without an event");
+    };
+    let chat = ChatMessageEvent::deserialize(event)?;
+    Ok(chat.into_message(&envelope.metadata.message_timestamp))
+}
+
+// Turn an alert notification into a StreamAlert. None for event types that
+// don't make an alert (including subs that are part of a gift, which the
+// gift event already covers).
+fn parse_alert(
+    event_type: &str,
+    event: serde_json::Value,
+) -> Option<serde_json::Result<StreamAlert>> {
+    let alert = match event_type {
+        "channel.follow" => serde_json::from_value::<FollowEvent>(event)
+            .map(|e| StreamAlert::Follow { user: e.user_login }),
+        "channel.subscribe" => match serde_json::from_value::<SubscribeEvent>(event) {
+            Ok(e) if e.is_gift => return None,
+            result => result.map(|e| StreamAlert::Subscribe {
+                user: e.user_login,
+                tier: e.tier,
+            }),
+        },
+        "channel.subscription.gift" => {
+            serde_json::from_value::<GiftEvent>(event).map(|e| StreamAlert::GiftSub {
+                user: e.user_login,
+                count: e.total,
+                tier: e.tier,
+            })
+        }
+        "channel.raid" => serde_json::from_value::<RaidEvent>(event).map(|e| StreamAlert::Raid {
+            from: e.from_broadcaster_user_login,
+            from_id: e.from_broadcaster_user_id,
+            viewers: e.viewers,
+        }),
+        "channel.cheer" => {
+            serde_json::from_value::<CheerEvent>(event).map(|e| StreamAlert::Cheer {
+                user: e.user_login,
+                bits: e.bits,
+                message: e.message,
+            })
+        }
+        _ => return None,
+    };
+    Some(alert)
+}
+
+// The connection is closed once `shutdown` is cancelled
 pub async fn connect_eventsub_ws(
     _http_client: Client,
-    _config: Config,
+    config: Arc<Config>,
     event_tx: mpsc::UnboundedSender<AppEvent>,
+    shutdown: CancellationToken,
 ) -> Result<(String, tokio::task::JoinHandle<Result<()>>)> {
-    let (_ws_stream, _) = tokio_tungstenite::connect_async(EVENTSUB_WS_URL).await?;
-    let (mut _write, rx) = _ws_stream.split();
+    let (_ws_stream, _) = tokio_tungstenite::connect_async(&config.endpoints.eventsub).await?;
+    let (mut write, rx) = _ws_stream.split();
 
     let session_id = std::sync::Arc::new(tokio::sync::Mutex::new(String::new()));
     let session_id_clone = session_id.clone();
@@ -52,21 +287,24 @@ pub async fn connect_eventsub_ws(
         // But to make it cleaner, let's just use raw stream matching logic from previous
         let mut stream = rx.fuse();
 
-        while let Some(msg) = stream.next().await {
+        loop {
+            let msg = tokio::select! {
+                _ = shutdown.cancelled() => {
+                    let _ = write.send(Message::Close(None)).await;
+                    break;
+                }
+                msg = stream.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+            };
             match msg {
                 Ok(Message::Text(text)) => {
                     let text = text.to_string();
                     // Clean up debug print:
                     // println!("WS: Got text message: {}", text);
 
-                    if let Ok(mut file) = std::fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open("debug.log")
-                    {
-                        use std::io::Write;
-                        writeln!(file, "WS Received: {}", text).unwrap_or(());
-                    }
+                    let _ = event_tx.send(AppEvent::Debug(format!("WS Received: {}", text)));
 
                     let envelope: Envelope = match serde_json::from_str(&text) {
                         Ok(v) => v,
@@ -83,35 +321,110 @@ pub async fn connect_eventsub_ws(
                             {
                                 let mut guard = session_id_clone.lock().await;
                                 *guard = welcome.session.id.clone();
+                                let _ = event_tx.send(AppEvent::Connection(
+                                    Service::EventSub,
+                                    ConnectionState::Connected,
+                                ));
                                 // println!("Session welcome! ID = {}", *guard);
                             } else {
                                 let _ = event_tx
                                     .send(AppEvent::Error("Failed to parse welcome".into()));
                             }
                         }
+                        "notification"
+                            if envelope.metadata.subscription_type != "channel.chat.message" =>
+                        {
+                            let event_type = envelope.metadata.subscription_type;
+                            let Some(event) = envelope.payload.get("event") else {
+                                continue;
+                            };
+                            if event_type == REDEMPTION_EVENT {
+                                match serde_json::from_value::<RedemptionEvent>(event.clone()) {
+                                    Ok(e) => {
+                                        let _ = event_tx.send(AppEvent::Redemption {
+                                            user: e.user_login,
+                                            reward_id: e.reward.id,
+                                            reward: e.reward.title,
+                                            input: e.user_input,
+                                        });
+                                    }
+                                    Err(e) => {
+                                        let _ = event_tx.send(AppEvent::Debug(format!(
+                                            "Failed to parse {} event: {} JSON: {}",
+                                            event_type, e, event
+                                        )));
+                                    }
+                                }
+                                continue;
+                            }
+                            if event_type == AUTOMOD_HOLD_EVENT {
+                                match serde_json::from_value::<AutoModHoldEvent>(event.clone()) {
+                                    Ok(e) => {
+                                        let held = HeldMessage {
+                                            id: e.message_id,
+                                            user: e.user_login,
+                                            text: e.message.text,
+                                            category: e.category,
+                                            level: e.level,
+                                        };
+                                        let _ =
+                                            event_tx.send(AppEvent::Moderation(held.mod_event()));
+                                        let _ = event_tx.send(AppEvent::AutoModHeld(held));
+                                    }
+                                    Err(e) => {
+                                        let _ = event_tx.send(AppEvent::Debug(format!(
+                                            "Failed to parse {} event: {} JSON: {}",
+                                            event_type, e, event
+                                        )));
+                                    }
+                                }
+                                continue;
+                            }
+                            if event_type == AUTOMOD_UPDATE_EVENT {
+                                match serde_json::from_value::<AutoModUpdateEvent>(event.clone()) {
+                                    Ok(e) => {
+                                        let _ = event_tx.send(AppEvent::AutoModResolved {
+                                            id: e.message_id,
+                                            status: e.status.to_lowercase(),
+                                            moderator: e.moderator_user_login,
+                                        });
+                                    }
+                                    Err(e) => {
+                                        let _ = event_tx.send(AppEvent::Debug(format!(
+                                            "Failed to parse {} event: {} JSON: {}",
+                                            event_type, e, event
+                                        )));
+                                    }
+                                }
+                                continue;
+                            }
+                            match parse_alert(&event_type, event.clone()) {
+                                Some(Ok(alert)) => {
+                                    let _ = event_tx.send(AppEvent::Alert(alert));
+                                }
+                                Some(Err(e)) => {
+                                    let _ = event_tx.send(AppEvent::Debug(format!(
+                                        "Failed to parse {} event: {} JSON: {}",
+                                        event_type, e, event
+                                    )));
+                                }
+                                None => {}
+                            }
+                        }
                         "notification" => {
                             if let Some(event) = envelope.payload.get("event") {
                                 match serde_json::from_value::<ChatMessageEvent>(event.clone()) {
                                     Ok(chat) => {
-                                        let _ = event_tx.send(AppEvent::ChatMessage {
-                                            user: chat.chatter_user_login,
-                                            text: chat.message.text,
-                                        });
+                                        let message =
+                                            chat.into_message(&envelope.metadata.message_timestamp);
+                                        let _ =
+                                            event_tx.send(AppEvent::ChatMessage(Arc::new(message)));
                                     }
                                     Err(e) => {
-                                        if let Ok(mut file) = std::fs::OpenOptions::new()
-                                            .create(true)
-                                            .append(true)
-                                            .open("debug.log")
-                                        {
-                                            use std::io::Write;
-                                            writeln!(
-                                                file,
-                                                "Failed to parse ChatMessageEvent: {} \nJSON: {}",
-                                                e, event
-                                            )
-                                            .unwrap_or(());
-                                        }
+                                        let _ = event_tx.send(AppEvent::Debug(format!(
+                                            "Failed to parse ChatMessageEvent: {} JSON: {}",
+                                            e, event
+                                        )));
                                     }
                                 }
                             }
@@ -128,6 +441,10 @@ pub async fn connect_eventsub_ws(
                 _ => {}
             }
         }
+        let _ = event_tx.send(AppEvent::Connection(
+            Service::EventSub,
+            ConnectionState::Disconnected,
+        ));
         Ok(())
     });
 
@@ -141,90 +458,3 @@ pub async fn connect_eventsub_ws(
 
     Ok((session_id, handle))
 }
-
-// Basic IRC WebSocket Connection
-pub async fn connect_irc_ws(
-    config: Config,
-    event_tx: mpsc::UnboundedSender<AppEvent>,
-) -> Result<tokio::task::JoinHandle<()>> {
-    let url = "wss://irc-ws.chat.twitch.tv:443";
-    let (ws_stream, _) = tokio_tungstenite::connect_async(url)
-        .await
-        .context("Failed to connect to IRC")?;
-
-    let (mut write, mut read) = ws_stream.split();
-
-    // Authenticate
-    let token = config.oauth_token.as_ref().context("Token missing")?;
-    let channel = config
-        .channel_name
-        .as_ref()
-        .context("Channel name missing")?;
-
-    let caps_cmd =
-        Message::Text("CAP REQ :twitch.tv/membership twitch.tv/tags twitch.tv/commands".into());
-    write.send(caps_cmd).await?;
-
-    let pass_cmd = Message::Text(format!("PASS oauth:{}", token).into());
-    write.send(pass_cmd).await?;
-
-    write.send(Message::Text("NICK infobot".into())).await?;
-
-    let join_cmd = Message::Text(format!("JOIN #{}", channel).into());
-    write.send(join_cmd).await?;
-
-    let _ = event_tx.send(AppEvent::Info("IRC Connected - Listening for Joins".into()));
-
-    let handle = tokio::spawn(async move {
-        while let Some(msg) = read.next().await {
-            match msg {
-                Ok(Message::Text(text)) => {
-                    for line in text.lines() {
-                        let line = line.trim();
-                        if line.is_empty() {
-                            continue;
-                        }
-
-                        if line.starts_with("PING") {
-                            let pong = line.replace("PING", "PONG");
-                            if let Err(_) = write.send(Message::Text(pong.into())).await {
-                                break;
-                            }
-                            continue;
-                        }
-
-                        // Parse JOIN/PART
-                        // Format: :<user>!<user>@<user>.tmi.twitch.tv JOIN #<channel>
-                        if line.contains(" JOIN #") {
-                            if let Some(user) = parse_irc_user(line) {
-                                let _ = event_tx.send(AppEvent::UserJoined(user));
-                            }
-                        } else if line.contains(" PART #") {
-                            if let Some(user) = parse_irc_user(line) {
-                                let _ = event_tx.send(AppEvent::UserLeft(user));
-                            }
-                        }
-                    }
-                }
-                Ok(Message::Close(_)) => break,
-                Err(_) => break,
-                _ => {}
-            }
-        }
-    });
-
-    Ok(handle)
-}
-
-fn parse_irc_user(line: &str) -> Option<String> {
-    // :username!username@username.tmi.twitch.tv JOIN #channel
-    if !line.starts_with(':') {
-        return None;
-    }
-    let end = line.find('!')?;
-    if end > 1 {
-        Some(line[1..end].to_string())
-    } else {
-        None
-    }
-}
@@ -0,0 +1,175 @@
+mod common;
+
+use choui_the_no_gui_chatbot::ai::ask;
+use choui_the_no_gui_chatbot::config::{Config, LlmProvider};
+use serde_json::json;
+use tempfile::TempDir;
+use wiremock::matchers::{body_partial_json, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn gemini(url: &str) -> (Config, TempDir) {
+    let (mut config, dir) = common::config(url);
+    config.llm_provider = LlmProvider::Gemini;
+    config.gemini_api_key = Some("gemini-key".to_string());
+    config.gemini_model = "gemini-test".to_string();
+    (config, dir)
+}
+
+fn ollama(url: &str) -> (Config, TempDir) {
+    let (mut config, dir) = common::config(url);
+    config.llm_provider = LlmProvider::Ollama;
+    config.ollama_model = "llama-test".to_string();
+    (config, dir)
+}
+
+// Answer every Gemini request with `status` and `body`
+async fn gemini_server(status: u16, body: serde_json::Value) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-test:generateContent"))
+        .and(query_param("key", "gemini-key"))
+        .respond_with(ResponseTemplate::new(status).set_body_json(body))
+        .mount(&server)
+        .await;
+    server
+}
+
+// Answer every Ollama request with `status` and `body`
+async fn ollama_server(status: u16, body: serde_json::Value) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .respond_with(ResponseTemplate::new(status).set_body_json(body))
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn gemini_reply_is_the_first_candidate() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1beta/models/gemini-test:generateContent"))
+        .and(query_param("key", "gemini-key"))
+        .and(body_partial_json(json!({
+            "contents": [{ "parts": [{ "text": "User viewer: hello" }] }],
+            "system_instruction": { "parts": [{ "text": "Be nice" }] }
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [
+                { "content": { "parts": [{ "text": "  Hi there!  " }], "role": "model" } },
+                { "content": { "parts": [{ "text": "Other" }] } }
+            ]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = gemini(&server.uri());
+
+    let reply = ask("User viewer: hello", "Be nice", &config).await.unwrap();
+
+    assert_eq!(reply, "Hi there!");
+}
+
+#[tokio::test]
+async fn gemini_without_text_falls_back() {
+    // What a blocked prompt looks like
+    let server = gemini_server(
+        200,
+        json!({ "promptFeedback": { "blockReason": "SAFETY" } }),
+    )
+    .await;
+    let (config, _dir) = gemini(&server.uri());
+
+    let reply = ask("hello", "", &config).await.unwrap();
+
+    assert_eq!(reply, "*Squeak?* (I have no words!)");
+}
+
+#[tokio::test]
+async fn gemini_quota_is_a_reply_not_an_error() {
+    let server = gemini_server(429, json!({ "error": { "code": 429 } })).await;
+    let (config, _dir) = gemini(&server.uri());
+
+    let reply = ask("hello", "", &config).await.unwrap();
+
+    assert!(reply.contains("Quota Exceeded"), "{}", reply);
+}
+
+#[tokio::test]
+async fn gemini_errors_carry_the_status() {
+    let server = gemini_server(400, json!({ "error": { "message": "API key not valid" } })).await;
+    let (config, _dir) = gemini(&server.uri());
+
+    let error = ask("hello", "", &config).await.unwrap_err();
+
+    let message = error.to_string();
+    assert!(message.contains("400"), "{}", message);
+    assert!(message.contains("API key not valid"), "{}", message);
+}
+
+#[tokio::test]
+async fn gemini_needs_a_key() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+    let (mut config, _dir) = gemini(&server.uri());
+    config.gemini_api_key = None;
+
+    assert!(ask("hello", "", &config).await.is_err());
+}
+
+#[tokio::test]
+async fn ollama_reply_is_trimmed() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .and(body_partial_json(json!({
+            "model": "llama-test",
+            "prompt": "User viewer: hello",
+            "system": "Be nice",
+            "stream": false
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "model": "llama-test", "response": "\n Hello from Ollama \n", "done": true
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = ollama(&server.uri());
+
+    let reply = ask("User viewer: hello", "Be nice", &config).await.unwrap();
+
+    assert_eq!(reply, "Hello from Ollama");
+}
+
+#[tokio::test]
+async fn ollama_empty_reply_falls_back() {
+    let server = ollama_server(200, json!({ "response": "   ", "done": true })).await;
+    let (config, _dir) = ollama(&server.uri());
+
+    let reply = ask("hello", "", &config).await.unwrap();
+
+    assert_eq!(reply, "*Squeak?* (Empty thought bubble!)");
+}
+
+#[tokio::test]
+async fn ollama_errors_carry_the_status() {
+    let server = ollama_server(404, json!({ "error": "model 'llama-test' not found" })).await;
+    let (config, _dir) = ollama(&server.uri());
+
+    let error = ask("hello", "", &config).await.unwrap_err();
+
+    assert!(error.to_string().contains("not found"), "{}", error);
+}
+
+#[tokio::test]
+async fn ollama_malformed_reply_is_an_error() {
+    let server = ollama_server(200, json!({ "unexpected": true })).await;
+    let (config, _dir) = ollama(&server.uri());
+
+    assert!(ask("hello", "", &config).await.is_err());
+}
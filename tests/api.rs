@@ -0,0 +1,158 @@
+use choui_the_no_gui_chatbot::api::{self, ApiRequest, ApiSettings};
+use choui_the_no_gui_chatbot::commands::TtsControl;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+// Serve the API on a free port, with a stand-in for the event loop that
+// answers every request with its Debug form
+async fn start() -> String {
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let settings = ApiSettings {
+        enabled: true,
+        address: address.clone(),
+        token: "secret".to_string(),
+        messages: vec![("brb".to_string(), "Be right back!".to_string())],
+        sounds: Vec::new(),
+    };
+    let (calls, mut requests) = mpsc::unbounded_channel::<api::ApiCall>();
+    tokio::spawn(api::serve(settings, calls));
+    tokio::spawn(async move {
+        while let Some(call) = requests.recv().await {
+            let _ = call.reply.send(match call.request {
+                ApiRequest::Tts(TtsControl::Toggle) => Ok(json!({ "tts": true })),
+                request => Ok(json!(format!("{:?}", request))),
+            });
+        }
+    });
+    // Until it's listening
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(&address).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    address
+}
+
+// Send a frame and read the answer
+async fn ask<S>(socket: &mut S, frame: Value) -> Value
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
+        + SinkExt<Message>
+        + Unpin,
+    S::Error: std::fmt::Debug,
+{
+    socket.send(Message::text(frame.to_string())).await.unwrap();
+    let answer = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(answer.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn websocket_takes_the_same_requests() {
+    let address = start().await;
+
+    let refused =
+        tokio_tungstenite::connect_async(format!("ws://{}/ws?token=wrong", address)).await;
+    assert!(refused.is_err());
+
+    let (mut socket, _) =
+        tokio_tungstenite::connect_async(format!("ws://{}/ws?token=secret", address))
+            .await
+            .unwrap();
+
+    assert_eq!(
+        ask(
+            &mut socket,
+            json!({ "id": 7, "command": "tts", "action": "toggle" })
+        )
+        .await,
+        json!({ "id": 7, "ok": true, "result": { "tts": true } })
+    );
+    let sound = ask(
+        &mut socket,
+        json!({ "command": "sound", "name": "AirHorn" }),
+    )
+    .await;
+    assert_eq!(sound["result"], r#"Sound("airhorn")"#);
+    let unknown = ask(&mut socket, json!({ "command": "selfdestruct" })).await;
+    assert_eq!(unknown["ok"], false);
+    assert!(unknown["error"].as_str().unwrap().contains("selfdestruct"));
+    let missing = ask(&mut socket, json!({ "command": "tts", "action": "louder" })).await;
+    assert_eq!(missing["ok"], false);
+    assert_eq!(ask(&mut socket, json!("status")).await["ok"], false);
+}
+
+#[tokio::test]
+async fn canned_messages_over_http() {
+    let address = start().await;
+    let client = reqwest::Client::new();
+    let post = |path: &str, token: &str| {
+        client
+            .post(format!("http://{}{}", address, path))
+            .bearer_auth(token)
+            .json(&json!({ "name": "brb" }))
+            .send()
+    };
+
+    let answer = post("/api/message", "secret").await.unwrap();
+    assert_eq!(answer.status(), 200);
+    assert_eq!(answer.json::<Value>().await.unwrap(), r#"Message("brb")"#);
+    assert_eq!(post("/api/message", "nope").await.unwrap().status(), 401);
+    assert_eq!(post("/api/ws", "secret").await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn routes_answer_like_before() {
+    let address = start().await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", address, path);
+
+    let dashboard = client.get(url("/")).send().await.unwrap();
+    assert_eq!(dashboard.status(), 200);
+    assert!(dashboard.text().await.unwrap().contains("<html"));
+
+    let status = client
+        .get(url("/api/status?token=secret"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(status.json::<Value>().await.unwrap(), "Status");
+
+    let wrong_method = client
+        .get(url("/api/chat"))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wrong_method.status(), 405);
+    let not_json = client
+        .post(url("/api/chat"))
+        .bearer_auth("secret")
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(not_json.status(), 400);
+    let plain_http = client
+        .get(url("/ws"))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(plain_http.status(), 400);
+    assert_eq!(
+        client.get(url("/nowhere")).send().await.unwrap().status(),
+        404
+    );
+}
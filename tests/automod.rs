@@ -0,0 +1,54 @@
+use choui_the_no_gui_chatbot::automod::{HeldMessage, HeldMessages};
+
+fn held(id: &str) -> HeldMessage {
+    HeldMessage {
+        id: id.to_string(),
+        user: "viewer".to_string(),
+        text: "some bad words".to_string(),
+        category: "swearing".to_string(),
+        level: 2,
+    }
+}
+
+fn current(held: &HeldMessages) -> Option<&str> {
+    held.current().map(|message| message.id.as_str())
+}
+
+#[test]
+fn acts_on_the_oldest_until_one_is_selected() {
+    let mut queue = HeldMessages::default();
+    assert_eq!(current(&queue), None);
+    for id in ["a", "b", "c"] {
+        queue.hold(held(id));
+    }
+    assert_eq!(current(&queue), Some("a"));
+
+    queue.select_step(false);
+    queue.select_step(false);
+    assert_eq!(current(&queue), Some("b"));
+    queue.select_step(false);
+    queue.select_step(false);
+    assert_eq!(current(&queue), Some("c"));
+}
+
+#[test]
+fn selection_follows_resolved_messages() {
+    let mut queue = HeldMessages::default();
+    for id in ["a", "b", "c"] {
+        queue.hold(held(id));
+    }
+    queue.selected = Some(2);
+    // Another mod decided on one above the selection
+    assert_eq!(
+        queue.resolve("a").map(|message| message.id),
+        Some("a".to_string())
+    );
+    assert_eq!(current(&queue), Some("c"));
+    assert!(queue.resolve("a").is_none());
+
+    queue.resolve("c");
+    assert_eq!(current(&queue), Some("b"));
+    queue.resolve("b");
+    assert_eq!(queue.selected, None);
+    assert!(queue.is_empty());
+}
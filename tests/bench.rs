@@ -0,0 +1,101 @@
+use choui_the_no_gui_chatbot::bench::{CpuMonitor, Latencies, Scenario};
+use choui_the_no_gui_chatbot::state::{AppEvent, StreamAlert};
+use std::time::{Duration, Instant};
+
+fn scenario(args: &[&str]) -> anyhow::Result<Scenario> {
+    Scenario::from_args(args.iter().map(|arg| arg.to_string()), "bench")
+}
+
+#[test]
+fn reads_the_scenario() {
+    let defaults = scenario(&[]).unwrap();
+    assert_eq!(defaults.messages, Some(50));
+    assert_eq!(defaults.rate, 10.0);
+
+    let raids = scenario(&["--duration", "5", "--raid", "50@3", "--raid", "10@1"]).unwrap();
+    assert_eq!(raids.messages, None);
+    assert_eq!(
+        raids.raids,
+        [(10, Duration::from_secs(1)), (50, Duration::from_secs(3))]
+    );
+    assert_eq!(raids.burst(), 101);
+
+    assert!(scenario(&["--raid", "50"]).is_err());
+    assert!(scenario(&["--join-ratio", "2"]).is_err());
+    assert!(scenario(&["--rate", "0"]).is_err());
+    assert!(scenario(&["--messages"]).is_err());
+}
+
+#[tokio::test]
+async fn sends_joins_chat_and_raids() {
+    let scenario = scenario(&[
+        "--messages",
+        "10",
+        "--rate",
+        "1000",
+        "--join-ratio",
+        "0.3",
+        "--raid",
+        "2@0",
+    ])
+    .unwrap();
+    let mut events = Vec::new();
+    let sent = scenario
+        .send_events(&mut CpuMonitor::new(), |event| events.push(event))
+        .await;
+
+    assert_eq!(sent, 15);
+    assert_eq!(events.len(), 15);
+    assert!(matches!(
+        &events[0],
+        AppEvent::Alert(StreamAlert::Raid { viewers: 2, .. })
+    ));
+    let joins = events[5..]
+        .iter()
+        .filter(|event| matches!(event, AppEvent::UserJoined(_)))
+        .count();
+    assert_eq!(joins, 3);
+    // Every chat message can be told apart
+    let mut ids: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            AppEvent::ChatMessage(message) => Some(message.id.clone()),
+            _ => None,
+        })
+        .collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 9);
+}
+
+#[test]
+fn measures_its_own_cpu() {
+    let mut cpu = CpuMonitor::new();
+    cpu.sample();
+    let busy = std::time::Instant::now();
+    while busy.elapsed() < Duration::from_millis(300) {
+        std::hint::black_box(busy.elapsed());
+    }
+    cpu.sample();
+
+    let report = cpu.to_json();
+    // Spinning keeps one core busy, whatever else the machine is doing
+    let process = report["process"]["avg"].as_f64().unwrap();
+    assert!(process > 30.0, "{}", report);
+    assert!(report["system"]["avg"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn percentiles_need_no_report_first() {
+    let now = Instant::now();
+    let mut latencies = Latencies::new();
+    for ms in [50, 10, 30] {
+        latencies.record(now - Duration::from_millis(ms));
+    }
+
+    let json = latencies.to_json();
+    assert_eq!(json["events"], 3);
+    let p50 = json["p50"].as_f64().unwrap();
+    assert!((30_000.0..40_000.0).contains(&p50), "p50 {}", p50);
+    assert!(json["max"].as_f64().unwrap() >= 50_000.0);
+}
@@ -0,0 +1,60 @@
+use choui_the_no_gui_chatbot::chat_speed::{ChatSpeed, SpeedChange};
+use std::time::{Duration, Instant};
+
+fn chat(speed: &mut ChatSpeed, start: Instant, messages: u64, over: Duration) {
+    for n in 0..messages {
+        let at = start + over.mul_f64(n as f64 / messages as f64);
+        speed.count("viewer", &format!("message {}", n), at);
+    }
+}
+
+#[test]
+fn fast_at_the_threshold_and_calm_below_half() {
+    let start = Instant::now();
+    let mut speed = ChatSpeed::default();
+    chat(&mut speed, start, 59, Duration::from_secs(60));
+    assert_eq!(speed.check(start + Duration::from_secs(60), 60), None);
+
+    speed.count("viewer", "one more", start + Duration::from_secs(60));
+    assert_eq!(
+        speed.check(start + Duration::from_secs(60), 60),
+        Some(SpeedChange::Fast { per_minute: 60 })
+    );
+    assert!(speed.is_fast());
+    assert_eq!(speed.check(start + Duration::from_secs(61), 60), None);
+
+    // Quiet, but not for long enough yet
+    assert_eq!(speed.check(start + Duration::from_secs(150), 60), None);
+    assert_eq!(
+        speed.check(start + Duration::from_secs(180), 60),
+        Some(SpeedChange::Calm)
+    );
+    assert!(!speed.is_fast());
+}
+
+#[test]
+fn never_fast_when_off() {
+    let start = Instant::now();
+    let mut speed = ChatSpeed::default();
+    chat(&mut speed, start, 500, Duration::from_secs(10));
+    assert_eq!(speed.check(start + Duration::from_secs(10), 0), None);
+    assert_eq!(speed.per_minute(start + Duration::from_secs(10)), 500);
+    assert_eq!(speed.per_minute(start + Duration::from_secs(100)), 0);
+}
+
+#[test]
+fn summarizes_the_latest_messages_while_fast() {
+    let start = Instant::now();
+    let mut speed = ChatSpeed::default();
+    assert_eq!(speed.summary_due(start), None);
+    chat(&mut speed, start, 100, Duration::from_secs(30));
+    speed.check(start + Duration::from_secs(30), 50);
+
+    let messages = speed.summary_due(start + Duration::from_secs(30)).unwrap();
+    assert_eq!(messages.len(), 30);
+    assert_eq!(messages.last().unwrap(), "viewer: message 99");
+    assert_eq!(speed.summary_due(start + Duration::from_secs(60)), None);
+    assert!(speed
+        .summary_due(start + Duration::from_secs(210))
+        .is_some());
+}
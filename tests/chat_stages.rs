@@ -0,0 +1,249 @@
+mod common;
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use choui_the_no_gui_chatbot::audio::Sound;
+use choui_the_no_gui_chatbot::auto_replies::AutoReply;
+use choui_the_no_gui_chatbot::chat_commands::ChatCommand;
+use choui_the_no_gui_chatbot::chat_stages::{
+    Ai, Chat, ChatContext, Commands, Display, SpamFilter, Speech, Triggers,
+};
+use choui_the_no_gui_chatbot::chatlog::Record;
+use choui_the_no_gui_chatbot::config::{Config, VolumeChannel};
+use choui_the_no_gui_chatbot::pipeline::{Flow, Pipeline, Stage};
+use choui_the_no_gui_chatbot::shield::Trigger;
+use choui_the_no_gui_chatbot::state::{App, AppEvent, Badge, ChatMessage, Tab};
+use choui_the_no_gui_chatbot::tts::SpeechKind;
+use tempfile::TempDir;
+
+// Keeps what the stages asked for instead of doing it
+struct Fake {
+    app: App,
+    // Where the App's config says its files go
+    _dir: TempDir,
+    // Chat log messages, as (id, text)
+    logged: RefCell<Vec<(String, String)>>,
+    spoken: RefCell<Vec<(SpeechKind, String)>>,
+    removed: Vec<String>,
+    permitted: bool,
+    // The commands run, and whether the bot has them
+    commands: Vec<String>,
+    has_commands: bool,
+    asked: Vec<String>,
+    prompt: Option<String>,
+}
+
+impl ChatContext for Fake {
+    fn app(&self) -> &App {
+        &self.app
+    }
+
+    fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    fn send(&self, _event: AppEvent) {}
+
+    fn record(&self, record: Record) {
+        if let Record::Message {
+            message_id, text, ..
+        } = record
+        {
+            self.logged.borrow_mut().push((message_id, text));
+        }
+    }
+
+    fn play(&self, _sound: Sound, _channel: VolumeChannel) {}
+
+    fn speak(&self, kind: SpeechKind, _user: &str, text: &str) {
+        self.spoken.borrow_mut().push((kind, text.to_string()));
+    }
+
+    fn fetch_emote(&self, _name: &str, _id: &str) {}
+
+    fn raise_shield(&mut self, _trigger: Trigger) {}
+
+    fn permitted(&mut self, _user: &str) -> bool {
+        self.permitted
+    }
+
+    fn remove_link(&mut self, _message: &ChatMessage, link: &str) {
+        self.removed.push(link.to_string());
+    }
+
+    fn run_plugins(&mut self, _message: &ChatMessage) -> bool {
+        false
+    }
+
+    fn count_message(&mut self, _user: &str) {}
+
+    fn opted_out(&self, _user: &str) -> bool {
+        false
+    }
+
+    fn translate(&self, _message: &ChatMessage, _language: &'static str) {}
+
+    fn run_command(&mut self, _message: &ChatMessage, command: ChatCommand) -> bool {
+        self.commands.push(command.name);
+        self.has_commands
+    }
+
+    fn auto_reply(&mut self, reply: &AutoReply, _user: &str) -> Option<String> {
+        Some(reply.reply.clone())
+    }
+
+    fn ask_ai(&mut self, _user: &str, prompt: String) {
+        self.asked.push(prompt);
+    }
+
+    fn prompt(&mut self) -> &mut Option<String> {
+        &mut self.prompt
+    }
+}
+
+fn fake(change: impl FnOnce(&mut Config)) -> Fake {
+    let (mut config, dir) = common::config("http://127.0.0.1:9");
+    change(&mut config);
+    let mut app = App::new(Arc::new(config), "choui_bot".to_string());
+    app.tts_enabled = true;
+    Fake {
+        app,
+        _dir: dir,
+        logged: RefCell::default(),
+        spoken: RefCell::default(),
+        removed: Vec::new(),
+        permitted: false,
+        commands: Vec::new(),
+        has_commands: true,
+        asked: Vec::new(),
+        prompt: None,
+    }
+}
+
+fn message(user: &str, text: &str) -> ChatMessage {
+    ChatMessage {
+        id: format!("{}-{}", user, text.len()),
+        user: user.to_string(),
+        display_name: user.to_string(),
+        text: text.to_string(),
+        color: None,
+        badges: Vec::new(),
+        fragments: Vec::new(),
+        timestamp: jiff::Timestamp::now(),
+    }
+}
+
+fn moderator(text: &str) -> ChatMessage {
+    ChatMessage {
+        badges: vec![Badge {
+            set_id: "moderator".to_string(),
+            id: "1".to_string(),
+            info: String::new(),
+        }],
+        ..message("amod", text)
+    }
+}
+
+#[test]
+fn display_logs_and_shows_the_message() {
+    let mut cx = fake(|_| {});
+    let message = message("viewer", "hello there");
+    assert_eq!(Display.run(&mut cx, &message), Flow::Next);
+
+    let logged = cx.logged.borrow().clone();
+    assert_eq!(logged, [(message.id.clone(), "hello there".to_string())]);
+    let shown = cx.app.tab_lines(Tab::Chat).back().unwrap();
+    assert_eq!(shown.text, "hello there");
+    assert_eq!(cx.app.chatters["viewer"].message_count, 1);
+}
+
+#[test]
+fn spam_filter_removes_links_without_a_permit() {
+    let mut cx = fake(|config| {
+        config.links.enabled = true;
+        config.links.allowed_domains = vec!["clips.twitch.tv".to_string()];
+    });
+    let link = message("viewer", "free followers at https://spam.example/now");
+    assert_eq!(SpamFilter.run(&mut cx, &link), Flow::Done);
+    assert_eq!(cx.removed, ["https://spam.example/now"]);
+
+    // Allowed domains, mods and permitted viewers keep theirs
+    let clip = message("viewer", "https://clips.twitch.tv/SomeClip");
+    assert_eq!(SpamFilter.run(&mut cx, &clip), Flow::Next);
+    assert_eq!(SpamFilter.run(&mut cx, &moderator(&link.text)), Flow::Next);
+    cx.permitted = true;
+    assert_eq!(SpamFilter.run(&mut cx, &link), Flow::Next);
+    assert_eq!(cx.removed.len(), 1);
+}
+
+#[test]
+fn commands_are_answered_instead_of_the_ai() {
+    let mut cx = fake(|config| {
+        config.auto_replies = vec![AutoReply::parse("what elo => Diamond 2").unwrap()];
+    });
+    assert_eq!(
+        Commands.run(&mut cx, &message("viewer", "!quote 3")),
+        Flow::Done
+    );
+    assert_eq!(cx.commands, ["quote"]);
+
+    // Commands the bot doesn't have are left to the AI triggers
+    cx.has_commands = false;
+    assert_eq!(
+        Commands.run(&mut cx, &message("viewer", "!other")),
+        Flow::Next
+    );
+
+    assert_eq!(
+        Commands.run(&mut cx, &message("viewer", "What ELO?")),
+        Flow::Done
+    );
+    assert_eq!(cx.app.outbox.waiting(), 1);
+    assert_eq!(
+        Commands.run(&mut cx, &message("viewer", "good game")),
+        Flow::Next
+    );
+}
+
+#[test]
+fn ai_answers_the_command_once_per_cooldown() {
+    let mut cx = fake(|config| {
+        config.ai_triggers.command = Some("!bot".to_string());
+        config.ai_triggers.cooldown = std::time::Duration::from_secs(60);
+    });
+    let mut pipeline = Pipeline::<Chat>::default()
+        .then(Triggers)
+        .then(Ai::default());
+
+    pipeline.run(&mut cx, &message("viewer", "!bot what's up"));
+    assert_eq!(cx.asked, ["User viewer: what's up"]);
+    assert_eq!(cx.prompt, None);
+
+    // Still cooling down, for everyone
+    pipeline.run(&mut cx, &message("other", "!bot and now?"));
+    assert_eq!(cx.asked.len(), 1);
+}
+
+#[test]
+fn speech_reads_chat_unless_the_viewer_lurks() {
+    let mut cx = fake(|_| {});
+    Speech.run(&mut cx, &message("viewer", "hi all"));
+    Speech.run(&mut cx, &message("choui_bot", "hello viewer"));
+    cx.app.lurkers.start("lurker");
+    Speech.run(&mut cx, &message("lurker", "brb"));
+
+    let spoken = cx.spoken.borrow().clone();
+    assert_eq!(
+        spoken,
+        [
+            (SpeechKind::Chat, "hi all".to_string()),
+            // The bot's own messages are its AI replies
+            (SpeechKind::Ai, "hello viewer".to_string()),
+        ]
+    );
+
+    cx.app.tts_enabled = false;
+    Speech.run(&mut cx, &message("viewer", "anyone?"));
+    assert_eq!(cx.spoken.borrow().len(), 2);
+}
@@ -0,0 +1,54 @@
+use choui_the_no_gui_chatbot::chatlog::{ChatLog, Record};
+use choui_the_no_gui_chatbot::sqlite::{self, Connection};
+use choui_the_no_gui_chatbot::state::AppEvent;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+fn message(text: &str) -> Record {
+    Record::Message {
+        message_id: String::new(),
+        user: "viewer".to_string(),
+        text: text.to_string(),
+    }
+}
+
+#[test]
+fn a_failing_record_loses_only_itself() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chat.db");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let log = ChatLog::open(&path, "testchannel".to_string(), tx).unwrap();
+    let db = Connection::open(&path).unwrap();
+    db.execute_batch(
+        "CREATE TRIGGER no_poison BEFORE INSERT ON messages WHEN NEW.message = 'poison'
+         BEGIN SELECT RAISE(ABORT, 'poisoned'); END;",
+    )
+    .unwrap();
+
+    // Sent together, so they're usually written as one batch
+    for text in ["first", "poison", "last"] {
+        log.record(message(text));
+    }
+    log.record(Record::Event {
+        kind: "join",
+        user: Some("viewer".to_string()),
+        detail: String::new(),
+    });
+    drop(log);
+    assert!(sqlite::wait_for_writers(Duration::from_secs(5)));
+
+    let messages: Vec<String> = db
+        .query("SELECT message FROM messages ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| row[0].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(messages, ["first", "last"]);
+    let events = db.query("SELECT kind FROM events", &[]).unwrap();
+    assert_eq!(events.len(), 1);
+    match rx.try_recv() {
+        Ok(AppEvent::Error(error)) => assert!(error.contains("poisoned"), "{}", error),
+        Ok(_) => panic!("Expected the write error"),
+        Err(e) => panic!("No write error: {}", e),
+    }
+}
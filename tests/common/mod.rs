@@ -0,0 +1,44 @@
+// What the integration tests share: a Config that points at a mock server
+// (wiremock's) and keeps everything the bot writes in a directory of the
+// test's own.
+
+#![allow(dead_code)]
+
+use choui_the_no_gui_chatbot::config::Config;
+use tempfile::TempDir;
+use wiremock::{MockServer, Request};
+
+/// Settings for a signed-in bot in channel 1000, with Twitch and Gemini at
+/// `url`. Everything the bot would write (the token, the database, emotes)
+/// goes to the returned directory, which is removed when it's dropped, so
+/// no choui.toml, keyring entry or token of the developer's is read, moved
+/// or overwritten.
+pub fn config(url: &str) -> (Config, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+    let mut config = Config::builder("test-client", "2000")
+        .channel("testchannel")
+        .oauth_token("test-token")
+        .setting("CHANNEL_USER_ID", "1000")
+        .setting("CHOUI_CONFIG", path("choui.toml"))
+        .setting("DATA_DIR", path("data"))
+        .setting("CACHE_DIR", path("cache"))
+        .setting("SECRET_STORE", "file")
+        .build()
+        .expect("test config");
+    config.endpoints.helix = format!("{}/helix", url);
+    config.endpoints.auth = format!("{}/oauth2", url);
+    config.endpoints.gemini = format!("{}/v1beta", url);
+    config.ollama_host = url.to_string();
+    (config, dir)
+}
+
+/// What the server was sent, oldest first.
+pub async fn requests(server: &MockServer) -> Vec<Request> {
+    server.received_requests().await.unwrap_or_default()
+}
+
+/// A request's JSON body.
+pub fn json(request: &Request) -> serde_json::Value {
+    request.body_json().expect("JSON request body")
+}
@@ -0,0 +1,134 @@
+use choui_the_no_gui_chatbot::config_file::{ConfigFile, Value};
+use choui_the_no_gui_chatbot::{Config, Endpoints, LlmProvider};
+
+#[test]
+fn builder_settings_win_over_the_environment() {
+    std::env::set_var("CHANNEL_NAME", "from_env");
+
+    let config = Config::builder("builder-client", "builder_bot")
+        .channel("from_builder")
+        .ollama("http://ollama.test:11434", "llama-test")
+        .setting("AI_COOLDOWN", "5")
+        .build()
+        .unwrap();
+
+    assert_eq!(config.client_id, "builder-client");
+    assert_eq!(config.bot_user_id, "builder_bot");
+    assert_eq!(config.channel_name.as_deref(), Some("from_builder"));
+    assert!(matches!(config.llm_provider, LlmProvider::Ollama));
+    assert_eq!(config.ollama_host, "http://ollama.test:11434");
+    assert_eq!(config.ollama_model, "llama-test");
+
+    // Only while building
+    std::env::set_var("BOT_USER_ID", "env_bot");
+    std::env::set_var("CLIENT_ID", "env-client");
+    let config = Config::from_env().unwrap();
+    assert_eq!(config.bot_user_id, "env_bot");
+    assert_eq!(config.channel_name.as_deref(), Some("from_env"));
+}
+
+#[test]
+fn builder_takes_a_token_and_endpoints() {
+    let endpoints = Endpoints {
+        helix: "http://mock.test/helix".to_string(),
+        ..Endpoints::default()
+    };
+
+    let config = Config::builder("client", "bot")
+        .oauth_token("token")
+        .endpoints(endpoints.clone())
+        .build()
+        .unwrap();
+
+    assert_eq!(config.oauth_token.as_deref(), Some("token"));
+    assert_eq!(config.endpoints, endpoints);
+}
+
+#[test]
+fn builder_checks_the_settings() {
+    let result = Config::builder("client", "bot")
+        .setting("LOW_POWER", "sometimes")
+        .build();
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn flags_are_on_off_or_an_error() {
+    let flag = |name: &str, value: &str| {
+        Config::builder("client", "bot")
+            .setting(name, value)
+            .build()
+    };
+
+    assert!(!flag("HIDE_BOTS", "Off").unwrap().hide_bots);
+    assert!(flag("HIDE_BOTS", " YES ").unwrap().hide_bots);
+    assert!(flag("SHIELD", "1").unwrap().shield.enabled);
+    // Unset means the default
+    assert!(flag("HIDE_BOTS", "").unwrap().hide_bots);
+    for name in ["HIDE_BOTS", "SHIELD", "MQTT_ENABLED", "TIPS_READ"] {
+        let error = flag(name, "ture").unwrap_err();
+        assert!(format!("{:#}", error).contains("ture"), "{:#}", error);
+    }
+}
+
+#[test]
+fn reads_the_example_file() {
+    let file = ConfigFile::parse(include_str!("../choui.toml.example")).unwrap();
+
+    assert_eq!(
+        file.get_int("overlay.chat_window", "width").unwrap(),
+        Some(400)
+    );
+    assert_eq!(
+        file.get_bool("overlay.alerts_window", "enabled").unwrap(),
+        Some(true)
+    );
+    assert_eq!(
+        file.get_str("goal", "kind").unwrap().as_deref(),
+        Some("follow")
+    );
+}
+
+#[test]
+fn reads_all_of_toml_the_settings_use() {
+    let file = ConfigFile::parse(
+        r#"
+top = 'literal \n'
+
+[chat]
+blocked = [
+    "one",   # first
+    'two',
+]
+text = """
+multi"""
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        file.get("", "top"),
+        Some(&Value::Str("literal \\n".to_string()))
+    );
+    assert_eq!(
+        file.get_str_list("chat", "blocked").unwrap(),
+        Some(vec!["one".to_string(), "two".to_string()])
+    );
+    assert_eq!(
+        file.get_str("chat", "text").unwrap().as_deref(),
+        Some("multi")
+    );
+}
+
+#[test]
+fn rejects_what_the_settings_cant_use() {
+    for text in [
+        "[[alerts]]\nkind = \"follow\"",
+        "[goal]\nends = 2026-10-15",
+        "[chat]\nblocked = [\"one\"",
+        "[chat]\nkey = \"unterminated",
+    ] {
+        assert!(ConfigFile::parse(text).is_err(), "{}", text);
+    }
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use choui_the_no_gui_chatbot::diagnostics::snapshot;
+use choui_the_no_gui_chatbot::state::App;
+use std::sync::Arc;
+
+#[test]
+fn snapshot_has_the_queues_and_no_secrets() {
+    std::env::set_var("GEMINI_API_KEY", "super-secret-key");
+    let (config, _dir) = common::config("http://127.0.0.1:9");
+    let mut app = App::new(Arc::new(config), "bot".to_string());
+    app.send_failed("hello chat".to_string(), "401");
+
+    let json = snapshot(&app, &[("tts", 3)]);
+
+    assert_eq!(json["bot_login"], "bot");
+    assert_eq!(json["queues"]["tts"], 3);
+    assert_eq!(json["queues"]["unsent"], 1);
+    assert_eq!(json["unsent"][0], "hello chat");
+    assert_eq!(json["settings"]["GEMINI_API_KEY"], "(set)");
+    assert!(!json.to_string().contains("super-secret-key"));
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use choui_the_no_gui_chatbot::{emotes, state::EMOJIS, AppEvent};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn loads_in_picker_order_and_reports_progress() {
+    // TwitchHypeTrain isn't bundled and its download fails
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/helix/chat/emotes/global"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{
+                "name": "TwitchHypeTrain",
+                "images": { "url_1x": format!("{}/missing.png", server.uri()) }
+            }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/missing.png"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+    let cache = config.cache_dir.join("emotes");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    emotes::load(reqwest::Client::new(), Arc::new(config), tx).await;
+
+    let mut names = Vec::new();
+    let mut progress = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        match event {
+            AppEvent::EmoteImage(name, _) => names.push(name),
+            AppEvent::EmotesLoading { loaded, total } => progress.push((loaded, total)),
+            _ => {}
+        }
+    }
+
+    let total = EMOJIS.len();
+    assert_eq!(progress.first(), Some(&(0, total)));
+    assert_eq!(progress.last(), Some(&(total, total)));
+    assert!(progress.windows(2).all(|pair| pair[1].0 == pair[0].0 + 1));
+
+    // The bundled ones, as the picker lists them
+    let bundled: Vec<&str> = EMOJIS
+        .iter()
+        .copied()
+        .filter(|name| std::path::Path::new(&format!("assets/emotes/{}.png", name)).exists())
+        .collect();
+    assert_eq!(names, bundled);
+    assert!(!cache.join("TwitchHypeTrain.png").exists());
+}
+
+#[tokio::test]
+async fn encodes_off_the_event_loop_and_caches_per_protocol() {
+    use choui_the_no_gui_chatbot::encoder::{EmoteSize, Encoder, ProtocolCache};
+    use ratatui_image::picker::{Picker, ProtocolType};
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let encoder = Encoder::start(Picker::new((8, 16)), tx);
+    let image = Arc::new(image::DynamicImage::new_rgba8(28, 28));
+    encoder.encode(
+        ProtocolType::Halfblocks,
+        EmoteSize::Picker,
+        "Kappa".to_string(),
+        image.clone(),
+    );
+    encoder.encode(
+        ProtocolType::Halfblocks,
+        EmoteSize::Inline,
+        "Kappa".to_string(),
+        image,
+    );
+
+    let mut cache = ProtocolCache::default();
+    for size in [EmoteSize::Picker, EmoteSize::Inline] {
+        let Some(AppEvent::EmoteEncoded {
+            name,
+            size: encoded_size,
+            protocol,
+            result,
+        }) = rx.recv().await
+        else {
+            panic!("expected an encoded emote");
+        };
+        assert_eq!((name.as_str(), encoded_size), ("Kappa", size));
+        cache.insert(protocol, size, name, result.unwrap());
+    }
+
+    assert_eq!(cache.len(ProtocolType::Halfblocks), 2);
+    assert_eq!(cache.len(ProtocolType::Sixel), 0);
+    assert!(cache
+        .get(ProtocolType::Halfblocks, EmoteSize::Inline, "Kappa")
+        .is_some());
+    assert!(cache
+        .get(ProtocolType::Sixel, EmoteSize::Inline, "Kappa")
+        .is_none());
+}
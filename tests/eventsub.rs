@@ -0,0 +1,205 @@
+mod common;
+
+use choui_the_no_gui_chatbot::modlog::ModKind;
+use choui_the_no_gui_chatbot::state::{AppEvent, ConnectionState, Fragment, Service, StreamAlert};
+use choui_the_no_gui_chatbot::ws::{connect_eventsub_ws, parse_chat_notification};
+use futures_util::{SinkExt, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+const WELCOME: &str = r#"{"metadata":{"message_id":"1","message_type":"session_welcome","message_timestamp":"2024-05-01T20:00:00Z"},"payload":{"session":{"id":"session-1","status":"connected","keepalive_timeout_seconds":10}}}"#;
+
+const CHAT: &str = r##"{"metadata":{"message_id":"2","message_type":"notification","message_timestamp":"2024-05-01T20:14:05.123456789Z","subscription_type":"channel.chat.message","subscription_version":"1"},"payload":{"subscription":{"id":"sub-1","type":"channel.chat.message"},"event":{"broadcaster_user_id":"1000","chatter_user_id":"3000","chatter_user_login":"viewer","chatter_user_name":"Viewer","message_id":"msg-1","message":{"text":"hi @bot Kappa cheer100","fragments":[{"type":"text","text":"hi ","cheermote":null,"emote":null,"mention":null},{"type":"mention","text":"@bot","cheermote":null,"emote":null,"mention":{"user_id":"2000","user_login":"bot","user_name":"Bot"}},{"type":"text","text":" ","cheermote":null,"emote":null,"mention":null},{"type":"emote","text":"Kappa","cheermote":null,"emote":{"id":"25","emote_set_id":"0"},"mention":null},{"type":"text","text":" ","cheermote":null,"emote":null,"mention":null},{"type":"cheermote","text":"cheer100","cheermote":{"prefix":"cheer","bits":100,"tier":100},"emote":null,"mention":null}]},"color":"#FF0000","badges":[{"set_id":"moderator","id":"1","info":""},{"set_id":"subscriber","id":"12","info":"14"}],"message_type":"text"}}}"##;
+
+const FOLLOW: &str = r#"{"metadata":{"message_id":"3","message_type":"notification","message_timestamp":"2024-05-01T20:15:00Z","subscription_type":"channel.follow","subscription_version":"2"},"payload":{"subscription":{"id":"sub-2","type":"channel.follow"},"event":{"user_id":"4000","user_login":"newfollower","user_name":"NewFollower","broadcaster_user_id":"1000","followed_at":"2024-05-01T20:15:00Z"}}}"#;
+
+const AUTOMOD_HOLD: &str = r#"{"metadata":{"message_id":"4","message_type":"notification","message_timestamp":"2024-05-01T20:16:00Z","subscription_type":"automod.message.hold","subscription_version":"1"},"payload":{"subscription":{"id":"sub-3","type":"automod.message.hold"},"event":{"broadcaster_user_id":"1000","user_id":"5000","user_login":"rude","user_name":"Rude","message_id":"held-1","message":{"text":"some bad words","fragments":[]},"category":"swearing","level":3,"held_at":"2024-05-01T20:16:00Z"}}}"#;
+
+const AUTOMOD_UPDATE: &str = r#"{"metadata":{"message_id":"5","message_type":"notification","message_timestamp":"2024-05-01T20:17:00Z","subscription_type":"automod.message.update","subscription_version":"1"},"payload":{"subscription":{"id":"sub-4","type":"automod.message.update"},"event":{"broadcaster_user_id":"1000","user_id":"5000","user_login":"rude","user_name":"Rude","moderator_user_id":"6000","moderator_user_login":"somemod","moderator_user_name":"SomeMod","message_id":"held-1","message":{"text":"some bad words","fragments":[]},"category":"swearing","level":3,"status":"Denied","held_at":"2024-05-01T20:16:00Z"}}}"#;
+
+// A fake EventSub server for one connection: it sends `messages` and then
+// waits for the bot to close
+async fn fake_eventsub(messages: Vec<&'static str>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        for message in messages {
+            ws.send(Message::text(message)).await.unwrap();
+        }
+        while let Some(Ok(message)) = ws.next().await {
+            if message.is_close() {
+                break;
+            }
+        }
+    });
+    url
+}
+
+// The next event that isn't a protocol dump
+async fn next_event(events: &mut mpsc::UnboundedReceiver<AppEvent>) -> AppEvent {
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("an event in time")
+            .expect("the connection to be running");
+        if !matches!(event, AppEvent::Debug(_)) {
+            return event;
+        }
+    }
+}
+
+#[tokio::test]
+async fn delivers_chat_and_alerts_until_shut_down() {
+    let (mut config, _dir) = common::config("http://127.0.0.1:9");
+    config.endpoints.eventsub = fake_eventsub(vec![WELCOME, CHAT, FOLLOW]).await;
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let shutdown = CancellationToken::new();
+
+    let (session_id, handle) = connect_eventsub_ws(
+        reqwest::Client::new(),
+        Arc::new(config),
+        tx,
+        shutdown.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(session_id, "session-1");
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        AppEvent::Connection(Service::EventSub, ConnectionState::Connected)
+    ));
+
+    let AppEvent::ChatMessage(message) = next_event(&mut events).await else {
+        panic!("expected a chat message");
+    };
+    assert_eq!(message.id, "msg-1");
+    assert_eq!(message.user, "viewer");
+    assert_eq!(message.display_name, "Viewer");
+    assert_eq!(message.text, "hi @bot Kappa cheer100");
+    assert_eq!(message.color.as_deref(), Some("#FF0000"));
+    assert_eq!(
+        message.timestamp.to_string(),
+        "2024-05-01T20:14:05.123456789Z"
+    );
+    assert_eq!(message.badges[1].set_id, "subscriber");
+    assert_eq!(message.badges[1].info, "14");
+    assert_eq!(
+        message.role(),
+        choui_the_no_gui_chatbot::state::Role::Moderator
+    );
+    assert_eq!(
+        message.fragments,
+        vec![
+            Fragment::Text("hi ".to_string()),
+            Fragment::Mention {
+                text: "@bot".to_string(),
+                user: "bot".to_string()
+            },
+            Fragment::Text(" ".to_string()),
+            Fragment::Emote {
+                name: "Kappa".to_string(),
+                id: "25".to_string()
+            },
+            Fragment::Text(" ".to_string()),
+            Fragment::Cheermote {
+                text: "cheer100".to_string(),
+                prefix: "cheer".to_string(),
+                bits: 100
+            },
+        ]
+    );
+    assert_eq!(message.emotes().collect::<Vec<_>>(), vec![("Kappa", "25")]);
+
+    let AppEvent::Alert(StreamAlert::Follow { user }) = next_event(&mut events).await else {
+        panic!("expected a follow");
+    };
+    assert_eq!(user, "newfollower");
+
+    shutdown.cancel();
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("the connection to close")
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        next_event(&mut events).await,
+        AppEvent::Connection(Service::EventSub, ConnectionState::Disconnected)
+    ));
+}
+
+#[tokio::test]
+async fn delivers_automod_holds_and_their_outcome() {
+    let (mut config, _dir) = common::config("http://127.0.0.1:9");
+    config.endpoints.eventsub = fake_eventsub(vec![WELCOME, AUTOMOD_HOLD, AUTOMOD_UPDATE]).await;
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let shutdown = CancellationToken::new();
+    let (_, handle) = connect_eventsub_ws(
+        reqwest::Client::new(),
+        Arc::new(config),
+        tx,
+        shutdown.clone(),
+    )
+    .await
+    .unwrap();
+    next_event(&mut events).await;
+
+    // The hold goes to the mod log, then the queue
+    let AppEvent::Moderation(logged) = next_event(&mut events).await else {
+        panic!("expected the hold for the mod log");
+    };
+    assert_eq!(logged.kind, ModKind::AutoModHold);
+    assert_eq!(logged.detail, "swearing (level 3): some bad words");
+    let AppEvent::AutoModHeld(held) = next_event(&mut events).await else {
+        panic!("expected a held message");
+    };
+    assert_eq!(held.id, "held-1");
+    assert_eq!(held.user, "rude");
+
+    let AppEvent::AutoModResolved {
+        id,
+        status,
+        moderator,
+    } = next_event(&mut events).await
+    else {
+        panic!("expected the held message's outcome");
+    };
+    assert_eq!(id, "held-1");
+    assert_eq!(status, "denied");
+    assert_eq!(moderator.as_deref(), Some("somemod"));
+
+    shutdown.cancel();
+    let _ = tokio::time::timeout(Duration::from_secs(5), handle).await;
+}
+
+#[tokio::test]
+async fn no_welcome_is_an_error() {
+    let (mut config, _dir) = common::config("http://127.0.0.1:9");
+    config.endpoints.eventsub = fake_eventsub(Vec::new()).await;
+    let (tx, _events) = mpsc::unbounded_channel();
+
+    let result = connect_eventsub_ws(
+        reqwest::Client::new(),
+        Arc::new(config),
+        tx,
+        CancellationToken::new(),
+    )
+    .await;
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn parses_a_chat_notification_on_its_own() {
+    let message = parse_chat_notification(CHAT).unwrap();
+    assert_eq!(message.user, "viewer");
+    assert_eq!(message.text, "hi @bot Kappa cheer100");
+    assert_eq!(message.fragments.len(), 6);
+    assert!(parse_chat_notification(WELCOME).is_err());
+}
@@ -0,0 +1,57 @@
+use choui_the_no_gui_chatbot::follows::{self, FollowSettings, FollowThanks};
+use std::time::{Duration, Instant};
+
+fn settings() -> FollowSettings {
+    FollowSettings {
+        thank: true,
+        window: Duration::from_secs(20),
+        cooldown: Duration::from_secs(120),
+    }
+}
+
+#[test]
+fn thanks_a_batch_after_the_window() {
+    let (settings, start) = (settings(), Instant::now());
+    let mut thanks = FollowThanks::default();
+    assert_eq!(thanks.due(start, &settings), None);
+
+    thanks.add("a", start);
+    thanks.add("b", start + Duration::from_secs(5));
+    thanks.add("A", start + Duration::from_secs(6));
+    assert_eq!(thanks.due(start + Duration::from_secs(19), &settings), None);
+    let batch = thanks.due(start + Duration::from_secs(20), &settings);
+    assert_eq!(batch.unwrap(), ["a", "b"]);
+    assert_eq!(thanks.due(start + Duration::from_secs(40), &settings), None);
+}
+
+#[test]
+fn follow_storms_wait_for_the_cooldown() {
+    let (settings, start) = (settings(), Instant::now());
+    let mut thanks = FollowThanks::default();
+    thanks.add("a", start);
+    assert!(thanks
+        .due(start + Duration::from_secs(20), &settings)
+        .is_some());
+
+    for n in 0..50 {
+        thanks.add(&format!("user{}", n), start + Duration::from_secs(30 + n));
+    }
+    // The window is long over, but the last thank-you was too recent
+    assert_eq!(
+        thanks.due(start + Duration::from_secs(100), &settings),
+        None
+    );
+    let batch = thanks
+        .due(start + Duration::from_secs(140), &settings)
+        .unwrap();
+    assert_eq!(batch.len(), 50);
+}
+
+#[test]
+fn lists_the_names() {
+    let users = |n: usize| (1..=n).map(|i| format!("u{}", i)).collect::<Vec<_>>();
+    assert_eq!(follows::names(&users(1)), "@u1");
+    assert_eq!(follows::names(&users(2)), "@u1 and @u2");
+    assert_eq!(follows::names(&users(3)), "@u1, @u2 and @u3");
+    assert!(follows::names(&users(13)).ends_with("@u10 and 3 more"));
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use choui_the_no_gui_chatbot::irc::{self, Command, Line, Members, RateLimit};
+use choui_the_no_gui_chatbot::modlog::ModKind;
+use choui_the_no_gui_chatbot::state::{AppEvent, ConnectionState};
+use futures_util::{SinkExt, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+#[test]
+fn parses_tags_source_and_params() {
+    let line = Line::parse(
+        r"@login=viewer;target-msg-id=abc;note=two\swords\:\\ :tmi.twitch.tv CLEARMSG #channel :the bad message",
+    )
+    .unwrap();
+    assert_eq!(line.command, "CLEARMSG");
+    assert_eq!(line.source.as_deref(), Some("tmi.twitch.tv"));
+    assert_eq!(line.params, ["#channel", "the bad message"]);
+    assert_eq!(line.tag("note"), Some(r"two words;\"));
+    assert_eq!(line.nick(), None);
+
+    let join = Line::parse(":viewer!viewer@viewer.tmi.twitch.tv JOIN #channel").unwrap();
+    assert_eq!(join.nick(), Some("viewer"));
+    assert_eq!(
+        Command::from_line(&join),
+        Command::Join("viewer".to_string())
+    );
+    assert!(Line::parse("").is_none());
+}
+
+#[test]
+fn reads_commands() {
+    let command = |text| Command::from_line(&Line::parse(text).unwrap());
+
+    assert_eq!(
+        command("PING :tmi.twitch.tv"),
+        Command::Ping("tmi.twitch.tv".to_string())
+    );
+    assert_eq!(command(":tmi.twitch.tv RECONNECT"), Command::Reconnect);
+    let Command::Moderation(timeout) =
+        command("@ban-duration=600;room-id=1 :tmi.twitch.tv CLEARCHAT #channel :troll")
+    else {
+        panic!("a timeout");
+    };
+    assert_eq!(timeout.kind, ModKind::Timeout);
+    assert_eq!(timeout.user.as_deref(), Some("troll"));
+    assert_eq!(timeout.detail, "600s");
+    let Command::Moderation(clear) = command("@room-id=1 :tmi.twitch.tv CLEARCHAT #channel") else {
+        panic!("a clear");
+    };
+    assert_eq!(clear.kind, ModKind::Clear);
+    assert_eq!(
+        command(":tmi.twitch.tv 001 bot :Welcome, GLHF!"),
+        Command::Other
+    );
+}
+
+#[test]
+fn members_and_rate_limit() {
+    let mut members = Members::default();
+    assert!(members.join("Viewer"));
+    assert!(!members.join("viewer"));
+    members.part("VIEWER");
+    assert!(members.is_empty());
+
+    let mut limit = RateLimit::new(2, Duration::from_secs(30));
+    assert_eq!(limit.wait(), Duration::ZERO);
+    limit.record();
+    limit.record();
+    assert!(limit.wait() > Duration::from_secs(29));
+}
+
+// A fake Twitch IRC server: the first connection sees two joins (one of them
+// twice) and is asked to reconnect, the second one gets the JOINs Twitch
+// repeats after a reconnect plus one new one, and a raw line from the bot.
+async fn fake_irc() -> (String, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (received_tx, received) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let batches = [
+            ":a!a@a.tmi.twitch.tv JOIN #testchannel\r\n:b!b@b.tmi.twitch.tv JOIN #testchannel\r\n:a!a@a.tmi.twitch.tv JOIN #testchannel\r\n:tmi.twitch.tv RECONNECT",
+            ":a!a@a.tmi.twitch.tv JOIN #testchannel\r\n:c!c@c.tmi.twitch.tv JOIN #testchannel\r\nPING :tmi.twitch.tv",
+        ];
+        for batch in batches {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            ws.send(Message::text(batch)).await.unwrap();
+            while let Some(Ok(message)) = ws.next().await {
+                match message {
+                    Message::Text(text) => {
+                        let _ = received_tx.send(text.to_string());
+                    }
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+        }
+    });
+    (url, received)
+}
+
+#[tokio::test]
+async fn reconnects_without_repeating_joins() {
+    let (url, mut received) = fake_irc().await;
+    let (mut config, _dir) = common::config("http://127.0.0.1:9");
+    config.endpoints.irc = url;
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let shutdown = CancellationToken::new();
+
+    let (task, lines) = irc::connect(Arc::new(config), tx, shutdown.clone())
+        .await
+        .unwrap();
+    lines
+        .send("PRIVMSG #testchannel :/me waves".to_string())
+        .unwrap();
+
+    let mut joined = Vec::new();
+    let mut reconnected = false;
+    while joined.len() < 3 {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("an event in time")
+            .unwrap();
+        match event {
+            AppEvent::UserJoined(user) => joined.push(user),
+            AppEvent::Connection(_, ConnectionState::Connecting) => reconnected = true,
+            _ => {}
+        }
+    }
+    assert_eq!(joined, ["a", "b", "c"]);
+    assert!(reconnected);
+
+    // The queued line goes out on whichever connection is up at the time
+    let expected = [
+        "PASS oauth:test-token",
+        "JOIN #testchannel",
+        "PRIVMSG #testchannel :/me waves",
+        "PONG :tmi.twitch.tv",
+    ];
+    let mut sent = Vec::new();
+    while !expected.iter().all(|line| sent.contains(&line.to_string())) {
+        let line = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap_or_else(|_| panic!("only got {:?}", sent))
+            .unwrap();
+        sent.push(line);
+    }
+
+    shutdown.cancel();
+    tokio::time::timeout(Duration::from_secs(5), task)
+        .await
+        .unwrap()
+        .unwrap();
+}
@@ -0,0 +1,76 @@
+use choui_the_no_gui_chatbot::lang::{self, detect};
+
+#[test]
+fn each_language_is_told_apart() {
+    let messages = [
+        ("en", "hello everyone, thanks for the stream tonight"),
+        ("es", "hola a todos, muchas gracias por el directo de hoy"),
+        (
+            "de",
+            "hallo zusammen, vielen Dank für den tollen Stream heute",
+        ),
+        ("fr", "bonjour tout le monde, merci beaucoup pour le live"),
+        ("pt", "olá pessoal, muito obrigado pela live de hoje"),
+        ("it", "ciao a tutti, grazie mille per la diretta di oggi"),
+        (
+            "nl",
+            "hallo allemaal, heel erg bedankt voor de stream vandaag",
+        ),
+        (
+            "pl",
+            "cześć wszystkim, bardzo dziękuję za dzisiejszy stream",
+        ),
+        ("ru", "всем привет, спасибо большое за сегодняшний стрим"),
+        ("uk", "всім привіт, дуже дякую за сьогоднішній стрім"),
+        ("ja", "みなさん、今日は配信ありがとうございます"),
+        ("ko", "여러분 안녕하세요, 오늘 방송 감사합니다"),
+        ("zh", "大家好，谢谢你今天的直播"),
+        ("ar", "مرحبا بالجميع، شكرا على البث اليوم"),
+        ("he", "שלום לכולם, תודה רבה על השידור היום"),
+        ("el", "γεια σας, ευχαριστώ πολύ για τη σημερινή μετάδοση"),
+        ("th", "สวัสดีทุกคน ขอบคุณสำหรับการสตรีมวันนี้"),
+        ("hi", "सभी को नमस्ते, आज की स्ट्रीम के लिए धन्यवाद"),
+    ];
+    for (code, message) in messages {
+        assert_eq!(detect(message), Some(code), "{}", message);
+        assert!(lang::is_supported(code));
+    }
+    assert_eq!(lang::codes().count(), messages.len());
+}
+
+#[test]
+fn short_chat_lines() {
+    for message in ["lol", "gg", "KEKW", "o7", "!!!", ""] {
+        assert_eq!(detect(message), None, "{}", message);
+    }
+    // Short, but only one language says it like that
+    assert_eq!(detect("привет всем"), Some("ru"));
+    assert_eq!(detect("привіт усім"), Some("uk"));
+}
+
+#[test]
+fn cyrillic_is_not_all_russian() {
+    assert_eq!(detect("Привіт! Як справи у всіх сьогодні?"), Some("uk"));
+    assert_eq!(detect("Привет! Как у всех дела сегодня?"), Some("ru"));
+    // Russian and Ukrainian spell this almost alike
+    assert_eq!(detect("спасибо за стрим"), None);
+}
+
+#[test]
+fn mixed_scripts_go_by_most_of_the_text() {
+    assert_eq!(
+        detect("Привет, as I said earlier the stream was great fun today"),
+        Some("en")
+    );
+    // Kanji in kana text is still Japanese
+    assert_eq!(detect("今日は配信ありがとうございます"), Some("ja"));
+    assert_eq!(detect("hello all 你好"), None);
+}
+
+#[test]
+fn unknown_codes_are_unsupported() {
+    assert!(!lang::is_supported("sv"));
+    assert!(!lang::is_supported("EN"));
+    assert_eq!(lang::name("uk"), Some("Ukrainian"));
+    assert_eq!(lang::name("xx"), None);
+}
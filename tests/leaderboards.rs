@@ -0,0 +1,97 @@
+use choui_the_no_gui_chatbot::leaderboards::{Board, Leaderboards, Scope};
+use choui_the_no_gui_chatbot::sqlite;
+use choui_the_no_gui_chatbot::state::StreamAlert;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+fn cheer(user: Option<&str>, bits: u32) -> StreamAlert {
+    StreamAlert::Cheer {
+        user: user.map(String::from),
+        bits,
+        message: String::new(),
+    }
+}
+
+fn gift(user: &str, count: u32) -> StreamAlert {
+    StreamAlert::GiftSub {
+        user: Some(user.to_string()),
+        count,
+        tier: "1000".to_string(),
+    }
+}
+
+fn leaders(leaderboards: &Leaderboards, board: Board, scope: Scope) -> Vec<(String, i64)> {
+    leaderboards.standings(board, scope).leaders
+}
+
+#[test]
+fn ranks_per_stream_and_all_time_across_restarts() {
+    let path = std::env::temp_dir().join(format!("choui-leaderboards-{}.db", std::process::id()));
+    let (tx, _rx) = mpsc::unbounded_channel();
+
+    let mut leaderboards = Leaderboards::open(&path, tx.clone()).unwrap();
+    assert!(leaderboards.set_stream(Some("2024-05-01T18:00:00Z")));
+    assert_eq!(
+        leaderboards.record(&cheer(Some("Alice"), 500)),
+        Some(Board::Cheers)
+    );
+    assert_eq!(leaderboards.record(&cheer(None, 10000)), None);
+    assert_eq!(leaderboards.record(&gift("bob", 5)), Some(Board::Gifts));
+    assert_eq!(
+        leaderboards.record(&StreamAlert::Follow {
+            user: "carol".to_string()
+        }),
+        None
+    );
+    drop(leaderboards);
+    assert!(sqlite::wait_for_writers(Duration::from_secs(5)));
+
+    let mut leaderboards = Leaderboards::open(&path, tx).unwrap();
+    // Offline: nothing counts as this stream, but all time still does
+    assert_eq!(leaders(&leaderboards, Board::Cheers, Scope::Stream), []);
+    leaderboards.record(&cheer(Some("dave"), 100));
+    assert!(leaderboards.set_stream(Some("2024-05-02T18:00:00Z")));
+    assert!(!leaderboards.set_stream(Some("2024-05-02T18:00:00Z")));
+    leaderboards.record(&cheer(Some("dave"), 100));
+    leaderboards.record(&cheer(Some("alice"), 1));
+    assert_eq!(
+        leaders(&leaderboards, Board::Cheers, Scope::Stream),
+        [("dave".to_string(), 100), ("alice".to_string(), 1)]
+    );
+    assert_eq!(
+        leaders(&leaderboards, Board::Cheers, Scope::AllTime),
+        [("alice".to_string(), 501), ("dave".to_string(), 200)]
+    );
+    assert_eq!(
+        leaderboards.describe(Board::Gifts),
+        "Top gifters this stream: nobody yet | all time: 1. bob (5 subs)"
+    );
+
+    // Back to the first stream after a restart mid-stream
+    leaderboards.set_stream(Some("2024-05-01T18:00:00Z"));
+    assert_eq!(
+        leaders(&leaderboards, Board::Cheers, Scope::Stream),
+        [("alice".to_string(), 500)]
+    );
+    drop(leaderboards);
+    sqlite::wait_for_writers(Duration::from_secs(5));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn keeps_the_top_three() {
+    let path = std::env::temp_dir().join(format!("choui-top-{}.db", std::process::id()));
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let mut leaderboards = Leaderboards::open(&path, tx).unwrap();
+    for (user, bits) in [("a", 1), ("b", 50), ("c", 50), ("d", 10)] {
+        leaderboards.record(&cheer(Some(user), bits));
+    }
+    let standings = leaderboards.standings(Board::Cheers, Scope::AllTime);
+    assert_eq!(
+        standings.describe(),
+        "1. b (50 bits), 2. c (50 bits), 3. d (10 bits)"
+    );
+    drop(leaderboards);
+    sqlite::wait_for_writers(Duration::from_secs(5));
+    let _ = std::fs::remove_file(path);
+}
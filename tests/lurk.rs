@@ -0,0 +1,54 @@
+use choui_the_no_gui_chatbot::lurk::{self, Lurkers};
+use choui_the_no_gui_chatbot::sqlite;
+use choui_the_no_gui_chatbot::viewers::Viewers;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[test]
+fn lurks_until_unlurk() {
+    let mut lurkers = Lurkers::default();
+    assert!(lurkers.start("Viewer"));
+    assert!(!lurkers.start("viewer"));
+    assert!(lurkers.is_lurking("VIEWER"));
+    assert!(lurkers.stop("viewer").is_some());
+    assert!(!lurkers.is_lurking("viewer"));
+    assert_eq!(lurkers.stop("viewer"), None);
+
+    lurkers.start("a");
+    lurkers.start("b");
+    let mut stopped: Vec<String> = lurkers
+        .stop_all()
+        .into_iter()
+        .map(|(user, _)| user)
+        .collect();
+    stopped.sort();
+    assert_eq!(stopped, ["a", "b"]);
+}
+
+#[test]
+fn describes_lurk_time() {
+    assert_eq!(lurk::describe(Duration::from_secs(40)), "40s");
+    assert_eq!(lurk::describe(Duration::from_secs(12 * 60 + 5)), "12m");
+    assert_eq!(
+        lurk::describe(Duration::from_secs(2 * 3600 + 5 * 60)),
+        "2h 5m"
+    );
+}
+
+#[test]
+fn lurk_time_adds_up_across_restarts() {
+    let path = std::env::temp_dir().join(format!("choui-lurk-{}.db", std::process::id()));
+    let (tx, _rx) = mpsc::unbounded_channel();
+
+    let mut viewers = Viewers::open(&path, tx.clone()).unwrap();
+    viewers.add_watch_time("Viewer", Duration::from_secs(600));
+    drop(viewers);
+    assert!(sqlite::wait_for_writers(Duration::from_secs(5)));
+
+    let mut viewers = Viewers::open(&path, tx).unwrap();
+    let total = viewers.add_watch_time("viewer", Duration::from_secs(60));
+    assert_eq!(total, Duration::from_secs(660));
+    drop(viewers);
+    sqlite::wait_for_writers(Duration::from_secs(5));
+    let _ = std::fs::remove_file(path);
+}
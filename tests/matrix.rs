@@ -0,0 +1,127 @@
+mod common;
+
+use choui_the_no_gui_chatbot::api::ApiRequest;
+use choui_the_no_gui_chatbot::commands::SlashCommand;
+use choui_the_no_gui_chatbot::matrix::{self, MatrixSettings};
+use choui_the_no_gui_chatbot::{AppEvent, StreamAlert};
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use wiremock::http::Method;
+use wiremock::matchers::{header, method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn parses_admin_commands() {
+    let parse = |text| matrix::parse(text).map(|result| result.map_err(|e| e.to_string()));
+
+    assert!(parse("hello there").is_none());
+    assert!(matches!(parse("!say hi chat"), Some(Ok(ApiRequest::Say(text))) if text == "hi chat"));
+    assert!(matches!(
+        parse("!timeout @troll 60 spam"),
+        Some(Ok(ApiRequest::Moderate(SlashCommand::Timeout { user, seconds: 60, reason })))
+            if user == "troll" && reason == "spam"
+    ));
+    assert!(matches!(
+        parse("!timers off"),
+        Some(Ok(ApiRequest::Settings {
+            timers: Some(false),
+            ai_approval: None
+        }))
+    ));
+    assert!(parse("!tts louder").unwrap().is_err());
+    assert!(parse("!help").unwrap().unwrap_err().contains("!say"));
+}
+
+#[tokio::test]
+async fn mirrors_chat_and_runs_admin_commands() {
+    let server = MockServer::start().await;
+    let sync = |events| {
+        ResponseTemplate::new(200).set_body_json(json!({
+            "next_batch": "s1",
+            "rooms": { "join": { "!room:test": { "timeline": { "events": events } } } }
+        }))
+    };
+    // The first sync is only where to start; then two commands, one from
+    // someone who isn't an admin
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/v3/sync"))
+        .respond_with(sync(json!([])))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/v3/sync"))
+        .respond_with(sync(json!([
+            { "type": "m.room.message", "sender": "@stranger:test",
+              "content": { "msgtype": "m.text", "body": "!say nope" } },
+            { "type": "m.room.message", "sender": "@admin:test",
+              "content": { "msgtype": "m.text", "body": "!say hi from matrix" } },
+        ])))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/v3/sync"))
+        .respond_with(sync(json!([])))
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path_regex(
+            r"^/_matrix/client/v3/rooms/!room:test/send/m\.room\.message/",
+        ))
+        .and(header("authorization", "Bearer matrix-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$1" })))
+        .mount(&server)
+        .await;
+    let settings = MatrixSettings {
+        enabled: true,
+        homeserver: server.uri(),
+        token: "matrix-token".to_string(),
+        room: "!room:test".to_string(),
+        admins: vec!["@admin:test".to_string()],
+    };
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let (broadcast_tx, events) = broadcast::channel(16);
+    let (calls, mut requests) = mpsc::unbounded_channel();
+    tokio::spawn(matrix::run(
+        settings,
+        reqwest::Client::new(),
+        tx,
+        events,
+        calls,
+    ));
+
+    let call = tokio::time::timeout(Duration::from_secs(5), requests.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(&call.request, ApiRequest::Say(text) if text == "hi from matrix"));
+    let _ = call.reply.send(Ok(json!({ "ok": true })));
+
+    let _ = broadcast_tx.send(AppEvent::Alert(StreamAlert::Follow {
+        user: "newfriend".to_string(),
+    }));
+    let sent = || async {
+        common::requests(&server)
+            .await
+            .iter()
+            .filter(|request| request.method == Method::PUT)
+            .map(|request| common::json(request)["body"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    for _ in 0..100 {
+        if sent().await.len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let sent = sent().await;
+    assert!(sent.contains(&"Done".to_string()), "{:?}", sent);
+    assert!(
+        sent.iter().any(|body| body.contains("newfriend")),
+        "{:?}",
+        sent
+    );
+    assert!(requests.try_recv().is_err());
+}
@@ -0,0 +1,172 @@
+use choui_the_no_gui_chatbot::mqtt::{self, MqttSettings, Topics};
+use choui_the_no_gui_chatbot::twitch::StreamStats;
+use choui_the_no_gui_chatbot::{AppEvent, ChatMessage, Config, StreamAlert};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+
+fn chat(user: &str, text: &str) -> AppEvent {
+    AppEvent::ChatMessage(Arc::new(ChatMessage {
+        id: "1".to_string(),
+        user: user.to_string(),
+        display_name: user.to_uppercase(),
+        text: text.to_string(),
+        color: None,
+        badges: Vec::new(),
+        fragments: Vec::new(),
+        timestamp: jiff::Timestamp::now(),
+    }))
+}
+
+fn stats(live: bool) -> AppEvent {
+    AppEvent::StreamStats(live.then(|| StreamStats {
+        viewer_count: 12,
+        started_at: jiff::Timestamp::now(),
+        game_name: "Celeste".to_string(),
+    }))
+}
+
+#[test]
+fn picks_topics_and_payloads() {
+    let mut topics = Topics::new("home/stream", vec!["infobot".into(), "streamer".into()]);
+
+    let raid = AppEvent::Alert(StreamAlert::Raid {
+        from: "friend".to_string(),
+        from_id: "9".to_string(),
+        viewers: 30,
+    });
+    let (topic, payload) = topics.publication(&raid).unwrap();
+    assert_eq!(topic, "home/stream/raid");
+    assert_eq!(payload["user"], "friend");
+    assert_eq!(payload["viewers"], 30);
+
+    let (topic, payload) = topics.publication(&chat("fan", "hi @Streamer!")).unwrap();
+    assert_eq!(topic, "home/stream/mention");
+    assert_eq!(payload, json!({ "user": "FAN", "text": "hi @Streamer!" }));
+    assert!(topics
+        .publication(&chat("fan", "streamers are great"))
+        .is_none());
+    // The bot talking about itself isn't a mention
+    assert!(topics
+        .publication(&chat("infobot", "infobot is here"))
+        .is_none());
+
+    // Already live at startup isn't going live
+    assert!(topics.publication(&stats(true)).is_none());
+    assert!(topics.publication(&stats(true)).is_none());
+    let (topic, _) = topics.publication(&stats(false)).unwrap();
+    assert_eq!(topic, "home/stream/offline");
+    let (topic, payload) = topics.publication(&stats(true)).unwrap();
+    assert_eq!(topic, "home/stream/online");
+    assert_eq!(payload["game"], "Celeste");
+
+    assert!(topics.publication(&AppEvent::Info("hi".into())).is_none());
+}
+
+#[test]
+fn a_password_needs_a_username() {
+    let build = |username: Option<&str>| {
+        let mut builder = Config::builder("client", "bot")
+            .setting("MQTT_ENABLED", "true")
+            .setting("MQTT_PASSWORD", "secret");
+        if let Some(username) = username {
+            builder = builder.setting("MQTT_USERNAME", username);
+        }
+        builder.build()
+    };
+
+    let error = build(None).unwrap_err();
+    assert!(error.to_string().contains("MQTT_USERNAME"), "{}", error);
+    let mqtt = build(Some("home")).unwrap().mqtt;
+    assert_eq!(mqtt.broker, "localhost:1883");
+}
+
+// One MQTT packet: its first byte and its body
+async fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let kind = stream.read_u8().await.unwrap();
+    let (mut len, mut shift) = (0usize, 0);
+    loop {
+        let byte = stream.read_u8().await.unwrap();
+        len |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body).await.unwrap();
+    (kind, body)
+}
+
+fn settings(broker: String) -> MqttSettings {
+    MqttSettings {
+        enabled: true,
+        broker,
+        tls: false,
+        prefix: "choui".to_string(),
+        username: Some("home".to_string()),
+        password: Some("secret".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn publishes_alerts_to_the_broker() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker = listener.local_addr().unwrap().to_string();
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let (events_tx, events) = broadcast::channel(16);
+    tokio::spawn(mqtt::run(
+        settings(broker),
+        vec!["infobot".into()],
+        tx,
+        events,
+    ));
+
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let (kind, connect) = read_packet(&mut stream).await;
+    assert_eq!(kind, 0x10);
+    assert_eq!(&connect[..7], b"\x00\x04MQTT\x04");
+    // Clean session plus a username and password, which come last
+    assert_eq!(connect[7], 0xC2);
+    assert!(connect.ends_with(b"\x00\x04home\x00\x06secret"));
+    stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+    events_tx
+        .send(AppEvent::Alert(StreamAlert::Follow {
+            user: "newfriend".to_string(),
+        }))
+        .unwrap();
+    let (kind, publish) = tokio::time::timeout(Duration::from_secs(5), read_packet(&mut stream))
+        .await
+        .unwrap();
+    assert_eq!(kind, 0x30);
+    let topic_len = u16::from_be_bytes([publish[0], publish[1]]) as usize;
+    assert_eq!(&publish[2..2 + topic_len], b"choui/follow");
+    let payload: Value = serde_json::from_slice(&publish[2 + topic_len..]).unwrap();
+    assert_eq!(payload["user"], "newfriend");
+    assert_eq!(payload["text"], "newfriend followed!");
+}
+
+#[tokio::test]
+async fn stops_when_the_login_is_refused() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_packet(&mut stream).await;
+        stream.write_all(&[0x20, 0x02, 0x00, 0x04]).await.unwrap();
+    });
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let (_events_tx, events) = broadcast::channel(16);
+    let error = tokio::time::timeout(
+        Duration::from_secs(5),
+        mqtt::run(settings(broker), Vec::new(), tx, events),
+    )
+    .await
+    .unwrap()
+    .unwrap_err();
+    assert!(error.to_string().contains("MQTT_PASSWORD"), "{}", error);
+}
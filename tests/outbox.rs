@@ -0,0 +1,115 @@
+mod common;
+
+use choui_the_no_gui_chatbot::outbox::{Outbox, Priority};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Twitch's chat endpoint, answering with `status`
+async fn chat_api(status: u16) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/helix/chat/messages"))
+        .respond_with(
+            ResponseTemplate::new(status).set_body_json(serde_json::json!({ "data": [] })),
+        )
+        .mount(&server)
+        .await;
+    server
+}
+
+async fn sent(server: &MockServer) -> Vec<String> {
+    common::requests(server)
+        .await
+        .iter()
+        .map(|request| {
+            common::json(request)["message"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect()
+}
+
+async fn wait_for(server: &MockServer, count: usize) {
+    for _ in 0..100 {
+        if common::requests(server).await.len() >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("expected {} messages, got {:?}", count, sent(server).await);
+}
+
+#[tokio::test]
+async fn sends_by_priority_and_merges_greetings() {
+    let server = chat_api(200).await;
+    let outbox = Outbox::default();
+    outbox.say(Priority::Low, "@a welcome!".to_string());
+    outbox.say(Priority::Low, "@b welcome!".to_string());
+    outbox.say(Priority::Normal, "@c the answer".to_string());
+    outbox.say(Priority::Normal, "@c the answer".to_string());
+    outbox.say(Priority::High, "no links please".to_string());
+    assert_eq!(outbox.waiting(), 3);
+
+    let (config, _dir) = common::config(&server.uri());
+    let (tx, _events) = mpsc::unbounded_channel();
+    let shutdown = CancellationToken::new();
+    tokio::spawn(outbox.clone().run(Arc::new(config), tx, shutdown.clone()));
+    wait_for(&server, 3).await;
+    shutdown.cancel();
+
+    assert_eq!(
+        sent(&server).await,
+        vec![
+            "no links please",
+            "@c the answer",
+            "@a welcome! | @b welcome!"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn holds_messages_over_the_rate_limit() {
+    let server = chat_api(200).await;
+    let (mut config, _dir) = common::config(&server.uri());
+    config.chat_rate_limit = 2;
+    let outbox = Outbox::default();
+    for n in 1..=3 {
+        outbox.say(Priority::Normal, format!("message {}", n));
+    }
+
+    let (tx, _events) = mpsc::unbounded_channel();
+    let shutdown = CancellationToken::new();
+    tokio::spawn(outbox.clone().run(Arc::new(config), tx, shutdown.clone()));
+    wait_for(&server, 2).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    shutdown.cancel();
+
+    assert_eq!(sent(&server).await, vec!["message 1", "message 2"]);
+    assert_eq!(outbox.waiting(), 1);
+}
+
+#[tokio::test]
+async fn failures_come_back_with_the_message() {
+    let server = chat_api(500).await;
+    let outbox = Outbox::default();
+    outbox.say(Priority::Normal, "hello".to_string());
+
+    let (config, _dir) = common::config(&server.uri());
+    let (tx, mut events) = mpsc::unbounded_channel();
+    tokio::spawn(outbox.run(Arc::new(config), tx, CancellationToken::new()));
+
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let choui_the_no_gui_chatbot::AppEvent::SendFailed { message, error } = event else {
+        panic!("expected SendFailed");
+    };
+    assert_eq!(message, "hello");
+    assert!(error.contains("500"), "{}", error);
+}
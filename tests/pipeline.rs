@@ -0,0 +1,95 @@
+use choui_the_no_gui_chatbot::pipeline::{Context, Dedup, Flow, Pipeline, Stage};
+use choui_the_no_gui_chatbot::ChatMessage;
+
+// Which stages a message went through
+struct Trail;
+
+impl Context for Trail {
+    type Of<'a> = Vec<&'static str>;
+}
+
+// Passes everything on, unless the message says to stop here
+struct Step(&'static str);
+
+impl Stage<Trail> for Step {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn run(&mut self, trail: &mut Vec<&'static str>, message: &ChatMessage) -> Flow {
+        trail.push(self.0);
+        if message.text == format!("stop at {}", self.0) {
+            Flow::Done
+        } else {
+            Flow::Next
+        }
+    }
+}
+
+fn message(id: &str, text: &str) -> ChatMessage {
+    ChatMessage {
+        id: id.to_string(),
+        user: "viewer".to_string(),
+        display_name: "Viewer".to_string(),
+        text: text.to_string(),
+        color: None,
+        badges: Vec::new(),
+        fragments: Vec::new(),
+        timestamp: jiff::Timestamp::now(),
+    }
+}
+
+fn pipeline() -> Pipeline<Trail> {
+    Pipeline::default()
+        .then(Dedup::new(2))
+        .then(Step("commands"))
+        .then(Step("triggers"))
+        .then(Step("ai"))
+}
+
+#[test]
+fn runs_stages_in_order_until_one_is_done() {
+    let mut pipeline = pipeline();
+
+    let mut trail = Vec::new();
+    assert_eq!(pipeline.run(&mut trail, &message("1", "hi")), None);
+    assert_eq!(trail, ["commands", "triggers", "ai"]);
+
+    let mut trail = Vec::new();
+    let done = pipeline.run(&mut trail, &message("2", "stop at triggers"));
+    assert_eq!(done, Some("triggers"));
+    assert_eq!(trail, ["commands", "triggers"]);
+}
+
+#[test]
+fn dedup_drops_repeats_it_still_remembers() {
+    let mut pipeline = pipeline();
+    let mut trail = Vec::new();
+
+    assert_eq!(pipeline.run(&mut trail, &message("a", "hi")), None);
+    assert_eq!(pipeline.run(&mut trail, &message("a", "hi")), Some("dedup"));
+
+    // Only the last two ids are kept
+    pipeline.run(&mut trail, &message("b", "hi"));
+    pipeline.run(&mut trail, &message("c", "hi"));
+    assert_eq!(pipeline.run(&mut trail, &message("a", "hi")), None);
+}
+
+#[test]
+fn skips_stages_by_name() {
+    let mut pipeline = pipeline();
+    pipeline
+        .skip(&["dedup".to_string(), "triggers".to_string()])
+        .unwrap();
+
+    let mut trail = Vec::new();
+    pipeline.run(&mut trail, &message("1", "stop at triggers"));
+    pipeline.run(&mut trail, &message("1", "hi"));
+    assert_eq!(trail, ["commands", "ai", "commands", "ai"]);
+
+    assert!(pipeline.skip(&["spellcheck".to_string()]).is_err());
+    // A bad list leaves the last one in place
+    trail.clear();
+    pipeline.run(&mut trail, &message("1", "hi"));
+    assert_eq!(trail, ["commands", "ai"]);
+}
@@ -0,0 +1,120 @@
+use choui_the_no_gui_chatbot::plugins::{Plugin, PluginAction, PluginEvent};
+use choui_the_no_gui_chatbot::scripts::Scripts;
+use choui_the_no_gui_chatbot::state::ChatMessage;
+use std::time::Duration;
+
+fn scripts(name: &str, source: &str) -> Scripts {
+    let dir = std::env::temp_dir().join(format!("choui-scripts-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join(format!("{}.lua", name)), source).unwrap();
+    Scripts::new(dir)
+}
+
+// What the scripts asked for on `event`. The answer comes with the next
+// event, once they had the time they get for one.
+fn answer(scripts: &mut Scripts, event: &PluginEvent) -> Vec<PluginAction> {
+    let mut actions = scripts.on_event(event);
+    std::thread::sleep(Duration::from_millis(500));
+    actions.extend(scripts.on_event(&PluginEvent::Tick));
+    actions
+}
+
+fn chat(text: &str) -> PluginEvent {
+    PluginEvent::Chat(ChatMessage {
+        id: "1".to_string(),
+        user: "viewer".to_string(),
+        display_name: "Viewer".to_string(),
+        text: text.to_string(),
+        color: None,
+        badges: Vec::new(),
+        fragments: Vec::new(),
+        timestamp: jiff::Timestamp::now(),
+    })
+}
+
+#[test]
+fn calls_what_the_script_defines() {
+    let mut scripts = scripts(
+        "echo",
+        r#"
+        function on_chat(user, text, role)
+            send_chat(user .. " said " .. text .. " as a " .. role)
+            return true
+        end
+        "#,
+    );
+
+    let actions = answer(&mut scripts, &chat("hi"));
+    assert!(actions.contains(&PluginAction::Log("Scripts: echo.lua".to_string())));
+    assert!(actions.contains(&PluginAction::Say("viewer said hi as a viewer".to_string())));
+    // Too late to hold the message
+    assert!(!actions.contains(&PluginAction::Handled));
+}
+
+#[test]
+fn never_waits_for_the_scripts() {
+    let mut scripts = scripts(
+        "slow",
+        r#"
+        function on_join(user) while true do end end
+        "#,
+    );
+
+    let join = PluginEvent::Join {
+        user: "viewer".to_string(),
+    };
+    let started = std::time::Instant::now();
+    for _ in 0..10 {
+        scripts.on_event(&join);
+    }
+    assert!(started.elapsed() < Duration::from_millis(100));
+}
+
+#[test]
+fn print_goes_to_the_log() {
+    let mut scripts = scripts("print", r#"print("points:", 5, nil, true)"#);
+
+    let actions = answer(&mut scripts, &PluginEvent::Tick);
+    assert!(actions.contains(&PluginAction::Log("points:\t5\tnil\ttrue".to_string())));
+}
+
+#[test]
+fn stops_a_script_that_runs_too_long() {
+    let mut scripts = scripts(
+        "loop",
+        r#"
+        function on_join(user) while true do end end
+        function on_chat(user, text) send_chat("still here") end
+        "#,
+    );
+
+    let actions = answer(
+        &mut scripts,
+        &PluginEvent::Join {
+            user: "viewer".to_string(),
+        },
+    );
+    assert!(
+        actions.iter().any(|action| matches!(
+            action,
+            PluginAction::Log(line) if line.contains("ran too long")
+        )),
+        "{:?}",
+        actions
+    );
+    assert_eq!(
+        answer(&mut scripts, &chat("hi")),
+        vec![PluginAction::Say("still here".to_string())]
+    );
+}
+
+#[test]
+fn has_no_files_or_programs() {
+    let mut scripts = scripts(
+        "sandbox",
+        r#"send_chat(tostring(io) .. tostring(os) .. tostring(require) .. tostring(dofile) .. tostring(loadfile))"#,
+    );
+
+    let actions = answer(&mut scripts, &PluginEvent::Tick);
+    assert!(actions.contains(&PluginAction::Say("nilnilnilnilnil".to_string())));
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use choui_the_no_gui_chatbot::bot::BotCore;
+use choui_the_no_gui_chatbot::session::Session;
+use choui_the_no_gui_chatbot::state::{App, AppEvent, Tab, UiState};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+use tempfile::TempDir;
+
+// A session on an offline bot, as `bench` runs it: nothing connects and the
+// TTS is silent
+fn session() -> (Session, TempDir) {
+    let (config, dir) = common::config("http://127.0.0.1:9");
+    let mut bot = BotCore::demo(config.clone());
+    bot.run_offline();
+    let app = App::new(bot.config.clone(), bot.bot_login.clone());
+    let session = Session::new(
+        app,
+        &bot,
+        tokio::sync::broadcast::channel(16).0,
+        &UiState::default(),
+        config,
+        true,
+    )
+    .unwrap();
+    (session, dir)
+}
+
+fn key(code: KeyCode, kind: KeyEventKind) -> Event {
+    Event::Key(KeyEvent {
+        code,
+        modifiers: KeyModifiers::NONE,
+        kind,
+        state: KeyEventState::NONE,
+    })
+}
+
+fn chat(session: &Session) -> Vec<String> {
+    let lines = session.app.tab_lines(Tab::Chat);
+    lines.iter().map(|line| line.text.clone()).collect()
+}
+
+#[tokio::test]
+async fn joins_show_in_chat_and_the_user_list() {
+    let (mut session, _dir) = session();
+    assert!(session.handle_event(AppEvent::UserJoined("viewer".to_string())));
+    assert!(chat(&session).contains(&"-> viewer joined".to_string()));
+    assert!(session.app.chatters["viewer"].present);
+
+    session.handle_event(AppEvent::UserLeft("viewer".to_string()));
+    assert!(chat(&session).contains(&"<- viewer left".to_string()));
+    assert!(!session.app.chatters["viewer"].present);
+}
+
+#[tokio::test]
+async fn typed_messages_go_out_on_enter() {
+    let (mut session, _dir) = session();
+    for c in "hello".chars() {
+        session.handle_terminal_event(key(KeyCode::Char(c), KeyEventKind::Press));
+    }
+    assert_eq!(session.app.input.value(), "hello");
+    session.handle_terminal_event(key(KeyCode::Enter, KeyEventKind::Press));
+    assert_eq!(session.app.input.value(), "");
+    assert!(chat(&session).contains(&"Me: hello".to_string()));
+}
+
+#[tokio::test]
+async fn key_releases_change_nothing() {
+    let (mut session, _dir) = session();
+    assert!(!session.handle_terminal_event(key(KeyCode::Esc, KeyEventKind::Release)));
+    assert!(!session.app.exit);
+    assert!(session.handle_terminal_event(key(KeyCode::Esc, KeyEventKind::Press)));
+    assert!(session.app.exit);
+}
@@ -0,0 +1,131 @@
+use choui_the_no_gui_chatbot::shield::{self, Shield, ShieldSettings, Trigger};
+use std::time::{Duration, Instant};
+
+fn settings() -> ShieldSettings {
+    ShieldSettings {
+        enabled: true,
+        repeats: 3,
+        links: 4,
+        fresh_joins: 5,
+        account_age: Duration::from_secs(7 * 24 * 60 * 60),
+        actions: Vec::new(),
+        duration: Duration::from_secs(600),
+    }
+}
+
+#[test]
+fn raised_by_viewers_sending_the_same_message() {
+    let (start, settings) = (Instant::now(), settings());
+    let mut shield = Shield::default();
+    let spam = "Go back where you came from!!";
+    assert_eq!(shield.message("bot1", spam, false, start, &settings), None);
+    // The same viewer again doesn't count twice
+    assert_eq!(shield.message("bot1", spam, false, start, &settings), None);
+    assert_eq!(
+        shield.message(
+            "bot2",
+            "go back WHERE you came from",
+            false,
+            start,
+            &settings
+        ),
+        None
+    );
+    assert_eq!(
+        shield.message("bot3", spam, false, start, &settings),
+        Some(Trigger::Repeats { viewers: 3 })
+    );
+}
+
+#[test]
+fn short_and_old_messages_are_left_out() {
+    let (start, settings) = (Instant::now(), settings());
+    let mut shield = Shield::default();
+    for viewer in ["a", "b", "c", "d"] {
+        assert_eq!(shield.message(viewer, "GG", false, start, &settings), None);
+    }
+    let text = "that was an amazing play";
+    shield.message("a", text, false, start, &settings);
+    shield.message("b", text, false, start, &settings);
+    let later = start + Duration::from_secs(90);
+    assert_eq!(shield.message("c", text, false, later, &settings), None);
+}
+
+#[test]
+fn raised_by_a_link_flood() {
+    let (start, settings) = (Instant::now(), settings());
+    let mut shield = Shield::default();
+    for n in 0..3 {
+        let at = start + Duration::from_secs(n);
+        assert_eq!(
+            shield.message(&format!("bot{}", n), "spam.xyz", true, at, &settings),
+            None
+        );
+    }
+    assert_eq!(
+        shield.message(
+            "bot3",
+            "spam.xyz",
+            true,
+            start + Duration::from_secs(3),
+            &settings
+        ),
+        Some(Trigger::Links { messages: 4 })
+    );
+}
+
+#[test]
+fn looks_up_joins_once_enough_came_in() {
+    let (start, settings) = (Instant::now(), settings());
+    let mut shield = Shield::default();
+    for n in 0..4 {
+        shield.join(&format!("Viewer{}", n), start);
+    }
+    assert_eq!(shield.accounts_to_check(start, &settings), None);
+    shield.join("viewer4", start);
+    assert_eq!(
+        shield.accounts_to_check(start, &settings).unwrap(),
+        ["viewer0", "viewer1", "viewer2", "viewer3", "viewer4"]
+    );
+    assert_eq!(shield.accounts_to_check(start, &settings), None);
+
+    assert_eq!(shield.fresh_joined(3, start, &settings), None);
+    assert_eq!(
+        shield.fresh_joined(2, start + Duration::from_secs(10), &settings),
+        Some(Trigger::FreshJoins { accounts: 5 })
+    );
+}
+
+#[test]
+fn fresh_accounts_are_younger_than_the_age() {
+    let now: jiff::Timestamp = "2026-10-15T12:00:00Z".parse().unwrap();
+    let accounts = vec![
+        ("old".to_string(), "2020-01-01T00:00:00Z".parse().unwrap()),
+        ("new".to_string(), "2026-10-15T11:00:00Z".parse().unwrap()),
+        ("week".to_string(), "2026-10-08T11:00:00Z".parse().unwrap()),
+    ];
+    assert_eq!(
+        shield::fresh_accounts(&accounts, now, settings().account_age),
+        ["new"]
+    );
+}
+
+#[test]
+fn comes_down_after_the_duration_without_triggers() {
+    let (start, settings) = (Instant::now(), settings());
+    let mut shield = Shield::default();
+    assert!(shield.raise(start));
+    // Raised again, it stays up for longer
+    assert!(!shield.raise(start + Duration::from_secs(300)));
+    assert!(!shield.expired(start + Duration::from_secs(600), &settings));
+    assert!(shield.expired(start + Duration::from_secs(900), &settings));
+
+    let by_hand = ShieldSettings {
+        duration: Duration::ZERO,
+        ..settings
+    };
+    assert!(!shield.expired(start + Duration::from_secs(9000), &by_hand));
+    assert!(shield.lower());
+    assert!(!shield.is_raised());
+    assert!(!shield.lower());
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use choui_the_no_gui_chatbot::config::PowerMode;
+use choui_the_no_gui_chatbot::state::{App, AppEvent, Selection, Tab};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn keeps_the_last_history_lines_per_tab() {
+    let (mut config, _dir) = common::config("http://127.0.0.1:9");
+    config.history_lines = 5;
+    let mut app = App::new(Arc::new(config), "bot".to_string());
+    for i in 0..5 {
+        app.push(Tab::Chat, format!("line {}", i));
+    }
+    app.unread_marker = Some(3);
+    app.selection = Some(Selection {
+        tab: Tab::Chat,
+        anchor: 1,
+        cursor: 4,
+    });
+
+    for i in 5..8 {
+        app.push(Tab::Chat, format!("line {}", i));
+    }
+    app.push(Tab::Log, "elsewhere".to_string());
+
+    let chat: Vec<String> = app.messages.iter().map(|line| line.text.clone()).collect();
+    assert_eq!(chat, ["line 3", "line 4", "line 5", "line 6", "line 7"]);
+    assert_eq!(app.log.len(), 1);
+    // Still on "line 3", and on "line 4" with the dropped anchor at the oldest
+    assert_eq!(app.unread_marker, Some(0));
+    let selection = app.selection.unwrap();
+    assert_eq!((selection.anchor, selection.cursor), (0, 1));
+}
+
+#[test]
+fn caps_redraws_and_skips_ones_that_change_nothing() {
+    let (mut config, _dir) = common::config("http://127.0.0.1:9");
+    config.max_fps = 20;
+    let mut app = App::new(Arc::new(config), "bot".to_string());
+    app.power_mode = PowerMode::Off;
+    assert_eq!(app.frame_interval(), Duration::from_millis(50));
+    app.power_mode = PowerMode::On;
+    assert_eq!(app.frame_interval(), Duration::from_millis(250));
+
+    let debug = AppEvent::Debug("< PING".to_string());
+    assert!(!app.redraws_for(&debug));
+    assert!(!app.redraws_for(&AppEvent::TtsFinished(1)));
+    assert!(app.redraws_for(&AppEvent::UserJoined("viewer".to_string())));
+    app.tab = Tab::Log;
+    assert!(app.redraws_for(&debug));
+}
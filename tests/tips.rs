@@ -0,0 +1,104 @@
+mod common;
+
+use choui_the_no_gui_chatbot::tips::{self, parse_packet, Packet, TipService};
+use choui_the_no_gui_chatbot::{AppEvent, StreamAlert};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+#[test]
+fn reads_socket_io_packets() {
+    assert_eq!(
+        parse_packet(r#"0{"sid":"abc","pingInterval":20000,"pingTimeout":5000}"#),
+        Packet::Open {
+            ping_interval: Duration::from_secs(20)
+        }
+    );
+    assert_eq!(parse_packet("40"), Packet::Connected);
+    assert_eq!(
+        parse_packet(r#"42["authenticated",{"channelId":"1"}]"#),
+        Packet::Event("authenticated".to_string(), json!({ "channelId": "1" }))
+    );
+    assert_eq!(parse_packet("3"), Packet::Other);
+    assert_eq!(parse_packet("41"), Packet::Disconnected);
+}
+
+#[test]
+fn finds_tips_and_ignores_twitch_events() {
+    let streamlabs = json!({
+        "type": "donation",
+        "for": "streamlabs",
+        "message": [
+            { "name": "kind_soul", "amount": "5.00", "currency": "USD", "message": "for snacks" },
+            { "name": "", "amount": 2, "currency": "EUR", "message": "" },
+        ]
+    });
+    let tips = TipService::Streamlabs.tips("event", &streamlabs);
+    assert!(matches!(
+        &tips[..],
+        [
+            StreamAlert::Tip { user, amount, currency, message },
+            StreamAlert::Tip { user: anonymous, .. },
+        ] if user == "kind_soul" && *amount == 5.0 && currency == "USD"
+            && message == "for snacks" && anonymous == "Anonymous"
+    ));
+
+    let follow = json!({ "type": "follower", "data": { "username": "someone" } });
+    assert!(TipService::StreamElements.tips("event", &follow).is_empty());
+    // Tips are only ever one service's shape
+    assert!(TipService::StreamElements
+        .tips("event", &streamlabs)
+        .is_empty());
+}
+
+#[tokio::test]
+async fn streamelements_tips_arrive_as_alerts() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (mut config, _dir) = common::config("http://127.0.0.1:9");
+    config.endpoints.streamelements = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        ws.send(Message::text(r#"0{"sid":"1","pingInterval":25000}"#))
+            .await
+            .unwrap();
+        ws.send(Message::text("40")).await.unwrap();
+        // Only a tip once the bot has authenticated
+        while let Some(Ok(message)) = ws.next().await {
+            let text = message.into_text().unwrap();
+            if text.starts_with(r#"42["authenticate""#) {
+                assert!(text.contains(r#""token":"se-jwt""#));
+                break;
+            }
+        }
+        let tip = json!(["event", {
+            "type": "tip",
+            "data": { "username": "generous", "amount": 10, "currency": "CAD", "message": "gg" }
+        }]);
+        ws.send(Message::text(format!("42{}", tip))).await.unwrap();
+        while let Some(Ok(_)) = ws.next().await {}
+    });
+
+    let (tx, mut events) = mpsc::unbounded_channel();
+    tokio::spawn(tips::run(
+        TipService::StreamElements,
+        "se-jwt".to_string(),
+        Arc::new(config),
+        tx,
+    ));
+    let alert = loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("an event in time")
+            .unwrap();
+        if let AppEvent::Alert(alert) = event {
+            break alert;
+        }
+    };
+    assert_eq!(alert.describe(), "generous tipped 10.00 CAD!");
+    assert!(matches!(alert, StreamAlert::Tip { message, .. } if message == "gg"));
+}
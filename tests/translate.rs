@@ -0,0 +1,114 @@
+mod common;
+
+use choui_the_no_gui_chatbot::config::{Config, LlmProvider};
+use choui_the_no_gui_chatbot::sqlite;
+use choui_the_no_gui_chatbot::translate::{self, OptOuts};
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use wiremock::matchers::{body_partial_json, method, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn only_other_languages_are_foreign() {
+    assert_eq!(
+        translate::foreign_language("hola amigos, gracias por el stream", "en"),
+        Some("es")
+    );
+    assert_eq!(
+        translate::foreign_language("thanks for the stream, this is great", "en"),
+        None
+    );
+    assert_eq!(
+        translate::foreign_language("hola amigos, gracias por el stream", "es"),
+        None
+    );
+    // Too short to tell
+    assert_eq!(translate::foreign_language("lol", "en"), None);
+    assert_eq!(translate::language_name("de"), "German");
+}
+
+#[test]
+fn messages_in_the_target_language_are_not_translated() {
+    let config = Config::builder("client", "bot")
+        .setting("TRANSLATE", "auto")
+        .setting("TRANSLATE_LANGUAGE", "uk")
+        .build()
+        .unwrap();
+    let message = "всім привіт, дуже дякую за сьогоднішній стрім";
+
+    assert_eq!(
+        translate::foreign_language(message, &config.translate.language),
+        None
+    );
+    // Only Cyrillic in common, so Russian is still translated
+    assert_eq!(
+        translate::foreign_language("всем привет, спасибо большое за сегодняшний стрим", "uk"),
+        Some("ru")
+    );
+    assert_eq!(translate::foreign_language(message, "ru"), Some("uk"));
+}
+
+#[test]
+fn only_detectable_languages_are_targets() {
+    for language in ["sv", "xx", "english"] {
+        let result = Config::builder("client", "bot")
+            .setting("TRANSLATE_LANGUAGE", language)
+            .build();
+
+        assert!(result.is_err(), "{}", language);
+    }
+}
+
+#[tokio::test]
+async fn asks_the_ai_for_just_the_translation() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/v1beta/models/.+:generateContent$"))
+        .and(body_partial_json(json!({
+            "contents": [{ "parts": [{ "text": "¡Hola amigos!" }] }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [
+                { "content": { "parts": [{ "text": " Hello friends! \n" }], "role": "model" } }
+            ]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (mut config, _dir) = common::config(&server.uri());
+    config.llm_provider = LlmProvider::Gemini;
+    config.gemini_api_key = Some("gemini-key".to_string());
+
+    let translation = translate::translate("¡Hola amigos!", "en", &config)
+        .await
+        .unwrap();
+
+    assert_eq!(translation, "Hello friends!");
+    let body = common::json(&common::requests(&server).await[0]);
+    let system = body["system_instruction"]["parts"][0]["text"]
+        .as_str()
+        .unwrap();
+    assert!(system.contains("into English"), "{}", system);
+}
+
+#[test]
+fn opt_outs_survive_a_restart() {
+    let path = std::env::temp_dir().join(format!("choui-translate-{}.db", std::process::id()));
+    let (tx, _rx) = mpsc::unbounded_channel();
+
+    let mut opt_outs = OptOuts::open(&path, tx.clone()).unwrap();
+    opt_outs.set("Viewer", true);
+    opt_outs.set("other", true);
+    opt_outs.set("other", false);
+    assert!(opt_outs.contains("viewer"));
+    drop(opt_outs);
+    assert!(sqlite::wait_for_writers(Duration::from_secs(5)));
+
+    let opt_outs = OptOuts::open(&path, tx).unwrap();
+    assert!(opt_outs.contains("VIEWER"));
+    assert!(!opt_outs.contains("other"));
+    drop(opt_outs);
+    sqlite::wait_for_writers(Duration::from_secs(5));
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,415 @@
+mod common;
+
+use choui_the_no_gui_chatbot::twitch::{
+    self, authenticate_via_device_flow, delete_eventsub_subscription, get_account_ages,
+    load_token_cache, manage_held_message, refresh_token, send_chat_message, set_shield_mode,
+    set_slow_mode, subscribe_to_chat_messages, validate_token,
+};
+use common::{json, requests};
+use serde_json::json;
+use wiremock::matchers::{
+    body_partial_json, body_string_contains, header, method, path, query_param,
+};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn device_flow_polls_until_authorized() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/device"))
+        .and(body_string_contains("client_id=test-client"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "device_code": "dev", "user_code": "ABCD",
+            "verification_uri": "https://twitch.tv/activate",
+            "expires_in": 1800, "interval": 0
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    // The first poll is before the user has typed in the code
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "status": 400, "message": "authorization_pending"
+        })))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .and(body_string_contains("device_code=dev"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "new-token", "refresh_token": "new-refresh",
+            "expires_in": 14000, "scope": ["chat:read"], "token_type": "bearer"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, dir) = common::config(&server.uri());
+
+    let token = authenticate_via_device_flow(&reqwest::Client::new(), &config)
+        .await
+        .unwrap();
+
+    assert_eq!(token.access_token, "new-token");
+    // And it's cached for the next start
+    assert_eq!(load_token_cache(&config).unwrap().access_token, "new-token");
+    assert!(twitch::token_cache_path(&config)
+        .unwrap()
+        .starts_with(dir.path()));
+}
+
+#[tokio::test]
+async fn device_flow_gives_up_on_other_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/device"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "device_code": "dev", "user_code": "ABCD",
+            "verification_uri": "https://twitch.tv/activate",
+            "expires_in": 1800, "interval": 0
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "status": 400, "message": "access_denied"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    let error = authenticate_via_device_flow(&reqwest::Client::new(), &config)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("access_denied"), "{}", error);
+}
+
+#[tokio::test]
+async fn refreshes_the_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .and(body_string_contains("grant_type=refresh_token"))
+        .and(body_string_contains("refresh_token=old-refresh"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "fresh", "refresh_token": "next",
+            "expires_in": 14000, "scope": [], "token_type": "bearer"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    let token = refresh_token(&reqwest::Client::new(), &config, "old-refresh")
+        .await
+        .unwrap();
+
+    assert_eq!(token.access_token, "fresh");
+}
+
+#[tokio::test]
+async fn failed_refresh_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .respond_with(
+            ResponseTemplate::new(400).set_body_json(json!({"message": "Invalid refresh token"})),
+        )
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    let error = refresh_token(&reqwest::Client::new(), &config, "revoked")
+        .await
+        .unwrap_err();
+
+    assert!(
+        error.to_string().contains("Invalid refresh token"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn validates_tokens() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/oauth2/validate"))
+        .and(header("Authorization", "OAuth good"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "login": "bot", "scopes": [], "expires_in": 100
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/oauth2/validate"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "status": 401, "message": "invalid access token"
+        })))
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+    let client = reqwest::Client::new();
+
+    assert!(validate_token(&client, &config, "good").await.unwrap());
+    assert!(!validate_token(&client, &config, "expired").await.unwrap());
+}
+
+#[tokio::test]
+async fn subscribes_to_chat_over_the_session() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/helix/eventsub/subscriptions"))
+        .and(header("Authorization", "Bearer test-token"))
+        .and(header("Client-Id", "test-client"))
+        .and(body_partial_json(json!({
+            "type": "channel.chat.message",
+            "condition": { "broadcaster_user_id": "1000", "user_id": "2000" },
+            "transport": { "session_id": "session-1" }
+        })))
+        .respond_with(ResponseTemplate::new(202).set_body_json(json!({
+            "data": [{ "id": "sub-1", "status": "enabled" }], "total": 1
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    let id = subscribe_to_chat_messages(&reqwest::Client::new(), "session-1", &config)
+        .await
+        .unwrap();
+
+    assert_eq!(id, "sub-1");
+}
+
+#[tokio::test]
+async fn refused_subscription_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/helix/eventsub/subscriptions"))
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+            "error": "Forbidden",
+            "message": "subscription missing proper authorization"
+        })))
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    let error = subscribe_to_chat_messages(&reqwest::Client::new(), "session-1", &config)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("403"), "{}", error);
+}
+
+#[tokio::test]
+async fn subscription_without_an_id_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/helix/eventsub/subscriptions"))
+        .respond_with(ResponseTemplate::new(202).set_body_json(json!({ "data": [] })))
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    let result = subscribe_to_chat_messages(&reqwest::Client::new(), "session-1", &config).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn unsubscribes_by_id() {
+    let server = MockServer::start().await;
+    Mock::given(method("DELETE"))
+        .and(path("/helix/eventsub/subscriptions"))
+        .and(query_param("id", "sub-1"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    delete_eventsub_subscription(&reqwest::Client::new(), &config, "sub-1")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn sends_chat_messages() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/helix/chat/messages"))
+        .and(body_partial_json(json!({
+            "message": "hello chat", "broadcaster_id": "1000", "sender_id": "2000"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{ "message_id": "m1", "is_sent": true }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    send_chat_message("hello chat", &config).await.unwrap();
+}
+
+#[tokio::test]
+async fn rejected_chat_message_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/helix/chat/messages"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "error": "Unauthorized", "message": "Missing scope: user:write:chat"
+        })))
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    let error = send_chat_message("hello chat", &config).await.unwrap_err();
+
+    let message = error.to_string();
+    assert!(message.contains("401"), "{}", message);
+    assert!(message.contains("user:write:chat"), "{}", message);
+}
+
+#[tokio::test]
+async fn chat_message_needs_a_token_and_a_channel() {
+    let server = MockServer::start().await;
+    // Neither gets as far as Twitch
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+    let (mut config, _dir) = common::config(&server.uri());
+    config.oauth_token = None;
+    assert!(send_chat_message("hi", &config).await.is_err());
+
+    let (mut config, _dir) = common::config(&server.uri());
+    config.channel_user_id = None;
+    assert!(send_chat_message("hi", &config).await.is_err());
+}
+
+#[tokio::test]
+async fn chat_message_to_an_unreachable_api_is_an_error() {
+    // Nothing listens on port 9 locally
+    let (config, _dir) = common::config("http://127.0.0.1:9");
+
+    assert!(send_chat_message("hi", &config).await.is_err());
+}
+
+#[tokio::test]
+async fn allows_and_denies_held_messages() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/helix/moderation/automod/message"))
+        .and(body_partial_json(json!({
+            "user_id": "2000", "msg_id": "held-1", "action": "ALLOW"
+        })))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/helix/moderation/automod/message"))
+        .and(body_partial_json(
+            json!({ "msg_id": "held-2", "action": "DENY" }),
+        ))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": "Bad Request", "message": "message is not held"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+    let client = reqwest::Client::new();
+
+    manage_held_message(&client, &config, "held-1", true)
+        .await
+        .unwrap();
+    let error = manage_held_message(&client, &config, "held-2", false)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("not held"), "{}", error);
+}
+
+#[tokio::test]
+async fn turns_slow_mode_on_and_off() {
+    let server = MockServer::start().await;
+    Mock::given(method("PATCH"))
+        .and(path("/helix/chat/settings"))
+        .and(query_param("broadcaster_id", "1000"))
+        .and(query_param("moderator_id", "2000"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": [] })))
+        .expect(2)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+    let client = reqwest::Client::new();
+
+    set_slow_mode(&client, &config, Some(10)).await.unwrap();
+    set_slow_mode(&client, &config, None).await.unwrap();
+
+    let requests = requests(&server).await;
+    assert_eq!(json(&requests[0])["slow_mode"], true);
+    assert_eq!(json(&requests[0])["slow_mode_wait_time"], 10);
+    assert_eq!(json(&requests[1])["slow_mode"], false);
+    assert!(json(&requests[1]).get("slow_mode_wait_time").is_none());
+}
+
+#[tokio::test]
+async fn turns_shield_mode_on() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/helix/moderation/shield_mode"))
+        .and(query_param("broadcaster_id", "1000"))
+        .and(query_param("moderator_id", "2000"))
+        .and(body_partial_json(json!({ "is_active": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": [] })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+
+    set_shield_mode(&reqwest::Client::new(), &config, true)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn looks_up_when_accounts_were_made() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/helix/users"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [
+                { "id": "1", "login": "old", "created_at": "2016-12-14T20:32:28Z" },
+                { "id": "2", "login": "new", "created_at": "2026-10-14T08:00:00Z" }
+            ]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (config, _dir) = common::config(&server.uri());
+    let logins = vec!["old".to_string(), "new".to_string(), "gone".to_string()];
+
+    let ages = get_account_ages(&reqwest::Client::new(), &config, &logins)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        requests(&server).await[0].url.query(),
+        Some("login=old&login=new&login=gone")
+    );
+    assert_eq!(
+        ages,
+        vec![
+            ("old".to_string(), "2016-12-14T20:32:28Z".parse().unwrap()),
+            ("new".to_string(), "2026-10-14T08:00:00Z".parse().unwrap()),
+        ]
+    );
+}
@@ -0,0 +1,27 @@
+use choui_the_no_gui_chatbot::ui::EmoteGrid;
+use ratatui::layout::Rect;
+
+#[test]
+fn emote_grid_cells_and_clicks_agree() {
+    // 22 columns inside the border: five 4-wide cells a row, three rows
+    let grid = EmoteGrid::new(Rect::new(10, 20, 24, 8), 23);
+    assert_eq!(grid.items_per_row(), 5);
+    assert_eq!(grid.total_rows(), 5);
+    assert_eq!(grid.visible_rows(), 3);
+    assert_eq!(grid.max_scroll(), 2);
+
+    assert_eq!(grid.cell(0, 0), Some(Rect::new(11, 21, 3, 2)));
+    assert_eq!(grid.cell(7, 1), Some(Rect::new(19, 21, 3, 2)));
+    // Scrolled out of view, below the panel, or past the last emote
+    assert_eq!(grid.cell(3, 1), None);
+    assert_eq!(grid.cell(15, 0), None);
+    assert_eq!(grid.cell(23, 2), None);
+
+    for (index, scroll) in [(0, 0), (7, 1), (22, 2)] {
+        let cell = grid.cell(index, scroll).unwrap();
+        assert_eq!(grid.index_at(cell.x + 1, cell.y + 1, scroll), Some(index));
+    }
+    // On the border
+    assert_eq!(grid.index_at(10, 21, 0), None);
+    assert_eq!(grid.index_at(11, 20, 0), None);
+}